@@ -11,7 +11,7 @@ fn get_dependencies<'a>(
 ) -> Result<BTreeSet<&'a str>> {
     let mut result = BTreeSet::new();
     format.visit(&mut |format| {
-        if let Format::TypeName(x) = format {
+        if let Format::TypeName(x) | Format::Apply { name: x, .. } = format {
             if !external.contains(x) {
                 result.insert(x.as_str());
             }
@@ -30,7 +30,7 @@ pub(crate) fn mangle_type(format: &serde_reflection::Format) -> String {
         F64 => "f64".into(),
         Char => "char".into(),
         Str => "str".into(),
-        Bytes => "bytes".into(),
+        Bytes | FixedBytes(_) => "bytes".into(),
 
         Option(format) => format!("option_{}", mangle_type(format)),
         Seq(format) => format!("vector_{}", mangle_type(format)),
@@ -46,9 +46,60 @@ pub(crate) fn mangle_type(format: &serde_reflection::Format) -> String {
         ),
         TupleArray { content, size } => format!("array{}_{}_array", size, mangle_type(content)),
         Variable(_) => panic!("unexpected value"),
+        TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+        Union(_) => panic!("unions of formats are not supported"),
     }
 }
 
+/// Reject the registries that contain formats that no code generator supports, i.e.
+/// generic containers, their type parameters and instances, and unions of formats.
+#[cfg(any(
+    feature = "cpp",
+    feature = "csharp",
+    feature = "dart",
+    feature = "golang",
+    feature = "java",
+    feature = "kotlin",
+    feature = "ocaml",
+    feature = "python3",
+    feature = "rust",
+    feature = "solidity",
+    feature = "swift",
+    feature = "typescript",
+))]
+pub(crate) fn check_supported(registry: &serde_reflection::Registry) -> std::io::Result<()> {
+    use serde_reflection::{ContainerFormat, Format, FormatHolder};
+    for (name, format) in registry {
+        let unsupported = if matches!(format, ContainerFormat::Generic { .. }) {
+            Some("generic containers")
+        } else {
+            let mut unsupported = None;
+            format
+                .visit(&mut |format| {
+                    match format {
+                        Format::TypeParam(_) | Format::Apply { .. } => {
+                            unsupported.get_or_insert("generic containers");
+                        }
+                        Format::Union(_) => {
+                            unsupported.get_or_insert("unions of formats");
+                        }
+                        _ => (),
+                    }
+                    Ok(())
+                })
+                .unwrap_or(());
+            unsupported
+        };
+        if let Some(construct) = unsupported {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("{construct} are not supported (found in container {name})"),
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(feature = "ocaml")]
 pub(crate) fn uppercase_first_letter(s: &str) -> String {
     let mut c = s.chars();
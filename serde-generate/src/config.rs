@@ -123,6 +123,21 @@ impl CodeGeneratorConfig {
         self
     }
 
+    /// Mention deprecated fields and variants in the comments attached to them.
+    /// Note that this should be called after `with_comments`.
+    pub fn with_deprecations(mut self, deprecations: &serde_reflection::Deprecations) -> Self {
+        for path in deprecations.iter() {
+            let mut name = vec![self.module_name.clone()];
+            name.extend(path.iter().cloned());
+            let comment = self.comments.entry(name).or_default();
+            *comment = match comment.trim() {
+                "" => "Deprecated.\n".to_string(),
+                text => format!("{text}\n\nDeprecated.\n"),
+            };
+        }
+        self
+    }
+
     /// Custom code attached to particular entity.
     pub fn with_custom_code(mut self, code: CustomCode) -> Self {
         self.custom_code = code;
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    analyzer,
+    analyzer, common,
     indent::{IndentConfig, IndentedWriter},
     CodeGeneratorConfig, Encoding,
 };
@@ -60,6 +60,7 @@ impl<'a> CodeGenerator<'a> {
         out: &mut dyn Write,
         registry: &Registry,
     ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        common::check_supported(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -211,7 +212,7 @@ where
             F64 => "double".into(),
             Char => "char32_t".into(),
             Str => "std::string".into(),
-            Bytes => "std::vector<uint8_t>".into(),
+            Bytes | FixedBytes(_) => "std::vector<uint8_t>".into(),
 
             Option(format) => format!(
                 "std::optional<{}>",
@@ -234,6 +235,8 @@ where
             ),
 
             Variable(_) => panic!("unexpected value"),
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
     }
 
@@ -307,15 +310,19 @@ where
             assert_eq!(*index, expected_index as u32);
             self.output_variant(&variant.name, &variant.value)?;
         }
-        writeln!(
-            self.out,
-            "\nstd::variant<{}> value;",
-            variants
-                .values()
-                .map(|v| v.name.clone())
-                .collect::<Vec<_>>()
-                .join(", "),
-        )?;
+        // `std::variant` requires at least one alternative. Enums without variants have no
+        // values, hence no field.
+        if !variants.is_empty() {
+            writeln!(
+                self.out,
+                "\nstd::variant<{}> value;",
+                variants
+                    .values()
+                    .map(|v| v.name.clone())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )?;
+        }
         writeln!(self.out)?;
         self.output_class_method_declarations(name)?;
         self.output_custom_code()?;
@@ -363,6 +370,7 @@ where
                 self.output_enum_container(name, variants)?;
                 return Ok(());
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         };
         self.output_struct_or_variant_container(name, &fields)
     }
@@ -513,6 +521,40 @@ template <typename Deserializer>
         Ok(())
     }
 
+    /// Enums without variants cannot be serialized, and deserializing them always fails
+    /// after reading the variant index.
+    fn output_empty_enum_traits(&mut self, name: &str) -> Result<()> {
+        self.output_open_namespace()?;
+        self.output_struct_equality_test(name, &[])?;
+        if self.generator.config.serialization {
+            for encoding in &self.generator.config.encodings {
+                self.output_struct_serialize_for_encoding(name, *encoding)?;
+                self.output_struct_deserialize_for_encoding(name, *encoding)?;
+            }
+        }
+        self.output_close_namespace()?;
+        let name = self.quote_qualified_name(name);
+        if self.generator.config.serialization {
+            writeln!(
+                self.out,
+                r#"
+template <>
+template <typename Serializer>
+void serde::Serializable<{name}>::serialize(const {name} &, Serializer &) {{
+    throw serde::serialization_error("Cannot serialize a value of an enum without variants");
+}}
+
+template <>
+template <typename Deserializer>
+{name} serde::Deserializable<{name}>::deserialize(Deserializer &deserializer) {{
+    deserializer.deserialize_variant_index();
+    throw serde::deserialization_error("Unknown variant index for enum");
+}}"#,
+            )?;
+        }
+        Ok(())
+    }
+
     fn get_variant_fields(format: &VariantFormat) -> Vec<&str> {
         use VariantFormat::*;
         match format {
@@ -541,6 +583,7 @@ template <typename Deserializer>
                     .collect::<Vec<_>>(),
                 true,
             ),
+            Enum(variants) if variants.is_empty() => self.output_empty_enum_traits(name),
             Enum(variants) => {
                 self.output_struct_traits(name, &["value"], true)?;
                 for variant in variants.values() {
@@ -552,6 +595,7 @@ template <typename Deserializer>
                 }
                 Ok(())
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         }
     }
 }
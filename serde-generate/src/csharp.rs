@@ -66,6 +66,7 @@ impl<'a> CodeGenerator<'a> {
         install_dir: std::path::PathBuf,
         registry: &Registry,
     ) -> Result<std::path::PathBuf> {
+        common::check_supported(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -84,10 +85,8 @@ impl<'a> CodeGenerator<'a> {
         let mut cstyle_enum_names = Vec::new();
         if self.config.enums.c_style {
             for (name, format) in registry {
-                if let ContainerFormat::Enum(variants) = format {
-                    if variants.values().all(|f| f.value == VariantFormat::Unit) {
-                        cstyle_enum_names.push(name.clone());
-                    }
+                if format.is_c_like() {
+                    cstyle_enum_names.push(name.clone());
                 }
             }
         }
@@ -276,7 +275,7 @@ using System.Numerics;"
             F64 => "double".into(),
             Char => "char".into(),
             Str => "string".into(),
-            Bytes => "Serde.ValueArray<byte>".into(),
+            Bytes | FixedBytes(_) => "Serde.ValueArray<byte>".into(),
 
             Option(format) => format!("Serde.Option<{}>", self.quote_type(format)),
             Seq(format) => format!("Serde.ValueArray<{}>", self.quote_type(format)),
@@ -291,6 +290,8 @@ using System.Numerics;"
                 size: _size,
             } => format!("Serde.ValueArray<{}>", self.quote_type(content),),
             Variable(_) => panic!("unexpected value"),
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
     }
 
@@ -377,7 +378,7 @@ using System.Numerics;"
             F64 => format!("serializer.serialize_f64({value});"),
             Char => format!("serializer.serialize_char({value});"),
             Str => format!("serializer.serialize_str({value});"),
-            Bytes => format!("serializer.serialize_bytes({value});"),
+            Bytes | FixedBytes(_) => format!("serializer.serialize_bytes({value});"),
             _ => format!(
                 "{}.serialize_{}({}, serializer);",
                 self.quote_qualified_name("TraitHelpers"),
@@ -420,7 +421,7 @@ using System.Numerics;"
             F64 => "deserializer.deserialize_f64()".to_string(),
             Char => "deserializer.deserialize_char()".to_string(),
             Str => "deserializer.deserialize_str()".to_string(),
-            Bytes => "deserializer.deserialize_bytes()".to_string(),
+            Bytes | FixedBytes(_) => "deserializer.deserialize_bytes()".to_string(),
             _ => format!(
                 "{}.deserialize_{}(deserializer)",
                 self.quote_qualified_name("TraitHelpers"),
@@ -1112,17 +1113,14 @@ public static {0} {1}Deserialize(ArraySegment<byte> input) {{
                 .collect::<Vec<_>>(),
             Struct(fields) => fields.clone(),
             Enum(variants) => {
-                if variants
-                    .iter()
-                    .all(|(_i, v)| v.value == VariantFormat::Unit)
-                    && self.cstyle_enum_names.contains(&name.into())
-                {
+                if format.is_c_like() && self.cstyle_enum_names.contains(&name.into()) {
                     self.output_cstyle_enum(name, variants)?;
                 } else {
                     self.output_enum_container(name, variants)?;
                 }
                 return Ok(());
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         };
         self.output_struct_or_variant_container(None, None, name, &fields)
     }
@@ -47,6 +47,7 @@ impl<'a> CodeGenerator<'a> {
 
     /// Output class definitions for `registry`.
     pub fn output(&self, install_dir: std::path::PathBuf, registry: &Registry) -> Result<()> {
+        common::check_supported(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -192,13 +193,10 @@ where
     // yet so we must call the extension class instead
     fn get_class(&self, name: &str) -> String {
         if self.generator.config.enums.c_style {
-            use ContainerFormat::Enum;
             match self.get_field_container_type(name) {
                 // if we have an enum AND all of that enum's members are Unit
                 // then we will generate an extension class name
-                Some(Enum(variants))
-                    if variants.values().all(|f| f.value == VariantFormat::Unit) =>
-                {
+                Some(format) if format.is_c_like() => {
                     format!("{name}Extension")
                 }
                 _ => name.to_string(),
@@ -244,7 +242,7 @@ where
             F64 => "double".into(),
             Char => "int".into(),
             Str => "String".into(),
-            Bytes => "Bytes".into(),
+            Bytes | FixedBytes(_) => "Bytes".into(),
 
             Option(format) => format!("{}?", self.quote_type(format)),
             Seq(format) => format!("List<{}>", self.quote_type(format)),
@@ -254,6 +252,8 @@ where
             Tuple(formats) => format!("Tuple{}<{}>", formats.len(), self.quote_types(formats)),
             TupleArray { content, size: _ } => format!("List<{}>", self.quote_type(content)),
             Variable(_) => panic!("unexpected value"),
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
     }
 
@@ -285,7 +285,7 @@ where
             F64 => format!("serializer.serializeFloat64({value});"),
             Char => format!("serializer.serializeChar({value});"),
             Str => format!("serializer.serializeString({value});"),
-            Bytes => format!("serializer.serializeBytes({value});"),
+            Bytes | FixedBytes(_) => format!("serializer.serializeBytes({value});"),
             _ => format!(
                 "{}.serialize{}({}, serializer);",
                 self.quote_qualified_name("TraitHelpers"),
@@ -320,7 +320,7 @@ where
             F64 => "deserializer.deserializeFloat64()".to_string(),
             Char => "deserializer.deserializeChar()".to_string(),
             Str => "deserializer.deserializeString()".to_string(),
-            Bytes => "deserializer.deserializeBytes()".to_string(),
+            Bytes | FixedBytes(_) => "deserializer.deserializeBytes()".to_string(),
             _ => format!(
                 "{}.deserialize{}(deserializer)",
                 self.quote_qualified_name("TraitHelpers"),
@@ -585,7 +585,7 @@ return obj;
                 if ((self.generator.config.enums.c_style
                     && !self.generator.config.enums.output_type.contains_key(name))
                     || self.generator.config.enums.output_type.get(name) == Some(&"enum"))
-                    && variants.values().all(|f| f.value == VariantFormat::Unit)
+                    && format.is_c_like()
                 {
                     self.output_enum_container(name, variants)?;
                 } else if (self.generator.config.enums.sealed
@@ -598,6 +598,7 @@ return obj;
                 }
                 return Ok(());
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         };
         self.output_struct_or_variant_container(None, None, name, &fields)
     }
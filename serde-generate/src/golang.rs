@@ -73,6 +73,7 @@ impl<'a> CodeGenerator<'a> {
 
     /// Output class definitions for `registry`.
     pub fn output(&self, out: &mut dyn Write, registry: &Registry) -> Result<()> {
+        common::check_supported(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -218,7 +219,7 @@ where
             F64 => "float64".into(),
             Char => "rune".into(),
             Str => "string".into(),
-            Bytes => "[]byte".into(),
+            Bytes | FixedBytes(_) => "[]byte".into(),
 
             Option(format) => format!("*{}", self.quote_type(format)),
             Seq(format) => format!("[]{}", self.quote_type(format)),
@@ -237,6 +238,8 @@ where
             TupleArray { content, size } => format!("[{}]{}", size, self.quote_type(content)),
 
             Variable(_) => panic!("unexpected value"),
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
     }
 
@@ -297,7 +300,7 @@ where
             F64 => format!("serializer.SerializeF64({value})"),
             Char => format!("serializer.SerializeChar({value})"),
             Str => format!("serializer.SerializeStr({value})"),
-            Bytes => format!("serializer.SerializeBytes({value})"),
+            Bytes | FixedBytes(_) => format!("serializer.SerializeBytes({value})"),
             _ => format!(
                 "serialize_{}({}, serializer)",
                 common::mangle_type(format),
@@ -330,7 +333,7 @@ where
             F64 => "deserializer.DeserializeF64()".to_string(),
             Char => "deserializer.DeserializeChar()".to_string(),
             Str => "deserializer.DeserializeStr()".to_string(),
-            Bytes => "deserializer.DeserializeBytes()".to_string(),
+            Bytes | FixedBytes(_) => "deserializer.DeserializeBytes()".to_string(),
             _ => format!("deserialize_{}(deserializer)", common::mangle_type(format)),
         };
         format!(
@@ -950,6 +953,7 @@ switch index {{"#,
                 self.output_enum_container(name, &variants)?;
                 return Ok(());
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         };
         self.output_struct_or_variant_container(None, None, name, &fields)
     }
@@ -65,6 +65,7 @@ impl<'a> CodeGenerator<'a> {
         install_dir: std::path::PathBuf,
         registry: &Registry,
     ) -> Result<()> {
+        common::check_supported(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -206,7 +207,7 @@ where
             F64 => "Double".into(),
             Char => "Character".into(),
             Str => "String".into(),
-            Bytes => "com.novi.serde.Bytes".into(),
+            Bytes | FixedBytes(_) => "com.novi.serde.Bytes".into(),
 
             Option(format) => format!("java.util.Optional<{}>", self.quote_type(format)),
             Seq(format) => format!("java.util.List<{}>", self.quote_type(format)),
@@ -226,6 +227,8 @@ where
                 self.quote_type(content)
             ),
             Variable(_) => panic!("unexpected value"),
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
     }
 
@@ -312,7 +315,7 @@ where
             F64 => format!("serializer.serialize_f64({value});"),
             Char => format!("serializer.serialize_char({value});"),
             Str => format!("serializer.serialize_str({value});"),
-            Bytes => format!("serializer.serialize_bytes({value});"),
+            Bytes | FixedBytes(_) => format!("serializer.serialize_bytes({value});"),
             _ => format!(
                 "{}.serialize_{}({}, serializer);",
                 self.quote_qualified_name("TraitHelpers"),
@@ -345,7 +348,7 @@ where
             F64 => "deserializer.deserialize_f64()".to_string(),
             Char => "deserializer.deserialize_char()".to_string(),
             Str => "deserializer.deserialize_str()".to_string(),
-            Bytes => "deserializer.deserialize_bytes()".to_string(),
+            Bytes | FixedBytes(_) => "deserializer.deserialize_bytes()".to_string(),
             _ => format!(
                 "{}.deserialize_{}(deserializer)",
                 self.quote_qualified_name("TraitHelpers"),
@@ -921,6 +924,7 @@ public static {0} {1}Deserialize(byte[] input) throws com.novi.serde.Deserializa
                 self.output_enum_container(name, variants)?;
                 return Ok(());
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         };
         self.output_struct_or_variant_container(None, None, name, &fields)
     }
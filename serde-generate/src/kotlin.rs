@@ -60,6 +60,7 @@ impl<'a> CodeGenerator<'a> {
         install_dir: std::path::PathBuf,
         registry: &Registry,
     ) -> Result<()> {
+        common::check_supported(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -178,7 +179,7 @@ where
             F64 => "Double".into(),
             Char => "Char".into(),
             Str => "String".into(),
-            Bytes => "com.novi.serde.Bytes".into(),
+            Bytes | FixedBytes(_) => "com.novi.serde.Bytes".into(),
 
             Option(format) => {
                 let inner = self.quote_type(format);
@@ -217,6 +218,8 @@ where
             }
 
             Variable(_) => panic!("unexpected value"),
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
     }
 
@@ -291,7 +294,7 @@ where
             F64 => format!("serializer.serialize_f64({value})"),
             Char => format!("serializer.serialize_char({value})"),
             Str => format!("serializer.serialize_str({value})"),
-            Bytes => format!("serializer.serialize_bytes({value})"),
+            Bytes | FixedBytes(_) => format!("serializer.serialize_bytes({value})"),
             _ => format!(
                 "TraitHelpers.serialize_{}({}, serializer)",
                 common::mangle_type(format),
@@ -323,7 +326,7 @@ where
             F64 => "deserializer.deserialize_f64()".to_string(),
             Char => "deserializer.deserialize_char()".to_string(),
             Str => "deserializer.deserialize_str()".to_string(),
-            Bytes => "deserializer.deserialize_bytes()".to_string(),
+            Bytes | FixedBytes(_) => "deserializer.deserialize_bytes()".to_string(),
             _ => format!(
                 "TraitHelpers.deserialize_{}(deserializer)",
                 common::mangle_type(format),
@@ -903,6 +906,7 @@ fun {1}Deserialize(input: ByteArray): {0} {{
                 self.output_enum_container(name, variants)?;
                 return Ok(());
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         };
         self.output_struct_or_variant_container(None, None, name, &fields)
     }
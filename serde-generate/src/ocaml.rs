@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    common::uppercase_first_letter,
+    common::{self, uppercase_first_letter},
     indent::{IndentConfig, IndentedWriter},
     CodeGeneratorConfig, Encoding,
 };
@@ -44,6 +44,7 @@ impl<'a> CodeGenerator<'a> {
     }
 
     pub fn output(&self, out: &mut dyn Write, registry: &Registry) -> Result<()> {
+        common::check_supported(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -158,7 +159,7 @@ where
             F64 => write!(self.out, "float")?,
             Char => write!(self.out, "char")?,
             Str => write!(self.out, "string")?,
-            Bytes => write!(self.out, "bytes")?,
+            Bytes | FixedBytes(_) => write!(self.out, "bytes")?,
             Option(f) => {
                 self.output_format(f, false)?;
                 write!(self.out, " option")?
@@ -174,6 +175,8 @@ where
                 self.output_format(content, false)?;
                 write!(self.out, " array [@length {size}])")?
             }
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
         if is_struct {
             write!(self.out, " [@struct])")?
@@ -336,6 +339,7 @@ where
             Enum(variants) => {
                 self.output_enum(&name.to_camel_case(), variants, false)?;
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         }
 
         if last && self.generator.config.serialization {
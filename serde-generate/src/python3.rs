@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    common,
     indent::{IndentConfig, IndentedWriter},
     CodeGeneratorConfig, Encoding,
 };
@@ -69,6 +70,7 @@ impl<'a> CodeGenerator<'a> {
 
     /// Write container definitions in Python.
     pub fn output(&self, out: &mut dyn Write, registry: &Registry) -> Result<()> {
+        common::check_supported(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -156,7 +158,7 @@ import typing
             F64 => "st.float64".into(),
             Char => "st.char".into(),
             Str => "str".into(),
-            Bytes => "bytes".into(),
+            Bytes | FixedBytes(_) => "bytes".into(),
 
             Option(format) => format!("typing.Optional[{}]", self.quote_type(format)),
             Seq(format) => format!("typing.Sequence[{}]", self.quote_type(format)),
@@ -178,6 +180,8 @@ import typing
             ), // Sadly, there are no fixed-size arrays in python.
 
             Variable(_) => panic!("unexpected value"),
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
     }
 
@@ -367,6 +371,7 @@ def {0}_deserialize(input: bytes) -> '{1}':
                 self.output_enum_container(name, variants)?;
                 return Ok(());
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         };
         // Struct case.
         writeln!(self.out, "\n@dataclass(frozen=True)\nclass {name}:")?;
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    analyzer,
+    analyzer, common,
     indent::{IndentConfig, IndentedWriter},
     CodeGeneratorConfig,
 };
@@ -77,6 +77,7 @@ impl<'a> CodeGenerator<'a> {
         out: &mut dyn Write,
         registry: &Registry,
     ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        common::check_supported(registry)?;
         let external_names = self
             .config
             .external_definitions
@@ -120,6 +121,7 @@ impl<'a> CodeGenerator<'a> {
         &self,
         registry: &Registry,
     ) -> std::result::Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+        common::check_supported(registry)?;
         let dependencies = analyzer::get_dependency_map(registry)?;
         let entries = analyzer::best_effort_topological_sort(&dependencies);
 
@@ -242,7 +244,7 @@ where
             F64 => "f64".into(),
             Char => "char".into(),
             Str => "String".into(),
-            Bytes => "Bytes".into(),
+            Bytes | FixedBytes(_) => "Bytes".into(),
 
             Option(format) => format!("Option<{}>", Self::quote_type(format, known_sizes)),
             Seq(format) => format!("Vec<{}>", Self::quote_type(format, None)),
@@ -257,6 +259,8 @@ where
             }
 
             Variable(_) => panic!("unexpected value"),
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
     }
 
@@ -389,6 +393,7 @@ where
                 self.current_namespace.pop();
                 writeln!(self.out, "}}\n")?;
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         }
         self.output_custom_code(name)
     }
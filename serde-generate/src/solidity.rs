@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    common,
     indent::{IndentConfig, IndentedWriter},
     CodeGeneratorConfig,
 };
@@ -1336,7 +1337,7 @@ impl SolRegistry {
             F64 => panic!("floating point is not supported in solidity"),
             Char => SolFormat::Primitive(Primitive::Char),
             Str => SolFormat::Primitive(Primitive::Str),
-            Bytes => SolFormat::Primitive(Primitive::Bytes),
+            Bytes | FixedBytes(_) => SolFormat::Primitive(Primitive::Bytes),
             Option(format) => {
                 let format = self.parse_format(*format);
                 if format == SolFormat::Primitive(Primitive::Bool) {
@@ -1401,6 +1402,8 @@ impl SolRegistry {
                     }
                 }
             }
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         };
         self.insert(sol_format.clone());
         sol_format
@@ -1503,6 +1506,7 @@ impl SolRegistry {
                     SolFormat::Enum { name, formats }
                 }
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         };
         self.insert(sol_format);
     }
@@ -1613,6 +1617,7 @@ impl<'a> CodeGenerator<'a> {
         out: &mut dyn Write,
         registry: &Registry,
     ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        common::check_supported(registry)?;
         let mut emitter = SolEmitter {
             out: IndentedWriter::new(out, IndentConfig::Space(4)),
             generator: self,
@@ -64,6 +64,7 @@ impl<'a> CodeGenerator<'a> {
 
     /// Output class definitions for `registry`.
     pub fn output(&self, out: &mut dyn Write, registry: &Registry) -> Result<()> {
+        common::check_supported(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -149,7 +150,7 @@ where
             F64 => "Double".into(),
             Char => "Character".into(),
             Str => "String".into(),
-            Bytes => "[UInt8]".into(),
+            Bytes | FixedBytes(_) => "[UInt8]".into(),
 
             Option(format) => format!("{}?", self.quote_type(format)),
             Seq(format) => format!("[{}]", self.quote_type(format)),
@@ -164,6 +165,8 @@ where
             }
 
             Variable(_) => panic!("unexpected value"),
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
     }
 
@@ -235,7 +238,7 @@ where
             F64 => format!("try serializer.serialize_f64(value: {value})"),
             Char => format!("try serializer.serialize_char(value: {value})"),
             Str => format!("try serializer.serialize_str(value: {value})"),
-            Bytes => format!("try serializer.serialize_bytes(value: {value})"),
+            Bytes | FixedBytes(_) => format!("try serializer.serialize_bytes(value: {value})"),
             _ => format!(
                 "try serialize_{}(value: {}, serializer: serializer)",
                 common::mangle_type(format),
@@ -267,7 +270,7 @@ where
             F64 => "try deserializer.deserialize_f64()".to_string(),
             Char => "try deserializer.deserialize_char()".to_string(),
             Str => "try deserializer.deserialize_str()".to_string(),
-            Bytes => "try deserializer.deserialize_bytes()".to_string(),
+            Bytes | FixedBytes(_) => "try deserializer.deserialize_bytes()".to_string(),
             _ => format!(
                 "try deserialize_{}(deserializer: deserializer)",
                 common::mangle_type(format)
@@ -819,6 +822,7 @@ switch index {{"#,
                 self.output_enum_container(name, variants)?;
                 return Ok(());
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         };
         self.output_struct_container(name, &fields)
     }
@@ -63,6 +63,7 @@ impl<'a> CodeGenerator<'a> {
 
     /// Output class definitions for `registry` in a single source file.
     pub fn output(&self, out: &mut dyn Write, registry: &Registry) -> Result<()> {
+        common::check_supported(registry)?;
         let mut emitter = TypeScriptEmitter {
             out: IndentedWriter::new(out, IndentConfig::Space(2)),
             generator: self,
@@ -144,7 +145,7 @@ import {{ Optional, Seq, Tuple, ListTuple, unit, bool, int8, int16, int32, int64
             F64 => "float64".into(),
             Char => "char".into(),
             Str => "str".into(),
-            Bytes => "bytes".into(),
+            Bytes | FixedBytes(_) => "bytes".into(),
 
             Option(format) => format!("Optional<{}>", self.quote_type(format)),
             Seq(format) => format!("Seq<{}>", self.quote_type(format)),
@@ -157,6 +158,8 @@ import {{ Optional, Seq, Tuple, ListTuple, unit, bool, int8, int16, int32, int64
                 size: _size,
             } => format!("ListTuple<[{}]>", self.quote_type(content),),
             Variable(_) => panic!("unexpected value"),
+            TypeParam(_) | Apply { .. } => panic!("type parameters are not supported"),
+            Union(_) => panic!("unions of formats are not supported"),
         }
     }
 
@@ -222,7 +225,7 @@ import {{ Optional, Seq, Tuple, ListTuple, unit, bool, int8, int16, int32, int64
             F64 => format!("serializer.serializeF64({this_str}{value});"),
             Char => format!("serializer.serializeChar({this_str}{value});"),
             Str => format!("serializer.serializeStr({this_str}{value});"),
-            Bytes => format!("serializer.serializeBytes({this_str}{value});"),
+            Bytes | FixedBytes(_) => format!("serializer.serializeBytes({this_str}{value});"),
             _ => format!(
                 "Helpers.serialize{}({}{}, serializer);",
                 common::mangle_type(format).to_camel_case(),
@@ -255,7 +258,7 @@ import {{ Optional, Seq, Tuple, ListTuple, unit, bool, int8, int16, int32, int64
             F64 => "deserializer.deserializeF64()".to_string(),
             Char => "deserializer.deserializeChar()".to_string(),
             Str => "deserializer.deserializeStr()".to_string(),
-            Bytes => "deserializer.deserializeBytes()".to_string(),
+            Bytes | FixedBytes(_) => "deserializer.deserializeBytes()".to_string(),
             _ => format!(
                 "Helpers.deserialize{}(deserializer)",
                 common::mangle_type(format).to_camel_case(),
@@ -669,6 +672,7 @@ switch (index) {{"#,
                 self.output_enum_container(name, variants)?;
                 return Ok(());
             }
+            Generic { .. } => panic!("generic containers are not supported"),
         };
         self.output_struct_or_variant_container(None, None, name, &fields)
     }
@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::test_utils;
+use serde::{Deserialize, Serialize};
 use serde_generate::{cpp, CodeGeneratorConfig, Encoding};
+use serde_reflection::{Tracer, TracerConfig};
 use std::{collections::BTreeMap, fs::File, io::Write, process::Command};
 use tempfile::{tempdir, TempDir};
 
@@ -106,6 +108,67 @@ fn test_that_cpp_code_compiles_with_comments() {
     ));
 }
 
+#[test]
+fn test_that_cpp_code_compiles_with_empty_containers() {
+    let registry = test_utils::get_empty_containers_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let header_path = dir.path().join("test.hpp");
+    let mut header = File::create(&header_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = cpp::CodeGenerator::new(&config);
+    generator.output(&mut header, &registry).unwrap();
+
+    let source_path = dir.path().join("test.cpp");
+    let mut source = File::create(&source_path).unwrap();
+    writeln!(
+        source,
+        r#"
+#include "bincode.hpp"
+#include "test.hpp"
+"#
+    )
+    .unwrap();
+
+    let status = Command::new("clang++")
+        .arg("--std=c++17")
+        .arg("-c")
+        .arg("-o")
+        .arg(dir.path().join("test.o"))
+        .arg("-I")
+        .arg("runtime/cpp")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let content = std::fs::read_to_string(header_path).unwrap();
+    assert!(!content.contains("std::variant<>"));
+}
+
+#[test]
+fn test_cpp_code_with_skipped_variants() {
+    #[derive(Serialize, Deserialize)]
+    enum Resource {
+        Local,
+        Handle(u64),
+        Remote(String),
+    }
+
+    let config = TracerConfig::default().skip_variant("Resource", "Handle");
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Resource>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    // The skipped variant keeps its index, so the generator sees contiguous indices.
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = cpp::CodeGenerator::new(&config);
+    let mut header = Vec::new();
+    generator.output(&mut header, &registry).unwrap();
+    let content = String::from_utf8(header).unwrap();
+    assert!(content.contains("std::variant<Local, Handle, Remote> value;"));
+}
+
 #[test]
 fn test_cpp_code_with_external_definitions() {
     let registry = test_utils::get_registry().unwrap();
@@ -230,3 +293,32 @@ int main() {{
         .unwrap();
     assert!(status.success());
 }
+
+#[test]
+fn test_cpp_generic_containers_are_rejected() {
+    #[derive(Serialize, Deserialize)]
+    struct Pair<T> {
+        first: T,
+        second: T,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Point {
+        coordinates: Pair<u64>,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer
+        .trace_simple_type::<Pair<serde_reflection::TypeParam<0>>>()
+        .unwrap();
+    tracer.trace_simple_type::<Point>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = cpp::CodeGenerator::new(&config);
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "generic containers are not supported (found in container Pair)"
+    );
+}
@@ -42,6 +42,33 @@ fn test_that_java_code_compiles() {
     test_that_java_code_compiles_with_config(&config);
 }
 
+#[test]
+fn test_that_java_code_compiles_with_empty_containers() {
+    let registry = test_utils::get_empty_containers_registry().unwrap();
+    let dir = tempdir().unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = java::CodeGenerator::new(&config);
+    generator
+        .write_source_files(dir.path().to_path_buf(), &registry)
+        .unwrap();
+
+    let paths = std::iter::empty()
+        .chain(std::fs::read_dir("runtime/java/com/novi/serde").unwrap())
+        .chain(std::fs::read_dir("runtime/java/com/novi/bincode").unwrap())
+        .chain(std::fs::read_dir("runtime/java/com/novi/bcs").unwrap())
+        .chain(std::fs::read_dir(dir.path().join("testing")).unwrap())
+        .map(|e| e.unwrap().path());
+    let status = Command::new("javac")
+        .arg("-Xlint")
+        .arg("-d")
+        .arg(dir.path())
+        .args(paths)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
 #[test]
 fn test_that_java_code_compiles_without_serialization() {
     let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
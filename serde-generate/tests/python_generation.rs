@@ -38,6 +38,30 @@ fn test_that_python_code_parses() {
     test_that_python_code_parses_with_config(&config);
 }
 
+#[test]
+fn test_that_python_code_parses_with_empty_containers() {
+    let registry = test_utils::get_empty_containers_registry().unwrap();
+    let dir = tempdir().unwrap();
+
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = python3::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let python_path = format!(
+        "{}:runtime/python",
+        std::env::var("PYTHONPATH").unwrap_or_default()
+    );
+    let status = Command::new("python3")
+        .arg(&source_path)
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
 #[test]
 fn test_that_python_code_parses_without_serialization() {
     let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
@@ -3,6 +3,7 @@
 
 use crate::test_utils;
 use serde_generate::{rust, CodeGeneratorConfig};
+use serde_reflection::Deprecations;
 use std::{fs::File, io::Write, process::Command};
 use tempfile::{tempdir, TempDir};
 
@@ -54,6 +55,27 @@ fn test_that_rust_code_compiles_with_comments() {
     assert!(content.contains("/// Some\n/// comments\n"));
 }
 
+#[test]
+fn test_that_rust_code_compiles_with_deprecations() {
+    let comments = vec![(
+        vec!["testing".to_string(), "SerdeData".to_string()],
+        "Some\ncomments".to_string(),
+    )]
+    .into_iter()
+    .collect();
+    let mut deprecations = Deprecations::new();
+    deprecations.deprecate(["SerdeData"]);
+    deprecations.deprecate_field("Struct", "y");
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_serialization(false)
+        .with_comments(comments)
+        .with_deprecations(&deprecations);
+    let (_dir, source_path) = test_that_rust_code_compiles_with_config(&config);
+    let content = std::fs::read_to_string(source_path).unwrap();
+    assert!(content.contains("/// Some\n/// comments\n///\n/// Deprecated.\n"));
+    assert!(content.contains("/// Deprecated.\n    pub y: u64,"));
+}
+
 #[test]
 fn test_that_rust_code_compiles_with_external_definitions() {
     let registry = test_utils::get_registry().unwrap();
@@ -111,6 +133,29 @@ mod foo {{
     assert!(status.success());
 }
 
+#[test]
+fn test_that_rust_code_compiles_with_empty_containers() {
+    let registry = test_utils::get_empty_containers_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
 // Full test using cargo. This may take a while.
 #[test]
 fn test_that_rust_code_compiles_with_serialization() {
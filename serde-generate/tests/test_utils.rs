@@ -31,6 +31,31 @@ pub fn get_simple_registry() -> Result<Registry> {
     tracer.registry()
 }
 
+// Containers without fields or variants.
+#[derive(Serialize, Deserialize)]
+pub struct EmptyStruct {}
+
+#[derive(Serialize, Deserialize)]
+pub struct EmptyTupleStruct();
+
+#[derive(Serialize, Deserialize)]
+pub enum EmptyEnum {}
+
+#[derive(Serialize, Deserialize)]
+pub struct EmptyContainers {
+    pub a: EmptyStruct,
+    pub b: EmptyTupleStruct,
+    pub c: Option<EmptyEnum>,
+    pub d: Vec<EmptyEnum>,
+}
+
+pub fn get_empty_containers_registry() -> Result<Registry> {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let samples = Samples::new();
+    tracer.trace_type::<EmptyContainers>(&samples)?;
+    tracer.registry()
+}
+
 // More complex data format used to test re-serialization and basic fuzzing.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum SerdeData {
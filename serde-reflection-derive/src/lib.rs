@@ -0,0 +1,529 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![forbid(unsafe_code)]
+
+//! This crate provides `#[derive(Reflect)]`, which records the `#[serde(..)]` attributes of
+//! a struct or an enum so that the tracer of `serde-reflection` does not have to guess them.
+//!
+//! It also provides `#[trace_test]`, which generates a test checking that a struct or an
+//! enum can be traced, and optionally comparing the resulting registry with a golden file.
+//!
+//! Both are meant to be used through the feature `derive` of `serde-reflection`, which
+//! re-exports them as `serde_reflection::Reflect` and `serde_reflection::trace_test`. See
+//! the module `serde_reflection::attributes` for details.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    meta::ParseNestedMeta, parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, LitStr,
+    Result, Token,
+};
+
+/// Implement `serde_reflection::attributes::Reflect` from the `#[serde(..)]` attributes of
+/// a struct or an enum. Attributes that do not affect the recorded metadata are ignored.
+#[proc_macro_derive(Reflect, attributes(serde))]
+pub fn derive_reflect(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Generate a test `trace_test_<name>` that traces the annotated struct or enum with a
+/// default `TracerConfig` and fails unless the resulting registry is complete.
+/// * `include(A, B)` traces the types `A` and `B` after the annotated type, e.g. to
+///   complete the enums that it contains.
+/// * `snapshot = "tests/wire.yaml"` compares the registry with a golden file, relative to
+///   the manifest of the crate, like `serde_reflection::assert_registry_snapshot!`. This
+///   requires the feature `snapshot` of `serde-reflection`.
+///
+/// The attribute must be placed before `#[derive(..)]`. Generic types are not supported.
+#[proc_macro_attribute]
+pub fn trace_test(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut options = TraceTestOptions::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta));
+    parse_macro_input!(args with parser);
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_trace_test(options, input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_trace_test(options: TraceTestOptions, input: DeriveInput) -> Result<TokenStream2> {
+    if let Data::Union(_) = &input.data {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "trace_test cannot be used on unions",
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "trace_test cannot be used on generic types",
+        ));
+    }
+    let ident = &input.ident;
+    let test_name = format_ident!(
+        "trace_test_{}",
+        RenameRule::Snake.apply_to_variant(&unraw(ident))
+    );
+    let mut types = vec![quote!(#ident)];
+    types.extend(options.include.iter().map(|path| quote!(#path)));
+    let names: Vec<_> = types
+        .iter()
+        .map(|path| path.to_string().replace(' ', ""))
+        .collect();
+    let check = match &options.snapshot {
+        Some(path) => quote! {
+            let registry = tracer
+                .registry()
+                .unwrap_or_else(|error| panic!("Failed to complete the registry: {}", error));
+            ::serde_reflection::snapshot::assert_registry_snapshot(
+                &registry,
+                ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(#path),
+            );
+        },
+        None => quote! {
+            if let Err(error) = tracer.registry() {
+                panic!("Failed to complete the registry: {}", error);
+            }
+        },
+    };
+    Ok(quote! {
+        #input
+
+        #[cfg(test)]
+        #[test]
+        fn #test_name() {
+            let mut tracer =
+                ::serde_reflection::Tracer::new(::serde_reflection::TracerConfig::default());
+            #(
+                if let Err(error) = tracer.trace_simple_type::<#types>() {
+                    panic!("Failed to trace {}: {}", #names, error);
+                }
+            )*
+            #check
+        }
+    })
+}
+
+/// The arguments of `#[trace_test(..)]`.
+#[derive(Default)]
+struct TraceTestOptions {
+    include: Vec<syn::Path>,
+    snapshot: Option<LitStr>,
+}
+
+impl TraceTestOptions {
+    fn parse(&mut self, meta: ParseNestedMeta) -> Result<()> {
+        if meta.path.is_ident("include") {
+            meta.parse_nested_meta(|nested| {
+                self.include.push(nested.path);
+                Ok(())
+            })
+        } else if meta.path.is_ident("snapshot") {
+            self.snapshot = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `include(..)` or `snapshot = \"..\"`"))
+        }
+    }
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let container = ContainerOptions::parse(&input.attrs)?;
+    let rust_name = unraw(&input.ident);
+    let name = container
+        .rename
+        .clone()
+        .unwrap_or_else(|| rust_name.clone());
+    let (kind, fields, variants) = match &input.data {
+        Data::Struct(data) => (
+            fields_kind(&data.fields),
+            expand_fields(&data.fields, container.rename_all)?,
+            Vec::new(),
+        ),
+        Data::Enum(data) => {
+            let mut variants = Vec::new();
+            for variant in &data.variants {
+                let options = VariantOptions::parse(&variant.attrs)?;
+                let rust_name = unraw(&variant.ident);
+                let name = match &options.rename {
+                    Some(name) => name.clone(),
+                    None => container.rename_all.apply_to_variant(&rust_name),
+                };
+                let rule = options.rename_all.unwrap_or(container.rename_all_fields);
+                let kind = fields_kind(&variant.fields);
+                let fields = expand_fields(&variant.fields, rule)?;
+                let VariantOptions {
+                    skip_serializing,
+                    skip_deserializing,
+                    other,
+                    untagged,
+                    ..
+                } = options;
+                variants.push(quote! {
+                    ::serde_reflection::attributes::VariantAttributes {
+                        name: #name,
+                        rust_name: #rust_name,
+                        kind: ::serde_reflection::ContainerKind::#kind,
+                        skip_serializing: #skip_serializing,
+                        skip_deserializing: #skip_deserializing,
+                        other: #other,
+                        untagged: #untagged,
+                        fields: &[#(#fields),*],
+                    }
+                });
+            }
+            (quote!(Enum), Vec::new(), variants)
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Reflect cannot be derived for unions",
+            ))
+        }
+    };
+    let tag = match (&container.tag, &container.content, container.untagged) {
+        (_, _, true) => quote!(::serde_reflection::attributes::TagMode::Untagged),
+        (Some(tag), Some(content), false) => quote! {
+            ::serde_reflection::attributes::TagMode::Adjacent { tag: #tag, content: #content }
+        },
+        (Some(tag), None, false) => {
+            quote!(::serde_reflection::attributes::TagMode::Internal { tag: #tag })
+        }
+        (None, _, false) => quote!(::serde_reflection::attributes::TagMode::External),
+    };
+    let ContainerOptions {
+        transparent,
+        deny_unknown_fields,
+        default,
+        ..
+    } = container;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::serde_reflection::attributes::Reflect
+            for #ident #ty_generics #where_clause
+        {
+            fn attributes() -> &'static ::serde_reflection::attributes::ContainerAttributes {
+                static ATTRIBUTES: ::serde_reflection::attributes::ContainerAttributes =
+                    ::serde_reflection::attributes::ContainerAttributes {
+                        name: #name,
+                        rust_name: #rust_name,
+                        kind: ::serde_reflection::ContainerKind::#kind,
+                        tag: #tag,
+                        transparent: #transparent,
+                        deny_unknown_fields: #deny_unknown_fields,
+                        default: #default,
+                        fields: &[#(#fields),*],
+                        variants: &[#(#variants),*],
+                    };
+                &ATTRIBUTES
+            }
+        }
+    })
+}
+
+fn expand_fields(fields: &Fields, rule: RenameRule) -> Result<Vec<TokenStream2>> {
+    let mut result = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let options = FieldOptions::parse(&field.attrs)?;
+        let rust_name = match &field.ident {
+            Some(ident) => unraw(ident),
+            None => index.to_string(),
+        };
+        let name = match (&options.rename, &field.ident) {
+            (Some(name), _) => name.clone(),
+            (None, Some(_)) => rule.apply_to_field(&rust_name),
+            (None, None) => rust_name.clone(),
+        };
+        let FieldOptions {
+            default,
+            skip_serializing,
+            skip_deserializing,
+            flatten,
+            ..
+        } = options;
+        result.push(quote! {
+            ::serde_reflection::attributes::FieldAttributes {
+                name: #name,
+                rust_name: #rust_name,
+                default: #default,
+                skip_serializing: #skip_serializing,
+                skip_deserializing: #skip_deserializing,
+                flatten: #flatten,
+            }
+        });
+    }
+    Ok(result)
+}
+
+/// The kind of a struct with the given fields, as seen by Serde.
+fn fields_kind(fields: &Fields) -> TokenStream2 {
+    match fields {
+        Fields::Unit => quote!(UnitStruct),
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => quote!(NewTypeStruct),
+        Fields::Unnamed(_) => quote!(TupleStruct),
+        Fields::Named(_) => quote!(Struct),
+    }
+}
+
+fn unraw(ident: &syn::Ident) -> String {
+    let name = ident.to_string();
+    match name.strip_prefix("r#") {
+        Some(name) => name.to_string(),
+        None => name,
+    }
+}
+
+#[derive(Default)]
+struct ContainerOptions {
+    rename: Option<String>,
+    rename_all: RenameRule,
+    rename_all_fields: RenameRule,
+    tag: Option<String>,
+    content: Option<String>,
+    untagged: bool,
+    transparent: bool,
+    deny_unknown_fields: bool,
+    default: bool,
+}
+
+impl ContainerOptions {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = Self::default();
+        for_each_serde_meta(attrs, |meta| {
+            if meta.path.is_ident("rename") {
+                options.rename = Some(parse_name(&meta)?);
+            } else if meta.path.is_ident("rename_all") {
+                options.rename_all = RenameRule::parse(&meta)?;
+            } else if meta.path.is_ident("rename_all_fields") {
+                options.rename_all_fields = RenameRule::parse(&meta)?;
+            } else if meta.path.is_ident("tag") {
+                options.tag = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("content") {
+                options.content = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("untagged") {
+                options.untagged = true;
+            } else if meta.path.is_ident("transparent") {
+                options.transparent = true;
+            } else if meta.path.is_ident("deny_unknown_fields") {
+                options.deny_unknown_fields = true;
+            } else if meta.path.is_ident("default") {
+                options.default = true;
+                skip_meta(&meta)?;
+            } else {
+                skip_meta(&meta)?;
+            }
+            Ok(())
+        })?;
+        Ok(options)
+    }
+}
+
+#[derive(Default)]
+struct VariantOptions {
+    rename: Option<String>,
+    rename_all: Option<RenameRule>,
+    skip_serializing: bool,
+    skip_deserializing: bool,
+    other: bool,
+    untagged: bool,
+}
+
+impl VariantOptions {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = Self::default();
+        for_each_serde_meta(attrs, |meta| {
+            if meta.path.is_ident("rename") {
+                options.rename = Some(parse_name(&meta)?);
+            } else if meta.path.is_ident("rename_all") {
+                options.rename_all = Some(RenameRule::parse(&meta)?);
+            } else if meta.path.is_ident("skip") {
+                options.skip_serializing = true;
+                options.skip_deserializing = true;
+            } else if meta.path.is_ident("skip_serializing") {
+                options.skip_serializing = true;
+            } else if meta.path.is_ident("skip_deserializing") {
+                options.skip_deserializing = true;
+            } else if meta.path.is_ident("other") {
+                options.other = true;
+            } else if meta.path.is_ident("untagged") {
+                options.untagged = true;
+            } else {
+                skip_meta(&meta)?;
+            }
+            Ok(())
+        })?;
+        Ok(options)
+    }
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<String>,
+    default: bool,
+    skip_serializing: bool,
+    skip_deserializing: bool,
+    flatten: bool,
+}
+
+impl FieldOptions {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut options = Self::default();
+        for_each_serde_meta(attrs, |meta| {
+            if meta.path.is_ident("rename") {
+                options.rename = Some(parse_name(&meta)?);
+            } else if meta.path.is_ident("default") {
+                options.default = true;
+                skip_meta(&meta)?;
+            } else if meta.path.is_ident("skip") {
+                options.skip_serializing = true;
+                options.skip_deserializing = true;
+            } else if meta.path.is_ident("skip_serializing") {
+                options.skip_serializing = true;
+            } else if meta.path.is_ident("skip_deserializing") {
+                options.skip_deserializing = true;
+            } else if meta.path.is_ident("flatten") {
+                options.flatten = true;
+            } else {
+                skip_meta(&meta)?;
+            }
+            Ok(())
+        })?;
+        Ok(options)
+    }
+}
+
+fn for_each_serde_meta<F>(attrs: &[Attribute], mut f: F) -> Result<()>
+where
+    F: FnMut(ParseNestedMeta) -> Result<()>,
+{
+    for attr in attrs {
+        if attr.path().is_ident("serde") {
+            attr.parse_nested_meta(&mut f)?;
+        }
+    }
+    Ok(())
+}
+
+/// Consume the value of an attribute that is not recorded, e.g. `bound = ".."` or
+/// `bound(serialize = "..")`.
+fn skip_meta(meta: &ParseNestedMeta) -> Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|nested| skip_meta(&nested))?;
+    }
+    Ok(())
+}
+
+/// Parse `name = ".."`, or `name(serialize = "..", deserialize = "..")` in which case the
+/// serialized name is used.
+fn parse_name(meta: &ParseNestedMeta) -> Result<String> {
+    if meta.input.peek(Token![=]) {
+        return Ok(meta.value()?.parse::<LitStr>()?.value());
+    }
+    let mut serialize = None;
+    let mut deserialize = None;
+    meta.parse_nested_meta(|nested| {
+        let value = nested.value()?.parse::<LitStr>()?.value();
+        if nested.path.is_ident("serialize") {
+            serialize = Some(value);
+        } else if nested.path.is_ident("deserialize") {
+            deserialize = Some(value);
+        } else {
+            return Err(nested.error("expected `serialize` or `deserialize`"));
+        }
+        Ok(())
+    })?;
+    serialize
+        .or(deserialize)
+        .ok_or_else(|| meta.error("expected `serialize` or `deserialize`"))
+}
+
+/// The case conventions of `#[serde(rename_all = "..")]`.
+#[derive(Clone, Copy, Default)]
+enum RenameRule {
+    #[default]
+    None,
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(meta: &ParseNestedMeta) -> Result<Self> {
+        let name = parse_name(meta)?;
+        match name.as_str() {
+            "lowercase" => Ok(Self::Lower),
+            "UPPERCASE" => Ok(Self::Upper),
+            "PascalCase" => Ok(Self::Pascal),
+            "camelCase" => Ok(Self::Camel),
+            "snake_case" => Ok(Self::Snake),
+            "SCREAMING_SNAKE_CASE" => Ok(Self::ScreamingSnake),
+            "kebab-case" => Ok(Self::Kebab),
+            "SCREAMING-KEBAB-CASE" => Ok(Self::ScreamingKebab),
+            _ => Err(meta.error(format!("unknown rename rule `{name}`"))),
+        }
+    }
+
+    /// Rename a variant, written in PascalCase in Rust.
+    fn apply_to_variant(self, variant: &str) -> String {
+        match self {
+            Self::None | Self::Pascal => variant.to_string(),
+            Self::Lower => variant.to_ascii_lowercase(),
+            Self::Upper => variant.to_ascii_uppercase(),
+            Self::Camel => {
+                let mut chars = variant.chars();
+                match chars.next() {
+                    Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+                    None => String::new(),
+                }
+            }
+            Self::Snake | Self::ScreamingSnake | Self::Kebab | Self::ScreamingKebab => {
+                let mut snake = String::new();
+                for (index, ch) in variant.char_indices() {
+                    if index > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                self.apply_to_field(&snake)
+            }
+        }
+    }
+
+    /// Rename a field, written in snake_case in Rust.
+    fn apply_to_field(self, field: &str) -> String {
+        match self {
+            Self::None | Self::Lower | Self::Snake => field.to_string(),
+            Self::Upper | Self::ScreamingSnake => field.to_ascii_uppercase(),
+            Self::Pascal | Self::Camel => {
+                let mut result = String::new();
+                let mut capitalize = matches!(self, Self::Pascal);
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        result.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        result.push(ch);
+                    }
+                }
+                result
+            }
+            Self::Kebab => field.replace('_', "-"),
+            Self::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
@@ -0,0 +1,310 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use serde_reflection::{
+    attributes::{self, TagMode},
+    trace_test, Error, Reflect, Tracer, TracerConfig,
+};
+
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Reflect)]
+#[serde(rename = "Person", rename_all = "camelCase", deny_unknown_fields)]
+struct PersonV1 {
+    first_name: String,
+    #[serde(rename = "years", default)]
+    age: u8,
+    #[serde(skip)]
+    cache: Option<u64>,
+    r#type: u8,
+}
+
+#[derive(Serialize, Deserialize, Reflect)]
+#[serde(
+    tag = "kind",
+    rename_all = "snake_case",
+    rename_all_fields = "PascalCase"
+)]
+enum Event {
+    UserCreated {
+        user_id: u64,
+    },
+    #[serde(rename_all = "SCREAMING-KEBAB-CASE")]
+    UserRemoved {
+        user_id: u64,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Reflect)]
+struct Pair<T>(T, #[serde(skip_serializing)] u8);
+
+#[derive(Serialize, Deserialize, Reflect)]
+enum Choice {
+    A(u8),
+    #[serde(rename = "Second")]
+    B,
+}
+
+#[test]
+fn test_struct_attributes() {
+    let attrs = PersonV1::attributes();
+    assert_eq!(attrs.name, "Person");
+    assert_eq!(attrs.rust_name, "PersonV1");
+    assert_eq!(attrs.tag, TagMode::External);
+    assert!(attrs.deny_unknown_fields);
+    assert!(attrs.variants.is_empty());
+    assert_eq!(
+        attrs
+            .fields
+            .iter()
+            .map(|field| (field.name, field.rust_name))
+            .collect::<Vec<_>>(),
+        vec![
+            ("firstName", "first_name"),
+            ("years", "age"),
+            ("cache", "cache"),
+            ("type", "type"),
+        ]
+    );
+    assert!(attrs.field("years").unwrap().default);
+    let cache = attrs.field("cache").unwrap();
+    assert!(cache.skip_serializing && cache.skip_deserializing);
+    assert!(!attrs.requires_self_describing());
+
+    let attrs = Pair::<String>::attributes();
+    assert_eq!(
+        attrs
+            .fields
+            .iter()
+            .map(|field| (field.name, field.skip_serializing))
+            .collect::<Vec<_>>(),
+        vec![("0", false), ("1", true)]
+    );
+}
+
+#[test]
+fn test_enum_attributes() {
+    let attrs = Event::attributes();
+    assert_eq!(attrs.tag, TagMode::Internal { tag: "kind" });
+    assert!(attrs.requires_self_describing());
+    assert_eq!(attrs.fallback_variant(), Some("unknown"));
+    assert_eq!(
+        attrs.variant("user_created").unwrap().fields[0].name,
+        "UserId"
+    );
+    assert_eq!(
+        attrs.variant("user_removed").unwrap().fields[0].name,
+        "USER-ID"
+    );
+
+    let attrs = Choice::attributes();
+    assert_eq!(
+        attrs
+            .variants
+            .iter()
+            .map(|variant| (variant.name, variant.fields.len()))
+            .collect::<Vec<_>>(),
+        vec![("A", 1), ("Second", 0)]
+    );
+    assert_eq!(attrs.fallback_variant(), None);
+}
+
+#[test]
+fn test_tracer_uses_registered_attributes() {
+    attributes::register::<PersonV1>().unwrap();
+    attributes::register::<Event>().unwrap();
+    assert!(attributes::lookup("Choice").is_none());
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<PersonV1>().unwrap();
+    assert_eq!(tracer.denies_unknown_fields("Person"), Some(true));
+    assert_eq!(tracer.attributes("Person"), Some(PersonV1::attributes()));
+    assert!(!tracer.requires_self_describing("Person"));
+
+    // Registered attributes take precedence over tracing when the tracer cannot tell.
+    assert_eq!(tracer.fallback_variant("Event"), Some("unknown"));
+    assert!(!tracer.requires_self_describing("Event"));
+}
+
+#[test]
+fn test_register_rejects_clashes() {
+    #[derive(Serialize, Deserialize, Reflect)]
+    #[serde(rename = "Clash")]
+    struct First {
+        a: u8,
+    }
+
+    #[derive(Serialize, Deserialize, Reflect)]
+    #[serde(rename = "Clash")]
+    struct Second {
+        b: u8,
+    }
+
+    attributes::register::<First>().unwrap();
+    // Registering the same attributes again is fine.
+    attributes::register::<First>().unwrap();
+    assert_eq!(
+        attributes::register::<Second>().unwrap_err(),
+        Error::NameClash("Clash".into())
+    );
+    assert_eq!(attributes::lookup("Clash"), Some(First::attributes()));
+}
+
+#[test]
+fn test_probing_skips_registered_structs() {
+    #[derive(Serialize, Deserialize, Reflect)]
+    struct Outer {
+        inner: Inner,
+    }
+
+    #[derive(Serialize, Deserialize, Reflect)]
+    #[serde(deny_unknown_fields)]
+    struct Inner {
+        a: u8,
+    }
+
+    attributes::register::<Inner>().unwrap();
+    let config = TracerConfig::default().probe_unknown_fields(true);
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Outer>().unwrap();
+    assert_eq!(tracer.denies_unknown_fields("Inner"), Some(true));
+    assert_eq!(tracer.denies_unknown_fields("Outer"), Some(false));
+}
+
+#[test]
+fn test_cross_check() {
+    use serde_reflection::{
+        attributes::{check_container, cross_check, AttributeMismatch},
+        ContainerFormat, ContainerKind, Format, Named, Registry, VariantFormat,
+    };
+
+    #[derive(Serialize, Deserialize, Reflect)]
+    struct Checked {
+        a: u8,
+        #[serde(skip_deserializing)]
+        b: u8,
+        c: (u8, u16),
+    }
+
+    #[allow(dead_code)]
+    #[derive(Serialize, Deserialize, Reflect)]
+    enum CheckedEnum {
+        A(u8, u16),
+        B {
+            x: u8,
+        },
+        #[serde(skip)]
+        C,
+    }
+
+    attributes::register::<Checked>().unwrap();
+    attributes::register::<CheckedEnum>().unwrap();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Checked>().unwrap();
+    tracer.trace_simple_type::<CheckedEnum>().unwrap();
+    let registry = tracer.registry().unwrap();
+    assert_eq!(cross_check(&registry), Vec::new());
+
+    // Formats as they would be traced from diverging hand-written implementations.
+    let field = |name: &str| Named {
+        name: name.into(),
+        value: Format::U8,
+    };
+    let format = ContainerFormat::Struct(vec![field("c"), field("a")]);
+    assert_eq!(
+        check_container(Checked::attributes(), &format),
+        vec![AttributeMismatch::FieldOrder {
+            path: vec!["Checked".into()],
+            expected: vec!["a".into(), "c".into()],
+            found: vec!["c".into(), "a".into()],
+        }]
+    );
+    let format = ContainerFormat::Struct(vec![field("a"), field("d")]);
+    assert_eq!(
+        check_container(Checked::attributes(), &format)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec![
+            "Checked::c: field was not traced",
+            "Checked::d: traced field is not declared",
+        ]
+    );
+    assert_eq!(
+        check_container(Checked::attributes(), &ContainerFormat::UnitStruct),
+        vec![AttributeMismatch::KindMismatch {
+            path: vec!["Checked".into()],
+            expected: ContainerKind::Struct,
+            found: ContainerKind::UnitStruct,
+        }]
+    );
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "CheckedEnum".into(),
+        ContainerFormat::Enum(
+            vec![
+                (
+                    0,
+                    Named {
+                        name: "A".into(),
+                        value: VariantFormat::Tuple(vec![Format::U8]),
+                    },
+                ),
+                (
+                    1,
+                    Named {
+                        name: "C".into(),
+                        value: VariantFormat::Unit,
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        ),
+    );
+    assert_eq!(
+        cross_check(&registry),
+        vec![
+            AttributeMismatch::MissingVariant {
+                path: vec!["CheckedEnum".into(), "B".into()],
+            },
+            AttributeMismatch::FieldCount {
+                path: vec!["CheckedEnum".into(), "A".into()],
+                expected: 2,
+                found: 1,
+            },
+            AttributeMismatch::UnexpectedVariant {
+                path: vec!["CheckedEnum".into(), "C".into()],
+            },
+        ]
+    );
+}
+
+// Each annotated type generates a test `trace_test_<name>`.
+#[trace_test(include(Color), snapshot = "tests/snapshots/palette.yaml")]
+#[derive(Serialize, Deserialize)]
+struct Palette {
+    name: String,
+    colors: Vec<Color>,
+}
+
+#[trace_test]
+#[derive(Serialize, Deserialize)]
+enum Color {
+    Red,
+    Rgb(u8, u8, u8),
+}
+
+#[test]
+fn test_trace_test_keeps_the_item() {
+    let palette = Palette {
+        name: "primary".into(),
+        colors: vec![Color::Red, Color::Rgb(0, 0, 255)],
+    };
+    assert_eq!(palette.colors.len(), 2);
+    trace_test_color();
+}
@@ -0,0 +1,75 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Benchmarks of the tracer, run with `cargo bench --features bench-fixtures`.
+//!
+//! Pass a substring as argument to only run the matching benchmarks, e.g.
+//! `cargo bench --features bench-fixtures -- samples`.
+
+use serde_reflection::{
+    bench_fixtures::{large_registry, workloads},
+    registry, Samples, Tracer, TracerConfig,
+};
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+/// The time spent measuring each benchmark, after one warm-up iteration.
+const MEASUREMENT_TIME: Duration = Duration::from_secs(2);
+const MIN_ITERATIONS: usize = 10;
+
+fn bench<F, T>(filter: Option<&str>, name: &str, mut routine: F)
+where
+    F: FnMut() -> T,
+{
+    if filter.is_some_and(|filter| !name.contains(filter)) {
+        return;
+    }
+    black_box(routine());
+    let mut timings = Vec::new();
+    let start = Instant::now();
+    while timings.len() < MIN_ITERATIONS || start.elapsed() < MEASUREMENT_TIME {
+        let iteration = Instant::now();
+        black_box(routine());
+        timings.push(iteration.elapsed());
+    }
+    timings.sort_unstable();
+    println!(
+        "{name:<32} median {:>12?}  min {:>12?}  ({} iterations)",
+        timings[timings.len() / 2],
+        timings[0],
+        timings.len()
+    );
+}
+
+fn main() {
+    // Skip the flags added by `cargo bench`, e.g. `--bench`.
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let filter = filter.as_deref();
+
+    for workload in workloads() {
+        bench(filter, &format!("trace/{}", workload.name), || {
+            workload.run().unwrap()
+        });
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default().record_samples_for_structs(true));
+    let mut samples = Samples::new();
+    for workload in workloads() {
+        (workload.trace)(&mut tracer, &mut samples).unwrap();
+    }
+    let names: Vec<_> = samples.names().map(String::from).collect();
+    bench(filter, "samples/value", || {
+        names
+            .iter()
+            .filter(|name| samples.value(name).is_some())
+            .count()
+    });
+
+    let large = large_registry(4096);
+    bench(filter, "registry/validate", || registry::validate(&large));
+    bench(filter, "registry/reachable", || {
+        registry::reachable(&large, ["C0"])
+    });
+}
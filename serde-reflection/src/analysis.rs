@@ -0,0 +1,803 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Static analyses of formats.
+//!
+//! The function [`size_bounds`] computes the minimal and maximal sizes of the encoding of a
+//! container in one of the binary encodings described by [`EncodingProfile`], e.g. to size
+//! the buffers of embedded systems.
+//!
+//! ```rust
+//! # #[cfg(feature = "trace")]
+//! # {
+//! use serde::{Deserialize, Serialize};
+//! use serde_reflection::analysis::{size_bounds, MaxSize};
+//! use serde_reflection::compat::EncodingProfile;
+//! use serde_reflection::{Tracer, TracerConfig};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! enum Message {
+//!     Ping(u64),
+//!     Data { id: u32, payload: Vec<u8> },
+//! }
+//!
+//! let mut tracer = Tracer::new(TracerConfig::default());
+//! tracer.trace_simple_type::<Message>().unwrap();
+//! let registry = tracer.registry().unwrap();
+//!
+//! let bounds = size_bounds(&registry, "Message", EncodingProfile::Bcs).unwrap();
+//! // Tag of `Data`, `id`, and the length of an empty payload.
+//! assert_eq!(bounds.min, 6);
+//! assert_eq!(bounds.max, MaxSize::UnboundedSequence);
+//! # }
+//! ```
+//!
+//! The function [`registry_stats`] summarizes the shape of a registry, e.g. to monitor the
+//! growth of a schema over time.
+//!
+//! The function [`uninhabited_containers`] finds the containers without values, such as
+//! enums without variants, so that producers of sample values can avoid them.
+//!
+//! The function [`json_safety`] lists the maps with non-string keys and the 128-bit integers
+//! of a registry, so that JSON-unsafe types are detected before they reach a JSON serializer.
+
+use crate::{
+    compat::{EncodingProfile, IntegerEncoding, NumericType, TagEncoding},
+    registry, ContainerFormat, Error, Format, Named, Registry, Result, VariantFormat,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// Bounds on the size of an encoded value, in bytes.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SizeBounds {
+    /// The minimal size, or `usize::MAX` if the format has no finite values.
+    pub min: usize,
+    /// The maximal size, if any.
+    pub max: MaxSize,
+}
+
+/// The maximal size of an encoded value.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MaxSize {
+    /// Encoded values are never larger than the given size.
+    Bounded(usize),
+    /// The value contains a sequence, a map, a string, or bytes of unbounded length.
+    UnboundedSequence,
+    /// The value may contain the given container, recursively.
+    Recursive(String),
+    /// The value contains a type parameter of a generic container.
+    Generic,
+}
+
+impl SizeBounds {
+    /// Bounds of a value whose encoding always has the given size.
+    pub fn fixed(size: usize) -> Self {
+        Self {
+            min: size,
+            max: MaxSize::Bounded(size),
+        }
+    }
+
+    /// The size of the encoding, if all the values have the same size.
+    pub fn fixed_size(&self) -> Option<usize> {
+        match self.max {
+            MaxSize::Bounded(max) if max == self.min => Some(max),
+            _ => None,
+        }
+    }
+
+    /// The maximal size, if bounded.
+    pub fn max_size(&self) -> Option<usize> {
+        match self.max {
+            MaxSize::Bounded(max) => Some(max),
+            _ => None,
+        }
+    }
+
+    /// Bounds of a value made of `self` followed by `other`.
+    fn then(self, other: SizeBounds) -> SizeBounds {
+        let max = match (self.max, other.max) {
+            (MaxSize::Bounded(x), MaxSize::Bounded(y)) => MaxSize::Bounded(x + y),
+            (MaxSize::Bounded(_), max) | (max, _) => max,
+        };
+        SizeBounds {
+            min: self.min.saturating_add(other.min),
+            max,
+        }
+    }
+
+    /// Bounds of a value that is either `self` or `other`.
+    fn or(self, other: SizeBounds) -> SizeBounds {
+        let max = match (self.max, other.max) {
+            (MaxSize::Bounded(x), MaxSize::Bounded(y)) => MaxSize::Bounded(x.max(y)),
+            (MaxSize::Bounded(_), max) | (max, _) => max,
+        };
+        SizeBounds {
+            min: self.min.min(other.min),
+            max,
+        }
+    }
+
+    /// Bounds of a value whose length is unbounded, starting from the given minimal size.
+    fn unbounded(min: usize) -> Self {
+        Self {
+            min,
+            max: MaxSize::UnboundedSequence,
+        }
+    }
+}
+
+/// Compute bounds on the size of the encoding of the container `container` in the encoding
+/// `profile`.
+///
+/// Recursive containers and containers with sequences, maps, strings, or bytes have an
+/// unbounded maximal size, unless the bytes have a declared size (`Format::FixedBytes`).
+/// BCS does not support floats and characters, which are reported as `Error::NotSupported`.
+pub fn size_bounds(
+    registry: &Registry,
+    container: &str,
+    profile: EncodingProfile,
+) -> Result<SizeBounds> {
+    size_bounds_with_tags(registry, container, profile, profile.tag_encoding())
+}
+
+/// Same as [`size_bounds`] with a custom encoding of the variants of enums, e.g. for
+/// encodings that identify variants by name.
+pub fn size_bounds_with_tags(
+    registry: &Registry,
+    container: &str,
+    profile: EncodingProfile,
+    tags: TagEncoding,
+) -> Result<SizeBounds> {
+    let mut analyzer = SizeAnalyzer::new(registry, profile);
+    analyzer.tags = tags;
+    analyzer.container(container)
+}
+
+/// Same as [`size_bounds`] for an arbitrary format, whose containers are found in `registry`.
+pub fn format_size_bounds(
+    registry: &Registry,
+    format: &Format,
+    profile: EncodingProfile,
+) -> Result<SizeBounds> {
+    let mut analyzer = SizeAnalyzer::new(registry, profile);
+    analyzer.format(format)
+}
+
+struct SizeAnalyzer<'a> {
+    registry: &'a Registry,
+    profile: EncodingProfile,
+    /// How the variants of enums are identified.
+    tags: TagEncoding,
+    /// The containers being analyzed.
+    in_progress: Vec<&'a str>,
+    /// Bounds of the containers analyzed so far, unless they were recursive.
+    known: BTreeMap<&'a str, SizeBounds>,
+    /// Number of recursive references encountered so far.
+    recursions: usize,
+}
+
+impl<'a> SizeAnalyzer<'a> {
+    fn new(registry: &'a Registry, profile: EncodingProfile) -> Self {
+        Self {
+            registry,
+            profile,
+            tags: profile.tag_encoding(),
+            in_progress: Vec::new(),
+            known: BTreeMap::new(),
+            recursions: 0,
+        }
+    }
+
+    fn container(&mut self, name: &str) -> Result<SizeBounds> {
+        let (name, format) = self
+            .registry
+            .get_key_value(name)
+            .ok_or_else(|| Error::MissingContainer(name.to_string()))?;
+        if let Some(bounds) = self.known.get(name.as_str()) {
+            return Ok(bounds.clone());
+        }
+        if self.in_progress.contains(&name.as_str()) {
+            // The smallest value of a container never contains the container itself.
+            self.recursions += 1;
+            return Ok(SizeBounds {
+                min: usize::MAX,
+                max: MaxSize::Recursive(name.clone()),
+            });
+        }
+        let recursions = self.recursions;
+        self.in_progress.push(name);
+        let bounds = self.container_format(format);
+        self.in_progress.pop();
+        let bounds = bounds?;
+        // Bounds computed with recursive references depend on the containers in progress.
+        if self.recursions == recursions {
+            self.known.insert(name, bounds.clone());
+        }
+        Ok(bounds)
+    }
+
+    fn container_format(&mut self, format: &ContainerFormat) -> Result<SizeBounds> {
+        match format {
+            ContainerFormat::UnitStruct => Ok(SizeBounds::fixed(0)),
+            ContainerFormat::NewTypeStruct(format) => self.format(format),
+            ContainerFormat::TupleStruct(formats) => self.formats(formats),
+            ContainerFormat::Struct(fields) => self.fields(fields),
+            ContainerFormat::Enum(variants) => {
+                let mut result: Option<SizeBounds> = None;
+                for (index, variant) in variants {
+                    let tag = self.tags.encoded_size(*index, &variant.name);
+                    let bounds = SizeBounds::fixed(tag).then(self.variant(&variant.value)?);
+                    result = Some(match result {
+                        Some(result) => result.or(bounds),
+                        None => bounds,
+                    });
+                }
+                Ok(result.unwrap_or_else(|| SizeBounds::fixed(0)))
+            }
+            ContainerFormat::Generic { body, .. } => self.container_format(body),
+        }
+    }
+
+    fn variant(&mut self, format: &VariantFormat) -> Result<SizeBounds> {
+        match format {
+            VariantFormat::Variable(variable) => match variable.borrow().as_ref() {
+                Some(format) => self.variant(format),
+                None => Err(Error::UnknownFormat),
+            },
+            VariantFormat::Unit => Ok(SizeBounds::fixed(0)),
+            VariantFormat::NewType(format) => self.format(format),
+            VariantFormat::Tuple(formats) => self.formats(formats),
+            VariantFormat::Struct(fields) => self.fields(fields),
+        }
+    }
+
+    fn formats(&mut self, formats: &[Format]) -> Result<SizeBounds> {
+        let mut bounds = SizeBounds::fixed(0);
+        for format in formats {
+            bounds = bounds.then(self.format(format)?);
+        }
+        Ok(bounds)
+    }
+
+    fn fields(&mut self, fields: &[Named<Format>]) -> Result<SizeBounds> {
+        let mut bounds = SizeBounds::fixed(0);
+        for field in fields {
+            bounds = bounds.then(self.format(&field.value)?);
+        }
+        Ok(bounds)
+    }
+
+    fn format(&mut self, format: &Format) -> Result<SizeBounds> {
+        let bounds = match format {
+            Format::Variable(variable) => match variable.borrow().as_ref() {
+                Some(format) => self.format(format)?,
+                None => return Err(Error::UnknownFormat),
+            },
+            // The arguments are not substituted: the parameters of the container count as
+            // generic.
+            Format::TypeName(name) | Format::Apply { name, .. } => self.container(name)?,
+            Format::TypeParam(_) => SizeBounds {
+                min: 0,
+                max: MaxSize::Generic,
+            },
+            Format::Unit => SizeBounds::fixed(0),
+            Format::Bool => SizeBounds::fixed(1),
+            Format::I8 => self.integer(NumericType::I8),
+            Format::I16 => self.integer(NumericType::I16),
+            Format::I32 => self.integer(NumericType::I32),
+            Format::I64 => self.integer(NumericType::I64),
+            Format::I128 => self.integer(NumericType::I128),
+            Format::U8 => self.integer(NumericType::U8),
+            Format::U16 => self.integer(NumericType::U16),
+            Format::U32 => self.integer(NumericType::U32),
+            Format::U64 => self.integer(NumericType::U64),
+            Format::U128 => self.integer(NumericType::U128),
+            Format::F32 | Format::F64 if self.profile == EncodingProfile::Bcs => {
+                return Err(Error::NotSupported("floats in BCS"));
+            }
+            Format::F32 => SizeBounds::fixed(4),
+            Format::F64 => SizeBounds::fixed(8),
+            Format::Char => match self.profile {
+                EncodingProfile::Bcs => return Err(Error::NotSupported("characters in BCS")),
+                // UTF-8.
+                EncodingProfile::Bincode1 => SizeBounds {
+                    min: 1,
+                    max: MaxSize::Bounded(4),
+                },
+                // A string of one character.
+                EncodingProfile::Postcard => SizeBounds {
+                    min: 2,
+                    max: MaxSize::Bounded(5),
+                },
+            },
+            Format::Str | Format::Bytes | Format::Seq(_) | Format::Map { .. } => {
+                SizeBounds::unbounded(self.length(0))
+            }
+            Format::FixedBytes(size) => SizeBounds::fixed(self.length(*size) + size),
+            Format::Option(format) => {
+                SizeBounds::fixed(1).or(SizeBounds::fixed(1).then(self.format(format)?))
+            }
+            Format::Tuple(formats) => self.formats(formats)?,
+            Format::TupleArray { size: 0, .. } => SizeBounds::fixed(0),
+            Format::TupleArray { content, size } => {
+                let content = self.format(content)?;
+                SizeBounds {
+                    min: content.min.saturating_mul(*size),
+                    max: match content.max {
+                        MaxSize::Bounded(max) => MaxSize::Bounded(max * size),
+                        max => max,
+                    },
+                }
+            }
+            // Alternatives cannot be told apart in binary encodings.
+            Format::Union(formats) => {
+                let mut result: Option<SizeBounds> = None;
+                for format in formats {
+                    let bounds = self.format(format)?;
+                    result = Some(match result {
+                        Some(result) => result.or(bounds),
+                        None => bounds,
+                    });
+                }
+                result.unwrap_or_else(|| SizeBounds::fixed(0))
+            }
+        };
+        Ok(bounds)
+    }
+
+    fn integer(&self, value: NumericType) -> SizeBounds {
+        let bits = value.bits() as usize;
+        match self.profile.integer_encoding(value) {
+            IntegerEncoding::FixedLittleEndian => SizeBounds::fixed(bits / 8),
+            IntegerEncoding::Varint | IntegerEncoding::ZigZagVarint => SizeBounds {
+                min: 1,
+                max: MaxSize::Bounded((bits + 6) / 7),
+            },
+        }
+    }
+
+    /// The size of the prefix encoding the length `length`.
+    fn length(&self, length: usize) -> usize {
+        self.profile.length_encoding().encoded_size(length as u64)
+    }
+}
+
+/// Statistics about the shape of a registry. See [`registry_stats`].
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+pub struct RegistryStats {
+    /// The number of containers.
+    pub containers: usize,
+    /// The number of unit structs.
+    pub unit_structs: usize,
+    /// The number of newtype structs.
+    pub newtype_structs: usize,
+    /// The number of tuple structs.
+    pub tuple_structs: usize,
+    /// The number of structs.
+    pub structs: usize,
+    /// The number of enums.
+    pub enums: usize,
+    /// The number of generic containers, which are not counted in the other kinds.
+    pub generics: usize,
+    /// The number of fields and tuple elements of structs and variants. Newtypes count as
+    /// one field.
+    pub fields: usize,
+    /// The number of enum variants.
+    pub variants: usize,
+    /// The length of the longest chain of containers nested in one another, not counting
+    /// recursive references. Containers without nested containers have depth 1.
+    pub max_depth: usize,
+    /// The containers that refer to themselves, directly or not.
+    pub recursive: Vec<String>,
+    /// The enum with the most variants, if any. Ties are broken by name.
+    pub widest_enum: Option<Named<usize>>,
+    /// The struct (or tuple struct) with the most fields, if any. Ties are broken by name.
+    pub widest_struct: Option<Named<usize>>,
+    /// The containers referenced by other containers, with the number of containers
+    /// referring to them, most referenced first. Ties are broken by name.
+    pub most_referenced: Vec<Named<usize>>,
+}
+
+/// Compute statistics about the shape of `registry`.
+pub fn registry_stats(registry: &Registry) -> RegistryStats {
+    let mut stats = RegistryStats {
+        containers: registry.len(),
+        ..RegistryStats::default()
+    };
+    let mut referrers: BTreeMap<&str, usize> = BTreeMap::new();
+    for (name, format) in registry {
+        let mut width = None;
+        let mut variants = None;
+        match format {
+            ContainerFormat::UnitStruct => stats.unit_structs += 1,
+            ContainerFormat::NewTypeStruct(_) => {
+                stats.newtype_structs += 1;
+                stats.fields += 1;
+            }
+            ContainerFormat::TupleStruct(formats) => {
+                stats.tuple_structs += 1;
+                width = Some(formats.len());
+            }
+            ContainerFormat::Struct(fields) => {
+                stats.structs += 1;
+                width = Some(fields.len());
+            }
+            ContainerFormat::Enum(map) => {
+                stats.enums += 1;
+                variants = Some(map.len());
+                stats.fields += map
+                    .values()
+                    .map(|variant| match &variant.value {
+                        VariantFormat::Variable(_) | VariantFormat::Unit => 0,
+                        VariantFormat::NewType(_) => 1,
+                        VariantFormat::Tuple(formats) => formats.len(),
+                        VariantFormat::Struct(fields) => fields.len(),
+                    })
+                    .sum::<usize>();
+            }
+            ContainerFormat::Generic { .. } => stats.generics += 1,
+        }
+        if let Some(width) = width {
+            stats.fields += width;
+            widen(&mut stats.widest_struct, name, width);
+        }
+        if let Some(variants) = variants {
+            stats.variants += variants;
+            widen(&mut stats.widest_enum, name, variants);
+        }
+        let mut names = registry::references(format);
+        names.sort();
+        names.dedup();
+        for other in names {
+            if other != name {
+                *referrers.entry(other).or_default() += 1;
+            }
+        }
+    }
+    let mut most_referenced: Vec<Named<usize>> = referrers
+        .into_iter()
+        .map(|(name, value)| Named {
+            name: name.to_string(),
+            value,
+        })
+        .collect();
+    most_referenced.sort_by(|a, b| b.value.cmp(&a.value).then_with(|| a.name.cmp(&b.name)));
+    stats.most_referenced = most_referenced;
+
+    let mut depths = DepthAnalyzer {
+        registry,
+        in_progress: Vec::new(),
+        memo: BTreeMap::new(),
+        recursive: BTreeSet::new(),
+    };
+    for name in registry.keys() {
+        let (depth, _) = depths.depth(name);
+        stats.max_depth = stats.max_depth.max(depth);
+    }
+    stats.recursive = depths.recursive.into_iter().map(String::from).collect();
+    stats
+}
+
+/// Keep the entry with the largest value, or the first name among equal values.
+fn widen(widest: &mut Option<Named<usize>>, name: &str, value: usize) {
+    if widest.as_ref().map_or(true, |widest| value > widest.value) {
+        *widest = Some(Named {
+            name: name.to_string(),
+            value,
+        });
+    }
+}
+
+struct DepthAnalyzer<'a> {
+    registry: &'a Registry,
+    in_progress: Vec<&'a str>,
+    /// The depths of the containers whose analysis did not meet a recursive reference.
+    memo: BTreeMap<&'a str, usize>,
+    recursive: BTreeSet<&'a str>,
+}
+
+impl<'a> DepthAnalyzer<'a> {
+    /// The nesting depth of the container `name`, and whether a recursive reference was
+    /// met. Missing containers have depth 0.
+    fn depth(&mut self, name: &str) -> (usize, bool) {
+        let Some((name, format)) = self.registry.get_key_value(name) else {
+            return (0, false);
+        };
+        let name = name.as_str();
+        if let Some(position) = self.in_progress.iter().position(|other| *other == name) {
+            self.recursive.extend(&self.in_progress[position..]);
+            return (0, true);
+        }
+        if let Some(depth) = self.memo.get(name) {
+            return (*depth, false);
+        }
+        self.in_progress.push(name);
+        let mut depth = 0;
+        let mut recursion = false;
+        for other in registry::references(format) {
+            let (other_depth, other_recursion) = self.depth(other);
+            depth = depth.max(other_depth);
+            recursion |= other_recursion;
+        }
+        self.in_progress.pop();
+        if !recursion {
+            self.memo.insert(name, depth + 1);
+        }
+        (depth + 1, recursion)
+    }
+}
+
+/// A construct that JSON cannot represent faithfully. See [`json_safety`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum JsonHazard {
+    /// The keys of a map are not written as strings, e.g. structs or sequences, whereas JSON
+    /// objects only have string keys. Floats are reported too, since `serde_json` rejects
+    /// the keys that are not finite.
+    NonStringKey(Format),
+    /// The keys of a map are byte strings (`Bytes` or `FixedBytes`).
+    BytesKey(Format),
+    /// A 128-bit integer, which most JSON parsers cannot read without losing precision.
+    Integer128(Format),
+}
+
+/// A JSON-unsafe construct found by [`json_safety`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct JsonSafetyIssue {
+    /// The name of the container, followed by the names of the fields, variants, and tuple
+    /// elements leading to the format. Map entries are named `key` and `value`.
+    pub path: Vec<String>,
+    /// The construct found at `path`.
+    pub hazard: JsonHazard,
+}
+
+impl fmt::Display for JsonSafetyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.path.join("."))?;
+        match &self.hazard {
+            JsonHazard::NonStringKey(format) => write!(f, "map key {format:?} is not a string"),
+            JsonHazard::BytesKey(format) => write!(f, "map key {format:?} is made of bytes"),
+            JsonHazard::Integer128(format) => write!(f, "128-bit integer {format:?}"),
+        }
+    }
+}
+
+/// Find the constructs of `registry` that cannot be written to JSON as is, in a
+/// deterministic order: maps whose keys are not strings, maps whose keys are bytes, and
+/// 128-bit integers.
+/// * Keys are accepted if JSON serializers such as `serde_json` write them as strings:
+///   strings, chars, booleans, integers, unit variants of enums, and newtype structs of
+///   such keys. Floats are reported, since only finite ones are accepted.
+/// * Each container is analyzed on its own, so referenced containers are not repeated.
+pub fn json_safety(registry: &Registry) -> Vec<JsonSafetyIssue> {
+    let mut checker = JsonSafetyChecker {
+        registry,
+        path: Vec::new(),
+        issues: Vec::new(),
+    };
+    for (name, format) in registry {
+        checker.path.push(name.clone());
+        checker.check_container(format);
+        checker.path.pop();
+    }
+    checker.issues
+}
+
+struct JsonSafetyChecker<'a> {
+    registry: &'a Registry,
+    path: Vec<String>,
+    issues: Vec<JsonSafetyIssue>,
+}
+
+impl JsonSafetyChecker<'_> {
+    fn check_container(&mut self, format: &ContainerFormat) {
+        match format {
+            ContainerFormat::UnitStruct => (),
+            ContainerFormat::NewTypeStruct(format) => self.check(format),
+            ContainerFormat::TupleStruct(formats) => self.check_all(formats),
+            ContainerFormat::Struct(fields) => self.check_fields(fields),
+            ContainerFormat::Enum(variants) => {
+                for variant in variants.values() {
+                    self.path.push(variant.name.clone());
+                    match &variant.value {
+                        VariantFormat::Variable(_) | VariantFormat::Unit => (),
+                        VariantFormat::NewType(format) => self.check(format),
+                        VariantFormat::Tuple(formats) => self.check_all(formats),
+                        VariantFormat::Struct(fields) => self.check_fields(fields),
+                    }
+                    self.path.pop();
+                }
+            }
+            ContainerFormat::Generic { body, .. } => self.check_container(body),
+        }
+    }
+
+    fn check(&mut self, format: &Format) {
+        match format {
+            Format::I128 | Format::U128 => self.report(JsonHazard::Integer128(format.clone())),
+            Format::Option(format)
+            | Format::Seq(format)
+            | Format::TupleArray {
+                content: format, ..
+            } => self.check(format),
+            Format::Map { key, value } => {
+                self.path.push("key".to_string());
+                if let Some(hazard) = self.key_hazard(key, &mut BTreeSet::new()) {
+                    self.report(hazard);
+                }
+                self.check(key);
+                self.path.pop();
+                self.check_labeled("value".to_string(), value);
+            }
+            Format::Tuple(formats) | Format::Union(formats) => self.check_all(formats),
+            _ => (),
+        }
+    }
+
+    fn check_all(&mut self, formats: &[Format]) {
+        for (index, format) in formats.iter().enumerate() {
+            self.check_labeled(index.to_string(), format);
+        }
+    }
+
+    fn check_fields(&mut self, fields: &[Named<Format>]) {
+        for field in fields {
+            self.check_labeled(field.name.clone(), &field.value);
+        }
+    }
+
+    fn check_labeled(&mut self, label: String, format: &Format) {
+        self.path.push(label);
+        self.check(format);
+        self.path.pop();
+    }
+
+    /// Why the map key `format` is not written as a string, if it is not. Newtype structs
+    /// in `visited` are not expanded again.
+    fn key_hazard<'b>(
+        &'b self,
+        format: &'b Format,
+        visited: &mut BTreeSet<&'b str>,
+    ) -> Option<JsonHazard> {
+        match format {
+            Format::Variable(_) | Format::TypeParam(_) => None,
+            Format::Str | Format::Char | Format::Bool => None,
+            format if NumericType::from_format(format).is_some() => None,
+            Format::F32 | Format::F64 => Some(JsonHazard::NonStringKey(format.clone())),
+            Format::Bytes | Format::FixedBytes(_) => Some(JsonHazard::BytesKey(format.clone())),
+            Format::TypeName(name) => match self.registry.get(name).map(unwrap_generic) {
+                // Missing containers are reported by `registry::validate`.
+                None => None,
+                Some(ContainerFormat::NewTypeStruct(inner)) if visited.insert(name) => {
+                    self.key_hazard(inner, visited)
+                }
+                Some(ContainerFormat::Enum(variants))
+                    if variants
+                        .values()
+                        .all(|variant| variant.value == VariantFormat::Unit) =>
+                {
+                    None
+                }
+                Some(_) => Some(JsonHazard::NonStringKey(format.clone())),
+            },
+            _ => Some(JsonHazard::NonStringKey(format.clone())),
+        }
+    }
+
+    fn report(&mut self, hazard: JsonHazard) {
+        self.issues.push(JsonSafetyIssue {
+            path: self.path.clone(),
+            hazard,
+        });
+    }
+}
+
+/// Find the containers of `registry` that have no values, e.g. enums without variants and
+/// the structs that contain them. Such containers can be referred to by variants, options,
+/// and sequences, but no value of them can be encoded.
+/// * Recursive containers are inhabited only if they have a base case, e.g. the variant
+///   `Empty` of `enum List { Empty, Node(u32, Box<List>) }`.
+/// * References to missing containers and unknown formats are assumed to be inhabited.
+pub fn uninhabited_containers(registry: &Registry) -> BTreeSet<String> {
+    let mut inhabited = BTreeSet::new();
+    // Compute the least fixed point, starting with no inhabited containers.
+    loop {
+        let found: Vec<_> = registry
+            .iter()
+            .filter(|(name, format)| {
+                !inhabited.contains(*name)
+                    && is_inhabited_container(registry, &inhabited, unwrap_generic(format))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        if found.is_empty() {
+            break;
+        }
+        inhabited.extend(found);
+    }
+    registry
+        .keys()
+        .filter(|name| !inhabited.contains(*name))
+        .cloned()
+        .collect()
+}
+
+fn is_inhabited_container(
+    registry: &Registry,
+    inhabited: &BTreeSet<String>,
+    format: &ContainerFormat,
+) -> bool {
+    match format {
+        ContainerFormat::UnitStruct => true,
+        ContainerFormat::NewTypeStruct(format) => is_inhabited(registry, inhabited, format),
+        ContainerFormat::TupleStruct(formats) => formats
+            .iter()
+            .all(|format| is_inhabited(registry, inhabited, format)),
+        ContainerFormat::Struct(fields) => fields
+            .iter()
+            .all(|field| is_inhabited(registry, inhabited, &field.value)),
+        ContainerFormat::Enum(variants) => variants
+            .values()
+            .any(|variant| is_inhabited_variant(registry, inhabited, &variant.value)),
+        ContainerFormat::Generic { body, .. } => is_inhabited_container(registry, inhabited, body),
+    }
+}
+
+/// Whether the variant has values, given the containers known to be inhabited so far.
+pub(crate) fn is_inhabited_variant(
+    registry: &Registry,
+    inhabited: &BTreeSet<String>,
+    format: &VariantFormat,
+) -> bool {
+    match format {
+        VariantFormat::Variable(variable) => match variable.borrow().as_ref() {
+            Some(format) => is_inhabited_variant(registry, inhabited, format),
+            None => true,
+        },
+        VariantFormat::Unit => true,
+        VariantFormat::NewType(format) => is_inhabited(registry, inhabited, format),
+        VariantFormat::Tuple(formats) => formats
+            .iter()
+            .all(|format| is_inhabited(registry, inhabited, format)),
+        VariantFormat::Struct(fields) => fields
+            .iter()
+            .all(|field| is_inhabited(registry, inhabited, &field.value)),
+    }
+}
+
+/// Whether the format has values, given the containers known to be inhabited so far.
+pub(crate) fn is_inhabited(
+    registry: &Registry,
+    inhabited: &BTreeSet<String>,
+    format: &Format,
+) -> bool {
+    match format {
+        Format::Variable(variable) => match variable.borrow().as_ref() {
+            Some(format) => is_inhabited(registry, inhabited, format),
+            None => true,
+        },
+        Format::TypeName(name) | Format::Apply { name, .. } => {
+            !registry.contains_key(name) || inhabited.contains(name)
+        }
+        Format::Tuple(formats) => formats
+            .iter()
+            .all(|format| is_inhabited(registry, inhabited, format)),
+        Format::TupleArray { content, size } => {
+            *size == 0 || is_inhabited(registry, inhabited, content)
+        }
+        // Options, sequences, and maps may be empty.
+        _ => true,
+    }
+}
+
+/// The body of a generic container, or the container itself.
+fn unwrap_generic(format: &ContainerFormat) -> &ContainerFormat {
+    match format {
+        ContainerFormat::Generic { body, .. } => body,
+        format => format,
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    error::Result,
+    format::Format,
+    trace::{Samples, Tracer},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// Records a fetched value in the samples.
+type Recorder = Box<dyn FnOnce(&mut Tracer, &mut Samples) -> Result<()> + Send>;
+
+type PendingSample = Pin<Box<dyn Future<Output = Recorder> + Send>>;
+
+type Provider = Arc<dyn Fn() -> PendingSample + Send + Sync>;
+
+/// The asynchronous providers of a `Samples` structure that were not awaited yet.
+#[derive(Clone, Default)]
+pub(crate) struct AsyncProviders(Vec<Provider>);
+
+impl std::fmt::Debug for AsyncProviders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} pending", self.0.len())
+    }
+}
+
+impl PartialEq for AsyncProviders {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(&other.0)
+                .all(|(p1, p2)| Arc::ptr_eq(p1, p2))
+    }
+}
+
+impl Samples {
+    /// Register an asynchronous function producing a sample value of type `T`, e.g. by
+    /// loading a fixture from remote storage.
+    /// * The function is not called until `Tracer::trace_type_async` needs the samples.
+    ///   The values of all the pending providers are then fetched concurrently and recorded
+    ///   with `Tracer::trace_value`, in the order of registration.
+    /// * Cloning the samples shares the pending providers, which are called once per clone.
+    pub fn register_async<T, F, Fut>(&mut self, provider: F)
+    where
+        T: Serialize + Send + 'static,
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        let provider = move || -> PendingSample {
+            let future = provider();
+            Box::pin(async move {
+                let value = future.await;
+                let recorder: Recorder = Box::new(move |tracer, samples| {
+                    tracer.trace_value(samples, &value)?;
+                    Ok(())
+                });
+                recorder
+            })
+        };
+        self.providers.0.push(Arc::new(provider));
+    }
+
+    /// The number of asynchronous providers whose values were not fetched yet.
+    pub fn pending_async(&self) -> usize {
+        self.providers.0.len()
+    }
+}
+
+impl Tracer {
+    /// Same as `trace_type` after fetching the values of the providers registered with
+    /// `Samples::register_async`, if any.
+    /// * The providers run concurrently within the returned future, so that this works with
+    ///   any executor. Providers that need a particular runtime (e.g. for I/O) must be
+    ///   awaited on that runtime.
+    /// * Once recorded, the fetched values are kept in `samples` and the providers are
+    ///   dropped.
+    pub async fn trace_type_async<'de, T>(
+        &mut self,
+        samples: &'de mut Samples,
+    ) -> Result<(Format, Vec<T>)>
+    where
+        T: Deserialize<'de>,
+    {
+        let pending = samples.providers.0.iter().map(|provider| provider());
+        let recorders = JoinAll::new(pending).await;
+        // Providers are only dropped once all their values are fetched.
+        samples.providers.0.clear();
+        for recorder in recorders {
+            recorder(self, samples)?;
+        }
+        let samples: &'de Samples = samples;
+        self.trace_type(samples)
+    }
+}
+
+/// Poll a collection of futures until all of them are ready.
+struct JoinAll<O> {
+    futures: Vec<Pin<Box<dyn Future<Output = O> + Send>>>,
+    outputs: Vec<Option<O>>,
+}
+
+impl<O> JoinAll<O> {
+    fn new<I>(futures: I) -> Self
+    where
+        I: IntoIterator<Item = Pin<Box<dyn Future<Output = O> + Send>>>,
+    {
+        let futures: Vec<_> = futures.into_iter().collect();
+        let outputs = futures.iter().map(|_| None).collect();
+        Self { futures, outputs }
+    }
+}
+
+impl<O: Unpin> Future for JoinAll<O> {
+    type Output = Vec<O>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Vec<O>> {
+        let this = self.get_mut();
+        for (future, output) in this.futures.iter_mut().zip(&mut this.outputs) {
+            if output.is_none() {
+                if let Poll::Ready(value) = future.as_mut().poll(cx) {
+                    *output = Some(value);
+                }
+            }
+        }
+        if this.outputs.iter().any(Option::is_none) {
+            return Poll::Pending;
+        }
+        Poll::Ready(this.outputs.iter_mut().map(|o| o.take().unwrap()).collect())
+    }
+}
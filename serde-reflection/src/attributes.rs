@@ -0,0 +1,437 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Serde attributes of containers, as recorded by `#[derive(Reflect)]`.
+//!
+//! Tracing only observes what Serde implementations do, so some attributes must be guessed
+//! (e.g. `#[serde(deny_unknown_fields)]`, which takes an extra pass to detect) or cannot be
+//! observed at all. The derive macro of the companion crate `serde-reflection-derive`
+//! (re-exported with the feature `derive`) records the `#[serde(..)]` attributes of a type
+//! as a [`ContainerAttributes`]. Once the type is registered in [`ATTRIBUTE_TABLE`] with
+//! [`register`], the `Tracer` uses the recorded attributes instead:
+//! * `Tracer::denies_unknown_fields` and `Tracer::fallback_variant` are answered without
+//!   probing,
+//! * `Tracer::requires_self_describing` holds for internally tagged, adjacently tagged,
+//!   and untagged enums, and for structs with flattened fields,
+//! * `Tracer::attributes` gives access to the record.
+//!
+//! Records are looked up by the Serde name of the container, so registering two types
+//! with the same Serde name but different attributes is rejected.
+//!
+//! Conversely, [`cross_check`] compares a traced registry with the registered attributes,
+//! e.g. to catch a hand-written implementation of `Deserialize` that no longer matches the
+//! declaration of its type.
+//!
+//! ```rust
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use serde::{Deserialize, Serialize};
+//! use serde_reflection::{attributes, Reflect, Tracer, TracerConfig};
+//!
+//! #[derive(Serialize, Deserialize, Reflect)]
+//! #[serde(rename_all = "camelCase", deny_unknown_fields)]
+//! struct Foo {
+//!     first_name: String,
+//!     #[serde(default)]
+//!     age: u8,
+//! }
+//!
+//! attributes::register::<Foo>().unwrap();
+//! let mut tracer = Tracer::new(TracerConfig::default());
+//! tracer.trace_simple_type::<Foo>().unwrap();
+//! assert_eq!(tracer.denies_unknown_fields("Foo"), Some(true));
+//! let fields = tracer.attributes("Foo").unwrap().fields;
+//! assert_eq!(fields[0].name, "firstName");
+//! assert!(fields[1].default);
+//! # }
+//! ```
+
+use crate::{ContainerFormat, ContainerKind, Error, Registry, Result, VariantFormat};
+use once_cell::sync::Lazy;
+use std::{collections::BTreeMap, fmt, sync::Mutex};
+
+/// Types whose Serde attributes are known statically, usually through `#[derive(Reflect)]`.
+pub trait Reflect {
+    /// The attributes of the type.
+    fn attributes() -> &'static ContainerAttributes;
+}
+
+/// How the variants of an enum are represented (see the Serde documentation on enum
+/// representations).
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum TagMode {
+    /// The default representation, e.g. `{"A": {..}}` in JSON.
+    #[default]
+    External,
+    /// `#[serde(tag = "..")]`, e.g. `{"type": "A", ..}`.
+    Internal { tag: &'static str },
+    /// `#[serde(tag = "..", content = "..")]`, e.g. `{"t": "A", "c": {..}}`.
+    Adjacent {
+        tag: &'static str,
+        content: &'static str,
+    },
+    /// `#[serde(untagged)]`.
+    Untagged,
+}
+
+/// The Serde attributes of a container.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ContainerAttributes {
+    /// The Serde name of the container, i.e. its Rust name unless it is renamed.
+    pub name: &'static str,
+    /// The Rust name of the container.
+    pub rust_name: &'static str,
+    /// The kind of the container, following the declaration of the Rust type.
+    pub kind: ContainerKind,
+    /// The representation of the variants. Always `External` for structs.
+    pub tag: TagMode,
+    /// `#[serde(transparent)]`.
+    pub transparent: bool,
+    /// `#[serde(deny_unknown_fields)]`.
+    pub deny_unknown_fields: bool,
+    /// `#[serde(default)]` on the container.
+    pub default: bool,
+    /// The fields of a struct. Empty for enums.
+    pub fields: &'static [FieldAttributes],
+    /// The variants of an enum, in declaration order. Empty for structs.
+    pub variants: &'static [VariantAttributes],
+}
+
+/// The Serde attributes of a field. Fields of tuple structs and tuple variants are named
+/// by position (`"0"`, `"1"`, ...).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct FieldAttributes {
+    /// The serialized name of the field, after `rename` and `rename_all`.
+    pub name: &'static str,
+    /// The Rust name of the field.
+    pub rust_name: &'static str,
+    /// `#[serde(default)]` or `#[serde(default = "..")]`.
+    pub default: bool,
+    /// `#[serde(skip)]` or `#[serde(skip_serializing)]`. Fields skipped conditionally with
+    /// `skip_serializing_if` are not included.
+    pub skip_serializing: bool,
+    /// `#[serde(skip)]` or `#[serde(skip_deserializing)]`.
+    pub skip_deserializing: bool,
+    /// `#[serde(flatten)]`.
+    pub flatten: bool,
+}
+
+/// The Serde attributes of an enum variant.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct VariantAttributes {
+    /// The serialized name of the variant, after `rename` and `rename_all`.
+    pub name: &'static str,
+    /// The Rust name of the variant.
+    pub rust_name: &'static str,
+    /// The kind of a struct with the same fields, e.g. `ContainerKind::NewTypeStruct` for
+    /// a newtype variant.
+    pub kind: ContainerKind,
+    /// `#[serde(skip)]` or `#[serde(skip_serializing)]`.
+    pub skip_serializing: bool,
+    /// `#[serde(skip)]` or `#[serde(skip_deserializing)]`.
+    pub skip_deserializing: bool,
+    /// `#[serde(other)]`.
+    pub other: bool,
+    /// `#[serde(untagged)]` on the variant.
+    pub untagged: bool,
+    /// The fields of the variant.
+    pub fields: &'static [FieldAttributes],
+}
+
+impl ContainerAttributes {
+    /// Find a field of a struct by serialized name.
+    pub fn field(&self, name: &str) -> Option<&'static FieldAttributes> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Find a variant of an enum by serialized name.
+    pub fn variant(&self, name: &str) -> Option<&'static VariantAttributes> {
+        self.variants.iter().find(|variant| variant.name == name)
+    }
+
+    /// Whether the container can only be read from self-describing encodings because of
+    /// its attributes, i.e. it is an enum that is not externally tagged, it has untagged
+    /// variants, or it has flattened fields.
+    pub fn requires_self_describing(&self) -> bool {
+        self.tag != TagMode::External
+            || self.fields.iter().any(|field| field.flatten)
+            || self
+                .variants
+                .iter()
+                .any(|variant| variant.untagged || variant.fields.iter().any(|field| field.flatten))
+    }
+
+    /// The name of the variant marked with `#[serde(other)]`, if any.
+    pub fn fallback_variant(&self) -> Option<&'static str> {
+        self.variants
+            .iter()
+            .find(|variant| variant.other)
+            .map(|variant| variant.name)
+    }
+}
+
+/// The attributes registered with [`register`], indexed by Serde name.
+pub static ATTRIBUTE_TABLE: Lazy<Mutex<BTreeMap<&'static str, &'static ContainerAttributes>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Record the attributes of `T` in [`ATTRIBUTE_TABLE`]. Registering attributes that differ
+/// from those already recorded under the same Serde name fails with `Error::NameClash`.
+pub fn register<T: Reflect + ?Sized>() -> Result<()> {
+    let attributes = T::attributes();
+    let mut table = ATTRIBUTE_TABLE.lock().unwrap();
+    match table.get(attributes.name) {
+        Some(existing) if *existing != attributes => {
+            Err(Error::NameClash(attributes.name.to_string()))
+        }
+        _ => {
+            table.insert(attributes.name, attributes);
+            Ok(())
+        }
+    }
+}
+
+/// Look up the attributes of the container with the given Serde name.
+pub fn lookup(name: &str) -> Option<&'static ContainerAttributes> {
+    ATTRIBUTE_TABLE.lock().unwrap().get(name).copied()
+}
+
+/// A difference between the registered attributes of a container and its traced format,
+/// found by [`cross_check`]. This usually indicates a hand-written implementation of
+/// `Serialize` or `Deserialize` that diverges from the declared attributes.
+///
+/// Members are identified by their qualified names, as in [`Deprecations`](crate::Deprecations).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AttributeMismatch {
+    /// A container or a variant was traced as another kind. Variants are described by the
+    /// kind of a struct with the same fields.
+    KindMismatch {
+        path: Vec<String>,
+        expected: ContainerKind,
+        found: ContainerKind,
+    },
+    /// A field that is not skipped during deserialization was not traced.
+    MissingField { path: Vec<String> },
+    /// A traced field has no counterpart in the attributes.
+    UnexpectedField { path: Vec<String> },
+    /// The traced fields are the expected ones, in another order.
+    FieldOrder {
+        path: Vec<String>,
+        expected: Vec<String>,
+        found: Vec<String>,
+    },
+    /// A tuple struct or a tuple variant was traced with another number of fields.
+    FieldCount {
+        path: Vec<String>,
+        expected: usize,
+        found: usize,
+    },
+    /// A variant that is not skipped during deserialization was not traced.
+    MissingVariant { path: Vec<String> },
+    /// A traced variant has no counterpart in the attributes.
+    UnexpectedVariant { path: Vec<String> },
+}
+
+impl AttributeMismatch {
+    /// The qualified name of the member where the mismatch was found.
+    pub fn path(&self) -> &[String] {
+        use AttributeMismatch::*;
+        match self {
+            KindMismatch { path, .. }
+            | MissingField { path }
+            | UnexpectedField { path }
+            | FieldOrder { path, .. }
+            | FieldCount { path, .. }
+            | MissingVariant { path }
+            | UnexpectedVariant { path } => path,
+        }
+    }
+}
+
+impl fmt::Display for AttributeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AttributeMismatch::*;
+        write!(f, "{}: ", self.path().join("::"))?;
+        match self {
+            KindMismatch {
+                expected, found, ..
+            } => write!(f, "expected {expected:?}, traced {found:?}"),
+            MissingField { .. } => write!(f, "field was not traced"),
+            UnexpectedField { .. } => write!(f, "traced field is not declared"),
+            FieldOrder {
+                expected, found, ..
+            } => write!(
+                f,
+                "expected fields {}, traced {}",
+                expected.join(", "),
+                found.join(", ")
+            ),
+            FieldCount {
+                expected, found, ..
+            } => write!(f, "expected {expected} fields, traced {found}"),
+            MissingVariant { .. } => write!(f, "variant was not traced"),
+            UnexpectedVariant { .. } => write!(f, "traced variant is not declared"),
+        }
+    }
+}
+
+/// Compare the containers of `registry` with the attributes registered for them, if any.
+/// Mismatches are returned in a deterministic order. See [`check_container`].
+pub fn cross_check(registry: &Registry) -> Vec<AttributeMismatch> {
+    let mut mismatches = Vec::new();
+    for (name, format) in registry {
+        if let Some(attributes) = lookup(name) {
+            mismatches.extend(check_container(attributes, format));
+        }
+    }
+    mismatches
+}
+
+/// Compare a traced container with its attributes.
+/// * The traced format is expected to come from deserialization tracing: fields and
+///   variants skipped during deserialization are ignored, while those skipped during
+///   serialization only are expected.
+/// * Transparent containers and containers requiring a self-describing encoding (see
+///   [`ContainerAttributes::requires_self_describing`]) are not traced according to their
+///   declaration and are not checked.
+pub fn check_container(
+    attributes: &ContainerAttributes,
+    format: &ContainerFormat,
+) -> Vec<AttributeMismatch> {
+    let mut mismatches = Vec::new();
+    if attributes.transparent || attributes.requires_self_describing() {
+        return mismatches;
+    }
+    let path = vec![attributes.name.to_string()];
+    if format.kind() != attributes.kind {
+        mismatches.push(AttributeMismatch::KindMismatch {
+            path,
+            expected: attributes.kind,
+            found: format.kind(),
+        });
+        return mismatches;
+    }
+    match format.body() {
+        ContainerFormat::Struct(fields) => {
+            let found = fields.iter().map(|field| field.name.as_str()).collect();
+            check_named_fields(&path, attributes.fields, found, &mut mismatches);
+        }
+        ContainerFormat::TupleStruct(formats) => {
+            check_field_count(&path, attributes.fields, formats.len(), &mut mismatches);
+        }
+        ContainerFormat::Enum(variants) => {
+            for variant in attributes.variants {
+                if !variant.skip_deserializing
+                    && !variants.values().any(|named| named.name == variant.name)
+                {
+                    mismatches.push(AttributeMismatch::MissingVariant {
+                        path: vec![path[0].clone(), variant.name.to_string()],
+                    });
+                }
+            }
+            for named in variants.values() {
+                let path = vec![path[0].clone(), named.name.clone()];
+                match attributes.variant(&named.name) {
+                    Some(variant) if !variant.skip_deserializing => {
+                        check_variant(path, variant, &named.value, &mut mismatches);
+                    }
+                    _ => mismatches.push(AttributeMismatch::UnexpectedVariant { path }),
+                }
+            }
+        }
+        _ => (),
+    }
+    mismatches
+}
+
+fn check_variant(
+    path: Vec<String>,
+    attributes: &VariantAttributes,
+    format: &VariantFormat,
+    mismatches: &mut Vec<AttributeMismatch>,
+) {
+    let found = match format {
+        // Unknown formats are reported by `registry::validate`.
+        VariantFormat::Variable(_) => return,
+        VariantFormat::Unit => ContainerKind::UnitStruct,
+        VariantFormat::NewType(_) => ContainerKind::NewTypeStruct,
+        VariantFormat::Tuple(_) => ContainerKind::TupleStruct,
+        VariantFormat::Struct(_) => ContainerKind::Struct,
+    };
+    if found != attributes.kind {
+        mismatches.push(AttributeMismatch::KindMismatch {
+            path,
+            expected: attributes.kind,
+            found,
+        });
+        return;
+    }
+    match format {
+        VariantFormat::Struct(fields) => {
+            let found = fields.iter().map(|field| field.name.as_str()).collect();
+            check_named_fields(&path, attributes.fields, found, mismatches);
+        }
+        VariantFormat::Tuple(formats) => {
+            check_field_count(&path, attributes.fields, formats.len(), mismatches);
+        }
+        _ => (),
+    }
+}
+
+fn check_named_fields(
+    path: &[String],
+    fields: &[FieldAttributes],
+    found: Vec<&str>,
+    mismatches: &mut Vec<AttributeMismatch>,
+) {
+    let expected: Vec<&str> = fields
+        .iter()
+        .filter(|field| !field.skip_deserializing)
+        .map(|field| field.name)
+        .collect();
+    let field_path = |name: &str| {
+        let mut path = path.to_vec();
+        path.push(name.to_string());
+        path
+    };
+    let count = mismatches.len();
+    for name in &expected {
+        if !found.contains(name) {
+            mismatches.push(AttributeMismatch::MissingField {
+                path: field_path(name),
+            });
+        }
+    }
+    for name in &found {
+        if !expected.contains(name) {
+            mismatches.push(AttributeMismatch::UnexpectedField {
+                path: field_path(name),
+            });
+        }
+    }
+    if mismatches.len() == count && expected != found {
+        mismatches.push(AttributeMismatch::FieldOrder {
+            path: path.to_vec(),
+            expected: expected.into_iter().map(String::from).collect(),
+            found: found.into_iter().map(String::from).collect(),
+        });
+    }
+}
+
+fn check_field_count(
+    path: &[String],
+    fields: &[FieldAttributes],
+    found: usize,
+    mismatches: &mut Vec<AttributeMismatch>,
+) {
+    let expected = fields
+        .iter()
+        .filter(|field| !field.skip_deserializing)
+        .count();
+    if expected != found {
+        mismatches.push(AttributeMismatch::FieldCount {
+            path: path.to_vec(),
+            expected,
+            found,
+        });
+    }
+}
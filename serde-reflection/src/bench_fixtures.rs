@@ -0,0 +1,274 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Representative workloads for benchmarking the tracer.
+//!
+//! The benchmarks of this crate (`cargo bench --features bench-fixtures`) time the
+//! [`workloads`] below, so that performance regressions in the passes over nested enums,
+//! the lookups of samples, or the unification of large registries are caught before
+//! release. Crates wrapping the tracer can time the same workloads against their own code.
+//!
+//! ```rust
+//! use serde_reflection::bench_fixtures::workloads;
+//!
+//! for workload in workloads() {
+//!     let registry = workload.run().unwrap();
+//!     assert!(!registry.is_empty(), "{}", workload.name);
+//! }
+//! ```
+
+use crate::{
+    ContainerFormat, Format, Named, Registry, Result, Samples, Tracer, TracerConfig, VariantFormat,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+macro_rules! deep_enums {
+    ($last:ident) => {
+        /// The innermost level of the nested enums starting with `Deep0`.
+        #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+        pub enum $last {
+            Leaf(u64),
+            Flag(bool),
+        }
+    };
+    ($name:ident, $next:ident $(, $rest:ident)*) => {
+        /// A level of nested enums. Every level except the innermost one has a variant per
+        /// shape of payload, so that each pass of the tracer visits all the levels.
+        #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+        pub enum $name {
+            Leaf(u64),
+            Next($next),
+            Boxed(Box<$name>),
+            Pair(Box<$next>, Box<$next>),
+            Named { inner: Box<$next>, label: String },
+            Empty,
+        }
+
+        deep_enums!($next $(, $rest)*);
+    };
+}
+
+deep_enums!(Deep0, Deep1, Deep2, Deep3, Deep4, Deep5, Deep6, Deep7);
+
+macro_rules! wide_enums {
+    ($($variant:ident),*) => {
+        /// An enum with many variants, nested in each variant of [`WideOuter`].
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+        pub enum WideInner {
+            $($variant),*
+        }
+
+        /// An enum with many variants, each holding an enum with many variants.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+        pub enum WideOuter {
+            $($variant(WideInner)),*
+        }
+    };
+}
+
+wide_enums!(
+    V000, V001, V002, V003, V004, V005, V006, V007, V008, V009, V010, V011, V012, V013, V014, V015,
+    V016, V017, V018, V019, V020, V021, V022, V023, V024, V025, V026, V027, V028, V029, V030, V031,
+    V032, V033, V034, V035, V036, V037, V038, V039, V040, V041, V042, V043, V044, V045, V046, V047,
+    V048, V049, V050, V051, V052, V053, V054, V055, V056, V057, V058, V059, V060, V061, V062, V063,
+    V064, V065, V066, V067, V068, V069, V070, V071, V072, V073, V074, V075, V076, V077, V078, V079,
+    V080, V081, V082, V083, V084, V085, V086, V087, V088, V089, V090, V091, V092, V093, V094, V095,
+    V096, V097, V098, V099, V100, V101, V102, V103, V104, V105, V106, V107, V108, V109, V110, V111,
+    V112, V113, V114, V115, V116, V117, V118, V119, V120, V121, V122, V123, V124, V125, V126, V127
+);
+
+/// A struct with many fields of different formats.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WideStruct {
+    pub f00: bool,
+    pub f01: u8,
+    pub f02: u16,
+    pub f03: u32,
+    pub f04: u64,
+    pub f05: u128,
+    pub f06: i8,
+    pub f07: i16,
+    pub f08: i32,
+    pub f09: i64,
+    pub f10: i128,
+    pub f11: f32,
+    pub f12: f64,
+    pub f13: char,
+    pub f14: String,
+    pub f15: Vec<u8>,
+    pub f16: Option<u32>,
+    pub f17: Option<String>,
+    pub f18: Vec<u64>,
+    pub f19: Vec<String>,
+    pub f20: BTreeMap<String, u64>,
+    pub f21: BTreeMap<u32, Vec<u8>>,
+    pub f22: (u8, u16),
+    pub f23: (String, bool, i64),
+    pub f24: [u8; 4],
+    pub f25: [u32; 8],
+    pub f26: Option<Vec<u16>>,
+    pub f27: Vec<Option<i32>>,
+    pub f28: Vec<(u8, String)>,
+    pub f29: Option<(u64, u64)>,
+    pub f30: BTreeMap<String, Option<bool>>,
+    pub f31: Vec<Vec<u8>>,
+}
+
+/// A struct nesting deep enums, traced with samples.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    pub id: u64,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub parent: Option<u64>,
+    pub root: Deep0,
+}
+
+/// A struct nesting records, traced with samples.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Batch {
+    pub records: Vec<Record>,
+    pub latest: Option<Record>,
+}
+
+/// A way to exercise the tracer, timed by the benchmarks.
+pub struct Workload {
+    /// The name of the workload, e.g. `"deep_enums"`.
+    pub name: &'static str,
+    /// The configuration of the tracer.
+    pub config: TracerConfig,
+    /// Trace the types of the workload.
+    pub trace: fn(&mut Tracer, &mut Samples) -> Result<()>,
+}
+
+impl Workload {
+    /// Run the workload with a new tracer and return the registry.
+    pub fn run(&self) -> Result<Registry> {
+        let mut tracer = Tracer::new(self.config.clone());
+        let mut samples = Samples::new();
+        (self.trace)(&mut tracer, &mut samples)?;
+        tracer.registry()
+    }
+}
+
+/// All the workloads, sorted by name.
+pub fn workloads() -> Vec<Workload> {
+    vec![
+        Workload {
+            name: "deep_enums",
+            config: TracerConfig::default(),
+            trace: |tracer, _samples| {
+                tracer.trace_simple_type::<Deep0>()?;
+                tracer.trace_simple_type::<Deep1>()?;
+                tracer.trace_simple_type::<Deep2>()?;
+                tracer.trace_simple_type::<Deep3>()?;
+                tracer.trace_simple_type::<Deep4>()?;
+                tracer.trace_simple_type::<Deep5>()?;
+                tracer.trace_simple_type::<Deep6>()?;
+                tracer.trace_simple_type::<Deep7>()?;
+                Ok(())
+            },
+        },
+        Workload {
+            name: "large_registry",
+            config: TracerConfig::default(),
+            trace: |tracer, _samples| {
+                for (name, format) in large_registry(512) {
+                    tracer.record_dyn_container(&name, format)?;
+                }
+                Ok(())
+            },
+        },
+        Workload {
+            name: "samples",
+            config: TracerConfig::default().record_samples_for_structs(true),
+            trace: |tracer, samples| {
+                for i in 0..64 {
+                    let record = Record {
+                        id: i,
+                        name: format!("record {i}"),
+                        tags: vec!["sample".to_string()],
+                        parent: i.checked_sub(1),
+                        root: Deep0::Next(Deep1::Leaf(i)),
+                    };
+                    tracer.trace_value(samples, &record)?;
+                }
+                tracer.trace_type::<Batch>(samples)?;
+                tracer.trace_type::<Deep0>(samples)?;
+                tracer.trace_simple_type::<Deep1>()?;
+                tracer.trace_simple_type::<Deep2>()?;
+                tracer.trace_simple_type::<Deep3>()?;
+                tracer.trace_simple_type::<Deep4>()?;
+                tracer.trace_simple_type::<Deep5>()?;
+                tracer.trace_simple_type::<Deep6>()?;
+                tracer.trace_simple_type::<Deep7>()?;
+                Ok(())
+            },
+        },
+        Workload {
+            name: "wide_enums",
+            config: TracerConfig::default(),
+            trace: |tracer, _samples| {
+                tracer.trace_simple_type::<WideOuter>()?;
+                tracer.trace_simple_type::<WideInner>()?;
+                Ok(())
+            },
+        },
+        Workload {
+            name: "wide_struct",
+            config: TracerConfig::default(),
+            trace: |tracer, _samples| {
+                tracer.trace_simple_type::<WideStruct>()?;
+                Ok(())
+            },
+        },
+    ]
+}
+
+/// A synthetic registry of `size` containers named `C0`, `C1`, etc. Each container refers
+/// to the next ones, and every fourth container is an enum.
+pub fn large_registry(size: usize) -> Registry {
+    let name = |i: usize| Format::TypeName(format!("C{}", i % size));
+    (0..size)
+        .map(|i| {
+            let format = if i % 4 == 3 {
+                let variants = [
+                    ("Empty", VariantFormat::Unit),
+                    ("Value", VariantFormat::NewType(Box::new(Format::U64))),
+                    ("Next", VariantFormat::Tuple(vec![name(i + 1), Format::Str])),
+                ];
+                ContainerFormat::Enum(
+                    variants
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, (name, value))| {
+                            let named = Named {
+                                name: name.to_string(),
+                                value,
+                            };
+                            (index as u32, named)
+                        })
+                        .collect(),
+                )
+            } else {
+                let fields = [
+                    ("id", Format::U32),
+                    ("label", Format::Str),
+                    ("next", Format::Option(Box::new(name(i + 1)))),
+                    ("others", Format::Seq(Box::new(name(i + 2)))),
+                ];
+                ContainerFormat::Struct(
+                    fields
+                        .into_iter()
+                        .map(|(name, value)| Named {
+                            name: name.to_string(),
+                            value,
+                        })
+                        .collect(),
+                )
+            };
+            (format!("C{i}"), format)
+        })
+        .collect()
+}
@@ -0,0 +1,478 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{ContainerFormat, Format, Named, Registry, Value, VariantFormat};
+
+/// A compact binary encoding whose byte layout is predicted from a registry, without the
+/// original Rust types.
+/// * `BincodeV2` is the standard configuration of Bincode 2 (variable-length integers,
+///   little endian): integers below 251 take one byte, larger ones are prefixed by a
+///   byte between 251 and 254 giving their width.
+/// * `Postcard` encodes integers as LEB128 varints.
+///
+/// In both encodings, signed integers wider than 8 bits are zigzag-encoded, lengths and
+/// variant indices are varints, and tuples, structs, and arrays have no length prefix.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CompactEncoding {
+    BincodeV2,
+    Postcard,
+}
+
+type Result<T> = std::result::Result<T, String>;
+
+impl CompactEncoding {
+    /// The name of the encoding, e.g. to report errors.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::BincodeV2 => "bincode v2",
+            Self::Postcard => "postcard",
+        }
+    }
+
+    /// Encode a value of the given format, as recorded by `Tracer::trace_value`.
+    pub fn encode(&self, registry: &Registry, format: &Format, value: &Value) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.encode_format(registry, format, value, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Decode a value of the given format. All the bytes must be consumed.
+    pub fn decode(&self, registry: &Registry, format: &Format, bytes: &[u8]) -> Result<Value> {
+        let mut input = bytes;
+        let value = self.decode_format(registry, format, &mut input)?;
+        if !input.is_empty() {
+            return Err(format!("{} trailing bytes", input.len()));
+        }
+        Ok(value)
+    }
+
+    fn encode_format(
+        &self,
+        registry: &Registry,
+        format: &Format,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        match (format, value) {
+            (Format::TypeName(name), _) => {
+                let container = lookup(registry, name)?;
+                self.encode_container(registry, container, value, out)
+            }
+            (Format::Unit, Value::Unit) => Ok(()),
+            (Format::Bool, Value::Bool(v)) => {
+                out.push(u8::from(*v));
+                Ok(())
+            }
+            (Format::I8, Value::I8(v)) => {
+                out.extend(v.to_le_bytes());
+                Ok(())
+            }
+            (Format::I16, Value::I16(v)) => self.encode_signed(i128::from(*v), out),
+            (Format::I32, Value::I32(v)) => self.encode_signed(i128::from(*v), out),
+            (Format::I64, Value::I64(v)) => self.encode_signed(i128::from(*v), out),
+            (Format::I128, Value::I128(v)) => self.encode_signed(*v, out),
+            (Format::U8, Value::U8(v)) => {
+                out.push(*v);
+                Ok(())
+            }
+            (Format::U16, Value::U16(v)) => self.encode_unsigned(u128::from(*v), out),
+            (Format::U32, Value::U32(v)) => self.encode_unsigned(u128::from(*v), out),
+            (Format::U64, Value::U64(v)) => self.encode_unsigned(u128::from(*v), out),
+            (Format::U128, Value::U128(v)) => self.encode_unsigned(*v, out),
+            (Format::F32, Value::F32(v)) => {
+                out.extend(v.to_le_bytes());
+                Ok(())
+            }
+            (Format::F64, Value::F64(v)) => {
+                out.extend(v.to_le_bytes());
+                Ok(())
+            }
+            (Format::Char, Value::Char(v)) => {
+                let mut buffer = [0; 4];
+                let bytes = v.encode_utf8(&mut buffer).as_bytes();
+                // Bincode writes the UTF-8 bytes of a char alone, Postcard writes a string.
+                if *self == Self::Postcard {
+                    self.encode_unsigned(bytes.len() as u128, out)?;
+                }
+                out.extend(bytes);
+                Ok(())
+            }
+            (Format::Str, Value::Str(v)) => self.encode_bytes(v.as_bytes(), out),
+            (Format::Bytes, Value::Bytes(v)) => self.encode_bytes(v, out),
+            (Format::Option(_), Value::Option(None)) => {
+                out.push(0);
+                Ok(())
+            }
+            (Format::Option(format), Value::Option(Some(value))) => {
+                out.push(1);
+                self.encode_format(registry, format, value, out)
+            }
+            (Format::Seq(format), Value::Seq(values)) => {
+                self.encode_unsigned(values.len() as u128, out)?;
+                for value in values {
+                    self.encode_format(registry, format, value, out)?;
+                }
+                Ok(())
+            }
+            (
+                Format::Map {
+                    key,
+                    value: content,
+                },
+                Value::Seq(values),
+            ) => {
+                if values.len() % 2 != 0 {
+                    return Err(format!("odd number of keys and values in map {value:?}"));
+                }
+                self.encode_unsigned((values.len() / 2) as u128, out)?;
+                for pair in values.chunks(2) {
+                    self.encode_format(registry, key, &pair[0], out)?;
+                    self.encode_format(registry, content, &pair[1], out)?;
+                }
+                Ok(())
+            }
+            (
+                Format::Map {
+                    key,
+                    value: content,
+                },
+                Value::Map(entries),
+            ) => {
+                self.encode_unsigned(entries.len() as u128, out)?;
+                for (k, v) in entries {
+                    self.encode_format(registry, key, k, out)?;
+                    self.encode_format(registry, content, v, out)?;
+                }
+                Ok(())
+            }
+            (Format::Tuple(formats), Value::Seq(values)) => {
+                self.encode_tuple(registry, formats.iter(), values, out)
+            }
+            (Format::TupleArray { content, size }, Value::Seq(values)) => self.encode_tuple(
+                registry,
+                std::iter::repeat(&**content).take(*size),
+                values,
+                out,
+            ),
+            _ => Err(format!(
+                "value {value:?} does not have the format {format:?}"
+            )),
+        }
+    }
+
+    fn encode_container(
+        &self,
+        registry: &Registry,
+        container: &ContainerFormat,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        match (container, value) {
+            (ContainerFormat::UnitStruct, Value::Unit) => Ok(()),
+            (ContainerFormat::NewTypeStruct(format), _) => {
+                self.encode_format(registry, format, value, out)
+            }
+            (ContainerFormat::TupleStruct(formats), Value::Seq(values)) => {
+                self.encode_tuple(registry, formats.iter(), values, out)
+            }
+            (ContainerFormat::Struct(fields), Value::Seq(values)) => {
+                self.encode_tuple(registry, fields.iter().map(|f| &f.value), values, out)
+            }
+            (ContainerFormat::Enum(variants), Value::Variant(index, value)) => {
+                let variant = variants
+                    .get(index)
+                    .ok_or_else(|| format!("unknown variant index {index}"))?;
+                self.encode_unsigned(u128::from(*index), out)?;
+                match (&variant.value, &**value) {
+                    (VariantFormat::Unit, Value::Unit) => Ok(()),
+                    (VariantFormat::NewType(format), value) => {
+                        self.encode_format(registry, format, value, out)
+                    }
+                    (VariantFormat::Tuple(formats), Value::Seq(values)) => {
+                        self.encode_tuple(registry, formats.iter(), values, out)
+                    }
+                    (VariantFormat::Struct(fields), Value::Seq(values)) => {
+                        self.encode_tuple(registry, fields.iter().map(|f| &f.value), values, out)
+                    }
+                    _ => Err(format!(
+                        "value {value:?} does not match the variant {}",
+                        variant.name
+                    )),
+                }
+            }
+            _ => Err(format!(
+                "value {value:?} does not have the format {container:?}"
+            )),
+        }
+    }
+
+    fn encode_tuple<'a, I>(
+        &self,
+        registry: &Registry,
+        formats: I,
+        values: &[Value],
+        out: &mut Vec<u8>,
+    ) -> Result<()>
+    where
+        I: ExactSizeIterator<Item = &'a Format>,
+    {
+        if formats.len() != values.len() {
+            return Err(format!(
+                "expected {} values, found {}",
+                formats.len(),
+                values.len()
+            ));
+        }
+        for (format, value) in formats.zip(values) {
+            self.encode_format(registry, format, value, out)?;
+        }
+        Ok(())
+    }
+
+    fn encode_bytes(&self, bytes: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        self.encode_unsigned(bytes.len() as u128, out)?;
+        out.extend(bytes);
+        Ok(())
+    }
+
+    fn encode_signed(&self, value: i128, out: &mut Vec<u8>) -> Result<()> {
+        let zigzag = ((value << 1) ^ (value >> 127)) as u128;
+        self.encode_unsigned(zigzag, out)
+    }
+
+    fn encode_unsigned(&self, value: u128, out: &mut Vec<u8>) -> Result<()> {
+        match self {
+            Self::BincodeV2 => {
+                if value < 251 {
+                    out.push(value as u8);
+                } else if let Ok(value) = u16::try_from(value) {
+                    out.push(251);
+                    out.extend(value.to_le_bytes());
+                } else if let Ok(value) = u32::try_from(value) {
+                    out.push(252);
+                    out.extend(value.to_le_bytes());
+                } else if let Ok(value) = u64::try_from(value) {
+                    out.push(253);
+                    out.extend(value.to_le_bytes());
+                } else {
+                    out.push(254);
+                    out.extend(value.to_le_bytes());
+                }
+            }
+            Self::Postcard => {
+                let mut value = value;
+                while value >= 0x80 {
+                    out.push((value as u8) | 0x80);
+                    value >>= 7;
+                }
+                out.push(value as u8);
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_format(
+        &self,
+        registry: &Registry,
+        format: &Format,
+        input: &mut &[u8],
+    ) -> Result<Value> {
+        let value = match format {
+            Format::TypeName(name) => {
+                let container = lookup(registry, name)?;
+                return self.decode_container(registry, container, input);
+            }
+            Format::Unit => Value::Unit,
+            Format::Bool => match take(input, 1)?[0] {
+                0 => Value::Bool(false),
+                1 => Value::Bool(true),
+                byte => return Err(format!("invalid boolean {byte}")),
+            },
+            Format::I8 => Value::I8(take(input, 1)?[0] as i8),
+            Format::I16 => Value::I16(narrow(self.decode_signed(input)?)?),
+            Format::I32 => Value::I32(narrow(self.decode_signed(input)?)?),
+            Format::I64 => Value::I64(narrow(self.decode_signed(input)?)?),
+            Format::I128 => Value::I128(self.decode_signed(input)?),
+            Format::U8 => Value::U8(take(input, 1)?[0]),
+            Format::U16 => Value::U16(narrow(self.decode_unsigned(input)?)?),
+            Format::U32 => Value::U32(narrow(self.decode_unsigned(input)?)?),
+            Format::U64 => Value::U64(narrow(self.decode_unsigned(input)?)?),
+            Format::U128 => Value::U128(self.decode_unsigned(input)?),
+            Format::F32 => Value::F32(f32::from_le_bytes(take(input, 4)?.try_into().unwrap())),
+            Format::F64 => Value::F64(f64::from_le_bytes(take(input, 8)?.try_into().unwrap())),
+            Format::Char => {
+                let len = match self {
+                    Self::BincodeV2 => match input.first() {
+                        Some(byte) if *byte < 0x80 => 1,
+                        Some(byte) if *byte >= 0xF0 => 4,
+                        Some(byte) if *byte >= 0xE0 => 3,
+                        _ => 2,
+                    },
+                    Self::Postcard => self.decode_len(input)?,
+                };
+                let bytes = take(input, len)?;
+                let mut chars = std::str::from_utf8(bytes)
+                    .map_err(|e| e.to_string())?
+                    .chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Value::Char(c),
+                    _ => return Err(format!("invalid char {bytes:?}")),
+                }
+            }
+            Format::Str => {
+                let len = self.decode_len(input)?;
+                let bytes = take(input, len)?;
+                let string = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+                Value::Str(string.to_string())
+            }
+            Format::Bytes => {
+                let len = self.decode_len(input)?;
+                Value::Bytes(take(input, len)?.to_vec())
+            }
+            Format::Option(format) => match take(input, 1)?[0] {
+                0 => Value::Option(None),
+                1 => Value::Option(Some(Box::new(self.decode_format(registry, format, input)?))),
+                byte => return Err(format!("invalid option tag {byte}")),
+            },
+            Format::Seq(format) => {
+                let len = self.decode_len(input)?;
+                let values = (0..len)
+                    .map(|_| self.decode_format(registry, format, input))
+                    .collect::<Result<_>>()?;
+                Value::Seq(values)
+            }
+            Format::Map { key, value } => {
+                let len = self.decode_len(input)?;
+                let mut values = Vec::new();
+                for _ in 0..len {
+                    values.push(self.decode_format(registry, key, input)?);
+                    values.push(self.decode_format(registry, value, input)?);
+                }
+                Value::Seq(values)
+            }
+            Format::Tuple(formats) => self.decode_tuple(registry, formats.iter(), input)?,
+            Format::TupleArray { content, size } => {
+                self.decode_tuple(registry, std::iter::repeat(&**content).take(*size), input)?
+            }
+            Format::Variable(_)
+            | Format::TypeParam(_)
+            | Format::Apply { .. }
+            | Format::FixedBytes(_)
+            | Format::Union(_) => return Err(format!("unsupported format {format:?}")),
+        };
+        Ok(value)
+    }
+
+    fn decode_container(
+        &self,
+        registry: &Registry,
+        container: &ContainerFormat,
+        input: &mut &[u8],
+    ) -> Result<Value> {
+        match container {
+            ContainerFormat::UnitStruct => Ok(Value::Unit),
+            ContainerFormat::NewTypeStruct(format) => self.decode_format(registry, format, input),
+            ContainerFormat::TupleStruct(formats) => {
+                self.decode_tuple(registry, formats.iter(), input)
+            }
+            ContainerFormat::Struct(fields) => {
+                self.decode_tuple(registry, fields.iter().map(|f| &f.value), input)
+            }
+            ContainerFormat::Enum(variants) => {
+                let index = narrow(self.decode_unsigned(input)?)?;
+                let Some(Named { value: variant, .. }) = variants.get(&index) else {
+                    return Err(format!("unknown variant index {index}"));
+                };
+                let value = match variant {
+                    VariantFormat::Unit => Value::Unit,
+                    VariantFormat::NewType(format) => {
+                        self.decode_format(registry, format, input)?
+                    }
+                    VariantFormat::Tuple(formats) => {
+                        self.decode_tuple(registry, formats.iter(), input)?
+                    }
+                    VariantFormat::Struct(fields) => {
+                        self.decode_tuple(registry, fields.iter().map(|f| &f.value), input)?
+                    }
+                    VariantFormat::Variable(_) => {
+                        return Err(format!("unsupported format {variant:?}"))
+                    }
+                };
+                Ok(Value::Variant(index, Box::new(value)))
+            }
+            ContainerFormat::Generic { .. } => Err(format!("unsupported format {container:?}")),
+        }
+    }
+
+    fn decode_tuple<'a, I>(
+        &self,
+        registry: &Registry,
+        formats: I,
+        input: &mut &[u8],
+    ) -> Result<Value>
+    where
+        I: Iterator<Item = &'a Format>,
+    {
+        let values = formats
+            .map(|format| self.decode_format(registry, format, input))
+            .collect::<Result<_>>()?;
+        Ok(Value::Seq(values))
+    }
+
+    fn decode_len(&self, input: &mut &[u8]) -> Result<usize> {
+        narrow(self.decode_unsigned(input)?)
+    }
+
+    fn decode_signed(&self, input: &mut &[u8]) -> Result<i128> {
+        let zigzag = self.decode_unsigned(input)?;
+        Ok(((zigzag >> 1) as i128) ^ -((zigzag & 1) as i128))
+    }
+
+    fn decode_unsigned(&self, input: &mut &[u8]) -> Result<u128> {
+        match self {
+            Self::BincodeV2 => {
+                let width = match take(input, 1)?[0] {
+                    byte @ 0..=250 => return Ok(u128::from(byte)),
+                    251 => 2,
+                    252 => 4,
+                    253 => 8,
+                    254 => 16,
+                    byte => return Err(format!("invalid integer prefix {byte}")),
+                };
+                let mut buffer = [0; 16];
+                buffer[..width].copy_from_slice(take(input, width)?);
+                Ok(u128::from_le_bytes(buffer))
+            }
+            Self::Postcard => {
+                let mut value = 0u128;
+                for shift in (0..128).step_by(7) {
+                    let byte = take(input, 1)?[0];
+                    value |= u128::from(byte & 0x7F) << shift;
+                    if byte < 0x80 {
+                        return Ok(value);
+                    }
+                }
+                Err("varint is too long".to_string())
+            }
+        }
+    }
+}
+
+fn lookup<'a>(registry: &'a Registry, name: &str) -> Result<&'a ContainerFormat> {
+    registry
+        .get(name)
+        .ok_or_else(|| format!("missing container {name}"))
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if input.len() < len {
+        return Err("unexpected end of input".to_string());
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn narrow<S, T: TryFrom<S>>(value: S) -> Result<T> {
+    T::try_from(value).map_err(|_| "integer out of range".to_string())
+}
@@ -0,0 +1,383 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    error::{Error, Result},
+    Value,
+};
+
+/// The first bytes of a value encoded by `Value::to_compact_bytes`.
+const VALUE_MAGIC: &[u8; 3] = b"SRV";
+
+/// The maximal nesting of options, variants, sequences, and maps accepted by the decoder, so
+/// that malicious inputs cannot exhaust the stack.
+const MAX_DEPTH: usize = 256;
+
+// Tags of the compact encoding, in the order of the variants of `Value`.
+const UNIT: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const I8: u8 = 3;
+const I16: u8 = 4;
+const I32: u8 = 5;
+const I64: u8 = 6;
+const I128: u8 = 7;
+const U8: u8 = 8;
+const U16: u8 = 9;
+const U32: u8 = 10;
+const U64: u8 = 11;
+const U128: u8 = 12;
+const F32: u8 = 13;
+const F64: u8 = 14;
+const CHAR: u8 = 15;
+const STR: u8 = 16;
+const BYTES: u8 = 17;
+const NONE: u8 = 18;
+const SOME: u8 = 19;
+const VARIANT: u8 = 20;
+const SEQ: u8 = 21;
+const MAP: u8 = 22;
+
+impl Value {
+    /// The version of the encoding of `to_compact_bytes`.
+    pub const COMPACT_VERSION: u8 = 1;
+
+    /// Encode the value in a compact, self-describing binary form, e.g. to embed sample
+    /// corpora in binaries or to transmit them between machines. See also
+    /// `Samples::to_compact_bytes`.
+    ///
+    /// The encoding starts with the header `SRV` followed by the version byte
+    /// [`Value::COMPACT_VERSION`], then the value:
+    /// * Each value is a tag byte followed by its content. Tags number the variants of `Value`
+    ///   from 0 (`Unit`) to 22 (`Map`), except that booleans use two tags, `1` for `false`
+    ///   and `2` for `true`, and options use `18` for `None` and `19` for `Some`.
+    /// * Varints are canonical ULEB128 encodings: the shortest encoding of the number.
+    /// * `I8` and `U8` are one byte, other integers are varints, zigzag-encoded if signed.
+    ///   Floats are the little-endian bytes of their IEEE 754 representation, and `Char` is
+    ///   the varint of its code point.
+    /// * `Str` and `Bytes` are a varint length followed by the bytes (in UTF-8 for strings).
+    /// * `Some` and `Variant` are followed by their content, `Variant` after the varint index.
+    /// * `Seq` and `Map` are a varint count followed by the elements or by the keys and
+    ///   values of the entries, in order.
+    ///
+    /// ```rust
+    /// use serde_reflection::Value;
+    ///
+    /// let value = Value::Variant(1, Box::new(Value::Str("hi".into())));
+    /// let bytes = value.to_compact_bytes();
+    /// assert_eq!(bytes, b"SRV\x01\x14\x01\x10\x02hi");
+    /// assert_eq!(Value::from_compact_bytes(&bytes).unwrap(), value);
+    /// ```
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let mut out = header(VALUE_MAGIC);
+        write_value(self, &mut out);
+        out
+    }
+
+    /// Decode a value encoded by `to_compact_bytes`.
+    /// * Return `Error::InvalidSamples` if the input is not a valid encoding of the version
+    ///   `COMPACT_VERSION`, including when bytes remain after the value.
+    /// * Inputs are untrusted: nesting is limited to 256 levels, and lengths are checked
+    ///   against the size of the input before allocating.
+    pub fn from_compact_bytes(bytes: &[u8]) -> Result<Value> {
+        let mut reader = CompactReader::new(bytes, VALUE_MAGIC)?;
+        let value = reader.value()?;
+        reader.finish()?;
+        Ok(value)
+    }
+}
+
+/// The header of a compact encoding.
+pub(crate) fn header(magic: &[u8; 3]) -> Vec<u8> {
+    let mut out = magic.to_vec();
+    out.push(Value::COMPACT_VERSION);
+    out
+}
+
+pub(crate) fn write_varint(mut value: u128, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_signed(value: i128, out: &mut Vec<u8>) {
+    write_varint(((value << 1) ^ (value >> 127)) as u128, out);
+}
+
+fn write_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    write_varint(bytes.len() as u128, out);
+    out.extend(bytes);
+}
+
+pub(crate) fn write_str(value: &str, out: &mut Vec<u8>) {
+    write_bytes(value.as_bytes(), out);
+}
+
+pub(crate) fn write_value(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Unit => out.push(UNIT),
+        Value::Bool(false) => out.push(FALSE),
+        Value::Bool(true) => out.push(TRUE),
+        Value::I8(x) => out.extend([I8, *x as u8]),
+        Value::I16(x) => {
+            out.push(I16);
+            write_signed((*x).into(), out);
+        }
+        Value::I32(x) => {
+            out.push(I32);
+            write_signed((*x).into(), out);
+        }
+        Value::I64(x) => {
+            out.push(I64);
+            write_signed((*x).into(), out);
+        }
+        Value::I128(x) => {
+            out.push(I128);
+            write_signed(*x, out);
+        }
+        Value::U8(x) => out.extend([U8, *x]),
+        Value::U16(x) => {
+            out.push(U16);
+            write_varint((*x).into(), out);
+        }
+        Value::U32(x) => {
+            out.push(U32);
+            write_varint((*x).into(), out);
+        }
+        Value::U64(x) => {
+            out.push(U64);
+            write_varint((*x).into(), out);
+        }
+        Value::U128(x) => {
+            out.push(U128);
+            write_varint(*x, out);
+        }
+        Value::F32(x) => {
+            out.push(F32);
+            out.extend(x.to_le_bytes());
+        }
+        Value::F64(x) => {
+            out.push(F64);
+            out.extend(x.to_le_bytes());
+        }
+        Value::Char(x) => {
+            out.push(CHAR);
+            write_varint(u32::from(*x).into(), out);
+        }
+        Value::Str(x) => {
+            out.push(STR);
+            write_str(x, out);
+        }
+        Value::Bytes(x) => {
+            out.push(BYTES);
+            write_bytes(x, out);
+        }
+        Value::Option(None) => out.push(NONE),
+        Value::Option(Some(x)) => {
+            out.push(SOME);
+            write_value(x, out);
+        }
+        Value::Variant(index, x) => {
+            out.push(VARIANT);
+            write_varint((*index).into(), out);
+            write_value(x, out);
+        }
+        Value::Seq(values) => {
+            out.push(SEQ);
+            write_varint(values.len() as u128, out);
+            for value in values {
+                write_value(value, out);
+            }
+        }
+        Value::Map(entries) => {
+            out.push(MAP);
+            write_varint(entries.len() as u128, out);
+            for (key, value) in entries {
+                write_value(key, out);
+                write_value(value, out);
+            }
+        }
+    }
+}
+
+/// Decoder of the compact encoding of values.
+pub(crate) struct CompactReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> CompactReader<'a> {
+    /// Check the header of `bytes`, then read from the following byte.
+    pub(crate) fn new(bytes: &'a [u8], magic: &[u8; 3]) -> Result<Self> {
+        let mut reader = Self {
+            bytes,
+            position: 0,
+            depth: 0,
+        };
+        if reader.take(magic.len()).ok() != Some(magic.as_slice()) {
+            return Err(invalid(format!(
+                "missing header {}",
+                String::from_utf8_lossy(magic)
+            )));
+        }
+        let version = reader.byte()?;
+        if version != Value::COMPACT_VERSION {
+            return Err(invalid(format!(
+                "unsupported version {version} (expected {})",
+                Value::COMPACT_VERSION
+            )));
+        }
+        Ok(reader)
+    }
+
+    /// Check that the input was entirely read.
+    pub(crate) fn finish(&self) -> Result<()> {
+        let remaining = self.bytes.len() - self.position;
+        if remaining > 0 {
+            return Err(invalid(format!("{remaining} trailing bytes")));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn error(&self, msg: &str) -> Error {
+        invalid(format!("{msg} at byte {}", self.position))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.bytes.len() - self.position {
+            return Err(self.error("unexpected end of input"));
+        }
+        let result = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(result)
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u128> {
+        let mut value = 0u128;
+        for shift in (0..128).step_by(7) {
+            let byte = self.byte()?;
+            let bits = u128::from(byte & 0x7F);
+            if shift > 0 && byte == 0 {
+                return Err(self.error("non-canonical varint"));
+            }
+            if bits.leading_zeros() < shift {
+                return Err(self.error("varint overflow"));
+            }
+            value |= bits << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(self.error("varint overflow"))
+    }
+
+    fn narrow<T: TryFrom<u128>>(&mut self) -> Result<T> {
+        let value = self.varint()?;
+        T::try_from(value).map_err(|_| self.error("integer out of range"))
+    }
+
+    fn signed<T: TryFrom<i128>>(&mut self) -> Result<T> {
+        let value = self.varint()?;
+        let value = (value >> 1) as i128 ^ -((value & 1) as i128);
+        T::try_from(value).map_err(|_| self.error("integer out of range"))
+    }
+
+    /// Read a length or a count of elements, each taking at least one byte.
+    pub(crate) fn len(&mut self) -> Result<usize> {
+        let len = self.narrow::<usize>()?;
+        if len > self.bytes.len() - self.position {
+            return Err(self.error("length exceeds the input"));
+        }
+        Ok(len)
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.len()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    pub(crate) fn str(&mut self) -> Result<String> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("N bytes were taken"))
+    }
+
+    pub(crate) fn value(&mut self) -> Result<Value> {
+        let tag = self.byte()?;
+        let value = match tag {
+            UNIT => Value::Unit,
+            FALSE => Value::Bool(false),
+            TRUE => Value::Bool(true),
+            I8 => Value::I8(self.byte()? as i8),
+            I16 => Value::I16(self.signed()?),
+            I32 => Value::I32(self.signed()?),
+            I64 => Value::I64(self.signed()?),
+            I128 => Value::I128(self.signed()?),
+            U8 => Value::U8(self.byte()?),
+            U16 => Value::U16(self.narrow()?),
+            U32 => Value::U32(self.narrow()?),
+            U64 => Value::U64(self.narrow()?),
+            U128 => Value::U128(self.varint()?),
+            F32 => Value::F32(f32::from_le_bytes(self.array()?)),
+            F64 => Value::F64(f64::from_le_bytes(self.array()?)),
+            CHAR => {
+                let code = self.narrow::<u32>()?;
+                Value::Char(char::from_u32(code).ok_or_else(|| self.error("invalid char"))?)
+            }
+            STR => Value::Str(self.str()?),
+            BYTES => Value::Bytes(self.bytes()?),
+            NONE => Value::Option(None),
+            SOME => Value::Option(Some(Box::new(self.nested(Self::value)?))),
+            VARIANT => {
+                let index = self.narrow()?;
+                Value::Variant(index, Box::new(self.nested(Self::value)?))
+            }
+            SEQ => {
+                let len = self.len()?;
+                let values = (0..len)
+                    .map(|_| self.nested(Self::value))
+                    .collect::<Result<_>>()?;
+                Value::Seq(values)
+            }
+            MAP => {
+                let len = self.len()?;
+                let entries = (0..len)
+                    .map(|_| Ok((self.nested(Self::value)?, self.nested(Self::value)?)))
+                    .collect::<Result<_>>()?;
+                Value::Map(entries)
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.error(&format!("unknown tag {tag}")));
+            }
+        };
+        Ok(value)
+    }
+
+    fn nested<F>(&mut self, f: F) -> Result<Value>
+    where
+        F: FnOnce(&mut Self) -> Result<Value>,
+    {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("values nested too deeply"));
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+}
+
+fn invalid(msg: String) -> Error {
+    Error::InvalidSamples(format!("compact encoding: {msg}"))
+}
@@ -0,0 +1,584 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Encoding-specific compatibility rules.
+//!
+//! Whether a change of format breaks the exchange of serialized data depends on the
+//! encoding. For instance, widening a field from `u32` to `u64` is harmless in JSON but
+//! changes the layout of Bincode and BCS data. The rules of an encoding are described by a
+//! [`CompatRules`] table, which can be customized.
+//!
+//! ```rust
+//! use serde_reflection::{ContainerFormat, Format, Registry};
+//! use serde_reflection::compat::{CompatRules, NumericType};
+//! use serde_reflection::diff::RegistryDiff;
+//!
+//! let mut old = Registry::new();
+//! old.insert("Foo".into(), ContainerFormat::NewTypeStruct(Box::new(Format::U32)));
+//! let mut new = Registry::new();
+//! new.insert("Foo".into(), ContainerFormat::NewTypeStruct(Box::new(Format::U64)));
+//!
+//! let diff = RegistryDiff::new(&old, &new);
+//! assert!(!diff.is_breaking_with(&CompatRules::json()));
+//! assert!(diff.is_breaking_with(&CompatRules::binary()));
+//!
+//! // Rules can be adjusted for custom encodings.
+//! let rules = CompatRules::binary().allow_numeric_change(NumericType::U32, NumericType::U64);
+//! assert!(!diff.is_breaking_with(&rules));
+//! ```
+//!
+//! The byte-level properties of common binary encodings are described by
+//! [`EncodingProfile`], which also provides their compatibility rules. How the variants of
+//! enums are identified is described by [`TagEncoding`].
+//!
+//! The function [`recommend_version_bump`] translates the changes of a registry into an
+//! increment of its semantic version, e.g. to check the version of a schema in CI.
+
+use crate::{
+    diff::{ChangeKind, RegistryDiff},
+    Format, Registry,
+};
+use std::{collections::BTreeSet, fmt};
+
+/// The integer formats.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum NumericType {
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+}
+
+impl NumericType {
+    /// All the integer formats.
+    pub const ALL: [NumericType; 10] = [
+        NumericType::I8,
+        NumericType::I16,
+        NumericType::I32,
+        NumericType::I64,
+        NumericType::I128,
+        NumericType::U8,
+        NumericType::U16,
+        NumericType::U32,
+        NumericType::U64,
+        NumericType::U128,
+    ];
+
+    /// Obtain the integer type described by a format, if any.
+    pub fn from_format(format: &Format) -> Option<Self> {
+        use NumericType::*;
+        let value = match format {
+            Format::I8 => I8,
+            Format::I16 => I16,
+            Format::I32 => I32,
+            Format::I64 => I64,
+            Format::I128 => I128,
+            Format::U8 => U8,
+            Format::U16 => U16,
+            Format::U32 => U32,
+            Format::U64 => U64,
+            Format::U128 => U128,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// The format of the integer type.
+    pub fn to_format(self) -> Format {
+        use NumericType::*;
+        match self {
+            I8 => Format::I8,
+            I16 => Format::I16,
+            I32 => Format::I32,
+            I64 => Format::I64,
+            I128 => Format::I128,
+            U8 => Format::U8,
+            U16 => Format::U16,
+            U32 => Format::U32,
+            U64 => Format::U64,
+            U128 => Format::U128,
+        }
+    }
+
+    /// The size of the type in bits.
+    pub fn bits(self) -> u32 {
+        use NumericType::*;
+        match self {
+            I8 | U8 => 8,
+            I16 | U16 => 16,
+            I32 | U32 => 32,
+            I64 | U64 => 64,
+            I128 | U128 => 128,
+        }
+    }
+
+    /// Whether the type is signed.
+    pub fn is_signed(self) -> bool {
+        use NumericType::*;
+        matches!(self, I8 | I16 | I32 | I64 | I128)
+    }
+
+    /// Whether every value of the type `other` is also a value of `self`.
+    pub fn contains(self, other: NumericType) -> bool {
+        match (self.is_signed(), other.is_signed()) {
+            (true, true) | (false, false) => self.bits() >= other.bits(),
+            (true, false) => self.bits() > other.bits(),
+            (false, true) => false,
+        }
+    }
+}
+
+/// The compatibility of a change of format for a given encoding.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Compatibility {
+    /// Data serialized with the old format can be read with the new format.
+    Compatible,
+    /// Some data serialized with the old format cannot be read with the new format.
+    Breaking,
+}
+
+/// A table of compatibility rules for a given encoding.
+///
+/// Changes of formats that are not explicitly allowed by the table are considered breaking.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CompatRules {
+    /// Allowed changes of integer types, as pairs (old, new).
+    numeric_changes: BTreeSet<(NumericType, NumericType)>,
+    /// Whether a format `T` can be changed into `Option<T>`.
+    option_wrapping: bool,
+    /// Whether the fields of a struct can be reordered.
+    field_reordering: bool,
+    /// Whether readers can skip unknown fields.
+    unknown_fields: bool,
+}
+
+impl CompatRules {
+    /// Rules for binary encodings with fixed-size integers such as Bincode and BCS: every
+    /// change of integer type is breaking, and so are wrapping a format into an option and
+    /// reordering fields.
+    pub fn binary() -> Self {
+        Self::default()
+    }
+
+    /// Rules for JSON: an integer type can be changed into any type that contains all
+    /// its values (e.g. `u32` into `u64` or `i64`). Changing a signed type into an
+    /// unsigned type is always breaking. A format `T` can be changed into `Option<T>`,
+    /// but not the other way around. Fields can be reordered, and unknown fields can be
+    /// skipped.
+    pub fn json() -> Self {
+        let mut rules = Self {
+            option_wrapping: true,
+            field_reordering: true,
+            unknown_fields: true,
+            ..Self::default()
+        };
+        for old in NumericType::ALL {
+            for new in NumericType::ALL {
+                if old != new && new.contains(old) {
+                    rules.numeric_changes.insert((old, new));
+                }
+            }
+        }
+        rules
+    }
+
+    /// Allow the integer type `old` to be changed into `new`.
+    pub fn allow_numeric_change(mut self, old: NumericType, new: NumericType) -> Self {
+        self.numeric_changes.insert((old, new));
+        self
+    }
+
+    /// Forbid the integer type `old` to be changed into `new`.
+    pub fn forbid_numeric_change(mut self, old: NumericType, new: NumericType) -> Self {
+        self.numeric_changes.remove(&(old, new));
+        self
+    }
+
+    /// Allow or forbid changing a format `T` into `Option<T>`.
+    pub fn allow_option_wrapping(mut self, value: bool) -> Self {
+        self.option_wrapping = value;
+        self
+    }
+
+    /// Allow or forbid reordering the fields of structs.
+    pub fn allow_field_reordering(mut self, value: bool) -> Self {
+        self.field_reordering = value;
+        self
+    }
+
+    /// Whether the fields of structs can be reordered.
+    pub fn allows_field_reordering(&self) -> bool {
+        self.field_reordering
+    }
+
+    /// Allow or forbid readers to skip the fields that they do not know, as with
+    /// self-describing encodings that name the fields of structs.
+    pub fn allow_unknown_fields(mut self, value: bool) -> Self {
+        self.unknown_fields = value;
+        self
+    }
+
+    /// Whether readers can skip the fields that they do not know.
+    pub fn allows_unknown_fields(&self) -> bool {
+        self.unknown_fields
+    }
+
+    /// Classify a change of integer type.
+    pub fn numeric_change(&self, old: NumericType, new: NumericType) -> Compatibility {
+        if old == new || self.numeric_changes.contains(&(old, new)) {
+            Compatibility::Compatible
+        } else {
+            Compatibility::Breaking
+        }
+    }
+
+    /// Classify a change of format. Nested formats are compared recursively.
+    pub fn format_change(&self, old: &Format, new: &Format) -> Compatibility {
+        use Format::*;
+        if let (Some(old), Some(new)) =
+            (NumericType::from_format(old), NumericType::from_format(new))
+        {
+            return self.numeric_change(old, new);
+        }
+        match (old, new) {
+            (Option(old), Option(new)) | (Seq(old), Seq(new)) => self.format_change(old, new),
+            (
+                Map {
+                    key: old_key,
+                    value: old_value,
+                },
+                Map {
+                    key: new_key,
+                    value: new_value,
+                },
+            ) => self.all_compatible([(&**old_key, &**new_key), (&**old_value, &**new_value)]),
+            (Tuple(old), Tuple(new)) if old.len() == new.len() => {
+                self.all_compatible(old.iter().zip(new))
+            }
+            (
+                TupleArray {
+                    content: old,
+                    size: old_size,
+                },
+                TupleArray {
+                    content: new,
+                    size: new_size,
+                },
+            ) if old_size == new_size => self.format_change(old, new),
+            (_, Option(new)) if self.option_wrapping => self.format_change(old, new),
+            // Fixed-size bytes are encoded like bytes of any size.
+            (FixedBytes(_), Bytes) => Compatibility::Compatible,
+            _ if old == new => Compatibility::Compatible,
+            _ => Compatibility::Breaking,
+        }
+    }
+
+    fn all_compatible<'a>(
+        &self,
+        pairs: impl IntoIterator<Item = (&'a Format, &'a Format)>,
+    ) -> Compatibility {
+        if pairs
+            .into_iter()
+            .all(|(old, new)| self.format_change(old, new) == Compatibility::Compatible)
+        {
+            Compatibility::Compatible
+        } else {
+            Compatibility::Breaking
+        }
+    }
+}
+
+/// The encoding of an integer type.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum IntegerEncoding {
+    /// Fixed-size little-endian encoding.
+    FixedLittleEndian,
+    /// Variable-length LEB128 encoding.
+    Varint,
+    /// Variable-length LEB128 encoding of the ZigZag transform of a signed integer.
+    ZigZagVarint,
+}
+
+/// The encoding of a length prefix or an enum tag.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PrefixEncoding {
+    /// Fixed-size little-endian `u32`.
+    U32,
+    /// Fixed-size little-endian `u64`.
+    U64,
+    /// Variable-length ULEB128 encoding.
+    Uleb128,
+}
+
+impl PrefixEncoding {
+    /// The number of bytes used to encode `value`.
+    pub fn encoded_size(self, value: u64) -> usize {
+        match self {
+            PrefixEncoding::U32 => 4,
+            PrefixEncoding::U64 => 8,
+            PrefixEncoding::Uleb128 => {
+                let bits = u64::BITS - value.leading_zeros();
+                (std::cmp::max(bits, 1) as usize + 6) / 7
+            }
+        }
+    }
+}
+
+/// How an encoding identifies the variant of an enum value.
+///
+/// Registries record both the index and the name of each variant (see
+/// `ContainerFormat::Enum`). The binary encodings of [`EncodingProfile`] write the index,
+/// while self-describing encodings such as JSON write the name. Encodings that write names
+/// in binary form are described by `TagEncoding::Name`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TagEncoding {
+    /// The index of the variant, encoded as a prefix.
+    Index(PrefixEncoding),
+    /// The name of the variant, encoded as a string with a length prefix.
+    Name(PrefixEncoding),
+}
+
+impl TagEncoding {
+    /// The number of bytes used to encode the tag of the variant `name` with index `index`.
+    pub fn encoded_size(self, index: u32, name: &str) -> usize {
+        match self {
+            TagEncoding::Index(encoding) => encoding.encoded_size(index.into()),
+            TagEncoding::Name(encoding) => encoding.encoded_size(name.len() as u64) + name.len(),
+        }
+    }
+}
+
+/// Byte-level descriptions of common binary encodings.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum EncodingProfile {
+    /// Bincode 1.x with its legacy configuration (e.g. `bincode::serialize`).
+    Bincode1,
+    /// [BCS](https://github.com/diem/bcs).
+    Bcs,
+    /// [Postcard](https://github.com/jamesmunns/postcard).
+    Postcard,
+}
+
+impl fmt::Display for EncodingProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EncodingProfile::Bincode1 => "Bincode 1",
+            EncodingProfile::Bcs => "BCS",
+            EncodingProfile::Postcard => "Postcard",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl EncodingProfile {
+    /// How the integer type `value` is encoded.
+    pub fn integer_encoding(self, value: NumericType) -> IntegerEncoding {
+        match self {
+            EncodingProfile::Bincode1 | EncodingProfile::Bcs => IntegerEncoding::FixedLittleEndian,
+            EncodingProfile::Postcard => match value {
+                NumericType::I8 | NumericType::U8 => IntegerEncoding::FixedLittleEndian,
+                _ if value.is_signed() => IntegerEncoding::ZigZagVarint,
+                _ => IntegerEncoding::Varint,
+            },
+        }
+    }
+
+    /// How the lengths of sequences, maps, strings, and bytes are encoded.
+    pub fn length_encoding(self) -> PrefixEncoding {
+        match self {
+            EncodingProfile::Bincode1 => PrefixEncoding::U64,
+            EncodingProfile::Bcs | EncodingProfile::Postcard => PrefixEncoding::Uleb128,
+        }
+    }
+
+    /// How the indices of enum variants are encoded.
+    pub fn variant_tag_encoding(self) -> PrefixEncoding {
+        match self {
+            EncodingProfile::Bincode1 => PrefixEncoding::U32,
+            EncodingProfile::Bcs | EncodingProfile::Postcard => PrefixEncoding::Uleb128,
+        }
+    }
+
+    /// How the variants of enum values are identified, that is, by their indices
+    /// encoded with `variant_tag_encoding`.
+    pub fn tag_encoding(self) -> TagEncoding {
+        TagEncoding::Index(self.variant_tag_encoding())
+    }
+
+    /// The compatibility rules of the encoding. With variable-length encodings, an integer
+    /// type can be widened into a type with the same signedness and the same encoding.
+    pub fn compat_rules(self) -> CompatRules {
+        let mut rules = CompatRules::binary();
+        for old in NumericType::ALL {
+            for new in NumericType::ALL {
+                let encoding = self.integer_encoding(old);
+                if old != new
+                    && encoding != IntegerEncoding::FixedLittleEndian
+                    && encoding == self.integer_encoding(new)
+                    && new.contains(old)
+                {
+                    rules = rules.allow_numeric_change(old, new);
+                }
+            }
+        }
+        rules
+    }
+}
+
+/// A recommended increment of the version of a schema, following semantic versioning.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum SemverBump {
+    /// The schema did not change.
+    None,
+    /// Increment the patch version, e.g. `1.4.2` to `1.4.3`.
+    Patch,
+    /// Increment the minor version, e.g. `1.4.2` to `1.5.0`.
+    Minor,
+    /// Increment the major version, e.g. `1.4.2` to `2.0.0`.
+    Major,
+}
+
+impl SemverBump {
+    /// The increment from the version `old` to the version `new`, given as
+    /// `major.minor.patch`, or `None` if a version cannot be parsed or if `new` is not
+    /// greater than or equal to `old`. Pre-release and build suffixes are ignored.
+    ///
+    /// ```rust
+    /// use serde_reflection::compat::SemverBump;
+    ///
+    /// assert_eq!(SemverBump::between("1.4.2", "2.0.0"), Some(SemverBump::Major));
+    /// assert_eq!(SemverBump::between("1.4.2", "1.4.2"), Some(SemverBump::None));
+    /// assert_eq!(SemverBump::between("1.4.2", "1.3.0"), None);
+    /// ```
+    pub fn between(old: &str, new: &str) -> Option<SemverBump> {
+        let old = parse_version(old)?;
+        let new = parse_version(new)?;
+        if new < old {
+            return None;
+        }
+        let bump = if new.0 != old.0 {
+            SemverBump::Major
+        } else if new.1 != old.1 {
+            SemverBump::Minor
+        } else if new.2 != old.2 {
+            SemverBump::Patch
+        } else {
+            SemverBump::None
+        };
+        Some(bump)
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    let mut numbers = version.split('.').map(|n| n.parse::<u64>().ok());
+    let version = (numbers.next()??, numbers.next()??, numbers.next()??);
+    numbers.next().is_none().then_some(version)
+}
+
+/// How the changes of a schema translate into increments of its version. See
+/// [`recommend_version_bump`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SemverPolicy {
+    breaking: SemverBump,
+    additions: SemverBump,
+    compatible_changes: SemverBump,
+}
+
+impl Default for SemverPolicy {
+    /// Breaking changes require a major version, additions of containers and variants a
+    /// minor version, and other compatible changes (e.g. renaming a variant) a patch version.
+    fn default() -> Self {
+        Self {
+            breaking: SemverBump::Major,
+            additions: SemverBump::Minor,
+            compatible_changes: SemverBump::Patch,
+        }
+    }
+}
+
+impl SemverPolicy {
+    /// The policy of versions `0.x.y`, following Cargo: breaking changes require a minor
+    /// version and other changes a patch version.
+    pub fn initial_development() -> Self {
+        Self {
+            breaking: SemverBump::Minor,
+            additions: SemverBump::Patch,
+            compatible_changes: SemverBump::Patch,
+        }
+    }
+
+    /// Set the increment required by breaking changes.
+    pub fn breaking(mut self, value: SemverBump) -> Self {
+        self.breaking = value;
+        self
+    }
+
+    /// Set the increment required by the compatible additions of containers and variants,
+    /// and by the removal of the condition of a container (see [`Conditions`](crate::Conditions)).
+    pub fn additions(mut self, value: SemverBump) -> Self {
+        self.additions = value;
+        self
+    }
+
+    /// Set the increment required by the other compatible changes.
+    pub fn compatible_changes(mut self, value: SemverBump) -> Self {
+        self.compatible_changes = value;
+        self
+    }
+
+    /// The increment required by the changes of `diff`, classified with `rules`.
+    pub fn recommend(&self, diff: &RegistryDiff, rules: &CompatRules) -> SemverBump {
+        diff.changes
+            .iter()
+            .map(|change| {
+                if change.is_breaking_with(rules) {
+                    return self.breaking;
+                }
+                match &change.kind {
+                    ChangeKind::ContainerAdded
+                    | ChangeKind::VariantAdded { .. }
+                    | ChangeKind::ConditionChanged { new: None, .. } => self.additions,
+                    _ => self.compatible_changes,
+                }
+            })
+            .max()
+            .unwrap_or(SemverBump::None)
+    }
+}
+
+/// Recommend an increment of the version of a schema changing from `old` to `new`, for
+/// data encoded with `profile`, under the default [`SemverPolicy`].
+///
+/// ```rust
+/// use serde_reflection::{ContainerFormat, Format, Registry};
+/// use serde_reflection::compat::{recommend_version_bump, EncodingProfile, SemverBump};
+///
+/// let mut old = Registry::new();
+/// old.insert("Foo".into(), ContainerFormat::NewTypeStruct(Box::new(Format::U32)));
+/// let mut new = old.clone();
+/// new.insert("Bar".into(), ContainerFormat::UnitStruct);
+/// assert_eq!(recommend_version_bump(&old, &new, EncodingProfile::Bcs), SemverBump::Minor);
+///
+/// new.insert("Foo".into(), ContainerFormat::NewTypeStruct(Box::new(Format::U64)));
+/// let bump = recommend_version_bump(&old, &new, EncodingProfile::Bcs);
+/// assert_eq!(bump, SemverBump::Major);
+///
+/// // In CI: reject breaking changes without a major version.
+/// assert!(SemverBump::between("1.4.0", "1.5.0").unwrap() < bump);
+/// ```
+pub fn recommend_version_bump(
+    old: &Registry,
+    new: &Registry,
+    profile: EncodingProfile,
+) -> SemverBump {
+    let diff = RegistryDiff::new(old, new);
+    SemverPolicy::default().recommend(&diff, &profile.compat_rules())
+}
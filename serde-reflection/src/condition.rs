@@ -0,0 +1,97 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::Registry;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A sidecar record of the build configurations in which containers exist.
+///
+/// Conditions are `cfg` predicates such as `feature = "extra"`, attached to container names.
+/// They are not interpreted by this crate. Containers without a condition exist in every
+/// configuration. This makes it possible to keep a single registry for all the
+/// configurations of a crate:
+/// * registries traced with different features are combined with
+///   [`merge_conditional`](crate::registry::merge_conditional),
+/// * the containers of a given configuration are extracted with `select`, e.g. before
+///   calling an exporter,
+/// * changes of conditions are reported by
+///   [`RegistryDiff::with_conditions`](crate::diff::RegistryDiff::with_conditions).
+///
+/// ```rust
+/// use serde_reflection::{Conditions, ContainerFormat, Registry};
+///
+/// let mut registry = Registry::new();
+/// registry.insert("Foo".into(), ContainerFormat::UnitStruct);
+/// registry.insert("Bar".into(), ContainerFormat::UnitStruct);
+/// let mut conditions = Conditions::new();
+/// conditions.set_condition("Foo", "feature = \"extra\"");
+///
+/// let selected = conditions.select(&registry, |condition| condition == "feature = \"extra\"");
+/// assert_eq!(selected.len(), 2);
+/// let selected = conditions.select(&registry, |_| false);
+/// assert_eq!(selected.keys().collect::<Vec<_>>(), vec!["Bar"]);
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+pub struct Conditions(BTreeMap<String, String>);
+
+impl Conditions {
+    /// Create an empty set of conditions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare that the container `container` only exists when `condition` holds. Replace
+    /// the previous condition of the container, if any.
+    pub fn set_condition(&mut self, container: &str, condition: &str) {
+        self.0.insert(container.to_string(), condition.to_string());
+    }
+
+    /// Declare that the container `container` also exists when `condition` holds. If the
+    /// container already has a different condition, the two are combined with `any(..)`.
+    pub fn add_condition(&mut self, container: &str, condition: &str) {
+        let combined = match self.0.get(container) {
+            None => condition.to_string(),
+            Some(existing) if existing == condition => return,
+            Some(existing) => match existing
+                .strip_prefix("any(")
+                .and_then(|rest| rest.strip_suffix(')'))
+            {
+                Some(alternatives) => format!("any({alternatives}, {condition})"),
+                None => format!("any({existing}, {condition})"),
+            },
+        };
+        self.0.insert(container.to_string(), combined);
+    }
+
+    /// Declare that the container `container` exists in every configuration. Return its
+    /// previous condition, if any.
+    pub fn remove_condition(&mut self, container: &str) -> Option<String> {
+        self.0.remove(container)
+    }
+
+    /// The condition of the container `container`, if any.
+    pub fn condition(&self, container: &str) -> Option<&str> {
+        self.0.get(container).map(String::as_str)
+    }
+
+    /// Iterate over the containers that have a condition, together with the condition.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, condition)| (name.as_str(), condition.as_str()))
+    }
+
+    /// Return the containers of `registry` that exist in a configuration, where
+    /// `is_enabled` tells whether a condition holds in this configuration.
+    pub fn select<F>(&self, registry: &Registry, mut is_enabled: F) -> Registry
+    where
+        F: FnMut(&str) -> bool,
+    {
+        registry
+            .iter()
+            .filter(|(name, _)| self.condition(name).map_or(true, &mut is_enabled))
+            .map(|(name, format)| (name.clone(), format.clone()))
+            .collect()
+    }
+}
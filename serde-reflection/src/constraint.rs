@@ -0,0 +1,287 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{registry, Format, FormatHolder, Registry, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Refinements of the format of a field, for the validation of values beyond their
+/// structure. Every bound is inclusive and optional.
+///
+/// ```rust
+/// use serde_reflection::{Constraint, Value};
+///
+/// let constraint = Constraint::new().min(1).max(10);
+/// assert!(constraint.is_satisfied_by(&Value::U32(10)));
+/// assert!(!constraint.is_satisfied_by(&Value::U32(0)));
+///
+/// let constraint = Constraint::new().max_length(3);
+/// assert!(!constraint.is_satisfied_by(&Value::Str("abcd".into())));
+///
+/// let constraint = Constraint::new().finite(true);
+/// assert!(!constraint.is_satisfied_by(&Value::F64(f64::NAN)));
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Constraint {
+    /// The minimal value of an integer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<i128>,
+    /// The maximal value of an integer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<i128>,
+    /// The minimal length of a string (in bytes), of bytes, or of a sequence (in elements).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    /// The maximal length of a string (in bytes), of bytes, or of a sequence (in elements).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    /// A regular expression that strings must match. It is recorded for the consumers of
+    /// the registry but not checked by this crate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Whether floating-point numbers must be finite (`Some(true)`) or may be NaN or
+    /// infinite (`Some(false)`). Languages disagree on the encoding and the comparison of
+    /// non-finite numbers, so consumers may need to handle them specially.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finite: Option<bool>,
+}
+
+/// How `TracerConfig::float_policy` treats the floating-point members of the traced
+/// containers whose constraint does not say whether they must be finite.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum FloatPolicy {
+    /// Record nothing.
+    #[default]
+    Allow,
+    /// Record that such members may be NaN or infinite, with `finite: Some(false)`, in the
+    /// constraints returned by `Tracer::constraints`.
+    Flag,
+    /// Fail with `Error::UnspecifiedFloat` when the registry is returned, so that every
+    /// floating-point member is declared with `Constraint::finite`.
+    Reject,
+}
+
+impl Constraint {
+    /// A constraint without any bound.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the minimal value of integers.
+    pub fn min(mut self, value: i128) -> Self {
+        self.min = Some(value);
+        self
+    }
+
+    /// Set the maximal value of integers.
+    pub fn max(mut self, value: i128) -> Self {
+        self.max = Some(value);
+        self
+    }
+
+    /// Set the minimal length of strings, bytes, and sequences.
+    pub fn min_length(mut self, value: usize) -> Self {
+        self.min_length = Some(value);
+        self
+    }
+
+    /// Set the maximal length of strings, bytes, and sequences.
+    pub fn max_length(mut self, value: usize) -> Self {
+        self.max_length = Some(value);
+        self
+    }
+
+    /// Set the regular expression that strings must match.
+    pub fn pattern(mut self, value: &str) -> Self {
+        self.pattern = Some(value.to_string());
+        self
+    }
+
+    /// Set whether floating-point numbers must be finite.
+    pub fn finite(mut self, value: bool) -> Self {
+        self.finite = Some(value);
+        self
+    }
+
+    /// Clamp an integer into the bounds `min` and `max`. Integers that cannot be represented
+    /// once clamped are returned unchanged.
+    #[cfg(feature = "trace")]
+    pub(crate) fn clamp<T>(&self, value: T) -> T
+    where
+        T: Copy + TryFrom<i128>,
+        i128: TryFrom<T>,
+    {
+        let Ok(wide) = i128::try_from(value) else {
+            return value;
+        };
+        let clamped = wide
+            .max(self.min.unwrap_or(i128::MIN))
+            .min(self.max.unwrap_or(i128::MAX));
+        T::try_from(clamped).unwrap_or(value)
+    }
+
+    /// Whether a value satisfies the bounds that apply to it. Integers are checked against
+    /// `min` and `max`, strings, bytes, and sequences against `min_length` and `max_length`,
+    /// floating-point numbers against `finite`. Options are checked by their content, if any.
+    /// The `pattern` is not checked.
+    pub fn is_satisfied_by(&self, value: &Value) -> bool {
+        let float = match value {
+            Value::F32(x) => Some(x.is_finite()),
+            Value::F64(x) => Some(x.is_finite()),
+            _ => None,
+        };
+        if let Some(is_finite) = float {
+            return is_finite || self.finite != Some(true);
+        }
+        let integer = match value {
+            Value::I8(x) => Some(i128::from(*x)),
+            Value::I16(x) => Some(i128::from(*x)),
+            Value::I32(x) => Some(i128::from(*x)),
+            Value::I64(x) => Some(i128::from(*x)),
+            Value::I128(x) => Some(*x),
+            Value::U8(x) => Some(i128::from(*x)),
+            Value::U16(x) => Some(i128::from(*x)),
+            Value::U32(x) => Some(i128::from(*x)),
+            Value::U64(x) => Some(i128::from(*x)),
+            Value::U128(x) => Some(i128::try_from(*x).unwrap_or(i128::MAX)),
+            _ => None,
+        };
+        if let Some(integer) = integer {
+            return self.min.map_or(true, |min| integer >= min)
+                && self.max.map_or(true, |max| integer <= max);
+        }
+        let length = match value {
+            Value::Str(x) => x.len(),
+            Value::Bytes(x) => x.len(),
+            Value::Seq(x) => x.len(),
+            Value::Map(x) => x.len(),
+            Value::Option(Some(x)) => return self.is_satisfied_by(x),
+            _ => return true,
+        };
+        self.min_length.map_or(true, |min| length >= min)
+            && self.max_length.map_or(true, |max| length <= max)
+    }
+}
+
+/// A sidecar record of the constraints of fields, variants, and containers.
+///
+/// Members are identified by their qualified names, as in [`Deprecations`](crate::Deprecations).
+/// Tuple elements are named after their positions, e.g. `["Foo", "0"]`. Constraints can be
+/// declared with `TracerConfig::constrain` and obtained with `Tracer::constraints`, or built
+/// directly.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+#[serde(from = "Vec<ConstrainedMember>", into = "Vec<ConstrainedMember>")]
+pub struct Constraints(BTreeMap<Vec<String>, Constraint>);
+
+/// Serialized form of an entry of `Constraints`, since qualified names cannot be used as
+/// keys of JSON objects.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConstrainedMember {
+    path: Vec<String>,
+    constraint: Constraint,
+}
+
+impl From<Vec<ConstrainedMember>> for Constraints {
+    fn from(members: Vec<ConstrainedMember>) -> Self {
+        Self(
+            members
+                .into_iter()
+                .map(|member| (member.path, member.constraint))
+                .collect(),
+        )
+    }
+}
+
+impl From<Constraints> for Vec<ConstrainedMember> {
+    fn from(constraints: Constraints) -> Self {
+        constraints
+            .0
+            .into_iter()
+            .map(|(path, constraint)| ConstrainedMember { path, constraint })
+            .collect()
+    }
+}
+
+impl Constraints {
+    /// Create an empty set of constraints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach a constraint to the member with the given qualified name, replacing the
+    /// previous one, if any.
+    pub fn constrain<I, S>(&mut self, path: I, constraint: Constraint)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.0
+            .insert(path.into_iter().map(Into::into).collect(), constraint);
+    }
+
+    /// Attach a constraint to the field `field` of the container `container`.
+    pub fn constrain_field(&mut self, container: &str, field: &str, constraint: Constraint) {
+        self.constrain([container, field], constraint);
+    }
+
+    /// The constraint of the member with the given qualified name, if any.
+    pub fn get<S: AsRef<str>>(&self, path: &[S]) -> Option<&Constraint> {
+        let path: Vec<String> = path.iter().map(|s| s.as_ref().to_string()).collect();
+        self.0.get(&path)
+    }
+
+    /// Whether no constraint was declared.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The qualified names of the members of the registry holding floating-point numbers
+    /// (possibly within options, sequences, etc.) whose constraint does not set `finite`.
+    pub fn unspecified_floats(&self, registry: &Registry) -> Vec<Vec<String>> {
+        let mut paths = Vec::new();
+        for (name, format) in registry {
+            for (field_path, member) in registry::members(format) {
+                let mut path = vec![name.clone()];
+                path.extend(field_path);
+                if is_float(member)
+                    && self
+                        .get(&path)
+                        .map_or(true, |constraint| constraint.finite.is_none())
+                {
+                    paths.push(path);
+                }
+            }
+        }
+        paths
+    }
+
+    /// Record that the members returned by `unspecified_floats` may be NaN or infinite,
+    /// with `finite: Some(false)`. Return their qualified names.
+    pub fn flag_floats(&mut self, registry: &Registry) -> Vec<Vec<String>> {
+        let paths = self.unspecified_floats(registry);
+        for path in &paths {
+            self.0.entry(path.clone()).or_default().finite = Some(false);
+        }
+        paths
+    }
+
+    /// Iterate over the qualified names of the constrained members and their constraints.
+    pub fn iter(&self) -> impl Iterator<Item = (&[String], &Constraint)> {
+        self.0
+            .iter()
+            .map(|(path, constraint)| (path.as_slice(), constraint))
+    }
+}
+
+fn is_float(format: &Format) -> bool {
+    let mut found = false;
+    format
+        .visit(&mut |format| {
+            found |= matches!(format, Format::F32 | Format::F64);
+            Ok(())
+        })
+        .unwrap_or(());
+    found
+}
@@ -2,10 +2,16 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    attributes,
     error::{Error, Result},
-    format::{ContainerFormat, ContainerFormatEntry, Format, FormatHolder, Named, VariantFormat},
-    trace::{IncompleteEnumReason, Samples, Tracer, VariantId},
-    value::IntoSeqDeserializer,
+    events::TraceEvent,
+    format::{
+        contains_union, unite_variants, ContainerFormat, ContainerFormatEntry, Format,
+        FormatHolder, Named, VariantFormat,
+    },
+    generic::{type_param_index, TYPE_PARAM_NAMES},
+    trace::{IncompleteEnumReason, Samples, Tracer, VariantId, VariantOrder},
+    value::{IntoSeqDeserializer, Value},
 };
 use erased_discriminant::Discriminant;
 use serde::de::{
@@ -15,6 +21,64 @@ use serde::de::{
 };
 use std::collections::btree_map::{BTreeMap, Entry};
 
+/// Unify the format `value` traced for a variant of the enum `enum_name` into the `existing`
+/// one. Conflicting integer and floating-point formats are widened if `widen` is set.
+fn unify_variant(
+    enum_name: &str,
+    existing: &mut Named<VariantFormat>,
+    value: VariantFormat,
+    widen: bool,
+) -> Result<()> {
+    // The copies are reduced before unifying, since a failed unification may leave the
+    // variables that they share with `existing` and `value` empty.
+    let copies = widen.then(|| {
+        let (mut previous, mut copy) = (existing.value.clone(), value.clone());
+        previous.reduce();
+        copy.reduce();
+        (previous, copy)
+    });
+    let (first, second) = match existing.value.unify(value) {
+        Err(Error::Incompatible(first, second)) => (first, second),
+        result => return result,
+    };
+    if let Some((previous, copy)) = copies {
+        if let Some(widened) =
+            unite_variants(&previous, &copy).filter(|widened| !contains_union(widened))
+        {
+            existing.value = widened;
+            return Ok(());
+        }
+    }
+    Err(Error::ConflictingFormats {
+        container: Some(enum_name.to_string()),
+        field_path: vec![existing.name.clone()],
+        first,
+        second,
+    })
+}
+
+/// Report the failure to deserialize a recorded sample.
+fn sample_error(name: &'static str, format: Option<&ContainerFormat>, err: Error) -> Error {
+    match (err, format) {
+        (Error::DeserializationError(msg), Some(format)) => {
+            let mut format = format.clone();
+            format.reduce();
+            Error::UnexpectedDeserializationFormat(name, format, msg)
+        }
+        // The sample was not recorded by this tracer (e.g. it was loaded from a file).
+        (Error::DeserializationError(msg), None) => Error::Custom(format!(
+            "In container {name}, recorded value failed to deserialize into {msg}"
+        )),
+        (err, _) => err,
+    }
+}
+
+/// Whether a variant recorded in the registry has a format without unknown parts.
+fn is_complete(format: &VariantFormat) -> bool {
+    let mut format = format.clone();
+    format.visit_mut(&mut |_| Ok(())).is_ok()
+}
+
 /// Deserialize a single value.
 /// * The lifetime 'a is set by the deserialization call site and the
 ///   `&'a mut` references used to return tracing results.
@@ -35,135 +99,325 @@ impl<'de, 'a> Deserializer<'de, 'a> {
             format,
         }
     }
+
+    /// Answer the visitor according to a known format.
+    fn deserialize_format<V>(self, format: Format, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        use de::Deserializer as _;
+        match format {
+            Format::Variable(_) => unreachable!(),
+            Format::TypeParam(index) => {
+                self.deserialize_unit_struct(TYPE_PARAM_NAMES[index], visitor)
+            }
+            // Type arguments are only introduced once tracing is finished.
+            Format::Apply { .. } => Err(Error::NotSupported("type arguments while tracing")),
+            Format::TypeName(name) => match self.tracer.sample_value(self.samples, &name)? {
+                Some(sample) => {
+                    self.tracer
+                        .record_event(|| TraceEvent::SampleConsulted { container: name });
+                    de::Deserializer::deserialize_any(sample.into_deserializer(), visitor)
+                }
+                None => Err(Error::Custom(format!(
+                    "Missing sample value for container {name} in deserialize_any"
+                ))),
+            },
+            Format::Unit => self.deserialize_unit(visitor),
+            Format::Bool => self.deserialize_bool(visitor),
+            Format::I8 => self.deserialize_i8(visitor),
+            Format::I16 => self.deserialize_i16(visitor),
+            Format::I32 => self.deserialize_i32(visitor),
+            Format::I64 => self.deserialize_i64(visitor),
+            Format::I128 => self.deserialize_i128(visitor),
+            Format::U8 => self.deserialize_u8(visitor),
+            Format::U16 => self.deserialize_u16(visitor),
+            Format::U32 => self.deserialize_u32(visitor),
+            Format::U64 => self.deserialize_u64(visitor),
+            Format::U128 => self.deserialize_u128(visitor),
+            Format::F32 => self.deserialize_f32(visitor),
+            Format::F64 => self.deserialize_f64(visitor),
+            Format::Char => self.deserialize_char(visitor),
+            Format::Str => self.deserialize_string(visitor),
+            Format::Bytes | Format::FixedBytes(_) => self.deserialize_byte_buf(visitor),
+            Format::Option(_) => self.deserialize_option(visitor),
+            Format::Seq(_) => self.deserialize_seq(visitor),
+            Format::Map { .. } => self.deserialize_map(visitor),
+            Format::Tuple(formats) => self.deserialize_tuple(formats.len(), visitor),
+            Format::TupleArray { size, .. } => self.deserialize_tuple(size, visitor),
+            // Follow the first alternative recorded for heterogeneous map values.
+            Format::Union(mut formats) => {
+                let mut format = formats.swap_remove(0);
+                let deserializer = Deserializer::new(self.tracer, self.samples, &mut format);
+                de::Deserializer::deserialize_any(deserializer, visitor)
+            }
+        }
+    }
+
+    /// Answer a method that cannot be traced with the format supplied by the fallback
+    /// handler of the configuration, if any, or else fail with `error`.
+    fn fallback<V>(self, method: &'static str, error: Error, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let Some(format) = self.tracer.fallback_format(method) else {
+            return Err(error);
+        };
+        self.tracer.record_event(|| TraceEvent::FallbackUsed {
+            method,
+            format: format.clone(),
+        });
+        self.format.unify(format.clone())?;
+        self.deserialize_format(format, visitor)
+    }
+
+    /// Answer the visitor for a container given to `TracerConfig::ignore_container`, which
+    /// is recorded as a placeholder. The value is obtained with `replay` from a sample if
+    /// any, or else with `trace` from a separate tracer whose results are discarded.
+    fn deserialize_ignored<V, R, T>(
+        self,
+        name: &'static str,
+        visitor: V,
+        replay: R,
+        trace: T,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+        R: FnOnce(&'de Value, V) -> Result<V::Value>,
+        T: FnOnce(Deserializer<'de, '_>, V) -> Result<V::Value>,
+    {
+        self.format.unify(Format::TypeName(name.into()))?;
+        self.tracer.mark_visited(name);
+        self.tracer
+            .registry
+            .entry(name.to_string())
+            .unify(ContainerFormat::NewTypeStruct(Box::new(Format::Bytes)))?;
+        if let Some((_, sample)) = self.tracer.get_sample(self.samples, name)? {
+            return replay(sample, visitor).map_err(|err| sample_error(name, None, err));
+        }
+        let mut config = self.tracer.config.clone();
+        config.unignore_container(name);
+        config.forbidden_types.clear();
+        let mut scratch = Tracer::new(config);
+        let mut format = Format::unknown();
+        trace(
+            Deserializer::new(&mut scratch, self.samples, &mut format),
+            visitor,
+        )
+    }
 }
 
 impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
     type Error = Error;
 
-    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::NotSupported("deserialize_any"))
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_any",
+        });
+        if !self.tracer.config.self_describing {
+            let error = self.tracer.self_describing_error();
+            return self.fallback("deserialize_any", error, visitor);
+        }
+        // Follow the format recorded at this location during serialization tracing.
+        let mut format = self.format.clone();
+        format.reduce();
+        if format.is_unknown() {
+            let error = self.tracer.self_describing_error();
+            return self.fallback("deserialize_any", error, visitor);
+        }
+        if let Some((name, _)) = self.tracer.containers_in_progress.last() {
+            self.tracer
+                .self_describing_containers
+                .insert(name.to_string());
+        }
+        self.deserialize_format(format, visitor)
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::Bool)?;
-        visitor.visit_bool(self.tracer.config.default_bool_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_bool",
+        });
+        self.tracer.unify_primitive(self.format, Format::Bool)?;
+        visitor.visit_bool(
+            self.tracer
+                .synthesize(self.tracer.config.default_bool_value),
+        )
     }
 
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::I8)?;
-        visitor.visit_i8(self.tracer.config.default_i8_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_i8",
+        });
+        self.tracer.unify_primitive(self.format, Format::I8)?;
+        let value = self.tracer.synthesize(self.tracer.config.default_i8_value);
+        visitor.visit_i8(self.tracer.bounded(value))
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::I16)?;
-        visitor.visit_i16(self.tracer.config.default_i16_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_i16",
+        });
+        self.tracer.unify_primitive(self.format, Format::I16)?;
+        let value = self.tracer.synthesize(self.tracer.config.default_i16_value);
+        visitor.visit_i16(self.tracer.bounded(value))
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::I32)?;
-        visitor.visit_i32(self.tracer.config.default_i32_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_i32",
+        });
+        self.tracer.unify_primitive(self.format, Format::I32)?;
+        let value = self.tracer.synthesize(self.tracer.config.default_i32_value);
+        visitor.visit_i32(self.tracer.bounded(value))
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::I64)?;
-        visitor.visit_i64(self.tracer.config.default_i64_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_i64",
+        });
+        self.tracer.unify_primitive(self.format, Format::I64)?;
+        let value = self.tracer.synthesize(self.tracer.config.default_i64_value);
+        visitor.visit_i64(self.tracer.bounded(value))
     }
 
     fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::I128)?;
-        visitor.visit_i128(self.tracer.config.default_i128_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_i128",
+        });
+        self.tracer.unify_primitive(self.format, Format::I128)?;
+        let value = self
+            .tracer
+            .synthesize(self.tracer.config.default_i128_value);
+        visitor.visit_i128(self.tracer.bounded(value))
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::U8)?;
-        visitor.visit_u8(self.tracer.config.default_u8_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_u8",
+        });
+        self.tracer.unify_primitive(self.format, Format::U8)?;
+        let value = self.tracer.synthesize(self.tracer.config.default_u8_value);
+        visitor.visit_u8(self.tracer.bounded(value))
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::U16)?;
-        visitor.visit_u16(self.tracer.config.default_u16_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_u16",
+        });
+        self.tracer.unify_primitive(self.format, Format::U16)?;
+        let value = self.tracer.synthesize(self.tracer.config.default_u16_value);
+        visitor.visit_u16(self.tracer.bounded(value))
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::U32)?;
-        visitor.visit_u32(self.tracer.config.default_u32_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_u32",
+        });
+        self.tracer.unify_primitive(self.format, Format::U32)?;
+        let value = self.tracer.synthesize(self.tracer.config.default_u32_value);
+        visitor.visit_u32(self.tracer.bounded(value))
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::U64)?;
-        visitor.visit_u64(self.tracer.config.default_u64_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_u64",
+        });
+        self.tracer.unify_primitive(self.format, Format::U64)?;
+        let value = self.tracer.synthesize(self.tracer.config.default_u64_value);
+        visitor.visit_u64(self.tracer.bounded(value))
     }
 
     fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::U128)?;
-        visitor.visit_u128(self.tracer.config.default_u128_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_u128",
+        });
+        self.tracer.unify_primitive(self.format, Format::U128)?;
+        let value = self
+            .tracer
+            .synthesize(self.tracer.config.default_u128_value);
+        visitor.visit_u128(self.tracer.bounded(value))
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::F32)?;
-        visitor.visit_f32(self.tracer.config.default_f32_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_f32",
+        });
+        self.tracer.unify_primitive(self.format, Format::F32)?;
+        visitor.visit_f32(self.tracer.synthesize(self.tracer.config.default_f32_value))
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::F64)?;
-        visitor.visit_f64(self.tracer.config.default_f64_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_f64",
+        });
+        self.tracer.unify_primitive(self.format, Format::F64)?;
+        visitor.visit_f64(self.tracer.synthesize(self.tracer.config.default_f64_value))
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::Char)?;
-        visitor.visit_char(self.tracer.config.default_char_value)
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_char",
+        });
+        self.tracer.unify_primitive(self.format, Format::Char)?;
+        visitor.visit_char(
+            self.tracer
+                .synthesize(self.tracer.config.default_char_value),
+        )
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::Str)?;
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_str",
+        });
+        self.tracer.unify_primitive(self.format, Format::Str)?;
         visitor.visit_borrowed_str(self.tracer.config.default_borrowed_str_value)
     }
 
@@ -171,15 +425,22 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::Str)?;
-        visitor.visit_string(self.tracer.config.default_string_value.clone())
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_string",
+        });
+        self.tracer.unify_primitive(self.format, Format::Str)?;
+        let value = self.tracer.config.default_string_value.clone();
+        visitor.visit_string(self.tracer.synthesize(value))
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::Bytes)?;
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_bytes",
+        });
+        self.tracer.unify_primitive(self.format, Format::Bytes)?;
         visitor.visit_borrowed_bytes(self.tracer.config.default_borrowed_bytes_value)
     }
 
@@ -187,20 +448,29 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::Bytes)?;
-        visitor.visit_byte_buf(self.tracer.config.default_byte_buf_value.clone())
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_byte_buf",
+        });
+        self.tracer.unify_primitive(self.format, Format::Bytes)?;
+        let value = self.tracer.config.default_byte_buf_value.clone();
+        visitor.visit_byte_buf(self.tracer.synthesize(value))
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_option",
+        });
         let mut format = Format::unknown();
         self.format
             .unify(Format::Option(Box::new(format.clone())))?;
         if format.is_unknown() {
             let inner = Deserializer::new(self.tracer, self.samples, &mut format);
-            visitor.visit_some(inner)
+            let result = visitor.visit_some(inner);
+            self.tracer.retry_if_uninhabited(&result);
+            result
         } else {
             // Cut exploration.
             visitor.visit_none()
@@ -211,7 +481,10 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
     where
         V: Visitor<'de>,
     {
-        self.format.unify(Format::Unit)?;
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_unit",
+        });
+        self.tracer.unify_primitive(self.format, Format::Unit)?;
         visitor.visit_unit()
     }
 
@@ -219,7 +492,30 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
     where
         V: Visitor<'de>,
     {
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_unit_struct",
+        });
+        if let Some(index) = type_param_index(name) {
+            self.tracer
+                .unify_primitive(self.format, Format::TypeParam(index))?;
+            return visitor.visit_unit();
+        }
+        if self.tracer.config.is_ignored_container(name) {
+            return self.deserialize_ignored(
+                name,
+                visitor,
+                |sample, visitor| {
+                    de::Deserializer::deserialize_unit_struct(
+                        sample.into_deserializer(),
+                        name,
+                        visitor,
+                    )
+                },
+                |inner, visitor| de::Deserializer::deserialize_unit_struct(inner, name, visitor),
+            );
+        }
         self.format.unify(Format::TypeName(name.into()))?;
+        self.tracer.mark_visited(name);
         self.tracer
             .registry
             .entry(name.to_string())
@@ -231,20 +527,45 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
     where
         V: Visitor<'de>,
     {
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_newtype_struct",
+        });
+        if self.tracer.config.transparent_containers.contains(name) {
+            return visitor.visit_newtype_struct(self);
+        }
+        if self.tracer.config.is_ignored_container(name) {
+            return self.deserialize_ignored(
+                name,
+                visitor,
+                |sample, visitor| visitor.visit_newtype_struct(sample.into_deserializer()),
+                |inner, visitor| de::Deserializer::deserialize_newtype_struct(inner, name, visitor),
+            );
+        }
         self.format.unify(Format::TypeName(name.into()))?;
+        self.tracer.mark_visited(name);
         if self.tracer.config.record_samples_for_newtype_structs {
             // If a value was recorded during serialization, use it.
-            if let Some((format, sample)) = self.tracer.get_sample(self.samples, name) {
-                return visitor
-                    .visit_newtype_struct(sample.into_deserializer())
-                    .map_err(|err| match err {
-                        Error::DeserializationError(msg) => {
-                            let mut format = format.clone();
-                            format.reduce();
-                            Error::UnexpectedDeserializationFormat(name, format, msg)
-                        }
-                        _ => err,
-                    });
+            if let Some((format, sample)) = self.tracer.get_sample(self.samples, name)? {
+                if format.is_some() {
+                    return visitor
+                        .visit_newtype_struct(sample.into_deserializer())
+                        .map_err(|err| sample_error(name, format, err));
+                }
+                // The sample was not recorded by this tracer (e.g. it was loaded from a
+                // file). Record the levels that the sample goes through while replaying it,
+                // so that the container is not missing from the registry.
+                let mut format = Format::unknown();
+                self.tracer
+                    .registry
+                    .entry(name.to_string())
+                    .unify(ContainerFormat::NewTypeStruct(Box::new(format.clone())))?;
+                self.tracer.enter_container(name);
+                let inner = SampleDeserializer::new(self.tracer, sample, &mut format);
+                let result = visitor
+                    .visit_newtype_struct(inner)
+                    .map_err(|err| sample_error(name, None, err));
+                self.tracer.exit_container();
+                return result;
             }
         }
         // Pre-update the registry.
@@ -253,36 +574,51 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
             .registry
             .entry(name.to_string())
             .unify(ContainerFormat::NewTypeStruct(Box::new(format.clone())))?;
-        // Compute the format.
+        // Compute the format. The registry may hold a copy of the format recorded by
+        // `trace_value`, in which case the formats widened meanwhile are recorded again.
+        let widened_formats = self.tracer.widened_formats;
+        self.tracer.enter_container(name);
         let inner = Deserializer::new(self.tracer, self.samples, &mut format);
-        visitor.visit_newtype_struct(inner)
+        let result = visitor.visit_newtype_struct(inner);
+        self.tracer.exit_container();
+        if self.tracer.widened_formats > widened_formats {
+            self.tracer
+                .widen_container(name, ContainerFormat::NewTypeStruct(Box::new(format)));
+        }
+        result
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let mut format = Format::unknown();
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_seq",
+        });
+        let format = Format::unknown();
         self.format.unify(Format::Seq(Box::new(format.clone())))?;
-        if format.is_unknown() {
-            // Simulate vector of size 1.
-            let inner =
-                SeqDeserializer::new(self.tracer, self.samples, std::iter::once(&mut format));
-            visitor.visit_seq(inner)
-        } else {
-            // Cut exploration with a vector of size 0.
-            let inner = SeqDeserializer::new(self.tracer, self.samples, std::iter::empty());
-            visitor.visit_seq(inner)
-        }
+        // Simulate a vector of size 1 (by default) while the format of the elements is
+        // unknown, otherwise cut exploration with a vector of size 0. The elements share
+        // the same format variable.
+        let len = self.tracer.seq_sample_len(&[&format]);
+        let mut formats = vec![format; len];
+        let inner = SeqDeserializer::new(self.tracer, self.samples, formats.iter_mut());
+        let result = visitor.visit_seq(inner);
+        self.tracer.retry_if_uninhabited(&result);
+        result
     }
 
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_tuple",
+        });
         let mut formats: Vec<_> = std::iter::repeat_with(Format::unknown).take(len).collect();
         self.format.unify(Format::Tuple(formats.clone()))?;
-        let inner = SeqDeserializer::new(self.tracer, self.samples, formats.iter_mut());
+        let inner = SeqDeserializer::new(self.tracer, self.samples, formats.iter_mut())
+            .with_labels(Labels::Indices);
         visitor.visit_seq(inner)
     }
 
@@ -295,19 +631,26 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
     where
         V: Visitor<'de>,
     {
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_tuple_struct",
+        });
+        if self.tracer.config.is_ignored_container(name) {
+            return self.deserialize_ignored(
+                name,
+                visitor,
+                |sample, visitor| visitor.visit_seq(sample.seq_values()?.into_seq_deserializer()),
+                |inner, visitor| {
+                    de::Deserializer::deserialize_tuple_struct(inner, name, len, visitor)
+                },
+            );
+        }
         self.format.unify(Format::TypeName(name.into()))?;
+        self.tracer.mark_visited(name);
         if self.tracer.config.record_samples_for_tuple_structs {
             // If a value was recorded during serialization, use it.
-            if let Some((format, sample)) = self.tracer.get_sample(self.samples, name) {
+            if let Some((format, sample)) = self.tracer.get_sample(self.samples, name)? {
                 let result = || visitor.visit_seq(sample.seq_values()?.into_seq_deserializer());
-                return result().map_err(|err| match err {
-                    Error::DeserializationError(msg) => {
-                        let mut format = format.clone();
-                        format.reduce();
-                        Error::UnexpectedDeserializationFormat(name, format, msg)
-                    }
-                    _ => err,
-                });
+                return result().map_err(|err| sample_error(name, format, err));
             }
         }
         // Pre-update the registry.
@@ -317,33 +660,47 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
             .entry(name.to_string())
             .unify(ContainerFormat::TupleStruct(formats.clone()))?;
         // Compute the formats.
-        let inner = SeqDeserializer::new(self.tracer, self.samples, formats.iter_mut());
-        visitor.visit_seq(inner)
+        let widened_formats = self.tracer.widened_formats;
+        self.tracer.enter_container(name);
+        let inner = SeqDeserializer::new(self.tracer, self.samples, formats.iter_mut())
+            .with_labels(Labels::Indices);
+        let result = visitor.visit_seq(inner);
+        self.tracer.exit_container();
+        if self.tracer.widened_formats > widened_formats {
+            self.tracer
+                .widen_container(name, ContainerFormat::TupleStruct(formats));
+        }
+        result
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let mut key_format = Format::unknown();
-        let mut value_format = Format::unknown();
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_map",
+        });
+        let key_format = Format::unknown();
+        let value_format = Format::unknown();
         self.format.unify(Format::Map {
             key: Box::new(key_format.clone()),
             value: Box::new(value_format.clone()),
         })?;
-        if key_format.is_unknown() || value_format.is_unknown() {
-            // Simulate a map with one entry.
-            let inner = SeqDeserializer::new(
-                self.tracer,
-                self.samples,
-                vec![&mut key_format, &mut value_format].into_iter(),
-            );
-            visitor.visit_map(inner)
-        } else {
-            // Stop exploration.
-            let inner = SeqDeserializer::new(self.tracer, self.samples, std::iter::empty());
-            visitor.visit_map(inner)
+        // Simulate a map with one entry (by default) while the formats are unknown,
+        // otherwise stop exploration.
+        let len = self.tracer.seq_sample_len(&[&key_format, &value_format]);
+        let mut formats: Vec<_> = std::iter::repeat([key_format, value_format.clone()])
+            .take(len)
+            .flatten()
+            .collect();
+        let inner = SeqDeserializer::new(self.tracer, self.samples, formats.iter_mut());
+        let result = visitor.visit_map(inner);
+        // An uninhabited key leaves the format of the values unknown, so the next pass
+        // would not avoid it.
+        if !value_format.is_unknown() {
+            self.tracer.retry_if_uninhabited(&result);
         }
+        result
     }
 
     fn deserialize_struct<V>(
@@ -355,19 +712,48 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
     where
         V: Visitor<'de>,
     {
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_struct",
+        });
+        if self.tracer.config.is_ignored_container(name) {
+            return self.deserialize_ignored(
+                name,
+                visitor,
+                |sample, visitor| visitor.visit_seq(sample.seq_values()?.into_seq_deserializer()),
+                |inner, visitor| de::Deserializer::deserialize_struct(inner, name, fields, visitor),
+            );
+        }
         self.format.unify(Format::TypeName(name.into()))?;
+        self.tracer.mark_visited(name);
+        if self.tracer.unknown_fields_probe && !self.tracer.unknown_fields.contains_key(name) {
+            // The answer may be known from the attributes of the struct, in which case
+            // the pass goes on.
+            if let Some(attrs) = attributes::lookup(name) {
+                self.tracer
+                    .unknown_fields
+                    .insert(name.to_string(), Some(attrs.deny_unknown_fields));
+            }
+        }
+        if self.tracer.unknown_fields_probe && !self.tracer.unknown_fields.contains_key(name) {
+            // Offer an unknown field then abort the pass. Visitors that ignore unknown
+            // fields request its value, whereas the others fail on its name.
+            let mut key_requested = false;
+            let mut value_requested = false;
+            visitor
+                .visit_map(UnknownFieldProbe {
+                    key_requested: &mut key_requested,
+                    value_requested: &mut value_requested,
+                })
+                .ok();
+            let denied = key_requested.then_some(!value_requested);
+            self.tracer.unknown_fields.insert(name.to_string(), denied);
+            return Err(Error::NotSupported("probing unknown fields"));
+        }
         if self.tracer.config.record_samples_for_structs {
             // If a value was recorded during serialization, use it.
-            if let Some((format, sample)) = self.tracer.get_sample(self.samples, name) {
+            if let Some((format, sample)) = self.tracer.get_sample(self.samples, name)? {
                 let result = || visitor.visit_seq(sample.seq_values()?.into_seq_deserializer());
-                return result().map_err(|err| match err {
-                    Error::DeserializationError(msg) => {
-                        let mut format = format.clone();
-                        format.reduce();
-                        Error::UnexpectedDeserializationFormat(name, format, msg)
-                    }
-                    _ => err,
-                });
+                return result().map_err(|err| sample_error(name, format, err));
             }
         }
         // Pre-update the registry.
@@ -383,12 +769,21 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
             .entry(name.to_string())
             .unify(ContainerFormat::Struct(formats.clone()))?;
         // Compute the formats.
+        let widened_formats = self.tracer.widened_formats;
+        self.tracer.enter_container(name);
         let inner = SeqDeserializer::new(
             self.tracer,
             self.samples,
             formats.iter_mut().map(|named| &mut named.value),
-        );
-        visitor.visit_seq(inner)
+        )
+        .with_labels(Labels::Names(fields));
+        let result = visitor.visit_seq(inner);
+        self.tracer.exit_container();
+        if self.tracer.widened_formats > widened_formats {
+            self.tracer
+                .widen_container(name, ContainerFormat::Struct(formats));
+        }
+        result
     }
 
     // Assumption: The first variant(s) should be "base cases", i.e. not cause infinite recursion
@@ -403,45 +798,154 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
     where
         V: Visitor<'de>,
     {
-        if variants.is_empty() {
-            return Err(Error::NotSupported("deserialize_enum with 0 variants"));
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_enum",
+        });
+        if self.tracer.config.is_ignored_container(enum_name) {
+            return self.deserialize_ignored(
+                enum_name,
+                visitor,
+                |sample, visitor| {
+                    de::Deserializer::deserialize_enum(
+                        sample.into_deserializer(),
+                        enum_name,
+                        variants,
+                        visitor,
+                    )
+                },
+                |inner, visitor| {
+                    de::Deserializer::deserialize_enum(inner, enum_name, variants, visitor)
+                },
+            );
         }
 
         let enum_type_id = typeid::of::<V::Value>();
+        // Containers are indexed by name, so enums sharing the same name, e.g. a borrowed and
+        // an owned version of a message, are traced into the same container, like structs.
+        // Their formats must agree. Enums with different variants are rejected rather than
+        // merged silently.
+        if *self
+            .tracer
+            .enum_variant_names
+            .entry(enum_name)
+            .or_insert(variants)
+            != variants
+        {
+            return Err(Error::NameClash(enum_name.to_string()));
+        }
+        self.tracer.enum_type_ids.insert(enum_name, enum_type_id);
         self.format.unify(Format::TypeName(enum_name.into()))?;
+        self.tracer.mark_visited(enum_name);
         // Pre-update the registry.
         self.tracer
             .registry
             .entry(enum_name.to_string())
             .unify(ContainerFormat::Enum(BTreeMap::new()))?;
-        let known_variants = match self.tracer.registry.get_mut(enum_name) {
-            Some(ContainerFormat::Enum(x)) => x,
-            _ => unreachable!(),
+        // Enums without variants, e.g. `enum Void {}`, are uninhabited. They are recorded
+        // as such but no value can be produced.
+        if variants.is_empty() {
+            return Err(Error::Uninhabited(enum_name.to_string()));
+        }
+        // When probing for a fallback variant, use an index beyond the declared variants.
+        // Derived implementations reject it unless a variant is marked `#[serde(other)]`.
+        if self.tracer.fallback_probe.as_deref() == Some(enum_name) {
+            self.tracer.fallback_probe = None;
+            let enum_value = visitor
+                .visit_enum(EnumDeserializer::new(
+                    self.tracer,
+                    self.samples,
+                    enum_name,
+                    VariantId::Index(variants.len() as u32),
+                    &mut VariantFormat::unknown(),
+                ))
+                .map_err(|error| {
+                    self.tracer.fallback_probe_rejected = true;
+                    error
+                })?;
+            let discriminant = Discriminant::of(&enum_value);
+            let fallback = self
+                .tracer
+                .discriminants
+                .iter()
+                .find(|((type_id, id), value)| {
+                    *type_id == enum_type_id
+                        && matches!(id, VariantId::Name(_))
+                        && **value == discriminant
+                })
+                .map(|((_, id), _)| id);
+            if let Some(VariantId::Name(name)) = fallback {
+                self.tracer
+                    .fallback_variants
+                    .insert(enum_name.to_string(), name.to_string());
+            }
+            return Ok(enum_value);
+        }
+
+        // Variants skipped by the configuration or found to be uninhabited are never
+        // visited. Their indices are assumed to be their positions in `variants`, as with
+        // derived implementations.
+        let skipped_indices = self.tracer.unvisited_positions(enum_name, variants);
+        let Some(first_index) = (0..variants.len() as u32).find(|i| !skipped_indices.contains(i))
+        else {
+            // An enum whose variants are all uninhabited is uninhabited too.
+            if self.tracer.is_uninhabited_enum(enum_name, variants) {
+                self.tracer.incomplete_enums.remove(enum_name);
+                return Err(Error::Uninhabited(enum_name.to_string()));
+            }
+            return Err(Error::NotSupported(
+                "deserialize_enum with all variants skipped",
+            ));
         };
 
         // If the enum is already marked as incomplete, visit the first index, hoping to
-        // avoid recursion.
-        if self.tracer.incomplete_enums.contains_key(enum_name) {
-            return visitor.visit_enum(EnumDeserializer::new(
+        // avoid recursion. Enums restored from a checkpoint are visited the same way since
+        // their variants are known.
+        if self.tracer.incomplete_enums.contains_key(enum_name)
+            || self.tracer.restored_enums.contains(enum_name)
+        {
+            let mut value = self.tracer.retried_variant(enum_name, first_index);
+            let result = visitor.visit_enum(EnumDeserializer::new(
                 self.tracer,
                 self.samples,
-                VariantId::Index(0),
-                &mut VariantFormat::unknown(),
+                enum_name,
+                VariantId::Index(first_index),
+                &mut value,
             ));
+            self.tracer
+                .keep_retried_variant(enum_name, first_index, value, &result);
+            return result;
         }
 
         // First, visit each of the variants by name according to `variants`. Later, we
         // will revisit them by u32 index until an index matching each of the named
         // variants has been determined.
+        let known_variants = match self.tracer.registry.get_mut(enum_name) {
+            Some(ContainerFormat::Enum(x)) => x,
+            _ => unreachable!(),
+        };
         let provisional_min = u32::MAX - (variants.len() - 1) as u32;
-        for (i, &variant_name) in variants.iter().enumerate() {
-            if self
-                .tracer
-                .discriminants
-                .contains_key(&(enum_type_id, VariantId::Name(variant_name)))
+        // With `VariantOrder::SampleFirst`, the variants already recorded with their index
+        // and a complete format are not visited again, neither by name nor by index.
+        let identified: BTreeMap<u32, String> = match self.tracer.config.variant_order {
+            VariantOrder::Declaration => BTreeMap::new(),
+            VariantOrder::SampleFirst => known_variants
+                .range(..provisional_min)
+                .filter(|(_, variant)| is_complete(&variant.value))
+                .map(|(index, variant)| (*index, variant.name.clone()))
+                .collect(),
+        };
+        let progress = self.tracer.enum_progress.entry(enum_type_id).or_default();
+        for (i, &variant_name) in variants.iter().enumerate().skip(progress.next_name) {
+            if skipped_indices.contains(&(i as u32))
+                || identified.values().any(|name| name == variant_name)
+                || self
+                    .tracer
+                    .discriminants
+                    .contains_key(&(enum_type_id, VariantId::Name(variant_name)))
             {
                 continue;
             }
+            progress.next_name = i;
             // Insert into known_variants with a provisional index.
             let provisional_index = provisional_min + i as u32;
             let variant = known_variants
@@ -456,13 +960,44 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
             );
             // Compute the discriminant and format for this variant.
             let mut value = variant.value.clone();
-            let enum_value = visitor.visit_enum(EnumDeserializer::new(
+            self.tracer.record_event(|| TraceEvent::VariantAdvanced {
+                enum_name: enum_name.to_string(),
+                name: Some(variant_name.to_string()),
+                index: None,
+            });
+            let result = visitor.visit_enum(EnumDeserializer::new(
                 self.tracer,
                 self.samples,
+                enum_name,
                 VariantId::Name(variant_name),
                 &mut value,
-            ))?;
+            ));
+            let enum_value = match result {
+                // The content of the variant is uninhabited, unless the error comes from an
+                // enum explored deeper in the current pass.
+                Err(Error::Uninhabited(name)) if !self.tracer.uninhabited_retry => {
+                    self.tracer.record_uninhabited_variant(
+                        enum_name,
+                        variant_name,
+                        i as u32,
+                        provisional_index,
+                    )?;
+                    // Unless all the variants are uninhabited, the enum is not.
+                    if self.tracer.is_uninhabited_enum(enum_name, variants) {
+                        self.tracer.incomplete_enums.remove(enum_name);
+                        return Err(Error::Uninhabited(enum_name.to_string()));
+                    }
+                    self.tracer.uninhabited_retry = true;
+                    return Err(Error::Uninhabited(name));
+                }
+                result => result?,
+            };
             let discriminant = Discriminant::of(&enum_value);
+            self.tracer
+                .discriminant_names
+                .entry((enum_type_id, discriminant.clone()))
+                .or_default()
+                .push(variant_name);
             self.tracer
                 .discriminants
                 .insert((enum_type_id, VariantId::Name(variant_name)), discriminant);
@@ -473,71 +1008,93 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
         // by index to find the u32 id that goes with each name.
         //
         // If there are no provisional entries waiting for an index, just go
-        // with index 0.
-        let mut index = 0;
+        // with the first index.
+        progress.next_name = variants.len();
+        let mut index = (first_index..variants.len() as u32)
+            .find(|i| !skipped_indices.contains(i) && !identified.contains_key(i))
+            .unwrap_or(first_index);
         if known_variants.range(provisional_min..).next().is_some() {
             self.tracer.incomplete_enums.insert(
                 enum_name.into(),
                 IncompleteEnumReason::IndexedVariantsRemaining,
             );
-            while known_variants.contains_key(&index)
-                && self
-                    .tracer
-                    .discriminants
-                    .contains_key(&(enum_type_id, VariantId::Index(index)))
+            index = progress.next_index;
+            while skipped_indices.contains(&index)
+                || identified.contains_key(&index)
+                || (known_variants.contains_key(&index)
+                    && self
+                        .tracer
+                        .discriminants
+                        .contains_key(&(enum_type_id, VariantId::Index(index))))
             {
                 index += 1;
             }
+            progress.next_index = index;
+            self.tracer.record_event(|| TraceEvent::VariantAdvanced {
+                enum_name: enum_name.to_string(),
+                name: None,
+                index: Some(index),
+            });
         }
 
         // Compute the discriminant and format for this variant.
-        let mut value = VariantFormat::unknown();
-        let enum_value = visitor.visit_enum(EnumDeserializer::new(
+        let mut value = self.tracer.retried_variant(enum_name, index);
+        let result = visitor.visit_enum(EnumDeserializer::new(
             self.tracer,
             self.samples,
+            enum_name,
             VariantId::Index(index),
             &mut value,
-        ))?;
+        ));
+        self.tracer
+            .keep_retried_variant(enum_name, index, value.clone(), &result);
+        let enum_value = result?;
         let discriminant = Discriminant::of(&enum_value);
         self.tracer.discriminants.insert(
             (enum_type_id, VariantId::Index(index)),
             discriminant.clone(),
         );
 
-        // Rewrite provisional entries for which we now know a u32 index.
+        // Rewrite provisional entries for which we now know a u32 index. They are found
+        // through the names of the variants with the same discriminant.
+        let positions: Vec<u32> = self
+            .tracer
+            .discriminant_names
+            .get(&(enum_type_id, discriminant))
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|name| self.tracer.variant_position(enum_name, variants, name))
+            .collect();
+        let widen = self.tracer.config.widen_formats;
         let known_variants = match self.tracer.registry.get_mut(enum_name) {
             Some(ContainerFormat::Enum(x)) => x,
             _ => unreachable!(),
         };
 
-        let mut has_indexed_variants_remaining = false;
-        for provisional_index in provisional_min..=u32::MAX {
-            if let Entry::Occupied(provisional_entry) = known_variants.entry(provisional_index) {
-                if self.tracer.discriminants
-                    [&(enum_type_id, VariantId::Name(&provisional_entry.get().name))]
-                    == discriminant
-                {
-                    let provisional_entry = provisional_entry.remove();
-                    match known_variants.entry(index) {
-                        Entry::Vacant(vacant) => {
-                            vacant.insert(provisional_entry);
-                        }
-                        Entry::Occupied(mut existing_entry) => {
-                            // Discard the provisional entry's name and just
-                            // keep the existing one.
-                            existing_entry
-                                .get_mut()
-                                .value
-                                .unify(provisional_entry.value)?;
-                        }
+        for position in positions {
+            if let Some(provisional_entry) = known_variants.remove(&(provisional_min + position)) {
+                match known_variants.entry(index) {
+                    Entry::Vacant(vacant) => {
+                        vacant.insert(provisional_entry);
+                    }
+                    Entry::Occupied(mut existing_entry) => {
+                        // Discard the provisional entry's name and just
+                        // keep the existing one.
+                        unify_variant(
+                            enum_name,
+                            existing_entry.get_mut(),
+                            provisional_entry.value,
+                            widen,
+                        )?;
                     }
-                } else {
-                    has_indexed_variants_remaining = true;
                 }
             }
         }
+        let has_indexed_variants_remaining =
+            known_variants.range(provisional_min..).next().is_some();
         if let Some(existing_entry) = known_variants.get_mut(&index) {
-            existing_entry.value.unify(value)?;
+            unify_variant(enum_name, existing_entry, value, widen)?;
         }
         if has_indexed_variants_remaining {
             // Signal that the top-level tracing must continue.
@@ -553,18 +1110,26 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
         Ok(enum_value)
     }
 
-    fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::NotSupported("deserialize_identifier"))
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_identifier",
+        });
+        let error = Error::NotSupported("deserialize_identifier");
+        self.fallback("deserialize_identifier", error, visitor)
     }
 
-    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::NotSupported("deserialize_ignored_any"))
+        self.tracer.record_event(|| TraceEvent::VisitorHit {
+            method: "deserialize_ignored_any",
+        });
+        let error = Error::NotSupported("deserialize_ignored_any");
+        self.fallback("deserialize_ignored_any", error, visitor)
     }
 
     fn is_human_readable(&self) -> bool {
@@ -572,10 +1137,22 @@ impl<'de, 'a> de::Deserializer<'de> for Deserializer<'de, 'a> {
     }
 }
 
+/// How the elements of a sequence appear in the field paths of errors.
+enum Labels {
+    /// Elements of a collection are not named.
+    None,
+    /// Elements of a tuple are named by their positions.
+    Indices,
+    /// Fields of a struct are named by their names.
+    Names(&'static [&'static str]),
+}
+
 struct SeqDeserializer<'de, 'a, I> {
     tracer: &'a mut Tracer,
     samples: &'de Samples,
     formats: I,
+    labels: Labels,
+    index: usize,
 }
 
 impl<'de, 'a, I> SeqDeserializer<'de, 'a, I> {
@@ -584,7 +1161,41 @@ impl<'de, 'a, I> SeqDeserializer<'de, 'a, I> {
             tracer,
             samples,
             formats,
+            labels: Labels::None,
+            index: 0,
+        }
+    }
+
+    fn with_labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    fn next_label(&mut self) -> Option<String> {
+        let index = self.index;
+        self.index += 1;
+        match self.labels {
+            Labels::None => None,
+            Labels::Indices => Some(index.to_string()),
+            Labels::Names(names) => names.get(index).map(|name| name.to_string()),
+        }
+    }
+
+    fn deserialize_element<T>(&mut self, seed: T, format: &'a mut Format) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let label = self.next_label();
+        let labeled = label.is_some();
+        self.tracer.field_path.extend(label);
+        let result = self.tracer.check_type::<T>().and_then(|()| {
+            let inner = Deserializer::new(self.tracer, self.samples, format);
+            seed.deserialize(inner)
+        });
+        if labeled {
+            self.tracer.field_path.pop();
         }
+        result
     }
 }
 
@@ -602,8 +1213,7 @@ where
             Some(x) => x,
             None => return Ok(None),
         };
-        let inner = Deserializer::new(self.tracer, self.samples, format);
-        seed.deserialize(inner).map(Some)
+        self.deserialize_element(seed, format).map(Some)
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -626,6 +1236,7 @@ where
             Some(x) => x,
             None => return Ok(None),
         };
+        self.tracer.check_type::<K>()?;
         let inner = Deserializer::new(self.tracer, self.samples, format);
         seed.deserialize(inner).map(Some)
     }
@@ -638,6 +1249,7 @@ where
             Some(x) => x,
             None => unreachable!(),
         };
+        self.tracer.check_type::<V>()?;
         let inner = Deserializer::new(self.tracer, self.samples, format);
         seed.deserialize(inner)
     }
@@ -647,9 +1259,42 @@ where
     }
 }
 
+/// Map containing a single field with an unused name.
+struct UnknownFieldProbe<'p> {
+    key_requested: &'p mut bool,
+    value_requested: &'p mut bool,
+}
+
+impl<'de, 'p> de::MapAccess<'de> for UnknownFieldProbe<'p> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if *self.key_requested {
+            return Ok(None);
+        }
+        *self.key_requested = true;
+        seed.deserialize(BorrowedStrDeserializer::new(
+            "__serde_reflection_unknown_field",
+        ))
+        .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, _seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        *self.value_requested = true;
+        Err(Error::NotSupported("probing unknown fields"))
+    }
+}
+
 struct EnumDeserializer<'de, 'a> {
     tracer: &'a mut Tracer,
     samples: &'de Samples,
+    enum_name: &'static str,
     variant_id: VariantId<'static>,
     format: &'a mut VariantFormat,
 }
@@ -658,18 +1303,36 @@ impl<'de, 'a> EnumDeserializer<'de, 'a> {
     fn new(
         tracer: &'a mut Tracer,
         samples: &'de Samples,
+        enum_name: &'static str,
         variant_id: VariantId<'static>,
         format: &'a mut VariantFormat,
     ) -> Self {
         Self {
             tracer,
             samples,
+            enum_name,
             variant_id,
             format,
         }
     }
 }
 
+impl<'de, 'a> EnumDeserializer<'de, 'a> {
+    fn enter_variant(&mut self) {
+        self.tracer.enter_container(self.enum_name);
+        let label = match self.variant_id {
+            VariantId::Index(index) => index.to_string(),
+            VariantId::Name(name) => name.to_string(),
+        };
+        self.tracer.field_path.push(label);
+    }
+
+    fn exit_variant(&mut self) {
+        self.tracer.field_path.pop();
+        self.tracer.exit_container();
+    }
+}
+
 impl<'de, 'a> de::EnumAccess<'de> for EnumDeserializer<'de, 'a> {
     type Error = Error;
     type Variant = Self;
@@ -693,28 +1356,37 @@ impl<'de, 'a> de::VariantAccess<'de> for EnumDeserializer<'de, 'a> {
         self.format.unify(VariantFormat::Unit)
     }
 
-    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value>
     where
         T: DeserializeSeed<'de>,
     {
         let mut format = Format::unknown();
         self.format
             .unify(VariantFormat::NewType(Box::new(format.clone())))?;
-        let inner = Deserializer::new(self.tracer, self.samples, &mut format);
-        seed.deserialize(inner)
+        self.enter_variant();
+        let result = self.tracer.check_type::<T>().and_then(|()| {
+            let inner = Deserializer::new(self.tracer, self.samples, &mut format);
+            seed.deserialize(inner)
+        });
+        self.exit_variant();
+        result
     }
 
-    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    fn tuple_variant<V>(mut self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         let mut formats: Vec<_> = std::iter::repeat_with(Format::unknown).take(len).collect();
         self.format.unify(VariantFormat::Tuple(formats.clone()))?;
-        let inner = SeqDeserializer::new(self.tracer, self.samples, formats.iter_mut());
-        visitor.visit_seq(inner)
+        self.enter_variant();
+        let inner = SeqDeserializer::new(self.tracer, self.samples, formats.iter_mut())
+            .with_labels(Labels::Indices);
+        let result = visitor.visit_seq(inner);
+        self.exit_variant();
+        result
     }
 
-    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    fn struct_variant<V>(mut self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -727,11 +1399,204 @@ impl<'de, 'a> de::VariantAccess<'de> for EnumDeserializer<'de, 'a> {
             .collect();
         self.format.unify(VariantFormat::Struct(formats.clone()))?;
 
+        self.enter_variant();
         let inner = SeqDeserializer::new(
             self.tracer,
             self.samples,
             formats.iter_mut().map(|named| &mut named.value),
-        );
-        visitor.visit_seq(inner)
+        )
+        .with_labels(Labels::Names(fields));
+        let result = visitor.visit_seq(inner);
+        self.exit_variant();
+        result
+    }
+}
+
+/// Replay a sample that was not recorded by the tracer, recording the formats that can be
+/// told from the calls of the visitor: primitive formats, options, and the names of the
+/// containers. Newtype structs and unit structs are recorded in the registry. The content
+/// of the other containers, and the elements of sequences and maps, are not recorded.
+struct SampleDeserializer<'de, 'a> {
+    tracer: &'a mut Tracer,
+    value: &'de Value,
+    format: &'a mut Format,
+}
+
+impl<'de, 'a> SampleDeserializer<'de, 'a> {
+    fn new(tracer: &'a mut Tracer, value: &'de Value, format: &'a mut Format) -> Self {
+        Self {
+            tracer,
+            value,
+            format,
+        }
+    }
+
+    /// Record the name of a container whose content is not replayed.
+    fn record_name(&mut self, name: &'static str) -> Result<()> {
+        self.format.unify(Format::TypeName(name.into()))?;
+        self.tracer.mark_visited(name);
+        Ok(())
+    }
+}
+
+macro_rules! declare_replay {
+    ($method:ident, $format:expr) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: Visitor<'de>,
+        {
+            self.format.unify($format)?;
+            de::Deserializer::$method(self.value.into_deserializer(), visitor)
+        }
+    };
+}
+
+macro_rules! declare_replay_unrecorded {
+    ($method:ident) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: Visitor<'de>,
+        {
+            de::Deserializer::$method(self.value.into_deserializer(), visitor)
+        }
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for SampleDeserializer<'de, 'a> {
+    type Error = Error;
+
+    declare_replay_unrecorded!(deserialize_any);
+
+    declare_replay!(deserialize_bool, Format::Bool);
+
+    declare_replay!(deserialize_i8, Format::I8);
+    declare_replay!(deserialize_i16, Format::I16);
+    declare_replay!(deserialize_i32, Format::I32);
+    declare_replay!(deserialize_i64, Format::I64);
+    declare_replay!(deserialize_i128, Format::I128);
+
+    declare_replay!(deserialize_u8, Format::U8);
+    declare_replay!(deserialize_u16, Format::U16);
+    declare_replay!(deserialize_u32, Format::U32);
+    declare_replay!(deserialize_u64, Format::U64);
+    declare_replay!(deserialize_u128, Format::U128);
+
+    declare_replay!(deserialize_f32, Format::F32);
+    declare_replay!(deserialize_f64, Format::F64);
+
+    declare_replay!(deserialize_char, Format::Char);
+    declare_replay!(deserialize_str, Format::Str);
+    declare_replay!(deserialize_string, Format::Str);
+    declare_replay!(deserialize_bytes, Format::Bytes);
+    declare_replay!(deserialize_byte_buf, Format::Bytes);
+
+    declare_replay!(deserialize_unit, Format::Unit);
+
+    declare_replay_unrecorded!(deserialize_seq);
+    declare_replay_unrecorded!(deserialize_map);
+    declare_replay_unrecorded!(deserialize_identifier);
+    declare_replay_unrecorded!(deserialize_ignored_any);
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut format = Format::unknown();
+        self.format
+            .unify(Format::Option(Box::new(format.clone())))?;
+        match self.value {
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(value)) => {
+                visitor.visit_some(SampleDeserializer::new(self.tracer, value, &mut format))
+            }
+            _ => Err(Error::DeserializationError("option")),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(mut self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.record_name(name)?;
+        self.tracer
+            .registry
+            .entry(name.to_string())
+            .unify(ContainerFormat::UnitStruct)?;
+        de::Deserializer::deserialize_unit_struct(self.value.into_deserializer(), name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(mut self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.tracer.config.transparent_containers.contains(name) {
+            return visitor.visit_newtype_struct(self);
+        }
+        self.record_name(name)?;
+        let mut format = Format::unknown();
+        self.tracer
+            .registry
+            .entry(name.to_string())
+            .unify(ContainerFormat::NewTypeStruct(Box::new(format.clone())))?;
+        self.tracer.enter_container(name);
+        let inner = SampleDeserializer::new(self.tracer, self.value, &mut format);
+        let result = visitor.visit_newtype_struct(inner);
+        self.tracer.exit_container();
+        result
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(self.value.into_deserializer(), len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        mut self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.record_name(name)?;
+        de::Deserializer::deserialize_tuple_struct(
+            self.value.into_deserializer(),
+            name,
+            len,
+            visitor,
+        )
+    }
+
+    fn deserialize_struct<V>(
+        mut self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.record_name(name)?;
+        de::Deserializer::deserialize_struct(self.value.into_deserializer(), name, fields, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        mut self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.record_name(name)?;
+        de::Deserializer::deserialize_enum(self.value.into_deserializer(), name, variants, visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.tracer.config.is_human_readable
     }
 }
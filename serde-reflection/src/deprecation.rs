@@ -0,0 +1,55 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// A sidecar record of deprecated fields and variants.
+///
+/// Members are identified by their qualified names, that is, the name of the container
+/// followed by the names of the nested variant and/or field. For instance:
+/// * `["Foo", "a"]` for the field `a` of the struct `Foo`,
+/// * `["Bar", "B"]` for the variant `B` of the enum `Bar`,
+/// * `["Bar", "C", "x"]` for the field `x` of the struct variant `Bar::C`.
+///
+/// Removing a deprecated member is not considered a breaking change by the module
+/// [`diff`](crate::diff).
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+pub struct Deprecations(BTreeSet<Vec<String>>);
+
+impl Deprecations {
+    /// Create an empty set of deprecations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark the member with the given qualified name as deprecated.
+    pub fn deprecate<I, S>(&mut self, path: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.0.insert(path.into_iter().map(Into::into).collect());
+    }
+
+    /// Mark the field `field` of the struct `container` as deprecated.
+    pub fn deprecate_field(&mut self, container: &str, field: &str) {
+        self.deprecate([container, field]);
+    }
+
+    /// Mark the variant `variant` of the enum `container` as deprecated.
+    pub fn deprecate_variant(&mut self, container: &str, variant: &str) {
+        self.deprecate([container, variant]);
+    }
+
+    /// Whether the member with the given qualified name is deprecated.
+    pub fn is_deprecated<S: AsRef<str>>(&self, path: &[S]) -> bool {
+        let path: Vec<String> = path.iter().map(|s| s.as_ref().to_string()).collect();
+        self.0.contains(&path)
+    }
+
+    /// Iterate over the qualified names of the deprecated members.
+    pub fn iter(&self) -> impl Iterator<Item = &[String]> {
+        self.0.iter().map(Vec::as_slice)
+    }
+}
@@ -0,0 +1,743 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Comparison of registries.
+//!
+//! This module computes the differences between two versions of a registry, e.g. a
+//! registry stored under version control and a freshly traced one.
+//! `RegistryDiff::to_changelog_markdown` renders the changes for release notes.
+//!
+//! ```rust
+//! use serde_reflection::{ContainerFormat, Deprecations, Format, Named, Registry};
+//! use serde_reflection::diff::{ChangeKind, RegistryDiff};
+//!
+//! let mut old = Registry::new();
+//! old.insert("Foo".into(), ContainerFormat::Struct(vec![
+//!     Named { name: "a".into(), value: Format::U32 },
+//!     Named { name: "b".into(), value: Format::Str },
+//! ]));
+//! let mut new = Registry::new();
+//! new.insert("Foo".into(), ContainerFormat::Struct(vec![
+//!     Named { name: "a".into(), value: Format::U32 },
+//! ]));
+//!
+//! let diff = RegistryDiff::new(&old, &new);
+//! assert_eq!(diff.changes.len(), 1);
+//! assert_eq!(diff.changes[0].path, vec!["Foo", "b"]);
+//! assert!(matches!(diff.changes[0].kind, ChangeKind::FieldRemoved { .. }));
+//! assert!(diff.is_breaking());
+//!
+//! // Removing a deprecated field is accepted.
+//! let mut deprecations = Deprecations::new();
+//! deprecations.deprecate_field("Foo", "b");
+//! let diff = RegistryDiff::with_deprecations(&old, &new, &deprecations);
+//! assert!(!diff.is_breaking());
+//! ```
+
+use crate::{
+    compat::{CompatRules, Compatibility, EncodingProfile},
+    Conditions, ContainerFormat, ContainerId, Deprecations, Format, Named, Registry,
+    RegistryMetadata, VariantFormat,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+};
+
+/// The differences between two registries.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RegistryDiff {
+    /// The list of changes, sorted by container name.
+    pub changes: Vec<Change>,
+}
+
+/// A single difference between two registries.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Change {
+    /// The qualified name of the entity that changed, using the same conventions as
+    /// [`Deprecations`]. Tuple elements are named after their positions.
+    pub path: Vec<String>,
+    /// What changed.
+    pub kind: ChangeKind,
+    /// Whether the entity was marked as deprecated in the old registry.
+    pub deprecated: bool,
+    /// For a field added to or removed from a struct, whether the version of the struct
+    /// without the field is known to ignore unknown fields (see
+    /// [`RegistryMetadata::denies_unknown_fields`]). Always false unless the diff is
+    /// computed by `RegistryDiff::with_metadata`.
+    pub unknown_field_ignored: bool,
+}
+
+/// Description of a change.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ChangeKind {
+    /// A container was added.
+    ContainerAdded,
+    /// A container was removed.
+    ContainerRemoved,
+    /// The condition under which a container exists changed (see [`Conditions`]). `None`
+    /// means that the container exists in every configuration.
+    ConditionChanged {
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// A container changed in a way that is not described more precisely by other
+    /// kinds of changes, e.g. a struct was turned into an enum.
+    ContainerChanged {
+        old: ContainerFormat,
+        new: ContainerFormat,
+    },
+    /// A named field was added at the given position.
+    FieldAdded { index: usize, format: Format },
+    /// A named field was removed from the given (old) position.
+    FieldRemoved { index: usize, format: Format },
+    /// The format of a field or a tuple element changed.
+    FieldChanged { old: Format, new: Format },
+    /// An element was added to a tuple struct or a tuple variant at the given position.
+    /// `trailing` tells whether it was appended after the existing elements rather than
+    /// inserted before some of them, which shifts their positions.
+    ElementAdded {
+        index: usize,
+        format: Format,
+        trailing: bool,
+    },
+    /// An element was removed from the given (old) position of a tuple struct or a tuple
+    /// variant. `trailing` tells whether no element followed it.
+    ElementRemoved {
+        index: usize,
+        format: Format,
+        trailing: bool,
+    },
+    /// The relative order of the fields present in both versions of a struct or a struct
+    /// variant changed. The names of these fields are given in the old and the new order.
+    FieldsReordered { old: Vec<String>, new: Vec<String> },
+    /// The format of a field or a tuple element changed from `T` to `Option<T>`.
+    OptionAdded { format: Format },
+    /// The format of a field or a tuple element changed from `Option<T>` to `T`.
+    OptionRemoved { format: Format },
+    /// A variant was added.
+    VariantAdded { index: u32 },
+    /// A variant was removed.
+    VariantRemoved { index: u32 },
+    /// The variant with the given index was renamed. The path contains the new name.
+    VariantRenamed { index: u32, old_name: String },
+    /// A variant changed in a way that is not described more precisely by other kinds
+    /// of changes, e.g. a unit variant became a newtype variant.
+    VariantChanged {
+        old: VariantFormat,
+        new: VariantFormat,
+    },
+}
+
+impl Change {
+    /// Whether the change breaks the compatibility of binary encodings such as Bincode
+    /// and BCS, in the sense that some serialized data cannot be exchanged between the
+    /// old and the new definitions anymore.
+    /// * Adding containers or enum variants is considered compatible.
+    /// * Adding or changing the condition of a container is breaking, since the container
+    ///   may be missing from configurations where it used to exist. Removing a condition is
+    ///   compatible.
+    /// * Removing a deprecated member is considered compatible.
+    /// * Renaming a variant is compatible since variant names are not serialized.
+    /// * Reordering fields is breaking since fields are serialized by position.
+    pub fn is_breaking(&self) -> bool {
+        use ChangeKind::*;
+        match &self.kind {
+            ContainerAdded | VariantAdded { .. } | VariantRenamed { .. } => false,
+            ContainerRemoved
+            | FieldRemoved { .. }
+            | ElementRemoved { .. }
+            | VariantRemoved { .. } => !self.deprecated,
+            ConditionChanged { new, .. } => new.is_some(),
+            ContainerChanged { .. }
+            | FieldAdded { .. }
+            | ElementAdded { .. }
+            | FieldChanged { .. }
+            | FieldsReordered { .. }
+            | OptionAdded { .. }
+            | OptionRemoved { .. }
+            | VariantChanged { .. } => true,
+        }
+    }
+
+    /// Same as `is_breaking` but changes of field formats and field orders are classified
+    /// with the rules of a particular encoding. See [`CompatRules`].
+    /// * If the encoding allows unknown fields, adding or removing an optional field of a
+    ///   struct is compatible when the version of the struct without the field ignores
+    ///   unknown fields (see `unknown_field_ignored`): that version skips the field, and the
+    ///   other one reads it as `None` when it is missing.
+    pub fn is_breaking_with(&self, rules: &CompatRules) -> bool {
+        match &self.kind {
+            ChangeKind::FieldAdded { format, .. } | ChangeKind::FieldRemoved { format, .. }
+                if rules.allows_unknown_fields()
+                    && self.unknown_field_ignored
+                    && matches!(format, Format::Option(_)) =>
+            {
+                false
+            }
+            ChangeKind::FieldChanged { old, new } => {
+                rules.format_change(old, new) == Compatibility::Breaking
+            }
+            ChangeKind::OptionAdded { format } => {
+                let new = Format::Option(Box::new(format.clone()));
+                rules.format_change(format, &new) == Compatibility::Breaking
+            }
+            ChangeKind::OptionRemoved { format } => {
+                let old = Format::Option(Box::new(format.clone()));
+                rules.format_change(&old, format) == Compatibility::Breaking
+            }
+            ChangeKind::FieldsReordered { .. } => !rules.allows_field_reordering(),
+            _ => self.is_breaking(),
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.join("::"), Description(self))
+    }
+}
+
+/// The description of a change, without its path.
+struct Description<'a>(&'a Change);
+
+impl fmt::Display for Description<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ChangeKind::*;
+        match &self.0.kind {
+            ContainerAdded => write!(f, "container added")?,
+            ContainerRemoved => write!(f, "container removed")?,
+            ConditionChanged { old, new } => {
+                let describe = |condition: &Option<String>| match condition {
+                    Some(condition) => format!("`{condition}`"),
+                    None => "none".to_string(),
+                };
+                write!(
+                    f,
+                    "condition changed from {} to {}",
+                    describe(old),
+                    describe(new)
+                )?
+            }
+            ContainerChanged { old, new } => {
+                write!(f, "container changed from {old:?} to {new:?}")?
+            }
+            FieldAdded { index, format } => {
+                write!(f, "field added at position {index} with format {format:?}")?
+            }
+            FieldRemoved { index, format } => write!(
+                f,
+                "field removed from position {index} with format {format:?}"
+            )?,
+            FieldChanged { old, new } => write!(f, "format changed from {old:?} to {new:?}")?,
+            ElementAdded {
+                index,
+                format,
+                trailing,
+            } => write!(
+                f,
+                "element {} at position {index} with format {format:?}",
+                if *trailing { "appended" } else { "inserted" }
+            )?,
+            ElementRemoved {
+                index,
+                format,
+                trailing,
+            } => write!(
+                f,
+                "{} element removed from position {index} with format {format:?}",
+                if *trailing { "trailing" } else { "inner" }
+            )?,
+            FieldsReordered { old, new } => write!(
+                f,
+                "fields reordered from ({}) to ({})",
+                old.join(", "),
+                new.join(", ")
+            )?,
+            OptionAdded { format } => write!(f, "format {format:?} wrapped into an option")?,
+            OptionRemoved { format } => write!(f, "format {format:?} unwrapped from an option")?,
+            VariantAdded { index } => write!(f, "variant added with index {index}")?,
+            VariantRemoved { index } => write!(f, "variant removed from index {index}")?,
+            VariantRenamed { index, old_name } => {
+                write!(f, "variant {index} renamed from {old_name}")?
+            }
+            VariantChanged { old, new } => write!(f, "variant changed from {old:?} to {new:?}")?,
+        }
+        if self.0.deprecated {
+            write!(f, " (deprecated)")?;
+        }
+        Ok(())
+    }
+}
+
+impl RegistryDiff {
+    /// Compute the changes from the registry `old` to the registry `new`.
+    pub fn new(old: &Registry, new: &Registry) -> Self {
+        Self::with_deprecations(old, new, &Deprecations::new())
+    }
+
+    /// Same as `new` but taking into account the members marked as deprecated in `old`.
+    pub fn with_deprecations(old: &Registry, new: &Registry, deprecations: &Deprecations) -> Self {
+        let no_conditions = Conditions::new();
+        Self::with_conditions(old, &no_conditions, new, &no_conditions, deprecations)
+    }
+
+    /// Same as `with_deprecations` but also reporting the containers present in both
+    /// registries whose conditions changed (see [`Conditions`]).
+    pub fn with_conditions(
+        old: &Registry,
+        old_conditions: &Conditions,
+        new: &Registry,
+        new_conditions: &Conditions,
+        deprecations: &Deprecations,
+    ) -> Self {
+        let no_metadata = RegistryMetadata::new();
+        Self::compute(
+            (old, old_conditions, &no_metadata),
+            (new, new_conditions, &no_metadata),
+            deprecations,
+        )
+    }
+
+    /// Same as `with_deprecations` but also recording whether the structs with added or
+    /// removed fields ignore unknown fields, according to the metadata of the registries
+    /// (see `Change::unknown_field_ignored`).
+    pub fn with_metadata(
+        old: &Registry,
+        old_metadata: &RegistryMetadata,
+        new: &Registry,
+        new_metadata: &RegistryMetadata,
+        deprecations: &Deprecations,
+    ) -> Self {
+        let no_conditions = Conditions::new();
+        Self::compute(
+            (old, &no_conditions, old_metadata),
+            (new, &no_conditions, new_metadata),
+            deprecations,
+        )
+    }
+
+    fn compute(
+        (old, old_conditions, old_metadata): (&Registry, &Conditions, &RegistryMetadata),
+        (new, new_conditions, new_metadata): (&Registry, &Conditions, &RegistryMetadata),
+        deprecations: &Deprecations,
+    ) -> Self {
+        let mut differ = Differ {
+            deprecations,
+            old_metadata,
+            new_metadata,
+            changes: Vec::new(),
+        };
+        let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let path = vec![name.clone()];
+            match (old.get(name), new.get(name)) {
+                (Some(old), Some(new)) => {
+                    let old_condition = old_conditions.condition(name);
+                    let new_condition = new_conditions.condition(name);
+                    if old_condition != new_condition {
+                        differ.push(
+                            path.clone(),
+                            ChangeKind::ConditionChanged {
+                                old: old_condition.map(String::from),
+                                new: new_condition.map(String::from),
+                            },
+                        );
+                    }
+                    differ.diff_container(path, old, new)
+                }
+                (Some(_), None) => differ.push(path, ChangeKind::ContainerRemoved),
+                (None, Some(_)) => differ.push(path, ChangeKind::ContainerAdded),
+                (None, None) => unreachable!(),
+            }
+        }
+        Self {
+            changes: differ.changes,
+        }
+    }
+
+    /// Whether the two registries are identical.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Iterate over the changes of the container `id`, including its fields and variants.
+    pub fn changes_of<'a>(&'a self, id: &'a ContainerId) -> impl Iterator<Item = &'a Change> {
+        self.changes
+            .iter()
+            .filter(move |change| change.path.first().map(String::as_str) == Some(id.as_str()))
+    }
+
+    /// Whether at least one change is breaking. See `Change::is_breaking`.
+    pub fn is_breaking(&self) -> bool {
+        self.changes.iter().any(Change::is_breaking)
+    }
+
+    /// Iterate over the breaking changes.
+    pub fn breaking_changes(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter().filter(|change| change.is_breaking())
+    }
+
+    /// Whether at least one change is breaking for the encoding described by `rules`.
+    pub fn is_breaking_with(&self, rules: &CompatRules) -> bool {
+        self.changes
+            .iter()
+            .any(|change| change.is_breaking_with(rules))
+    }
+
+    /// Iterate over the changes that are breaking for the encoding described by `rules`.
+    pub fn breaking_changes_with<'a>(
+        &'a self,
+        rules: &'a CompatRules,
+    ) -> impl Iterator<Item = &'a Change> {
+        self.changes
+            .iter()
+            .filter(move |change| change.is_breaking_with(rules))
+    }
+
+    /// Render the changes as a Markdown document with the given title, e.g. for release
+    /// notes. Changes are grouped by container, then into additions, removals, and
+    /// modifications. Breaking changes are marked with the encoding profiles for which they
+    /// are breaking, or simply as breaking (see `Change::is_breaking`) if `profiles` is empty.
+    pub fn to_changelog_markdown(&self, title: &str, profiles: &[EncodingProfile]) -> String {
+        let rules: Vec<_> = profiles
+            .iter()
+            .map(|profile| (profile, profile.compat_rules()))
+            .collect();
+        let mut out = format!("# {title}\n");
+        if self.changes.is_empty() {
+            out.push_str("\nNo changes.\n");
+            return out;
+        }
+        let mut containers: BTreeMap<&str, [Vec<&Change>; 3]> = BTreeMap::new();
+        for change in &self.changes {
+            let group = match change.kind {
+                ChangeKind::ContainerAdded
+                | ChangeKind::FieldAdded { .. }
+                | ChangeKind::ElementAdded { .. }
+                | ChangeKind::VariantAdded { .. } => 0,
+                ChangeKind::ContainerRemoved
+                | ChangeKind::FieldRemoved { .. }
+                | ChangeKind::ElementRemoved { .. }
+                | ChangeKind::VariantRemoved { .. } => 1,
+                _ => 2,
+            };
+            containers.entry(&change.path[0]).or_default()[group].push(change);
+        }
+        for (name, groups) in containers {
+            let _ = write!(out, "\n## `{name}`\n");
+            for (heading, changes) in ["Added", "Removed", "Modified"].iter().zip(groups) {
+                if changes.is_empty() {
+                    continue;
+                }
+                let _ = write!(out, "\n### {heading}\n\n");
+                for change in changes {
+                    let _ = write!(
+                        out,
+                        "- `{}`: {}",
+                        change.path.join("::"),
+                        Description(change)
+                    );
+                    if rules.is_empty() {
+                        if change.is_breaking() {
+                            out.push_str(" **Breaking**");
+                        }
+                    } else {
+                        let breaking: Vec<_> = rules
+                            .iter()
+                            .filter(|(_, rules)| change.is_breaking_with(rules))
+                            .map(|(profile, _)| profile.to_string())
+                            .collect();
+                        if !breaking.is_empty() {
+                            let _ = write!(out, " **Breaking** ({})", breaking.join(", "));
+                        }
+                    }
+                    out.push('\n');
+                }
+            }
+        }
+        out
+    }
+}
+
+struct Differ<'a> {
+    deprecations: &'a Deprecations,
+    old_metadata: &'a RegistryMetadata,
+    new_metadata: &'a RegistryMetadata,
+    changes: Vec<Change>,
+}
+
+fn extend(path: &[String], name: impl Into<String>) -> Vec<String> {
+    let mut path = path.to_vec();
+    path.push(name.into());
+    path
+}
+
+impl Differ<'_> {
+    fn push(&mut self, path: Vec<String>, kind: ChangeKind) {
+        let deprecated = self.deprecations.is_deprecated(&path);
+        // The version of the struct without the field sees it as an unknown field. Fields
+        // of struct variants are not concerned.
+        let metadata = match kind {
+            ChangeKind::FieldAdded { .. } => Some(self.old_metadata),
+            ChangeKind::FieldRemoved { .. } => Some(self.new_metadata),
+            _ => None,
+        };
+        let unknown_field_ignored = path.len() == 2
+            && metadata
+                .is_some_and(|metadata| metadata.denies_unknown_fields(&path[0]) == Some(false));
+        self.changes.push(Change {
+            path,
+            kind,
+            deprecated,
+            unknown_field_ignored,
+        });
+    }
+
+    fn push_format_change(&mut self, path: Vec<String>, old: &Format, new: &Format) {
+        let kind = match (old, new) {
+            (_, Format::Option(inner)) if old == inner.as_ref() => ChangeKind::OptionAdded {
+                format: old.clone(),
+            },
+            (Format::Option(inner), _) if new == inner.as_ref() => ChangeKind::OptionRemoved {
+                format: new.clone(),
+            },
+            _ => ChangeKind::FieldChanged {
+                old: old.clone(),
+                new: new.clone(),
+            },
+        };
+        self.push(path, kind);
+    }
+
+    fn diff_container(&mut self, path: Vec<String>, old: &ContainerFormat, new: &ContainerFormat) {
+        use ContainerFormat::*;
+        match (old, new) {
+            (UnitStruct, UnitStruct) => (),
+            (NewTypeStruct(old), NewTypeStruct(new)) => {
+                self.diff_elements(
+                    &path,
+                    std::slice::from_ref(old.as_ref()),
+                    std::slice::from_ref(new.as_ref()),
+                );
+            }
+            (TupleStruct(old_formats), TupleStruct(new_formats)) => {
+                self.diff_elements(&path, old_formats, new_formats);
+            }
+            (Struct(old_fields), Struct(new_fields)) => {
+                self.diff_fields(&path, old_fields, new_fields);
+            }
+            (Enum(old_variants), Enum(new_variants)) => {
+                self.diff_variants(&path, old_variants, new_variants);
+            }
+            (
+                Generic {
+                    params: old_params,
+                    body: old_body,
+                },
+                Generic {
+                    params: new_params,
+                    body: new_body,
+                },
+            ) if old_params == new_params => self.diff_container(path, old_body, new_body),
+            _ => {
+                if old != new {
+                    self.push(
+                        path,
+                        ChangeKind::ContainerChanged {
+                            old: old.clone(),
+                            new: new.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Compare tuple elements by position. When the number of elements changed and the
+    /// other elements are unchanged, the added or removed elements are located between the
+    /// longest common prefix and suffix. Otherwise, elements are compared position by
+    /// position and the extra elements are considered trailing.
+    fn diff_elements(&mut self, path: &[String], old: &[Format], new: &[Format]) {
+        let min_len = old.len().min(new.len());
+        let prefix = old
+            .iter()
+            .zip(new)
+            .take_while(|(old, new)| old == new)
+            .count();
+        let suffix = old
+            .iter()
+            .rev()
+            .zip(new.iter().rev())
+            .take(min_len - prefix)
+            .take_while(|(old, new)| old == new)
+            .count();
+        let (start, old_end, new_end) = if prefix + suffix == min_len {
+            (prefix, old.len() - suffix, new.len() - suffix)
+        } else {
+            for (index, (old, new)) in old.iter().zip(new).enumerate() {
+                if old != new {
+                    self.push_format_change(extend(path, index.to_string()), old, new);
+                }
+            }
+            (min_len, old.len(), new.len())
+        };
+        for (index, format) in old.iter().enumerate().take(old_end).skip(start) {
+            self.push(
+                extend(path, index.to_string()),
+                ChangeKind::ElementRemoved {
+                    index,
+                    format: format.clone(),
+                    trailing: old_end == old.len(),
+                },
+            );
+        }
+        for (index, format) in new.iter().enumerate().take(new_end).skip(start) {
+            self.push(
+                extend(path, index.to_string()),
+                ChangeKind::ElementAdded {
+                    index,
+                    format: format.clone(),
+                    trailing: new_end == new.len(),
+                },
+            );
+        }
+    }
+
+    /// Compare named fields by name.
+    fn diff_fields(&mut self, path: &[String], old: &[Named<Format>], new: &[Named<Format>]) {
+        let old_positions: BTreeMap<_, _> = old
+            .iter()
+            .enumerate()
+            .map(|(index, field)| (field.name.as_str(), index))
+            .collect();
+        let new_positions: BTreeMap<_, _> = new
+            .iter()
+            .enumerate()
+            .map(|(index, field)| (field.name.as_str(), index))
+            .collect();
+        let old_common: Vec<_> = old
+            .iter()
+            .filter(|field| new_positions.contains_key(field.name.as_str()))
+            .map(|field| field.name.as_str())
+            .collect();
+        let new_common: Vec<_> = new
+            .iter()
+            .filter(|field| old_positions.contains_key(field.name.as_str()))
+            .map(|field| field.name.as_str())
+            .collect();
+        if old_common != new_common {
+            self.push(
+                path.to_vec(),
+                ChangeKind::FieldsReordered {
+                    old: old_common.into_iter().map(String::from).collect(),
+                    new: new_common.into_iter().map(String::from).collect(),
+                },
+            );
+        }
+        for (index, field) in old.iter().enumerate() {
+            match new_positions.get(field.name.as_str()) {
+                None => self.push(
+                    extend(path, &field.name),
+                    ChangeKind::FieldRemoved {
+                        index,
+                        format: field.value.clone(),
+                    },
+                ),
+                Some(new_index) => {
+                    let new_format = &new[*new_index].value;
+                    if &field.value != new_format {
+                        self.push_format_change(
+                            extend(path, &field.name),
+                            &field.value,
+                            new_format,
+                        );
+                    }
+                }
+            }
+        }
+        for (index, field) in new.iter().enumerate() {
+            if !old_positions.contains_key(field.name.as_str()) {
+                self.push(
+                    extend(path, &field.name),
+                    ChangeKind::FieldAdded {
+                        index,
+                        format: field.value.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    fn diff_variants(
+        &mut self,
+        path: &[String],
+        old: &BTreeMap<u32, Named<VariantFormat>>,
+        new: &BTreeMap<u32, Named<VariantFormat>>,
+    ) {
+        for (index, old_variant) in old {
+            let new_variant = match new.get(index) {
+                Some(variant) => variant,
+                None => {
+                    self.push(
+                        extend(path, &old_variant.name),
+                        ChangeKind::VariantRemoved { index: *index },
+                    );
+                    continue;
+                }
+            };
+            let variant_path = extend(path, &new_variant.name);
+            if old_variant.name != new_variant.name {
+                self.push(
+                    variant_path.clone(),
+                    ChangeKind::VariantRenamed {
+                        index: *index,
+                        old_name: old_variant.name.clone(),
+                    },
+                );
+            }
+            self.diff_variant(variant_path, &old_variant.value, &new_variant.value);
+        }
+        for (index, new_variant) in new {
+            if !old.contains_key(index) {
+                self.push(
+                    extend(path, &new_variant.name),
+                    ChangeKind::VariantAdded { index: *index },
+                );
+            }
+        }
+    }
+
+    fn diff_variant(&mut self, path: Vec<String>, old: &VariantFormat, new: &VariantFormat) {
+        use VariantFormat::*;
+        match (old, new) {
+            (Unit, Unit) => (),
+            (NewType(old), NewType(new)) => {
+                self.diff_elements(
+                    &path,
+                    std::slice::from_ref(old.as_ref()),
+                    std::slice::from_ref(new.as_ref()),
+                );
+            }
+            (Tuple(old_formats), Tuple(new_formats)) => {
+                self.diff_elements(&path, old_formats, new_formats);
+            }
+            (Struct(old_fields), Struct(new_fields)) => {
+                self.diff_fields(&path, old_fields, new_fields);
+            }
+            _ => {
+                if old != new {
+                    self.push(
+                        path,
+                        ChangeKind::VariantChanged {
+                            old: old.clone(),
+                            new: new.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright (c) Facebook, Inc. and its affiliates
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use crate::compat::NumericType;
 use serde::{de, ser};
 use std::fmt;
 use thiserror::Error;
@@ -21,12 +22,111 @@ pub enum Error {
     UnexpectedDeserializationFormat(&'static str, crate::ContainerFormat, &'static str),
     #[error("Incompatible formats detected: {0} {1}")]
     Incompatible(String, String),
+    #[error(
+        "Conflicting formats {first} and {second} were traced {}",
+        describe_location(.container, .field_path)
+    )]
+    ConflictingFormats {
+        /// The innermost container being traced, if any.
+        container: Option<String>,
+        /// The names of the variant and/or fields leading to the value, relative to `container`.
+        field_path: Vec<String>,
+        /// The format traced first.
+        first: String,
+        /// The format traced next, which does not agree with `first`.
+        second: String,
+    },
     #[error("Incomplete tracing detected")]
     UnknownFormat,
     #[error("Incomplete tracing detected inside container: {0}")]
     UnknownFormatInContainer(String),
     #[error("Missing variants detected for specific enums: {0:?}")]
     MissingVariants(Vec<String>),
+    #[error("Container not found in registry: {0}")]
+    MissingContainer(String),
+    #[error("Name {0} matches several containers: {}", .1.join(", "))]
+    AmbiguousName(String, Vec<String>),
+    #[error("Format of frozen container {0} (version {1}) has changed")]
+    FrozenContainerChanged(String, u32),
+    #[error("Container already present in registry: {0}")]
+    ContainerExists(String),
+    #[error("Distinct Rust types share the same container name: {0}")]
+    NameClash(String),
+    #[error("A previous tracing call was interrupted by a panic")]
+    TracerPoisoned,
+    #[error("Invalid samples file: {0}")]
+    InvalidSamples(String),
+    #[error("Invalid tracing checkpoint: {0}")]
+    InvalidCheckpoint(String),
+    #[error("Invalid registry file: {0}")]
+    InvalidRegistryFile(String),
+    #[error("Failed to trace {0}: {1}")]
+    RootFailed(String, Box<Error>),
+    #[error("Registry does not agree with the actual encoding of {0}")]
+    RoundTripMismatch(String),
+    #[error(
+        "Type requires a self-describing format: deserialize_any was called {}",
+        describe_location(.container, .field_path)
+    )]
+    RequiresSelfDescribingFormat {
+        /// The innermost container being traced, if any.
+        container: Option<String>,
+        /// The names of the variant and/or fields leading to the value, relative to `container`.
+        field_path: Vec<String>,
+    },
+    #[error("Values of the same map have incompatible formats: {0:?} and {1:?}")]
+    IncompatibleMapValues(crate::Format, crate::Format),
+    #[error("Invalid declaration in the tracer configuration: {0}")]
+    InvalidDeclaration(String),
+    #[error("No exporter is registered under the name {0}")]
+    UnknownExporter(String),
+    #[error("Exporter {exporter} does not support {construct}, used in container {container}")]
+    UnsupportedConstruct {
+        /// The name of the exporter.
+        exporter: String,
+        /// A container using the construct.
+        container: String,
+        /// The unsupported construct.
+        construct: crate::export::Construct,
+    },
+    #[error("Tracing of enum {0} did not converge after {1} passes")]
+    NoConvergence(String, usize),
+    #[error(
+        "Forbidden type {type_name} traced {}",
+        describe_location(.container, .field_path)
+    )]
+    ForbiddenType {
+        /// The forbidden type, as given to `TracerConfig::forbid_type`.
+        type_name: String,
+        /// The innermost container being traced, if any.
+        container: Option<String>,
+        /// The names of the variant and/or fields leading to the value, relative to `container`.
+        field_path: Vec<String>,
+    },
+    #[error(
+        "Forbidden format {format:?} used {}",
+        describe_location(&Some(.container.clone()), .field_path)
+    )]
+    ForbiddenFormat {
+        /// The forbidden format.
+        format: crate::Format,
+        /// The container using the format.
+        container: String,
+        /// The names of the variant and/or field using the format.
+        field_path: Vec<String>,
+    },
+    #[error(
+        "Floating-point member {} may be NaN or infinite",
+        describe_location(&Some(.container.clone()), .field_path)
+    )]
+    UnspecifiedFloat {
+        /// The container of the member.
+        container: String,
+        /// The names of the variant and/or field of the member.
+        field_path: Vec<String>,
+    },
+    #[error("Type {0} is uninhabited: no value of it can be deserialized")]
+    Uninhabited(String),
 }
 
 impl ser::Error for Error {
@@ -65,7 +165,8 @@ are generally not supported. This includes: `#[serde(flatten)]`, `#[serde(tag =
 `#[serde(tag = "t", content = "c")]`, and `#[serde(untagged)]`.
 
 To fix this, avoid unsupported Serde attributes or use custom (de)serialize implementations with different
-behaviors depending on the Serde callback `(De)Serializer::is_human_readable()`.
+behaviors depending on the Serde callback `(De)Serializer::is_human_readable()`. Alternatively, supply the
+formats of such locations with `TracerConfig::fallback_handler`.
 "#.to_string()
             }
             DeserializationError(_) => {
@@ -90,6 +191,18 @@ the Serialize and Deserialize traits do not agree, e.g. if one uses `Vec<u8>` an
 
 Verify the implementations of Serialize and Deserialize for the given format.
 "#.to_string()
+            }
+            ConflictingFormats { container, field_path, .. } => {
+                let location = describe_location(container, field_path);
+                format!(r#"
+The same value, traced {location}, was given two different formats, e.g. `U32` by `trace_value`
+and `U64` by `trace_type`. This happens when custom implementations of the Serialize and
+Deserialize traits do not agree, in which case the encoded values could not be read back.
+
+Verify the implementations of Serialize and Deserialize for the given container. If the
+Deserialize implementation deliberately accepts both formats, enable `TracerConfig::widen_formats`
+to record the widest one instead.
+"#)
             }
             UnknownFormat => {
                 r#"
@@ -115,8 +228,308 @@ inside the given enums {names:?}.
 
 To fix this, make sure to call `tracer.trace_type<T>(..)` at least once for each enum type `T` in the
 corpus of definitions. You may also use `tracer.registry_unchecked()` for debugging.
+"#)
+            }
+            AmbiguousName(..) => {
+                r#"
+A name given to look up a container matches several containers of the registry, e.g. because
+the lookup ignores case or accepts module-qualified names.
+
+To fix this, use one of the candidates listed in the error as the exact name.
+"#.to_string()
+            }
+            MissingContainer(name) => {
+                format!(r#"
+The container {name} was expected in the registry but could not be found. This can happen if the
+container was renamed (e.g. with `#[serde(rename = ..)]`) or if it is no longer reachable from the
+traced types.
+
+Verify the name of the container or trace the types that depend on it.
+"#)
+            }
+            FrozenContainerChanged(name, version) => {
+                format!(r#"
+The container {name} was frozen at version {version} but its format has changed since then. Released
+formats must not change silently.
+
+To fix this, revert the change to the format, or bump the version of the container and freeze it
+again with `frozen.freeze(&registry, ["{name}"], new_version)`.
+"#)
+            }
+            ContainerExists(name) => {
+                format!(r#"
+A container was to be renamed into {name} but the registry already contains a container with this
+name. Renaming it would conflate the two formats.
+
+To fix this, pick another name, or rename or remove the existing container {name} first.
+"#)
+            }
+            NameClash(name) => {
+                format!(r#"
+Two Rust enums with different variants were traced under the same container name {name}, or two
+types with different attributes were registered under this name with `attributes::register`. Since
+containers are indexed by their (non-qualified) names, their formats or attributes would otherwise
+be conflated. This typically happens with types of the same name defined in different modules.
+
+To fix this, give each type a distinct name with `#[serde(rename = ..)]`.
+"#)
+            }
+            TracerPoisoned => {
+                r#"
+A user-provided implementation of Serialize or Deserialize panicked during a previous tracing call.
+The state of the tracer may be inconsistent, so it cannot be used anymore.
+
+To fix this, create a new tracer with `Tracer::new`, and make sure to provide samples with
+`tracer.trace_value` for the types whose (de)serialization panics on the default values.
+"#.to_string()
+            }
+            InvalidSamples(_) => {
+                r#"
+A file of samples could not be loaded, either because it is malformed or because it was written
+with an incompatible version of serde-reflection.
+
+To fix this, delete the file and record the samples again with `tracer.trace_value`, then save them
+with `samples.save(..)`.
+"#.to_string()
+            }
+            InvalidCheckpoint(_) => {
+                r#"
+A checkpoint given to `Tracer::restore` is inconsistent or was saved by an incompatible version of
+serde-reflection.
+
+To fix this, discard the checkpoint and trace the types again from scratch.
+"#.to_string()
+            }
+            InvalidRegistryFile(_) => {
+                r#"
+A serialized registry could not be read, either because it is malformed or because it was written
+with a version of the registry format newer than `registry::FORMAT_VERSION`.
+
+To fix this, upgrade serde-reflection, or trace the registry again.
+"#.to_string()
+            }
+            RootFailed(_, error) => error.explanation(),
+            RoundTripMismatch(_) => {
+                r#"
+A value was encoded by a real serializer in a way that does not match the formats of the registry.
+This typically happens when a hand-written implementation of Serialize diverges from the
+corresponding implementation of Deserialize, or when the registry is outdated.
+
+To fix this, make sure that Serialize and Deserialize agree for the type mentioned in the error,
+then trace the registry again.
+"#.to_string()
+            }
+            RequiresSelfDescribingFormat { container, field_path } => {
+                let location = describe_location(container, field_path);
+                format!(r#"
+A type traced {location} implements Deserialize by calling `deserialize_any`. This is the case of
+untagged enums and of many hand-written implementations. Such types can only be read from
+self-describing encodings (e.g. JSON), whereas the tracing deserializer does not describe its input.
+
+To fix this, either:
+* enable `TracerConfig::self_describing` and call `tracer.trace_value` first with a value of the
+  enclosing container, so that the recorded formats can drive the calls to `deserialize_any`, or
+* enable the `TracerConfig::record_samples_for_*` option matching the enclosing container and call
+  `tracer.trace_value` first, so that deserialization tracing uses the recorded sample instead.
+"#)
+            }
+            IncompatibleMapValues(_, _) => {
+                r#"
+A map traced by `trace_value` contains values whose formats cannot be unified or widened into a
+common format. This happens with dynamically typed values, such as `serde_json::Value`.
+
+To fix this, enable `TracerConfig::union_formats` to record the values as a `Format::Union`,
+or trace a sample whose map values all have the same shape.
+"#.to_string()
+            }
+            InvalidDeclaration(_) => {
+                r#"
+A declaration of `TracerConfig`, such as `declare_fixed_bytes`, does not match the traced formats.
+
+To fix this, verify the names of the container and the field given in the declaration.
+"#.to_string()
+            }
+            UnknownExporter(_) => {
+                r#"
+No exporter with the given name was found in the collection of exporters.
+
+To fix this, use one of the names returned by `Exporters::names`, or register the exporter with
+`Exporters::register` first.
+"#.to_string()
+            }
+            UnsupportedConstruct { .. } => {
+                r#"
+The registry uses a construct that the schema language of the exporter cannot express.
+
+To fix this, change the traced Rust types to avoid the construct (e.g. use a struct instead of
+a tuple), or choose another exporter.
+"#.to_string()
+            }
+            NoConvergence(_, _) => {
+                r#"
+Tracing an enum by deserialization needs at most two passes per variant: one to visit each
+variant by name and one to match each name with its index. More passes were needed, which
+happens when the Deserialize implementation of the enum is not deterministic or maps several
+indices to the same variant (e.g. aliases).
+
+To fix this, mark the variants that cannot be visited with `TracerConfig::skip_variant`, or
+trace the enum with `trace_value` on samples of each variant.
+"#.to_string()
+            }
+            ForbiddenType { .. } => {
+                r#"
+A value of a type ruled out by `TracerConfig::forbid_type` is reachable from a traced type.
+
+To fix this, replace the type at the location mentioned in the error, or remove it from the
+forbidden types if the policy has changed.
+"#.to_string()
+            }
+            ForbiddenFormat { .. } => {
+                r#"
+A container uses a format ruled out by `TracerConfig::forbid_format`, e.g. a floating-point number
+in a message that must be encoded deterministically.
+
+To fix this, change the type of the field mentioned in the error, e.g. use a fixed-point integer
+instead of a float, or remove the format from the forbidden formats if the policy has changed.
+"#.to_string()
+            }
+            UnspecifiedFloat { .. } => {
+                r#"
+`TracerConfig::float_policy` requires every floating-point member to declare whether it may carry
+NaN or infinite values, since languages disagree on their encoding and comparison.
+
+To fix this, declare the member mentioned in the error with `TracerConfig::constrain` and
+`Constraint::new().finite(true)` (or `finite(false)` if non-finite values are expected).
+"#.to_string()
+            }
+            Uninhabited(name) => {
+                format!(r#"
+The traced type cannot be deserialized because it requires a value of {name}, which has none
+(e.g. an enum without variants). The type {name} is recorded as an enum without variants.
+Variants, options, and sequences containing it are traced as if it did not exist.
+
+To fix this, trace the types that contain {name} in a variant, an option, or a sequence
+instead of the uninhabited type itself.
 "#)
             }
         }
     }
 }
+
+/// A suggested fix for a tracing error caused by a common Serde pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Hint {
+    /// A `NonZero` integer type rejected the default value `0`.
+    NonZeroInteger(NumericType),
+    /// A type implements Deserialize by calling `deserialize_any`, e.g. an untagged or
+    /// internally tagged enum.
+    SelfDescribing {
+        /// The innermost container being traced, if any.
+        container: Option<String>,
+    },
+    /// A hand-written implementation of Deserialize, such as a validating constructor,
+    /// rejected the candidate value.
+    RecordSample,
+    /// A `Mutex` or `RwLock` was poisoned, or a `RefCell` was mutably borrowed, while the
+    /// value holding it was serialized.
+    LockUnavailable,
+}
+
+impl Hint {
+    /// The suggested call to `TracerConfig` or `Tracer` as Rust code.
+    pub fn suggestion(&self) -> String {
+        match self {
+            Hint::NonZeroInteger(value) => {
+                let value = format!("{value:?}").to_lowercase();
+                format!("TracerConfig::default().default_{value}_value(1)")
+            }
+            Hint::SelfDescribing { .. } => {
+                "TracerConfig::default().self_describing(true) and tracer.trace_value(&mut samples, &value)"
+                    .to_string()
+            }
+            Hint::RecordSample | Hint::LockUnavailable => {
+                "tracer.trace_value(&mut samples, &value)".to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hint::NonZeroInteger(value) => write!(
+                f,
+                "A NonZero type rejected the default {value:?} value 0: use `{}`",
+                self.suggestion()
+            ),
+            Hint::SelfDescribing { container } => write!(
+                f,
+                "{} requires a self-describing format: use `{}` with a valid value",
+                container.as_deref().unwrap_or("The traced type"),
+                self.suggestion()
+            ),
+            Hint::RecordSample => write!(
+                f,
+                "A Deserialize implementation rejected the candidate value: record a valid value \
+                 with `{}` before tracing the type, and enable the matching \
+                 `TracerConfig::record_samples_for_*` option",
+                self.suggestion()
+            ),
+            Hint::LockUnavailable => write!(
+                f,
+                "A lock or RefCell could not be accessed during serialization: call `{}` with a \
+                 value that is not poisoned or borrowed elsewhere, e.g. a fresh one",
+                self.suggestion()
+            ),
+        }
+    }
+}
+
+impl Error {
+    /// Recognize the Serde pattern that is the likely cause of a tracing error, if any.
+    pub fn hint(&self) -> Option<Hint> {
+        match self {
+            Error::Custom(message) => {
+                if let Some(message) = message.strip_prefix("Failed to serialize value: ") {
+                    // Messages of the implementations of Serialize for locks and `RefCell`.
+                    return [
+                        "\"lock poison error while serializing\"",
+                        "\"already mutably borrowed\"",
+                    ]
+                    .contains(&message)
+                    .then_some(Hint::LockUnavailable);
+                }
+                let message = message.strip_prefix("Failed to deserialize value: ")?;
+                Some(match nonzero_type(message) {
+                    Some(value) => Hint::NonZeroInteger(value),
+                    None => Hint::RecordSample,
+                })
+            }
+            Error::RequiresSelfDescribingFormat { container, .. } => Some(Hint::SelfDescribing {
+                container: container.clone(),
+            }),
+            Error::RootFailed(_, error) => error.hint(),
+            _ => None,
+        }
+    }
+}
+
+/// Recognize the error message of a `NonZero` integer type receiving `0`.
+fn nonzero_type(message: &str) -> Option<NumericType> {
+    let (_, rest) = message.split_once("expected a nonzero ")?;
+    let name: String = rest
+        .chars()
+        .take_while(char::is_ascii_alphanumeric)
+        .collect();
+    NumericType::ALL
+        .into_iter()
+        .find(|value| format!("{value:?}").eq_ignore_ascii_case(&name))
+}
+
+fn describe_location(container: &Option<String>, field_path: &[String]) -> String {
+    match container {
+        Some(name) if field_path.is_empty() => format!("inside container {name}"),
+        Some(name) => format!("at {name}::{}", field_path.join("::")),
+        None => "at the root of the traced type".to_string(),
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::Format;
+
+/// A low-level decision of the tracer, recorded when `TracerConfig::record_events` is enabled.
+///
+/// Events are meant for debugging: when a trace produces a surprising format, they show which
+/// calls of the `Serialize` and `Deserialize` implementations led to it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TraceEvent {
+    /// A `Serialize` implementation called the given method of the tracing serializer,
+    /// e.g. `"serialize_u32"`.
+    SerializerCall {
+        /// The name of the method.
+        method: &'static str,
+    },
+    /// A `Deserialize` implementation called the given method of the tracing deserializer,
+    /// e.g. `"deserialize_u32"`, which answered by calling the visitor.
+    VisitorHit {
+        /// The name of the method.
+        method: &'static str,
+    },
+    /// The tracing deserializer could not answer the given method and used the format
+    /// supplied by the fallback handler of the configuration.
+    FallbackUsed {
+        /// The name of the method.
+        method: &'static str,
+        /// The format supplied by the handler.
+        format: Format,
+    },
+    /// The recorded sample of a container was consulted during deserialization.
+    SampleConsulted {
+        /// The name of the container.
+        container: String,
+    },
+    /// The exploration of an enum advanced to a variant that was not visited yet. Variants are
+    /// visited by name first, then by index.
+    VariantAdvanced {
+        /// The name of the enum.
+        enum_name: String,
+        /// The name of the variant, when visited by name.
+        name: Option<String>,
+        /// The index of the variant, when visited by index.
+        index: Option<u32>,
+    },
+}
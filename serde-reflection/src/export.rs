@@ -0,0 +1,646 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Pluggable backends that write a registry in a schema language.
+//!
+//! An [`Exporter`] declares the constructs that it can express. [`Exporters`] collects
+//! exporters by name, so that the library and the command-line tool can discover the
+//! built-in backends and the ones registered by other crates in the same way. Registries
+//! using an unsupported construct are rejected before the exporter is called.
+//!
+//! Containers without content keep distinct representations, since self-describing
+//! encodings tell them apart: unit structs (`struct A;`) are `UNITSTRUCT`, empty structs
+//! (`struct A {}`) are `STRUCT: []`, and empty tuple structs (`struct A();`) are
+//! `TUPLESTRUCT: []`. Uninhabited types, such as `enum Void {}`, are recorded as enums
+//! without variants ([`Construct::EmptyEnum`]), written `ENUM: {}`. The `docs` exporter
+//! describes each of them explicitly. Exporters for languages without empty enums should
+//! reject the construct, or map these containers to a type that cannot be instantiated and
+//! the variants using them to variants that are never decoded.
+//!
+//! Exporters stream their output into an `io::Write` one container at a time, flushing after
+//! each container.
+//!
+//! ```rust
+//! use serde_reflection::{ContainerFormat, Format, Registry, Result};
+//! use serde_reflection::export::{Construct, Exporter, Exporters};
+//! use std::io::Write;
+//!
+//! struct Names;
+//!
+//! impl Exporter for Names {
+//!     fn name(&self) -> &str {
+//!         "names"
+//!     }
+//!
+//!     fn supports(&self, construct: Construct) -> bool {
+//!         construct != Construct::GenericContainer
+//!     }
+//!
+//!     fn export_container(
+//!         &self,
+//!         _index: usize,
+//!         name: &str,
+//!         _format: &ContainerFormat,
+//!         out: &mut dyn Write,
+//!     ) -> Result<()> {
+//!         writeln!(out, "{name}").map_err(serde_reflection::export::write_error)
+//!     }
+//! }
+//!
+//! let mut exporters = Exporters::default();
+//! exporters.register(Names);
+//!
+//! let mut registry = Registry::new();
+//! registry.insert("Foo".into(), ContainerFormat::NewTypeStruct(Box::new(Format::U32)));
+//! let mut out = Vec::new();
+//! exporters.export("names", &registry, &mut out).unwrap();
+//! assert_eq!(out, b"Foo\n");
+//! ```
+
+use crate::{
+    ContainerFormat, Error, Format, FormatHolder, Named, Registry, RegistryMetadata, Result,
+    VariantFormat,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::Write,
+};
+
+/// Constructs of formats that some schema languages cannot express.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Construct {
+    /// `ContainerFormat::Generic`, `Format::TypeParam`, and `Format::Apply`.
+    GenericContainer,
+    /// `Format::I128` and `Format::U128`.
+    Int128,
+    /// `Format::Char`.
+    Char,
+    /// `Format::Map`.
+    Map,
+    /// `Format::Tuple` and `Format::TupleArray`.
+    Tuple,
+    /// `Format::Union`.
+    Union,
+    /// `ContainerFormat::Enum` without variants, i.e. an uninhabited type such as
+    /// `enum Void {}`.
+    EmptyEnum,
+    /// Containers that can only be read from self-describing encodings, as recorded in the
+    /// [`RegistryMetadata`].
+    SelfDescribing,
+}
+
+impl Construct {
+    /// All the constructs.
+    pub const ALL: [Construct; 8] = [
+        Construct::GenericContainer,
+        Construct::Int128,
+        Construct::Char,
+        Construct::Map,
+        Construct::Tuple,
+        Construct::Union,
+        Construct::EmptyEnum,
+        Construct::SelfDescribing,
+    ];
+
+    fn of_format(format: &Format) -> Option<Self> {
+        match format {
+            Format::TypeParam(_) | Format::Apply { .. } => Some(Construct::GenericContainer),
+            Format::I128 | Format::U128 => Some(Construct::Int128),
+            Format::Char => Some(Construct::Char),
+            Format::Map { .. } => Some(Construct::Map),
+            Format::Tuple(_) | Format::TupleArray { .. } => Some(Construct::Tuple),
+            Format::Union(_) => Some(Construct::Union),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Construct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Construct::GenericContainer => "generic containers",
+            Construct::Int128 => "128-bit integers",
+            Construct::Char => "characters",
+            Construct::Map => "maps",
+            Construct::Tuple => "tuples",
+            Construct::Union => "unions of formats",
+            Construct::EmptyEnum => "enums without variants",
+            Construct::SelfDescribing => "containers requiring a self-describing encoding",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A backend writing registries in a schema language.
+///
+/// Registries are written one container at a time, so that exporters only need to hold a
+/// single container in memory and the output remains useful up to the last complete
+/// container if an error occurs.
+pub trait Exporter: Send + Sync {
+    /// The name under which the exporter is registered, e.g. `"yaml"`.
+    fn name(&self) -> &str;
+
+    /// Whether the exporter can express the given construct. By default, every construct is
+    /// supported.
+    fn supports(&self, construct: Construct) -> bool {
+        let _ = construct;
+        true
+    }
+
+    /// Write what precedes the containers of `registry`. Nothing by default.
+    fn begin(&self, registry: &Registry, out: &mut dyn Write) -> Result<()> {
+        let _ = (registry, out);
+        Ok(())
+    }
+
+    /// Write the container `name`, found at position `index` in the registry.
+    fn export_container(
+        &self,
+        index: usize,
+        name: &str,
+        format: &ContainerFormat,
+        out: &mut dyn Write,
+    ) -> Result<()>;
+
+    /// Write what follows the containers of `registry`. Nothing by default.
+    fn end(&self, registry: &Registry, out: &mut dyn Write) -> Result<()> {
+        let _ = (registry, out);
+        Ok(())
+    }
+
+    /// Write `registry` into `out`, flushing `out` after each container. The registry only
+    /// uses supported constructs.
+    fn export(&self, registry: &Registry, out: &mut dyn Write) -> Result<()> {
+        self.begin(registry, out)?;
+        for (index, (name, format)) in registry.iter().enumerate() {
+            self.export_container(index, name, format, out)?;
+            out.flush().map_err(write_error)?;
+        }
+        self.end(registry, out)?;
+        out.flush().map_err(write_error)
+    }
+}
+
+/// Convert an I/O error raised while writing the output of an exporter.
+pub fn write_error(error: std::io::Error) -> Error {
+    Error::Custom(format!("Failed to write output: {error}"))
+}
+
+/// The constructs of `registry` that `exporter` does not support, together with the name
+/// of a container using them.
+pub fn unsupported_constructs(
+    exporter: &dyn Exporter,
+    registry: &Registry,
+) -> BTreeMap<Construct, String> {
+    unsupported_constructs_with_metadata(exporter, registry, &RegistryMetadata::new())
+}
+
+/// The constructs of `registry` that `exporter` does not support, as in
+/// [`unsupported_constructs`], including the ones recorded in the metadata of the registry.
+pub fn unsupported_constructs_with_metadata(
+    exporter: &dyn Exporter,
+    registry: &Registry,
+    metadata: &RegistryMetadata,
+) -> BTreeMap<Construct, String> {
+    let mut result = BTreeMap::new();
+    for (name, format) in registry {
+        let mut record = |construct: Construct| {
+            if !exporter.supports(construct) {
+                result.entry(construct).or_insert_with(|| name.clone());
+            }
+        };
+        if metadata.requires_self_describing(name) {
+            record(Construct::SelfDescribing);
+        }
+        if matches!(format, ContainerFormat::Generic { .. }) {
+            record(Construct::GenericContainer);
+        }
+        if matches!(format, ContainerFormat::Enum(variants) if variants.is_empty()) {
+            record(Construct::EmptyEnum);
+        }
+        format
+            .visit(&mut |format| {
+                if let Some(construct) = Construct::of_format(format) {
+                    record(construct);
+                }
+                Ok(())
+            })
+            .unwrap_or(());
+    }
+    result
+}
+
+/// A collection of exporters, indexed by name.
+///
+/// `Exporters::default()` contains the built-in exporters: `docs`, as well as `json` and
+/// `yaml` when the corresponding crate features are enabled. `Exporters::new()` is empty.
+pub struct Exporters {
+    exporters: BTreeMap<String, Box<dyn Exporter>>,
+}
+
+impl Exporters {
+    /// An empty collection.
+    pub fn new() -> Self {
+        Self {
+            exporters: BTreeMap::new(),
+        }
+    }
+
+    /// Add an exporter under its name. Return the exporter previously registered with
+    /// the same name, if any.
+    pub fn register<E>(&mut self, exporter: E) -> Option<Box<dyn Exporter>>
+    where
+        E: Exporter + 'static,
+    {
+        self.exporters
+            .insert(exporter.name().to_string(), Box::new(exporter))
+    }
+
+    /// The exporter registered under `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&dyn Exporter> {
+        self.exporters.get(name).map(|exporter| exporter.as_ref())
+    }
+
+    /// The names of the registered exporters, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.exporters.keys().map(String::as_str)
+    }
+
+    /// Write `registry` into `out` with the exporter registered under `name`, after checking
+    /// that the exporter supports every construct used by the registry.
+    pub fn export(&self, name: &str, registry: &Registry, out: &mut dyn Write) -> Result<()> {
+        self.export_with_metadata(name, registry, &RegistryMetadata::new(), out)
+    }
+
+    /// Write `registry` into `out` as in `export`, also checking that the exporter supports
+    /// the constructs recorded in the metadata of the registry, e.g. containers that require
+    /// a self-describing encoding.
+    pub fn export_with_metadata(
+        &self,
+        name: &str,
+        registry: &Registry,
+        metadata: &RegistryMetadata,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        let exporter = self
+            .get(name)
+            .ok_or_else(|| Error::UnknownExporter(name.to_string()))?;
+        if let Some((construct, container)) =
+            unsupported_constructs_with_metadata(exporter, registry, metadata)
+                .into_iter()
+                .next()
+        {
+            return Err(Error::UnsupportedConstruct {
+                exporter: name.to_string(),
+                container,
+                construct,
+            });
+        }
+        exporter.export(registry, out)
+    }
+}
+
+impl Default for Exporters {
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut exporters = Self::new();
+        exporters.register(DocsExporter::default());
+        #[cfg(feature = "json")]
+        exporters.register(JsonExporter);
+        #[cfg(feature = "serde_yaml")]
+        exporters.register(YamlExporter);
+        exporters
+    }
+}
+
+/// Export registries in pretty-printed JSON.
+#[cfg(feature = "json")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonExporter;
+
+#[cfg(feature = "json")]
+impl Exporter for JsonExporter {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn begin(&self, _registry: &Registry, out: &mut dyn Write) -> Result<()> {
+        write!(out, "{{").map_err(write_error)
+    }
+
+    fn export_container(
+        &self,
+        index: usize,
+        name: &str,
+        format: &ContainerFormat,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        let name = serde_json::to_string(name).map_err(|e| Error::Custom(e.to_string()))?;
+        let format =
+            serde_json::to_string_pretty(format).map_err(|e| Error::Custom(e.to_string()))?;
+        let separator = if index == 0 { "" } else { "," };
+        // Nest the pretty-printed container into the top-level object.
+        write!(
+            out,
+            "{separator}\n  {name}: {}",
+            format.replace('\n', "\n  ")
+        )
+        .map_err(write_error)
+    }
+
+    fn end(&self, registry: &Registry, out: &mut dyn Write) -> Result<()> {
+        if registry.is_empty() {
+            writeln!(out, "}}").map_err(write_error)
+        } else {
+            writeln!(out, "\n}}").map_err(write_error)
+        }
+    }
+}
+
+/// Export registries in YAML.
+#[cfg(feature = "serde_yaml")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct YamlExporter;
+
+#[cfg(feature = "serde_yaml")]
+impl Exporter for YamlExporter {
+    fn name(&self) -> &str {
+        "yaml"
+    }
+
+    fn begin(&self, registry: &Registry, out: &mut dyn Write) -> Result<()> {
+        writeln!(out, "---").map_err(write_error)?;
+        if registry.is_empty() {
+            writeln!(out, "{{}}").map_err(write_error)?;
+        }
+        Ok(())
+    }
+
+    fn export_container(
+        &self,
+        _index: usize,
+        name: &str,
+        format: &ContainerFormat,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        let entry = BTreeMap::from([(name, format)]);
+        let content = serde_yaml::to_string(&entry).map_err(|e| Error::Custom(e.to_string()))?;
+        let content = content.strip_prefix("---\n").unwrap_or(&content);
+        writeln!(out, "{}", content.trim_end_matches('\n')).map_err(write_error)
+    }
+}
+
+/// Export registries as a standalone HTML reference, e.g. to publish the documentation of
+/// a wire format.
+///
+/// The page lists the containers with their fields and variants. Container names are
+/// cross-linked, and the dependency graph between containers is included both as a list of
+/// links and in the DOT language of Graphviz.
+#[derive(Debug, Clone)]
+pub struct DocsExporter {
+    /// The title of the page.
+    pub title: String,
+}
+
+impl Default for DocsExporter {
+    fn default() -> Self {
+        Self {
+            title: "Format reference".to_string(),
+        }
+    }
+}
+
+impl Exporter for DocsExporter {
+    fn name(&self) -> &str {
+        "docs"
+    }
+
+    fn begin(&self, registry: &Registry, out: &mut dyn Write) -> Result<()> {
+        let title = escape_html(&self.title);
+        writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>"
+        )
+        .map_err(write_error)?;
+        writeln!(out, "<h2>Containers</h2>\n<ul>").map_err(write_error)?;
+        for name in registry.keys() {
+            writeln!(out, "<li>{}</li>", link(name)).map_err(write_error)?;
+        }
+        writeln!(out, "</ul>\n<h2>Dependency graph</h2>\n<ul>").map_err(write_error)?;
+        let dependencies: BTreeMap<_, _> = registry
+            .iter()
+            .map(|(name, format)| (name, dependencies(format)))
+            .collect();
+        for (name, names) in &dependencies {
+            if names.is_empty() {
+                continue;
+            }
+            let links = names.iter().map(|name| link(name)).collect::<Vec<_>>();
+            writeln!(out, "<li>{} uses {}</li>", link(name), links.join(", "))
+                .map_err(write_error)?;
+        }
+        writeln!(out, "</ul>\n<pre class=\"dependency-graph\">digraph {{").map_err(write_error)?;
+        for (name, names) in &dependencies {
+            let name = escape_text(&format!("{name:?}"));
+            writeln!(out, "  {name};").map_err(write_error)?;
+            for other in names {
+                let other = escape_text(&format!("{other:?}"));
+                writeln!(out, "  {name} -> {other};").map_err(write_error)?;
+            }
+        }
+        writeln!(out, "}}</pre>").map_err(write_error)
+    }
+
+    fn export_container(
+        &self,
+        _index: usize,
+        name: &str,
+        format: &ContainerFormat,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        let (params, body): (&[String], _) = match format {
+            ContainerFormat::Generic { params, body } => (params, body.body()),
+            format => (&[], format),
+        };
+        let id = escape_html(name);
+        let mut heading = id.clone();
+        if !params.is_empty() {
+            heading = format!("{heading}&lt;{}&gt;", escape_html(&params.join(", ")));
+        }
+        writeln!(out, "<h2 id=\"{id}\">{heading}</h2>").map_err(write_error)?;
+        let render = |format: &Format| render_format(format, params);
+        match body {
+            ContainerFormat::UnitStruct => writeln!(out, "<p>Unit struct.</p>"),
+            ContainerFormat::NewTypeStruct(format) => {
+                writeln!(
+                    out,
+                    "<p>Newtype struct of <code>{}</code>.</p>",
+                    render(format)
+                )
+            }
+            ContainerFormat::TupleStruct(formats) if formats.is_empty() => {
+                writeln!(out, "<p>Tuple struct without fields.</p>")
+            }
+            ContainerFormat::TupleStruct(formats) => writeln!(
+                out,
+                "<p>Tuple struct of <code>{}</code>.</p>",
+                render_tuple(formats, params)
+            ),
+            ContainerFormat::Struct(fields) if fields.is_empty() => {
+                writeln!(out, "<p>Struct without fields.</p>")
+            }
+            ContainerFormat::Struct(fields) => {
+                writeln!(out, "<p>Struct.</p>").map_err(write_error)?;
+                writeln!(out, "<table>\n<tr><th>Field</th><th>Format</th></tr>")
+                    .map_err(write_error)?;
+                for field in fields {
+                    writeln!(
+                        out,
+                        "<tr><td>{}</td><td><code>{}</code></td></tr>",
+                        escape_html(&field.name),
+                        render(&field.value)
+                    )
+                    .map_err(write_error)?;
+                }
+                writeln!(out, "</table>")
+            }
+            ContainerFormat::Enum(variants) if variants.is_empty() => {
+                writeln!(out, "<p>Uninhabited enum: no value can be encoded.</p>")
+            }
+            ContainerFormat::Enum(variants) => {
+                writeln!(out, "<p>Enum.</p>").map_err(write_error)?;
+                writeln!(
+                    out,
+                    "<table>\n<tr><th>Index</th><th>Variant</th><th>Format</th></tr>"
+                )
+                .map_err(write_error)?;
+                for (index, variant) in variants {
+                    writeln!(
+                        out,
+                        "<tr><td>{index}</td><td>{}</td><td><code>{}</code></td></tr>",
+                        escape_html(&variant.name),
+                        render_variant(&variant.value, params)
+                    )
+                    .map_err(write_error)?;
+                }
+                writeln!(out, "</table>")
+            }
+            ContainerFormat::Generic { .. } => unreachable!("bodies are not generic"),
+        }
+        .map_err(write_error)
+    }
+
+    fn end(&self, _registry: &Registry, out: &mut dyn Write) -> Result<()> {
+        writeln!(out, "</body>\n</html>").map_err(write_error)
+    }
+}
+
+/// The containers referred to by a container.
+fn dependencies(format: &ContainerFormat) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    format
+        .visit(&mut |format| {
+            if let Format::TypeName(name) | Format::Apply { name, .. } = format {
+                names.insert(name.clone());
+            }
+            Ok(())
+        })
+        .unwrap_or(());
+    names
+}
+
+/// Escape text, including within attributes.
+fn escape_html(text: &str) -> String {
+    escape_text(text).replace('"', "&quot;")
+}
+
+/// Escape text outside of attributes.
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn link(name: &str) -> String {
+    let name = escape_html(name);
+    format!("<a href=\"#{name}\">{name}</a>")
+}
+
+/// Render a format in Rust-like syntax, as HTML with links to containers.
+fn render_format(format: &Format, params: &[String]) -> String {
+    let render = |format: &Format| render_format(format, params);
+    match format {
+        Format::Variable(variable) => match variable.borrow().as_ref() {
+            Some(format) => render(format),
+            None => "?".to_string(),
+        },
+        Format::TypeName(name) => link(name),
+        Format::Apply { name, args } => {
+            let args = args.iter().map(render).collect::<Vec<_>>();
+            format!("{}&lt;{}&gt;", link(name), args.join(", "))
+        }
+        Format::TypeParam(index) => match params.get(*index) {
+            Some(param) => escape_html(param),
+            None => format!("T{index}"),
+        },
+        Format::Unit => "()".to_string(),
+        Format::Bool => "bool".to_string(),
+        Format::I8 => "i8".to_string(),
+        Format::I16 => "i16".to_string(),
+        Format::I32 => "i32".to_string(),
+        Format::I64 => "i64".to_string(),
+        Format::I128 => "i128".to_string(),
+        Format::U8 => "u8".to_string(),
+        Format::U16 => "u16".to_string(),
+        Format::U32 => "u32".to_string(),
+        Format::U64 => "u64".to_string(),
+        Format::U128 => "u128".to_string(),
+        Format::F32 => "f32".to_string(),
+        Format::F64 => "f64".to_string(),
+        Format::Char => "char".to_string(),
+        Format::Str => "String".to_string(),
+        Format::Bytes => "Bytes".to_string(),
+        Format::FixedBytes(size) => format!("Bytes[{size}]"),
+        Format::Option(format) => format!("Option&lt;{}&gt;", render(format)),
+        Format::Seq(format) => format!("Vec&lt;{}&gt;", render(format)),
+        Format::Map { key, value } => {
+            format!("Map&lt;{}, {}&gt;", render(key), render(value))
+        }
+        Format::Tuple(formats) => render_tuple(formats, params),
+        Format::TupleArray { content, size } => format!("[{}; {size}]", render(content)),
+        Format::Union(formats) => formats.iter().map(render).collect::<Vec<_>>().join(" | "),
+    }
+}
+
+fn render_tuple(formats: &[Format], params: &[String]) -> String {
+    let formats = formats
+        .iter()
+        .map(|format| render_format(format, params))
+        .collect::<Vec<_>>();
+    format!("({})", formats.join(", "))
+}
+
+fn render_fields(fields: &[Named<Format>], params: &[String]) -> String {
+    let fields = fields
+        .iter()
+        .map(|field| {
+            let format = render_format(&field.value, params);
+            format!("{}: {format}", escape_html(&field.name))
+        })
+        .collect::<Vec<_>>();
+    format!("{{ {} }}", fields.join(", "))
+}
+
+fn render_variant(format: &VariantFormat, params: &[String]) -> String {
+    match format {
+        VariantFormat::Variable(variable) => match variable.borrow().as_ref() {
+            Some(format) => render_variant(format, params),
+            None => "?".to_string(),
+        },
+        VariantFormat::Unit => String::new(),
+        VariantFormat::NewType(format) => render_format(format, params),
+        VariantFormat::Tuple(formats) => render_tuple(formats, params),
+        VariantFormat::Struct(fields) => render_fields(fields, params),
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::Format;
+use std::{fmt, sync::Arc};
+
+/// A location that the tracer cannot model, passed to a [`FallbackHandler`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FallbackContext<'a> {
+    /// The method of the tracing deserializer that was called, e.g. `"deserialize_any"`.
+    pub method: &'static str,
+    /// The innermost container being traced, if any.
+    pub container: Option<&'a str>,
+    /// The names of the variants, fields, and tuple indices leading from `container` to the
+    /// location.
+    pub field_path: &'a [String],
+}
+
+/// Supply formats for the calls that the tracing deserializer cannot answer, instead of
+/// failing the whole trace. See `TracerConfig::fallback_handler`.
+///
+/// The calls concerned are `deserialize_any` (unless answered by
+/// `TracerConfig::self_describing`), `deserialize_identifier`, and `deserialize_ignored_any`.
+/// The returned format is recorded at the location and the `Deserialize` implementation is
+/// answered as if it had asked for this format. Containers must be given by name and have a
+/// recorded sample value.
+///
+/// Closures taking a `&FallbackContext` and returning an `Option<Format>` are handlers.
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use serde_reflection::{Format, Tracer, TracerConfig};
+///
+/// // A type that peeks at self-describing data.
+/// #[derive(Deserialize)]
+/// #[serde(untagged)]
+/// enum Id {
+///     Number(u64),
+///     Name(String),
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     id: Id,
+/// }
+///
+/// let config = TracerConfig::default().fallback_handler(|context: &serde_reflection::FallbackContext| {
+///     (context.container == Some("User")).then_some(Format::U64)
+/// });
+/// let mut tracer = Tracer::new(config);
+/// tracer.trace_simple_type::<User>().unwrap();
+/// let registry = tracer.registry().unwrap();
+/// assert_eq!(
+///     registry["User"].named_fields().next().unwrap().value,
+///     Format::U64
+/// );
+/// ```
+pub trait FallbackHandler: Send + Sync {
+    /// Return the format to assume at the given location, or `None` to fail as usual.
+    fn fallback(&self, context: &FallbackContext<'_>) -> Option<Format>;
+}
+
+impl<F> FallbackHandler for F
+where
+    F: Fn(&FallbackContext<'_>) -> Option<Format> + Send + Sync,
+{
+    fn fallback(&self, context: &FallbackContext<'_>) -> Option<Format> {
+        self(context)
+    }
+}
+
+/// A shared handler, so that configurations can be cloned and compared.
+#[derive(Clone)]
+pub(crate) struct SharedFallbackHandler(pub(crate) Arc<dyn FallbackHandler>);
+
+impl fmt::Debug for SharedFallbackHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FallbackHandler")
+    }
+}
+
+impl PartialEq for SharedFallbackHandler {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
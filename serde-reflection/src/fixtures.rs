@@ -0,0 +1,98 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    error::{Error, Result},
+    trace::{Samples, Tracer},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+type Loader = Box<dyn Fn(&mut Tracer, &mut Samples, &str) -> Result<()>>;
+
+/// A registry of Rust types used to read JSON fixtures. See `Samples::from_json_dir`.
+#[derive(Default)]
+pub struct JsonFixtures {
+    loaders: BTreeMap<String, Loader>,
+}
+
+impl JsonFixtures {
+    /// Create an empty set of fixture types.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the fixture file `<name>.json` as a value of type `T`.
+    /// Typically, `name` is the container name of `T`.
+    pub fn register<T>(mut self, name: &str) -> Self
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let loader = |tracer: &mut Tracer, samples: &mut Samples, content: &str| {
+            let value: T = serde_json::from_str(content)
+                .map_err(|error| Error::InvalidSamples(error.to_string()))?;
+            tracer.trace_value(samples, &value)?;
+            Ok(())
+        };
+        self.loaders.insert(name.to_string(), Box::new(loader));
+        self
+    }
+
+    /// Iterate over the names of the registered types.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.loaders.keys().map(String::as_str)
+    }
+}
+
+impl std::fmt::Debug for JsonFixtures {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.loaders.keys()).finish()
+    }
+}
+
+impl Samples {
+    /// Record samples from the JSON fixtures found in the directory `path`.
+    /// * Each file `<name>.json` must contain a single value of the type registered
+    ///   under `name` in `fixtures`. Files with other extensions are ignored.
+    /// * Values are recorded with `tracer.trace_value`, so their formats are also traced.
+    pub fn from_json_dir<P: AsRef<Path>>(
+        tracer: &mut Tracer,
+        fixtures: &JsonFixtures,
+        path: P,
+    ) -> Result<Samples> {
+        let path = path.as_ref();
+        let io_error =
+            |error: std::io::Error| Error::InvalidSamples(format!("{}: {}", path.display(), error));
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(path).map_err(io_error)? {
+            let file = entry.map_err(io_error)?.path();
+            if file.extension().is_some_and(|ext| ext == "json") {
+                files.push(file);
+            }
+        }
+        // Make the order of tracing deterministic.
+        files.sort();
+
+        let mut samples = Samples::new();
+        for file in files {
+            let name = file
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+            let loader = fixtures.loaders.get(name).ok_or_else(|| {
+                Error::InvalidSamples(format!(
+                    "{}: no type registered for fixture {name}",
+                    file.display()
+                ))
+            })?;
+            let content = std::fs::read_to_string(&file).map_err(io_error)?;
+            loader(tracer, &mut samples, &content).map_err(|error| match error {
+                Error::InvalidSamples(msg) => {
+                    Error::InvalidSamples(format!("{}: {}", file.display(), msg))
+                }
+                error => error,
+            })?;
+        }
+        Ok(samples)
+    }
+}
@@ -11,7 +11,12 @@
 //! * `Named<VariantFormat>`: the format of a variant in a enum, together with its name,
 //! * `Variable<Format>`: a variable holding an initially unknown value format,
 //! * `Variable<VariantFormat>`: a variable holding an initially unknown variant format.
+//!
+//! Formats can be traversed without cloning using `FormatRef`, a borrowed view of a
+//! `Format`, and the iterators `ContainerFormat::formats`, `named_fields`, and `variants`.
 
+#[cfg(any(feature = "json", feature = "trace"))]
+use crate::compat::NumericType;
 use crate::error::{Error, Result};
 use serde::{
     de, ser,
@@ -33,6 +38,16 @@ pub enum Format {
     Variable(#[serde(with = "not_implemented")] Variable<Format>),
     /// The name of a container.
     TypeName(String),
+    /// A type parameter of the enclosing generic container, given by its position.
+    /// See `ContainerFormat::Generic`.
+    TypeParam(usize),
+    /// A generic container instantiated with the given type arguments, one per parameter of
+    /// the container. See `ContainerFormat::Generic`.
+    #[serde(rename_all = "UPPERCASE")]
+    Apply {
+        name: String,
+        args: Box<[Format]>,
+    },
 
     // The formats of primitive types
     Unit,
@@ -52,6 +67,9 @@ pub enum Format {
     Char,
     Str,
     Bytes,
+    /// Bytes whose length is declared to be the given size, e.g. a hash. Encoded like `Bytes`.
+    /// See `TracerConfig::declare_fixed_bytes`.
+    FixedBytes(usize),
 
     /// The format of `Option<T>`.
     Option(Box<Format>),
@@ -73,6 +91,11 @@ pub enum Format {
         content: Box<Format>,
         size: usize,
     },
+    /// A value of one of the given formats, e.g. recorded for the values of a map or the
+    /// samples of an untagged enum when `TracerConfig::union_formats` is enabled, or inferred
+    /// from JSON data. The alternatives are ordered and cannot be told apart in binary
+    /// encodings, only in self-describing ones.
+    Union(Vec<Format>),
 }
 
 /// Serde-based serialization format for named "container" types.
@@ -84,13 +107,37 @@ pub enum ContainerFormat {
     UnitStruct,
     /// A struct with a single unnamed parameter, e.g. `struct A(u16)`
     NewTypeStruct(Box<Format>),
-    /// A struct with several unnamed parameters, e.g. `struct A(u16, u32)`
+    /// A struct with several unnamed parameters, e.g. `struct A(u16, u32)`, or none, e.g.
+    /// `struct A()`.
     TupleStruct(Vec<Format>),
-    /// A struct with named parameters, e.g. `struct A { a: Foo }`.
+    /// A struct with named parameters, e.g. `struct A { a: Foo }`, or none, e.g.
+    /// `struct A {}`. Unlike unit structs, such structs are encoded as empty sequences or
+    /// maps by self-describing formats.
     Struct(Vec<Named<Format>>),
     /// An enum, that is, an enumeration of variants.
-    /// Each variant has a unique name and index within the enum.
+    /// Each variant has a unique name and index within the enum. Binary encodings identify
+    /// variants by index and self-describing ones by name; see `compat::TagEncoding`.
+    /// An enum without variants, e.g. `enum A {}`, is uninhabited.
     Enum(BTreeMap<u32, Named<VariantFormat>>),
+    /// A container with type parameters, e.g. `struct A<T> { a: T }`. Within `body`,
+    /// parameters are referred to by their positions in `params` using `Format::TypeParam`.
+    /// Other containers refer to a generic container with `Format::Apply`, which gives the
+    /// type arguments of the parameters.
+    #[serde(rename_all = "UPPERCASE")]
+    Generic {
+        params: Vec<String>,
+        body: Box<ContainerFormat>,
+    },
+}
+
+/// The kind of a container, regardless of its contents. See [`ContainerFormat::kind`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum ContainerKind {
+    UnitStruct,
+    NewTypeStruct,
+    TupleStruct,
+    Struct,
+    Enum,
 }
 
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
@@ -423,6 +470,7 @@ impl FormatHolder for ContainerFormat {
                     variant.1.visit(f)?;
                 }
             }
+            Self::Generic { body, .. } => body.visit(f)?,
         }
         Ok(())
     }
@@ -446,6 +494,7 @@ impl FormatHolder for ContainerFormat {
                     variant.1.visit_mut(f)?;
                 }
             }
+            Self::Generic { body, .. } => body.visit_mut(f)?,
         }
         Ok(())
     }
@@ -489,6 +538,19 @@ impl FormatHolder for ContainerFormat {
                 }
             }
 
+            (
+                Self::Generic {
+                    params: params1,
+                    body: body1,
+                },
+                Self::Generic {
+                    params: params2,
+                    body: body2,
+                },
+            ) if *params1 == params2 => {
+                body1.as_mut().unify(*body2)?;
+            }
+
             (format1, format2) => {
                 return Err(unification_error(format1, format2));
             }
@@ -506,6 +568,7 @@ impl FormatHolder for Format {
         match self {
             Self::Variable(variable) => variable.visit(f)?,
             Self::TypeName(_)
+            | Self::TypeParam(_)
             | Self::Unit
             | Self::Bool
             | Self::I8
@@ -522,7 +585,8 @@ impl FormatHolder for Format {
             | Self::F64
             | Self::Char
             | Self::Str
-            | Self::Bytes => (),
+            | Self::Bytes
+            | Self::FixedBytes(_) => (),
 
             Self::Option(format)
             | Self::Seq(format)
@@ -537,11 +601,16 @@ impl FormatHolder for Format {
                 value.visit(f)?;
             }
 
-            Self::Tuple(formats) => {
+            Self::Tuple(formats) | Self::Union(formats) => {
                 for format in formats {
                     format.visit(f)?;
                 }
             }
+            Self::Apply { args, .. } => {
+                for format in args.iter() {
+                    format.visit(f)?;
+                }
+            }
         }
         f(self)
     }
@@ -557,6 +626,7 @@ impl FormatHolder for Format {
                     .expect("variable is known");
             }
             Self::TypeName(_)
+            | Self::TypeParam(_)
             | Self::Unit
             | Self::Bool
             | Self::I8
@@ -573,7 +643,8 @@ impl FormatHolder for Format {
             | Self::F64
             | Self::Char
             | Self::Str
-            | Self::Bytes => (),
+            | Self::Bytes
+            | Self::FixedBytes(_) => (),
 
             Self::Option(format)
             | Self::Seq(format)
@@ -588,11 +659,16 @@ impl FormatHolder for Format {
                 value.visit_mut(f)?;
             }
 
-            Self::Tuple(formats) => {
+            Self::Tuple(formats) | Self::Union(formats) => {
                 for format in formats {
                     format.visit_mut(f)?;
                 }
             }
+            Self::Apply { args, .. } => {
+                for format in args.iter_mut() {
+                    format.visit_mut(f)?;
+                }
+            }
         }
         f(self)
     }
@@ -646,18 +722,38 @@ impl FormatHolder for Format {
             | (Self::Bytes, Self::Bytes) => (),
 
             (Self::TypeName(name1), Self::TypeName(name2)) if *name1 == name2 => (),
+            (Self::TypeParam(index1), Self::TypeParam(index2)) if *index1 == index2 => (),
+            (Self::FixedBytes(size1), Self::FixedBytes(size2)) if *size1 == size2 => (),
 
             (Self::Option(format1), Self::Option(format2))
             | (Self::Seq(format1), Self::Seq(format2)) => {
                 format1.as_mut().unify(*format2)?;
             }
 
-            (Self::Tuple(formats1), Self::Tuple(formats2)) if formats1.len() == formats2.len() => {
+            (Self::Tuple(formats1), Self::Tuple(formats2))
+            | (Self::Union(formats1), Self::Union(formats2))
+                if formats1.len() == formats2.len() =>
+            {
                 for (format1, format2) in formats1.iter_mut().zip(formats2.into_iter()) {
                     format1.unify(format2)?;
                 }
             }
 
+            (
+                Self::Apply {
+                    name: name1,
+                    args: args1,
+                },
+                Self::Apply {
+                    name: name2,
+                    args: args2,
+                },
+            ) if *name1 == name2 && args1.len() == args2.len() => {
+                for (format1, format2) in args1.iter_mut().zip(args2.into_vec()) {
+                    format1.unify(format2)?;
+                }
+            }
+
             (
                 Self::Map {
                     key: key1,
@@ -688,10 +784,12 @@ impl FormatHolder for Format {
 }
 
 /// Helper trait to update formats in maps.
+#[cfg(feature = "trace")]
 pub(crate) trait ContainerFormatEntry {
     fn unify(self, format: ContainerFormat) -> Result<()>;
 }
 
+#[cfg(feature = "trace")]
 impl<'a, K> ContainerFormatEntry for Entry<'a, K, ContainerFormat>
 where
     K: std::cmp::Ord,
@@ -714,11 +812,435 @@ impl Format {
     }
 }
 
+/// The narrowest format that can represent the values of both `a` and `b`, if any: integer
+/// types are widened into a type containing both (e.g. `u8` and `i16` into `i16`) and `F32`
+/// into `F64`, recursively. Unknown formats are widened into the other format.
+#[cfg(any(feature = "json", feature = "trace"))]
+pub(crate) fn widen(a: &Format, b: &Format) -> Option<Format> {
+    use Format::*;
+    if a.is_unknown() || a == b {
+        return Some(b.clone());
+    }
+    if b.is_unknown() {
+        return Some(a.clone());
+    }
+    if let (Some(a), Some(b)) = (NumericType::from_format(a), NumericType::from_format(b)) {
+        return NumericType::ALL
+            .into_iter()
+            .filter(|value| value.contains(a) && value.contains(b))
+            .min_by_key(|value| value.bits())
+            .map(NumericType::to_format);
+    }
+    let widened = match (a, b) {
+        (F32, F64) | (F64, F32) => F64,
+        (Option(a), Option(b)) => Option(Box::new(widen(a, b)?)),
+        (Seq(a), Seq(b)) => Seq(Box::new(widen(a, b)?)),
+        (
+            Map {
+                key: key1,
+                value: value1,
+            },
+            Map {
+                key: key2,
+                value: value2,
+            },
+        ) => Map {
+            key: Box::new(widen(key1, key2)?),
+            value: Box::new(widen(value1, value2)?),
+        },
+        (Tuple(formats1), Tuple(formats2)) if formats1.len() == formats2.len() => Tuple(
+            formats1
+                .iter()
+                .zip(formats2)
+                .map(|(a, b)| widen(a, b))
+                .collect::<std::option::Option<_>>()?,
+        ),
+        _ => return None,
+    };
+    Some(widened)
+}
+
+/// A format representing the values of both `a` and `b`: their widened format if any,
+/// otherwise a `Format::Union` of the alternatives. Reduced formats are expected.
+#[cfg(any(feature = "json", feature = "trace"))]
+pub(crate) fn unite(a: Format, b: Format) -> Format {
+    if let Some(widened) = widen(&a, &b) {
+        return widened;
+    }
+    let mut alternatives = match a {
+        Format::Union(alternatives) => alternatives,
+        other => vec![other],
+    };
+    let others = match b {
+        Format::Union(alternatives) => alternatives,
+        other => vec![other],
+    };
+    for format in others {
+        match alternatives
+            .iter_mut()
+            .find_map(|alternative| Some((widen(alternative, &format)?, alternative)))
+        {
+            Some((widened, alternative)) => *alternative = widened,
+            None => alternatives.push(format),
+        }
+    }
+    Format::Union(alternatives)
+}
+
+#[cfg(feature = "trace")]
+fn unite_all(a: &[Format], b: &[Format]) -> Option<Vec<Format>> {
+    if a.len() != b.len() {
+        return None;
+    }
+    Some(
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| unite(a.clone(), b.clone()))
+            .collect(),
+    )
+}
+
+#[cfg(feature = "trace")]
+fn unite_fields(a: &[Named<Format>], b: &[Named<Format>]) -> Option<Vec<Named<Format>>> {
+    if a.len() != b.len() || a.iter().zip(b).any(|(a, b)| a.name != b.name) {
+        return None;
+    }
+    Some(
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| Named {
+                name: a.name.clone(),
+                value: unite(a.value.clone(), b.value.clone()),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(feature = "trace")]
+pub(crate) fn unite_variants(a: &VariantFormat, b: &VariantFormat) -> Option<VariantFormat> {
+    use VariantFormat::*;
+    let united = match (a, b) {
+        (Unit, Unit) => Unit,
+        (NewType(a), NewType(b)) => NewType(Box::new(unite(*a.clone(), *b.clone()))),
+        (Tuple(a), Tuple(b)) => Tuple(unite_all(a, b)?),
+        (Struct(a), Struct(b)) => Struct(unite_fields(a, b)?),
+        _ => return None,
+    };
+    Some(united)
+}
+
+/// Unite the formats of the fields of two containers of the same shape, e.g. when samples
+/// of an untagged enum give different formats to the same field. Reduced formats are
+/// expected.
+#[cfg(feature = "trace")]
+pub(crate) fn unite_containers(
+    a: &ContainerFormat,
+    b: &ContainerFormat,
+) -> Option<ContainerFormat> {
+    use ContainerFormat::*;
+    let united = match (a, b) {
+        (UnitStruct, UnitStruct) => UnitStruct,
+        (NewTypeStruct(a), NewTypeStruct(b)) => {
+            NewTypeStruct(Box::new(unite(*a.clone(), *b.clone())))
+        }
+        (TupleStruct(a), TupleStruct(b)) => TupleStruct(unite_all(a, b)?),
+        (Struct(a), Struct(b)) => Struct(unite_fields(a, b)?),
+        (Enum(a), Enum(b)) => {
+            let mut variants = a.clone();
+            for (index, variant) in b {
+                match variants.get_mut(index) {
+                    Some(existing) if existing.name == variant.name => {
+                        existing.value = unite_variants(&existing.value, &variant.value)?;
+                    }
+                    Some(_) => return None,
+                    None => {
+                        variants.insert(*index, variant.clone());
+                    }
+                }
+            }
+            Enum(variants)
+        }
+        _ => return None,
+    };
+    Some(united)
+}
+
+/// Whether `value` contains a `Format::Union`, e.g. after uniting containers. Formats
+/// with variables are assumed to contain one.
+#[cfg(feature = "trace")]
+pub(crate) fn contains_union<T: FormatHolder>(value: &T) -> bool {
+    value
+        .visit(&mut |format| match format {
+            Format::Union(_) => Err(Error::NotSupported("unions of formats")),
+            _ => Ok(()),
+        })
+        .is_err()
+}
+
+/// A borrowed view of a `Format`, giving access to the nested formats without cloning.
+/// See [`Format::view`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormatRef<'a> {
+    /// A variable. Its value, if any, must be accessed through the variable itself.
+    Variable(&'a Variable<Format>),
+    /// The name of a container.
+    TypeName(&'a str),
+    /// A type parameter.
+    TypeParam(usize),
+    /// A primitive format, e.g. `Format::U64`.
+    Primitive(&'a Format),
+    Option(&'a Format),
+    Seq(&'a Format),
+    Map {
+        key: &'a Format,
+        value: &'a Format,
+    },
+    Tuple(&'a [Format]),
+    TupleArray {
+        content: &'a Format,
+        size: usize,
+    },
+    /// The alternatives of a union.
+    Union(&'a [Format]),
+    /// A generic container and its type arguments.
+    Apply {
+        name: &'a str,
+        args: &'a [Format],
+    },
+}
+
+impl<'a> FormatRef<'a> {
+    /// Iterate over the formats directly nested in this format.
+    pub fn children(self) -> impl Iterator<Item = &'a Format> {
+        let (first, rest): (Option<&'a Format>, &'a [Format]) = match self {
+            FormatRef::Variable(_)
+            | FormatRef::TypeName(_)
+            | FormatRef::TypeParam(_)
+            | FormatRef::Primitive(_) => (None, &[]),
+            FormatRef::Option(format)
+            | FormatRef::Seq(format)
+            | FormatRef::TupleArray {
+                content: format, ..
+            } => (Some(format), &[]),
+            FormatRef::Map { key, value } => (Some(key), std::slice::from_ref(value)),
+            FormatRef::Tuple(formats)
+            | FormatRef::Union(formats)
+            | FormatRef::Apply { args: formats, .. } => (None, formats),
+        };
+        first.into_iter().chain(rest)
+    }
+
+    /// Clone the viewed format.
+    pub fn to_owned(self) -> Format {
+        match self {
+            FormatRef::Variable(variable) => Format::Variable(variable.clone()),
+            FormatRef::TypeName(name) => Format::TypeName(name.to_string()),
+            FormatRef::TypeParam(index) => Format::TypeParam(index),
+            FormatRef::Primitive(format) => format.clone(),
+            FormatRef::Option(format) => Format::Option(Box::new(format.clone())),
+            FormatRef::Seq(format) => Format::Seq(Box::new(format.clone())),
+            FormatRef::Map { key, value } => Format::Map {
+                key: Box::new(key.clone()),
+                value: Box::new(value.clone()),
+            },
+            FormatRef::Tuple(formats) => Format::Tuple(formats.to_vec()),
+            FormatRef::TupleArray { content, size } => Format::TupleArray {
+                content: Box::new(content.clone()),
+                size,
+            },
+            FormatRef::Union(formats) => Format::Union(formats.to_vec()),
+            FormatRef::Apply { name, args } => Format::Apply {
+                name: name.to_string(),
+                args: args.into(),
+            },
+        }
+    }
+}
+
+impl Format {
+    /// Return a borrowed view of this format.
+    pub fn view(&self) -> FormatRef<'_> {
+        match self {
+            Format::Variable(variable) => FormatRef::Variable(variable),
+            Format::TypeName(name) => FormatRef::TypeName(name),
+            Format::TypeParam(index) => FormatRef::TypeParam(*index),
+            Format::Option(format) => FormatRef::Option(format),
+            Format::Seq(format) => FormatRef::Seq(format),
+            Format::Map { key, value } => FormatRef::Map { key, value },
+            Format::Tuple(formats) => FormatRef::Tuple(formats),
+            Format::TupleArray { content, size } => FormatRef::TupleArray {
+                content,
+                size: *size,
+            },
+            Format::Union(formats) => FormatRef::Union(formats),
+            Format::Apply { name, args } => FormatRef::Apply { name, args },
+            _ => FormatRef::Primitive(self),
+        }
+    }
+}
+
+impl ContainerFormat {
+    /// The container without its type parameters, that is, the body of a generic container
+    /// or the container itself.
+    pub fn body(&self) -> &ContainerFormat {
+        let mut container = self;
+        while let ContainerFormat::Generic { body, .. } = container {
+            container = body;
+        }
+        container
+    }
+
+    /// The kind of this container. Generic containers have the kind of their body.
+    pub fn kind(&self) -> ContainerKind {
+        match self.body() {
+            ContainerFormat::UnitStruct | ContainerFormat::Generic { .. } => {
+                ContainerKind::UnitStruct
+            }
+            ContainerFormat::NewTypeStruct(_) => ContainerKind::NewTypeStruct,
+            ContainerFormat::TupleStruct(_) => ContainerKind::TupleStruct,
+            ContainerFormat::Struct(_) => ContainerKind::Struct,
+            ContainerFormat::Enum(_) => ContainerKind::Enum,
+        }
+    }
+
+    /// Whether this container is an enum.
+    pub fn is_enum(&self) -> bool {
+        self.kind() == ContainerKind::Enum
+    }
+
+    /// Whether this container is a struct of any kind, i.e. not an enum.
+    pub fn is_struct(&self) -> bool {
+        !self.is_enum()
+    }
+
+    /// Whether this container has type parameters.
+    pub fn is_generic(&self) -> bool {
+        matches!(self, ContainerFormat::Generic { .. })
+    }
+
+    /// The number of fields of a struct, named or not. Enums have no fields of their own.
+    pub fn field_count(&self) -> usize {
+        match self.body() {
+            ContainerFormat::UnitStruct
+            | ContainerFormat::Generic { .. }
+            | ContainerFormat::Enum(_) => 0,
+            ContainerFormat::NewTypeStruct(_) => 1,
+            ContainerFormat::TupleStruct(formats) => formats.len(),
+            ContainerFormat::Struct(fields) => fields.len(),
+        }
+    }
+
+    /// Iterate over the names of the variants of an enum, by increasing index. Other
+    /// containers have no variants.
+    pub fn variant_names(&self) -> impl Iterator<Item = &str> {
+        self.variants().map(|(_, variant)| variant.name.as_str())
+    }
+
+    /// Iterate over the formats directly nested in this container, i.e. the formats of
+    /// its fields, or the formats of the fields of its variants.
+    pub fn formats(&self) -> impl Iterator<Item = &Format> {
+        let (formats, fields, variants) = match self.body() {
+            ContainerFormat::UnitStruct | ContainerFormat::Generic { .. } => {
+                (&[][..], &[][..], None)
+            }
+            ContainerFormat::NewTypeStruct(format) => {
+                (std::slice::from_ref(&**format), &[][..], None)
+            }
+            ContainerFormat::TupleStruct(formats) => (&formats[..], &[][..], None),
+            ContainerFormat::Struct(fields) => (&[][..], &fields[..], None),
+            ContainerFormat::Enum(variants) => (&[][..], &[][..], Some(variants)),
+        };
+        formats
+            .iter()
+            .chain(fields.iter().map(|field| &field.value))
+            .chain(variants.into_iter().flat_map(|variants| {
+                variants
+                    .values()
+                    .flat_map(|variant| variant.value.formats())
+            }))
+    }
+
+    /// Iterate over the named fields of a struct. Other containers have no named fields.
+    pub fn named_fields(&self) -> impl Iterator<Item = &Named<Format>> {
+        match self.body() {
+            ContainerFormat::Struct(fields) => fields.iter(),
+            _ => [].iter(),
+        }
+    }
+
+    /// Iterate over the variants of an enum, by increasing index. Other containers have
+    /// no variants.
+    pub fn variants(&self) -> impl Iterator<Item = (u32, &Named<VariantFormat>)> {
+        let variants = match self.body() {
+            ContainerFormat::Enum(variants) => Some(variants),
+            _ => None,
+        };
+        variants
+            .into_iter()
+            .flat_map(|variants| variants.iter().map(|(index, variant)| (*index, variant)))
+    }
+
+    /// Reorder the named fields of a struct, or of every struct variant of an enum: the
+    /// fields listed in `order` come first, in the given order, followed by the other
+    /// fields in their current order. Names that are not fields are ignored.
+    ///
+    /// Note that this changes binary encodings, where fields are serialized by position.
+    pub fn reorder_fields(&mut self, order: &[&str]) {
+        fn reorder(fields: &mut Vec<Named<Format>>, order: &[&str]) {
+            let mut remaining = std::mem::take(fields);
+            for name in order {
+                if let Some(position) = remaining.iter().position(|field| field.name == *name) {
+                    fields.push(remaining.remove(position));
+                }
+            }
+            fields.append(&mut remaining);
+        }
+
+        match self {
+            ContainerFormat::Struct(fields) => reorder(fields, order),
+            ContainerFormat::Enum(variants) => {
+                for variant in variants.values_mut() {
+                    if let VariantFormat::Struct(fields) = &mut variant.value {
+                        reorder(fields, order);
+                    }
+                }
+            }
+            ContainerFormat::Generic { body, .. } => body.reorder_fields(order),
+            _ => (),
+        }
+    }
+
+    /// Whether this is an enum whose variants are all unit variants (aka a "C-like" enum).
+    /// Such enums may be mapped to plain enums or integers by code generators.
+    pub fn is_c_like(&self) -> bool {
+        match self.body() {
+            ContainerFormat::Enum(variants) => variants
+                .values()
+                .all(|variant| variant.value == VariantFormat::Unit),
+            _ => false,
+        }
+    }
+}
+
 impl VariantFormat {
     /// Return a format made of a fresh variable with no known value.
     pub fn unknown() -> Self {
         Self::Variable(Variable::new(None))
     }
+
+    /// Iterate over the formats directly nested in this variant, i.e. the formats of its
+    /// fields.
+    pub fn formats(&self) -> impl Iterator<Item = &Format> {
+        let (formats, fields) = match self {
+            VariantFormat::Variable(_) | VariantFormat::Unit => (&[][..], &[][..]),
+            VariantFormat::NewType(format) => (std::slice::from_ref(&**format), &[][..]),
+            VariantFormat::Tuple(formats) => (&formats[..], &[][..]),
+            VariantFormat::Struct(fields) => (&[][..], &fields[..]),
+        };
+        formats
+            .iter()
+            .chain(fields.iter().map(|field| &field.value))
+    }
 }
 
 impl Default for Format {
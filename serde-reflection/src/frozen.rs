@@ -0,0 +1,84 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    error::{Error, Result},
+    registry::Registry,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A sidecar record of containers whose formats are not allowed to change anymore
+/// (e.g. because they have been released), unless their version is bumped.
+///
+/// This is meant to be stored under version control next to the registry.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+pub struct FrozenContainers(BTreeMap<String, FrozenContainer>);
+
+/// The frozen state of a single container.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FrozenContainer {
+    /// The version of the container at the time it was frozen.
+    pub version: u32,
+    /// The value of `ContainerFormat::stable_hash` at the time it was frozen.
+    pub hash: u64,
+}
+
+impl FrozenContainers {
+    /// Create an empty set of frozen containers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Freeze the current formats of the containers `names` in `registry` with the given version.
+    /// * Re-freezing a container with the same version is only allowed if its format has not changed.
+    /// * Versions cannot decrease.
+    pub fn freeze<I, S>(&mut self, registry: &Registry, names: I, version: u32) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut updates = Vec::new();
+        for name in names {
+            let name = name.as_ref();
+            let format = registry
+                .get(name)
+                .ok_or_else(|| Error::MissingContainer(name.to_string()))?;
+            let hash = format.stable_hash();
+            if let Some(frozen) = self.0.get(name) {
+                if version < frozen.version || (version == frozen.version && hash != frozen.hash) {
+                    return Err(Error::FrozenContainerChanged(
+                        name.to_string(),
+                        frozen.version,
+                    ));
+                }
+            }
+            updates.push((name.to_string(), FrozenContainer { version, hash }));
+        }
+        self.0.extend(updates);
+        Ok(())
+    }
+
+    /// Check that the frozen containers still exist in `registry` with unchanged formats.
+    pub fn check(&self, registry: &Registry) -> Result<()> {
+        for (name, frozen) in &self.0 {
+            let format = registry
+                .get(name)
+                .ok_or_else(|| Error::MissingContainer(name.clone()))?;
+            if format.stable_hash() != frozen.hash {
+                return Err(Error::FrozenContainerChanged(name.clone(), frozen.version));
+            }
+        }
+        Ok(())
+    }
+
+    /// Obtain the frozen state of a container, if any.
+    pub fn get(&self, name: &str) -> Option<&FrozenContainer> {
+        self.0.get(name)
+    }
+
+    /// Iterate over the frozen containers.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &FrozenContainer)> {
+        self.0.iter().map(|(name, frozen)| (name.as_str(), frozen))
+    }
+}
@@ -0,0 +1,545 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Schema-guided corruption of encoded values, for the negative testing of decoders.
+//!
+//! The function [`mutate`] decodes a valid payload according to a registry and derives
+//! near-valid payloads from it, by altering the bytes that decoders must validate: enum
+//! tags, option tags and booleans, length prefixes, and strings. Unlike random bit flips,
+//! every mutation targets a position that is meaningful for the schema.
+//!
+//! ```rust
+//! # #[cfg(feature = "trace")]
+//! # {
+//! use serde::{Deserialize, Serialize};
+//! use serde_reflection::compat::EncodingProfile;
+//! use serde_reflection::fuzz::{mutate, MutationKind};
+//! use serde_reflection::{Tracer, TracerConfig};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! enum Message {
+//!     Ping(u64),
+//!     Data { id: u32, payload: Vec<u8> },
+//! }
+//!
+//! let mut tracer = Tracer::new(TracerConfig::default());
+//! tracer.trace_simple_type::<Message>().unwrap();
+//! let registry = tracer.registry().unwrap();
+//!
+//! let bytes = bcs::to_bytes(&Message::Data { id: 1, payload: vec![7, 8] }).unwrap();
+//! let mutations = mutate(&registry, "Message", &bytes, EncodingProfile::Bcs).unwrap();
+//! for mutation in &mutations {
+//!     if mutation.kind != MutationKind::OtherVariant {
+//!         // Decoders must reject the payload, without panicking.
+//!         assert!(bcs::from_bytes::<Message>(&mutation.bytes).is_err(), "{}", mutation);
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::{
+    compat::{EncodingProfile, IntegerEncoding, NumericType, PrefixEncoding},
+    ContainerFormat, Error, Format, Named, Registry, Result, VariantFormat,
+};
+use std::{collections::BTreeMap, fmt, ops::Bound};
+
+/// The kind of corruption applied to a payload.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum MutationKind {
+    /// The tag of an enum value was replaced by the tag of another variant. The payload may
+    /// still be valid.
+    OtherVariant,
+    /// The tag of an enum value was replaced by an index that no variant uses.
+    UnknownVariant,
+    /// The tag of an option, or a boolean, was replaced by a byte other than 0 and 1.
+    InvalidTag,
+    /// The length of a sequence, map, string, or bytes was replaced by a length exceeding the
+    /// size of the payload.
+    LengthOverflow,
+    /// The length of a non-empty sequence, map, string, or bytes was decreased by one.
+    ShortLength,
+    /// The payload was cut before the last byte of a non-empty sequence, map, string, or
+    /// bytes.
+    Truncated,
+    /// The first byte of a non-empty string was replaced so that the string is not valid
+    /// UTF-8.
+    InvalidUtf8,
+}
+
+impl fmt::Display for MutationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            MutationKind::OtherVariant => "other variant",
+            MutationKind::UnknownVariant => "unknown variant",
+            MutationKind::InvalidTag => "invalid tag",
+            MutationKind::LengthOverflow => "length overflow",
+            MutationKind::ShortLength => "short length",
+            MutationKind::Truncated => "truncated",
+            MutationKind::InvalidUtf8 => "invalid UTF-8",
+        };
+        write!(f, "{description}")
+    }
+}
+
+/// A corrupted payload.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Mutation {
+    /// The kind of corruption.
+    pub kind: MutationKind,
+    /// The path of the corrupted value, starting with the name of the container, e.g.
+    /// `["Tx", "payload", "Transfer", "amount"]`.
+    pub path: Vec<String>,
+    /// The position of the first altered byte.
+    pub offset: usize,
+    /// The corrupted payload.
+    pub bytes: Vec<u8>,
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at byte {} ({})",
+            self.kind,
+            self.offset,
+            self.path.join(".")
+        )
+    }
+}
+
+/// Derive corrupted payloads from `bytes`, the encoding of a value of the container
+/// `container` with `profile`.
+/// * Mutations are returned in the order of the altered bytes. Each one alters a single
+///   position of the original payload, so that decoders are exercised on near-valid
+///   inputs. Fuzzers may pick among them at random.
+/// * Return `Error::RoundTripMismatch` if `bytes` cannot be decoded according to the
+///   registry.
+pub fn mutate(
+    registry: &Registry,
+    container: &str,
+    bytes: &[u8],
+    profile: EncodingProfile,
+) -> Result<Vec<Mutation>> {
+    let mut walker = Walker {
+        registry,
+        profile,
+        bytes,
+        position: 0,
+        path: Vec::new(),
+        sites: Vec::new(),
+    };
+    let mismatch = |msg: String| Error::RoundTripMismatch(format!("{container}: {msg}"));
+    walker
+        .format(&Format::TypeName(container.to_string()))
+        .map_err(|error| match error {
+            Error::Custom(msg) => mismatch(msg),
+            error => error,
+        })?;
+    if walker.position != bytes.len() {
+        return Err(mismatch(format!(
+            "{} trailing bytes",
+            bytes.len() - walker.position
+        )));
+    }
+    let mut mutations: Vec<_> = walker
+        .sites
+        .into_iter()
+        .flat_map(|site| site.mutations(bytes, profile))
+        .collect();
+    mutations.sort_by_key(|mutation| mutation.offset);
+    Ok(mutations)
+}
+
+/// A position of the payload that decoders must validate.
+enum Site<'a> {
+    Tag {
+        path: Vec<String>,
+        start: usize,
+        end: usize,
+        index: u32,
+        variants: &'a BTreeMap<u32, Named<VariantFormat>>,
+    },
+    Flag {
+        path: Vec<String>,
+        offset: usize,
+    },
+    Length {
+        path: Vec<String>,
+        start: usize,
+        end: usize,
+        len: u64,
+        content_end: usize,
+    },
+    Str {
+        path: Vec<String>,
+        offset: usize,
+    },
+}
+
+impl Site<'_> {
+    fn mutations(self, bytes: &[u8], profile: EncodingProfile) -> Vec<Mutation> {
+        let splice = |start: usize, end: usize, prefix: Vec<u8>| {
+            let mut result = bytes[..start].to_vec();
+            result.extend(prefix);
+            result.extend(&bytes[end..]);
+            result
+        };
+        let mut mutations = Vec::new();
+        match self {
+            Site::Tag {
+                path,
+                start,
+                end,
+                index,
+                variants,
+            } => {
+                let encoding = profile.variant_tag_encoding();
+                let other = variants
+                    .range((Bound::Excluded(index), Bound::Unbounded))
+                    .chain(variants.range(..index))
+                    .next();
+                if let Some((other, _)) = other {
+                    mutations.push(Mutation {
+                        kind: MutationKind::OtherVariant,
+                        path: path.clone(),
+                        offset: start,
+                        bytes: splice(start, end, encode_prefix(encoding, (*other).into())),
+                    });
+                }
+                let unknown = variants
+                    .keys()
+                    .next_back()
+                    .and_then(|max| max.checked_add(1));
+                if let Some(unknown) = unknown {
+                    mutations.push(Mutation {
+                        kind: MutationKind::UnknownVariant,
+                        path,
+                        offset: start,
+                        bytes: splice(start, end, encode_prefix(encoding, unknown.into())),
+                    });
+                }
+            }
+            Site::Flag { path, offset } => mutations.push(Mutation {
+                kind: MutationKind::InvalidTag,
+                path,
+                offset,
+                bytes: splice(offset, offset + 1, vec![2]),
+            }),
+            Site::Length {
+                path,
+                start,
+                end,
+                len,
+                content_end,
+            } => {
+                let encoding = profile.length_encoding();
+                // Larger than any payload, yet representable by every length encoding.
+                let overflow = u64::from(u32::MAX);
+                if len < overflow {
+                    mutations.push(Mutation {
+                        kind: MutationKind::LengthOverflow,
+                        path: path.clone(),
+                        offset: start,
+                        bytes: splice(start, end, encode_prefix(encoding, overflow)),
+                    });
+                }
+                if len > 0 {
+                    mutations.push(Mutation {
+                        kind: MutationKind::ShortLength,
+                        path: path.clone(),
+                        offset: start,
+                        bytes: splice(start, end, encode_prefix(encoding, len - 1)),
+                    });
+                }
+                if content_end > end {
+                    mutations.push(Mutation {
+                        kind: MutationKind::Truncated,
+                        path,
+                        offset: content_end - 1,
+                        bytes: bytes[..content_end - 1].to_vec(),
+                    });
+                }
+            }
+            Site::Str { path, offset } => mutations.push(Mutation {
+                kind: MutationKind::InvalidUtf8,
+                path,
+                offset,
+                bytes: splice(offset, offset + 1, vec![0xFF]),
+            }),
+        }
+        mutations
+    }
+}
+
+fn encode_prefix(encoding: PrefixEncoding, value: u64) -> Vec<u8> {
+    match encoding {
+        PrefixEncoding::U32 => (value as u32).to_le_bytes().to_vec(),
+        PrefixEncoding::U64 => value.to_le_bytes().to_vec(),
+        PrefixEncoding::Uleb128 => {
+            let mut result = Vec::new();
+            let mut value = value;
+            while value >= 0x80 {
+                result.push((value as u8) | 0x80);
+                value >>= 7;
+            }
+            result.push(value as u8);
+            result
+        }
+    }
+}
+
+/// Decode a payload, recording the positions to corrupt.
+struct Walker<'a> {
+    registry: &'a Registry,
+    profile: EncodingProfile,
+    bytes: &'a [u8],
+    position: usize,
+    path: Vec<String>,
+    sites: Vec<Site<'a>>,
+}
+
+impl<'a> Walker<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self.bytes;
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(|| {
+                Error::Custom(format!("unexpected end of input at byte {}", bytes.len()))
+            })?;
+        let result = &bytes[self.position..end];
+        self.position = end;
+        Ok(result)
+    }
+
+    fn varint(&mut self) -> Result<u128> {
+        let start = self.position;
+        let mut value = 0u128;
+        for shift in (0..128).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u128::from(byte & 0x7F) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(Error::Custom(format!("varint too long at byte {start}")))
+    }
+
+    fn prefix(&mut self, encoding: PrefixEncoding) -> Result<u64> {
+        match encoding {
+            PrefixEncoding::U32 => Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()).into()),
+            PrefixEncoding::U64 => Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            PrefixEncoding::Uleb128 => {
+                let start = self.position;
+                u64::try_from(self.varint()?)
+                    .map_err(|_| Error::Custom(format!("prefix out of range at byte {start}")))
+            }
+        }
+    }
+
+    fn flag(&mut self) -> Result<bool> {
+        let offset = self.position;
+        let value = match self.take(1)?[0] {
+            0 => false,
+            1 => true,
+            byte => {
+                return Err(Error::Custom(format!(
+                    "invalid tag {byte} at byte {offset}"
+                )))
+            }
+        };
+        self.sites.push(Site::Flag {
+            path: self.path.clone(),
+            offset,
+        });
+        Ok(value)
+    }
+
+    fn integer(&mut self, value: NumericType) -> Result<()> {
+        match self.profile.integer_encoding(value) {
+            IntegerEncoding::FixedLittleEndian => {
+                self.take(value.bits() as usize / 8)?;
+            }
+            IntegerEncoding::Varint | IntegerEncoding::ZigZagVarint => {
+                self.varint()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a length prefix, then `content` once per element.
+    fn sequence<F>(&mut self, mut content: F) -> Result<()>
+    where
+        F: FnMut(&mut Self) -> Result<()>,
+    {
+        let start = self.position;
+        let len = self.prefix(self.profile.length_encoding())?;
+        let end = self.position;
+        let index = self.sites.len();
+        self.sites.push(Site::Length {
+            path: self.path.clone(),
+            start,
+            end,
+            len,
+            content_end: end,
+        });
+        for _ in 0..len {
+            content(self)?;
+        }
+        if let Site::Length { content_end, .. } = &mut self.sites[index] {
+            *content_end = self.position;
+        }
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<()> {
+        let path = self.path.clone();
+        let index = self.sites.len();
+        self.sequence(|walker| walker.take(1).map(|_| ()))?;
+        let Site::Length {
+            end: start,
+            content_end: end,
+            ..
+        } = self.sites[index]
+        else {
+            unreachable!("the length of the string is recorded first");
+        };
+        std::str::from_utf8(&self.bytes[start..end])
+            .map_err(|e| Error::Custom(format!("invalid string at byte {start}: {e}")))?;
+        if end > start {
+            self.sites.push(Site::Str {
+                path,
+                offset: start,
+            });
+        }
+        Ok(())
+    }
+
+    fn with_segment<F>(&mut self, segment: &str, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        self.path.push(segment.to_string());
+        let result = f(self);
+        self.path.pop();
+        result
+    }
+
+    fn format(&mut self, format: &Format) -> Result<()> {
+        match format {
+            Format::Variable(variable) => match variable.borrow().as_ref() {
+                Some(format) => self.format(format),
+                None => Err(Error::UnknownFormat),
+            },
+            Format::TypeName(name) => {
+                let container = self
+                    .registry
+                    .get(name)
+                    .ok_or_else(|| Error::MissingContainer(name.clone()))?;
+                self.with_segment(name, |walker| walker.container(container))
+            }
+            Format::TypeParam(_) => Err(Error::NotSupported("type parameters")),
+            Format::Apply { .. } => Err(Error::NotSupported("generic containers")),
+            Format::Unit => Ok(()),
+            Format::Bool => self.flag().map(|_| ()),
+            Format::F32 | Format::F64 if self.profile == EncodingProfile::Bcs => {
+                Err(Error::NotSupported("floats in BCS"))
+            }
+            Format::F32 => self.take(4).map(|_| ()),
+            Format::F64 => self.take(8).map(|_| ()),
+            Format::Char => match self.profile {
+                EncodingProfile::Bcs => Err(Error::NotSupported("characters in BCS")),
+                EncodingProfile::Bincode1 => {
+                    let width = match self.bytes.get(self.position) {
+                        Some(byte) if *byte < 0x80 => 1,
+                        Some(byte) if *byte >= 0xF0 => 4,
+                        Some(byte) if *byte >= 0xE0 => 3,
+                        _ => 2,
+                    };
+                    self.take(width).map(|_| ())
+                }
+                EncodingProfile::Postcard => self.string(),
+            },
+            Format::Str => self.string(),
+            Format::Bytes => self.sequence(|walker| walker.take(1).map(|_| ())),
+            Format::FixedBytes(size) => {
+                self.prefix(self.profile.length_encoding())?;
+                self.take(*size).map(|_| ())
+            }
+            Format::Option(format) => {
+                if self.flag()? {
+                    self.format(format)?;
+                }
+                Ok(())
+            }
+            Format::Seq(format) => self.sequence(|walker| walker.format(format)),
+            Format::Map { key, value } => self.sequence(|walker| {
+                walker.with_segment("key", |walker| walker.format(key))?;
+                walker.with_segment("value", |walker| walker.format(value))
+            }),
+            Format::Tuple(formats) => self.tuple(formats),
+            Format::TupleArray { content, size } => {
+                for _ in 0..*size {
+                    self.format(content)?;
+                }
+                Ok(())
+            }
+            Format::Union(_) => Err(Error::NotSupported("unions in binary encodings")),
+            _ => {
+                let value = NumericType::from_format(format).expect("integer format");
+                self.integer(value)
+            }
+        }
+    }
+
+    fn tuple(&mut self, formats: &[Format]) -> Result<()> {
+        for (index, format) in formats.iter().enumerate() {
+            self.with_segment(&index.to_string(), |walker| walker.format(format))?;
+        }
+        Ok(())
+    }
+
+    fn fields(&mut self, fields: &[Named<Format>]) -> Result<()> {
+        for field in fields {
+            self.with_segment(&field.name, |walker| walker.format(&field.value))?;
+        }
+        Ok(())
+    }
+
+    fn container(&mut self, container: &'a ContainerFormat) -> Result<()> {
+        match container {
+            ContainerFormat::UnitStruct => Ok(()),
+            ContainerFormat::NewTypeStruct(format) => self.format(format),
+            ContainerFormat::TupleStruct(formats) => self.tuple(formats),
+            ContainerFormat::Struct(fields) => self.fields(fields),
+            ContainerFormat::Enum(variants) => {
+                let start = self.position;
+                let tag = self.prefix(self.profile.variant_tag_encoding())?;
+                let variant = u32::try_from(tag)
+                    .ok()
+                    .and_then(|index| Some((index, variants.get(&index)?)));
+                let Some((index, variant)) = variant else {
+                    return Err(Error::Custom(format!(
+                        "unknown variant index {tag} at byte {start}"
+                    )));
+                };
+                self.sites.push(Site::Tag {
+                    path: self.path.clone(),
+                    start,
+                    end: self.position,
+                    index,
+                    variants,
+                });
+                self.with_segment(&variant.name, |walker| match &variant.value {
+                    VariantFormat::Variable(_) => Err(Error::UnknownFormat),
+                    VariantFormat::Unit => Ok(()),
+                    VariantFormat::NewType(format) => walker.format(format),
+                    VariantFormat::Tuple(formats) => walker.tuple(formats),
+                    VariantFormat::Struct(fields) => walker.fields(fields),
+                })
+            }
+            ContainerFormat::Generic { .. } => Err(Error::NotSupported("generic containers")),
+        }
+    }
+}
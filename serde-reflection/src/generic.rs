@@ -0,0 +1,474 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#[cfg(feature = "trace")]
+use crate::{ContainerFormat, Error, Format, FormatHolder, Named, Registry, Result, VariantFormat};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "trace")]
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The maximal number of type parameters supported by [`TypeParam`].
+pub const MAX_TYPE_PARAMS: usize = 8;
+
+/// Reserved container names used to recognize type parameters while tracing.
+pub(crate) const TYPE_PARAM_NAMES: [&str; MAX_TYPE_PARAMS] = [
+    "serde_reflection::TypeParam0",
+    "serde_reflection::TypeParam1",
+    "serde_reflection::TypeParam2",
+    "serde_reflection::TypeParam3",
+    "serde_reflection::TypeParam4",
+    "serde_reflection::TypeParam5",
+    "serde_reflection::TypeParam6",
+    "serde_reflection::TypeParam7",
+];
+
+/// A placeholder for the type parameter at position `N`, used to trace generic containers.
+///
+/// Tracing `Foo<TypeParam<0>>` records the format `Format::TypeParam(0)` wherever the
+/// parameter occurs. The containers reached from a root type that mentions type parameters
+/// are recorded apart from the other containers, so that they do not conflict with the
+/// instances of the same containers, e.g. `Foo<u32>`. `Tracer::registry` then returns:
+/// * the containers whose formats mention type parameters, directly or through other
+///   generic containers, as `ContainerFormat::Generic`. The parameters of each container
+///   are the type parameters that it mentions, renamed `T0`, `T1`, etc. in the order of
+///   their positions;
+/// * the references to generic containers as `Format::Apply`, with the type arguments
+///   inferred from the instances traced under the name of the container.
+///
+/// A registry holds a single instance of each container, so that `Foo<u32>` and
+/// `Foo<String>` still conflict. A generic container is assumed to pass distinct
+/// parameters to the generic containers that it refers to.
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// # #[cfg(feature = "trace")]
+/// use serde_reflection::{ContainerFormat, Format, Named, Samples, Tracer, TracerConfig, TypeParam};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Pair<T> {
+///     first: T,
+///     second: T,
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Point {
+///     coordinates: Pair<u64>,
+/// }
+///
+/// # #[cfg(feature = "trace")]
+/// # fn main() -> serde_reflection::Result<()> {
+/// let mut tracer = Tracer::new(TracerConfig::default());
+/// tracer.trace_simple_type::<Pair<TypeParam<0>>>()?;
+/// tracer.trace_simple_type::<Point>()?;
+/// let registry = tracer.registry()?;
+/// assert_eq!(
+///     registry.get("Pair").unwrap(),
+///     &ContainerFormat::Generic {
+///         params: vec!["T0".into()],
+///         body: Box::new(ContainerFormat::Struct(vec![
+///             Named { name: "first".into(), value: Format::TypeParam(0) },
+///             Named { name: "second".into(), value: Format::TypeParam(0) },
+///         ])),
+///     }
+/// );
+/// assert_eq!(
+///     registry.get("Point").unwrap(),
+///     &ContainerFormat::Struct(vec![Named {
+///         name: "coordinates".into(),
+///         value: Format::Apply { name: "Pair".into(), args: vec![Format::U64].into() },
+///     }])
+/// );
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "trace"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct TypeParam<const N: usize>;
+
+fn type_param_name<const N: usize>() -> Option<&'static str> {
+    TYPE_PARAM_NAMES.get(N).copied()
+}
+
+/// The position of the type parameter reserved for the container name `name`, if any.
+#[cfg(feature = "trace")]
+pub(crate) fn type_param_index(name: &str) -> Option<usize> {
+    TYPE_PARAM_NAMES.iter().position(|param| *param == name)
+}
+
+impl<const N: usize> Serialize for TypeParam<N> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let name = type_param_name::<N>().ok_or_else(|| {
+            <S::Error as ser::Error>::custom(format!("Too many type parameters: {N}"))
+        })?;
+        serializer.serialize_unit_struct(name)
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for TypeParam<N> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TypeParamVisitor<const N: usize>;
+
+        impl<const N: usize> de::Visitor<'_> for TypeParamVisitor<N> {
+            type Value = TypeParam<N>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "type parameter #{N}")
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(TypeParam)
+            }
+        }
+
+        let name = type_param_name::<N>().ok_or_else(|| {
+            <D::Error as de::Error>::custom(format!("Too many type parameters: {N}"))
+        })?;
+        deserializer.deserialize_unit_struct(name, TypeParamVisitor::<N>)
+    }
+}
+
+/// Whether the Rust type named `type_name` mentions type parameters, e.g.
+/// `Pair<TypeParam<0>>`.
+#[cfg(feature = "trace")]
+pub(crate) fn mentions_type_params(type_name: &str) -> bool {
+    let param = std::any::type_name::<TypeParam<0>>();
+    type_name.contains(param.trim_end_matches("0>"))
+}
+
+/// Move the containers of `registry` that mention type parameters, directly or through
+/// other containers, out of `registry` and return them as `ContainerFormat::Generic`.
+/// * The parameters of a container are the type parameters that it mentions, numbered in
+///   the order of their positions in the traced types.
+/// * A reference to a generic container within another generic container becomes a
+///   `Format::Apply` that passes the corresponding parameters.
+#[cfg(feature = "trace")]
+pub(crate) fn split_generic_containers(registry: &mut Registry) -> Registry {
+    let mut params: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
+    loop {
+        let mut changed = false;
+        for (name, format) in registry.iter() {
+            if format.is_generic() {
+                continue;
+            }
+            let mut found = params.get(name).cloned().unwrap_or_default();
+            format
+                .visit(&mut |format| {
+                    match format {
+                        Format::TypeParam(index) => {
+                            found.insert(*index);
+                        }
+                        Format::TypeName(other) => {
+                            found.extend(params.get(other).into_iter().flatten());
+                        }
+                        _ => (),
+                    }
+                    Ok(())
+                })
+                .unwrap_or(());
+            if found.len() > params.get(name).map_or(0, BTreeSet::len) {
+                params.insert(name.clone(), found);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    let mut generics = Registry::new();
+    for (name, indices) in &params {
+        let mut body = registry.remove(name).expect("container exists");
+        body.visit_mut(&mut |format| {
+            if let Format::TypeName(other) = format {
+                if let Some(args) = params.get(other) {
+                    *format = Format::Apply {
+                        name: other.clone(),
+                        args: args.iter().map(|index| Format::TypeParam(*index)).collect(),
+                    };
+                }
+            }
+            Ok(())
+        })
+        .unwrap_or(());
+        body.visit_mut(&mut |format| {
+            if let Format::TypeParam(index) = format {
+                *index = indices
+                    .iter()
+                    .position(|param| param == index)
+                    .expect("parameter was found");
+            }
+            Ok(())
+        })
+        .unwrap_or(());
+        let format = ContainerFormat::Generic {
+            params: (0..indices.len())
+                .map(|index| format!("T{index}"))
+                .collect(),
+            body: Box::new(body),
+        };
+        generics.insert(name.clone(), format);
+    }
+    generics
+}
+
+/// Add the generic containers `generics` to `registry`. A container of `registry` with the
+/// same name is an instance of the generic container: its type arguments are inferred
+/// from its format, then the references to it become `Format::Apply`.
+#[cfg(feature = "trace")]
+pub(crate) fn instantiate_generics(registry: &mut Registry, generics: Registry) -> Vec<Error> {
+    let mut errors = Vec::new();
+    let mut matcher = InstanceMatcher {
+        registry,
+        generics: &generics,
+        args: BTreeMap::new(),
+        in_progress: BTreeSet::new(),
+    };
+    for name in generics.keys() {
+        if matcher.registry.contains_key(name) {
+            if let Err(error) = matcher.instance_args(name) {
+                errors.push(error);
+            }
+        }
+    }
+    let instances = matcher.args;
+    registry.extend(generics);
+    for format in registry.values_mut() {
+        apply_type_args(format, &instances, &mut Vec::new());
+    }
+    errors
+}
+
+/// Replace the references to the instances of generic containers in `holder` with
+/// `Format::Apply`.
+#[cfg(feature = "trace")]
+fn apply_type_args<T: FormatHolder>(
+    holder: &mut T,
+    instances: &BTreeMap<String, Vec<Format>>,
+    in_progress: &mut Vec<String>,
+) {
+    holder
+        .visit_mut(&mut |format| {
+            if let Format::TypeName(name) = format {
+                if let Some(args) = instances.get(name) {
+                    // The arguments of an instance cannot refer to the instance itself.
+                    if in_progress.contains(name) {
+                        return Ok(());
+                    }
+                    let mut args = args.clone();
+                    in_progress.push(name.clone());
+                    for arg in &mut args {
+                        apply_type_args(arg, instances, in_progress);
+                    }
+                    in_progress.pop();
+                    *format = Format::Apply {
+                        name: name.clone(),
+                        args: args.into(),
+                    };
+                }
+            }
+            Ok(())
+        })
+        .unwrap_or(());
+}
+
+/// Infer the type arguments of the instances of generic containers recorded in a registry.
+#[cfg(feature = "trace")]
+struct InstanceMatcher<'a> {
+    registry: &'a Registry,
+    generics: &'a Registry,
+    args: BTreeMap<String, Vec<Format>>,
+    in_progress: BTreeSet<String>,
+}
+
+#[cfg(feature = "trace")]
+impl InstanceMatcher<'_> {
+    /// The type arguments of the instance `name` of a generic container, or `None` if they
+    /// are being inferred, i.e. the container is recursive.
+    fn instance_args(&mut self, name: &str) -> Result<Option<Vec<Format>>> {
+        if let Some(args) = self.args.get(name) {
+            return Ok(Some(args.clone()));
+        }
+        let (generics, registry) = (self.generics, self.registry);
+        let (Some(ContainerFormat::Generic { params, body }), Some(instance)) =
+            (generics.get(name), registry.get(name))
+        else {
+            return Ok(None);
+        };
+        if !self.in_progress.insert(name.to_string()) {
+            return Ok(None);
+        }
+        let mut args = vec![None; params.len()];
+        let matched = self.match_container(body.as_ref(), instance, &mut args);
+        self.in_progress.remove(name);
+        if !matched? {
+            return Err(Error::ConflictingFormats {
+                container: Some(name.to_string()),
+                field_path: Vec::new(),
+                first: format!("{:?}", generics[name]),
+                second: format!("{instance:?}"),
+            });
+        }
+        let args = args
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| Error::UnknownFormatInContainer(name.to_string()))?;
+        self.args.insert(name.to_string(), args.clone());
+        Ok(Some(args))
+    }
+
+    fn match_container(
+        &mut self,
+        generic: &ContainerFormat,
+        instance: &ContainerFormat,
+        args: &mut [Option<Format>],
+    ) -> Result<bool> {
+        use ContainerFormat::*;
+        Ok(match (generic, instance) {
+            (UnitStruct, UnitStruct) => true,
+            (NewTypeStruct(format1), NewTypeStruct(format2)) => {
+                self.match_format(format1, format2, args)?
+            }
+            (TupleStruct(formats1), TupleStruct(formats2)) => {
+                self.match_formats(formats1, formats2, args)?
+            }
+            (Struct(fields1), Struct(fields2)) => self.match_fields(fields1, fields2, args)?,
+            // The instance may only use some of the variants, e.g. when traced by
+            // serialization.
+            (Enum(variants1), Enum(variants2)) => {
+                for (index, variant2) in variants2 {
+                    match variants1.get(index) {
+                        Some(variant1)
+                            if variant1.name == variant2.name
+                                && self.match_variant(
+                                    &variant1.value,
+                                    &variant2.value,
+                                    args,
+                                )? => {}
+                        _ => return Ok(false),
+                    }
+                }
+                true
+            }
+            _ => false,
+        })
+    }
+
+    fn match_variant(
+        &mut self,
+        generic: &VariantFormat,
+        instance: &VariantFormat,
+        args: &mut [Option<Format>],
+    ) -> Result<bool> {
+        use VariantFormat::*;
+        Ok(match (generic, instance) {
+            (Unit, Unit) => true,
+            (NewType(format1), NewType(format2)) => self.match_format(format1, format2, args)?,
+            (Tuple(formats1), Tuple(formats2)) => self.match_formats(formats1, formats2, args)?,
+            (Struct(fields1), Struct(fields2)) => self.match_fields(fields1, fields2, args)?,
+            _ => false,
+        })
+    }
+
+    fn match_fields(
+        &mut self,
+        generic: &[Named<Format>],
+        instance: &[Named<Format>],
+        args: &mut [Option<Format>],
+    ) -> Result<bool> {
+        if generic.len() != instance.len() {
+            return Ok(false);
+        }
+        for (field1, field2) in generic.iter().zip(instance) {
+            if field1.name != field2.name
+                || !self.match_format(&field1.value, &field2.value, args)?
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn match_formats(
+        &mut self,
+        generic: &[Format],
+        instance: &[Format],
+        args: &mut [Option<Format>],
+    ) -> Result<bool> {
+        if generic.len() != instance.len() {
+            return Ok(false);
+        }
+        for (format1, format2) in generic.iter().zip(instance) {
+            if !self.match_format(format1, format2, args)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Whether `instance` is `generic` with the type parameters replaced by `args`. The
+    /// arguments that are still unknown are inferred along the way.
+    fn match_format(
+        &mut self,
+        generic: &Format,
+        instance: &Format,
+        args: &mut [Option<Format>],
+    ) -> Result<bool> {
+        Ok(match (generic, instance) {
+            (Format::TypeParam(index), _) => match &args[*index] {
+                Some(arg) => arg == instance,
+                None => {
+                    args[*index] = Some(instance.clone());
+                    true
+                }
+            },
+            (
+                Format::Apply {
+                    name: name1,
+                    args: args1,
+                },
+                Format::TypeName(name2),
+            ) if name1 == name2 => match self.instance_args(name2)? {
+                Some(args2) => self.match_formats(args1, &args2, args)?,
+                None => true,
+            },
+            (Format::Option(format1), Format::Option(format2))
+            | (Format::Seq(format1), Format::Seq(format2)) => {
+                self.match_format(format1, format2, args)?
+            }
+            (
+                Format::Map {
+                    key: key1,
+                    value: value1,
+                },
+                Format::Map {
+                    key: key2,
+                    value: value2,
+                },
+            ) => self.match_format(key1, key2, args)? && self.match_format(value1, value2, args)?,
+            (Format::Tuple(formats1), Format::Tuple(formats2))
+            | (Format::Union(formats1), Format::Union(formats2)) => {
+                self.match_formats(formats1, formats2, args)?
+            }
+            (
+                Format::TupleArray {
+                    content: content1,
+                    size: size1,
+                },
+                Format::TupleArray {
+                    content: content2,
+                    size: size2,
+                },
+            ) => size1 == size2 && self.match_format(content1, content2, args)?,
+            _ => generic == instance,
+        })
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Stable structural hashing of formats.
+//!
+//! Contrary to `std::hash::Hash`, the hash values computed here only depend on the
+//! structure of the formats. They do not depend on the platform, the Rust version, or the
+//! memory layout of the types, which makes them suitable for storage on disk.
+
+use crate::format::{ContainerFormat, Format, Named, VariantFormat};
+
+/// A 64-bit FNV-1a hasher fed with a canonical encoding of formats.
+pub(crate) struct StableHasher(u64);
+
+impl StableHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    pub(crate) fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    pub(crate) fn write_u8(&mut self, value: u8) {
+        self.write_bytes(&[value]);
+    }
+
+    pub(crate) fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write_bytes(value.as_bytes());
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Feed the canonical encoding of a value into a `StableHasher`.
+pub(crate) trait StableHash {
+    fn stable_hash_into(&self, hasher: &mut StableHasher);
+}
+
+impl<T: StableHash> StableHash for Named<T> {
+    fn stable_hash_into(&self, hasher: &mut StableHasher) {
+        hasher.write_str(&self.name);
+        self.value.stable_hash_into(hasher);
+    }
+}
+
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash_into(&self, hasher: &mut StableHasher) {
+        hasher.write_u64(self.len() as u64);
+        for item in self {
+            item.stable_hash_into(hasher);
+        }
+    }
+}
+
+impl StableHash for Format {
+    fn stable_hash_into(&self, hasher: &mut StableHasher) {
+        use Format::*;
+        match self {
+            Variable(variable) => match variable.borrow().as_ref() {
+                // Known variables are transparent.
+                Some(format) => format.stable_hash_into(hasher),
+                None => hasher.write_u8(0),
+            },
+            TypeName(name) => {
+                hasher.write_u8(1);
+                hasher.write_str(name);
+            }
+            Unit => hasher.write_u8(2),
+            Bool => hasher.write_u8(3),
+            I8 => hasher.write_u8(4),
+            I16 => hasher.write_u8(5),
+            I32 => hasher.write_u8(6),
+            I64 => hasher.write_u8(7),
+            I128 => hasher.write_u8(8),
+            U8 => hasher.write_u8(9),
+            U16 => hasher.write_u8(10),
+            U32 => hasher.write_u8(11),
+            U64 => hasher.write_u8(12),
+            U128 => hasher.write_u8(13),
+            F32 => hasher.write_u8(14),
+            F64 => hasher.write_u8(15),
+            Char => hasher.write_u8(16),
+            Str => hasher.write_u8(17),
+            Bytes => hasher.write_u8(18),
+            Option(format) => {
+                hasher.write_u8(19);
+                format.stable_hash_into(hasher);
+            }
+            Seq(format) => {
+                hasher.write_u8(20);
+                format.stable_hash_into(hasher);
+            }
+            Map { key, value } => {
+                hasher.write_u8(21);
+                key.stable_hash_into(hasher);
+                value.stable_hash_into(hasher);
+            }
+            Tuple(formats) => {
+                hasher.write_u8(22);
+                formats.stable_hash_into(hasher);
+            }
+            TupleArray { content, size } => {
+                hasher.write_u8(23);
+                content.stable_hash_into(hasher);
+                hasher.write_u64(*size as u64);
+            }
+            TypeParam(index) => {
+                hasher.write_u8(24);
+                hasher.write_u64(*index as u64);
+            }
+            FixedBytes(size) => {
+                hasher.write_u8(25);
+                hasher.write_u64(*size as u64);
+            }
+            Union(formats) => {
+                hasher.write_u8(26);
+                formats.stable_hash_into(hasher);
+            }
+            Apply { name, args } => {
+                hasher.write_u8(27);
+                hasher.write_str(name);
+                args.stable_hash_into(hasher);
+            }
+        }
+    }
+}
+
+impl StableHash for VariantFormat {
+    fn stable_hash_into(&self, hasher: &mut StableHasher) {
+        use VariantFormat::*;
+        match self {
+            Variable(variable) => match variable.borrow().as_ref() {
+                Some(format) => format.stable_hash_into(hasher),
+                None => hasher.write_u8(0),
+            },
+            Unit => hasher.write_u8(1),
+            NewType(format) => {
+                hasher.write_u8(2);
+                format.stable_hash_into(hasher);
+            }
+            Tuple(formats) => {
+                hasher.write_u8(3);
+                formats.stable_hash_into(hasher);
+            }
+            Struct(fields) => {
+                hasher.write_u8(4);
+                fields.stable_hash_into(hasher);
+            }
+        }
+    }
+}
+
+impl StableHash for ContainerFormat {
+    fn stable_hash_into(&self, hasher: &mut StableHasher) {
+        use ContainerFormat::*;
+        match self {
+            UnitStruct => hasher.write_u8(0),
+            NewTypeStruct(format) => {
+                hasher.write_u8(1);
+                format.stable_hash_into(hasher);
+            }
+            TupleStruct(formats) => {
+                hasher.write_u8(2);
+                formats.stable_hash_into(hasher);
+            }
+            Struct(fields) => {
+                hasher.write_u8(3);
+                fields.stable_hash_into(hasher);
+            }
+            Enum(variants) => {
+                hasher.write_u8(4);
+                hasher.write_u64(variants.len() as u64);
+                for (index, variant) in variants {
+                    hasher.write_u64(u64::from(*index));
+                    variant.stable_hash_into(hasher);
+                }
+            }
+            Generic { params, body } => {
+                hasher.write_u8(5);
+                hasher.write_u64(params.len() as u64);
+                for param in params {
+                    hasher.write_str(param);
+                }
+                body.stable_hash_into(hasher);
+            }
+        }
+    }
+}
+
+impl ContainerFormat {
+    /// Compute a hash of the structure of this container format.
+    /// * The result only depends on the content of the format. In particular, it is the
+    ///   same on every platform and with every version of Rust.
+    /// * Variables with known values are hashed as their values.
+    pub fn stable_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        self.stable_hash_into(&mut hasher);
+        hasher.finish()
+    }
+}
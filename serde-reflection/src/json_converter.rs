@@ -12,6 +12,7 @@
 //! ```rust
 //! use bincode::Options;
 //! use serde::{Serialize, Deserialize};
+//! # #[cfg(feature = "trace")]
 //! use serde_reflection::{Tracer, TracerConfig, Samples};
 //! use serde_reflection::json_converter::{DeserializationContext, SerializationContext, EmptyEnvironment};
 //! use serde_json::json;
@@ -22,6 +23,7 @@
 //!     y: i32,
 //! }
 //!
+//! # #[cfg(feature = "trace")]
 //! # fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! // Use tracer to extract the format
 //! let mut tracer = Tracer::new(TracerConfig::default());
@@ -56,6 +58,8 @@
 //! assert_eq!(encoded, bytes);
 //! # Ok(())
 //! # }
+//! # #[cfg(not(feature = "trace"))]
+//! # fn main() {}
 //! ```
 //!
 //! This approach is particularly useful for cryptographic applications where you need to
@@ -160,6 +164,12 @@ where
             Variable(_) => Err(<D::Error as serde::de::Error>::custom(
                 "Required formats cannot contain variables",
             )),
+            TypeParam(_) | Apply { .. } => Err(<D::Error as serde::de::Error>::custom(
+                "Required formats cannot contain type parameters",
+            )),
+            Union(_) => Err(<D::Error as serde::de::Error>::custom(
+                "Unions of formats cannot be decoded from binary data",
+            )),
             TypeName(name) => {
                 if let Some(container_format) = self.registry.get(&name) {
                     // Process the container format by deserializing according to its structure
@@ -254,7 +264,7 @@ where
                 let value = String::deserialize(deserializer)?;
                 Ok(Value::String(value))
             }
-            Bytes => {
+            Bytes | FixedBytes(_) => {
                 let value = Vec::<u8>::deserialize(deserializer)?;
                 Ok(Value::Array(
                     value
@@ -529,6 +539,9 @@ where
     use ContainerFormat::*;
 
     match container_format {
+        Generic { .. } => Err(<D::Error as serde::de::Error>::custom(
+            "Generic containers are not supported",
+        )),
         UnitStruct => {
             // Unit structs deserialize as null
             deserializer.deserialize_unit(UnitStructVisitor)
@@ -1034,6 +1047,12 @@ where
             Variable(_) => Err(serde::ser::Error::custom(
                 "Required formats cannot contain variables",
             )),
+            TypeParam(_) | Apply { .. } => Err(serde::ser::Error::custom(
+                "Required formats cannot contain type parameters",
+            )),
+            Union(_) => Err(serde::ser::Error::custom(
+                "Unions of formats cannot be encoded into binary data",
+            )),
             TypeName(name) => {
                 if let Some(container_format) = self.registry.get(name) {
                     serialize_container_format(
@@ -1086,7 +1105,7 @@ where
                 Value::String(s) => serializer.serialize_str(s),
                 _ => Err(serde::ser::Error::custom("Expected string value")),
             },
-            Bytes => match self.value {
+            Bytes | FixedBytes(_) => match self.value {
                 Value::Array(arr) => {
                     let bytes: Result<Vec<u8>, _> = arr
                         .iter()
@@ -1300,6 +1319,9 @@ where
     let static_name = environment.get_static_name(name);
 
     match container_format {
+        Generic { .. } => Err(serde::ser::Error::custom(
+            "Generic containers are not supported",
+        )),
         UnitStruct => serializer.serialize_unit_struct(static_name),
         NewTypeStruct(format) => {
             let context = SerializationContext {
@@ -1491,3 +1513,50 @@ where
         },
     }
 }
+
+/// Infer a format describing a JSON value, e.g. to describe data for which no Rust type is
+/// available. Objects are maps from strings to the inferred format of their values.
+/// * The elements of arrays and the values of objects are widened into a common format
+///   when possible (e.g. `u64` and `i64` into `i128`), otherwise gathered into a
+///   `Format::Union` of the alternatives, in order of appearance.
+/// * `null` is inferred as `Unit`, and so are the elements of empty arrays and objects.
+///
+/// ```rust
+/// use serde_json::json;
+/// use serde_reflection::{json_converter::infer_format, Format};
+///
+/// assert_eq!(
+///     infer_format(&json!([1, -2])),
+///     Format::Seq(Box::new(Format::I128)),
+/// );
+/// assert_eq!(
+///     infer_format(&json!({ "a": 1, "b": "two" })),
+///     Format::Map {
+///         key: Box::new(Format::Str),
+///         value: Box::new(Format::Union(vec![Format::U64, Format::Str])),
+///     },
+/// );
+/// ```
+pub fn infer_format(value: &Value) -> Format {
+    match value {
+        Value::Null => Format::Unit,
+        Value::Bool(_) => Format::Bool,
+        Value::Number(number) if number.is_u64() => Format::U64,
+        Value::Number(number) if number.is_i64() => Format::I64,
+        Value::Number(_) => Format::F64,
+        Value::String(_) => Format::Str,
+        Value::Array(values) => Format::Seq(Box::new(infer_common_format(values))),
+        Value::Object(values) => Format::Map {
+            key: Box::new(Format::Str),
+            value: Box::new(infer_common_format(values.values())),
+        },
+    }
+}
+
+fn infer_common_format<'a>(values: impl IntoIterator<Item = &'a Value>) -> Format {
+    values
+        .into_iter()
+        .map(infer_format)
+        .reduce(crate::format::unite)
+        .unwrap_or(Format::Unit)
+}
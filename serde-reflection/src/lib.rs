@@ -15,7 +15,70 @@
 //!   serialization in other languages (C++, python, Java, etc).
 //!
 //! * Together with the [`json_converter`] module, formats allow dynamic translation of
-//!   binary-serialized values to JSON and from JSON.
+//!   binary-serialized values to JSON and from JSON. The [`verify`] module uses the same
+//!   machinery to check formats against real binary encodings.
+//!   The [`fuzz`] module derives corrupted payloads from valid ones (unknown enum tags,
+//!   overflowing lengths, truncations), guided by a registry, to test decoders.
+//!   The [`testvectors`] module generates reproducible values paired with their binary
+//!   encodings, for the conformance testing of decoders written in other languages.
+//!
+//! * Saved formats can be compared, merged, filtered, validated, and hashed without the
+//!   original Rust types using the [`registry`] and [`diff`] modules, or the command-line
+//!   tool `serde-reflection` (`cargo install serde-reflection --features cli`).
+//!   Registries persisted long-term can be tagged with the version of the registry format as
+//!   a `registry::VersionedRegistry`, together with the [`RegistryMetadata`] returned by
+//!   `Tracer::registry_with_metadata`, and read back from older versions with
+//!   `registry::migrate_from`.
+//!   Finalized registries can be frozen by `registry::freeze_all` into a read-only
+//!   [`FrozenRegistry`] stamped with its hash, which APIs can require to rule out later changes.
+//!   Containers can be designated by a typed [`ContainerId`] rather than a string, e.g. to
+//!   look them up, rename them, or select their changes in a diff.
+//!   `registry::leaves` lists every primitive format of a registry with its path (e.g.
+//!   `Tx.payload.Transfer.amount: U64`), e.g. to review where floats or bytes are used.
+//!   Containers that only exist in some build configurations can be tagged with [`Conditions`],
+//!   so that a single registry covers all the features of a crate.
+//!   Fields can be refined with [`Constraints`] (integer bounds, lengths, patterns) for the
+//!   validation of values beyond their structure.
+//!   Whether floating-point fields may be NaN or infinite can be recorded there as well, and
+//!   `TracerConfig::float_policy` flags or rejects the fields that do not say.
+//!   Large registries that repeat the same formats can be held in memory as a
+//!   [`shared::SharedRegistry`], which stores each distinct format once.
+//!   The [`analysis`] module bounds the encoded sizes of containers in common binary
+//!   encodings, e.g. to size the buffers of embedded systems, and summarizes the shape of
+//!   registries (counts by kind, nesting depth, widest enums) to monitor schema growth.
+//!   Registries traced separately by the crates or services of a repository can be managed
+//!   together, with shared containers, as a [`workspace::Workspace`].
+//!
+//! * Registries can be written in other schema languages by the backends of the [`export`]
+//!   module, which other crates can extend with their own implementations of `Exporter`.
+//!   The `docs` exporter writes a cross-linked HTML reference of a registry, e.g. to publish
+//!   the documentation of a wire format (`serde-reflection export --exporter docs`).
+//!   Such implementations can be checked against the tricky types and expected registries of
+//!   the `test_fixtures` module (feature `test_fixtures`). The module also holds golden
+//!   Bincode 2 and Postcard encodings of sample values, to check that registries predict
+//!   the byte layouts of compact encodings.
+//!
+//! * With the feature `derive`, `#[derive(Reflect)]` records the `#[serde(..)]` attributes
+//!   of a type (renames, defaults, skipped and flattened fields, enum tagging). Once
+//!   registered in the [`attributes`] module, they spare the tracer some guesswork, e.g.
+//!   whether a struct denies unknown fields.
+//!   `#[serde_reflection::trace_test]` generates a test checking that a struct or an enum can
+//!   be traced completely, and optionally comparing its registry with a golden file (see
+//!   the module `snapshot`), so that every wire type of a crate is checked with one line.
+//!
+//! * The `bench_fixtures` module (feature `bench-fixtures`) exports representative workloads
+//!   (deep enums, wide structs, large registries) timed by the benchmarks of this crate, to
+//!   catch performance regressions of the tracer.
+//!
+//! * With the feature `spill`, `TracerConfig::spill_registry` bounds the number of containers
+//!   held by the tracer, e.g. when tracing tens of thousands of generated types. Completely
+//!   traced containers are appended to a file and read back through `SpilledRegistry`, which
+//!   offers the read operations of a registry without loading it in memory.
+//!
+//! * The tracer (`Tracer`, `Samples`, and the Serde serializer and deserializer that drive it)
+//!   is behind the default feature `trace`. Tools that only handle saved registries, such as
+//!   the command-line tool or validators compiled to WebAssembly, can disable the default
+//!   features to depend on formats, registries, values, and their analyses alone.
 //!
 //! # Quick Start
 //!
@@ -26,6 +89,7 @@
 //!
 //! ```rust
 //! # use serde::Deserialize;
+//! # #[cfg(feature = "trace")]
 //! # use serde_reflection::{Error, Samples, Tracer, TracerConfig};
 //! #[derive(Deserialize)]
 //! struct Foo {
@@ -39,6 +103,7 @@
 //! #[derive(Deserialize)]
 //! enum Choice { A, B, C }
 //!
+//! # #[cfg(feature = "trace")]
 //! # fn main() -> Result<(), Error> {
 //! // Start the tracing session.
 //! let mut tracer = Tracer::new(TracerConfig::default());
@@ -72,6 +137,8 @@
 //! "#);
 //! # Ok(())
 //! # }
+//! # #[cfg(not(feature = "trace"))]
+//! # fn main() {}
 //! ```
 //!
 //! # Features and Limitations
@@ -92,14 +159,75 @@
 //!   derived ones, provided that `trace_value` is used during tracing to provide sample
 //!   values for all such constrained types (see the detailed example below).
 //!
+//! * Types whose implementation of `Deserialize` calls `deserialize_any` (e.g. untagged
+//!   enums), provided that `TracerConfig::self_describing` is enabled and that `trace_value`
+//!   is used first to record the formats of the corresponding fields. The enclosing
+//!   containers are reported by `Tracer::requires_self_describing`, since their data can
+//!   only be read from self-describing encodings such as JSON. They are also marked in the
+//!   [`RegistryMetadata`], which exporters check for `export::Construct::SelfDescribing`.
+//!
+//! * Values whose formats differ between samples, such as untagged enums or dynamically
+//!   typed map values, provided that `TracerConfig::union_formats` is enabled. The
+//!   alternatives are recorded as a `Format::Union`.
+//!
+//! * Hand-written implementations of `Deserialize` that read wider numbers than `Serialize`
+//!   writes (e.g. a `u64` for a field written as a `u32`), provided that
+//!   `TracerConfig::widen_formats` is enabled. The widest format is recorded. Otherwise,
+//!   such disagreements are reported by `Error::ConflictingFormats`.
+//!
+//! * Types whose implementations of `Serialize` and `Deserialize` depend on
+//!   `is_human_readable()`, provided that `TracerConfig::both_encodings` is enabled. Both
+//!   encodings are traced by the same calls, and `Tracer::multi_registry` returns a
+//!   `MultiRegistry` with the formats shared by the encodings and those specific to each.
+//!
+//! * Types that implement `Serialize` but not `Deserialize`, e.g. types from other crates,
+//!   by providing representative values with a `SerializeSampleProvider` and calling
+//!   `Tracer::trace_serialize_only`.
+//!
+//! * Types described by frameworks that wrap Serde (e.g. the models of an ORM) instead of
+//!   Serde traits, by implementing `TraceableDyn` and calling `Tracer::trace_dyn`.
+//!
+//! * Enums with a fallback variant marked `#[serde(other)]`. The fallback variant is recorded
+//!   as a regular unit variant. It is detected when `TracerConfig::probe_fallback_variants`
+//!   is enabled, reported by `Tracer::fallback_variant`, and kept in the [`RegistryMetadata`].
+//!
+//! * Smart pointers such as `Box<T>`, `Rc<T>`, and `Arc<T>` (including `Box<str>` and `Arc<[T]>`),
+//!   which Serde encodes as their content. Custom wrappers that are newtype structs can be
+//!   traced as their content with `TracerConfig::transparent_container`.
+//!   Likewise, `Mutex<T>`, `RwLock<T>`, `RefCell<T>`, and `Cell<T>` are traced as `T`. Values
+//!   whose locks are poisoned or borrowed cannot be serialized, which `Hint::LockUnavailable`
+//!   reports.
+//!
+//! * Internal containers that are irrelevant to other languages, which can be recorded as
+//!   opaque placeholders without tracing their content with `TracerConfig::ignore_container`.
+//!
+//! * Remote definitions `#[serde(remote = "...")]`, which Serde names after the proxy type.
+//!   Proxies can be recorded under the names of the real types with `TracerConfig::remote_name`
+//!   or `TracerConfig::remote_suffix`.
+//!
+//! * Byte buffers marked with `serde_bytes` (or borrowed as `&[u8]`) are traced as `Format::Bytes`,
+//!   and other sequences of `u8` as `Seq(U8)`, unless `TracerConfig::u8_seqs_as_bytes` is enabled.
+//!
+//! * Uninhabited types, i.e. enums without variants such as `enum Void {}`, which are recorded
+//!   as `ContainerFormat::Enum` without variants. Variants containing them are recorded at the
+//!   index of their declaration but never constructed, and options, sequences, and maps of them
+//!   are traced as empty. Tracing an uninhabited type itself fails with `Error::Uninhabited`.
+//!
+//! * Generic containers, by tracing them with the placeholders `TypeParam<0>`, `TypeParam<1>`,
+//!   etc. Such containers are recorded once as `ContainerFormat::Generic`, and their instances
+//!   are referred to with `Format::Apply`. Note that code generators reject generic containers
+//!   with an error.
+//!
 //! * Mutually recursive types provided that the first variant of each enum is
 //!   recursion-free. (For instance, `enum List { None, Some(Box<List>)}`.) Note that each
 //!   enum must be traced separately with `trace_type` to discover all the variants.
 //!
 //! ## Unsupported idioms
 //!
-//! * Containers sharing the same base name (e.g. `Foo`) but from different modules. (Work
-//!   around: use `#[serde(rename = ..)]`)
+//! * Containers sharing the same base name (e.g. `Foo`) but from different modules. They
+//!   are traced into the same container, so their formats must agree; enums with different
+//!   variants are rejected with `Error::NameClash`. (Work around: use
+//!   `#[serde(rename = ..)]`)
 //!
 //! * Generic types instantiated multiple times in the same tracing session. (Work around:
 //!   use the crate [`serde-name`](https://crates.io/crates/serde-name) and its adapters `SerializeNameAdapter` and `DeserializeNameAdapter`.)
@@ -117,7 +245,9 @@
 //!   formats. This loss of information makes it difficult to use `trace_value` to work
 //!   around deserialization invariants (see example below). As a work around, you may
 //!   override the default for the primitive type using `TracerConfig` (e.g. `let config =
-//!   TracerConfig::default().default_u8_value(1);`).
+//!   TracerConfig::default().default_u8_value(1);`). Alternatively,
+//!   `TracerConfig::random_seed` synthesizes primitive values from a seeded generator, which
+//!   keeps traces reproducible.
 //!
 //! ## Security CAVEAT
 //!
@@ -130,11 +260,43 @@
 //! well-ordered serialized elements in this case). In the case of `HashSet<T>`,
 //! serialization will additionally be non-deterministic.
 //!
+//! # Deterministic Output
+//!
+//! Identical tracing inputs always produce identical registries, and serializing a registry
+//! with a deterministic encoding (e.g. JSON, YAML, Bincode) always produces the same bytes,
+//! on every platform and with every version of Rust. This is part of the API contract:
+//! * `Registry` is a `BTreeMap`, so containers are sorted by name.
+//! * The variants of `ContainerFormat::Enum` are stored in a `BTreeMap` indexed by variant
+//!   index, so they are sorted regardless of the order in which they were discovered.
+//! * The fields of structs and the elements of tuples follow their order in the Rust
+//!   definitions, which is also their order in serialized data.
+//! * The order of successful tracing calls does not matter as long as the same types and
+//!   samples are provided, except for the order of the alternatives of a `Format::Union`
+//!   (see `TracerConfig::union_formats`), which follows the order of discovery.
+//! * A tracing call that fails may leave partial results in the tracer. With
+//!   `Tracer::trace_all` and `Tracer::execute`, failing roots are rolled back, so that the
+//!   registry does not depend on where they appear among the roots.
+//!
+//! Registries are therefore suitable for content-addressed storage. See also
+//! `ContainerFormat::stable_hash` and `FrozenRegistry::stable_hash`.
+//!
 //! # Troubleshooting
 //!
 //! The error type used in this crate provides a method `error.explanation()` to help with
 //! troubleshooting during format tracing.
 //!
+//! When the error comes from a common Serde pattern (`NonZero` integers, untagged enums,
+//! validating implementations of `Deserialize`), `error.hint()` returns a structured [`Hint`]
+//! with a suggested call to `TracerConfig` or `Tracer`.
+//!
+//! When a trace produces a surprising format, `TracerConfig::record_events` makes the tracer
+//! keep a log of its low-level decisions (calls to the serializer and the deserializer, samples
+//! consulted, enum variants explored), available afterwards with `tracer.events()`.
+//!
+//! With the feature `tracing`, the tracer also reports its progress to the current
+//! [`tracing`](https://docs.rs/tracing) subscriber: top-level calls and the containers being
+//! deserialized are spans, and the enum variants explored are `DEBUG` events.
+//!
 //! # Detailed Example
 //!
 //! In the following, more complete example, we extract the Serde formats of two containers
@@ -143,6 +305,7 @@
 //!
 //! ```rust
 //! # use serde::{Deserialize, Serialize};
+//! # #[cfg(feature = "trace")]
 //! use serde_reflection::{ContainerFormat, Error, Format, Samples, Tracer, TracerConfig};
 //!
 //! #[derive(Serialize, PartialEq, Eq, Debug, Clone)]
@@ -177,6 +340,7 @@
 //!     FullName { first: Name, last: Name },
 //! }
 //!
+//! # #[cfg(feature = "trace")]
 //! # fn main() -> Result<(), Error> {
 //! // Start a session to trace formats.
 //! let mut tracer = Tracer::new(TracerConfig::default());
@@ -236,8 +400,15 @@
 //! "#);
 //! # Ok(())
 //! # }
+//! # #[cfg(not(feature = "trace"))]
+//! # fn main() {}
 //! ```
 //!
+//! When sample values are expensive to construct, they can be registered with
+//! `Samples::register_lazy` instead, so that only the values needed by a trace are built.
+//! Values loaded from remote storage can be registered with `Samples::register_async` and
+//! fetched concurrently by `Tracer::trace_type_async` (feature `async`).
+//!
 //! # Tracing Serialization with `trace_value`
 //!
 //! Tracing the serialization of a Rust value `v` consists of visiting the structural
@@ -253,6 +424,7 @@
 //!   last: &'a str,
 //! }
 //!
+//! # #[cfg(feature = "trace")]
 //! # fn main() -> Result<(), Error> {
 //! let mut tracer = Tracer::new(TracerConfig::default());
 //! let mut samples = Samples::new();
@@ -264,6 +436,8 @@
 //! };
 //! # Ok(())
 //! # }
+//! # #[cfg(not(feature = "trace"))]
+//! # fn main() {}
 //! ```
 //!
 //! This approach works well but it can only recover the formats of datatypes for which
@@ -283,6 +457,7 @@
 //! #   middle: Option<&'a str>,
 //! #   last: &'a str,
 //! # }
+//! # #[cfg(feature = "trace")]
 //! # fn main() -> Result<(), Error> {
 //! let mut tracer = Tracer::new(TracerConfig::default());
 //! let mut samples = Samples::new();
@@ -290,6 +465,8 @@
 //! assert_eq!(tracer.registry().unwrap_err(), Error::UnknownFormatInContainer("FullName".to_string()));
 //! # Ok(())
 //! # }
+//! # #[cfg(not(feature = "trace"))]
+//! # fn main() {}
 //! ```
 //!
 //! For this reason, we introduce a complementary set of APIs to trace deserialization of types.
@@ -312,7 +489,10 @@
 //!
 //! For this reason, the high-level API `trace_type<T>`
 //! will repeat calls to `trace_type_once<T>` until all the variants of `T` are known.
-//! Variant cases of `T` are explored in sequential order, starting with index `0`.
+//! Variant cases of `T` are explored in sequential order, starting with index `0`. This order
+//! of declaration is guaranteed. With `TracerConfig::variant_order(VariantOrder::SampleFirst)`,
+//! the variants already recorded from samples (e.g. with `trace_value`) are skipped instead,
+//! which helps when some variants cannot be deserialized from default values.
 //!
 //! ## Coverage Guarantees
 //!
@@ -358,19 +538,94 @@
 //! other than the parameter `T` of the main call `trace_type<T>`. As a consequence, each enum type must be
 //! traced separately.
 
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "test_fixtures")]
+mod compact;
+mod compact_value;
+mod condition;
+mod constraint;
+#[cfg(feature = "trace")]
 mod de;
+mod deprecation;
 mod error;
+#[cfg(feature = "trace")]
+mod events;
+#[cfg(feature = "trace")]
+mod fallback;
+#[cfg(all(feature = "json", feature = "trace"))]
+mod fixtures;
 mod format;
+mod frozen;
+mod generic;
+mod hash;
+mod metadata;
+#[cfg(feature = "trace")]
+mod profile;
+#[cfg(feature = "trace")]
+mod random;
+#[cfg(feature = "trace")]
 mod ser;
+#[cfg(feature = "spill")]
+mod spill;
+#[cfg(feature = "trace")]
 mod trace;
 mod value;
 
+pub mod analysis;
+pub mod attributes;
+#[cfg(feature = "bench-fixtures")]
+pub mod bench_fixtures;
+pub mod compat;
+pub mod diff;
+pub mod export;
+pub mod fuzz;
 #[cfg(feature = "json")]
 pub mod json_converter;
+pub mod registry;
+pub mod shared;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+#[cfg(feature = "test_fixtures")]
+pub mod test_fixtures;
+#[cfg(all(feature = "json", feature = "trace"))]
+pub mod testvectors;
+#[cfg(all(feature = "json", feature = "trace"))]
+pub mod verify;
+pub mod workspace;
 
+pub use attributes::Reflect;
+pub use condition::Conditions;
+pub use constraint::{Constraint, Constraints, FloatPolicy};
+#[cfg(feature = "trace")]
 pub use de::Deserializer;
-pub use error::{Error, Result};
-pub use format::{ContainerFormat, Format, FormatHolder, Named, Variable, VariantFormat};
+pub use deprecation::Deprecations;
+pub use error::{Error, Hint, Result};
+#[cfg(feature = "trace")]
+pub use events::TraceEvent;
+#[cfg(feature = "trace")]
+pub use fallback::{FallbackContext, FallbackHandler};
+#[cfg(all(feature = "json", feature = "trace"))]
+pub use fixtures::JsonFixtures;
+pub use format::{
+    ContainerFormat, ContainerKind, Format, FormatHolder, FormatRef, Named, Variable, VariantFormat,
+};
+pub use frozen::{FrozenContainer, FrozenContainers};
+pub use generic::{TypeParam, MAX_TYPE_PARAMS};
+pub use metadata::RegistryMetadata;
+#[cfg(feature = "trace")]
+pub use profile::{ContainerProfile, RootProfile, TraceProfile};
+pub use registry::{ContainerId, EncodingKind, FrozenRegistry, MultiRegistry, Registry};
+#[cfg(feature = "trace")]
 pub use ser::Serializer;
-pub use trace::{IncompleteEnumReason, Registry, Samples, Tracer, TracerConfig};
+#[cfg(feature = "spill")]
+pub use spill::SpilledRegistry;
+#[cfg(feature = "trace")]
+pub use trace::{
+    IncompleteEnumReason, RootStatus, Samples, SerializeSampleProvider, TraceCheckpoint, TracePlan,
+    TraceRoot, TraceableDyn, Tracer, TracerConfig, VariantOrder,
+};
 pub use value::Value;
+
+#[cfg(feature = "derive")]
+pub use serde_reflection_derive::{trace_test, Reflect};
@@ -0,0 +1,233 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! # Registry tool
+//!
+//! Operations on registries saved in YAML or JSON, which do not require the traced Rust
+//! types. To generate code from a registry, use `serdegen` from the crate
+//! `serde-generate-bin`.
+//!
+//! '''bash
+//! cargo run --features cli -- --help
+//! '''
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_reflection::{
+    compat::{CompatRules, EncodingProfile},
+    diff::RegistryDiff,
+    export::Exporters,
+    registry::{self, VersionedRegistry},
+    Deprecations, Registry,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Yaml,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Encoding {
+    Json,
+    Bincode1,
+    Bcs,
+    Postcard,
+}
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "Serde reflection",
+    about = "Inspect and transform registries of Serde formats"
+)]
+struct Options {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Format of the registries written by the command.
+    #[arg(long, value_enum, ignore_case = true, default_value = "yaml")]
+    format: Format,
+
+    /// File where to write the output (otherwise print it on stdout).
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the changes between two registries. Fail if a change is breaking.
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// Classify changes according to the compatibility rules of an encoding
+        /// (by default, Bincode and BCS).
+        #[arg(long, value_enum, ignore_case = true)]
+        encoding: Option<Encoding>,
+    },
+    /// Combine several registries into one. Fail if a container has incompatible definitions.
+    Merge {
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+    /// Keep the containers that can be reached from the given roots.
+    Filter {
+        input: PathBuf,
+        /// Name of a root container. Names are matched case-insensitively and may omit
+        /// module prefixes, provided that they designate a single container.
+        #[arg(long = "root", required = true)]
+        roots: Vec<String>,
+    },
+    /// Print the problems found in a registry. Fail if there is any.
+    Validate { input: PathBuf },
+    /// Print the stable hash of each container.
+    Hash { input: PathBuf },
+    /// Convert a registry with an exporter (by default, the one named after the output format).
+    Export {
+        input: PathBuf,
+        /// Name of the exporter.
+        #[arg(long)]
+        exporter: Option<String>,
+    },
+    /// Print the names of the available exporters.
+    Exporters,
+}
+
+/// Read a registry in JSON if the file has the extension `.json`, otherwise in YAML.
+fn read_registry(path: &Path) -> Result<Registry> {
+    Ok(read_versioned_registry(path)?.registry)
+}
+
+/// Read a registry as in `read_registry`, together with its metadata, if any.
+fn read_versioned_registry(path: &Path) -> Result<VersionedRegistry> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+    // Registries may be bare maps or tagged with a version of the registry format.
+    let bytes = if path.extension().is_some_and(|ext| ext == "json") {
+        content.into_bytes()
+    } else {
+        serde_json::to_vec(&serde_yaml::from_str::<serde_json::Value>(&content)?)?
+    };
+    let version = registry::detect_version(&bytes)?;
+    Ok(registry::migrate_versioned_from(&bytes, version)?)
+}
+
+impl Format {
+    /// The name of the exporter writing registries in this format.
+    fn exporter(self) -> &'static str {
+        match self {
+            Format::Yaml => "yaml",
+            Format::Json => "json",
+        }
+    }
+}
+
+/// Run a command, writing its output into `out`. Return whether it succeeded.
+fn run(options: &Options, out: &mut dyn Write) -> Result<bool> {
+    let exporters = Exporters::default();
+    let success = match &options.command {
+        Command::Diff { old, new, encoding } => {
+            let (old, new) = (read_versioned_registry(old)?, read_versioned_registry(new)?);
+            let diff = RegistryDiff::with_metadata(
+                &old.registry,
+                &old.metadata,
+                &new.registry,
+                &new.metadata,
+                &Deprecations::new(),
+            );
+            let rules = encoding.map(|encoding| match encoding {
+                Encoding::Json => CompatRules::json(),
+                Encoding::Bincode1 => EncodingProfile::Bincode1.compat_rules(),
+                Encoding::Bcs => EncodingProfile::Bcs.compat_rules(),
+                Encoding::Postcard => EncodingProfile::Postcard.compat_rules(),
+            });
+            for change in &diff.changes {
+                writeln!(out, "{change}")?;
+            }
+            match rules {
+                None => !diff.is_breaking(),
+                Some(rules) => !diff.is_breaking_with(&rules),
+            }
+        }
+        Command::Merge { inputs } => {
+            let mut merged = Registry::new();
+            let mut success = true;
+            for input in inputs {
+                for name in registry::merge(&mut merged, read_registry(input)?) {
+                    eprintln!("Conflicting definitions of {name} in {}", input.display());
+                    success = false;
+                }
+            }
+            exporters.export(options.format.exporter(), &merged, out)?;
+            success
+        }
+        Command::Filter { input, roots } => {
+            let registry = read_registry(input)?;
+            let find_options = registry::FindOptions::lenient();
+            let roots = roots
+                .iter()
+                .map(|root| registry::find(&registry, root, &find_options))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let filtered = registry::reachable(&registry, roots);
+            exporters.export(options.format.exporter(), &filtered, out)?;
+            true
+        }
+        Command::Validate { input } => {
+            let issues = registry::validate(&read_registry(input)?);
+            for issue in &issues {
+                writeln!(out, "{issue}")?;
+            }
+            issues.is_empty()
+        }
+        Command::Hash { input } => {
+            for (name, format) in read_registry(input)? {
+                writeln!(out, "{name}: {:016x}", format.stable_hash())?;
+            }
+            true
+        }
+        Command::Export { input, exporter } => {
+            let name = exporter
+                .as_deref()
+                .unwrap_or_else(|| options.format.exporter());
+            let input = read_versioned_registry(input)?;
+            exporters.export_with_metadata(name, &input.registry, &input.metadata, out)?;
+            true
+        }
+        Command::Exporters => {
+            for name in exporters.names() {
+                writeln!(out, "{name}")?;
+            }
+            true
+        }
+    };
+    out.flush()?;
+    Ok(success)
+}
+
+fn main() -> ExitCode {
+    let options = Options::parse();
+    let mut out: Box<dyn Write> = match &options.output {
+        None => Box::new(std::io::stdout().lock()),
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(error) => {
+                eprintln!("Error: cannot write {}: {error}", path.display());
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    match run(&options, &mut out) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("Error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
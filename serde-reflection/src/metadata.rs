@@ -0,0 +1,111 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A sidecar record of what the tracer learned about the containers of a registry beyond
+/// their formats.
+///
+/// The metadata is obtained together with the registry by `Tracer::registry_with_metadata`
+/// and may be persisted next to it in a
+/// [`VersionedRegistry`](crate::registry::VersionedRegistry).
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+pub struct RegistryMetadata {
+    /// The root types whose tracing introduced each container.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    provenance: BTreeMap<String, BTreeSet<String>>,
+    /// The containers that can only be read from self-describing encodings.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    self_describing: BTreeSet<String>,
+    /// The variant used by each enum to deserialize unknown variants.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    fallback_variants: BTreeMap<String, String>,
+    /// Whether each struct rejects unknown fields, when known.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: BTreeMap<String, bool>,
+}
+
+impl RegistryMetadata {
+    /// Create an empty record.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether nothing is recorded.
+    pub fn is_empty(&self) -> bool {
+        self.provenance.is_empty()
+            && self.self_describing.is_empty()
+            && self.fallback_variants.is_empty()
+            && self.unknown_fields.is_empty()
+    }
+
+    /// Record that tracing the root type `root` introduced the container `name`.
+    pub fn add_provenance(&mut self, name: &str, root: &str) {
+        self.provenance
+            .entry(name.to_string())
+            .or_default()
+            .insert(root.to_string());
+    }
+
+    /// The names of the root types whose tracing introduced the container `name`, in
+    /// alphabetical order. See `Tracer::provenance`.
+    pub fn provenance(&self, name: &str) -> Vec<String> {
+        self.provenance
+            .get(name)
+            .map(|roots| roots.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Record that the container `name` can only be read from self-describing encodings
+    /// such as JSON or CBOR.
+    pub fn mark_self_describing(&mut self, name: &str) {
+        self.self_describing.insert(name.to_string());
+    }
+
+    /// Whether the container `name` can only be read from self-describing encodings. See
+    /// `Tracer::requires_self_describing`.
+    pub fn requires_self_describing(&self, name: &str) -> bool {
+        self.self_describing.contains(name)
+    }
+
+    /// The names of the containers that can only be read from self-describing encodings,
+    /// in alphabetical order.
+    pub fn self_describing_containers(&self) -> impl Iterator<Item = &str> {
+        self.self_describing.iter().map(String::as_str)
+    }
+
+    /// Record that the enum `name` deserializes unknown variants as its variant `variant`,
+    /// e.g. a variant marked with `#[serde(other)]`.
+    pub fn set_fallback_variant(&mut self, name: &str, variant: &str) {
+        self.fallback_variants
+            .insert(name.to_string(), variant.to_string());
+    }
+
+    /// The name of the variant that the enum `name` uses to deserialize unknown variants,
+    /// if any. See `Tracer::fallback_variant`.
+    pub fn fallback_variant(&self, name: &str) -> Option<&str> {
+        self.fallback_variants.get(name).map(String::as_str)
+    }
+
+    /// Record whether the struct `name` rejects unknown fields during deserialization, e.g.
+    /// with `#[serde(deny_unknown_fields)]`.
+    pub fn set_denies_unknown_fields(&mut self, name: &str, value: bool) {
+        self.unknown_fields.insert(name.to_string(), value);
+    }
+
+    /// Whether the struct `name` rejects unknown fields during deserialization, if known.
+    /// See `Tracer::denies_unknown_fields`.
+    pub fn denies_unknown_fields(&self, name: &str) -> Option<bool> {
+        self.unknown_fields.get(name).copied()
+    }
+
+    /// Keep only the records of the containers for which `f` returns true, e.g. the
+    /// containers of a finished registry.
+    pub fn retain(&mut self, mut f: impl FnMut(&str) -> bool) {
+        self.provenance.retain(|name, _| f(name));
+        self.self_describing.retain(|name| f(name));
+        self.fallback_variants.retain(|name, _| f(name));
+        self.unknown_fields.retain(|name, _| f(name));
+    }
+}
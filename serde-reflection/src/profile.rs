@@ -0,0 +1,50 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::{collections::BTreeMap, time::Duration};
+
+/// Statistics collected while tracing, when `TracerConfig::record_profile` is enabled.
+///
+/// A pass is a single successful top-level tracing call such as `trace_value` or
+/// `trace_type_once`. Note that `trace_type` performs one pass per call to
+/// `trace_type_once`, that is, one pass per variant of the root enum (if any).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TraceProfile {
+    /// Statistics for each container, indexed by name.
+    pub containers: BTreeMap<String, ContainerProfile>,
+    /// Statistics for each root type, using the same names as `Tracer::provenance`.
+    pub roots: BTreeMap<String, RootProfile>,
+}
+
+/// Tracing statistics of a container.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ContainerProfile {
+    /// Number of passes that reached the container.
+    pub passes: usize,
+    /// Number of times a recorded sample value was used to deserialize the container.
+    pub samples_used: usize,
+    /// Cumulated duration of the passes that reached the container.
+    pub time: Duration,
+}
+
+/// Tracing statistics of a root type.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RootProfile {
+    /// Number of passes for this root.
+    pub passes: usize,
+    /// Cumulated duration of the passes for this root.
+    pub time: Duration,
+}
+
+impl TraceProfile {
+    /// Iterate over the containers, starting with the most expensive ones.
+    pub fn slowest_containers(&self) -> Vec<(&str, &ContainerProfile)> {
+        let mut containers: Vec<_> = self
+            .containers
+            .iter()
+            .map(|(name, profile)| (name.as_str(), profile))
+            .collect();
+        containers.sort_by(|(_, x), (_, y)| y.time.cmp(&x.time));
+        containers
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Reproducible synthesis of primitive values, used by the tracing deserializer when
+//! `TracerConfig::random_seed` is set.
+
+/// The SplitMix64 generator. It only uses wrapping arithmetic on `u64`, so the values that
+/// it produces for a given seed are the same on every platform.
+#[derive(Debug, Clone)]
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A random ASCII letter or digit.
+    fn next_alphanumeric(&mut self) -> char {
+        const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+        CHARS[(self.next_u64() % CHARS.len() as u64) as usize] as char
+    }
+}
+
+/// The length of synthesized strings and byte buffers.
+const SYNTHESIZED_LEN: usize = 8;
+
+/// Primitive values that can be synthesized from a `SplitMix64` generator.
+pub(crate) trait Synthesize {
+    fn synthesize(rng: &mut SplitMix64) -> Self;
+}
+
+macro_rules! impl_synthesize_for_integers {
+    ($($ty:ty),*) => {
+        $(
+            impl Synthesize for $ty {
+                fn synthesize(rng: &mut SplitMix64) -> Self {
+                    rng.next_u64() as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_synthesize_for_integers!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Synthesize for u128 {
+    fn synthesize(rng: &mut SplitMix64) -> Self {
+        ((rng.next_u64() as u128) << 64) | rng.next_u64() as u128
+    }
+}
+
+impl Synthesize for i128 {
+    fn synthesize(rng: &mut SplitMix64) -> Self {
+        u128::synthesize(rng) as i128
+    }
+}
+
+impl Synthesize for bool {
+    fn synthesize(rng: &mut SplitMix64) -> Self {
+        rng.next_u64() & 1 == 1
+    }
+}
+
+// Floats are drawn uniformly in `[0, 1)`, so that they are always finite.
+impl Synthesize for f32 {
+    fn synthesize(rng: &mut SplitMix64) -> Self {
+        (rng.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+impl Synthesize for f64 {
+    fn synthesize(rng: &mut SplitMix64) -> Self {
+        (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Synthesize for char {
+    fn synthesize(rng: &mut SplitMix64) -> Self {
+        rng.next_alphanumeric()
+    }
+}
+
+impl Synthesize for String {
+    fn synthesize(rng: &mut SplitMix64) -> Self {
+        (0..SYNTHESIZED_LEN)
+            .map(|_| rng.next_alphanumeric())
+            .collect()
+    }
+}
+
+impl Synthesize for Vec<u8> {
+    fn synthesize(rng: &mut SplitMix64) -> Self {
+        (0..SYNTHESIZED_LEN).map(|_| rng.next_u64() as u8).collect()
+    }
+}
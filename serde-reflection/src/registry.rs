@@ -0,0 +1,1489 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Utilities operating on a whole [`Registry`].
+
+use crate::{
+    hash::{StableHash, StableHasher},
+    Conditions, ContainerFormat, ContainerKind, Error, Format, FormatHolder, Named,
+    RegistryMetadata, Result, VariantFormat,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// A map of container formats.
+pub type Registry = BTreeMap<String, ContainerFormat>;
+
+/// The name of a container of a [`Registry`], as a distinct type.
+///
+/// Ids are obtained from the formats returned by tracing calls (see [`ContainerId::of`]) or
+/// converted from strings at the edges, e.g. from user input. They are accepted by
+/// [`lookup`], [`rename`], and [`RegistryDiff::changes_of`](crate::diff::RegistryDiff::changes_of).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ContainerId(String);
+
+impl ContainerId {
+    /// The id of the container named `name`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+
+    /// The id of the container designated by `format`, if `format` is a `Format::TypeName`,
+    /// e.g. the format returned by `Tracer::trace_type` for a struct or an enum.
+    pub fn of(format: &Format) -> Option<Self> {
+        match format {
+            Format::TypeName(name) => Some(Self::new(name.as_str())),
+            _ => None,
+        }
+    }
+
+    /// The name of the container.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for ContainerId {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for ContainerId {
+    fn from(name: String) -> Self {
+        Self(name)
+    }
+}
+
+impl From<ContainerId> for String {
+    fn from(id: ContainerId) -> Self {
+        id.0
+    }
+}
+
+impl AsRef<str> for ContainerId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::borrow::Borrow<str> for ContainerId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ContainerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The maximal length of a container, field, or variant name accepted by [`validate`].
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// A problem found by [`validate`].
+///
+/// Members are identified by their qualified names, as in [`Deprecations`](crate::Deprecations).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ValidationIssue {
+    /// A container, field, or variant has an empty name.
+    EmptyName { path: Vec<String> },
+    /// A name is longer than [`MAX_NAME_LENGTH`] or is not made of ASCII letters, digits,
+    /// and underscores, starting with a letter or an underscore.
+    InvalidName { path: Vec<String> },
+    /// Two fields of the same struct, or two variants of the same enum, share a name.
+    DuplicateName { path: Vec<String> },
+    /// A format refers to a container that is missing from the registry.
+    DanglingReference { path: Vec<String>, name: String },
+    /// A format is still unknown, e.g. because tracing was incomplete.
+    UnknownFormat { path: Vec<String> },
+    /// A type parameter is used outside of a generic container, or its position is not
+    /// the position of a parameter of the container.
+    InvalidTypeParam { path: Vec<String>, index: usize },
+    /// A `Format::Apply` gives a number of type arguments that is not the number of
+    /// parameters of the generic container `name`.
+    InvalidTypeArgs { path: Vec<String>, name: String },
+}
+
+impl ValidationIssue {
+    /// The qualified name of the member where the issue was found.
+    pub fn path(&self) -> &[String] {
+        use ValidationIssue::*;
+        match self {
+            EmptyName { path }
+            | InvalidName { path }
+            | DuplicateName { path }
+            | DanglingReference { path, .. }
+            | UnknownFormat { path }
+            | InvalidTypeParam { path, .. }
+            | InvalidTypeArgs { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ValidationIssue::*;
+        write!(f, "{}: ", self.path().join("::"))?;
+        match self {
+            EmptyName { .. } => write!(f, "empty name"),
+            InvalidName { .. } => write!(f, "invalid name"),
+            DuplicateName { .. } => write!(f, "duplicate name"),
+            DanglingReference { name, .. } => write!(f, "reference to missing container {name}"),
+            UnknownFormat { .. } => write!(f, "unknown format"),
+            InvalidTypeParam { index, .. } => write!(f, "invalid type parameter #{index}"),
+            InvalidTypeArgs { name, .. } => {
+                write!(f, "wrong number of type arguments for {name}")
+            }
+        }
+    }
+}
+
+/// Check a registry for problems that tracing would not produce but that may appear in
+/// registries loaded from external sources (e.g. YAML or JSON files). Issues are returned
+/// in a deterministic order.
+///
+/// Variant indices are unique by construction, since enums map them to variants.
+pub fn validate(registry: &Registry) -> Vec<ValidationIssue> {
+    let mut validator = Validator {
+        registry,
+        params: 0,
+        issues: Vec::new(),
+    };
+    for (name, format) in registry {
+        validator.check_container(name, format);
+    }
+    validator.issues
+}
+
+struct Validator<'a> {
+    registry: &'a Registry,
+    /// The number of type parameters of the current container.
+    params: usize,
+    issues: Vec<ValidationIssue>,
+}
+
+impl Validator<'_> {
+    fn check_name(&mut self, path: &[String]) {
+        let name = path.last().expect("paths are not empty");
+        let path = path.to_vec();
+        if name.is_empty() {
+            self.issues.push(ValidationIssue::EmptyName { path });
+        } else if !is_valid_name(name) {
+            self.issues.push(ValidationIssue::InvalidName { path });
+        }
+    }
+
+    fn check_container(&mut self, name: &str, format: &ContainerFormat) {
+        let path = vec![name.to_string()];
+        self.check_name(&path);
+        self.params = 0;
+        self.check_container_format(&path, format);
+    }
+
+    fn check_container_format(&mut self, path: &[String], format: &ContainerFormat) {
+        let path = path.to_vec();
+        match format {
+            ContainerFormat::UnitStruct => (),
+            ContainerFormat::NewTypeStruct(format) => self.check_format(&path, format),
+            ContainerFormat::TupleStruct(formats) => {
+                for format in formats {
+                    self.check_format(&path, format);
+                }
+            }
+            ContainerFormat::Struct(fields) => self.check_fields(&path, fields),
+            ContainerFormat::Enum(variants) => {
+                let mut names = BTreeSet::new();
+                for variant in variants.values() {
+                    let path = extend(&path, &variant.name);
+                    self.check_name(&path);
+                    if !names.insert(&variant.name) {
+                        self.issues
+                            .push(ValidationIssue::DuplicateName { path: path.clone() });
+                    }
+                    self.check_variant(&path, &variant.value);
+                }
+            }
+            ContainerFormat::Generic { params, body } => {
+                let mut names = BTreeSet::new();
+                for param in params {
+                    let path = extend(&path, param);
+                    self.check_name(&path);
+                    if !names.insert(param) {
+                        self.issues.push(ValidationIssue::DuplicateName { path });
+                    }
+                }
+                self.params = params.len();
+                self.check_container_format(&path, body);
+            }
+        }
+    }
+
+    fn check_fields(&mut self, path: &[String], fields: &[Named<Format>]) {
+        let mut names = BTreeSet::new();
+        for field in fields {
+            let path = extend(path, &field.name);
+            self.check_name(&path);
+            if !names.insert(&field.name) {
+                self.issues
+                    .push(ValidationIssue::DuplicateName { path: path.clone() });
+            }
+            self.check_format(&path, &field.value);
+        }
+    }
+
+    fn check_variant(&mut self, path: &[String], format: &VariantFormat) {
+        match format {
+            VariantFormat::Variable(variable) => match variable.borrow().as_ref() {
+                Some(format) => self.check_variant(path, format),
+                None => self.issues.push(ValidationIssue::UnknownFormat {
+                    path: path.to_vec(),
+                }),
+            },
+            VariantFormat::Unit => (),
+            VariantFormat::NewType(format) => self.check_format(path, format),
+            VariantFormat::Tuple(formats) => {
+                for format in formats {
+                    self.check_format(path, format);
+                }
+            }
+            VariantFormat::Struct(fields) => self.check_fields(path, fields),
+        }
+    }
+
+    fn check_format(&mut self, path: &[String], format: &Format) {
+        match format {
+            Format::Variable(variable) => match variable.borrow().as_ref() {
+                Some(format) => self.check_format(path, format),
+                None => self.issues.push(ValidationIssue::UnknownFormat {
+                    path: path.to_vec(),
+                }),
+            },
+            Format::TypeName(name) => {
+                if !self.registry.contains_key(name) {
+                    self.issues.push(ValidationIssue::DanglingReference {
+                        path: path.to_vec(),
+                        name: name.clone(),
+                    });
+                }
+            }
+            Format::Option(format) | Format::Seq(format) => self.check_format(path, format),
+            Format::Map { key, value } => {
+                self.check_format(path, key);
+                self.check_format(path, value);
+            }
+            Format::Tuple(formats) => {
+                for format in formats {
+                    self.check_format(path, format);
+                }
+            }
+            Format::Apply { name, args } => {
+                match self.registry.get(name) {
+                    None => self.issues.push(ValidationIssue::DanglingReference {
+                        path: path.to_vec(),
+                        name: name.clone(),
+                    }),
+                    Some(ContainerFormat::Generic { params, .. }) if params.len() == args.len() => {
+                    }
+                    Some(_) => self.issues.push(ValidationIssue::InvalidTypeArgs {
+                        path: path.to_vec(),
+                        name: name.clone(),
+                    }),
+                }
+                for format in args {
+                    self.check_format(path, format);
+                }
+            }
+            Format::TupleArray { content, .. } => self.check_format(path, content),
+            Format::TypeParam(index) => {
+                if *index >= self.params {
+                    self.issues.push(ValidationIssue::InvalidTypeParam {
+                        path: path.to_vec(),
+                        index: *index,
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn extend(path: &[String], name: &str) -> Vec<String> {
+    let mut path = path.to_vec();
+    path.push(name.to_string());
+    path
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    name.len() <= MAX_NAME_LENGTH
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A format where references to containers are replaced by the formats of the containers.
+/// See [`resolve`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedFormat<'a> {
+    /// A primitive format, e.g. `Format::U64`.
+    Primitive(&'a Format),
+    Option(Box<ResolvedFormat<'a>>),
+    Seq(Box<ResolvedFormat<'a>>),
+    Map {
+        key: Box<ResolvedFormat<'a>>,
+        value: Box<ResolvedFormat<'a>>,
+    },
+    Tuple(Vec<ResolvedFormat<'a>>),
+    TupleArray {
+        content: Box<ResolvedFormat<'a>>,
+        size: usize,
+    },
+    Union(Vec<ResolvedFormat<'a>>),
+    /// A container of the registry.
+    Container {
+        name: &'a str,
+        format: ResolvedContainer<'a>,
+    },
+    /// A reference to a container that is already being resolved, i.e. a recursive type.
+    Recursive(&'a str),
+    /// A reference to a container that is missing from the registry.
+    Missing(&'a str),
+    /// A generic container, resolved as a `Container`, `Recursive`, or `Missing` format,
+    /// with its type arguments.
+    Apply {
+        container: Box<ResolvedFormat<'a>>,
+        args: Vec<ResolvedFormat<'a>>,
+    },
+    /// A type parameter of the enclosing generic container.
+    TypeParam(usize),
+    /// A variable, which only occurs in formats that are still being traced.
+    Unknown,
+}
+
+/// The resolved format of a container. See [`resolve`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedContainer<'a> {
+    UnitStruct,
+    NewTypeStruct(Box<ResolvedFormat<'a>>),
+    TupleStruct(Vec<ResolvedFormat<'a>>),
+    Struct(Vec<(&'a str, ResolvedFormat<'a>)>),
+    Enum(Vec<(u32, &'a str, ResolvedVariant<'a>)>),
+    Generic {
+        params: &'a [String],
+        body: Box<ResolvedContainer<'a>>,
+    },
+}
+
+/// The resolved format of an enum variant. See [`resolve`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedVariant<'a> {
+    Unit,
+    NewType(Box<ResolvedFormat<'a>>),
+    Tuple(Vec<ResolvedFormat<'a>>),
+    Struct(Vec<(&'a str, ResolvedFormat<'a>)>),
+    /// A variable, which only occurs in formats that are still being traced.
+    Unknown,
+}
+
+impl ResolvedFormat<'_> {
+    /// The name of the container, if the format is a container or a recursive reference,
+    /// possibly with type arguments.
+    pub fn container_name(&self) -> Option<&str> {
+        match self {
+            ResolvedFormat::Container { name, .. } | ResolvedFormat::Recursive(name) => Some(name),
+            ResolvedFormat::Apply { container, .. } => container.container_name(),
+            _ => None,
+        }
+    }
+}
+
+/// Resolve `format` by following the references to the containers of `registry`, so that
+/// the logical structure of a format can be inspected without looking up containers at
+/// every level.
+/// * A container referenced again while it is being resolved (recursive types) is
+///   represented by `ResolvedFormat::Recursive`.
+/// * Variables are not followed. Registries returned by `Tracer::registry` contain none.
+pub fn resolve<'a>(registry: &'a Registry, format: &'a Format) -> ResolvedFormat<'a> {
+    Resolver {
+        registry,
+        in_progress: Vec::new(),
+    }
+    .resolve(format)
+}
+
+struct Resolver<'a> {
+    registry: &'a Registry,
+    in_progress: Vec<&'a str>,
+}
+
+impl<'a> Resolver<'a> {
+    fn resolve(&mut self, format: &'a Format) -> ResolvedFormat<'a> {
+        match format {
+            Format::Variable(_) => ResolvedFormat::Unknown,
+            Format::TypeName(name) => self.resolve_container(name),
+            Format::Apply { name, args } => ResolvedFormat::Apply {
+                container: Box::new(self.resolve_container(name)),
+                args: self.resolve_all(args),
+            },
+            Format::TypeParam(index) => ResolvedFormat::TypeParam(*index),
+            Format::Option(format) => ResolvedFormat::Option(Box::new(self.resolve(format))),
+            Format::Seq(format) => ResolvedFormat::Seq(Box::new(self.resolve(format))),
+            Format::Map { key, value } => ResolvedFormat::Map {
+                key: Box::new(self.resolve(key)),
+                value: Box::new(self.resolve(value)),
+            },
+            Format::Tuple(formats) => ResolvedFormat::Tuple(self.resolve_all(formats)),
+            Format::TupleArray { content, size } => ResolvedFormat::TupleArray {
+                content: Box::new(self.resolve(content)),
+                size: *size,
+            },
+            Format::Union(formats) => ResolvedFormat::Union(self.resolve_all(formats)),
+            _ => ResolvedFormat::Primitive(format),
+        }
+    }
+
+    fn resolve_all(&mut self, formats: &'a [Format]) -> Vec<ResolvedFormat<'a>> {
+        formats.iter().map(|format| self.resolve(format)).collect()
+    }
+
+    fn resolve_fields(
+        &mut self,
+        fields: &'a [Named<Format>],
+    ) -> Vec<(&'a str, ResolvedFormat<'a>)> {
+        fields
+            .iter()
+            .map(|field| (field.name.as_str(), self.resolve(&field.value)))
+            .collect()
+    }
+
+    fn resolve_container(&mut self, name: &'a str) -> ResolvedFormat<'a> {
+        let Some((name, format)) = self.registry.get_key_value(name) else {
+            return ResolvedFormat::Missing(name);
+        };
+        let name = name.as_str();
+        if self.in_progress.contains(&name) {
+            return ResolvedFormat::Recursive(name);
+        }
+        self.in_progress.push(name);
+        let format = self.resolve_container_format(format);
+        self.in_progress.pop();
+        ResolvedFormat::Container { name, format }
+    }
+
+    fn resolve_container_format(&mut self, format: &'a ContainerFormat) -> ResolvedContainer<'a> {
+        match format {
+            ContainerFormat::UnitStruct => ResolvedContainer::UnitStruct,
+            ContainerFormat::NewTypeStruct(format) => {
+                ResolvedContainer::NewTypeStruct(Box::new(self.resolve(format)))
+            }
+            ContainerFormat::TupleStruct(formats) => {
+                ResolvedContainer::TupleStruct(self.resolve_all(formats))
+            }
+            ContainerFormat::Struct(fields) => {
+                ResolvedContainer::Struct(self.resolve_fields(fields))
+            }
+            ContainerFormat::Enum(variants) => ResolvedContainer::Enum(
+                variants
+                    .iter()
+                    .map(|(index, variant)| {
+                        (
+                            *index,
+                            variant.name.as_str(),
+                            self.resolve_variant(&variant.value),
+                        )
+                    })
+                    .collect(),
+            ),
+            ContainerFormat::Generic { params, body } => ResolvedContainer::Generic {
+                params,
+                body: Box::new(self.resolve_container_format(body)),
+            },
+        }
+    }
+
+    fn resolve_variant(&mut self, format: &'a VariantFormat) -> ResolvedVariant<'a> {
+        match format {
+            VariantFormat::Variable(_) => ResolvedVariant::Unknown,
+            VariantFormat::Unit => ResolvedVariant::Unit,
+            VariantFormat::NewType(format) => {
+                ResolvedVariant::NewType(Box::new(self.resolve(format)))
+            }
+            VariantFormat::Tuple(formats) => ResolvedVariant::Tuple(self.resolve_all(formats)),
+            VariantFormat::Struct(fields) => ResolvedVariant::Struct(self.resolve_fields(fields)),
+        }
+    }
+}
+
+/// A format that does not contain other formats, reached from a container of a registry.
+/// See [`leaves`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Leaf<'a> {
+    /// The name of the root container, followed by the names of the fields, variants, and
+    /// containers leading to the format. Tuple elements are named after their positions,
+    /// map entries `key` and `value`. Options, sequences, arrays, and referenced containers
+    /// add no segment of their own.
+    pub path: Vec<String>,
+    /// A primitive format (including `Unit`, `Str`, and `Bytes`) or a type parameter. A
+    /// `Format::TypeName` is only a leaf when the container is missing or recursive.
+    pub format: &'a Format,
+}
+
+impl fmt::Display for Leaf<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:?}", self.path.join("."), self.format)
+    }
+}
+
+/// Every leaf format of `registry` with its path, e.g. `Tx.payload.Transfer.amount: U64`,
+/// in a deterministic order. This answers questions such as "where do we use floats?"
+/// without a custom recursive walker.
+/// * Paths start from the root containers, i.e. the containers that no other container
+///   refers to, then from the remaining containers (only reachable through cycles) by name.
+/// * References to containers are followed, so shared containers appear once per use.
+/// * Unit structs and unit variants have no leaves.
+pub fn leaves(registry: &Registry) -> Vec<Leaf<'_>> {
+    let referenced: BTreeSet<&str> = registry
+        .iter()
+        .flat_map(|(name, format)| {
+            references(format)
+                .into_iter()
+                .filter(move |other| *other != name)
+        })
+        .collect();
+    let mut walker = LeafWalker {
+        registry,
+        in_progress: Vec::new(),
+        path: Vec::new(),
+        visited: BTreeSet::new(),
+        leaves: Vec::new(),
+    };
+    let (roots, others): (Vec<&String>, Vec<&String>) = registry
+        .keys()
+        .partition(|name| !referenced.contains(name.as_str()));
+    for name in roots.into_iter().chain(others) {
+        if !walker.visited.contains(name.as_str()) {
+            walker.path.push(name.clone());
+            walker.walk_container(name);
+            walker.path.pop();
+        }
+    }
+    walker.leaves
+}
+
+/// Every leaf format reachable from the container `name`. See [`leaves`].
+pub fn container_leaves<'a>(registry: &'a Registry, name: &str) -> Vec<Leaf<'a>> {
+    let mut walker = LeafWalker {
+        registry,
+        in_progress: Vec::new(),
+        path: vec![name.to_string()],
+        visited: BTreeSet::new(),
+        leaves: Vec::new(),
+    };
+    walker.walk_container(name);
+    walker.leaves
+}
+
+struct LeafWalker<'a> {
+    registry: &'a Registry,
+    in_progress: Vec<&'a str>,
+    path: Vec<String>,
+    visited: BTreeSet<&'a str>,
+    leaves: Vec<Leaf<'a>>,
+}
+
+impl<'a> LeafWalker<'a> {
+    /// Walk the container `name`, whose path was already pushed. Return false if the
+    /// container is missing or already in progress.
+    fn walk_container(&mut self, name: &str) -> bool {
+        let Some((name, format)) = self.registry.get_key_value(name) else {
+            return false;
+        };
+        let name = name.as_str();
+        if self.in_progress.contains(&name) {
+            return false;
+        }
+        self.visited.insert(name);
+        self.in_progress.push(name);
+        self.walk_container_format(format);
+        self.in_progress.pop();
+        true
+    }
+
+    fn walk_container_format(&mut self, format: &'a ContainerFormat) {
+        match format {
+            ContainerFormat::UnitStruct => (),
+            ContainerFormat::NewTypeStruct(format) => self.walk(format),
+            ContainerFormat::TupleStruct(formats) => self.walk_all(formats),
+            ContainerFormat::Struct(fields) => self.walk_fields(fields),
+            ContainerFormat::Enum(variants) => {
+                for variant in variants.values() {
+                    self.path.push(variant.name.clone());
+                    match &variant.value {
+                        VariantFormat::Variable(_) | VariantFormat::Unit => (),
+                        VariantFormat::NewType(format) => self.walk(format),
+                        VariantFormat::Tuple(formats) => self.walk_all(formats),
+                        VariantFormat::Struct(fields) => self.walk_fields(fields),
+                    }
+                    self.path.pop();
+                }
+            }
+            ContainerFormat::Generic { body, .. } => self.walk_container_format(body),
+        }
+    }
+
+    fn walk(&mut self, format: &'a Format) {
+        match format {
+            Format::Variable(_) => (),
+            Format::TypeName(name) => {
+                if !self.walk_container(name) {
+                    self.push_leaf(format);
+                }
+            }
+            Format::Option(format)
+            | Format::Seq(format)
+            | Format::TupleArray {
+                content: format, ..
+            } => self.walk(format),
+            Format::Map { key, value } => {
+                self.walk_labeled("key".to_string(), key);
+                self.walk_labeled("value".to_string(), value);
+            }
+            Format::Tuple(formats) | Format::Union(formats) => self.walk_all(formats),
+            _ => self.push_leaf(format),
+        }
+    }
+
+    fn walk_all(&mut self, formats: &'a [Format]) {
+        for (index, format) in formats.iter().enumerate() {
+            self.walk_labeled(index.to_string(), format);
+        }
+    }
+
+    fn walk_fields(&mut self, fields: &'a [Named<Format>]) {
+        for field in fields {
+            self.walk_labeled(field.name.clone(), &field.value);
+        }
+    }
+
+    fn walk_labeled(&mut self, label: String, format: &'a Format) {
+        self.path.push(label);
+        self.walk(format);
+        self.path.pop();
+    }
+
+    fn push_leaf(&mut self, format: &'a Format) {
+        self.leaves.push(Leaf {
+            path: self.path.clone(),
+            format,
+        });
+    }
+}
+
+/// Inline the containers selected by `predicate` into the formats that refer to them, and
+/// remove them from the registry. Return the names of the inlined containers.
+/// * Only containers with a single inner format can be inlined, i.e. newtype structs and
+///   tuple structs or structs with a single field. The inner format replaces every
+///   reference to the container. This preserves binary encodings such as Bincode or BCS.
+/// * Recursive containers are never inlined.
+pub fn inline_containers<P>(registry: &mut Registry, mut predicate: P) -> Vec<String>
+where
+    P: FnMut(&str, &ContainerFormat) -> bool,
+{
+    let mut inlined: BTreeMap<String, Format> = registry
+        .iter()
+        .filter_map(|(name, format)| {
+            let inner = inner_format(format)?;
+            predicate(name, format).then(|| (name.clone(), inner.clone()))
+        })
+        .collect();
+    inlined.retain(|name, format| !reaches(registry, format, name));
+
+    // Expand the references between inlined containers. Since none of them is recursive,
+    // this terminates after at most one round per container.
+    loop {
+        let previous = inlined.clone();
+        for format in inlined.values_mut() {
+            replace_references(format, &previous);
+        }
+        if inlined == previous {
+            break;
+        }
+    }
+
+    for name in inlined.keys() {
+        registry.remove(name);
+    }
+    for format in registry.values_mut() {
+        replace_references(format, &inlined);
+    }
+    inlined.into_keys().collect()
+}
+
+/// Add the containers of `other` to `registry`. Containers defined in both registries are
+/// unified, so that the variants of an enum traced separately are combined. Return the
+/// names of the containers whose definitions are incompatible: such containers are left
+/// unchanged in `registry`.
+pub fn merge(registry: &mut Registry, other: Registry) -> Vec<String> {
+    let mut conflicts = Vec::new();
+    for (name, format) in other {
+        match registry.get_mut(&name) {
+            None => {
+                registry.insert(name, format);
+            }
+            Some(existing) => {
+                let mut merged = existing.clone();
+                if merged.unify(format).is_ok() {
+                    *existing = merged;
+                } else {
+                    conflicts.push(name);
+                }
+            }
+        }
+    }
+    conflicts
+}
+
+/// Same as `merge` for a registry `other` traced in the build configuration described by
+/// `condition`, e.g. `feature = "extra"`. The containers of `other` that are missing from
+/// `registry` or that already have a condition in `conditions` are recorded as existing
+/// when `condition` holds. Containers without a condition keep existing in every
+/// configuration.
+pub fn merge_conditional(
+    registry: &mut Registry,
+    conditions: &mut Conditions,
+    other: Registry,
+    condition: &str,
+) -> Vec<String> {
+    let conditional: Vec<String> = other
+        .keys()
+        .filter(|name| !registry.contains_key(*name) || conditions.condition(name).is_some())
+        .cloned()
+        .collect();
+    let conflicts = merge(registry, other);
+    for name in conditional {
+        if !conflicts.contains(&name) {
+            conditions.add_condition(&name, condition);
+        }
+    }
+    conflicts
+}
+
+/// Iterate over the containers of `registry` by increasing name.
+///
+/// `Registry` is an alias for a `BTreeMap`, so this cannot be an `IntoIterator`
+/// implementation. Prefer it to iterating over the map directly: it does not depend on
+/// how containers are stored.
+pub fn entries(registry: &Registry) -> impl Iterator<Item = (&str, &ContainerFormat)> {
+    registry
+        .iter()
+        .map(|(name, container)| (name.as_str(), container))
+}
+
+/// Iterate over the containers of `registry` of the given kind, by increasing name.
+/// Generic containers are matched by the kind of their body.
+pub fn entries_matching(
+    registry: &Registry,
+    kind: ContainerKind,
+) -> impl Iterator<Item = (&str, &ContainerFormat)> {
+    entries(registry).filter(move |(_, container)| container.kind() == kind)
+}
+
+/// Return the format of the container `id`, or `Error::MissingContainer`.
+pub fn lookup<'a>(registry: &'a Registry, id: &ContainerId) -> Result<&'a ContainerFormat> {
+    registry
+        .get(id.as_str())
+        .ok_or_else(|| Error::MissingContainer(id.to_string()))
+}
+
+/// Rename the container `from` of `registry` into `to` and update the references to it.
+/// * Fails with `Error::MissingContainer` if `from` is not in the registry, and with
+///   `Error::ContainerExists` if another container is already named `to`. The registry is
+///   left unchanged in both cases.
+/// * On success, return the id of the renamed container.
+pub fn rename(registry: &mut Registry, from: &ContainerId, to: ContainerId) -> Result<ContainerId> {
+    if from == &to {
+        lookup(registry, from)?;
+        return Ok(to);
+    }
+    if registry.contains_key(to.as_str()) {
+        return Err(Error::ContainerExists(to.to_string()));
+    }
+    let container = registry
+        .remove(from.as_str())
+        .ok_or_else(|| Error::MissingContainer(from.to_string()))?;
+    registry.insert(to.to_string(), container);
+    for container in registry.values_mut() {
+        container
+            .visit_mut(&mut |format| {
+                if let Format::TypeName(name) | Format::Apply { name, .. } = format {
+                    if name == from.as_str() {
+                        *name = to.to_string();
+                    }
+                }
+                Ok(())
+            })
+            .unwrap_or(());
+    }
+    Ok(to)
+}
+
+/// Return the containers of `registry` that can be reached from the containers named in
+/// `roots`, including the roots themselves. Names missing from the registry are ignored.
+pub fn reachable<'a, I>(registry: &Registry, roots: I) -> Registry
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut result = Registry::new();
+    let mut pending: Vec<&str> = roots.into_iter().collect();
+    while let Some(name) = pending.pop() {
+        if result.contains_key(name) {
+            continue;
+        }
+        if let Some(container) = registry.get(name) {
+            result.insert(name.to_string(), container.clone());
+            pending.extend(references(container));
+        }
+    }
+    result
+}
+
+/// Options of [`find`]. By default, only exact names are found.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct FindOptions {
+    case_insensitive: bool,
+    suffix: bool,
+    aliases: BTreeMap<String, String>,
+}
+
+impl FindOptions {
+    /// Options finding exact names only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options enabling case-insensitive and suffix matching.
+    pub fn lenient() -> Self {
+        Self::new().case_insensitive(true).suffix(true)
+    }
+
+    /// Compare names (and aliases) without regard to ASCII case.
+    pub fn case_insensitive(mut self, value: bool) -> Self {
+        self.case_insensitive = value;
+        self
+    }
+
+    /// Let a name match the containers whose names end with `::` followed by the name, e.g.
+    /// `MyType` matches `module::MyType`.
+    pub fn suffix(mut self, value: bool) -> Self {
+        self.suffix = value;
+        self
+    }
+
+    /// Register `alias` as another name of the container `name`, e.g. a former name.
+    pub fn alias(mut self, alias: &str, name: &str) -> Self {
+        self.aliases.insert(alias.to_string(), name.to_string());
+        self
+    }
+
+    fn matches(&self, candidate: &str, name: &str) -> bool {
+        if self.case_insensitive {
+            candidate.eq_ignore_ascii_case(name)
+        } else {
+            candidate == name
+        }
+    }
+
+    fn matches_suffix(&self, candidate: &str, name: &str) -> bool {
+        let Some(start) = candidate.len().checked_sub(name.len()) else {
+            return false;
+        };
+        candidate.is_char_boundary(start)
+            && candidate[..start].ends_with("::")
+            && self.matches(&candidate[start..], name)
+    }
+}
+
+/// Find the container of `registry` designated by `name`, e.g. when `name` is typed by a
+/// user. Return the name of the container as found in the registry.
+/// * The exact name is tried first, then the aliases, then the names matching
+///   case-insensitively, then the names matching as a suffix, as enabled by `options`.
+/// * A step matching several containers fails with `Error::AmbiguousName`, listing the
+///   candidates. No match fails with `Error::MissingContainer`.
+pub fn find<'a>(registry: &'a Registry, name: &str, options: &FindOptions) -> Result<&'a str> {
+    if let Some((found, _)) = registry.get_key_value(name) {
+        return Ok(found);
+    }
+    let aliases: Vec<&str> = options
+        .aliases
+        .iter()
+        .filter(|(alias, _)| options.matches(alias, name))
+        .map(|(_, target)| target.as_str())
+        .collect();
+    let steps: [&dyn Fn(&str) -> bool; 3] = [
+        &|candidate| aliases.contains(&candidate),
+        &|candidate| options.case_insensitive && options.matches(candidate, name),
+        &|candidate| options.suffix && options.matches_suffix(candidate, name),
+    ];
+    for step in steps {
+        let candidates: Vec<&str> = registry
+            .keys()
+            .map(String::as_str)
+            .filter(|candidate| step(candidate))
+            .collect();
+        match candidates[..] {
+            [] => continue,
+            [found] => return Ok(found),
+            _ => {
+                return Err(Error::AmbiguousName(
+                    name.to_string(),
+                    candidates.into_iter().map(String::from).collect(),
+                ))
+            }
+        }
+    }
+    Err(Error::MissingContainer(name.to_string()))
+}
+
+/// A naming convention applied by [`apply_naming_convention`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum NamingConvention {
+    /// E.g. `foo_bar`.
+    SnakeCase,
+    /// E.g. `FOO_BAR`.
+    ScreamingSnakeCase,
+    /// E.g. `fooBar`.
+    CamelCase,
+    /// E.g. `FooBar`.
+    PascalCase,
+}
+
+impl NamingConvention {
+    /// Convert a name to this convention. Words are delimited by underscores, dashes, and
+    /// changes of case, e.g. `HTTPServer_v2` is made of the words `http`, `server`, and `v2`.
+    pub fn apply(self, name: &str) -> String {
+        let words = split_words(name);
+        let capitalize = |word: &str| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        };
+        match self {
+            NamingConvention::SnakeCase => words.join("_"),
+            NamingConvention::ScreamingSnakeCase => words.join("_").to_ascii_uppercase(),
+            NamingConvention::CamelCase => words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| {
+                    if i == 0 {
+                        word.clone()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .collect(),
+            NamingConvention::PascalCase => words.iter().map(|word| capitalize(word)).collect(),
+        }
+    }
+}
+
+/// The names renamed by [`apply_naming_convention`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct NamingScope {
+    pub containers: bool,
+    pub fields: bool,
+    pub variants: bool,
+}
+
+impl NamingScope {
+    /// Rename containers, fields, and variants.
+    pub const ALL: NamingScope = NamingScope {
+        containers: true,
+        fields: true,
+        variants: true,
+    };
+}
+
+/// Rename the containers, fields, and variants of `registry` selected by `scope` according
+/// to `convention`, and update the references to renamed containers.
+/// * On success, return the new names indexed by the qualified names of the renamed
+///   members, as in [`Deprecations`](crate::Deprecations). Unchanged names are omitted.
+/// * If several containers, or several fields or variants of the same container, would
+///   end up with the same name, the registry is left unchanged and the qualified new
+///   names in conflict are returned as an error.
+pub fn apply_naming_convention(
+    registry: &mut Registry,
+    convention: NamingConvention,
+    scope: NamingScope,
+) -> Result<BTreeMap<Vec<String>, String>, Vec<Vec<String>>> {
+    let mut renamer = Renamer {
+        convention,
+        scope,
+        renames: BTreeMap::new(),
+        conflicts: Vec::new(),
+    };
+    let mut container_names = BTreeMap::new();
+    for name in registry.keys() {
+        let new_name = renamer.rename(&[], name, scope.containers);
+        if container_names.insert(new_name.clone(), name).is_some() {
+            renamer.conflicts.push(vec![new_name]);
+        }
+    }
+    let mut renamed = Registry::new();
+    for (name, format) in registry.iter() {
+        let mut format = format.clone();
+        renamer.rename_container(&[name.clone()], &mut format);
+        format
+            .visit_mut(&mut |format| {
+                if let Format::TypeName(name) | Format::Apply { name, .. } = format {
+                    *name = convention_name(&renamer, name);
+                }
+                Ok(())
+            })
+            .unwrap_or(());
+        renamed.insert(convention_name(&renamer, name), format);
+    }
+    if !renamer.conflicts.is_empty() {
+        renamer.conflicts.sort();
+        renamer.conflicts.dedup();
+        return Err(renamer.conflicts);
+    }
+    *registry = renamed;
+    Ok(renamer.renames)
+}
+
+/// The new name of the container `name`.
+fn convention_name(renamer: &Renamer, name: &str) -> String {
+    renamer
+        .renames
+        .get(&[name.to_string()][..])
+        .cloned()
+        .unwrap_or_else(|| name.to_string())
+}
+
+struct Renamer {
+    convention: NamingConvention,
+    scope: NamingScope,
+    renames: BTreeMap<Vec<String>, String>,
+    conflicts: Vec<Vec<String>>,
+}
+
+impl Renamer {
+    /// Compute the new name of the member `name` of `path`, and record it if it changed.
+    fn rename(&mut self, path: &[String], name: &str, enabled: bool) -> String {
+        if !enabled {
+            return name.to_string();
+        }
+        let new_name = self.convention.apply(name);
+        if new_name != name {
+            self.renames.insert(extend(path, name), new_name.clone());
+        }
+        new_name
+    }
+
+    fn rename_container(&mut self, path: &[String], format: &mut ContainerFormat) {
+        match format {
+            ContainerFormat::Struct(fields) => self.rename_fields(path, fields),
+            ContainerFormat::Enum(variants) => {
+                let mut names = BTreeSet::new();
+                for variant in variants.values_mut() {
+                    let variant_path = extend(path, &variant.name);
+                    variant.name = self.rename(path, &variant.name, self.scope.variants);
+                    if !names.insert(variant.name.clone()) {
+                        self.conflicts.push(extend(path, &variant.name));
+                    }
+                    if let VariantFormat::Struct(fields) = &mut variant.value {
+                        self.rename_fields(&variant_path, fields);
+                    }
+                }
+            }
+            ContainerFormat::Generic { body, .. } => self.rename_container(path, body),
+            _ => (),
+        }
+    }
+
+    fn rename_fields(&mut self, path: &[String], fields: &mut [Named<Format>]) {
+        let mut names = BTreeSet::new();
+        for field in fields {
+            field.name = self.rename(path, &field.name, self.scope.fields);
+            if !names.insert(field.name.clone()) {
+                self.conflicts.push(extend(path, &field.name));
+            }
+        }
+    }
+}
+
+fn split_words(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '_' || c == '-' {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        // A word starts at an uppercase letter following a lowercase letter or a digit,
+        // or at the last uppercase letter of an acronym followed by a lowercase letter.
+        if c.is_ascii_uppercase() && !word.is_empty() {
+            let previous = chars[i - 1];
+            let next_is_lowercase = chars.get(i + 1).is_some_and(|c| c.is_ascii_lowercase());
+            if previous.is_ascii_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_ascii_uppercase() && next_is_lowercase)
+            {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.push(c.to_ascii_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// The version of the serialized form of registries tagged by [`VersionedRegistry`].
+/// Registries serialized directly as maps, e.g. by the `yaml` and `json` exporters for code
+/// generators, have the implicit version 0 and remain readable by [`migrate_from`].
+///
+/// Since [`Registry`] is a type alias, the version lives in this module rather than in an
+/// associated constant.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// A registry together with the version of its serialized form, for registries persisted
+/// long-term. Any Serde format may be used, e.g. YAML or JSON.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VersionedRegistry {
+    /// The version of the serialized form, normally [`FORMAT_VERSION`].
+    pub version: u32,
+    /// The formats of the containers.
+    pub registry: Registry,
+    /// What the tracer learned about the containers beyond their formats. Omitted when
+    /// empty (since version 2).
+    #[serde(default, skip_serializing_if = "RegistryMetadata::is_empty")]
+    pub metadata: RegistryMetadata,
+}
+
+impl VersionedRegistry {
+    /// Tag a registry with the current [`FORMAT_VERSION`].
+    pub fn new(registry: Registry) -> Self {
+        Self::with_metadata(registry, RegistryMetadata::new())
+    }
+
+    /// Tag a registry and its metadata with the current [`FORMAT_VERSION`].
+    pub fn with_metadata(registry: Registry, metadata: RegistryMetadata) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            registry,
+            metadata,
+        }
+    }
+}
+
+/// A finalized registry, stamped with its stable hash.
+///
+/// The registry can be read through `Deref`, but not mutated, since any change would
+/// invalidate the hash and the diffs computed against the registry. APIs that expect a
+/// finalized registry can require a `FrozenRegistry` so that such mistakes are caught at
+/// compile time:
+///
+/// ```compile_fail
+/// use serde_reflection::{registry, ContainerFormat, Registry};
+///
+/// let mut frozen = registry::freeze_all(Registry::new());
+/// frozen.insert("Foo".to_string(), ContainerFormat::UnitStruct);
+/// ```
+///
+/// Since [`Registry`] is a type alias, registries are frozen by [`freeze_all`] rather than
+/// by a method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrozenRegistry {
+    registry: Registry,
+    hash: u64,
+}
+
+impl FrozenRegistry {
+    /// The hash of the registry when it was frozen. Like [`ContainerFormat::stable_hash`],
+    /// it only depends on the content of the registry.
+    pub fn stable_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Release the registry, e.g. to update it before freezing it again.
+    pub fn into_inner(self) -> Registry {
+        self.registry
+    }
+}
+
+impl std::ops::Deref for FrozenRegistry {
+    type Target = Registry;
+
+    fn deref(&self) -> &Registry {
+        &self.registry
+    }
+}
+
+impl From<Registry> for FrozenRegistry {
+    fn from(registry: Registry) -> Self {
+        freeze_all(registry)
+    }
+}
+
+/// Freeze a finalized registry. See [`FrozenRegistry`].
+pub fn freeze_all(registry: Registry) -> FrozenRegistry {
+    let mut hasher = StableHasher::new();
+    hasher.write_u64(registry.len() as u64);
+    for (name, format) in &registry {
+        hasher.write_str(name);
+        format.stable_hash_into(&mut hasher);
+    }
+    FrozenRegistry {
+        registry,
+        hash: hasher.finish(),
+    }
+}
+
+/// The two kinds of encodings told apart by `is_human_readable()` in Serde, e.g. JSON and
+/// Bincode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum EncodingKind {
+    /// Encodings such as JSON or YAML.
+    HumanReadable,
+    /// Encodings such as Bincode or BCS.
+    Binary,
+}
+
+/// The registries of the same types traced with both kinds of encodings, e.g. with
+/// `TracerConfig::both_encodings`.
+///
+/// Most containers have the same format in both encodings. They are stored once, in the
+/// shared core. The containers whose formats differ, or that only exist in one encoding, are
+/// stored in the view of each encoding.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MultiRegistry {
+    core: Registry,
+    human_readable: Registry,
+    binary: Registry,
+}
+
+impl MultiRegistry {
+    /// Split the registries of both encodings into a shared core and the specific
+    /// containers of each encoding.
+    pub fn new(human_readable: Registry, mut binary: Registry) -> Self {
+        let mut result = Self::default();
+        for (name, format) in human_readable {
+            if binary.get(&name) == Some(&format) {
+                binary.remove(&name);
+                result.core.insert(name, format);
+            } else {
+                result.human_readable.insert(name, format);
+            }
+        }
+        result.binary = binary;
+        result
+    }
+
+    /// The containers that have the same format in both encodings.
+    pub fn core(&self) -> &Registry {
+        &self.core
+    }
+
+    /// The containers whose formats are specific to `encoding`.
+    pub fn specific(&self, encoding: EncodingKind) -> &Registry {
+        match encoding {
+            EncodingKind::HumanReadable => &self.human_readable,
+            EncodingKind::Binary => &self.binary,
+        }
+    }
+
+    /// The complete registry of `encoding`.
+    pub fn view(&self, encoding: EncodingKind) -> Registry {
+        let mut registry = self.core.clone();
+        registry.extend(
+            self.specific(encoding)
+                .iter()
+                .map(|(name, format)| (name.clone(), format.clone())),
+        );
+        registry
+    }
+
+    /// Whether both encodings have the same containers with the same formats.
+    pub fn is_uniform(&self) -> bool {
+        self.human_readable.is_empty() && self.binary.is_empty()
+    }
+}
+
+/// Upgrade a serialized registry from one version to the next.
+#[cfg(feature = "json")]
+type Migration = fn(serde_json::Value) -> Result<serde_json::Value>;
+
+/// The migration from each version `v` to `v + 1`, indexed by `v`.
+#[cfg(feature = "json")]
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize] = [
+    // Version 0 is a bare map of containers.
+    |registry| Ok(serde_json::json!({ "version": 1, "registry": registry })),
+    // Version 2 adds the optional metadata.
+    |mut versioned| {
+        versioned["version"] = 2.into();
+        Ok(versioned)
+    },
+];
+
+/// Detect the version of a registry serialized in JSON, either as a [`VersionedRegistry`]
+/// or as a bare map (version 0).
+#[cfg(feature = "json")]
+pub fn detect_version(bytes: &[u8]) -> Result<u32> {
+    let value: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| Error::InvalidRegistryFile(e.to_string()))?;
+    let serde_json::Value::Object(map) = value else {
+        return Err(Error::InvalidRegistryFile("expected a map".into()));
+    };
+    // Container formats are never numbers, so a bare map cannot be confused with the
+    // tagged form.
+    let expected_len = 2 + usize::from(map.contains_key("metadata"));
+    match (map.get("version"), map.get("registry")) {
+        (Some(serde_json::Value::Number(version)), Some(_)) if map.len() == expected_len => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| Error::InvalidRegistryFile(format!("invalid version {version}"))),
+        _ => Ok(0),
+    }
+}
+
+/// Read a registry serialized in JSON with the version `detected_version` (see
+/// [`detect_version`]), applying the migrations up to [`FORMAT_VERSION`].
+#[cfg(feature = "json")]
+pub fn migrate_from(bytes: &[u8], detected_version: u32) -> Result<Registry> {
+    Ok(migrate_versioned_from(bytes, detected_version)?.registry)
+}
+
+/// Read a registry serialized in JSON with the version `detected_version`, as in
+/// [`migrate_from`], keeping its metadata.
+#[cfg(feature = "json")]
+pub fn migrate_versioned_from(bytes: &[u8], detected_version: u32) -> Result<VersionedRegistry> {
+    if detected_version > FORMAT_VERSION {
+        return Err(Error::InvalidRegistryFile(format!(
+            "unsupported version {detected_version} (latest is {FORMAT_VERSION})"
+        )));
+    }
+    let mut value: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| Error::InvalidRegistryFile(e.to_string()))?;
+    for migration in &MIGRATIONS[detected_version as usize..] {
+        value = migration(value)?;
+    }
+    let versioned: VersionedRegistry =
+        serde_json::from_value(value).map_err(|e| Error::InvalidRegistryFile(e.to_string()))?;
+    if versioned.version != FORMAT_VERSION {
+        return Err(Error::InvalidRegistryFile(format!(
+            "found version {} after migrating from version {detected_version}",
+            versioned.version
+        )));
+    }
+    Ok(versioned)
+}
+
+fn inner_format(format: &ContainerFormat) -> Option<&Format> {
+    match format {
+        ContainerFormat::NewTypeStruct(format) => Some(format),
+        ContainerFormat::TupleStruct(formats) if formats.len() == 1 => Some(&formats[0]),
+        ContainerFormat::Struct(fields) if fields.len() == 1 => Some(&fields[0].value),
+        _ => None,
+    }
+}
+
+fn replace_references<F: FormatHolder>(holder: &mut F, inlined: &BTreeMap<String, Format>) {
+    holder
+        .visit_mut(&mut |format| {
+            if let Format::TypeName(name) = format {
+                if let Some(inner) = inlined.get(name) {
+                    *format = inner.clone();
+                }
+            }
+            Ok(())
+        })
+        .unwrap_or(());
+}
+
+/// Whether the container `target` can be reached from `format`.
+fn reaches(registry: &Registry, format: &Format, target: &str) -> bool {
+    let mut visited = BTreeSet::new();
+    let mut pending = references(format);
+    while let Some(name) = pending.pop() {
+        if name == target {
+            return true;
+        }
+        if visited.insert(name) {
+            if let Some(container) = registry.get(name) {
+                pending.extend(references(container));
+            }
+        }
+    }
+    false
+}
+
+pub(crate) fn references<F: FormatHolder>(holder: &F) -> Vec<&str> {
+    let mut names = Vec::new();
+    holder
+        .visit(&mut |format| {
+            if let Format::TypeName(name) | Format::Apply { name, .. } = format {
+                names.push(name.as_str());
+            }
+            Ok(())
+        })
+        .unwrap_or(());
+    names
+}
+
+/// The formats of the fields of a container, with their paths: field names or positions,
+/// preceded by the variant name for enums.
+pub(crate) fn members(format: &ContainerFormat) -> Vec<(Vec<String>, &Format)> {
+    match format {
+        ContainerFormat::UnitStruct => Vec::new(),
+        ContainerFormat::NewTypeStruct(format) => vec![(vec!["0".to_string()], format.as_ref())],
+        ContainerFormat::TupleStruct(formats) => positional_members(formats),
+        ContainerFormat::Struct(fields) => named_members(fields),
+        ContainerFormat::Enum(variants) => variants
+            .values()
+            .flat_map(|variant| {
+                let members = match &variant.value {
+                    VariantFormat::Variable(_) | VariantFormat::Unit => Vec::new(),
+                    VariantFormat::NewType(format) => vec![(Vec::new(), format.as_ref())],
+                    VariantFormat::Tuple(formats) => positional_members(formats),
+                    VariantFormat::Struct(fields) => named_members(fields),
+                };
+                members.into_iter().map(|(mut path, format)| {
+                    path.insert(0, variant.name.clone());
+                    (path, format)
+                })
+            })
+            .collect(),
+        ContainerFormat::Generic { body, .. } => members(body),
+    }
+}
+
+fn positional_members(formats: &[Format]) -> Vec<(Vec<String>, &Format)> {
+    formats
+        .iter()
+        .enumerate()
+        .map(|(index, format)| (vec![index.to_string()], format))
+        .collect()
+}
+
+fn named_members(fields: &[Named<Format>]) -> Vec<(Vec<String>, &Format)> {
+    fields
+        .iter()
+        .map(|field| (vec![field.name.clone()], &field.value))
+        .collect()
+}
@@ -3,7 +3,9 @@
 
 use crate::{
     error::{Error, Result},
+    events::TraceEvent,
     format::*,
+    generic::type_param_index,
     trace::{Samples, Tracer},
     value::Value,
 };
@@ -22,6 +24,37 @@ impl<'a> Serializer<'a> {
     pub fn new(tracer: &'a mut Tracer, samples: &'a mut Samples) -> Self {
         Self { tracer, samples }
     }
+
+    /// Serialize a member of a container, sequence, or option. Borrowed byte slices are
+    /// recorded as `Format::Bytes`, as expected by their implementation of `Deserialize`,
+    /// although their implementation of `Serialize` produces a sequence.
+    fn serialize_member<T>(self, content: &T) -> Result<(Format, Value)>
+    where
+        T: ?Sized + Serialize,
+    {
+        let (format, value) = content.serialize(self)?;
+        match value {
+            Value::Seq(values) if is_byte_slice::<T>() => {
+                let bytes = values
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::U8(byte) => byte,
+                        _ => unreachable!("byte slices contain bytes"),
+                    })
+                    .collect();
+                Ok((Format::Bytes, Value::Bytes(bytes)))
+            }
+            value => Ok((format, value)),
+        }
+    }
+}
+
+/// Whether `T` is a borrowed slice of bytes.
+fn is_byte_slice<T: ?Sized>() -> bool {
+    let type_id = typeid::of::<T>();
+    type_id == typeid::of::<[u8]>()
+        || type_id == typeid::of::<&[u8]>()
+        || type_id == typeid::of::<&mut [u8]>()
 }
 
 impl<'a> ser::Serializer for Serializer<'a> {
@@ -36,70 +69,121 @@ impl<'a> ser::Serializer for Serializer<'a> {
     type SerializeStructVariant = StructVariantSerializer<'a>;
 
     fn serialize_bool(self, content: bool) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_bool",
+        });
         Ok((Format::Bool, Value::Bool(content)))
     }
 
     fn serialize_i8(self, content: i8) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_i8",
+        });
         Ok((Format::I8, Value::I8(content)))
     }
 
     fn serialize_i16(self, content: i16) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_i16",
+        });
         Ok((Format::I16, Value::I16(content)))
     }
 
     fn serialize_i32(self, content: i32) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_i32",
+        });
         Ok((Format::I32, Value::I32(content)))
     }
 
     fn serialize_i64(self, content: i64) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_i64",
+        });
         Ok((Format::I64, Value::I64(content)))
     }
 
     fn serialize_i128(self, content: i128) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_i128",
+        });
         Ok((Format::I128, Value::I128(content)))
     }
 
     fn serialize_u8(self, content: u8) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_u8",
+        });
         Ok((Format::U8, Value::U8(content)))
     }
 
     fn serialize_u16(self, content: u16) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_u16",
+        });
         Ok((Format::U16, Value::U16(content)))
     }
 
     fn serialize_u32(self, content: u32) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_u32",
+        });
         Ok((Format::U32, Value::U32(content)))
     }
 
     fn serialize_u64(self, content: u64) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_u64",
+        });
         Ok((Format::U64, Value::U64(content)))
     }
 
     fn serialize_u128(self, content: u128) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_u128",
+        });
         Ok((Format::U128, Value::U128(content)))
     }
 
     fn serialize_f32(self, content: f32) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_f32",
+        });
         Ok((Format::F32, Value::F32(content)))
     }
 
     fn serialize_f64(self, content: f64) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_f64",
+        });
         Ok((Format::F64, Value::F64(content)))
     }
 
     fn serialize_char(self, content: char) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_char",
+        });
         Ok((Format::Char, Value::Char(content)))
     }
 
     fn serialize_str(self, content: &str) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_str",
+        });
         Ok((Format::Str, Value::Str(content.into())))
     }
 
     fn serialize_bytes(self, content: &[u8]) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_bytes",
+        });
         Ok((Format::Bytes, Value::Bytes(content.into())))
     }
 
     fn serialize_none(self) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_none",
+        });
         Ok((Format::unknown(), Value::Option(None)))
     }
 
@@ -107,7 +191,11 @@ impl<'a> ser::Serializer for Serializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let (format, value) = content.serialize(self)?;
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_some",
+        });
+        self.tracer.check_type::<T>()?;
+        let (format, value) = self.serialize_member(content)?;
         Ok((
             Format::Option(Box::new(format)),
             Value::Option(Some(Box::new(value))),
@@ -115,10 +203,19 @@ impl<'a> ser::Serializer for Serializer<'a> {
     }
 
     fn serialize_unit(self) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_unit",
+        });
         Ok((Format::Unit, Value::Unit))
     }
 
     fn serialize_unit_struct(self, name: &'static str) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_unit_struct",
+        });
+        if let Some(index) = type_param_index(name) {
+            return Ok((Format::TypeParam(index), Value::Unit));
+        }
         self.tracer.record_container(
             self.samples,
             name,
@@ -134,6 +231,9 @@ impl<'a> ser::Serializer for Serializer<'a> {
         variant_index: u32,
         variant_name: &'static str,
     ) -> Result<(Format, Value)> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_unit_variant",
+        });
         self.tracer.record_variant(
             self.samples,
             name,
@@ -148,7 +248,19 @@ impl<'a> ser::Serializer for Serializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let (format, value) = content.serialize(Serializer::new(self.tracer, self.samples))?;
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_newtype_struct",
+        });
+        if self.tracer.config.transparent_containers.contains(name) {
+            return content.serialize(self);
+        }
+        self.tracer.enter_ignored(name);
+        let result = self
+            .tracer
+            .check_member_type::<T>(name, &[])
+            .and_then(|()| Serializer::new(self.tracer, self.samples).serialize_member(content));
+        self.tracer.exit_ignored(name);
+        let (format, value) = result?;
         self.tracer.record_container(
             self.samples,
             name,
@@ -168,7 +280,16 @@ impl<'a> ser::Serializer for Serializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let (format, value) = content.serialize(Serializer::new(self.tracer, self.samples))?;
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_newtype_variant",
+        });
+        self.tracer.enter_ignored(name);
+        let result = self
+            .tracer
+            .check_member_type::<T>(name, &[variant_name])
+            .and_then(|()| Serializer::new(self.tracer, self.samples).serialize_member(content));
+        self.tracer.exit_ignored(name);
+        let (format, value) = result?;
         self.tracer.record_variant(
             self.samples,
             name,
@@ -180,6 +301,9 @@ impl<'a> ser::Serializer for Serializer<'a> {
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_seq",
+        });
         Ok(SeqSerializer {
             tracer: self.tracer,
             samples: self.samples,
@@ -189,6 +313,9 @@ impl<'a> ser::Serializer for Serializer<'a> {
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_tuple",
+        });
         Ok(TupleSerializer {
             tracer: self.tracer,
             samples: self.samples,
@@ -202,6 +329,10 @@ impl<'a> ser::Serializer for Serializer<'a> {
         name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_tuple_struct",
+        });
+        self.tracer.enter_ignored(name);
         Ok(TupleStructSerializer {
             tracer: self.tracer,
             samples: self.samples,
@@ -218,6 +349,10 @@ impl<'a> ser::Serializer for Serializer<'a> {
         variant_name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_tuple_variant",
+        });
+        self.tracer.enter_ignored(name);
         Ok(TupleVariantSerializer {
             tracer: self.tracer,
             samples: self.samples,
@@ -230,16 +365,23 @@ impl<'a> ser::Serializer for Serializer<'a> {
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_map",
+        });
         Ok(MapSerializer {
             tracer: self.tracer,
             samples: self.samples,
             key_format: Format::unknown(),
-            value_format: Format::unknown(),
+            value_formats: Vec::new(),
             values: Vec::new(),
         })
     }
 
     fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_struct",
+        });
+        self.tracer.enter_ignored(name);
         Ok(StructSerializer {
             tracer: self.tracer,
             samples: self.samples,
@@ -256,6 +398,10 @@ impl<'a> ser::Serializer for Serializer<'a> {
         variant_name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
+        self.tracer.record_event(|| TraceEvent::SerializerCall {
+            method: "serialize_struct_variant",
+        });
+        self.tracer.enter_ignored(name);
         Ok(StructVariantSerializer {
             tracer: self.tracer,
             samples: self.samples,
@@ -288,13 +434,32 @@ impl<'a> ser::SerializeSeq for SeqSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let (format, value) = content.serialize(Serializer::new(self.tracer, self.samples))?;
+        self.tracer.check_type::<T>()?;
+        let (format, value) =
+            Serializer::new(self.tracer, self.samples).serialize_member(content)?;
         self.format.unify(format)?;
         self.values.push(value);
         Ok(())
     }
 
     fn end(self) -> Result<(Format, Value)> {
+        // The format is coerced to `Format::Bytes` when the registry is built. The value is
+        // converted right away so that samples agree with the registry.
+        if self.tracer.config.u8_seqs_as_bytes {
+            let mut content = self.format.clone();
+            content.reduce();
+            if content == Format::U8 {
+                let bytes = self
+                    .values
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::U8(byte) => byte,
+                        _ => unreachable!("sequences of u8 contain bytes"),
+                    })
+                    .collect();
+                return Ok((Format::Seq(Box::new(self.format)), Value::Bytes(bytes)));
+            }
+        }
         Ok((Format::Seq(Box::new(self.format)), Value::Seq(self.values)))
     }
 }
@@ -315,7 +480,9 @@ impl<'a> ser::SerializeTuple for TupleSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let (format, value) = content.serialize(Serializer::new(self.tracer, self.samples))?;
+        self.tracer.check_type::<T>()?;
+        let (format, value) =
+            Serializer::new(self.tracer, self.samples).serialize_member(content)?;
         self.formats.push(format);
         self.values.push(value);
         Ok(())
@@ -343,7 +510,10 @@ impl<'a> ser::SerializeTupleStruct for TupleStructSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let (format, value) = content.serialize(Serializer::new(self.tracer, self.samples))?;
+        let index = self.formats.len().to_string();
+        self.tracer.check_member_type::<T>(self.name, &[&index])?;
+        let (format, value) =
+            Serializer::new(self.tracer, self.samples).serialize_member(content)?;
         self.formats.push(format);
         self.values.push(value);
         Ok(())
@@ -352,6 +522,7 @@ impl<'a> ser::SerializeTupleStruct for TupleStructSerializer<'a> {
     fn end(self) -> Result<(Format, Value)> {
         let format = ContainerFormat::TupleStruct(self.formats);
         let value = Value::Seq(self.values);
+        self.tracer.exit_ignored(self.name);
         self.tracer.record_container(
             self.samples,
             self.name,
@@ -381,7 +552,11 @@ impl<'a> ser::SerializeTupleVariant for TupleVariantSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let (format, value) = content.serialize(Serializer::new(self.tracer, self.samples))?;
+        let index = self.formats.len().to_string();
+        self.tracer
+            .check_member_type::<T>(self.name, &[self.variant_name, &index])?;
+        let (format, value) =
+            Serializer::new(self.tracer, self.samples).serialize_member(content)?;
         self.formats.push(format);
         self.values.push(value);
         Ok(())
@@ -390,6 +565,7 @@ impl<'a> ser::SerializeTupleVariant for TupleVariantSerializer<'a> {
     fn end(self) -> Result<(Format, Value)> {
         let variant = VariantFormat::Tuple(self.formats);
         let value = Value::Seq(self.values);
+        self.tracer.exit_ignored(self.name);
         self.tracer.record_variant(
             self.samples,
             self.name,
@@ -406,7 +582,7 @@ pub struct MapSerializer<'a> {
     samples: &'a mut Samples,
 
     key_format: Format,
-    value_format: Format,
+    value_formats: Vec<Format>,
     values: Vec<Value>,
 }
 
@@ -418,6 +594,7 @@ impl<'a> ser::SerializeMap for MapSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
+        self.tracer.check_type::<T>()?;
         let (format, value) = key.serialize(Serializer::new(self.tracer, self.samples))?;
         self.key_format.unify(format)?;
         self.values.push(value);
@@ -428,22 +605,55 @@ impl<'a> ser::SerializeMap for MapSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let (format, value) = content.serialize(Serializer::new(self.tracer, self.samples))?;
-        self.value_format.unify(format)?;
+        self.tracer.check_type::<T>()?;
+        let (format, value) =
+            Serializer::new(self.tracer, self.samples).serialize_member(content)?;
+        self.value_formats.push(format);
         self.values.push(value);
         Ok(())
     }
 
     fn end(self) -> Result<(Format, Value)> {
+        let value_format = unify_map_values(self.value_formats, self.tracer.config.union_formats)?;
         let format = Format::Map {
             key: Box::new(self.key_format),
-            value: Box::new(self.value_format),
+            value: Box::new(value_format),
+        };
+        let value = if self.tracer.config.record_map_entries {
+            let mut values = self.values.into_iter();
+            let mut entries = Vec::new();
+            while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                entries.push((key, value));
+            }
+            Value::Map(entries)
+        } else {
+            Value::Seq(self.values)
         };
-        let value = Value::Seq(self.values);
         Ok((format, value))
     }
 }
 
+/// Unify the formats of the values of a map. Formats that cannot be unified are widened into
+/// a common format, or else gathered into a `Format::Union` if `union` is set.
+fn unify_map_values(formats: Vec<Format>, union: bool) -> Result<Format> {
+    let mut result = Format::unknown();
+    for format in formats {
+        let mut previous = result.clone();
+        previous.reduce();
+        if result.unify(format.clone()).is_ok() {
+            continue;
+        }
+        let mut format = format;
+        format.reduce();
+        result = match widen(&previous, &format) {
+            Some(widened) => widened,
+            None if union => unite(previous, format),
+            None => return Err(Error::IncompatibleMapValues(previous, format)),
+        };
+    }
+    Ok(result)
+}
+
 pub struct StructSerializer<'a> {
     tracer: &'a mut Tracer,
     samples: &'a mut Samples,
@@ -461,7 +671,9 @@ impl<'a> ser::SerializeStruct for StructSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let (format, value) = content.serialize(Serializer::new(self.tracer, self.samples))?;
+        self.tracer.check_member_type::<T>(self.name, &[name])?;
+        let (format, value) =
+            Serializer::new(self.tracer, self.samples).serialize_member(content)?;
         self.fields.push(Named {
             name: name.into(),
             value: format,
@@ -473,6 +685,7 @@ impl<'a> ser::SerializeStruct for StructSerializer<'a> {
     fn end(self) -> Result<(Format, Value)> {
         let format = ContainerFormat::Struct(self.fields);
         let value = Value::Seq(self.values);
+        self.tracer.exit_ignored(self.name);
         self.tracer.record_container(
             self.samples,
             self.name,
@@ -501,7 +714,10 @@ impl<'a> ser::SerializeStructVariant for StructVariantSerializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        let (format, value) = content.serialize(Serializer::new(self.tracer, self.samples))?;
+        self.tracer
+            .check_member_type::<T>(self.name, &[self.variant_name, name])?;
+        let (format, value) =
+            Serializer::new(self.tracer, self.samples).serialize_member(content)?;
         self.fields.push(Named {
             name: name.into(),
             value: format,
@@ -513,6 +729,7 @@ impl<'a> ser::SerializeStructVariant for StructVariantSerializer<'a> {
     fn end(self) -> Result<(Format, Value)> {
         let variant = VariantFormat::Struct(self.fields);
         let value = Value::Seq(self.values);
+        self.tracer.exit_ignored(self.name);
         self.tracer.record_variant(
             self.samples,
             self.name,
@@ -0,0 +1,349 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Registries with structural sharing.
+//!
+//! Large registries often repeat the same formats, e.g. a field `Vec<BTreeMap<String,
+//! Vec<u8>>>` in many structs. A [`SharedRegistry`] stores each distinct format of a field,
+//! a tuple element, or a variant once and shares it between all its occurrences. Formats
+//! are compared by pointer first, which makes comparing registries with shared formats
+//! faster.
+//!
+//! Shared formats are mutated with `Rc::make_mut`, which copies a format only when it is
+//! shared. A `SharedRegistry` is serialized and deserialized exactly like a [`Registry`].
+//!
+//! ```rust
+//! use serde_reflection::{ContainerFormat, Format, Named, Registry};
+//! use serde_reflection::shared::{SharedContainer, SharedRegistry};
+//! use std::rc::Rc;
+//!
+//! let format = Format::Seq(Box::new(Format::Bytes));
+//! let mut registry = Registry::new();
+//! for name in ["A", "B"] {
+//!     let field = Named { name: "data".into(), value: format.clone() };
+//!     registry.insert(name.into(), ContainerFormat::Struct(vec![field]));
+//! }
+//!
+//! let mut shared = SharedRegistry::from(registry.clone());
+//! assert_eq!(shared.distinct_formats(), 1);
+//! assert_eq!(shared.to_registry(), registry);
+//!
+//! // Copy on write.
+//! if let Some(SharedContainer::Struct(fields)) = shared.get_mut("B") {
+//!     *Rc::make_mut(&mut fields[0].value) = Format::Bytes;
+//! }
+//! assert_eq!(shared.to_container("A").unwrap(), registry["A"]);
+//! ```
+
+use crate::{
+    hash::{StableHash, StableHasher},
+    ContainerFormat, Format, Named, Registry, VariantFormat,
+};
+use serde::{de, ser::SerializeMap, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    rc::Rc,
+};
+
+/// A format shared between its occurrences in a [`SharedRegistry`].
+pub type SharedFormat = Rc<Format>;
+
+/// Same as [`ContainerFormat`] with shared formats.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SharedContainer {
+    /// An empty struct, e.g. `struct A`.
+    UnitStruct,
+    /// A struct with a single unnamed parameter, e.g. `struct A(u16)`
+    NewTypeStruct(SharedFormat),
+    /// A struct with several unnamed parameters, e.g. `struct A(u16, u32)`
+    TupleStruct(Vec<SharedFormat>),
+    /// A struct with named parameters, e.g. `struct A { a: Foo }`.
+    Struct(Vec<Named<SharedFormat>>),
+    /// An enum, that is, an enumeration of variants.
+    Enum(BTreeMap<u32, Named<SharedVariant>>),
+    /// A container with type parameters. See `ContainerFormat::Generic`.
+    Generic {
+        params: Vec<String>,
+        body: Box<SharedContainer>,
+    },
+}
+
+/// Same as [`VariantFormat`] with shared formats.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SharedVariant {
+    /// A variant without parameters, e.g. `A` in `enum X { A }`
+    Unit,
+    /// A variant with a single unnamed parameter, e.g. `A` in `enum X { A(u16) }`
+    NewType(SharedFormat),
+    /// A struct with several unnamed parameters, e.g. `A` in `enum X { A(u16, u32) }`
+    Tuple(Vec<SharedFormat>),
+    /// A struct with named parameters, e.g. `A` in `enum X { A { a: Foo } }`
+    Struct(Vec<Named<SharedFormat>>),
+}
+
+/// A registry storing each distinct format once. See the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct SharedRegistry {
+    containers: BTreeMap<String, SharedContainer>,
+    /// Interned formats, indexed by stable hash.
+    formats: HashMap<u64, Vec<SharedFormat>>,
+}
+
+impl SharedRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a container, sharing its formats with the rest of the registry. Return the
+    /// previous definition of the container, if any.
+    pub fn insert(&mut self, name: String, format: ContainerFormat) -> Option<SharedContainer> {
+        let container = self.share_container(format);
+        self.containers.insert(name, container)
+    }
+
+    /// Remove a container.
+    pub fn remove(&mut self, name: &str) -> Option<SharedContainer> {
+        self.containers.remove(name)
+    }
+
+    /// The container `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&SharedContainer> {
+        self.containers.get(name)
+    }
+
+    /// The container `name` for modification, if any. Use `Rc::make_mut` to modify shared
+    /// formats without affecting the other containers.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut SharedContainer> {
+        self.containers.get_mut(name)
+    }
+
+    /// The container `name` as a regular container format, if any.
+    pub fn to_container(&self, name: &str) -> Option<ContainerFormat> {
+        self.containers.get(name).map(SharedContainer::to_container)
+    }
+
+    /// Iterate over the containers, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SharedContainer)> {
+        self.containers
+            .iter()
+            .map(|(name, container)| (name.as_str(), container))
+    }
+
+    /// The number of containers.
+    pub fn len(&self) -> usize {
+        self.containers.len()
+    }
+
+    /// Whether the registry has no containers.
+    pub fn is_empty(&self) -> bool {
+        self.containers.is_empty()
+    }
+
+    /// The number of distinct formats interned for fields, tuple elements, and variants,
+    /// including those of removed containers. Formats copied by `Rc::make_mut` are not
+    /// counted.
+    pub fn distinct_formats(&self) -> usize {
+        self.formats.values().map(Vec::len).sum()
+    }
+
+    /// Convert into a regular registry.
+    pub fn to_registry(&self) -> Registry {
+        self.containers
+            .iter()
+            .map(|(name, container)| (name.clone(), container.to_container()))
+            .collect()
+    }
+
+    fn share(&mut self, format: Format) -> SharedFormat {
+        let mut hasher = StableHasher::new();
+        format.stable_hash_into(&mut hasher);
+        let candidates = self.formats.entry(hasher.finish()).or_default();
+        if let Some(shared) = candidates.iter().find(|shared| ***shared == format) {
+            return shared.clone();
+        }
+        let shared = Rc::new(format);
+        candidates.push(shared.clone());
+        shared
+    }
+
+    fn share_all(&mut self, formats: Vec<Format>) -> Vec<SharedFormat> {
+        formats
+            .into_iter()
+            .map(|format| self.share(format))
+            .collect()
+    }
+
+    fn share_fields(&mut self, fields: Vec<Named<Format>>) -> Vec<Named<SharedFormat>> {
+        fields
+            .into_iter()
+            .map(|Named { name, value }| Named {
+                name,
+                value: self.share(value),
+            })
+            .collect()
+    }
+
+    fn share_container(&mut self, format: ContainerFormat) -> SharedContainer {
+        match format {
+            ContainerFormat::UnitStruct => SharedContainer::UnitStruct,
+            ContainerFormat::NewTypeStruct(format) => {
+                SharedContainer::NewTypeStruct(self.share(*format))
+            }
+            ContainerFormat::TupleStruct(formats) => {
+                SharedContainer::TupleStruct(self.share_all(formats))
+            }
+            ContainerFormat::Struct(fields) => SharedContainer::Struct(self.share_fields(fields)),
+            ContainerFormat::Enum(variants) => SharedContainer::Enum(
+                variants
+                    .into_iter()
+                    .map(|(index, Named { name, value })| {
+                        let value = self.share_variant(value);
+                        (index, Named { name, value })
+                    })
+                    .collect(),
+            ),
+            ContainerFormat::Generic { params, body } => SharedContainer::Generic {
+                params,
+                body: Box::new(self.share_container(*body)),
+            },
+        }
+    }
+
+    fn share_variant(&mut self, format: VariantFormat) -> SharedVariant {
+        match format {
+            VariantFormat::Variable(variable) => match variable.borrow().clone() {
+                Some(format) => self.share_variant(format),
+                // Unknown variants do not occur in registries returned by the tracer.
+                None => SharedVariant::Unit,
+            },
+            VariantFormat::Unit => SharedVariant::Unit,
+            VariantFormat::NewType(format) => SharedVariant::NewType(self.share(*format)),
+            VariantFormat::Tuple(formats) => SharedVariant::Tuple(self.share_all(formats)),
+            VariantFormat::Struct(fields) => SharedVariant::Struct(self.share_fields(fields)),
+        }
+    }
+}
+
+impl SharedContainer {
+    /// Convert into a regular container format.
+    pub fn to_container(&self) -> ContainerFormat {
+        match self {
+            SharedContainer::UnitStruct => ContainerFormat::UnitStruct,
+            SharedContainer::NewTypeStruct(format) => {
+                ContainerFormat::NewTypeStruct(Box::new(format.as_ref().clone()))
+            }
+            SharedContainer::TupleStruct(formats) => ContainerFormat::TupleStruct(unshare(formats)),
+            SharedContainer::Struct(fields) => ContainerFormat::Struct(unshare_fields(fields)),
+            SharedContainer::Enum(variants) => ContainerFormat::Enum(
+                variants
+                    .iter()
+                    .map(|(index, variant)| {
+                        let value = variant.value.to_variant();
+                        let name = variant.name.clone();
+                        (*index, Named { name, value })
+                    })
+                    .collect(),
+            ),
+            SharedContainer::Generic { params, body } => ContainerFormat::Generic {
+                params: params.clone(),
+                body: Box::new(body.to_container()),
+            },
+        }
+    }
+}
+
+impl SharedVariant {
+    /// Convert into a regular variant format.
+    pub fn to_variant(&self) -> VariantFormat {
+        match self {
+            SharedVariant::Unit => VariantFormat::Unit,
+            SharedVariant::NewType(format) => {
+                VariantFormat::NewType(Box::new(format.as_ref().clone()))
+            }
+            SharedVariant::Tuple(formats) => VariantFormat::Tuple(unshare(formats)),
+            SharedVariant::Struct(fields) => VariantFormat::Struct(unshare_fields(fields)),
+        }
+    }
+}
+
+fn unshare(formats: &[SharedFormat]) -> Vec<Format> {
+    formats
+        .iter()
+        .map(|format| format.as_ref().clone())
+        .collect()
+}
+
+fn unshare_fields(fields: &[Named<SharedFormat>]) -> Vec<Named<Format>> {
+    fields
+        .iter()
+        .map(|field| Named {
+            name: field.name.clone(),
+            value: field.value.as_ref().clone(),
+        })
+        .collect()
+}
+
+impl From<Registry> for SharedRegistry {
+    fn from(registry: Registry) -> Self {
+        let mut shared = Self::new();
+        for (name, format) in registry {
+            shared.insert(name, format);
+        }
+        shared
+    }
+}
+
+impl PartialEq for SharedRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        self.containers == other.containers
+    }
+}
+
+impl Eq for SharedRegistry {}
+
+// Containers are converted one at a time, so that the serialized form is the one of
+// `Registry` without holding a full copy of the registry in memory.
+impl Serialize for SharedRegistry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.containers.len()))?;
+        for (name, container) in &self.containers {
+            map.serialize_entry(name, &container.to_container())?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for SharedRegistry {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct SharedRegistryVisitor;
+
+        impl<'de> de::Visitor<'de> for SharedRegistryVisitor {
+            type Value = SharedRegistry;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of container formats")
+            }
+
+            fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+            where
+                M: de::MapAccess<'de>,
+            {
+                let mut registry = SharedRegistry::new();
+                while let Some((name, format)) = access.next_entry::<String, ContainerFormat>()? {
+                    registry.insert(name, format);
+                }
+                Ok(registry)
+            }
+        }
+
+        deserializer.deserialize_map(SharedRegistryVisitor)
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Golden-file tests for registries.
+//!
+//! A snapshot test traces some types, then compares the resulting registry with a
+//! "golden" YAML file committed next to the tests. On mismatch, the test fails and
+//! prints the differences. Setting the environment variable `UPDATE_REGISTRY_SNAPSHOTS=1`
+//! rewrites the golden files instead.
+//!
+//! ```rust,no_run
+//! use serde::Deserialize;
+//! use serde_reflection::assert_registry_snapshot;
+//!
+//! #[derive(Deserialize)]
+//! struct Foo {
+//!     a: u32,
+//! }
+//!
+//! assert_registry_snapshot!(
+//!     |tracer| {
+//!         tracer.trace_simple_type::<Foo>()?;
+//!         Ok(())
+//!     },
+//!     "tests/schema.yaml"
+//! );
+//! ```
+
+use crate::{diff::RegistryDiff, Registry, Result, Tracer, TracerConfig};
+use std::{fmt::Write as _, path::Path};
+
+/// The environment variable used to rewrite golden files.
+pub const UPDATE_VARIABLE: &str = "UPDATE_REGISTRY_SNAPSHOTS";
+
+/// Compare `registry` with the golden file at `path`, or rewrite the golden file if the
+/// environment variable `UPDATE_REGISTRY_SNAPSHOTS` is set to a non-empty value other than `0`.
+///
+/// # Panics
+///
+/// On mismatch, or if the golden file cannot be read or written.
+pub fn assert_registry_snapshot(registry: &Registry, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let content = serde_yaml::to_string(registry).expect("registries can be serialized");
+    if update_requested() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap_or_else(|error| {
+                panic!("Failed to create directory {}: {}", parent.display(), error)
+            });
+        }
+        std::fs::write(path, content)
+            .unwrap_or_else(|error| panic!("Failed to write {}: {}", path.display(), error));
+        return;
+    }
+    let golden = std::fs::read_to_string(path).unwrap_or_else(|error| {
+        panic!(
+            "Failed to read golden registry {}: {}\nRun with {}=1 to create it.",
+            path.display(),
+            error,
+            UPDATE_VARIABLE
+        )
+    });
+    let golden: Registry = serde_yaml::from_str(&golden).unwrap_or_else(|error| {
+        panic!(
+            "Failed to parse golden registry {}: {}",
+            path.display(),
+            error
+        )
+    });
+    let diff = RegistryDiff::new(&golden, registry);
+    if !diff.is_empty() {
+        let mut message = format!(
+            "Registry does not match the golden file {}:\n",
+            path.display()
+        );
+        for change in &diff.changes {
+            writeln!(message, "  {change}").unwrap();
+        }
+        write!(
+            message,
+            "Run with {UPDATE_VARIABLE}=1 to update the golden file."
+        )
+        .unwrap();
+        panic!("{}", message);
+    }
+}
+
+/// Trace types with a fresh `Tracer` and return the resulting registry.
+///
+/// # Panics
+///
+/// If `setup` fails or if the registry is incomplete.
+pub fn trace_registry<F>(config: TracerConfig, setup: F) -> Registry
+where
+    F: FnOnce(&mut Tracer) -> Result<()>,
+{
+    let mut tracer = Tracer::new(config);
+    setup(&mut tracer).expect("tracing should succeed");
+    tracer.registry().expect("registry should be complete")
+}
+
+fn update_requested() -> bool {
+    match std::env::var_os(UPDATE_VARIABLE) {
+        Some(value) => !value.is_empty() && value != "0",
+        None => false,
+    }
+}
+
+/// Trace types with a fresh `Tracer` and compare the resulting registry with a golden
+/// file. See the module [`snapshot`](crate::snapshot).
+///
+/// * The first argument is a function `FnOnce(&mut Tracer) -> serde_reflection::Result<()>`.
+/// * The second argument is the path of the golden file relative to the manifest of
+///   the current crate.
+/// * An optional third argument provides the `TracerConfig` to use.
+#[macro_export]
+macro_rules! assert_registry_snapshot {
+    ($setup:expr, $path:expr $(,)?) => {
+        $crate::assert_registry_snapshot!($setup, $path, $crate::TracerConfig::default())
+    };
+    ($setup:expr, $path:expr, $config:expr $(,)?) => {{
+        let registry = $crate::snapshot::trace_registry($config, $setup);
+        $crate::snapshot::assert_registry_snapshot(
+            &registry,
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($path),
+        );
+    }};
+}
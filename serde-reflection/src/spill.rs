@@ -0,0 +1,205 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! File-backed storage of the containers spilled by the tracer when
+//! `TracerConfig::spill_registry` is set.
+
+use crate::{
+    format::{ContainerFormatEntry, FormatHolder},
+    ContainerFormat, Error, Registry, Result,
+};
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// A registry stored in a file, with the same read operations as [`Registry`]. Only the
+/// index of the containers is kept in memory; formats are read from the file on demand.
+///
+/// The file holds one JSON record `[name, format]` per line. A container may be recorded
+/// several times, e.g. when a spilled container is traced again, in which case its records
+/// are unified when it is read.
+#[derive(Debug)]
+pub struct SpilledRegistry {
+    path: PathBuf,
+    file: File,
+    /// The position and length of the records of each container.
+    index: BTreeMap<String, Vec<(u64, usize)>>,
+    /// The length of the file.
+    end: u64,
+}
+
+impl SpilledRegistry {
+    /// Create an empty registry in the file `path`, replacing the file if it exists.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .map_err(|error| spill_error(&path, error))?;
+        Ok(Self {
+            path,
+            file,
+            index: BTreeMap::new(),
+            end: 0,
+        })
+    }
+
+    /// Open a registry previously written in the file `path`. New containers are appended
+    /// to the file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|error| spill_error(&path, error))?;
+        let mut index: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        let mut end = 0;
+        let mut reader = BufReader::new(&file);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let len = reader
+                .read_until(b'\n', &mut line)
+                .map_err(|error| spill_error(&path, error))?;
+            if len == 0 {
+                break;
+            }
+            let (name, _) = parse_record(&line)?;
+            index.entry(name).or_default().push((end, len));
+            end += len as u64;
+        }
+        Ok(Self {
+            path,
+            file,
+            index,
+            end,
+        })
+    }
+
+    /// The file holding the registry.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of containers.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Whether the registry has no containers.
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Whether the registry has a container named `name`.
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    /// The names of the containers, in alphabetical order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.index.keys().map(String::as_str)
+    }
+
+    /// Read the format of the container `name`, if any.
+    pub fn get(&self, name: &str) -> Result<Option<ContainerFormat>> {
+        let Some(records) = self.index.get(name) else {
+            return Ok(None);
+        };
+        let mut result: Option<ContainerFormat> = None;
+        for &(position, len) in records {
+            let format = self.read_record(position, len)?;
+            match &mut result {
+                Some(result) => result.unify(format)?,
+                None => result = Some(format),
+            }
+        }
+        if let Some(format) = &mut result {
+            format.normalize()?;
+        }
+        Ok(result)
+    }
+
+    /// Read the containers in alphabetical order.
+    pub fn iter(&self) -> impl Iterator<Item = Result<(&str, ContainerFormat)>> {
+        self.keys().map(|name| {
+            let format = self.get(name)?.expect("names are indexed");
+            Ok((name, format))
+        })
+    }
+
+    /// Append a record of the container `name` to the file.
+    pub fn insert(&mut self, name: String, format: ContainerFormat) -> Result<()> {
+        let mut line = serde_json::to_vec(&(&name, &format))
+            .map_err(|error| Error::Custom(error.to_string()))?;
+        line.push(b'\n');
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(self.end))
+            .and_then(|_| file.write_all(&line))
+            .and_then(|()| file.flush())
+            .map_err(|error| spill_error(&self.path, error))?;
+        self.index
+            .entry(name)
+            .or_default()
+            .push((self.end, line.len()));
+        self.end += line.len() as u64;
+        Ok(())
+    }
+
+    /// The length of the file, to be given to `truncate`.
+    pub(crate) fn file_len(&self) -> u64 {
+        self.end
+    }
+
+    /// Drop the records appended after the file had the length `len`, e.g. by a root that
+    /// failed to trace.
+    pub(crate) fn truncate(&mut self, len: u64) -> Result<()> {
+        self.file
+            .set_len(len)
+            .map_err(|error| spill_error(&self.path, error))?;
+        for records in self.index.values_mut() {
+            records.retain(|(position, _)| *position < len);
+        }
+        self.index.retain(|_, records| !records.is_empty());
+        self.end = len;
+        Ok(())
+    }
+
+    /// Load the whole registry in memory.
+    pub fn to_registry(&self) -> Result<Registry> {
+        let mut registry = Registry::new();
+        for entry in self.iter() {
+            let (name, format) = entry?;
+            registry.entry(name.to_string()).unify(format)?;
+        }
+        Ok(registry)
+    }
+
+    fn read_record(&self, position: u64, len: usize) -> Result<ContainerFormat> {
+        let mut line = vec![0; len];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(position))
+            .and_then(|_| file.read_exact(&mut line))
+            .map_err(|error| spill_error(&self.path, error))?;
+        let (_, format) = parse_record(&line)?;
+        Ok(format)
+    }
+}
+
+fn parse_record(line: &[u8]) -> Result<(String, ContainerFormat)> {
+    serde_json::from_slice(line).map_err(|error| Error::InvalidRegistryFile(error.to_string()))
+}
+
+fn spill_error(path: &Path, error: std::io::Error) -> Error {
+    Error::Custom(format!(
+        "Failed to access spilled registry {}: {error}",
+        path.display()
+    ))
+}
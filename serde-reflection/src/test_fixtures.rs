@@ -0,0 +1,417 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Ready-made types exercising tricky Serde patterns, together with the registries that
+//! tracing them is expected to produce.
+//!
+//! Exporters and code generators maintained in other crates can check their behavior against
+//! this shared corpus instead of redefining such types in their own tests.
+//!
+//! The [`encoding_fixtures`] pair sample values with their golden encodings in compact binary
+//! formats (see [`CompactEncoding`]), to check that traced registries predict byte layouts.
+//!
+//! ```rust
+//! use serde_reflection::test_fixtures::fixtures;
+//!
+//! for fixture in fixtures() {
+//!     assert_eq!(fixture.registry().unwrap(), fixture.expected, "{}", fixture.name);
+//! }
+//! ```
+
+pub use crate::compact::CompactEncoding;
+use crate::{
+    ContainerFormat, Error, Format, Named, Registry, Result, Samples, Tracer, TracerConfig, Value,
+    VariantFormat,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Mutually recursive enums. The first variant of each enum is a base case.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Expr {
+    Literal(i64),
+    Block(Vec<Stmt>),
+}
+
+/// See `Expr`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stmt {
+    Eval(Expr),
+    Return(Option<Box<Expr>>),
+}
+
+/// An enum containing itself through a `Box`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum List {
+    Nil,
+    Cons(u32, Box<List>),
+}
+
+/// An untagged enum. Its format depends on the samples.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Scalar {
+    Number(u64),
+    Text(String),
+}
+
+/// A struct using the untagged enum `Scalar`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Labeled {
+    pub value: Scalar,
+    pub label: String,
+}
+
+/// A struct with flattened fields, which Serde serializes as a map.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotated {
+    pub id: u32,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// The fields flattened into `Annotated`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Extra {
+    pub note: String,
+}
+
+/// A container for `Annotated`, which is not a container itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub body: Annotated,
+}
+
+/// 128-bit integers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Wide {
+    pub signed: i128,
+    pub unsigned: u128,
+}
+
+/// The largest arrays supported by Serde, nested.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LargeArray {
+    pub bytes: [u8; 32],
+    pub matrix: [[u16; 32]; 32],
+}
+
+/// Values of every primitive format, to check the encodings of integers of each width.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Primitives {
+    pub flag: bool,
+    pub byte: u8,
+    pub small: u16,
+    pub large: u32,
+    pub negative: i64,
+    pub wide: u128,
+    pub ratio: f32,
+    pub letter: char,
+    pub name: String,
+    pub maybe: Option<u16>,
+}
+
+/// A map, a tuple, and an array. Only maps are prefixed by their lengths.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Collections {
+    pub pairs: BTreeMap<String, (u8, i16)>,
+    pub grid: [u32; 2],
+}
+
+/// A tricky type, how to trace it, and the registry that tracing should produce.
+pub struct Fixture {
+    /// The name of the fixture, e.g. `"mutually_recursive_enums"`.
+    pub name: &'static str,
+    /// The configuration of the tracer.
+    pub config: TracerConfig,
+    /// Trace the types of the fixture.
+    pub trace: fn(&mut Tracer, &mut Samples) -> Result<()>,
+    /// The registry expected after tracing.
+    pub expected: Registry,
+}
+
+impl Fixture {
+    /// Trace the types of the fixture with a new tracer and return the registry.
+    pub fn registry(&self) -> Result<Registry> {
+        let mut tracer = Tracer::new(self.config.clone());
+        let mut samples = Samples::new();
+        (self.trace)(&mut tracer, &mut samples)?;
+        tracer.registry()
+    }
+}
+
+/// All the fixtures, sorted by name.
+pub fn fixtures() -> Vec<Fixture> {
+    vec![
+        Fixture {
+            name: "boxed_self",
+            config: TracerConfig::default(),
+            trace: |tracer, _samples| {
+                tracer.trace_simple_type::<List>()?;
+                Ok(())
+            },
+            expected: registry([(
+                "List",
+                enum_format([
+                    ("Nil", VariantFormat::Unit),
+                    (
+                        "Cons",
+                        VariantFormat::Tuple(vec![Format::U32, type_name("List")]),
+                    ),
+                ]),
+            )]),
+        },
+        Fixture {
+            name: "flattened_struct",
+            config: TracerConfig::default().union_formats(true),
+            trace: |tracer, samples| {
+                let body = Annotated {
+                    id: 1,
+                    extra: Extra {
+                        note: "note".into(),
+                    },
+                };
+                tracer.trace_value(samples, &Envelope { body })?;
+                Ok(())
+            },
+            expected: registry([(
+                "Envelope",
+                ContainerFormat::Struct(vec![named(
+                    "body",
+                    Format::Map {
+                        key: Box::new(Format::Str),
+                        value: Box::new(Format::Union(vec![Format::U32, Format::Str])),
+                    },
+                )]),
+            )]),
+        },
+        Fixture {
+            name: "large_arrays",
+            config: TracerConfig::default(),
+            trace: |tracer, _samples| {
+                tracer.trace_simple_type::<LargeArray>()?;
+                Ok(())
+            },
+            expected: registry([(
+                "LargeArray",
+                ContainerFormat::Struct(vec![
+                    named("bytes", array(Format::U8, 32)),
+                    named("matrix", array(array(Format::U16, 32), 32)),
+                ]),
+            )]),
+        },
+        Fixture {
+            name: "mutually_recursive_enums",
+            config: TracerConfig::default(),
+            trace: |tracer, _samples| {
+                tracer.trace_simple_type::<Expr>()?;
+                tracer.trace_simple_type::<Stmt>()?;
+                Ok(())
+            },
+            expected: registry([
+                (
+                    "Expr",
+                    enum_format([
+                        ("Literal", VariantFormat::NewType(Box::new(Format::I64))),
+                        (
+                            "Block",
+                            VariantFormat::NewType(Box::new(Format::Seq(Box::new(type_name(
+                                "Stmt",
+                            ))))),
+                        ),
+                    ]),
+                ),
+                (
+                    "Stmt",
+                    enum_format([
+                        ("Eval", VariantFormat::NewType(Box::new(type_name("Expr")))),
+                        (
+                            "Return",
+                            VariantFormat::NewType(Box::new(Format::Option(Box::new(type_name(
+                                "Expr",
+                            ))))),
+                        ),
+                    ]),
+                ),
+            ]),
+        },
+        Fixture {
+            name: "untagged_enum",
+            config: TracerConfig::default()
+                .union_formats(true)
+                .self_describing(true),
+            trace: |tracer, samples| {
+                for value in [Scalar::Number(1), Scalar::Text("one".into())] {
+                    let label = "label".into();
+                    tracer.trace_value(samples, &Labeled { value, label })?;
+                }
+                tracer.trace_type::<Labeled>(samples)?;
+                Ok(())
+            },
+            expected: registry([(
+                "Labeled",
+                ContainerFormat::Struct(vec![
+                    named("value", Format::Union(vec![Format::U64, Format::Str])),
+                    named("label", Format::Str),
+                ]),
+            )]),
+        },
+        Fixture {
+            name: "wide_integers",
+            config: TracerConfig::default(),
+            trace: |tracer, _samples| {
+                tracer.trace_simple_type::<Wide>()?;
+                Ok(())
+            },
+            expected: registry([(
+                "Wide",
+                ContainerFormat::Struct(vec![
+                    named("signed", Format::I128),
+                    named("unsigned", Format::U128),
+                ]),
+            )]),
+        },
+    ]
+}
+
+/// A sample value together with its golden encodings in compact binary formats.
+pub struct EncodingFixture {
+    /// The name of the fixture, e.g. `"primitives"`.
+    pub name: &'static str,
+    /// Trace the types of the fixture and return the format and the value of the sample.
+    pub trace: fn(&mut Tracer, &mut Samples) -> Result<(Format, Value)>,
+    /// The encoding of the sample with `CompactEncoding::BincodeV2`.
+    pub bincode_v2: Vec<u8>,
+    /// The encoding of the sample with `CompactEncoding::Postcard`.
+    pub postcard: Vec<u8>,
+}
+
+impl EncodingFixture {
+    /// The golden encoding of the sample.
+    pub fn bytes(&self, encoding: CompactEncoding) -> &[u8] {
+        match encoding {
+            CompactEncoding::BincodeV2 => &self.bincode_v2,
+            CompactEncoding::Postcard => &self.postcard,
+        }
+    }
+
+    /// Check that the registry obtained by tracing the fixture with a new tracer predicts the
+    /// golden encodings: the sample must be encoded as the golden bytes, and the golden bytes
+    /// must be decoded as the sample.
+    pub fn check(&self) -> Result<()> {
+        let mut tracer = Tracer::new(TracerConfig::default());
+        let (format, value) = (self.trace)(&mut tracer, &mut Samples::new())?;
+        let registry = tracer.registry()?;
+        for encoding in [CompactEncoding::BincodeV2, CompactEncoding::Postcard] {
+            let mismatch = |msg: String| {
+                Error::RoundTripMismatch(format!("{} in {}: {msg}", self.name, encoding.name()))
+            };
+            let expected = self.bytes(encoding);
+            let bytes = encoding
+                .encode(&registry, &format, &value)
+                .map_err(mismatch)?;
+            if bytes != expected {
+                return Err(mismatch(format!(
+                    "{value:?} was encoded as {bytes:?} instead of {expected:?}"
+                )));
+            }
+            let decoded = encoding
+                .decode(&registry, &format, expected)
+                .map_err(mismatch)?;
+            if decoded != value {
+                return Err(mismatch(format!(
+                    "{expected:?} was decoded as {decoded:?} instead of {value:?}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// All the encoding fixtures, sorted by name.
+pub fn encoding_fixtures() -> Vec<EncodingFixture> {
+    vec![
+        EncodingFixture {
+            name: "boxed_self",
+            trace: |tracer, samples| {
+                let list = List::Cons(7, Box::new(List::Cons(300, Box::new(List::Nil))));
+                tracer.trace_value(samples, &list)
+            },
+            bincode_v2: vec![1, 7, 1, 251, 0x2C, 0x01, 0],
+            postcard: vec![1, 7, 1, 0xAC, 0x02, 0],
+        },
+        EncodingFixture {
+            name: "collections",
+            trace: |tracer, samples| {
+                let value = Collections {
+                    pairs: BTreeMap::from([("a".to_string(), (1, -300))]),
+                    grid: [1, 251],
+                };
+                tracer.trace_value(samples, &value)
+            },
+            // `-300` is zigzag-encoded as 599.
+            bincode_v2: vec![1, 1, b'a', 1, 251, 0x57, 0x02, 1, 251, 251, 0],
+            postcard: vec![1, 1, b'a', 1, 0xD7, 0x04, 1, 0xFB, 0x01],
+        },
+        EncodingFixture {
+            name: "primitives",
+            trace: |tracer, samples| {
+                let value = Primitives {
+                    flag: true,
+                    byte: 200,
+                    small: 300,
+                    large: 70000,
+                    negative: -2,
+                    wide: 1,
+                    ratio: 1.5,
+                    letter: 'é',
+                    name: "hi".into(),
+                    maybe: Some(5),
+                };
+                tracer.trace_value(samples, &value)
+            },
+            bincode_v2: vec![
+                1, 200, 251, 0x2C, 0x01, 252, 0x70, 0x11, 0x01, 0x00, 3, 1, 0x00, 0x00, 0xC0, 0x3F,
+                0xC3, 0xA9, 2, b'h', b'i', 1, 5,
+            ],
+            postcard: vec![
+                1, 200, 0xAC, 0x02, 0xF0, 0xA2, 0x04, 3, 1, 0x00, 0x00, 0xC0, 0x3F, 2, 0xC3, 0xA9,
+                2, b'h', b'i', 1, 5,
+            ],
+        },
+    ]
+}
+
+fn registry<const N: usize>(containers: [(&str, ContainerFormat); N]) -> Registry {
+    containers
+        .into_iter()
+        .map(|(name, format)| (name.to_string(), format))
+        .collect()
+}
+
+fn enum_format<const N: usize>(variants: [(&str, VariantFormat); N]) -> ContainerFormat {
+    let variants = variants
+        .into_iter()
+        .enumerate()
+        .map(|(index, (name, value))| (index as u32, named(name, value)))
+        .collect::<BTreeMap<_, _>>();
+    ContainerFormat::Enum(variants)
+}
+
+fn named<T>(name: &str, value: T) -> Named<T> {
+    Named {
+        name: name.to_string(),
+        value,
+    }
+}
+
+fn type_name(name: &str) -> Format {
+    Format::TypeName(name.to_string())
+}
+
+fn array(content: Format, size: usize) -> Format {
+    Format::TupleArray {
+        content: Box::new(content),
+        size,
+    }
+}
@@ -0,0 +1,444 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Language-neutral test vectors, for the conformance testing of decoders written in other
+//! languages.
+//!
+//! The function [`generate`] synthesizes reproducible values of a container according to a
+//! registry, and pairs each value in JSON with its encoding in one of the binary encodings
+//! described by [`EncodingProfile`]. The JSON representation is the one of the
+//! [`json_converter`](crate::json_converter) module: enums are objects with a single key,
+//! bytes are arrays of numbers, and 128-bit integers out of the range of 64-bit integers are
+//! strings.
+//!
+//! ```rust
+//! use serde::{Deserialize, Serialize};
+//! use serde_reflection::compat::EncodingProfile;
+//! use serde_reflection::testvectors::generate;
+//! use serde_reflection::{Tracer, TracerConfig};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! enum Message {
+//!     Ping(u64),
+//!     Data { id: u32, payload: Vec<u8> },
+//! }
+//!
+//! let mut tracer = Tracer::new(TracerConfig::default());
+//! tracer.trace_simple_type::<Message>().unwrap();
+//! let registry = tracer.registry().unwrap();
+//!
+//! let vectors = generate(&registry, "Message", EncodingProfile::Bcs, 42, 10).unwrap();
+//! for vector in &vectors {
+//!     // Our encoder agrees with the vector.
+//!     let message: Message = serde_json::from_value(vector.value.clone()).unwrap();
+//!     assert_eq!(vector.bytes, hex(&bcs::to_bytes(&message).unwrap()));
+//! }
+//! // The file shared with other languages.
+//! let json = serde_json::to_string_pretty(&vectors).unwrap();
+//! # fn hex(bytes: &[u8]) -> String {
+//! #     bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+//! # }
+//! ```
+
+use crate::{
+    analysis::{self, uninhabited_containers},
+    compat::{EncodingProfile, IntegerEncoding, NumericType, PrefixEncoding},
+    random::{SplitMix64, Synthesize},
+    ContainerFormat, Error, Format, Named, Registry, Result, VariantFormat,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeSet;
+
+/// A value and its binary encoding.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// A description of the vector, e.g. `Message #3 (BCS, seed 42)`.
+    pub description: String,
+    /// The value, in JSON.
+    pub value: Value,
+    /// The encoding of the value, in lower-case hexadecimal.
+    pub bytes: String,
+}
+
+/// The number of nested containers, options, sequences, and maps after which values are
+/// kept as small as possible, so that recursive containers terminate.
+const SHALLOW_DEPTH: usize = 8;
+
+/// The number of nested values after which recursive containers are rejected.
+const MAX_DEPTH: usize = 64;
+
+/// The maximal number of elements of synthesized sequences and maps.
+const MAX_LEN: u64 = 3;
+
+/// Generate `count` test vectors for the container `container` encoded with `profile`.
+/// * Vectors are reproducible: the same registry, container, and `seed` always produce the
+///   same vectors.
+/// * Maps must have string keys to be represented in JSON. Their entries are encoded in the
+///   order of the keys, as with a `BTreeMap`, or in the canonical order of BCS.
+/// * Uninhabited containers (see [`uninhabited_containers`]) are avoided: the variants using
+///   them are never chosen, and options, sequences, and maps of them are empty. Return
+///   `Error::Uninhabited` if `container` itself is uninhabited.
+/// * Return `Error::NotSupported` for the formats that cannot be encoded with `profile`
+///   (e.g. floats in BCS), unions, type parameters, and generic containers.
+pub fn generate(
+    registry: &Registry,
+    container: &str,
+    profile: EncodingProfile,
+    seed: u64,
+    count: usize,
+) -> Result<Vec<TestVector>> {
+    let uninhabited = uninhabited_containers(registry);
+    if uninhabited.contains(container) {
+        return Err(Error::Uninhabited(container.to_string()));
+    }
+    let mut generator = Generator {
+        registry,
+        inhabited: registry
+            .keys()
+            .filter(|name| !uninhabited.contains(*name))
+            .cloned()
+            .collect(),
+        profile,
+        rng: SplitMix64::new(seed),
+        depth: 0,
+    };
+    let format = Format::TypeName(container.to_string());
+    (0..count)
+        .map(|index| {
+            let mut bytes = Vec::new();
+            let value = generator.format(&format, &mut bytes)?;
+            Ok(TestVector {
+                description: format!("{container} #{index} ({profile}, seed {seed})"),
+                value,
+                bytes: to_hex(&bytes),
+            })
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Synthesize values and their encodings at once.
+struct Generator<'a> {
+    registry: &'a Registry,
+    inhabited: BTreeSet<String>,
+    profile: EncodingProfile,
+    rng: SplitMix64,
+    depth: usize,
+}
+
+impl Generator<'_> {
+    fn shallow(&self) -> bool {
+        self.depth >= SHALLOW_DEPTH
+    }
+
+    fn is_inhabited(&self, format: &Format) -> bool {
+        analysis::is_inhabited(self.registry, &self.inhabited, format)
+    }
+
+    fn len(&mut self, format: &Format) -> u64 {
+        if self.shallow() || !self.is_inhabited(format) {
+            0
+        } else {
+            self.rng.next_u64() % (MAX_LEN + 1)
+        }
+    }
+
+    fn nested<F>(&mut self, f: F) -> Result<Value>
+    where
+        F: FnOnce(&mut Self) -> Result<Value>,
+    {
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::NotSupported(
+                "test vectors of containers without finite values",
+            ));
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn prefix(&self, encoding: PrefixEncoding, value: u64, out: &mut Vec<u8>) {
+        match encoding {
+            PrefixEncoding::U32 => out.extend((value as u32).to_le_bytes()),
+            PrefixEncoding::U64 => out.extend(value.to_le_bytes()),
+            PrefixEncoding::Uleb128 => write_varint(value.into(), out),
+        }
+    }
+
+    fn length(&self, len: usize, out: &mut Vec<u8>) {
+        self.prefix(self.profile.length_encoding(), len as u64, out);
+    }
+
+    fn string(&mut self, out: &mut Vec<u8>) -> Value {
+        let value = String::synthesize(&mut self.rng);
+        self.length(value.len(), out);
+        out.extend(value.as_bytes());
+        Value::String(value)
+    }
+
+    fn bytes(&mut self, size: Option<usize>, out: &mut Vec<u8>) -> Value {
+        let value = match size {
+            Some(size) => (0..size).map(|_| u8::synthesize(&mut self.rng)).collect(),
+            None => Vec::<u8>::synthesize(&mut self.rng),
+        };
+        self.length(value.len(), out);
+        out.extend(&value);
+        Value::Array(value.into_iter().map(Value::from).collect())
+    }
+
+    fn integer(&mut self, value: NumericType, out: &mut Vec<u8>) -> Value {
+        use NumericType::*;
+        // Draw the value as a `u128`, then truncate it to the size of the type.
+        let bits = u128::synthesize(&mut self.rng) >> (128 - value.bits());
+        let signed = match value {
+            I8 => bits as u8 as i8 as i128,
+            I16 => bits as u16 as i16 as i128,
+            I32 => bits as u32 as i32 as i128,
+            I64 => bits as u64 as i64 as i128,
+            I128 => bits as i128,
+            _ => 0,
+        };
+        match self.profile.integer_encoding(value) {
+            IntegerEncoding::FixedLittleEndian => {
+                out.extend(&bits.to_le_bytes()[..value.bits() as usize / 8])
+            }
+            IntegerEncoding::Varint => write_varint(bits, out),
+            IntegerEncoding::ZigZagVarint => {
+                write_varint(((signed << 1) ^ (signed >> 127)) as u128, out)
+            }
+        }
+        if value.is_signed() {
+            i64::try_from(signed).map_or_else(|_| Value::String(signed.to_string()), Value::from)
+        } else {
+            u64::try_from(bits).map_or_else(|_| Value::String(bits.to_string()), Value::from)
+        }
+    }
+
+    fn format(&mut self, format: &Format, out: &mut Vec<u8>) -> Result<Value> {
+        let value = match format {
+            Format::Variable(variable) => match variable.borrow().as_ref() {
+                Some(format) => self.format(format, out)?,
+                None => return Err(Error::UnknownFormat),
+            },
+            Format::TypeName(name) => {
+                let container = self
+                    .registry
+                    .get(name)
+                    .ok_or_else(|| Error::MissingContainer(name.clone()))?;
+                self.nested(|generator| generator.container(container, out))?
+            }
+            Format::TypeParam(_) => return Err(Error::NotSupported("type parameters")),
+            Format::Apply { .. } => return Err(Error::NotSupported("generic containers")),
+            Format::Unit => Value::Null,
+            Format::Bool => {
+                let value = bool::synthesize(&mut self.rng);
+                out.push(value.into());
+                Value::Bool(value)
+            }
+            Format::F32 | Format::F64 | Format::Char if self.profile == EncodingProfile::Bcs => {
+                return Err(Error::NotSupported("floats and characters in BCS"))
+            }
+            Format::F32 => {
+                let value = f32::synthesize(&mut self.rng);
+                out.extend(value.to_le_bytes());
+                Number::from_f64(value.into()).map_or(Value::Null, Value::Number)
+            }
+            Format::F64 => {
+                let value = f64::synthesize(&mut self.rng);
+                out.extend(value.to_le_bytes());
+                Number::from_f64(value).map_or(Value::Null, Value::Number)
+            }
+            Format::Char => {
+                let value = char::synthesize(&mut self.rng);
+                if self.profile == EncodingProfile::Postcard {
+                    self.length(value.len_utf8(), out);
+                }
+                out.extend(value.to_string().as_bytes());
+                Value::String(value.to_string())
+            }
+            Format::Str => self.string(out),
+            Format::Bytes => self.bytes(None, out),
+            Format::FixedBytes(size) => self.bytes(Some(*size), out),
+            Format::Option(format) => {
+                let some =
+                    !self.shallow() && self.is_inhabited(format) && bool::synthesize(&mut self.rng);
+                if some {
+                    out.push(1);
+                    self.nested(|generator| generator.format(format, out))?
+                } else {
+                    out.push(0);
+                    Value::Null
+                }
+            }
+            Format::Seq(format) => {
+                let len = self.len(format);
+                self.length(len as usize, out);
+                let values = (0..len)
+                    .map(|_| self.nested(|generator| generator.format(format, out)))
+                    .collect::<Result<_>>()?;
+                Value::Array(values)
+            }
+            Format::Map { key, value } => self.map(key, value, out)?,
+            Format::Tuple(formats) => self.tuple(formats, out)?,
+            Format::TupleArray { content, size } => {
+                let values = (0..*size)
+                    .map(|_| self.format(content, out))
+                    .collect::<Result<_>>()?;
+                Value::Array(values)
+            }
+            Format::Union(_) => return Err(Error::NotSupported("unions in binary encodings")),
+            _ => {
+                let value = NumericType::from_format(format).expect("integer format");
+                self.integer(value, out)
+            }
+        };
+        Ok(value)
+    }
+
+    fn map(&mut self, key: &Format, value: &Format, out: &mut Vec<u8>) -> Result<Value> {
+        if !matches!(key, Format::Str) {
+            return Err(Error::NotSupported(
+                "test vectors of maps without string keys",
+            ));
+        }
+        let mut entries = Vec::new();
+        for _ in 0..self.len(value) {
+            let mut key_bytes = Vec::new();
+            let Value::String(key) = self.string(&mut key_bytes) else {
+                unreachable!("strings are synthesized as JSON strings");
+            };
+            let mut value_bytes = Vec::new();
+            let value = self.nested(|generator| generator.format(value, &mut value_bytes))?;
+            entries.push((key, key_bytes, value, value_bytes));
+        }
+        if self.profile == EncodingProfile::Bcs {
+            entries.sort_by(|a, b| a.1.cmp(&b.1));
+        } else {
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        entries.dedup_by(|a, b| a.0 == b.0);
+        self.length(entries.len(), out);
+        let mut object = Map::new();
+        for (key, key_bytes, value, value_bytes) in entries {
+            out.extend(key_bytes);
+            out.extend(value_bytes);
+            object.insert(key, value);
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn tuple(&mut self, formats: &[Format], out: &mut Vec<u8>) -> Result<Value> {
+        let values = formats
+            .iter()
+            .map(|format| self.format(format, out))
+            .collect::<Result<_>>()?;
+        Ok(Value::Array(values))
+    }
+
+    fn fields(&mut self, fields: &[Named<Format>], out: &mut Vec<u8>) -> Result<Value> {
+        let mut object = Map::new();
+        for field in fields {
+            let value = self.format(&field.value, out)?;
+            object.insert(field.name.clone(), value);
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn container(&mut self, container: &ContainerFormat, out: &mut Vec<u8>) -> Result<Value> {
+        match container {
+            ContainerFormat::UnitStruct => Ok(Value::Null),
+            ContainerFormat::NewTypeStruct(format) => self.format(format, out),
+            ContainerFormat::TupleStruct(formats) => self.tuple(formats, out),
+            ContainerFormat::Struct(fields) => self.fields(fields, out),
+            ContainerFormat::Enum(variants) => {
+                let variants: Vec<_> = variants
+                    .iter()
+                    .filter(|(_, variant)| {
+                        analysis::is_inhabited_variant(
+                            self.registry,
+                            &self.inhabited,
+                            &variant.value,
+                        )
+                    })
+                    .collect();
+                if variants.is_empty() {
+                    return Err(Error::NotSupported("test vectors of empty enums"));
+                }
+                // Close to the depth limit, prefer the variants that do not nest containers.
+                let candidates: Vec<_> = if self.shallow() {
+                    let leaves: Vec<_> = variants
+                        .iter()
+                        .copied()
+                        .filter(|(_, variant)| is_leaf_variant(&variant.value))
+                        .collect();
+                    if leaves.is_empty() {
+                        variants
+                    } else {
+                        leaves
+                    }
+                } else {
+                    variants
+                };
+                let choice = self.rng.next_u64() % candidates.len() as u64;
+                let (index, variant) = candidates[choice as usize];
+                self.prefix(self.profile.variant_tag_encoding(), (*index).into(), out);
+                let value = match &variant.value {
+                    VariantFormat::Variable(variable) => match variable.borrow().as_ref() {
+                        Some(value) => self.variant(value, out)?,
+                        None => return Err(Error::UnknownFormat),
+                    },
+                    value => self.variant(value, out)?,
+                };
+                let mut object = Map::new();
+                object.insert(variant.name.clone(), value);
+                Ok(Value::Object(object))
+            }
+            ContainerFormat::Generic { .. } => {
+                Err(Error::NotSupported("test vectors of generic containers"))
+            }
+        }
+    }
+
+    fn variant(&mut self, variant: &VariantFormat, out: &mut Vec<u8>) -> Result<Value> {
+        match variant {
+            VariantFormat::Variable(_) => Err(Error::UnknownFormat),
+            VariantFormat::Unit => Ok(Value::Null),
+            VariantFormat::NewType(format) => self.format(format, out),
+            VariantFormat::Tuple(formats) => self.tuple(formats, out),
+            VariantFormat::Struct(fields) => self.fields(fields, out),
+        }
+    }
+}
+
+fn write_varint(mut value: u128, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Whether the values of a variant never contain containers, except in options, sequences,
+/// and maps, which are kept empty close to the depth limit.
+fn is_leaf_variant(variant: &VariantFormat) -> bool {
+    fn is_leaf(format: &Format) -> bool {
+        match format {
+            Format::TypeName(_) | Format::Apply { .. } | Format::Variable(_) => false,
+            Format::Tuple(formats) => formats.iter().all(is_leaf),
+            Format::TupleArray { content, .. } => is_leaf(content),
+            _ => true,
+        }
+    }
+    match variant {
+        VariantFormat::Variable(_) => false,
+        VariantFormat::Unit => true,
+        VariantFormat::NewType(format) => is_leaf(format),
+        VariantFormat::Tuple(formats) => formats.iter().all(is_leaf),
+        VariantFormat::Struct(fields) => fields.iter().all(|field| is_leaf(&field.value)),
+    }
+}
@@ -1,21 +1,41 @@
 // Copyright (c) Facebook, Inc. and its affiliates
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+#[cfg(feature = "spill")]
+use crate::spill::SpilledRegistry;
 use crate::{
+    analysis,
+    attributes::{self, ContainerAttributes},
+    compact_value::{self, CompactReader},
+    constraint::{Constraint, Constraints, FloatPolicy},
     de::Deserializer,
     error::{Error, Result},
+    events::TraceEvent,
+    fallback::{FallbackContext, FallbackHandler, SharedFallbackHandler},
     format::*,
+    generic::{instantiate_generics, mentions_type_params, split_generic_containers},
+    metadata::RegistryMetadata,
+    profile::TraceProfile,
+    random::{SplitMix64, Synthesize},
+    registry::{self, MultiRegistry, Registry},
     ser::Serializer,
     value::Value,
 };
 use erased_discriminant::Discriminant;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use serde::{de::DeserializeSeed, Deserialize, Serialize};
 use std::any::TypeId;
-use std::collections::BTreeMap;
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet, HashMap};
+use std::marker::PhantomData;
+#[cfg(feature = "spill")]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-/// A map of container formats.
-pub type Registry = BTreeMap<String, ContainerFormat>;
+/// Maximum number of passes restarted by a call to `Tracer::trace_type` after running into
+/// an uninhabited type. Each of them normally avoids one more uninhabited variant or
+/// collection content.
+const MAX_UNINHABITED_RETRIES: usize = 256;
 
 /// Structure to drive the tracing of Serde serialization and deserialization.
 /// This typically aims at computing a `Registry`.
@@ -34,6 +54,134 @@ pub struct Tracer {
 
     /// Discriminant associated with each variant of each enum.
     pub(crate) discriminants: BTreeMap<(TypeId, VariantId<'static>), Discriminant>,
+
+    /// Variant names of each enum traced during deserialization, indexed by container name.
+    pub(crate) enum_variant_names: BTreeMap<&'static str, &'static [&'static str]>,
+
+    /// Type of each enum traced during deserialization, indexed by container name.
+    pub(crate) enum_type_ids: BTreeMap<&'static str, TypeId>,
+
+    /// Exploration progress of each enum, to avoid re-scanning known variants.
+    pub(crate) enum_progress: BTreeMap<TypeId, EnumProgress>,
+
+    /// Position of each variant in the declaration of each enum, indexed by container name
+    /// and variant name.
+    pub(crate) variant_positions: BTreeMap<&'static str, HashMap<&'static str, u32>>,
+
+    /// Names of the variants that produced each discriminant when visited by name.
+    pub(crate) discriminant_names: HashMap<(TypeId, Discriminant), Vec<&'static str>>,
+
+    /// Containers visited during the current top-level tracing call.
+    pub(crate) visited_containers: BTreeSet<&'static str>,
+
+    /// Root types whose tracing introduced each container (if enabled).
+    pub(crate) provenance: BTreeMap<String, BTreeSet<String>>,
+
+    /// Tracing statistics (if enabled).
+    pub(crate) profile: TraceProfile,
+
+    /// Low-level tracing decisions (if enabled).
+    pub(crate) events: Vec<TraceEvent>,
+
+    /// Enums whose analysis was interrupted because of `TracerConfig::max_passes_per_trace`.
+    pub(crate) interrupted_enums: BTreeMap<String, IncompleteEnumReason>,
+
+    /// Whether a top-level tracing call is running. If this is still set when the next
+    /// call starts, the previous call was interrupted by a panic.
+    pub(crate) pass_in_progress: bool,
+
+    /// Containers whose content is being traced, innermost last, together with the length
+    /// of `field_path` when they were entered.
+    pub(crate) containers_in_progress: Vec<(&'static str, usize)>,
+
+    /// Names of the variants, fields, and tuple indices leading to the value being traced.
+    pub(crate) field_path: Vec<String>,
+
+    /// Containers that contain values deserialized with `deserialize_any`.
+    pub(crate) self_describing_containers: BTreeSet<String>,
+
+    /// Variant used by each enum to deserialize unknown variants, e.g. with `#[serde(other)]`.
+    pub(crate) fallback_variants: BTreeMap<String, String>,
+
+    /// Enum to be deserialized with an unknown variant index during the current pass.
+    pub(crate) fallback_probe: Option<String>,
+
+    /// Whether the enum probed during the current pass rejected the unknown variant index.
+    pub(crate) fallback_probe_rejected: bool,
+
+    /// Whether each probed struct rejects unknown fields, if this could be determined.
+    pub(crate) unknown_fields: BTreeMap<String, Option<bool>>,
+
+    /// Whether the current pass should probe the first struct missing from `unknown_fields`.
+    pub(crate) unknown_fields_probe: bool,
+
+    /// Enums restored from a checkpoint with all their variants. Their exploration is not
+    /// repeated.
+    pub(crate) restored_enums: BTreeSet<String>,
+
+    /// Variants whose content turned out to be uninhabited, indexed by enum name and variant
+    /// name. Like skipped variants, they are never visited again.
+    pub(crate) uninhabited_variants: BTreeSet<(String, String)>,
+
+    /// Positions of the variants that are never visited in each enum, computed on the first
+    /// visit of the enum rather than on every pass.
+    pub(crate) unvisited_positions: BTreeMap<&'static str, BTreeSet<u32>>,
+
+    /// Whether the current pass was aborted by an uninhabited type after recording enough
+    /// information to avoid it during the next pass.
+    pub(crate) uninhabited_retry: bool,
+
+    /// The formats of the variants, indexed by enum name and variant index, whose visit by
+    /// index was aborted for another pass by an uninhabited type. The next visit starts
+    /// from them, so that it avoids the uninhabited type.
+    retried_variants: BTreeMap<(String, u32), VariantFormat>,
+
+    /// The root being traced by `trace_dyn`, if any.
+    pub(crate) dyn_root: Option<String>,
+
+    /// The containers recorded while tracing each root type that mentions type parameters,
+    /// indexed by the Rust name of the root type. They are kept apart from `registry`, so
+    /// that generic containers do not conflict with their instances. See `TypeParam`.
+    generic_registries: BTreeMap<String, Registry>,
+
+    /// The root type with type parameters being traced, if any. Its containers are recorded
+    /// in `registry` in the meantime.
+    generic_root: Option<String>,
+
+    /// Number of containers given to `TracerConfig::ignore_container` whose content is
+    /// being serialized. Nothing is recorded inside them.
+    pub(crate) ignored_depth: usize,
+
+    /// The generator of primitive values, if `TracerConfig::random_seed` is set.
+    rng: Option<SplitMix64>,
+
+    /// Number of formats widened by the tracing deserializer because of
+    /// `TracerConfig::widen_formats`.
+    pub(crate) widened_formats: usize,
+
+    /// The tracer of the other encoding, if `TracerConfig::both_encodings` is set.
+    pub(crate) other_encoding: Option<Box<Tracer>>,
+
+    /// The containers moved out of `registry` because of `TracerConfig::spill_registry`.
+    #[cfg(feature = "spill")]
+    spilled: Option<SpilledRegistry>,
+
+    /// The spans of `containers_in_progress`, entered while their content is traced.
+    #[cfg(feature = "tracing")]
+    container_spans: Vec<tracing::Span>,
+}
+
+/// Enter `span` without a guard, since a container is entered and exited by different
+/// calls of the tracing deserializer.
+#[cfg(feature = "tracing")]
+fn enter_span(span: &tracing::Span) {
+    span.with_subscriber(|(id, dispatch)| dispatch.enter(id));
+}
+
+/// Exit a span entered by `enter_span`.
+#[cfg(feature = "tracing")]
+fn exit_span(span: &tracing::Span) {
+    span.with_subscriber(|(id, dispatch)| dispatch.exit(id));
 }
 
 /// Type of untraced enum variants
@@ -45,7 +193,29 @@ pub enum IncompleteEnumReason {
     IndexedVariantsRemaining,
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Debug)]
+/// The order in which the tracing deserializer explores the variants of an enum. See
+/// `TracerConfig::variant_order`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum VariantOrder {
+    /// Explore every variant, in the order of declaration, i.e. the order of the variant
+    /// names given by the `Deserialize` implementation.
+    #[default]
+    Declaration,
+    /// Skip the variants already recorded with their index and a complete format, e.g. by
+    /// `Tracer::trace_value`, then explore the others in the order of declaration. This
+    /// spares deserializing such variants from default values, which may fail.
+    SampleFirst,
+}
+
+/// Position of the first variant of an enum that may not have been visited yet,
+/// by name and by index.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct EnumProgress {
+    pub(crate) next_name: usize,
+    pub(crate) next_index: u32,
+}
+
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub(crate) enum VariantId<'a> {
     Index(u32),
     Name(&'a str),
@@ -53,30 +223,587 @@ pub(crate) enum VariantId<'a> {
 
 /// User inputs, aka "samples", recorded during serialization.
 /// This will help passing user-defined checks during deserialization.
-#[derive(Debug, Default)]
+///
+/// Samples are indexed by container name. They can be persisted, e.g. to avoid
+/// re-constructing expensive values in every run of a build script.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Samples {
-    pub(crate) values: BTreeMap<&'static str, Value>,
+    pub(crate) values: BTreeMap<String, Value>,
+    #[cfg(feature = "async")]
+    #[serde(skip)]
+    pub(crate) providers: crate::asynchronous::AsyncProviders,
+    #[serde(skip)]
+    pub(crate) lazy: BTreeMap<String, Arc<LazySample>>,
+    /// The samples recorded for the other encoding, see `TracerConfig::both_encodings`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) other_encoding: Option<Box<Samples>>,
+}
+
+/// Serializes the value of a lazy sample.
+type LazyProvider = Box<dyn FnOnce(&mut Tracer, &mut Samples) -> Result<(Format, Value)> + Send>;
+
+/// A sample registered with `Samples::register_lazy`, evaluated at most once.
+#[derive(Default)]
+pub(crate) struct LazySample {
+    provider: Mutex<Option<LazyProvider>>,
+    value: OnceCell<Value>,
+}
+
+impl std::fmt::Debug for LazySample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value.get() {
+            Some(value) => value.fmt(f),
+            None => write!(f, "<lazy>"),
+        }
+    }
+}
+
+impl PartialEq for LazySample {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// On-disk representation of `Samples`.
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct SamplesFile<S> {
+    version: u32,
+    samples: S,
+}
+
+/// Samples read by `Samples::load`, once the version of the file has been checked.
+#[cfg(feature = "json")]
+struct VersionedSamples(Samples);
+
+/// Read the version of the file before the samples, so that files written with another
+/// version are rejected before their samples are parsed. Samples are deserialized directly
+/// from the file, since an intermediate JSON value would turn large integers into floats.
+#[cfg(feature = "json")]
+impl<'de> Deserialize<'de> for SamplesFile<VersionedSamples> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct FileVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for FileVisitor {
+            type Value = SamplesFile<VersionedSamples>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a samples file")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                use serde::de::Error as _;
+
+                let mut version = None;
+                let mut samples = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "version" => {
+                            let value: u32 = map.next_value()?;
+                            if value != Samples::FILE_VERSION {
+                                return Err(A::Error::custom(format!(
+                                    "unsupported version {value} (expected {})",
+                                    Samples::FILE_VERSION
+                                )));
+                            }
+                            version = Some(value);
+                        }
+                        "samples" if version.is_none() => {
+                            return Err(A::Error::custom("the version must precede the samples"));
+                        }
+                        "samples" => samples = Some(VersionedSamples(map.next_value()?)),
+                        _ => {
+                            return Err(A::Error::unknown_field(&key, &["version", "samples"]));
+                        }
+                    }
+                }
+                Ok(SamplesFile {
+                    version: version.ok_or_else(|| A::Error::missing_field("version"))?,
+                    samples: samples.ok_or_else(|| A::Error::missing_field("samples"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct("SamplesFile", &["version", "samples"], FileVisitor)
+    }
 }
 
 impl Samples {
+    /// The version of the file format used by `save` and `load`.
+    pub const FILE_VERSION: u32 = 1;
+
     /// Create a new structure to hold value samples.
     pub fn new() -> Self {
         Self::default()
     }
 
     /// Obtain a (serialized) sample.
-    pub fn value(&self, name: &'static str) -> Option<&Value> {
-        self.values.get(name)
+    /// * Samples registered with `register_lazy` are only returned once evaluated.
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        self.values
+            .get(name)
+            .or_else(|| self.lazy.get(name).and_then(|sample| sample.value.get()))
+    }
+
+    /// Register a function producing a sample value for the container `name`. Typically,
+    /// `name` is the container name of `T`.
+    /// * The function is only called if a trace needs a sample for `name` during
+    ///   deserialization, which avoids constructing expensive values that are never used.
+    ///   The value is then serialized by the tracer, like with `Tracer::trace_value`, but
+    ///   the values of the containers nested in it are not recorded as samples.
+    /// * A value recorded for `name` with `trace_value` takes precedence.
+    /// * Clones of `Samples` share the lazy samples, which are still evaluated at most once.
+    ///   Lazy samples are not persisted by `save`.
+    pub fn register_lazy<T, F>(&mut self, name: &str, provider: F)
+    where
+        T: Serialize,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let provider: LazyProvider =
+            Box::new(move |tracer, samples| provider().serialize(Serializer::new(tracer, samples)));
+        let sample = LazySample {
+            provider: Mutex::new(Some(provider)),
+            value: OnceCell::new(),
+        };
+        self.lazy.insert(name.to_string(), Arc::new(sample));
+    }
+
+    /// Iterate over the names of the containers that have a sample.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    /// The name of the first sample containing a NaN or infinite float, if any.
+    #[cfg(feature = "json")]
+    fn first_non_finite_sample(&self) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(_, value)| value.has_non_finite_float())
+            .map(|(name, _)| name.as_str())
+            .or_else(|| self.other_encoding.as_ref()?.first_non_finite_sample())
+    }
+
+    /// Write the samples in JSON, together with the version of the file format.
+    /// Returns an error if a sample contains a NaN or infinite float, since JSON cannot
+    /// represent it. Such samples can be persisted with `to_compact_bytes` instead.
+    #[cfg(feature = "json")]
+    pub fn save<W: std::io::Write>(&self, writer: W) -> Result<()> {
+        if let Some(name) = self.first_non_finite_sample() {
+            return Err(Error::InvalidSamples(format!(
+                "the sample of {name} contains a non-finite float"
+            )));
+        }
+        let file = SamplesFile {
+            version: Self::FILE_VERSION,
+            samples: self,
+        };
+        serde_json::to_writer(writer, &file).map_err(|e| Error::InvalidSamples(e.to_string()))
+    }
+
+    /// Read samples previously written by `save`.
+    /// Returns an error if the file was written with an incompatible version of the file format.
+    /// * When a tracer uses the sample of a newtype struct that it has not recorded itself,
+    ///   the newtype structs nested in the sample are recorded too, level by level. Enums and
+    ///   other structs met in the sample are only referred to by name.
+    #[cfg(feature = "json")]
+    pub fn load<R: std::io::Read>(reader: R) -> Result<Self> {
+        let file: SamplesFile<VersionedSamples> =
+            serde_json::from_reader(reader).map_err(|e| Error::InvalidSamples(e.to_string()))?;
+        Ok(file.samples.0)
+    }
+
+    /// Encode the samples in the compact binary form of `Value::to_compact_bytes`, e.g. to
+    /// embed a corpus of samples in a binary. Like with `save`, lazy samples are left out.
+    ///
+    /// The encoding starts with the header `SRS` followed by the version byte
+    /// `Value::COMPACT_VERSION`. Then come the varint count of the samples, and the name (a
+    /// varint length and UTF-8 bytes) and the value of each sample, in the order of names.
+    /// Finally, a byte `1` is followed by the samples of the other encoding (see
+    /// `TracerConfig::both_encodings`) without header, or a byte `0` ends the encoding.
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let mut out = compact_value::header(SAMPLES_MAGIC);
+        self.write_compact(&mut out);
+        out
+    }
+
+    /// Decode samples encoded by `to_compact_bytes`. Return `Error::InvalidSamples` if the
+    /// input is not a valid encoding of the version `Value::COMPACT_VERSION`.
+    pub fn from_compact_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = CompactReader::new(bytes, SAMPLES_MAGIC)?;
+        let samples = Self::read_compact(&mut reader, true)?;
+        reader.finish()?;
+        Ok(samples)
+    }
+
+    fn write_compact(&self, out: &mut Vec<u8>) {
+        compact_value::write_varint(self.values.len() as u128, out);
+        for (name, value) in &self.values {
+            compact_value::write_str(name, out);
+            compact_value::write_value(value, out);
+        }
+        match &self.other_encoding {
+            None => out.push(0),
+            Some(other) => {
+                out.push(1);
+                other.write_compact(out);
+            }
+        }
+    }
+
+    fn read_compact(reader: &mut CompactReader<'_>, allow_other_encoding: bool) -> Result<Self> {
+        let mut samples = Self::new();
+        for _ in 0..reader.len()? {
+            let name = reader.str()?;
+            let value = reader.value()?;
+            if samples.values.insert(name, value).is_some() {
+                return Err(reader.error("duplicate sample"));
+            }
+        }
+        match reader.byte()? {
+            0 => (),
+            1 if allow_other_encoding => {
+                samples.other_encoding = Some(Box::new(Self::read_compact(reader, false)?));
+            }
+            _ => return Err(reader.error("invalid samples of the other encoding")),
+        }
+        Ok(samples)
     }
 }
 
-/// Configuration object to create a tracer.
+/// The first bytes of samples encoded by `Samples::to_compact_bytes`.
+const SAMPLES_MAGIC: &[u8; 3] = b"SRS";
+
+/// The progress of a tracer, saved by `Tracer::checkpoint` to be restored by
+/// `Tracer::restore`, e.g. in a later run of a build script after an interrupted trace.
+///
+/// A checkpoint can be (de)serialized with Serde. It holds the complete containers, the
+/// enums whose variants were all explored during deserialization, and the facts probed
+/// about them (fallback variants, unknown fields, self-describing containers). Enums still
+/// being explored are left out and explored again from scratch after a restore, since
+/// telling their variants apart relies on Rust values that only live in memory.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TraceCheckpoint {
+    version: u32,
+    registry: Registry,
+    complete_enums: BTreeSet<String>,
+    fallback_variants: BTreeMap<String, String>,
+    self_describing_containers: BTreeSet<String>,
+    unknown_fields: BTreeMap<String, Option<bool>>,
+}
+
+impl TraceCheckpoint {
+    /// The version of the format of checkpoints.
+    pub const VERSION: u32 = 1;
+
+    /// The names of the containers saved in the checkpoint.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.registry.keys().map(String::as_str)
+    }
+
+    /// The names of the enums whose exploration will not be repeated after a restore.
+    pub fn complete_enums(&self) -> impl Iterator<Item = &str> {
+        self.complete_enums.iter().map(String::as_str)
+    }
+}
+
+/// Representative values of a type that implements Serialize but not Deserialize, e.g. a
+/// type from another crate. The values are traced with `Tracer::trace_serialize_only`.
+///
+/// Since the trait is meant to be implemented by a local type, the values of foreign types
+/// can be provided despite the orphan rules. Closures returning a vector of values are
+/// providers too.
+///
+/// ```rust
+/// use serde::Serialize;
+/// use serde_reflection::{SerializeSampleProvider, Samples, Tracer, TracerConfig};
+///
+/// // Pretend that this type is defined in another crate.
+/// #[derive(Serialize)]
+/// enum Color {
+///     Rgb(u8, u8, u8),
+///     Named(String),
+/// }
+///
+/// struct Colors;
+///
+/// impl SerializeSampleProvider for Colors {
+///     type Value = Color;
+///
+///     fn samples(&self) -> Vec<Color> {
+///         vec![Color::Rgb(0, 0, 0), Color::Named("red".into())]
+///     }
+/// }
+///
+/// # fn main() -> serde_reflection::Result<()> {
+/// let mut tracer = Tracer::new(TracerConfig::default());
+/// let mut samples = Samples::new();
+/// tracer.trace_serialize_only(&mut samples, &Colors)?;
+/// let registry = tracer.registry()?;
+/// assert_eq!(registry["Color"].variants().count(), 2);
+/// # Ok(())
+/// # }
+/// ```
+pub trait SerializeSampleProvider {
+    /// The traced type.
+    type Value: Serialize;
+
+    /// Values covering the shapes of the type, e.g. one value per enum variant.
+    fn samples(&self) -> Vec<Self::Value>;
+}
+
+impl<F, T> SerializeSampleProvider for F
+where
+    F: Fn() -> Vec<T>,
+    T: Serialize,
+{
+    type Value = T;
+
+    fn samples(&self) -> Vec<T> {
+        self()
+    }
+}
+
+/// A type described by a framework that wraps Serde (e.g. the models of an ORM) rather than
+/// by Serde traits. The description is recorded with `Tracer::trace_dyn`.
+///
+/// Implementations record the containers of the type with `Tracer::record_dyn_container`.
+/// Nested types that implement Serde traits can be traced as usual, e.g. with
+/// `Tracer::trace_simple_type`, and referred to by name.
+///
+/// ```rust
+/// use serde_reflection::{
+///     ContainerFormat, Format, Named, Result, Samples, TraceableDyn, Tracer, TracerConfig,
+/// };
+///
+/// // A model whose columns are only known at runtime.
+/// struct Table {
+///     name: &'static str,
+///     columns: Vec<(&'static str, Format)>,
+/// }
+///
+/// impl TraceableDyn for Table {
+///     fn type_name(&self) -> String {
+///         self.name.to_string()
+///     }
+///
+///     fn trace(&self, tracer: &mut Tracer, _samples: &mut Samples) -> Result<Format> {
+///         let fields = self
+///             .columns
+///             .iter()
+///             .map(|(name, format)| Named { name: name.to_string(), value: format.clone() })
+///             .collect();
+///         tracer.record_dyn_container(self.name, ContainerFormat::Struct(fields))
+///     }
+/// }
+///
+/// # fn main() -> Result<()> {
+/// let table = Table { name: "User", columns: vec![("id", Format::U64), ("email", Format::Str)] };
+/// let mut tracer = Tracer::new(TracerConfig::default());
+/// let format = tracer.trace_dyn(&mut Samples::new(), &table)?;
+/// assert_eq!(format, Format::TypeName("User".into()));
+/// let registry = tracer.registry()?;
+/// assert!(matches!(&registry["User"], ContainerFormat::Struct(fields) if fields.len() == 2));
+/// # Ok(())
+/// # }
+/// ```
+pub trait TraceableDyn {
+    /// The name of the type, used as the root of provenance and to report errors.
+    fn type_name(&self) -> String;
+
+    /// Record the containers used by the type and return its format.
+    fn trace(&self, tracer: &mut Tracer, samples: &mut Samples) -> Result<Format>;
+}
+
+/// A root type to be traced by `Tracer::trace_all`.
+pub struct TraceRoot {
+    name: String,
+    #[allow(clippy::type_complexity)]
+    trace: Box<dyn Fn(&mut Tracer, &Samples) -> Result<()>>,
+}
+
+impl TraceRoot {
+    /// Trace the type `T` with `Tracer::trace_type`.
+    pub fn of<T>() -> Self
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        Self::new(std::any::type_name::<T>(), |tracer, samples| {
+            tracer.trace_type::<T>(samples)?;
+            Ok(())
+        })
+    }
+
+    /// Trace the values of a provider with `Tracer::trace_serialize_only`.
+    pub fn serialize_only<P>(provider: P) -> Self
+    where
+        P: SerializeSampleProvider + 'static,
+    {
+        Self::new(
+            std::any::type_name::<P::Value>(),
+            move |tracer, _samples| {
+                tracer.trace_serialize_only(&mut Samples::new(), &provider)?;
+                Ok(())
+            },
+        )
+    }
+
+    /// Trace a type described by another framework with `Tracer::trace_dyn`.
+    pub fn of_dyn<D>(value: D) -> Self
+    where
+        D: TraceableDyn + 'static,
+    {
+        Self::new(value.type_name(), move |tracer, _samples| {
+            tracer.trace_dyn(&mut Samples::new(), &value)?;
+            Ok(())
+        })
+    }
+
+    /// Trace a root with a custom function, e.g. to use `Tracer::trace_type_with_seed`.
+    pub fn new<F>(name: impl Into<String>, trace: F) -> Self
+    where
+        F: Fn(&mut Tracer, &Samples) -> Result<()> + 'static,
+    {
+        Self {
+            name: name.into(),
+            trace: Box::new(trace),
+        }
+    }
+
+    /// The name of the root, used to report errors.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// An order in which to trace roots, computed by `Tracer::plan` and followed by
+/// `Tracer::execute`.
+#[derive(Debug)]
+pub struct TracePlan {
+    steps: Vec<PlannedRoot>,
+}
+
 #[derive(Debug)]
+struct PlannedRoot {
+    root: TraceRoot,
+    /// The containers reached by the root while planning.
+    containers: BTreeSet<String>,
+    /// Whether the root failed while planning.
+    failed: bool,
+}
+
+impl TracePlan {
+    /// The names of the roots, in the order in which they will be traced.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.steps.iter().map(|step| step.root.name())
+    }
+
+    /// The containers found to be reachable from the root `name` while planning. Only the
+    /// first variant of each enum is explored at this stage.
+    pub fn containers(&self, name: &str) -> Option<impl Iterator<Item = &str>> {
+        let step = self.steps.iter().find(|step| step.root.name() == name)?;
+        Some(step.containers.iter().map(String::as_str))
+    }
+}
+
+/// The outcome of tracing a root with `Tracer::execute`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootStatus {
+    /// The name of the root.
+    pub name: String,
+    /// The containers recorded for the first time while tracing the root.
+    pub new_containers: Vec<String>,
+    /// The error that made the root fail, if any. The tracing state was then rolled back, as
+    /// in `Tracer::trace_all`.
+    pub error: Option<Error>,
+}
+
+/// The state of a tracer saved before tracing a root in `Tracer::trace_all` and
+/// `Tracer::execute`, to be restored if the root fails.
+struct TracerSnapshot {
+    registry: Registry,
+    incomplete_enums: BTreeMap<String, IncompleteEnumReason>,
+    discriminants: BTreeMap<(TypeId, VariantId<'static>), Discriminant>,
+    enum_variant_names: BTreeMap<&'static str, &'static [&'static str]>,
+    enum_type_ids: BTreeMap<&'static str, TypeId>,
+    enum_progress: BTreeMap<TypeId, EnumProgress>,
+    variant_positions: BTreeMap<&'static str, HashMap<&'static str, u32>>,
+    discriminant_names: HashMap<(TypeId, Discriminant), Vec<&'static str>>,
+    provenance: BTreeMap<String, BTreeSet<String>>,
+    interrupted_enums: BTreeMap<String, IncompleteEnumReason>,
+    self_describing_containers: BTreeSet<String>,
+    fallback_variants: BTreeMap<String, String>,
+    unknown_fields: BTreeMap<String, Option<bool>>,
+    restored_enums: BTreeSet<String>,
+    uninhabited_variants: BTreeSet<(String, String)>,
+    unvisited_positions: BTreeMap<&'static str, BTreeSet<u32>>,
+    retried_variants: BTreeMap<(String, u32), VariantFormat>,
+    generic_registries: BTreeMap<String, Registry>,
+    rng: Option<SplitMix64>,
+    widened_formats: usize,
+    other_encoding: Option<Box<TracerSnapshot>>,
+    /// The length of the file of the spilled registry, if any.
+    #[cfg(feature = "spill")]
+    spilled_len: Option<u64>,
+}
+
+impl std::fmt::Debug for TraceRoot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceRoot")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Configuration object to create a tracer.
+///
+/// The configuration can be (de)serialized, e.g. to be shared by several build scripts.
+/// Missing options take their default values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct TracerConfig {
     pub(crate) is_human_readable: bool,
+    pub(crate) both_encodings: bool,
     pub(crate) record_samples_for_newtype_structs: bool,
     pub(crate) record_samples_for_tuple_structs: bool,
     pub(crate) record_samples_for_structs: bool,
+    pub(crate) record_map_entries: bool,
+    pub(crate) record_provenance: bool,
+    pub(crate) record_profile: bool,
+    pub(crate) record_events: bool,
+    pub(crate) max_passes_per_trace: Option<usize>,
+    pub(crate) seq_sample_len: usize,
+    pub(crate) member_seq_sample_lens: Vec<(Vec<String>, usize)>,
+    pub(crate) skipped_variants: BTreeSet<(String, String)>,
+    pub(crate) declared_variants: BTreeMap<String, BTreeMap<u32, Named<VariantFormat>>>,
+    pub(crate) declared_fixed_bytes: BTreeMap<String, BTreeMap<String, usize>>,
+    pub(crate) u8_seqs_as_bytes: bool,
+    pub(crate) constraints: Constraints,
+    pub(crate) self_describing: bool,
+    pub(crate) union_formats: bool,
+    pub(crate) widen_formats: bool,
+    pub(crate) probe_unknown_fields: bool,
+    pub(crate) probe_fallback_variants: bool,
+    pub(crate) denied_unknown_fields: BTreeSet<String>,
+    pub(crate) transparent_containers: BTreeSet<String>,
+    pub(crate) ignored_containers: BTreeSet<String>,
+    pub(crate) remote_names: BTreeMap<String, String>,
+    pub(crate) remote_suffix: Option<String>,
+    pub(crate) forbidden_types: BTreeSet<String>,
+    pub(crate) forbidden_formats: Vec<Format>,
+    pub(crate) float_policy: FloatPolicy,
+    pub(crate) variant_order: VariantOrder,
+    pub(crate) random_seed: Option<u64>,
+    #[cfg(feature = "spill")]
+    pub(crate) spill: Option<(PathBuf, usize)>,
+    #[serde(skip)]
+    pub(crate) fallback_handler: Option<SharedFallbackHandler>,
     pub(crate) default_bool_value: bool,
     pub(crate) default_u8_value: u8,
     pub(crate) default_u16_value: u16,
@@ -91,9 +818,11 @@ pub struct TracerConfig {
     pub(crate) default_f32_value: f32,
     pub(crate) default_f64_value: f64,
     pub(crate) default_char_value: char,
-    pub(crate) default_borrowed_str_value: &'static str,
+    #[serde(deserialize_with = "deserialize_static_str")]
+    pub(crate) default_borrowed_str_value: StaticStr,
     pub(crate) default_string_value: String,
-    pub(crate) default_borrowed_bytes_value: &'static [u8],
+    #[serde(deserialize_with = "deserialize_static_bytes")]
+    pub(crate) default_borrowed_bytes_value: StaticBytes,
     pub(crate) default_byte_buf_value: Vec<u8>,
 }
 
@@ -102,9 +831,40 @@ impl Default for TracerConfig {
     fn default() -> Self {
         Self {
             is_human_readable: false,
+            both_encodings: false,
             record_samples_for_newtype_structs: true,
             record_samples_for_tuple_structs: false,
             record_samples_for_structs: false,
+            record_map_entries: false,
+            record_provenance: false,
+            record_profile: false,
+            record_events: false,
+            max_passes_per_trace: None,
+            seq_sample_len: 1,
+            member_seq_sample_lens: Vec::new(),
+            skipped_variants: BTreeSet::new(),
+            declared_variants: BTreeMap::new(),
+            declared_fixed_bytes: BTreeMap::new(),
+            u8_seqs_as_bytes: false,
+            constraints: Constraints::new(),
+            self_describing: false,
+            union_formats: false,
+            widen_formats: false,
+            probe_unknown_fields: false,
+            probe_fallback_variants: false,
+            denied_unknown_fields: BTreeSet::new(),
+            transparent_containers: BTreeSet::new(),
+            ignored_containers: BTreeSet::new(),
+            remote_names: BTreeMap::new(),
+            remote_suffix: None,
+            forbidden_types: BTreeSet::new(),
+            forbidden_formats: Vec::new(),
+            float_policy: FloatPolicy::Allow,
+            variant_order: VariantOrder::Declaration,
+            random_seed: None,
+            #[cfg(feature = "spill")]
+            spill: None,
+            fallback_handler: None,
             default_bool_value: false,
             default_u8_value: 0,
             default_u16_value: 0,
@@ -127,6 +887,258 @@ impl Default for TracerConfig {
     }
 }
 
+/// Apply the declarations of the configuration to a normalized registry. `variant_names`
+/// gives the declared variants of the enums traced during deserialization.
+fn apply_declarations(
+    config: &TracerConfig,
+    variant_names: &BTreeMap<&'static str, &'static [&'static str]>,
+    registry: &mut Registry,
+) -> Vec<Error> {
+    let mut errors = add_declared_variants(config, registry);
+    add_skipped_variant_placeholders(config, variant_names, registry);
+    if config.u8_seqs_as_bytes {
+        coerce_u8_seqs(registry);
+    }
+    errors.extend(add_declared_fixed_bytes(config, registry));
+    errors.extend(rename_remote_definitions(config, registry));
+    errors
+}
+
+/// Rename the proxies of remote definitions (see `TracerConfig::remote_name`) and merge
+/// their formats into the canonical containers.
+fn rename_remote_definitions(config: &TracerConfig, registry: &mut Registry) -> Vec<Error> {
+    let renames: BTreeMap<_, _> = registry
+        .keys()
+        .filter_map(|name| Some((name.clone(), config.canonical_name(name)?)))
+        .collect();
+    if renames.is_empty() {
+        return Vec::new();
+    }
+    for format in registry.values_mut() {
+        format
+            .visit_mut(&mut |format| {
+                if let Format::TypeName(name) = format {
+                    if let Some(canonical) = renames.get(name) {
+                        *name = canonical.clone();
+                    }
+                }
+                Ok(())
+            })
+            .unwrap_or(());
+    }
+    let mut errors = Vec::new();
+    for (proxy, canonical) in renames {
+        let format = registry
+            .remove(&proxy)
+            .expect("proxies are in the registry");
+        match registry.get_mut(&canonical) {
+            None => {
+                registry.insert(canonical, format);
+            }
+            Some(existing) => {
+                let mut merged = existing.clone();
+                match merged.unify(format) {
+                    Ok(()) => *existing = merged,
+                    Err(error) => errors.push(error),
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Replace the sequences of `u8` with `Format::Bytes`.
+fn coerce_u8_seqs(registry: &mut Registry) {
+    for format in registry.values_mut() {
+        format
+            .visit_mut(&mut |format| {
+                if matches!(format, Format::Seq(content) if **content == Format::U8) {
+                    *format = Format::Bytes;
+                }
+                Ok(())
+            })
+            .unwrap_or(());
+    }
+}
+
+fn add_declared_fixed_bytes(config: &TracerConfig, registry: &mut Registry) -> Vec<Error> {
+    let mut errors = Vec::new();
+    for (name, fields) in &config.declared_fixed_bytes {
+        let Some(format) = registry.get_mut(name) else {
+            continue;
+        };
+        for (field, size) in fields {
+            let mut found = false;
+            if let Some(field_format) = field_format_mut(format, field) {
+                field_format
+                    .visit_mut(&mut |format| {
+                        if *format == Format::Bytes {
+                            *format = Format::FixedBytes(*size);
+                            found = true;
+                        }
+                        Ok(())
+                    })
+                    .unwrap_or(());
+            }
+            if !found {
+                errors.push(Error::InvalidDeclaration(format!(
+                    "{name}::{field} is not a field containing bytes"
+                )));
+            }
+        }
+    }
+    errors
+}
+
+/// Find a use of a format forbidden by `TracerConfig::forbid_format` in a registry.
+fn find_forbidden_format(config: &TracerConfig, registry: &Registry) -> Option<Error> {
+    if config.forbidden_formats.is_empty() {
+        return None;
+    }
+    for (name, format) in registry {
+        if config.is_ignored_container(name) {
+            continue;
+        }
+        for (field_path, member) in registry::members(format) {
+            let mut found = None;
+            member
+                .visit(&mut |format| {
+                    if found.is_none() && config.forbidden_formats.contains(format) {
+                        found = Some(format.clone());
+                    }
+                    Ok(())
+                })
+                .unwrap_or(());
+            if let Some(format) = found {
+                return Some(Error::ForbiddenFormat {
+                    format,
+                    container: name.clone(),
+                    field_path,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Find a floating-point member without a `finite` constraint when the float policy rejects
+/// them.
+fn find_unspecified_float(config: &TracerConfig, registry: &Registry) -> Option<Error> {
+    if config.float_policy != FloatPolicy::Reject {
+        return None;
+    }
+    let mut path = config
+        .constraints
+        .unspecified_floats(registry)
+        .into_iter()
+        .next()?;
+    let container = path.remove(0);
+    Some(Error::UnspecifiedFloat {
+        container,
+        field_path: path,
+    })
+}
+
+/// The format of a field of a struct, given by name, or of a newtype or tuple struct, given
+/// by position.
+fn field_format_mut<'a>(format: &'a mut ContainerFormat, field: &str) -> Option<&'a mut Format> {
+    match format {
+        ContainerFormat::Struct(fields) => fields
+            .iter_mut()
+            .find(|named| named.name == field)
+            .map(|named| &mut named.value),
+        ContainerFormat::NewTypeStruct(format) if field == "0" => Some(format),
+        ContainerFormat::TupleStruct(formats) => formats.get_mut(field.parse::<usize>().ok()?),
+        ContainerFormat::Generic { body, .. } => field_format_mut(body, field),
+        _ => None,
+    }
+}
+
+/// Replace the value of a known `format` by `value`, through its variables, so that the
+/// formats sharing these variables see the new value.
+fn overwrite(format: &mut Format, value: Format) {
+    match format {
+        Format::Variable(variable) => {
+            let mut slot = variable.borrow_mut();
+            match slot.as_mut() {
+                Some(inner) => overwrite(inner, value),
+                None => *slot = Some(value),
+            }
+        }
+        _ => *format = value,
+    }
+}
+
+/// Record the variants excluded with `TracerConfig::skip_variant`, unless their formats were
+/// declared, as unit variants at their positions in the declarations of their enums, so that
+/// the indices of the variants of each enum remain contiguous.
+fn add_skipped_variant_placeholders(
+    config: &TracerConfig,
+    variant_names: &BTreeMap<&'static str, &'static [&'static str]>,
+    registry: &mut Registry,
+) {
+    for (enum_name, variant_name) in &config.skipped_variants {
+        let Some(position) = variant_names
+            .get(enum_name.as_str())
+            .and_then(|names| names.iter().position(|name| name == variant_name))
+        else {
+            continue;
+        };
+        if let Some(ContainerFormat::Enum(variants)) = registry.get_mut(enum_name) {
+            variants.entry(position as u32).or_insert_with(|| Named {
+                name: variant_name.clone(),
+                value: VariantFormat::Unit,
+            });
+        }
+    }
+}
+
+/// Add the variants declared with `TracerConfig::declare_variant` to the enums of the
+/// registry. Return the errors caused by conflicting declarations.
+fn add_declared_variants(config: &TracerConfig, registry: &mut Registry) -> Vec<Error> {
+    let mut errors = Vec::new();
+    for (name, variants) in &config.declared_variants {
+        if let Some(format) = registry.get_mut(name) {
+            let mut extended = format.clone();
+            match extended.unify(ContainerFormat::Enum(variants.clone())) {
+                Ok(()) => *format = extended,
+                Err(error) => errors.push(error),
+            }
+        }
+    }
+    errors
+}
+
+// Borrowed default values must live as long as the program. Deserializing a non-empty
+// value leaks it, which is acceptable for a configuration loaded once. The aliases prevent
+// Serde from borrowing `&str` and `&[u8]` fields from the input.
+type StaticStr = &'static str;
+type StaticBytes = &'static [u8];
+
+fn deserialize_static_str<'de, D>(deserializer: D) -> std::result::Result<StaticStr, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    if value.is_empty() {
+        Ok("")
+    } else {
+        Ok(Box::leak(value.into_boxed_str()))
+    }
+}
+
+fn deserialize_static_bytes<'de, D>(deserializer: D) -> std::result::Result<StaticBytes, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Vec::<u8>::deserialize(deserializer)?;
+    if value.is_empty() {
+        Ok(b"")
+    } else {
+        Ok(Box::leak(value.into_boxed_slice()))
+    }
+}
+
 macro_rules! define_default_value_setter {
     ($method:ident, $ty:ty) => {
         /// The default serialized value for this primitive type.
@@ -145,6 +1157,17 @@ impl TracerConfig {
         self
     }
 
+    /// Also trace the encoding not selected by `is_human_readable`, during the same tracing
+    /// calls. This is meant for types whose implementations of `Serialize` and `Deserialize`
+    /// depend on `is_human_readable()`, e.g. addresses written as strings in JSON and as
+    /// numbers in Bincode. The formats of both encodings are returned by
+    /// `Tracer::multi_registry`. Only `trace_value` and `trace_type` (including
+    /// `trace_type_once` and `trace_simple_type`) record the other encoding. (Default: false)
+    pub fn both_encodings(mut self, value: bool) -> Self {
+        self.both_encodings = value;
+        self
+    }
+
     /// Record samples of newtype structs during serialization and inject them during deserialization.
     pub fn record_samples_for_newtype_structs(mut self, value: bool) -> Self {
         self.record_samples_for_newtype_structs = value;
@@ -163,52 +1186,542 @@ impl TracerConfig {
         self
     }
 
-    define_default_value_setter!(default_bool_value, bool);
-    define_default_value_setter!(default_u8_value, u8);
-    define_default_value_setter!(default_u16_value, u16);
-    define_default_value_setter!(default_u32_value, u32);
-    define_default_value_setter!(default_u64_value, u64);
-    define_default_value_setter!(default_u128_value, u128);
-    define_default_value_setter!(default_i8_value, i8);
-    define_default_value_setter!(default_i16_value, i16);
-    define_default_value_setter!(default_i32_value, i32);
-    define_default_value_setter!(default_i64_value, i64);
-    define_default_value_setter!(default_i128_value, i128);
-    define_default_value_setter!(default_f32_value, f32);
-    define_default_value_setter!(default_f64_value, f64);
-    define_default_value_setter!(default_char_value, char);
-    define_default_value_setter!(default_borrowed_str_value, &'static str);
-    define_default_value_setter!(default_string_value, String);
-    define_default_value_setter!(default_borrowed_bytes_value, &'static [u8]);
-    define_default_value_setter!(default_byte_buf_value, Vec<u8>);
-}
+    /// Record the values of maps as `Value::Map`, i.e. as key-value pairs in serialization
+    /// order, rather than as a `Value::Seq` alternating keys and values. This keeps the
+    /// entries of samples whose order is significant (e.g. a `serde_json::Map` with the
+    /// feature `preserve_order`) recognizable as such when values are saved or compared.
+    pub fn record_map_entries(mut self, value: bool) -> Self {
+        self.record_map_entries = value;
+        self
+    }
 
-impl Tracer {
-    /// Start tracing deserialization.
-    pub fn new(config: TracerConfig) -> Self {
-        Self {
-            config,
-            registry: BTreeMap::new(),
-            incomplete_enums: BTreeMap::new(),
-            discriminants: BTreeMap::new(),
-        }
+    /// Record which root types introduced each container. See `Tracer::provenance`.
+    pub fn record_provenance(mut self, value: bool) -> Self {
+        self.record_provenance = value;
+        self
     }
 
-    /// Trace the serialization of a particular value.
-    /// * Nested containers will be added to the tracing registry, indexed by
-    ///   their (non-qualified) name.
+    /// Record timings and pass counts. See `Tracer::profile`.
+    /// Timings rely on `std::time::Instant`, which is not available on `wasm32-unknown-unknown`.
+    pub fn record_profile(mut self, value: bool) -> Self {
+        self.record_profile = value;
+        self
+    }
+
+    /// Record the low-level decisions of the tracer. See `Tracer::events`.
+    pub fn record_events(mut self, value: bool) -> Self {
+        self.record_events = value;
+        self
+    }
+
+    /// Limit the number of passes performed by a single call to `Tracer::trace_type` (or
+    /// `trace_type_with_seed`). Since each pass explores one new variant of the traced enum,
+    /// this allows exploring very large enums in chunks, interleaved with other work.
+    pub fn max_passes_per_trace(mut self, value: usize) -> Self {
+        self.max_passes_per_trace = Some(value);
+        self
+    }
+
+    /// The number of elements of the sequences (and entries of the maps) constructed during
+    /// deserialization tracing while the format of their elements is unknown. The default
+    /// is 1. Once the format is known, collections are constructed empty to cut the
+    /// exploration, unless `seq_sample_len_at` says otherwise.
+    pub fn seq_sample_len(mut self, value: usize) -> Self {
+        self.seq_sample_len = value;
+        self
+    }
+
+    /// Construct the sequences and maps found at the member with the given qualified name
+    /// (see [`Constraints`]) with `len` elements during deserialization tracing, including
+    /// once the format of their elements is known, e.g. for a field whose implementation of
+    /// `Deserialize` rejects empty collections. To guarantee termination, the collections of
+    /// a container nested within itself are still constructed empty once their format is
+    /// known.
+    pub fn seq_sample_len_at<I, S>(mut self, path: I, len: usize) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let path: Vec<String> = path.into_iter().map(Into::into).collect();
+        self.member_seq_sample_lens
+            .retain(|(existing, _)| *existing != path);
+        self.member_seq_sample_lens.push((path, len));
+        self
+    }
+
+    /// Never construct the variant `variant_name` of the enum `enum_name` during
+    /// deserialization tracing, e.g. because it requires values that cannot be sampled.
+    /// * The variant is considered explored, so it does not prevent the enum from being
+    ///   complete. Since its format is unknown, the registry records a unit variant with the
+    ///   same name as a placeholder, so that the indices of the variants remain contiguous
+    ///   as expected by code generators. Values of the variant are therefore not described
+    ///   faithfully, unless its actual format is given with `declare_variant`.
+    /// * The index of the variant is assumed to be its position in the declaration of the
+    ///   enum, as with derived implementations of `Deserialize`.
+    pub fn skip_variant(mut self, enum_name: &str, variant_name: &str) -> Self {
+        self.skipped_variants
+            .insert((enum_name.to_string(), variant_name.to_string()));
+        self
+    }
+
+    /// Declare the variant `variant_name` of the enum `enum_name` at the given index, e.g.
+    /// because it is only compiled on other platforms or with other features. Declared
+    /// variants are added to the enum when the registry is returned, provided that the enum
+    /// was traced. Containers used by the declared format must be traced separately.
+    /// This may also provide the formats of variants excluded with `skip_variant`.
+    pub fn declare_variant(
+        mut self,
+        enum_name: &str,
+        index: u32,
+        variant_name: &str,
+        format: VariantFormat,
+    ) -> Self {
+        self.declared_variants
+            .entry(enum_name.to_string())
+            .or_default()
+            .insert(
+                index,
+                Named {
+                    name: variant_name.to_string(),
+                    value: format,
+                },
+            );
+        self
+    }
+
+    /// Record values whose formats cannot be unified as a `Format::Union` of the distinct
+    /// formats, rather than failing. This applies to the values of a map (e.g. from a
+    /// dynamically typed value) and to the fields of a container traced with several
+    /// samples (e.g. a field holding an untagged enum). Integer and floating-point formats
+    /// are first widened into a common format (e.g. `u8` and `i16` into `i16`), so unions
+    /// only gather formats that have nothing in common. Without this option, maps fail with
+    /// `Error::IncompatibleMapValues` and containers with `Error::ConflictingFormats`.
+    pub fn union_formats(mut self, value: bool) -> Self {
+        self.union_formats = value;
+        self
+    }
+
+    /// Widen conflicting integer and floating-point formats traced for the same value into a
+    /// common format (e.g. `U32` and `U64` into `U64`), rather than failing with
+    /// `Error::ConflictingFormats`. Such conflicts usually mean that the implementations of
+    /// `Serialize` and `Deserialize` of a type disagree, e.g. one writes a `u32` and the
+    /// other reads a `u64`, so this lenient mode should only be used for types known to
+    /// accept both. (Default: false)
+    pub fn widen_formats(mut self, value: bool) -> Self {
+        self.widen_formats = value;
+        self
+    }
+
+    /// Record sequences of `u8`, e.g. `Vec<u8>` without `#[serde(with = "serde_bytes")]`, as
+    /// `Format::Bytes` when the registry is returned, so that other languages use their native
+    /// byte buffers. The values traced by `Tracer::trace_value` are recorded as `Value::Bytes`
+    /// accordingly. Binary encodings such as Bincode and BCS encode both alike, but
+    /// self-describing ones such as JSON do not. (Default: false)
+    pub fn u8_seqs_as_bytes(mut self, value: bool) -> Self {
+        self.u8_seqs_as_bytes = value;
+        self
+    }
+
+    /// Declare that the bytes of the field `field` of the container `container_name` always
+    /// have the given size, e.g. because they hold a hash or a key. The field is given by
+    /// name for structs and by position (`"0"`, `"1"`, ...) for newtype and tuple structs.
+    /// When the registry is returned, every `Bytes` format within the field (e.g. in
+    /// `Option<Bytes>`) becomes `Format::FixedBytes(size)`, so that exporters targeting
+    /// fixed-layout formats can use it. The encoding of the field is unchanged.
+    pub fn declare_fixed_bytes(mut self, container_name: &str, field: &str, size: usize) -> Self {
+        self.declared_fixed_bytes
+            .entry(container_name.to_string())
+            .or_default()
+            .insert(field.to_string(), size);
+        self
+    }
+
+    /// Attach a constraint to the member with the given qualified name (see [`Constraints`]),
+    /// e.g. `["Foo", "a"]` for the field `a` of the struct `Foo`. Constraints are returned by
+    /// `Tracer::constraints` for the consumers of the registry. During deserialization
+    /// tracing, the default integer values used at the location of the member (including
+    /// within options and sequences) are clamped into the bounds `min` and `max`.
+    pub fn constrain<I, S>(mut self, path: I, constraint: Constraint) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.constraints.constrain(path, constraint);
+        self
+    }
+
+    /// Support types whose implementation of `Deserialize` calls `deserialize_any`, such
+    /// as untagged enums. During deserialization tracing, such calls are answered according
+    /// to the format recorded for the same location by a previous call to `trace_value`,
+    /// using the sampled value of the container when the format is a container name.
+    /// The enclosing containers are marked as requiring a self-describing encoding. See
+    /// `Tracer::requires_self_describing`.
+    pub fn self_describing(mut self, value: bool) -> Self {
+        self.self_describing = value;
+        self
+    }
+
+    /// Detect whether structs reject unknown fields, e.g. with `#[serde(deny_unknown_fields)]`.
+    /// This costs one extra pass per struct at the end of `Tracer::trace_type` (and
+    /// `trace_type_with_seed`). See `Tracer::denies_unknown_fields`.
+    pub fn probe_unknown_fields(mut self, value: bool) -> Self {
+        self.probe_unknown_fields = value;
+        self
+    }
+
+    /// Detect whether enums fall back to one of their variants on unknown variants, e.g. with
+    /// `#[serde(other)]`. This costs one extra pass per enum at the end of
+    /// `Tracer::trace_type` (and `trace_type_with_seed`). See `Tracer::fallback_variant`.
+    pub fn probe_fallback_variants(mut self, value: bool) -> Self {
+        self.probe_fallback_variants = value;
+        self
+    }
+
+    /// Declare that the struct `name` rejects unknown fields, e.g. when this cannot be
+    /// detected automatically.
+    pub fn deny_unknown_fields(mut self, name: &str) -> Self {
+        self.denied_unknown_fields.insert(name.to_string());
+        self
+    }
+
+    /// Trace the newtype struct `name` as its content, like a smart pointer, e.g. for a
+    /// wrapper `struct Shared<T>(Arc<T>)` that is not `#[serde(transparent)]`. The container
+    /// is not recorded and its uses have the format of the content. This also allows
+    /// generic wrappers to be used with different type arguments. Binary encodings such as
+    /// Bincode and BCS encode newtype structs as their content, so they are preserved.
+    pub fn transparent_container(mut self, name: &str) -> Self {
+        self.transparent_containers.insert(name.to_string());
+        self
+    }
+
+    /// Record the container `name` as an opaque placeholder, a newtype struct around
+    /// `Format::Bytes`, without tracing its content, e.g. for internal types that are
+    /// irrelevant to other languages. Containers are matched by their Serde name: a path such
+    /// as `"telemetry::DebugBlob"` is accepted for readability but only its last segment is
+    /// compared, since Serde names do not include the module.
+    ///
+    /// Nothing is recorded inside the container and its members are not checked by
+    /// `forbid_type` and `forbid_format`. During serialization, the value is always recorded
+    /// as a sample. During deserialization, the sample is used if any; otherwise, the content
+    /// is traced once by a separate tracer, only to obtain a value.
+    pub fn ignore_container(mut self, name: &str) -> Self {
+        self.ignored_containers.insert(name.to_string());
+        self
+    }
+
+    /// Whether the container `name` was given to `ignore_container`.
+    pub(crate) fn is_ignored_container(&self, name: &str) -> bool {
+        self.ignored_containers
+            .iter()
+            .any(|entry| entry.rsplit("::").next() == Some(name))
+    }
+
+    /// Remove the entries of `ignore_container` matching the container `name`.
+    pub(crate) fn unignore_container(&mut self, name: &str) {
+        self.ignored_containers
+            .retain(|entry| entry.rsplit("::").next() != Some(name));
+    }
+
+    /// Record the container that Serde names `proxy` under the name `canonical`, e.g. for a
+    /// definition `#[serde(remote = "Duration")] struct DurationDef`, which Serde names
+    /// `DurationDef`. References to the proxy are renamed in the registry, and the formats
+    /// traced through the proxy and under the canonical name are merged.
+    ///
+    /// Renaming happens when the registry is obtained: the other options of the configuration
+    /// and the methods of the tracer refer to containers by their Serde names.
+    pub fn remote_name(mut self, proxy: &str, canonical: &str) -> Self {
+        self.remote_names
+            .insert(proxy.to_string(), canonical.to_string());
+        self
+    }
+
+    /// Detect the proxies of remote definitions by the suffix of their names, e.g. `"Def"`
+    /// as in the Serde documentation, and record them without the suffix, as with
+    /// `remote_name`. Explicit calls to `remote_name` take precedence. (Default: None)
+    pub fn remote_suffix(mut self, suffix: &str) -> Self {
+        self.remote_suffix = Some(suffix.to_string());
+        self
+    }
+
+    /// The name under which the container `name` is recorded, if it is the proxy of a remote
+    /// definition.
+    pub(crate) fn canonical_name(&self, name: &str) -> Option<String> {
+        if let Some(canonical) = self.remote_names.get(name) {
+            return Some(canonical.clone());
+        }
+        let suffix = self.remote_suffix.as_deref()?;
+        name.strip_suffix(suffix)
+            .filter(|canonical| !canonical.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Fail tracing with `Error::ForbiddenType` when a value of the Rust type `type_name` is
+    /// traced, e.g. `"std::path::PathBuf"`. Types are matched against the names given by
+    /// `std::any::type_name` for the roots and for the fields, elements, and contents that
+    /// Serde (de)serializes separately, including their type arguments (e.g. in
+    /// `Option<PathBuf>`). A name without a path, such as `"PathBuf"`, matches any path.
+    /// Values serialized by hand-written implementations without a nested call to
+    /// `Serialize` or `Deserialize`, such as the inner value of a `Box`, are only seen through
+    /// their enclosing type.
+    pub fn forbid_type(mut self, type_name: &str) -> Self {
+        self.forbidden_types.insert(type_name.to_string());
+        self
+    }
+
+    /// Make `Tracer::registry` (and `trace_all`) fail with `Error::ForbiddenFormat` when a
+    /// container uses the given format anywhere, e.g. `Format::F64` to rule out floats in
+    /// consensus-critical messages, or `Format::TypeName(name)` to rule out a container.
+    pub fn forbid_format(mut self, format: Format) -> Self {
+        if !self.forbidden_formats.contains(&format) {
+            self.forbidden_formats.push(format);
+        }
+        self
+    }
+
+    /// Choose how to treat the floating-point members of the traced containers (fields,
+    /// elements, or variants holding `f32` or `f64`, possibly within options, sequences,
+    /// etc.) that were not declared finite or non-finite with `Constraint::finite`. See
+    /// [`FloatPolicy`]. Declarations use the qualified names of `TracerConfig::constrain`.
+    pub fn float_policy(mut self, value: FloatPolicy) -> Self {
+        self.float_policy = value;
+        self
+    }
+
+    /// Choose the order in which the tracing deserializer explores the variants of enums.
+    /// By default, every variant is explored in the order of declaration. See
+    /// [`VariantOrder`].
+    pub fn variant_order(mut self, value: VariantOrder) -> Self {
+        self.variant_order = value;
+        self
+    }
+
+    /// Synthesize the primitive values of traced types from a random generator seeded with
+    /// `seed`, instead of the default values of the configuration, e.g. so that property
+    /// tests control the values. The values only depend on `seed` and on the sequence of
+    /// tracing calls, so traces are reproducible across runs and platforms.
+    /// * Integers are then clamped by constraints, floats are in `[0, 1)`, and characters,
+    ///   strings, and byte buffers are made of ASCII letters and digits, or random bytes.
+    /// * Borrowed strings and bytes keep their default values.
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// Bound the number of containers kept in memory by the tracer. Whenever a call to
+    /// `trace_value`, `trace_type`, or `trace_type_with_seed` leaves more than
+    /// `max_containers` containers in memory, the completely traced ones are appended to
+    /// the file `path` (replaced if it exists) and dropped from memory. (Default: disabled)
+    /// * Incomplete containers, such as enums whose variants are still being explored, are
+    ///   kept in memory, so the bound may be exceeded.
+    /// * `Tracer::registry` reads the spilled containers back. Use
+    ///   `Tracer::spilled_registry` to obtain the registry without loading it in memory.
+    /// * Checkpoints, plans, and the rollback of failing roots by `Tracer::trace_all` only
+    ///   consider the containers kept in memory.
+    #[cfg(feature = "spill")]
+    pub fn spill_registry(mut self, path: impl Into<PathBuf>, max_containers: usize) -> Self {
+        self.spill = Some((path.into(), max_containers));
+        self
+    }
+
+    /// Consult `handler` when the tracing deserializer is asked something that it cannot
+    /// model, e.g. a call to `deserialize_any` from a hand-written implementation of
+    /// `Deserialize`, rather than failing. See [`FallbackHandler`]. The handler is not
+    /// (de)serialized with the configuration.
+    pub fn fallback_handler<H>(mut self, handler: H) -> Self
+    where
+        H: FallbackHandler + 'static,
+    {
+        self.fallback_handler = Some(SharedFallbackHandler(Arc::new(handler)));
+        self
+    }
+
+    pub(crate) fn is_skipped_variant(&self, enum_name: &str, variant_name: &str) -> bool {
+        self.skipped_variants
+            .contains(&(enum_name.to_string(), variant_name.to_string()))
+    }
+
+    define_default_value_setter!(default_bool_value, bool);
+    define_default_value_setter!(default_u8_value, u8);
+    define_default_value_setter!(default_u16_value, u16);
+    define_default_value_setter!(default_u32_value, u32);
+    define_default_value_setter!(default_u64_value, u64);
+    define_default_value_setter!(default_u128_value, u128);
+    define_default_value_setter!(default_i8_value, i8);
+    define_default_value_setter!(default_i16_value, i16);
+    define_default_value_setter!(default_i32_value, i32);
+    define_default_value_setter!(default_i64_value, i64);
+    define_default_value_setter!(default_i128_value, i128);
+    define_default_value_setter!(default_f32_value, f32);
+    define_default_value_setter!(default_f64_value, f64);
+    define_default_value_setter!(default_char_value, char);
+    define_default_value_setter!(default_borrowed_str_value, &'static str);
+    define_default_value_setter!(default_string_value, String);
+    define_default_value_setter!(default_borrowed_bytes_value, &'static [u8]);
+    define_default_value_setter!(default_byte_buf_value, Vec<u8>);
+}
+
+impl Tracer {
+    /// Start tracing deserialization.
+    pub fn new(config: TracerConfig) -> Self {
+        Self {
+            registry: BTreeMap::new(),
+            incomplete_enums: BTreeMap::new(),
+            discriminants: BTreeMap::new(),
+            enum_variant_names: BTreeMap::new(),
+            enum_type_ids: BTreeMap::new(),
+            enum_progress: BTreeMap::new(),
+            variant_positions: BTreeMap::new(),
+            discriminant_names: HashMap::new(),
+            visited_containers: BTreeSet::new(),
+            provenance: BTreeMap::new(),
+            profile: TraceProfile::default(),
+            events: Vec::new(),
+            interrupted_enums: BTreeMap::new(),
+            pass_in_progress: false,
+            containers_in_progress: Vec::new(),
+            field_path: Vec::new(),
+            self_describing_containers: BTreeSet::new(),
+            fallback_variants: BTreeMap::new(),
+            fallback_probe: None,
+            fallback_probe_rejected: false,
+            unknown_fields: BTreeMap::new(),
+            unknown_fields_probe: false,
+            restored_enums: BTreeSet::new(),
+            uninhabited_variants: BTreeSet::new(),
+            unvisited_positions: BTreeMap::new(),
+            uninhabited_retry: false,
+            retried_variants: BTreeMap::new(),
+            dyn_root: None,
+            generic_registries: BTreeMap::new(),
+            generic_root: None,
+            ignored_depth: 0,
+            rng: config.random_seed.map(SplitMix64::new),
+            widened_formats: 0,
+            other_encoding: config.both_encodings.then(|| {
+                Box::new(Tracer::new(TracerConfig {
+                    is_human_readable: !config.is_human_readable,
+                    both_encodings: false,
+                    #[cfg(feature = "spill")]
+                    spill: None,
+                    ..config.clone()
+                }))
+            }),
+            #[cfg(feature = "spill")]
+            spilled: None,
+            #[cfg(feature = "tracing")]
+            container_spans: Vec::new(),
+            config,
+        }
+    }
+
+    /// Trace the serialization of a particular value.
+    /// * Nested containers will be added to the tracing registry, indexed by
+    ///   their (non-qualified) name.
     /// * Sampled Rust values will be inserted into `samples` to benefit future calls
     ///   to the `trace_type_*` methods.
     pub fn trace_value<T>(&mut self, samples: &mut Samples, value: &T) -> Result<(Format, Value)>
     where
         T: ?Sized + Serialize,
     {
+        self.with_root_registry(std::any::type_name::<T>(), |tracer| {
+            tracer.trace_root_value(samples, value)
+        })
+    }
+
+    fn trace_root_value<T>(&mut self, samples: &mut Samples, value: &T) -> Result<(Format, Value)>
+    where
+        T: ?Sized + Serialize,
+    {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("trace_value", type_name = std::any::type_name::<T>()).entered();
+        let start = self.start_pass()?;
+        if let Err(error) = self.check_type::<T>() {
+            self.pass_in_progress = false;
+            return Err(error);
+        }
         let serializer = Serializer::new(self, samples);
-        let (mut format, sample) = value.serialize(serializer)?;
+        let result = value.serialize(serializer);
+        self.pass_in_progress = false;
+        let (mut format, sample) = result?;
         format.reduce();
+        self.finish_pass(&format, std::any::type_name::<T>(), start);
+        #[cfg(feature = "spill")]
+        self.spill_complete_containers()?;
+        if let Some(other) = &mut self.other_encoding {
+            let samples = samples.other_encoding.get_or_insert_with(Default::default);
+            other.trace_value(samples, value)?;
+        }
         Ok((format, sample))
     }
 
+    /// Trace the serialization of the values given by `provider`, for types that do not
+    /// implement Deserialize and therefore cannot be traced with `trace_type`. Return the
+    /// format obtained by unifying the formats of all the values.
+    ///
+    /// Enum variants are only recorded if a value uses them, so the provider should cover
+    /// every variant.
+    pub fn trace_serialize_only<P>(&mut self, samples: &mut Samples, provider: &P) -> Result<Format>
+    where
+        P: SerializeSampleProvider + ?Sized,
+    {
+        let mut format = Format::unknown();
+        let values = provider.samples();
+        if values.is_empty() {
+            return Err(Error::Custom(format!(
+                "No sample value provided for {}",
+                std::any::type_name::<P::Value>()
+            )));
+        }
+        for value in &values {
+            let (other, _) = self.trace_value(samples, value)?;
+            format.unify(other)?;
+        }
+        format.reduce();
+        Ok(format)
+    }
+
+    /// Trace a type described by another framework than Serde. See `TraceableDyn`.
+    /// * The format returned by `value` is reduced, so that a container is referred to by
+    ///   name.
+    /// * Containers recorded with `record_dyn_container` during the call are attributed to
+    ///   `value.type_name()` by `provenance`.
+    pub fn trace_dyn(&mut self, samples: &mut Samples, value: &dyn TraceableDyn) -> Result<Format> {
+        if self.pass_in_progress || self.dyn_root.is_some() {
+            return Err(Error::TracerPoisoned);
+        }
+        self.dyn_root = Some(value.type_name());
+        let result = value.trace(self, samples);
+        self.dyn_root = None;
+        let mut format = result?;
+        format.reduce();
+        Ok(format)
+    }
+
+    /// Record the format of the container `name`, described by another framework than
+    /// Serde, and return the format referring to it. See `TraceableDyn`.
+    /// * The format is unified with the formats previously recorded under `name`.
+    /// * Container names ruled out by `TracerConfig::forbid_type` are rejected.
+    pub fn record_dyn_container(&mut self, name: &str, format: ContainerFormat) -> Result<Format> {
+        if let Some(type_name) = self.forbidden_name(name) {
+            return Err(Error::ForbiddenType {
+                type_name,
+                container: None,
+                field_path: Vec::new(),
+            });
+        }
+        self.registry.entry(name.to_string()).unify(format)?;
+        if self.config.record_provenance {
+            if let Some(root) = &self.dyn_root {
+                self.provenance
+                    .entry(name.to_string())
+                    .or_default()
+                    .insert(root.clone());
+            }
+        }
+        Ok(Format::TypeName(name.to_string()))
+    }
+
     /// Trace a single deserialization of a particular type.
     /// * Nested containers will be added to the tracing registry, indexed by
     ///   their (non-qualified) name.
@@ -221,14 +1734,41 @@ impl Tracer {
     where
         T: Deserialize<'de>,
     {
+        self.with_root_registry(std::any::type_name::<T>(), |tracer| {
+            tracer.trace_root_once(samples)
+        })
+    }
+
+    fn trace_root_once<'de, T>(&mut self, samples: &'de Samples) -> Result<(Format, T)>
+    where
+        T: Deserialize<'de>,
+    {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::trace_span!("trace_pass", type_name = std::any::type_name::<T>()).entered();
+        let start = self.start_pass()?;
+        if let Err(error) = self.check_type::<T>() {
+            self.pass_in_progress = false;
+            return Err(error);
+        }
         let mut format = Format::unknown();
         let deserializer = Deserializer::new(self, samples, &mut format);
-        let value = T::deserialize(deserializer)?;
+        let result = T::deserialize(deserializer);
+        self.pass_in_progress = false;
+        let value = result?;
         format.reduce();
+        self.finish_pass(&format, std::any::type_name::<T>(), start);
+        if let Some(other) = &mut self.other_encoding {
+            static NO_SAMPLES: Lazy<Samples> = Lazy::new(Samples::new);
+            let samples = samples.other_encoding.as_deref().unwrap_or(&*NO_SAMPLES);
+            other.trace_type_once::<T>(samples)?;
+        }
         Ok((format, value))
     }
 
     /// Same as `trace_type_once` for seeded deserialization.
+    /// * This is meant for types that can only be deserialized with some contextual
+    ///   information, i.e. a user-provided `DeserializeSeed`.
     pub fn trace_type_once_with_seed<'de, S>(
         &mut self,
         samples: &'de Samples,
@@ -237,40 +1777,516 @@ impl Tracer {
     where
         S: DeserializeSeed<'de>,
     {
+        self.with_root_registry(std::any::type_name::<S::Value>(), |tracer| {
+            tracer.trace_root_once_with_seed(samples, seed)
+        })
+    }
+
+    fn trace_root_once_with_seed<'de, S>(
+        &mut self,
+        samples: &'de Samples,
+        seed: S,
+    ) -> Result<(Format, S::Value)>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::trace_span!("trace_pass", type_name = std::any::type_name::<S::Value>())
+                .entered();
+        let start = self.start_pass()?;
+        if let Err(error) = self.check_type::<S::Value>() {
+            self.pass_in_progress = false;
+            return Err(error);
+        }
         let mut format = Format::unknown();
         let deserializer = Deserializer::new(self, samples, &mut format);
-        let value = seed.deserialize(deserializer)?;
+        let result = seed.deserialize(deserializer);
+        self.pass_in_progress = false;
+        if matches!(result, Err(Error::Uninhabited(_))) && self.uninhabited_retry {
+            // As after a complete pass, the next pass resumes the exploration of the traced
+            // enum by name instead of visiting its first variant.
+            format.reduce();
+            if let Format::TypeName(name) = &format {
+                self.incomplete_enums.remove(name);
+            }
+        }
+        let value = result?;
         format.reduce();
+        self.finish_pass(&format, std::any::type_name::<S::Value>(), start);
         Ok((format, value))
     }
 
+    /// Run `f`, which traces the root type `type_name`. If the root type mentions type
+    /// parameters, e.g. `Pair<TypeParam<0>>`, the containers that it reaches are recorded
+    /// apart from the others, in `generic_registries`.
+    fn with_root_registry<R>(
+        &mut self,
+        type_name: &str,
+        f: impl FnOnce(&mut Self) -> Result<R>,
+    ) -> Result<R> {
+        if self.generic_root.is_some() || !mentions_type_params(type_name) {
+            return f(self);
+        }
+        let recorded = self
+            .generic_registries
+            .remove(type_name)
+            .unwrap_or_default();
+        let registry = std::mem::replace(&mut self.registry, recorded);
+        self.generic_root = Some(type_name.to_string());
+        let result = f(self);
+        self.generic_root = None;
+        let recorded = std::mem::replace(&mut self.registry, registry);
+        self.generic_registries
+            .insert(type_name.to_string(), recorded);
+        result
+    }
+
+    /// Obtain the names of the root types whose tracing introduced the container `name`.
+    /// * A root type is the type `T` passed to a top-level call such as `trace_value` or
+    ///   `trace_type`. Its name is the container name when `T` is a container, and the Rust
+    ///   type name otherwise.
+    /// * This requires `TracerConfig::record_provenance` to be enabled. Otherwise, the
+    ///   result is always empty.
+    /// * The provenance is kept in the metadata returned by `registry_with_metadata`.
+    pub fn provenance(&self, name: &str) -> Vec<String> {
+        self.provenance
+            .get(name)
+            .map(|roots| roots.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Obtain the metadata recorded so far about the traced containers, e.g. their
+    /// provenance, as a sidecar that outlives the tracer. See `registry_with_metadata`.
+    pub fn metadata(&self) -> RegistryMetadata {
+        let mut metadata = RegistryMetadata::new();
+        for (name, roots) in &self.provenance {
+            for root in roots {
+                metadata.add_provenance(name, root);
+            }
+        }
+        for name in self.self_describing_containers() {
+            metadata.mark_self_describing(&name);
+        }
+        for name in self.fallback_variants.keys().chain(self.registry.keys()) {
+            if let Some(variant) = self.fallback_variant(name) {
+                metadata.set_fallback_variant(name, variant);
+            }
+        }
+        for (name, format) in &self.registry {
+            if let ContainerFormat::Struct(_) = format {
+                if let Some(value) = self.denies_unknown_fields(name) {
+                    metadata.set_denies_unknown_fields(name, value);
+                }
+            }
+        }
+        metadata
+    }
+
+    /// Obtain the statistics collected so far.
+    /// * This requires `TracerConfig::record_profile` to be enabled. Otherwise, the
+    ///   result is always empty.
+    pub fn profile(&self) -> TraceProfile {
+        self.profile.clone()
+    }
+
+    /// The constraints declared with `TracerConfig::constrain`.
+    pub fn constraints(&self) -> &Constraints {
+        &self.config.constraints
+    }
+
+    /// The value of a primitive member: synthesized if `TracerConfig::random_seed` is set,
+    /// otherwise `default`.
+    pub(crate) fn synthesize<T: Synthesize>(&mut self, default: T) -> T {
+        match &mut self.rng {
+            Some(rng) => T::synthesize(rng),
+            None => default,
+        }
+    }
+
+    /// Apply the integer bounds declared for the location being traced, if any, to a
+    /// default value.
+    pub(crate) fn bounded<T>(&self, default: T) -> T
+    where
+        T: Copy + TryFrom<i128>,
+        i128: TryFrom<T>,
+    {
+        if self.config.constraints.is_empty() {
+            return default;
+        }
+        let Some((name, depth)) = self.containers_in_progress.last() else {
+            return default;
+        };
+        let path: Vec<&str> = std::iter::once(*name)
+            .chain(self.field_path[*depth..].iter().map(String::as_str))
+            .collect();
+        match self.config.constraints.get(&path) {
+            Some(constraint) => constraint.clamp(default),
+            None => default,
+        }
+    }
+
+    /// The number of elements of the collection constructed at the location being traced,
+    /// given the formats traced so far for its elements (or the keys and the values of its
+    /// entries). Collections whose elements are known to be uninhabited are always empty.
+    pub(crate) fn seq_sample_len(&self, contents: &[&Format]) -> usize {
+        let known = contents.iter().all(|format| !format.is_unknown());
+        let default = if known { 0 } else { self.config.seq_sample_len };
+        if self.config.member_seq_sample_lens.is_empty() {
+            return default;
+        }
+        let Some((name, depth)) = self.containers_in_progress.last() else {
+            return default;
+        };
+        let recursive = self.containers_in_progress[..self.containers_in_progress.len() - 1]
+            .iter()
+            .any(|(other, _)| other == name);
+        if known && recursive {
+            return default;
+        }
+        let path: Vec<&str> = std::iter::once(*name)
+            .chain(self.field_path[*depth..].iter().map(String::as_str))
+            .collect();
+        let len = self
+            .config
+            .member_seq_sample_lens
+            .iter()
+            .find(|(member, _)| *member == path)
+            .map_or(default, |(_, len)| *len);
+        if known && len > 0 && self.is_uninhabited_content(contents) {
+            return 0;
+        }
+        len
+    }
+
+    /// Whether one of the known `formats` refers to a container found to be uninhabited,
+    /// such as an enum without variants.
+    fn is_uninhabited_content(&self, formats: &[&Format]) -> bool {
+        let uninhabited = analysis::uninhabited_containers(&self.registry);
+        if uninhabited.is_empty() {
+            return false;
+        }
+        let inhabited: BTreeSet<_> = self
+            .registry
+            .keys()
+            .filter(|name| !uninhabited.contains(*name))
+            .cloned()
+            .collect();
+        formats
+            .iter()
+            .any(|format| !analysis::is_inhabited(&self.registry, &inhabited, format))
+    }
+
+    /// Obtain the events recorded so far, in chronological order.
+    /// * This requires `TracerConfig::record_events` to be enabled. Otherwise, the
+    ///   result is always empty.
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// Record an event if `TracerConfig::record_events` is enabled. With the feature
+    /// `tracing`, events are also emitted to the current subscriber: variants at the level
+    /// `DEBUG`, other events at the level `TRACE`.
+    pub(crate) fn record_event<F>(&mut self, event: F)
+    where
+        F: FnOnce() -> TraceEvent,
+    {
+        #[cfg(feature = "tracing")]
+        let emit = tracing::enabled!(tracing::Level::DEBUG);
+        #[cfg(not(feature = "tracing"))]
+        let emit = false;
+        if !self.config.record_events && !emit {
+            return;
+        }
+        let event = event();
+        #[cfg(feature = "tracing")]
+        if emit {
+            match &event {
+                TraceEvent::VariantAdvanced {
+                    enum_name,
+                    name,
+                    index,
+                } => tracing::debug!(%enum_name, ?name, ?index, "variant advanced"),
+                event => tracing::trace!(?event, "trace event"),
+            }
+        }
+        if self.config.record_events {
+            self.events.push(event);
+        }
+    }
+
+    /// Whether the container `name` holds values that were deserialized with
+    /// `deserialize_any` (see `TracerConfig::self_describing`). Such containers can only be
+    /// read from self-describing encodings such as JSON or CBOR.
+    /// This also holds for the traced containers whose registered attributes require a
+    /// self-describing encoding (see [`attributes`](crate::attributes)).
+    /// The marker is kept in the metadata returned by `registry_with_metadata`, where
+    /// exporters can check it (see `Exporters::export_with_metadata`).
+    pub fn requires_self_describing(&self, name: &str) -> bool {
+        self.self_describing_containers.contains(name)
+            || (self.registry.contains_key(name)
+                && attributes::lookup(name).is_some_and(|attrs| attrs.requires_self_describing()))
+    }
+
+    /// Obtain the names of the containers that require a self-describing encoding.
+    pub fn self_describing_containers(&self) -> Vec<String> {
+        let mut names = self.self_describing_containers.clone();
+        names.extend(
+            self.registry
+                .keys()
+                .filter(|name| self.requires_self_describing(name))
+                .cloned(),
+        );
+        names.into_iter().collect()
+    }
+
+    /// Obtain the Serde attributes registered for the container `name`, if any. See
+    /// [`attributes`](crate::attributes).
+    pub fn attributes(&self, name: &str) -> Option<&'static ContainerAttributes> {
+        attributes::lookup(name)
+    }
+
+    /// Obtain the name of the variant that the enum `name` uses to deserialize unknown
+    /// variants, if any (e.g. a variant marked with `#[serde(other)]`). Such enums tolerate
+    /// variants added by future versions of the format.
+    /// * This is detected by `trace_type` (and `trace_type_with_seed`) once all the variants
+    ///   of the enum are known when `TracerConfig::probe_fallback_variants` is enabled, or
+    ///   read from the registered attributes of the enum.
+    /// * The fallback variant is kept in the metadata returned by `registry_with_metadata`.
+    pub fn fallback_variant(&self, name: &str) -> Option<&str> {
+        match self.fallback_variants.get(name) {
+            Some(variant) => Some(variant),
+            None => attributes::lookup(name)?.fallback_variant(),
+        }
+    }
+
+    /// Whether the struct `name` rejects unknown fields during deserialization, if known.
+    /// * This is either declared with `TracerConfig::deny_unknown_fields`, read from the
+    ///   registered attributes of the struct, or detected when
+    ///   `TracerConfig::probe_unknown_fields` is enabled.
+    /// * Unknown fields only occur in self-describing encodings. With such encodings, adding
+    ///   a field to a struct that does not reject unknown fields is compatible with older
+    ///   readers.
+    /// * The answer is kept in the metadata returned by `registry_with_metadata`, which
+    ///   `RegistryDiff::with_metadata` uses to classify added and removed fields.
+    pub fn denies_unknown_fields(&self, name: &str) -> Option<bool> {
+        if self.config.denied_unknown_fields.contains(name) {
+            return Some(true);
+        }
+        if let Some(attrs) = attributes::lookup(name) {
+            return Some(attrs.deny_unknown_fields);
+        }
+        self.unknown_fields.get(name).copied().flatten()
+    }
+
+    /// Whether the enum `name` is known to have variants that were not traced yet.
+    pub fn is_incomplete_enum(&self, name: &str) -> bool {
+        self.incomplete_enums.contains_key(name) || self.interrupted_enums.contains_key(name)
+    }
+
     /// Read the status of an enum and reset the value.
     pub fn check_incomplete_enum(&mut self, name: &str) -> Option<IncompleteEnumReason> {
         self.incomplete_enums.remove(name)
     }
 
+    /// Upper bound on the number of passes of `trace_type_once` still needed to cover all
+    /// the variants of the enum `name`, assuming that its Deserialize implementation is
+    /// deterministic and maps distinct indices to distinct variants (as derived
+    /// implementations do).
+    /// * Return `None` if `name` was not traced as an enum during deserialization.
+    /// * Each variant name takes at most two passes: one to visit the variant and one to find
+    ///   its index. Variants skipped by the configuration take none.
+    pub fn estimated_remaining_passes(&self, name: &str) -> Option<usize> {
+        if !self.enum_type_ids.contains_key(name) {
+            return None;
+        }
+        if !self.is_incomplete_enum(name) {
+            return Some(0);
+        }
+        Some(self.pending_enum_passes(name))
+    }
+
+    /// Whether the variant is never visited by the tracing deserializer, either because of
+    /// `TracerConfig::skip_variant` or because it was found to be uninhabited.
+    pub(crate) fn is_unvisited_variant(&self, enum_name: &str, variant_name: &str) -> bool {
+        self.config.is_skipped_variant(enum_name, variant_name)
+            || self
+                .uninhabited_variants
+                .contains(&(enum_name.to_string(), variant_name.to_string()))
+    }
+
+    /// The positions of the unvisited variants of an enum. They are memoized, then updated
+    /// when a variant is found to be uninhabited.
+    pub(crate) fn unvisited_positions(
+        &mut self,
+        enum_name: &'static str,
+        variants: &[&str],
+    ) -> BTreeSet<u32> {
+        if let Some(positions) = self.unvisited_positions.get(enum_name) {
+            return positions.clone();
+        }
+        let positions: BTreeSet<u32> = (0..variants.len() as u32)
+            .filter(|i| self.is_unvisited_variant(enum_name, variants[*i as usize]))
+            .collect();
+        self.unvisited_positions
+            .insert(enum_name, positions.clone());
+        positions
+    }
+
+    /// The position of a variant in the declaration of its enum.
+    pub(crate) fn variant_position(
+        &mut self,
+        enum_name: &'static str,
+        variants: &'static [&'static str],
+        variant_name: &str,
+    ) -> Option<u32> {
+        self.variant_positions
+            .entry(enum_name)
+            .or_insert_with(|| {
+                variants
+                    .iter()
+                    .enumerate()
+                    .map(|(position, name)| (*name, position as u32))
+                    .collect()
+            })
+            .get(variant_name)
+            .copied()
+    }
+
+    /// Whether all the variants of the enum were found to be uninhabited.
+    pub(crate) fn is_uninhabited_enum(&self, enum_name: &str, variants: &[&str]) -> bool {
+        variants.iter().all(|variant| {
+            self.uninhabited_variants
+                .contains(&(enum_name.to_string(), variant.to_string()))
+        })
+    }
+
+    /// Abort the current pass for another one if an option, a sequence, or a map failed on
+    /// an uninhabited content. The format of the content is now known, so the next pass
+    /// produces `None` or an empty collection instead.
+    pub(crate) fn retry_if_uninhabited<T>(&mut self, result: &Result<T>) {
+        if let Err(Error::Uninhabited(_)) = result {
+            self.uninhabited_retry = true;
+        }
+    }
+
+    /// The format to start from when visiting the variant `index` of the enum `enum_name`.
+    pub(crate) fn retried_variant(&mut self, enum_name: &str, index: u32) -> VariantFormat {
+        self.retried_variants
+            .remove(&(enum_name.to_string(), index))
+            .unwrap_or_else(VariantFormat::unknown)
+    }
+
+    /// Keep the format `value` traced for the variant `index` of the enum `enum_name` if
+    /// the visit was aborted for another pass by an uninhabited type.
+    pub(crate) fn keep_retried_variant<T>(
+        &mut self,
+        enum_name: &str,
+        index: u32,
+        value: VariantFormat,
+        result: &Result<T>,
+    ) {
+        if matches!(result, Err(Error::Uninhabited(_))) && self.uninhabited_retry {
+            self.retried_variants
+                .insert((enum_name.to_string(), index), value);
+        }
+    }
+
+    /// Record that visiting a variant failed on an uninhabited type. The entry of the
+    /// variant moves from its provisional index to its position in the declaration of the
+    /// enum, as for skipped variants.
+    pub(crate) fn record_uninhabited_variant(
+        &mut self,
+        enum_name: &str,
+        variant_name: &str,
+        position: u32,
+        provisional_index: u32,
+    ) -> Result<()> {
+        self.uninhabited_variants
+            .insert((enum_name.to_string(), variant_name.to_string()));
+        if let Some(positions) = self.unvisited_positions.get_mut(enum_name) {
+            positions.insert(position);
+        }
+        let Some(ContainerFormat::Enum(variants)) = self.registry.get_mut(enum_name) else {
+            return Ok(());
+        };
+        if let Some(variant) = variants.remove(&provisional_index) {
+            match variants.entry(position) {
+                Entry::Vacant(entry) => {
+                    entry.insert(variant);
+                }
+                Entry::Occupied(mut entry) => entry.get_mut().value.unify(variant.value)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn pending_enum_passes(&self, name: &str) -> usize {
+        let (Some(type_id), Some(variants)) = (
+            self.enum_type_ids.get(name),
+            self.enum_variant_names.get(name),
+        ) else {
+            return 0;
+        };
+        let unvisited = variants
+            .iter()
+            .filter(|variant| {
+                !self.is_unvisited_variant(name, variant)
+                    && !self
+                        .discriminants
+                        .contains_key(&(*type_id, VariantId::Name(variant)))
+            })
+            .count();
+        let provisional_min = u32::MAX - (variants.len() - 1) as u32;
+        let unresolved = match self.registry.get(name) {
+            Some(ContainerFormat::Enum(known_variants)) => {
+                known_variants.range(provisional_min..).count()
+            }
+            _ => 0,
+        };
+        // At least one pass is needed while the enum is incomplete.
+        std::cmp::max(2 * unvisited + unresolved, 1)
+    }
+
+    /// Fail if the passes made by the current call exceed the bound computed after the first
+    /// incomplete pass.
+    fn check_convergence(
+        &self,
+        name: &str,
+        passes: usize,
+        bound: &mut Option<usize>,
+    ) -> Result<()> {
+        let bound = *bound.get_or_insert_with(|| passes + self.pending_enum_passes(name));
+        if passes >= bound {
+            return Err(Error::NoConvergence(name.to_string(), passes));
+        }
+        Ok(())
+    }
+
     /// Same as `trace_type_once` but if `T` is an enum, we repeat the process
     /// until all variants of `T` are covered.
     /// We accumulate and return all the sampled values at the end.
+    /// * Variants are explored in the order of declaration: the values of the first passes
+    ///   are made of the first variants. With `VariantOrder::SampleFirst`, the variants
+    ///   already recorded with a complete format are skipped.
+    /// * If `TracerConfig::max_passes_per_trace` is set, the analysis may stop before all
+    ///   variants are covered. Use `is_incomplete_enum` to decide whether to call this
+    ///   function again.
+    /// * For an enum `T` with `n` variant names (including aliases), at most `2 * n` passes
+    ///   are made: one per name to visit the variant, then one per name to find its index. Nested enums are
+    ///   explored during the same passes and do not add to the bound, but they are only
+    ///   guaranteed to be complete once traced themselves. See `estimated_remaining_passes`.
+    /// * If the bound is exceeded, e.g. because the Deserialize implementation of `T` is not
+    ///   deterministic, return `Error::NoConvergence` instead of looping forever.
+    /// * If `T` is an enum restored complete from a checkpoint (see `restore`) or spilled
+    ///   to disk, its variants are not explored again: a single pass is made and a single
+    ///   value, made of the first variant, is returned. Enums that were still being explored
+    ///   when the checkpoint was saved are explored again from their first variant.
     pub fn trace_type<'de, T>(&mut self, samples: &'de Samples) -> Result<(Format, Vec<T>)>
     where
         T: Deserialize<'de>,
     {
-        let mut values = Vec::new();
-        loop {
-            let (format, value) = self.trace_type_once::<T>(samples)?;
-            values.push(value);
-            if let Format::TypeName(name) = &format {
-                if let Some(reason) = self.check_incomplete_enum(name) {
-                    // Restart the analysis to find more variants of T.
-                    if let IncompleteEnumReason::NamedVariantsRemaining = reason {
-                        values.pop().unwrap();
-                    }
-                    continue;
-                }
-            }
-            return Ok((format, values));
-        }
+        self.trace_type_with_seed(samples, PhantomData)
     }
 
     /// Trace a type `T` that is simple enough that no samples of values are needed.
@@ -286,6 +2302,7 @@ impl Tracer {
     }
 
     /// Same as `trace_type` for seeded deserialization.
+    /// * The seed is cloned for each call to `trace_type_once_with_seed`.
     pub fn trace_type_with_seed<'de, S>(
         &mut self,
         samples: &'de Samples,
@@ -294,55 +2311,991 @@ impl Tracer {
     where
         S: DeserializeSeed<'de> + Clone,
     {
+        self.with_root_registry(std::any::type_name::<S::Value>(), |tracer| {
+            tracer.trace_root_with_seed(samples, seed)
+        })
+    }
+
+    fn trace_root_with_seed<'de, S>(
+        &mut self,
+        samples: &'de Samples,
+        seed: S,
+    ) -> Result<(Format, Vec<S::Value>)>
+    where
+        S: DeserializeSeed<'de> + Clone,
+    {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("trace_type", type_name = std::any::type_name::<S::Value>())
+                .entered();
         let mut values = Vec::new();
+        let mut passes = 0;
+        let mut retries = 0;
+        let mut bound = None;
         loop {
-            let (format, value) = self.trace_type_once_with_seed(samples, seed.clone())?;
+            let (format, value) = match self.trace_pass_with_seed(samples, seed.clone()) {
+                // The uninhabited type is avoided during the next pass. Such passes do not
+                // count towards the bound on enum passes, but they count towards
+                // `TracerConfig::max_passes_per_trace` and are limited in number.
+                Err(Error::Uninhabited(name)) if self.uninhabited_retry => {
+                    retries += 1;
+                    if retries > MAX_UNINHABITED_RETRIES
+                        || self.reached_max_passes(passes + retries)
+                    {
+                        return Err(Error::Uninhabited(name));
+                    }
+                    continue;
+                }
+                result => result?,
+            };
             values.push(value);
+            passes += 1;
             if let Format::TypeName(name) = &format {
+                self.interrupted_enums.remove(name);
                 if let Some(reason) = self.check_incomplete_enum(name) {
-                    // Restart the analysis to find more variants of T.
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(enum_name = %name, passes, ?reason, "enum is incomplete");
+                    self.check_convergence(name, passes, &mut bound)?;
                     if let IncompleteEnumReason::NamedVariantsRemaining = reason {
                         values.pop().unwrap();
                     }
+                    if self.reached_max_passes(passes) {
+                        // Remember that the analysis must be resumed by another call.
+                        self.interrupted_enums.insert(name.clone(), reason);
+                        #[cfg(feature = "spill")]
+                        self.spill_complete_containers()?;
+                        return Ok((format, values));
+                    }
+                    // Restart the analysis to find more variants of T.
                     continue;
                 }
+                self.probe_fallback_variant(name, |tracer| {
+                    tracer
+                        .trace_pass_with_seed(samples, seed.clone())
+                        .map(|_| ())
+                })?;
             }
+            self.probe_unknown_fields(|tracer| {
+                tracer
+                    .trace_pass_with_seed(samples, seed.clone())
+                    .map(|_| ())
+            });
+            #[cfg(feature = "spill")]
+            self.spill_complete_containers()?;
             return Ok((format, values));
         }
     }
 
+    /// Same as `trace_type_once_with_seed`, followed by a pass of the tracer of the other
+    /// encoding if `TracerConfig::both_encodings` is set, as in `trace_type_once`.
+    fn trace_pass_with_seed<'de, S>(
+        &mut self,
+        samples: &'de Samples,
+        seed: S,
+    ) -> Result<(Format, S::Value)>
+    where
+        S: DeserializeSeed<'de> + Clone,
+    {
+        let result = self.trace_type_once_with_seed(samples, seed.clone())?;
+        if let Some(other) = &mut self.other_encoding {
+            static NO_SAMPLES: Lazy<Samples> = Lazy::new(Samples::new);
+            let samples = samples.other_encoding.as_deref().unwrap_or(&*NO_SAMPLES);
+            other.trace_type_once_with_seed(samples, seed)?;
+        }
+        Ok(result)
+    }
+
+    /// Save the progress of tracing so far. See [`TraceCheckpoint`].
+    /// * Containers whose formats are still incomplete are left out, as well as the enums
+    ///   whose variants are still being explored (see `is_incomplete_enum`). Their progress
+    ///   is lost: they are explored again from their first variant after a restore.
+    /// * Enums only traced by serialization are saved as containers, but their exploration
+    ///   by `trace_type` is not considered done.
+    /// * The containers recorded for root types with type parameters (see `TypeParam`) are
+    ///   left out: such roots must be traced again after a restore.
+    pub fn checkpoint(&self) -> TraceCheckpoint {
+        let complete_enums: BTreeSet<String> = self
+            .enum_type_ids
+            .keys()
+            .map(|name| name.to_string())
+            .chain(self.restored_enums.iter().cloned())
+            .filter(|name| !self.is_incomplete_enum(name))
+            .collect();
+        let registry = self
+            .registry
+            .iter()
+            .filter(|(name, _)| !self.is_incomplete_enum(name))
+            .filter_map(|(name, format)| {
+                let mut format = format.clone();
+                format.normalize().ok()?;
+                Some((name.clone(), format))
+            })
+            .collect();
+        TraceCheckpoint {
+            version: TraceCheckpoint::VERSION,
+            registry,
+            complete_enums,
+            fallback_variants: self.fallback_variants.clone(),
+            self_describing_containers: self.self_describing_containers.clone(),
+            unknown_fields: self.unknown_fields.clone(),
+        }
+    }
+
+    /// Resume tracing from a checkpoint saved by `checkpoint`, typically in a new tracer
+    /// with the same configuration. The saved containers are unified with the ones traced
+    /// so far. The complete enums of the checkpoint are not explored again: during
+    /// deserialization, they are only visited with their first variant, since the formats
+    /// of the other variants are already known. As a consequence, `trace_type` returns a
+    /// single value for them instead of one value per variant.
+    pub fn restore(&mut self, checkpoint: TraceCheckpoint) -> Result<()> {
+        if checkpoint.version != TraceCheckpoint::VERSION {
+            return Err(Error::InvalidCheckpoint(format!(
+                "unsupported version {} (expected {})",
+                checkpoint.version,
+                TraceCheckpoint::VERSION
+            )));
+        }
+        for (name, format) in checkpoint.registry {
+            self.registry.entry(name).unify(format)?;
+        }
+        for name in checkpoint.complete_enums {
+            if !matches!(self.registry.get(&name), Some(ContainerFormat::Enum(_))) {
+                return Err(Error::InvalidCheckpoint(format!(
+                    "enum {name} is missing from the checkpoint"
+                )));
+            }
+            self.incomplete_enums.remove(&name);
+            self.interrupted_enums.remove(&name);
+            self.restored_enums.insert(name);
+        }
+        self.fallback_variants.extend(checkpoint.fallback_variants);
+        self.self_describing_containers
+            .extend(checkpoint.self_describing_containers);
+        self.unknown_fields.extend(checkpoint.unknown_fields);
+        Ok(())
+    }
+
     /// Finish tracing and recover a map of normalized formats.
     /// Returns an error if we detect incompletely traced types.
     /// This may happen in a few of cases:
     /// * We traced serialization of user-provided values but we are still missing the content
     ///   of an option type, the content of a sequence type, the key or the value of a dictionary type.
     /// * We traced deserialization of an enum type but we detect that some enum variants are still missing.
-    pub fn registry(self) -> Result<Registry> {
-        let mut registry = self.registry;
+    pub fn registry(mut self) -> Result<Registry> {
+        if self.is_poisoned() {
+            return Err(Error::TracerPoisoned);
+        }
+        let (mut registry, errors) = self.take_registry();
+        if let Some(error) = errors.into_iter().next() {
+            return Err(error);
+        }
         for (name, format) in registry.iter_mut() {
             format
                 .normalize()
                 .map_err(|_| Error::UnknownFormatInContainer(name.clone()))?;
         }
-        if self.incomplete_enums.is_empty() {
+        if let Some(error) =
+            apply_declarations(&self.config, &self.enum_variant_names, &mut registry)
+                .into_iter()
+                .next()
+        {
+            return Err(error);
+        }
+        if let Some(error) = self.abstract_type_params(&mut registry).into_iter().next() {
+            return Err(error);
+        }
+        if let Some(error) = find_forbidden_format(&self.config, &registry) {
+            return Err(error);
+        }
+        if let Some(error) = find_unspecified_float(&self.config, &registry) {
+            return Err(error);
+        }
+        let mut incomplete_enums = self.incomplete_enums;
+        incomplete_enums.extend(self.interrupted_enums);
+        if incomplete_enums.is_empty() {
             Ok(registry)
         } else {
             Err(Error::MissingVariants(
-                self.incomplete_enums.into_keys().collect(),
+                incomplete_enums.into_keys().collect(),
             ))
         }
     }
 
+    /// Finish tracing as in `registry` and recover the metadata of the containers of the
+    /// registry along with it (see `metadata`).
+    pub fn registry_with_metadata(self) -> Result<(Registry, RegistryMetadata)> {
+        let mut metadata = self.metadata();
+        let registry = self.registry()?;
+        metadata.retain(|name| registry.contains_key(name));
+        Ok((registry, metadata))
+    }
+
+    /// Finish tracing with `TracerConfig::both_encodings` and recover the registries of both
+    /// encodings, as in `registry`. Containers whose formats agree are shared by both.
+    pub fn multi_registry(mut self) -> Result<MultiRegistry> {
+        let other = self.other_encoding.take().ok_or(Error::NotSupported(
+            "multi_registry requires TracerConfig::both_encodings",
+        ))?;
+        let is_human_readable = self.config.is_human_readable;
+        let (registry, other_registry) = (self.registry()?, other.registry()?);
+        Ok(if is_human_readable {
+            MultiRegistry::new(registry, other_registry)
+        } else {
+            MultiRegistry::new(other_registry, registry)
+        })
+    }
+
+    /// Trace all the given roots then finish tracing, collecting every error along the way
+    /// instead of stopping at the first one.
+    /// * A root that fails to trace is skipped: the tracing state, including the containers
+    ///   and the enum exploration that it recorded, is rolled back, and the error is
+    ///   returned wrapped in `Error::RootFailed`. Each root therefore costs a copy of the
+    ///   tracing state.
+    /// * Containers that remain incomplete are left out of the registry and reported as
+    ///   errors, as in `registry`.
+    pub fn trace_all<I>(mut self, roots: I, samples: &Samples) -> (Registry, Vec<Error>)
+    where
+        I: IntoIterator<Item = TraceRoot>,
+    {
+        let mut errors = Vec::new();
+        for root in roots {
+            if let Err(error) = self.trace_root(&root, samples) {
+                errors.push(Error::RootFailed(root.name, Box::new(error)));
+            }
+        }
+        let mut registry = BTreeMap::new();
+        let (recorded, spill_errors) = self.take_registry();
+        errors.extend(spill_errors);
+        for (name, mut format) in recorded {
+            match format.normalize() {
+                Ok(()) => {
+                    registry.insert(name, format);
+                }
+                Err(_) => errors.push(Error::UnknownFormatInContainer(name)),
+            }
+        }
+        errors.extend(apply_declarations(
+            &self.config,
+            &self.enum_variant_names,
+            &mut registry,
+        ));
+        errors.extend(self.abstract_type_params(&mut registry));
+        errors.extend(find_forbidden_format(&self.config, &registry));
+        errors.extend(find_unspecified_float(&self.config, &registry));
+        let mut incomplete_enums = self.incomplete_enums;
+        incomplete_enums.extend(self.interrupted_enums);
+        if !incomplete_enums.is_empty() {
+            errors.push(Error::MissingVariants(
+                incomplete_enums.into_keys().collect(),
+            ));
+        }
+        (registry, errors)
+    }
+
+    /// Trace a root, rolling back the tracing state if it fails, so that the next roots are
+    /// traced as if this one had not been attempted.
+    fn trace_root(&mut self, root: &TraceRoot, samples: &Samples) -> Result<()> {
+        let snapshot = self.snapshot();
+        let result = (root.trace)(self, samples);
+        if result.is_err() {
+            self.rollback(snapshot)?;
+        }
+        result
+    }
+
+    /// Save the state of tracing, to be restored by `rollback`. The registry is copied with
+    /// its formats reduced, since variables are shared by clones. Diagnostics (the profile
+    /// and the events) are not saved.
+    fn snapshot(&self) -> TracerSnapshot {
+        TracerSnapshot {
+            registry: self
+                .registry
+                .iter()
+                .map(|(name, format)| {
+                    let mut format = format.clone();
+                    format.reduce();
+                    (name.clone(), format)
+                })
+                .collect(),
+            incomplete_enums: self.incomplete_enums.clone(),
+            discriminants: self.discriminants.clone(),
+            enum_variant_names: self.enum_variant_names.clone(),
+            enum_type_ids: self.enum_type_ids.clone(),
+            enum_progress: self.enum_progress.clone(),
+            variant_positions: self.variant_positions.clone(),
+            discriminant_names: self.discriminant_names.clone(),
+            provenance: self.provenance.clone(),
+            interrupted_enums: self.interrupted_enums.clone(),
+            self_describing_containers: self.self_describing_containers.clone(),
+            fallback_variants: self.fallback_variants.clone(),
+            unknown_fields: self.unknown_fields.clone(),
+            restored_enums: self.restored_enums.clone(),
+            uninhabited_variants: self.uninhabited_variants.clone(),
+            unvisited_positions: self.unvisited_positions.clone(),
+            retried_variants: self
+                .retried_variants
+                .iter()
+                .map(|(key, format)| {
+                    let mut format = format.clone();
+                    format.reduce();
+                    (key.clone(), format)
+                })
+                .collect(),
+            generic_registries: self
+                .generic_registries
+                .iter()
+                .map(|(root, registry)| {
+                    let registry = registry
+                        .iter()
+                        .map(|(name, format)| {
+                            let mut format = format.clone();
+                            format.reduce();
+                            (name.clone(), format)
+                        })
+                        .collect();
+                    (root.clone(), registry)
+                })
+                .collect(),
+            rng: self.rng.clone(),
+            widened_formats: self.widened_formats,
+            other_encoding: self
+                .other_encoding
+                .as_ref()
+                .map(|other| Box::new(other.snapshot())),
+            #[cfg(feature = "spill")]
+            spilled_len: self.spilled.as_ref().map(SpilledRegistry::file_len),
+        }
+    }
+
+    /// Restore the state saved by `snapshot`.
+    fn rollback(&mut self, snapshot: TracerSnapshot) -> Result<()> {
+        self.registry = snapshot.registry;
+        self.incomplete_enums = snapshot.incomplete_enums;
+        self.discriminants = snapshot.discriminants;
+        self.enum_variant_names = snapshot.enum_variant_names;
+        self.enum_type_ids = snapshot.enum_type_ids;
+        self.enum_progress = snapshot.enum_progress;
+        self.variant_positions = snapshot.variant_positions;
+        self.discriminant_names = snapshot.discriminant_names;
+        self.provenance = snapshot.provenance;
+        self.interrupted_enums = snapshot.interrupted_enums;
+        self.self_describing_containers = snapshot.self_describing_containers;
+        self.fallback_variants = snapshot.fallback_variants;
+        self.unknown_fields = snapshot.unknown_fields;
+        self.restored_enums = snapshot.restored_enums;
+        self.uninhabited_variants = snapshot.uninhabited_variants;
+        self.unvisited_positions = snapshot.unvisited_positions;
+        self.uninhabited_retry = false;
+        self.retried_variants = snapshot.retried_variants;
+        self.fallback_probe = None;
+        self.fallback_probe_rejected = false;
+        self.unknown_fields_probe = false;
+        self.dyn_root = None;
+        self.generic_registries = snapshot.generic_registries;
+        self.generic_root = None;
+        self.rng = snapshot.rng;
+        self.widened_formats = snapshot.widened_formats;
+        if let (Some(other), Some(snapshot)) = (&mut self.other_encoding, snapshot.other_encoding) {
+            other.rollback(*snapshot)?;
+        }
+        #[cfg(feature = "spill")]
+        match (&mut self.spilled, snapshot.spilled_len) {
+            (Some(spilled), Some(len)) => spilled.truncate(len)?,
+            (Some(spilled), None) => spilled.truncate(0)?,
+            (None, _) => (),
+        }
+        Ok(())
+    }
+
+    /// Compute an order in which to trace `roots` that avoids wasting passes on the roots
+    /// that fail.
+    /// * Each root is first traced by a separate tracer, limited to one pass, to find the
+    ///   containers that it reaches and whether it fails. The results of this tracer are
+    ///   discarded.
+    /// * Roots that fail during planning come last. A root that fails is rolled back, so the
+    ///   enums that it explored before failing would otherwise be explored again by the next
+    ///   roots. Coming last, it finds them already explored.
+    /// * A root whose containers are all reached by another root comes before it, e.g. an
+    ///   enum before the structs holding it, so that `RootStatus::new_containers` attributes
+    ///   each container to the most specific root. Otherwise, the given order is kept.
+    ///
+    /// Exploring an enum takes the same number of passes whichever successful root reaches
+    /// it first, so the order of the roots that succeed does not change the number of passes.
+    pub fn plan<I>(&self, roots: I, samples: &Samples) -> TracePlan
+    where
+        I: IntoIterator<Item = TraceRoot>,
+    {
+        let mut config = self.config.clone().max_passes_per_trace(1);
+        config.probe_unknown_fields = false;
+        config.probe_fallback_variants = false;
+        #[cfg(feature = "spill")]
+        {
+            config.spill = None;
+        }
+        let mut remaining: Vec<_> = roots
+            .into_iter()
+            .map(|root| {
+                let mut scratch = Tracer::new(config.clone());
+                let failed = (root.trace)(&mut scratch, samples).is_err();
+                PlannedRoot {
+                    root,
+                    containers: scratch.registry.into_keys().collect(),
+                    failed,
+                }
+            })
+            .collect();
+        // Pick the first remaining root that does not depend on another remaining one.
+        let depends_on = |step: &PlannedRoot, other: &PlannedRoot| {
+            if step.failed != other.failed {
+                step.failed
+            } else {
+                other.containers.len() < step.containers.len()
+                    && other.containers.is_subset(&step.containers)
+            }
+        };
+        let mut steps = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let position = (0..remaining.len())
+                .find(|&i| {
+                    !remaining
+                        .iter()
+                        .any(|other| depends_on(&remaining[i], other))
+                })
+                .expect("the order of roots is acyclic");
+            steps.push(remaining.remove(position));
+        }
+        TracePlan { steps }
+    }
+
+    /// Trace the roots of a plan in order. As in `trace_all`, a root that fails is rolled
+    /// back and the next roots are traced. The registry is then obtained with `registry`.
+    pub fn execute(&mut self, plan: TracePlan, samples: &Samples) -> Vec<RootStatus> {
+        plan.steps
+            .into_iter()
+            .map(|step| {
+                let known: BTreeSet<_> = self.registry.keys().cloned().collect();
+                let error = self.trace_root(&step.root, samples).err();
+                let new_containers = self
+                    .registry
+                    .keys()
+                    .filter(|name| !known.contains(*name))
+                    .cloned()
+                    .collect();
+                RootStatus {
+                    name: step.root.name,
+                    new_containers,
+                    error,
+                }
+            })
+            .collect()
+    }
+
     /// Same as registry but always return a value, even if we detected issues.
     /// This should only be use for debugging.
-    pub fn registry_unchecked(self) -> Registry {
-        let mut registry = self.registry;
+    pub fn registry_unchecked(mut self) -> Registry {
+        let (mut registry, _) = self.take_registry();
         for format in registry.values_mut() {
             format.normalize().unwrap_or(());
         }
+        apply_declarations(&self.config, &self.enum_variant_names, &mut registry);
+        self.abstract_type_params(&mut registry);
         registry
     }
 
+    /// Turn the containers that mention type parameters into generic containers, including
+    /// the ones recorded for the root types with type parameters, and add them to the
+    /// normalized `registry`, where their instances are referred to with `Format::Apply`.
+    /// See `TypeParam`.
+    fn abstract_type_params(&mut self, registry: &mut Registry) -> Vec<Error> {
+        let mut errors = Vec::new();
+        let mut generics = split_generic_containers(registry);
+        for recorded in std::mem::take(&mut self.generic_registries).into_values() {
+            let mut recorded: Registry = recorded
+                .into_iter()
+                .filter_map(|(name, mut format)| match format.normalize() {
+                    Ok(()) => Some((name, format)),
+                    Err(_) => {
+                        errors.push(Error::UnknownFormatInContainer(name));
+                        None
+                    }
+                })
+                .collect();
+            errors.extend(apply_declarations(
+                &self.config,
+                &self.enum_variant_names,
+                &mut recorded,
+            ));
+            for (name, format) in split_generic_containers(&mut recorded) {
+                if let Err(error) = generics.entry(name).unify(format) {
+                    errors.push(error);
+                }
+            }
+            for (name, format) in recorded {
+                if let Err(error) = registry.entry(name).unify(format) {
+                    errors.push(error);
+                }
+            }
+        }
+        // Enums still being explored are reported as incomplete rather than as instances
+        // that do not match.
+        for name in self
+            .incomplete_enums
+            .keys()
+            .chain(self.interrupted_enums.keys())
+        {
+            if generics.contains_key(name) {
+                registry.remove(name);
+            }
+        }
+        errors.extend(instantiate_generics(registry, generics));
+        errors
+    }
+
+    /// Take the containers recorded so far, including the containers spilled because of
+    /// `TracerConfig::spill_registry`. Spilled containers that cannot be read or unified are
+    /// reported as errors and left out.
+    fn take_registry(&mut self) -> (Registry, Vec<Error>) {
+        #[allow(unused_mut)]
+        let mut registry = std::mem::take(&mut self.registry);
+        #[allow(unused_mut)]
+        let mut errors = Vec::new();
+        #[cfg(feature = "spill")]
+        if let Some(spilled) = &self.spilled {
+            for entry in spilled.iter() {
+                match entry {
+                    Ok((name, format)) => {
+                        if let Err(error) = registry.entry(name.to_string()).unify(format) {
+                            errors.push(error);
+                        }
+                    }
+                    Err(error) => errors.push(error),
+                }
+            }
+        }
+        (registry, errors)
+    }
+
+    /// Move the completely traced containers to the spilled registry if the tracer holds
+    /// more than the number of containers given to `TracerConfig::spill_registry`. Spilled
+    /// enums are then visited like the enums restored from a checkpoint.
+    #[cfg(feature = "spill")]
+    fn spill_complete_containers(&mut self) -> Result<()> {
+        let Some((path, max_containers)) = &self.config.spill else {
+            return Ok(());
+        };
+        if self.generic_root.is_some() {
+            return Ok(());
+        }
+        if self.registry.len() <= *max_containers {
+            return Ok(());
+        }
+        let names: Vec<_> = self
+            .registry
+            .keys()
+            .filter(|name| !self.is_incomplete_enum(name))
+            .cloned()
+            .collect();
+        let spilled = match &mut self.spilled {
+            Some(spilled) => spilled,
+            None => self.spilled.insert(SpilledRegistry::create(path)?),
+        };
+        for name in names {
+            let mut format = self.registry[&name].clone();
+            if format.normalize().is_err() {
+                continue;
+            }
+            spilled.insert(name.clone(), format)?;
+            self.registry.remove(&name);
+            if self.enum_type_ids.contains_key(name.as_str()) {
+                self.restored_enums.insert(name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finish tracing and move all the containers to the file given to
+    /// `TracerConfig::spill_registry`, so that the registry is never held in memory as a
+    /// whole. Errors are detected as in `registry`.
+    /// * Unlike `registry`, the declarations of the configuration, remote names, and the
+    ///   abstraction of type parameters are not applied, and the checks of
+    ///   `TracerConfig::forbid_format` and `TracerConfig::float_policy` are skipped, since
+    ///   they require the whole registry.
+    #[cfg(feature = "spill")]
+    pub fn spilled_registry(mut self) -> Result<SpilledRegistry> {
+        if self.is_poisoned() {
+            return Err(Error::TracerPoisoned);
+        }
+        let Some((path, _)) = &self.config.spill else {
+            return Err(Error::NotSupported(
+                "spilled_registry requires TracerConfig::spill_registry",
+            ));
+        };
+        let mut incomplete_enums = std::mem::take(&mut self.incomplete_enums);
+        incomplete_enums.append(&mut self.interrupted_enums);
+        if !incomplete_enums.is_empty() {
+            return Err(Error::MissingVariants(
+                incomplete_enums.into_keys().collect(),
+            ));
+        }
+        let mut spilled = match self.spilled.take() {
+            Some(spilled) => spilled,
+            None => SpilledRegistry::create(path)?,
+        };
+        for (name, mut format) in std::mem::take(&mut self.registry) {
+            // Containers traced again after being spilled may only be complete together
+            // with their spilled formats.
+            if let Some(previous) = spilled.get(&name)? {
+                format.unify(previous)?;
+            }
+            format
+                .normalize()
+                .map_err(|_| Error::UnknownFormatInContainer(name.clone()))?;
+            spilled.insert(name, format)?;
+        }
+        Ok(spilled)
+    }
+
+    /// Whether a previous tracing call was interrupted by a panic, e.g. in a user-provided
+    /// implementation of `Serialize` or `Deserialize`. The state of a poisoned tracer may be
+    /// inconsistent, therefore subsequent tracing calls and `registry` return
+    /// `Error::TracerPoisoned`.
+    pub fn is_poisoned(&self) -> bool {
+        self.pass_in_progress
+    }
+
+    /// Run an extra pass where the enum `name` is given an unknown variant index, to
+    /// detect whether it falls back to one of its variants.
+    fn probe_fallback_variant<F>(&mut self, name: &str, trace: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        if !self.config.probe_fallback_variants
+            || !matches!(self.registry.get(name), Some(ContainerFormat::Enum(_)))
+        {
+            return Ok(());
+        }
+        self.fallback_probe = Some(name.to_string());
+        self.fallback_probe_rejected = false;
+        let result = trace(self);
+        self.fallback_probe = None;
+        match result {
+            // Enums without a fallback variant are expected to reject the unknown index.
+            Err(_) if std::mem::take(&mut self.fallback_probe_rejected) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Run extra passes until every struct reached by `trace` has been probed for unknown
+    /// fields. Each probing pass stops at the first struct that was not probed yet.
+    fn probe_unknown_fields<F>(&mut self, mut trace: F)
+    where
+        F: FnMut(&mut Self) -> Result<()>,
+    {
+        if !self.config.probe_unknown_fields {
+            return;
+        }
+        loop {
+            let probed = self.unknown_fields.len();
+            self.unknown_fields_probe = true;
+            let result = trace(self);
+            self.unknown_fields_probe = false;
+            if result.is_ok() || self.unknown_fields.len() == probed {
+                return;
+            }
+        }
+    }
+
+    fn reached_max_passes(&self, passes: usize) -> bool {
+        self.config
+            .max_passes_per_trace
+            .is_some_and(|max| passes >= max)
+    }
+
+    /// Start a top-level tracing call. The clock is only read when profiling, since
+    /// `Instant::now` is not supported on some platforms such as `wasm32-unknown-unknown`.
+    fn start_pass(&mut self) -> Result<Option<Instant>> {
+        if self.pass_in_progress {
+            return Err(Error::TracerPoisoned);
+        }
+        self.pass_in_progress = true;
+        self.visited_containers.clear();
+        self.containers_in_progress.clear();
+        #[cfg(feature = "tracing")]
+        for span in self.container_spans.drain(..).rev() {
+            exit_span(&span);
+        }
+        self.field_path.clear();
+        self.ignored_depth = 0;
+        self.uninhabited_retry = false;
+        Ok(self.config.record_profile.then(Instant::now))
+    }
+
+    /// Flag the floating-point members of the containers traced so far, skipping the
+    /// containers whose formats are still incomplete.
+    fn flag_floats(&mut self) {
+        let complete: Registry = self
+            .registry
+            .iter()
+            .filter_map(|(name, format)| {
+                let mut format = format.clone();
+                format.normalize().ok()?;
+                Some((name.clone(), format))
+            })
+            .collect();
+        self.config.constraints.flag_floats(&complete);
+    }
+
+    /// Remember that the container `name` was reached by the current top-level tracing call.
+    pub(crate) fn mark_visited(&mut self, name: &'static str) {
+        if self.config.record_provenance || self.config.record_profile {
+            self.visited_containers.insert(name);
+        }
+    }
+
+    /// Record the provenance and the statistics of a successful top-level tracing call.
+    fn finish_pass(&mut self, format: &Format, type_name: &str, start: Option<Instant>) {
+        if self.config.float_policy == FloatPolicy::Flag {
+            self.flag_floats();
+        }
+        if !self.config.record_provenance && !self.config.record_profile {
+            return;
+        }
+        let root = match format {
+            Format::TypeName(name) => name.clone(),
+            _ => type_name.to_string(),
+        };
+        let elapsed = start.map(|start| start.elapsed()).unwrap_or_default();
+        if self.config.record_profile {
+            let profile = self.profile.roots.entry(root.clone()).or_default();
+            profile.passes += 1;
+            profile.time += elapsed;
+        }
+        for name in std::mem::take(&mut self.visited_containers) {
+            if self.config.record_provenance {
+                self.provenance
+                    .entry(name.to_string())
+                    .or_default()
+                    .insert(root.clone());
+            }
+            if self.config.record_profile {
+                let profile = self.profile.containers.entry(name.to_string()).or_default();
+                profile.passes += 1;
+                profile.time += elapsed;
+            }
+        }
+    }
+
+    pub(crate) fn enter_container(&mut self, name: &'static str) {
+        self.containers_in_progress
+            .push((name, self.field_path.len()));
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::debug_span!("container", name);
+            enter_span(&span);
+            self.container_spans.push(span);
+        }
+    }
+
+    pub(crate) fn exit_container(&mut self) {
+        self.containers_in_progress.pop();
+        #[cfg(feature = "tracing")]
+        if let Some(span) = self.container_spans.pop() {
+            exit_span(&span);
+        }
+    }
+
+    /// Start serializing the content of the container `name`, if it is ignored.
+    pub(crate) fn enter_ignored(&mut self, name: &str) {
+        if self.config.is_ignored_container(name) {
+            self.ignored_depth += 1;
+        }
+    }
+
+    /// Finish serializing the content of the container `name`, if it is ignored.
+    pub(crate) fn exit_ignored(&mut self, name: &str) {
+        if self.config.is_ignored_container(name) {
+            self.ignored_depth -= 1;
+        }
+    }
+
+    /// The innermost container being traced, if any, and the path from it to the current
+    /// location.
+    fn location(&self) -> (Option<String>, Vec<String>) {
+        match self.containers_in_progress.last() {
+            Some((name, depth)) => (Some(name.to_string()), self.field_path[*depth..].to_vec()),
+            None => (None, self.field_path.clone()),
+        }
+    }
+
+    /// Ask the fallback handler, if any, for the format to assume at the current location
+    /// since the tracing deserializer cannot answer `method`. Unknown formats are ignored.
+    pub(crate) fn fallback_format(&self, method: &'static str) -> Option<Format> {
+        let handler = self.config.fallback_handler.as_ref()?;
+        let (container, field_path) = match self.containers_in_progress.last() {
+            Some((name, depth)) => (Some(*name), &self.field_path[*depth..]),
+            None => (None, &self.field_path[..]),
+        };
+        let mut format = handler.0.fallback(&FallbackContext {
+            method,
+            container,
+            field_path,
+        })?;
+        format.reduce();
+        if format.is_unknown() {
+            return None;
+        }
+        Some(format)
+    }
+
+    /// Report a call to `deserialize_any` at the current location.
+    pub(crate) fn self_describing_error(&self) -> Error {
+        let (container, field_path) = self.location();
+        Error::RequiresSelfDescribingFormat {
+            container,
+            field_path,
+        }
+    }
+
+    /// Unify the format of a primitive value, requested by a `Deserialize` implementation at
+    /// the current location, into the `format` traced so far. Conflicting concrete formats
+    /// are widened if `TracerConfig::widen_formats` is enabled, and reported otherwise.
+    pub(crate) fn unify_primitive(&mut self, format: &mut Format, value: Format) -> Result<()> {
+        let (first, second) = match format.unify(value.clone()) {
+            Err(Error::Incompatible(first, second)) => (first, second),
+            result => return result,
+        };
+        if self.config.widen_formats {
+            let mut previous = format.clone();
+            previous.reduce();
+            if let Some(widened) = widen(&previous, &value) {
+                overwrite(format, widened);
+                self.widened_formats += 1;
+                return Ok(());
+            }
+        }
+        let (container, field_path) = self.location();
+        Err(Error::ConflictingFormats {
+            container,
+            field_path,
+            first,
+            second,
+        })
+    }
+
+    /// Fail if the Rust type `T`, about to be traced at the current location, is forbidden
+    /// by `TracerConfig::forbid_type`.
+    pub(crate) fn check_type<T: ?Sized>(&self) -> Result<()> {
+        match self.forbidden_type::<T>() {
+            Some(type_name) => {
+                let (container, field_path) = self.location();
+                Err(Error::ForbiddenType {
+                    type_name,
+                    container,
+                    field_path,
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Same as `check_type` for a member of the container `name`, given by its path.
+    pub(crate) fn check_member_type<T: ?Sized>(
+        &self,
+        name: &str,
+        field_path: &[&str],
+    ) -> Result<()> {
+        match self.forbidden_type::<T>() {
+            Some(type_name) => Err(Error::ForbiddenType {
+                type_name,
+                container: Some(name.to_string()),
+                field_path: field_path.iter().map(|label| label.to_string()).collect(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The entry of `TracerConfig::forbid_type` matching the Rust type `T` or one of its
+    /// type arguments, if any.
+    fn forbidden_type<T: ?Sized>(&self) -> Option<String> {
+        self.forbidden_name(std::any::type_name::<T>())
+    }
+
+    /// Same as `forbidden_type` for a type given by its name.
+    fn forbidden_name(&self, type_name: &str) -> Option<String> {
+        if self.config.forbidden_types.is_empty() || self.ignored_depth > 0 {
+            return None;
+        }
+        type_name
+            .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+            .find_map(|path| {
+                self.config.forbidden_types.iter().find(|forbidden| {
+                    path == forbidden.as_str()
+                        || path
+                            .strip_suffix(forbidden.as_str())
+                            .is_some_and(|prefix| prefix.ends_with("::"))
+                })
+            })
+            .cloned()
+    }
+
+    /// Unify the `format` traced for the container `name` into the registry. Conflicting
+    /// formats are united into a `Format::Union` if `TracerConfig::union_formats` is enabled,
+    /// widened if `TracerConfig::union_formats` or `TracerConfig::widen_formats` is, and
+    /// reported otherwise.
+    pub(crate) fn unify_container(&mut self, name: &str, format: ContainerFormat) -> Result<()> {
+        let previous = match self.registry.get(name) {
+            Some(previous) if self.config.union_formats || self.config.widen_formats => {
+                let mut previous = previous.clone();
+                previous.reduce();
+                Some((previous, format.clone()))
+            }
+            _ => None,
+        };
+        if let Err(error) = self.registry.entry(name.to_string()).unify(format) {
+            // Unions require `union_formats`, whereas widening is allowed by both options.
+            let united = previous.and_then(|(previous, mut copy)| {
+                copy.reduce();
+                unite_containers(&previous, &copy)
+                    .filter(|united| self.config.union_formats || !contains_union(united))
+            });
+            match (united, error) {
+                (Some(united), _) => {
+                    self.registry.insert(name.to_string(), united);
+                }
+                (None, Error::Incompatible(first, second)) => {
+                    return Err(Error::ConflictingFormats {
+                        container: Some(name.to_string()),
+                        field_path: Vec::new(),
+                        first,
+                        second,
+                    });
+                }
+                (None, error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the formats widened by `unify_primitive` while deserializing the container
+    /// `name`, traced as `format`. They are missing from the registry when it holds a copy
+    /// of the formats recorded by `trace_value` rather than the variables of `format`.
+    pub(crate) fn widen_container(&mut self, name: &str, mut format: ContainerFormat) {
+        let Some(previous) = self.registry.get(name) else {
+            return;
+        };
+        let mut previous = previous.clone();
+        previous.reduce();
+        format.reduce();
+        if previous == format {
+            return;
+        }
+        if let Some(widened) =
+            unite_containers(&previous, &format).filter(|widened| !contains_union(widened))
+        {
+            self.registry.insert(name.to_string(), widened);
+        }
+    }
+
     pub(crate) fn record_container(
         &mut self,
         samples: &mut Samples,
@@ -351,9 +3304,23 @@ impl Tracer {
         value: Value,
         record_value: bool,
     ) -> Result<(Format, Value)> {
-        self.registry.entry(name.to_string()).unify(format)?;
-        if record_value {
-            samples.values.insert(name, value.clone());
+        if self.ignored_depth > 0 {
+            return Ok((Format::TypeName(name.into()), value));
+        }
+        self.mark_visited(name);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(container = name, "container recorded");
+        if self.config.is_ignored_container(name) {
+            self.registry
+                .entry(name.to_string())
+                .unify(ContainerFormat::NewTypeStruct(Box::new(Format::Bytes)))?;
+            samples.values.insert(name.to_string(), value.clone());
+            return Ok((Format::TypeName(name.into()), value));
+        }
+        self.unify_container(name, format)?;
+        // Values with type parameters would not deserialize as instances of the container.
+        if record_value && self.generic_root.is_none() {
+            samples.values.insert(name.to_string(), value.clone());
         }
         Ok((Format::TypeName(name.into()), value))
     }
@@ -381,19 +3348,52 @@ impl Tracer {
     }
 
     pub(crate) fn get_sample<'de, 'a>(
-        &'a self,
+        &'a mut self,
         samples: &'de Samples,
         name: &'static str,
-    ) -> Option<(&'a ContainerFormat, &'de Value)> {
-        match samples.value(name) {
+    ) -> Result<Option<(Option<&'a ContainerFormat>, &'de Value)>> {
+        match self.sample_value(samples, name)? {
             Some(value) => {
-                let format = self
-                    .registry
-                    .get(name)
-                    .expect("recorded containers should have a format already");
-                Some((format, value))
+                if self.config.record_profile {
+                    let profile = self.profile.containers.entry(name.to_string()).or_default();
+                    profile.samples_used += 1;
+                }
+                self.record_event(|| TraceEvent::SampleConsulted {
+                    container: name.to_string(),
+                });
+                // The format is missing if the sample was recorded by another tracer.
+                Ok(Some((self.registry.get(name), value)))
             }
-            None => None,
+            None => Ok(None),
+        }
+    }
+
+    /// Obtain the sample for the container `name`, evaluating it first if it was registered
+    /// with `Samples::register_lazy`.
+    pub(crate) fn sample_value<'de>(
+        &mut self,
+        samples: &'de Samples,
+        name: &str,
+    ) -> Result<Option<&'de Value>> {
+        if let Some(value) = samples.values.get(name) {
+            return Ok(Some(value));
+        }
+        let Some(sample) = samples.lazy.get(name) else {
+            return Ok(None);
+        };
+        if let Some(value) = sample.value.get() {
+            return Ok(Some(value));
         }
+        let provider = sample
+            .provider
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        let Some(provider) = provider else {
+            return Ok(None);
+        };
+        // Nested samples cannot be added to `samples` while it is borrowed.
+        let (_, value) = provider(self, &mut Samples::new())?;
+        Ok(Some(sample.value.get_or_init(|| value)))
     }
 }
@@ -2,11 +2,20 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::error::{Error, Result};
-use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::{
+    de::{self, DeserializeSeed, IntoDeserializer, Visitor},
+    Deserialize, Serialize,
+};
 
 /// A structured Serde value.
 /// Meant to be easily recorded while tracing serialization and easily used while tracing deserialization.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Values can be (de)serialized in order to be persisted between tracing sessions. The
+/// representation is stable: variants are externally tagged with their upper-case names
+/// (e.g. `{"U32": 5}` in JSON) in self-describing formats, and identified by their positions
+/// in compact binary formats such as Bincode or BCS.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
 pub enum Value {
     Unit,
     Bool(bool),
@@ -33,6 +42,10 @@ pub enum Value {
     Option(Option<Box<Value>>),
     Variant(u32, Box<Value>),
     Seq(Vec<Value>),
+    /// The entries of a map, in serialization order. Maps are only recorded this way with
+    /// `TracerConfig::record_map_entries`. Otherwise, they are recorded as a `Seq`
+    /// alternating keys and values.
+    Map(Vec<(Value, Value)>),
 }
 
 /// Deserializer meant to reconstruct the Rust value behind a particular Serde value.
@@ -55,12 +68,28 @@ impl<'de> IntoDeserializer<'de, Error> for &'de Value {
 }
 
 impl Value {
+    #[cfg(feature = "trace")]
     pub(crate) fn seq_values(&self) -> Result<&Vec<Value>> {
         match self {
             Value::Seq(x) => Ok(x),
             _ => Err(Error::DeserializationError("seq_values")),
         }
     }
+
+    /// Whether the value contains a NaN or infinite float, which JSON cannot represent.
+    #[cfg(feature = "json")]
+    pub(crate) fn has_non_finite_float(&self) -> bool {
+        match self {
+            Value::F32(x) => !x.is_finite(),
+            Value::F64(x) => !x.is_finite(),
+            Value::Option(Some(x)) | Value::Variant(_, x) => x.has_non_finite_float(),
+            Value::Seq(x) => x.iter().any(Value::has_non_finite_float),
+            Value::Map(x) => x
+                .iter()
+                .any(|(k, v)| k.has_non_finite_float() || v.has_non_finite_float()),
+            _ => false,
+        }
+    }
 }
 
 macro_rules! declare_deserialize {
@@ -94,11 +123,37 @@ macro_rules! declare_deserialize_borrowed {
 impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     type Error = Error;
 
-    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    // Structs and tuples are visited as sequences, and variants by index.
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::NotSupported("deserialize_any"))
+        match self.value {
+            Value::Unit => visitor.visit_unit(),
+            Value::Bool(x) => visitor.visit_bool(*x),
+            Value::I8(x) => visitor.visit_i8(*x),
+            Value::I16(x) => visitor.visit_i16(*x),
+            Value::I32(x) => visitor.visit_i32(*x),
+            Value::I64(x) => visitor.visit_i64(*x),
+            Value::I128(x) => visitor.visit_i128(*x),
+            Value::U8(x) => visitor.visit_u8(*x),
+            Value::U16(x) => visitor.visit_u16(*x),
+            Value::U32(x) => visitor.visit_u32(*x),
+            Value::U64(x) => visitor.visit_u64(*x),
+            Value::U128(x) => visitor.visit_u128(*x),
+            Value::F32(x) => visitor.visit_f32(*x),
+            Value::F64(x) => visitor.visit_f64(*x),
+            Value::Char(x) => visitor.visit_char(*x),
+            Value::Str(x) => visitor.visit_borrowed_str(x),
+            Value::Bytes(x) => visitor.visit_borrowed_bytes(x),
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(x)) => visitor.visit_some(x.into_deserializer()),
+            Value::Variant(index, variant) => {
+                visitor.visit_enum(EnumDeserializer::new(*index, variant))
+            }
+            Value::Seq(x) => visitor.visit_seq(x.into_seq_deserializer()),
+            Value::Map(x) => visitor.visit_map(MapDeserializer::new(x.iter())),
+        }
     }
 
     declare_deserialize!(deserialize_bool, Bool, visit_bool, "bool");
@@ -168,6 +223,10 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     {
         match self.value {
             Value::Seq(x) => visitor.visit_seq(x.into_seq_deserializer()),
+            // Sequences of `u8` traced with `TracerConfig::u8_seqs_as_bytes`.
+            Value::Bytes(x) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(x.iter().copied()))
+            }
             _ => Err(Error::DeserializationError("seq")),
         }
     }
@@ -203,6 +262,7 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     {
         match self.value {
             Value::Seq(x) => visitor.visit_seq(x.into_seq_deserializer()),
+            Value::Map(x) => visitor.visit_map(MapDeserializer::new(x.iter())),
             _ => Err(Error::DeserializationError("map")),
         }
     }
@@ -336,6 +396,51 @@ where
     }
 }
 
+struct MapDeserializer<'de> {
+    entries: std::slice::Iter<'de, (Value, Value)>,
+    value: Option<&'de Value>,
+}
+
+impl<'de> MapDeserializer<'de> {
+    fn new(entries: std::slice::Iter<'de, (Value, Value)>) -> Self {
+        Self {
+            entries,
+            value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(x) => seed.deserialize(x.into_deserializer()),
+            None => Err(Error::DeserializationError("value in map")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
 struct EnumDeserializer<'de> {
     index: u32,
     value: &'de Value,
@@ -0,0 +1,118 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Verification of registries against real encodings.
+//!
+//! Hand-written implementations of `Serialize` may diverge from what the tracer records
+//! while tracing deserialization. The function [`round_trip`] detects such divergences by
+//! encoding sampled values with a real encoding and decoding the bytes dynamically
+//! according to a registry.
+//!
+//! ```rust
+//! use bincode::Options;
+//! use serde::de::DeserializeSeed;
+//! use serde::{Deserialize, Serialize};
+//! use serde_reflection::{verify::{self, Encoding}, Samples, Tracer, TracerConfig};
+//!
+//! struct Bincode;
+//!
+//! impl Encoding for Bincode {
+//!     fn encode<T: ?Sized + Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+//!         bincode::DefaultOptions::new().serialize(value).map_err(|e| e.to_string())
+//!     }
+//!
+//!     fn decode<'de, S: DeserializeSeed<'de>>(
+//!         &self,
+//!         bytes: &'de [u8],
+//!         seed: S,
+//!     ) -> Result<S::Value, String> {
+//!         bincode::DefaultOptions::new()
+//!             .deserialize_seed(seed, bytes)
+//!             .map_err(|e| e.to_string())
+//!     }
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! enum Shape {
+//!     Circle(u32),
+//!     Square { side: u64 },
+//! }
+//!
+//! # fn main() -> serde_reflection::Result<()> {
+//! let mut tracer = Tracer::new(TracerConfig::default());
+//! tracer.trace_simple_type::<Shape>()?;
+//! let registry = tracer.registry()?;
+//!
+//! // Every variant of `Shape` was checked.
+//! assert_eq!(verify::round_trip::<Shape, _>(&Bincode, &registry, &Samples::new())?, 2);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    error::{Error, Result},
+    json_converter::{DeserializationContext, EmptyEnvironment, SerializationContext},
+    Registry, Samples, Tracer, TracerConfig,
+};
+use serde::{de::DeserializeSeed, Deserialize, Serialize};
+
+/// A binary encoding, such as Bincode or BCS, used to check a registry.
+pub trait Encoding {
+    /// Serialize a value into bytes.
+    fn encode<T: ?Sized + Serialize>(&self, value: &T) -> std::result::Result<Vec<u8>, String>;
+
+    /// Deserialize a value from bytes using a seed. All the bytes must be consumed.
+    fn decode<'de, S: DeserializeSeed<'de>>(
+        &self,
+        bytes: &'de [u8],
+        seed: S,
+    ) -> std::result::Result<S::Value, String>;
+}
+
+/// Check that the values of `T` are encoded by `encoding` as described by `registry`.
+/// * Values are obtained by tracing the deserialization of `T` with a new tracer, using
+///   `samples`. If `T` is an enum, every variant is covered.
+/// * Each value is encoded with `encoding`, decoded according to `registry`, then encoded
+///   again according to `registry`. The two encodings must agree.
+///
+/// Return the number of values that were checked.
+pub fn round_trip<'de, T, E>(
+    encoding: &E,
+    registry: &Registry,
+    samples: &'de Samples,
+) -> Result<usize>
+where
+    T: Serialize + Deserialize<'de>,
+    E: Encoding,
+{
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let (format, values) = tracer.trace_type::<T>(samples)?;
+    for (index, value) in values.iter().enumerate() {
+        let mismatch = |msg: String| {
+            Error::RoundTripMismatch(format!(
+                "{} (value #{index}): {msg}",
+                std::any::type_name::<T>()
+            ))
+        };
+        let bytes = encoding.encode(value).map_err(mismatch)?;
+        let context = DeserializationContext {
+            format: format.clone(),
+            registry,
+            environment: &EmptyEnvironment,
+        };
+        let decoded = encoding.decode(&bytes, context).map_err(mismatch)?;
+        let context = SerializationContext {
+            value: &decoded,
+            format: &format,
+            registry,
+            environment: &EmptyEnvironment,
+        };
+        let reencoded = encoding.encode(&context).map_err(mismatch)?;
+        if reencoded != bytes {
+            return Err(mismatch(format!(
+                "bytes {bytes:?} were decoded as {decoded} and encoded again as {reencoded:?}"
+            )));
+        }
+    }
+    Ok(values.len())
+}
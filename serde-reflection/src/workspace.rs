@@ -0,0 +1,281 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Several registries managed together.
+//!
+//! A [`Workspace`] holds the registries traced separately by the services or crates of a
+//! repository, together with a registry of common containers that every member may refer to.
+//! Workspaces can be saved as a single manifest in YAML or JSON.
+//!
+//! ```rust
+//! use serde_reflection::{ContainerFormat, Format, Registry};
+//! use serde_reflection::workspace::{Workspace, WorkspaceIssue};
+//!
+//! let header = ContainerFormat::NewTypeStruct(Box::new(Format::U64));
+//! let mut client = Registry::new();
+//! client.insert("Header".into(), header.clone());
+//! let header_ref = Format::TypeName("Header".into());
+//! client.insert("Request".into(), ContainerFormat::NewTypeStruct(Box::new(header_ref)));
+//! let mut server = Registry::new();
+//! server.insert("Header".into(), header);
+//! let request_ref = Format::TypeName("Request".into());
+//! server.insert("Reply".into(), ContainerFormat::NewTypeStruct(Box::new(request_ref)));
+//!
+//! let mut workspace = Workspace::new();
+//! workspace.insert("client", client);
+//! workspace.insert("server", server);
+//! assert_eq!(workspace.hoist_common(), vec!["Header"]);
+//! assert!(workspace.common().contains_key("Header"));
+//!
+//! // `Reply` refers to a container defined by another member.
+//! assert_eq!(
+//!     workspace.check(),
+//!     vec![WorkspaceIssue::ForeignReference {
+//!         registry: "server".into(),
+//!         container: "Reply".into(),
+//!         name: "Request".into(),
+//!         defined_in: vec!["client".into()],
+//!     }]
+//! );
+//! assert_eq!(workspace.combined().len(), 3);
+//! ```
+
+use crate::{export::Exporters, registry, ContainerFormat, Registry, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, io::Write};
+
+/// Named registries sharing a registry of common containers. See the
+/// [module documentation](self).
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Workspace {
+    /// Containers available to every member.
+    common: Registry,
+    /// The members, indexed by name.
+    registries: BTreeMap<String, Registry>,
+}
+
+/// A problem found by [`Workspace::check`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum WorkspaceIssue {
+    /// A container of the member `registry` refers to a container that is defined neither
+    /// in the member nor in the common registry, but only in other members.
+    ForeignReference {
+        registry: String,
+        container: String,
+        name: String,
+        defined_in: Vec<String>,
+    },
+    /// A container of the member `registry` refers to a container that is not defined
+    /// anywhere in the workspace.
+    DanglingReference {
+        registry: String,
+        container: String,
+        name: String,
+    },
+    /// The container `name` has different definitions in the given members, and possibly
+    /// in the common registry.
+    ConflictingDefinitions {
+        name: String,
+        registries: Vec<String>,
+        in_common: bool,
+    },
+}
+
+impl fmt::Display for WorkspaceIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use WorkspaceIssue::*;
+        match self {
+            ForeignReference {
+                registry,
+                container,
+                name,
+                defined_in,
+            } => write!(
+                f,
+                "{registry}: {container} refers to {name}, which is only defined in {}",
+                defined_in.join(", ")
+            ),
+            DanglingReference {
+                registry,
+                container,
+                name,
+            } => write!(
+                f,
+                "{registry}: {container} refers to missing container {name}"
+            ),
+            ConflictingDefinitions {
+                name,
+                registries,
+                in_common,
+            } => {
+                write!(
+                    f,
+                    "{name}: conflicting definitions in {}",
+                    registries.join(", ")
+                )?;
+                if *in_common {
+                    write!(f, " and in the common registry")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Workspace {
+    /// Create an empty workspace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the member `name`. Return the previous registry of the member, if any.
+    pub fn insert(&mut self, name: &str, registry: Registry) -> Option<Registry> {
+        self.registries.insert(name.to_string(), registry)
+    }
+
+    /// Remove the member `name`.
+    pub fn remove(&mut self, name: &str) -> Option<Registry> {
+        self.registries.remove(name)
+    }
+
+    /// The registry of the member `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&Registry> {
+        self.registries.get(name)
+    }
+
+    /// Iterate over the members, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Registry)> {
+        self.registries
+            .iter()
+            .map(|(name, registry)| (name.as_str(), registry))
+    }
+
+    /// The common containers.
+    pub fn common(&self) -> &Registry {
+        &self.common
+    }
+
+    /// The common containers, for modification.
+    pub fn common_mut(&mut self) -> &mut Registry {
+        &mut self.common
+    }
+
+    /// Move to the common registry the containers that have the same definition in at
+    /// least two members, or in one member and in the common registry, and that have no
+    /// conflicting definition. Return the names of these containers.
+    pub fn hoist_common(&mut self) -> Vec<String> {
+        let mut hoisted = Vec::new();
+        for (name, definitions) in self.definitions() {
+            let [(format, members)] = &definitions[..] else {
+                continue;
+            };
+            let common = self.common.get(name);
+            if common.is_some_and(|common| common != *format)
+                || members.len() + usize::from(common.is_some()) < 2
+            {
+                continue;
+            }
+            hoisted.push(name.to_string());
+        }
+        for name in &hoisted {
+            for registry in self.registries.values_mut() {
+                if let Some(format) = registry.remove(name) {
+                    self.common.insert(name.clone(), format);
+                }
+            }
+        }
+        hoisted
+    }
+
+    /// Check that the containers of each member only refer to containers of the member or
+    /// of the common registry, and that containers are not defined differently in several
+    /// places. Issues are returned in a deterministic order.
+    pub fn check(&self) -> Vec<WorkspaceIssue> {
+        let mut issues = Vec::new();
+        for (name, definitions) in self.definitions() {
+            let common = self.common.get(name);
+            let differs =
+                definitions.len() > 1 || common.is_some_and(|common| common != definitions[0].0);
+            if differs {
+                let mut registries: Vec<String> = definitions
+                    .into_iter()
+                    .flat_map(|(_, members)| members)
+                    .map(String::from)
+                    .collect();
+                registries.sort();
+                issues.push(WorkspaceIssue::ConflictingDefinitions {
+                    name: name.to_string(),
+                    registries,
+                    in_common: common.is_some(),
+                });
+            }
+        }
+        for (member, registry) in &self.registries {
+            for (container, format) in registry {
+                let mut names = registry::references(format);
+                names.sort();
+                names.dedup();
+                for name in names {
+                    if registry.contains_key(name) || self.common.contains_key(name) {
+                        continue;
+                    }
+                    let defined_in: Vec<String> = self
+                        .registries
+                        .iter()
+                        .filter(|(_, other)| other.contains_key(name))
+                        .map(|(other, _)| other.clone())
+                        .collect();
+                    issues.push(if defined_in.is_empty() {
+                        WorkspaceIssue::DanglingReference {
+                            registry: member.clone(),
+                            container: container.clone(),
+                            name: name.to_string(),
+                        }
+                    } else {
+                        WorkspaceIssue::ForeignReference {
+                            registry: member.clone(),
+                            container: container.clone(),
+                            name: name.to_string(),
+                            defined_in,
+                        }
+                    });
+                }
+            }
+        }
+        issues
+    }
+
+    /// Combine the common registry and the members into a single registry, with
+    /// [`registry::merge`]. Containers with conflicting definitions keep the first one, in
+    /// the common registry first, then in the members by name. See `check`.
+    pub fn combined(&self) -> Registry {
+        let mut combined = self.common.clone();
+        for registry in self.registries.values() {
+            registry::merge(&mut combined, registry.clone());
+        }
+        combined
+    }
+
+    /// Write the combined registry into `out` with the exporter registered under `name`.
+    pub fn export(&self, exporters: &Exporters, name: &str, out: &mut dyn Write) -> Result<()> {
+        exporters.export(name, &self.combined(), out)
+    }
+
+    /// The distinct definitions of each container in the members, with the names of the
+    /// members using them.
+    #[allow(clippy::type_complexity)]
+    fn definitions(&self) -> BTreeMap<&str, Vec<(&ContainerFormat, Vec<&str>)>> {
+        let mut definitions: BTreeMap<&str, Vec<(&ContainerFormat, Vec<&str>)>> = BTreeMap::new();
+        for (member, registry) in &self.registries {
+            for (name, format) in registry {
+                let entry = definitions.entry(name).or_default();
+                match entry.iter_mut().find(|(other, _)| *other == format) {
+                    Some((_, members)) => members.push(member),
+                    None => entry.push((format, vec![member])),
+                }
+            }
+        }
+        definitions
+    }
+}
@@ -0,0 +1,339 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![cfg(feature = "trace")]
+
+use serde::{Deserialize, Serialize};
+use serde_reflection::{
+    analysis::{
+        format_size_bounds, json_safety, registry_stats, size_bounds, size_bounds_with_tags,
+        uninhabited_containers, JsonHazard, JsonSafetyIssue, MaxSize, RegistryStats, SizeBounds,
+    },
+    compat::{EncodingProfile, PrefixEncoding, TagEncoding},
+    ContainerFormat, Error, Format, Named, Registry, Tracer, TracerConfig,
+};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize)]
+enum Command {
+    Stop,
+    Move { x: i16, y: i16 },
+    Set(Option<u64>, bool),
+    Hash([u8; 4]),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    id: u32,
+    command: Command,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Tree {
+    Leaf(u8),
+    Node(Box<Tree>, Box<Tree>),
+}
+
+fn trace_registry() -> Registry {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Command>().unwrap();
+    tracer.trace_simple_type::<Header>().unwrap();
+    tracer.trace_simple_type::<Tree>().unwrap();
+    tracer.registry().unwrap()
+}
+
+#[test]
+fn test_size_bounds_match_encodings() {
+    let registry = trace_registry();
+    let smallest = Header {
+        id: 0,
+        command: Command::Stop,
+    };
+    let largest = Header {
+        id: u32::MAX,
+        command: Command::Set(Some(u64::MAX), true),
+    };
+
+    let bounds = size_bounds(&registry, "Header", EncodingProfile::Bcs).unwrap();
+    assert_eq!(bounds.min, bcs::to_bytes(&smallest).unwrap().len());
+    assert_eq!(
+        bounds.max_size(),
+        Some(bcs::to_bytes(&largest).unwrap().len())
+    );
+    assert_eq!(
+        bounds,
+        SizeBounds {
+            min: 5,
+            max: MaxSize::Bounded(15)
+        }
+    );
+
+    let bounds = size_bounds(&registry, "Header", EncodingProfile::Bincode1).unwrap();
+    assert_eq!(bounds.min, bincode::serialize(&smallest).unwrap().len());
+    assert_eq!(
+        bounds.max_size(),
+        Some(bincode::serialize(&largest).unwrap().len())
+    );
+
+    // Postcard encodes integers as varints.
+    let bounds = size_bounds(&registry, "Header", EncodingProfile::Postcard).unwrap();
+    assert_eq!(
+        bounds,
+        SizeBounds {
+            min: 2,
+            max: MaxSize::Bounded(18)
+        }
+    );
+    assert_eq!(bounds.fixed_size(), None);
+}
+
+#[test]
+fn test_size_bounds_with_tags() {
+    let registry = trace_registry();
+    // Variants written by name: "Stop" is the shortest name and "Set" the largest variant.
+    let tags = TagEncoding::Name(PrefixEncoding::Uleb128);
+    let bounds = size_bounds_with_tags(&registry, "Header", EncodingProfile::Bcs, tags).unwrap();
+    assert_eq!(
+        bounds,
+        SizeBounds {
+            min: 9,
+            max: MaxSize::Bounded(18)
+        }
+    );
+
+    let tags = TagEncoding::Index(PrefixEncoding::U32);
+    let bounds = size_bounds_with_tags(&registry, "Command", EncodingProfile::Bcs, tags).unwrap();
+    assert_eq!(bounds.min, 4);
+}
+
+#[test]
+fn test_size_bounds_unbounded() {
+    let registry = trace_registry();
+    let bounds = size_bounds(&registry, "Tree", EncodingProfile::Bcs).unwrap();
+    assert_eq!(bounds.min, 2);
+    assert_eq!(bounds.max, MaxSize::Recursive("Tree".into()));
+
+    let format = Format::Seq(Box::new(Format::TypeName("Header".into())));
+    let bounds = format_size_bounds(&registry, &format, EncodingProfile::Bcs).unwrap();
+    assert_eq!(
+        bounds,
+        SizeBounds {
+            min: 1,
+            max: MaxSize::UnboundedSequence
+        }
+    );
+    let bounds = format_size_bounds(&registry, &format, EncodingProfile::Bincode1).unwrap();
+    assert_eq!(bounds.min, 8);
+
+    let bounds = format_size_bounds(&registry, &Format::FixedBytes(32), EncodingProfile::Bcs);
+    assert_eq!(bounds.unwrap().fixed_size(), Some(33));
+}
+
+#[test]
+fn test_size_bounds_errors() {
+    let mut registry = trace_registry();
+    assert_eq!(
+        size_bounds(&registry, "Missing", EncodingProfile::Bcs),
+        Err(Error::MissingContainer("Missing".into()))
+    );
+    registry.insert(
+        "Float".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::F64)),
+    );
+    assert!(matches!(
+        size_bounds(&registry, "Float", EncodingProfile::Bcs),
+        Err(Error::NotSupported(_))
+    ));
+    let bounds = size_bounds(&registry, "Float", EncodingProfile::Postcard).unwrap();
+    assert_eq!(bounds.fixed_size(), Some(8));
+}
+
+#[test]
+fn test_registry_stats() {
+    #[derive(Serialize, Deserialize)]
+    struct Unit;
+
+    #[derive(Serialize, Deserialize)]
+    struct Envelope(Header, Unit);
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Command>().unwrap();
+    tracer.trace_simple_type::<Envelope>().unwrap();
+    tracer.trace_simple_type::<Tree>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    let stats = registry_stats(&registry);
+    assert_eq!(
+        stats,
+        RegistryStats {
+            containers: 5,
+            unit_structs: 1,
+            newtype_structs: 0,
+            tuple_structs: 1,
+            structs: 1,
+            enums: 2,
+            generics: 0,
+            // Envelope: 2, Header: 2, Command: 0 + 2 + 2 + 1, Tree: 1 + 2.
+            fields: 12,
+            variants: 6,
+            // Envelope > Header > Command.
+            max_depth: 3,
+            recursive: vec!["Tree".into()],
+            widest_enum: Some(Named {
+                name: "Command".into(),
+                value: 4,
+            }),
+            widest_struct: Some(Named {
+                name: "Envelope".into(),
+                value: 2,
+            }),
+            most_referenced: vec![
+                Named {
+                    name: "Command".into(),
+                    value: 1,
+                },
+                Named {
+                    name: "Header".into(),
+                    value: 1,
+                },
+                Named {
+                    name: "Unit".into(),
+                    value: 1,
+                },
+            ],
+        }
+    );
+    let json = serde_json::to_value(&stats).unwrap();
+    assert_eq!(json["max_depth"], 3);
+    assert_eq!(json["widest_enum"], serde_json::json!({ "Command": 4 }));
+
+    assert_eq!(registry_stats(&Registry::new()), RegistryStats::default());
+}
+
+#[test]
+fn test_json_safety() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+    enum Color {
+        Red,
+        Green,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+    struct Id(u64);
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Scene {
+        by_color: BTreeMap<Color, u8>,
+        by_flag: BTreeMap<bool, u8>,
+        by_id: BTreeMap<Id, String>,
+        by_point: BTreeMap<Point, Vec<Color>>,
+        by_pair: Option<BTreeMap<(u8, u8), u128>>,
+        total: i128,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Scene>().unwrap();
+    tracer.trace_simple_type::<Color>().unwrap();
+    let mut registry = tracer.registry().unwrap();
+    registry.insert(
+        "Weights".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Map {
+            key: Box::new(Format::F64),
+            value: Box::new(Format::U8),
+        })),
+    );
+    registry.insert(
+        "Blobs".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Map {
+            key: Box::new(Format::Bytes),
+            value: Box::new(Format::Str),
+        })),
+    );
+
+    let issues = json_safety(&registry);
+    let path = |path: &[&str]| path.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        issues,
+        vec![
+            JsonSafetyIssue {
+                path: path(&["Blobs", "key"]),
+                hazard: JsonHazard::BytesKey(Format::Bytes),
+            },
+            JsonSafetyIssue {
+                path: path(&["Scene", "by_point", "key"]),
+                hazard: JsonHazard::NonStringKey(Format::TypeName("Point".into())),
+            },
+            JsonSafetyIssue {
+                path: path(&["Scene", "by_pair", "key"]),
+                hazard: JsonHazard::NonStringKey(Format::TupleArray {
+                    content: Box::new(Format::U8),
+                    size: 2,
+                }),
+            },
+            JsonSafetyIssue {
+                path: path(&["Scene", "by_pair", "value"]),
+                hazard: JsonHazard::Integer128(Format::U128),
+            },
+            JsonSafetyIssue {
+                path: path(&["Scene", "total"]),
+                hazard: JsonHazard::Integer128(Format::I128),
+            },
+            JsonSafetyIssue {
+                path: path(&["Weights", "key"]),
+                hazard: JsonHazard::NonStringKey(Format::F64),
+            },
+        ]
+    );
+    assert_eq!(
+        issues[1].to_string(),
+        "Scene.by_point.key: map key TypeName(\"Point\") is not a string"
+    );
+}
+
+#[test]
+fn test_uninhabited_containers() {
+    #[derive(Serialize, Deserialize)]
+    enum Void {}
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper {
+        id: u32,
+        void: Void,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Choice {
+        A(Wrapper),
+        B(Option<Wrapper>),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum List {
+        Empty,
+        Node(u32, Box<List>),
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Choice>().unwrap();
+    tracer.trace_simple_type::<List>().unwrap();
+    let mut registry = tracer.registry().unwrap();
+    assert_eq!(
+        uninhabited_containers(&registry)
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec!["Void".to_string(), "Wrapper".to_string()]
+    );
+
+    // Recursive containers without a base case are uninhabited.
+    registry.insert(
+        "Loop".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::TypeName("Loop".into()))),
+    );
+    assert!(uninhabited_containers(&registry).contains("Loop"));
+}
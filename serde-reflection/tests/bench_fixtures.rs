@@ -0,0 +1,32 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Integration tests for the bench_fixtures module
+#![cfg(feature = "bench-fixtures")]
+
+use serde_reflection::{
+    bench_fixtures::{large_registry, workloads},
+    registry::{reachable, validate},
+};
+
+#[test]
+fn test_workloads_complete() {
+    let workloads = workloads();
+    let names: Vec<_> = workloads.iter().map(|workload| workload.name).collect();
+    let mut sorted = names.clone();
+    sorted.sort_unstable();
+    assert_eq!(names, sorted);
+
+    for workload in workloads {
+        let registry = workload.run().unwrap();
+        assert!(validate(&registry).is_empty(), "{}", workload.name);
+    }
+}
+
+#[test]
+fn test_large_registry() {
+    let registry = large_registry(100);
+    assert_eq!(registry.len(), 100);
+    assert!(validate(&registry).is_empty());
+    assert_eq!(reachable(&registry, ["C0"]), registry);
+}
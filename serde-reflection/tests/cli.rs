@@ -0,0 +1,107 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![cfg(feature = "cli")]
+
+use serde_reflection::{registry::VersionedRegistry, ContainerFormat, Format, Named, Registry};
+use std::{path::Path, process::Command};
+
+fn write_registry(path: &Path, a: Format) {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Foo".into(),
+        ContainerFormat::Struct(vec![Named {
+            name: "a".into(),
+            value: a,
+        }]),
+    );
+    registry.insert(
+        "Bar".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::TypeName("Foo".into()))),
+    );
+    registry.insert("Baz".into(), ContainerFormat::UnitStruct);
+    std::fs::write(path, serde_yaml::to_string(&registry).unwrap()).unwrap();
+}
+
+fn run(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_serde-reflection"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn test_cli() {
+    let dir = std::env::temp_dir().join(format!("serde-reflection-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let old = dir.join("old.yaml");
+    let new = dir.join("new.yaml");
+    write_registry(&old, Format::U32);
+    write_registry(&new, Format::U64);
+    let old = old.to_str().unwrap();
+    let new = new.to_str().unwrap();
+
+    let (success, output) = run(&["diff", old, new]);
+    assert!(!success);
+    assert_eq!(output, "Foo::a: format changed from U32 to U64\n");
+    let (success, _) = run(&["diff", "--encoding", "json", old, new]);
+    assert!(success);
+
+    let (success, output) = run(&["--format", "json", "filter", old, "--root", "Bar"]);
+    assert!(success);
+    let filtered: Registry = serde_json::from_str(&output).unwrap();
+    assert_eq!(filtered.keys().collect::<Vec<_>>(), vec!["Bar", "Foo"]);
+    let (success, output) = run(&["--format", "json", "filter", old, "--root", "bar"]);
+    assert!(success);
+    let filtered: Registry = serde_json::from_str(&output).unwrap();
+    assert_eq!(filtered.len(), 2);
+    let (success, _) = run(&["filter", old, "--root", "Missing"]);
+    assert!(!success);
+
+    let (success, output) = run(&["merge", old, new]);
+    assert!(!success);
+    let merged: Registry = serde_yaml::from_str(&output).unwrap();
+    assert_eq!(merged.len(), 3);
+
+    let (success, output) = run(&["validate", old]);
+    assert!(success);
+    assert!(output.is_empty());
+
+    let (success, output) = run(&["hash", old]);
+    assert!(success);
+    assert_eq!(output.lines().count(), 3);
+
+    let exported = dir.join("old.json");
+    let (success, _) = run(&[
+        "--format",
+        "json",
+        "--output",
+        exported.to_str().unwrap(),
+        "export",
+        old,
+    ]);
+    assert!(success);
+    let (_, output) = run(&["diff", old, exported.to_str().unwrap()]);
+    assert!(output.is_empty());
+
+    // Registries tagged with a version of the registry format are accepted too.
+    let versioned = dir.join("versioned.yaml");
+    let registry: Registry = serde_yaml::from_str(&std::fs::read_to_string(old).unwrap()).unwrap();
+    let content = serde_yaml::to_string(&VersionedRegistry::new(registry)).unwrap();
+    std::fs::write(&versioned, content).unwrap();
+    let (success, output) = run(&["diff", old, versioned.to_str().unwrap()]);
+    assert!(success);
+    assert!(output.is_empty());
+
+    let (success, output) = run(&["exporters"]);
+    assert!(success);
+    assert_eq!(output, "docs\njson\nyaml\n");
+    let (success, _) = run(&["export", "--exporter", "unknown", old]);
+    assert!(!success);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
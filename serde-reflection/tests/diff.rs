@@ -0,0 +1,839 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![cfg(feature = "trace")]
+
+use serde::{Deserialize, Serialize};
+use serde_reflection::{
+    compat::{CompatRules, Compatibility, NumericType},
+    diff::{Change, ChangeKind, RegistryDiff},
+    Conditions, ContainerFormat, Deprecations, Format, Named, Registry, Tracer, TracerConfig,
+    VariantFormat,
+};
+
+fn trace<T>() -> Registry
+where
+    T: for<'de> Deserialize<'de>,
+{
+    trace_with(|tracer| {
+        tracer.trace_simple_type::<T>().unwrap();
+    })
+}
+
+fn trace_with(f: impl FnOnce(&mut Tracer)) -> Registry {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    f(&mut tracer);
+    tracer.registry().unwrap()
+}
+
+fn registry_v1() -> Registry {
+    trace_with(|tracer| {
+        tracer.trace_simple_type::<v1::Header>().unwrap();
+        tracer.trace_simple_type::<v1::Payload>().unwrap();
+        tracer.trace_simple_type::<v1::Wrapper>().unwrap();
+    })
+}
+
+fn registry_v2() -> Registry {
+    trace_with(|tracer| {
+        tracer.trace_simple_type::<v2::Header>().unwrap();
+        tracer.trace_simple_type::<v2::Payload>().unwrap();
+        tracer.trace_simple_type::<v2::Wrapper>().unwrap();
+        tracer.trace_simple_type::<v2::Added>().unwrap();
+    })
+}
+
+mod v1 {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Header {
+        pub version: u32,
+        pub legacy: bool,
+        pub payload: Payload,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum Payload {
+        Empty,
+        Data(Vec<u8>),
+        Old { x: u16 },
+        Pair(u8, u8),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Wrapper(pub u8);
+}
+
+mod v2 {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Header {
+        pub version: u32,
+        pub payload: Payload,
+        pub extra: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum Payload {
+        Nothing,
+        Data(Vec<u8>),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Wrapper(pub u16);
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Added;
+}
+
+fn change(path: &[&str], kind: ChangeKind, deprecated: bool) -> Change {
+    Change {
+        path: path.iter().map(|s| s.to_string()).collect(),
+        kind,
+        deprecated,
+        unknown_field_ignored: false,
+    }
+}
+
+#[test]
+fn test_registry_diff() {
+    let old = registry_v1();
+    let new = registry_v2();
+
+    assert!(RegistryDiff::new(&old, &old).is_empty());
+
+    let diff = RegistryDiff::new(&old, &new);
+    assert_eq!(
+        diff.changes,
+        vec![
+            change(&["Added"], ChangeKind::ContainerAdded, false),
+            change(
+                &["Header", "legacy"],
+                ChangeKind::FieldRemoved {
+                    index: 1,
+                    format: Format::Bool
+                },
+                false
+            ),
+            change(
+                &["Header", "extra"],
+                ChangeKind::FieldAdded {
+                    index: 2,
+                    format: Format::Str
+                },
+                false
+            ),
+            change(
+                &["Payload", "Nothing"],
+                ChangeKind::VariantRenamed {
+                    index: 0,
+                    old_name: "Empty".into()
+                },
+                false
+            ),
+            change(
+                &["Payload", "Old"],
+                ChangeKind::VariantRemoved { index: 2 },
+                false
+            ),
+            change(
+                &["Payload", "Pair"],
+                ChangeKind::VariantRemoved { index: 3 },
+                false
+            ),
+            change(
+                &["Wrapper", "0"],
+                ChangeKind::FieldChanged {
+                    old: Format::U8,
+                    new: Format::U16
+                },
+                false
+            ),
+        ]
+    );
+    assert!(diff.is_breaking());
+    assert_eq!(diff.breaking_changes().count(), 5);
+
+    // Reverse direction.
+    let diff = RegistryDiff::new(&new, &old);
+    assert!(diff
+        .changes
+        .contains(&change(&["Added"], ChangeKind::ContainerRemoved, false)));
+    assert!(diff.changes.contains(&change(
+        &["Payload", "Pair"],
+        ChangeKind::VariantAdded { index: 3 },
+        false
+    )));
+}
+
+#[test]
+fn test_registry_diff_with_deprecations() {
+    let old = registry_v1();
+    let mut new = registry_v2();
+    // Ignore the other changes for this test.
+    new.insert("Wrapper".into(), old["Wrapper"].clone());
+    new.remove("Added");
+    if let Some(ContainerFormat::Struct(fields)) = new.get_mut("Header") {
+        fields.pop();
+    }
+
+    let mut deprecations = Deprecations::new();
+    deprecations.deprecate_field("Header", "legacy");
+    deprecations.deprecate_variant("Payload", "Old");
+    deprecations.deprecate_variant("Payload", "Pair");
+    assert!(deprecations.is_deprecated(&["Payload", "Old"]));
+    assert!(!deprecations.is_deprecated(&["Payload", "Data"]));
+
+    let diff = RegistryDiff::with_deprecations(&old, &new, &deprecations);
+    assert_eq!(diff.changes.len(), 4);
+    assert!(!diff.is_breaking());
+    assert!(diff
+        .changes
+        .iter()
+        .filter(|change| !matches!(change.kind, ChangeKind::VariantRenamed { .. }))
+        .all(|change| change.deprecated));
+
+    // Deprecations can be stored next to the registry.
+    let data = serde_yaml::to_string(&deprecations).unwrap();
+    assert_eq!(deprecations, serde_yaml::from_str(&data).unwrap());
+}
+
+#[test]
+fn test_registry_diff_with_conditions() {
+    let registry = registry_v1();
+    let no_conditions = Conditions::new();
+    let mut conditions = Conditions::new();
+    conditions.set_condition("Header", "feature = \"extra\"");
+
+    // Making a container conditional is breaking.
+    let diff = RegistryDiff::with_conditions(
+        &registry,
+        &no_conditions,
+        &registry,
+        &conditions,
+        &Deprecations::new(),
+    );
+    assert_eq!(
+        diff.changes,
+        vec![change(
+            &["Header"],
+            ChangeKind::ConditionChanged {
+                old: None,
+                new: Some("feature = \"extra\"".into()),
+            },
+            false
+        )]
+    );
+    assert!(diff.is_breaking());
+    assert_eq!(
+        diff.changes[0].to_string(),
+        "Header: condition changed from none to `feature = \"extra\"`"
+    );
+
+    // Removing a condition is compatible.
+    let diff = RegistryDiff::with_conditions(
+        &registry,
+        &conditions,
+        &registry,
+        &no_conditions,
+        &Deprecations::new(),
+    );
+    assert_eq!(diff.changes.len(), 1);
+    assert!(!diff.is_breaking());
+}
+
+#[test]
+fn test_registry_diff_of_struct_variants() {
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "E")]
+    enum E1 {
+        A { x: u8, y: u8 },
+        B,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "E")]
+    enum E2 {
+        A { y: u8, x: u8 },
+        B(u8),
+    }
+
+    let diff = RegistryDiff::new(&trace::<E1>(), &trace::<E2>());
+    let kinds: Vec<_> = diff.changes.iter().map(|change| &change.kind).collect();
+    // Reordering fields is reported separately from other changes.
+    assert!(matches!(
+        kinds[..],
+        [
+            ChangeKind::FieldsReordered { .. },
+            ChangeKind::VariantChanged {
+                old: VariantFormat::Unit,
+                ..
+            }
+        ]
+    ));
+}
+
+#[test]
+fn test_numeric_compatibility() {
+    let rules = CompatRules::json();
+    assert_eq!(
+        rules.numeric_change(NumericType::U32, NumericType::U64),
+        Compatibility::Compatible
+    );
+    assert_eq!(
+        rules.numeric_change(NumericType::U32, NumericType::I64),
+        Compatibility::Compatible
+    );
+    assert_eq!(
+        rules.numeric_change(NumericType::U64, NumericType::U32),
+        Compatibility::Breaking
+    );
+    assert_eq!(
+        rules.numeric_change(NumericType::U64, NumericType::I64),
+        Compatibility::Breaking
+    );
+    assert_eq!(
+        rules.numeric_change(NumericType::I64, NumericType::U64),
+        Compatibility::Breaking
+    );
+    assert_eq!(
+        rules.numeric_change(NumericType::I8, NumericType::U128),
+        Compatibility::Breaking
+    );
+
+    let rules = CompatRules::binary();
+    assert_eq!(
+        rules.numeric_change(NumericType::U32, NumericType::U64),
+        Compatibility::Breaking
+    );
+    assert_eq!(
+        rules.numeric_change(NumericType::U32, NumericType::U32),
+        Compatibility::Compatible
+    );
+
+    // Nested formats.
+    let old = Format::Seq(Box::new(Format::Option(Box::new(Format::U16))));
+    let new = Format::Seq(Box::new(Format::Option(Box::new(Format::I32))));
+    assert_eq!(
+        CompatRules::json().format_change(&old, &new),
+        Compatibility::Compatible
+    );
+    assert_eq!(
+        CompatRules::binary().format_change(&old, &new),
+        Compatibility::Breaking
+    );
+    assert_eq!(
+        CompatRules::json().format_change(&Format::U8, &Format::Str),
+        Compatibility::Breaking
+    );
+}
+
+#[test]
+fn test_registry_diff_with_rules() {
+    let old = trace::<v1::Wrapper>();
+    let new = trace::<v2::Wrapper>();
+    let diff = RegistryDiff::new(&old, &new);
+    assert!(diff.is_breaking());
+    assert!(!diff.is_breaking_with(&CompatRules::json()));
+    assert!(diff.is_breaking_with(&CompatRules::binary()));
+    assert_eq!(
+        diff.breaking_changes_with(&CompatRules::binary()).count(),
+        1
+    );
+
+    let rules = CompatRules::json().forbid_numeric_change(NumericType::U8, NumericType::U16);
+    assert!(diff.is_breaking_with(&rules));
+    let rules = CompatRules::binary().allow_numeric_change(NumericType::U8, NumericType::U16);
+    assert!(!diff.is_breaking_with(&rules));
+}
+
+#[test]
+fn test_registry_diff_with_unknown_fields() {
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "S")]
+    struct S1 {
+        a: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "S")]
+    struct S2 {
+        a: u32,
+        b: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "S", deny_unknown_fields)]
+    struct S3 {
+        a: u32,
+    }
+
+    let trace = |f: fn(&mut Tracer)| {
+        let mut tracer = Tracer::new(TracerConfig::default().probe_unknown_fields(true));
+        f(&mut tracer);
+        tracer.registry_with_metadata().unwrap()
+    };
+    let (r1, m1) = trace(|tracer| {
+        tracer.trace_simple_type::<S1>().unwrap();
+    });
+    let (r2, m2) = trace(|tracer| {
+        tracer.trace_simple_type::<S2>().unwrap();
+    });
+    let (r3, m3) = trace(|tracer| {
+        tracer.trace_simple_type::<S3>().unwrap();
+    });
+    let deprecations = Deprecations::new();
+
+    // Readers of `S1` skip the new optional field, and readers of `S2` read it as `None`
+    // when it is missing.
+    let diff = RegistryDiff::with_metadata(&r1, &m1, &r2, &m2, &deprecations);
+    assert!(diff.changes[0].unknown_field_ignored);
+    assert!(diff.is_breaking());
+    assert!(!diff.is_breaking_with(&CompatRules::json()));
+    assert!(diff.is_breaking_with(&CompatRules::binary()));
+    assert!(RegistryDiff::new(&r1, &r2).is_breaking_with(&CompatRules::json()));
+
+    // Removing the field is compatible too.
+    let diff = RegistryDiff::with_metadata(&r2, &m2, &r1, &m1, &deprecations);
+    assert!(!diff.is_breaking_with(&CompatRules::json()));
+
+    // Readers of `S3` reject the field, whether it is added or removed.
+    let diff = RegistryDiff::with_metadata(&r3, &m3, &r2, &m2, &deprecations);
+    assert!(!diff.changes[0].unknown_field_ignored);
+    assert!(diff.is_breaking_with(&CompatRules::json()));
+    let diff = RegistryDiff::with_metadata(&r2, &m2, &r3, &m3, &deprecations);
+    assert!(diff.is_breaking_with(&CompatRules::json()));
+}
+
+#[test]
+fn test_registry_diff_of_options() {
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "S")]
+    struct S1 {
+        a: u32,
+        b: Option<String>,
+        c: u8,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "S")]
+    struct S2 {
+        a: Option<u32>,
+        b: String,
+        c: Option<u16>,
+    }
+
+    let diff = RegistryDiff::new(&trace::<S1>(), &trace::<S2>());
+    assert_eq!(
+        diff.changes,
+        vec![
+            change(
+                &["S", "a"],
+                ChangeKind::OptionAdded {
+                    format: Format::U32
+                },
+                false
+            ),
+            change(
+                &["S", "b"],
+                ChangeKind::OptionRemoved {
+                    format: Format::Str
+                },
+                false
+            ),
+            change(
+                &["S", "c"],
+                ChangeKind::FieldChanged {
+                    old: Format::U8,
+                    new: Format::Option(Box::new(Format::U16))
+                },
+                false
+            ),
+        ]
+    );
+
+    let json = CompatRules::json();
+    let breaking: Vec<_> = diff.breaking_changes_with(&json).collect();
+    assert_eq!(breaking, vec![&diff.changes[1]]);
+    assert_eq!(
+        diff.breaking_changes_with(&CompatRules::binary()).count(),
+        3
+    );
+    let rules = json.allow_option_wrapping(false);
+    assert_eq!(diff.breaking_changes_with(&rules).count(), 3);
+}
+
+#[test]
+fn test_registry_diff_of_tuple_arities() {
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "T")]
+    struct T1(u8, String);
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "T")]
+    struct T2(u8, String, bool);
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "T")]
+    struct T3(u64, u8, String);
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "E")]
+    enum E1 {
+        Pair(u8, u16, u32),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "E")]
+    enum E2 {
+        Pair(u8, u64),
+    }
+
+    // Trailing addition.
+    let diff = RegistryDiff::new(&trace::<T1>(), &trace::<T2>());
+    assert_eq!(
+        diff.changes,
+        vec![change(
+            &["T", "2"],
+            ChangeKind::ElementAdded {
+                index: 2,
+                format: Format::Bool,
+                trailing: true
+            },
+            false
+        )]
+    );
+    assert_eq!(
+        diff.changes[0].to_string(),
+        "T::2: element appended at position 2 with format Bool"
+    );
+
+    // Insertion before the existing elements, and the reverse removal.
+    let diff = RegistryDiff::new(&trace::<T1>(), &trace::<T3>());
+    assert_eq!(
+        diff.changes,
+        vec![change(
+            &["T", "0"],
+            ChangeKind::ElementAdded {
+                index: 0,
+                format: Format::U64,
+                trailing: false
+            },
+            false
+        )]
+    );
+    assert!(diff.is_breaking());
+    let diff = RegistryDiff::new(&trace::<T3>(), &trace::<T1>());
+    assert_eq!(
+        diff.changes[0].kind,
+        ChangeKind::ElementRemoved {
+            index: 0,
+            format: Format::U64,
+            trailing: false
+        }
+    );
+
+    // Elements that cannot be aligned are compared by position.
+    let diff = RegistryDiff::new(&trace::<E1>(), &trace::<E2>());
+    assert_eq!(
+        diff.changes,
+        vec![
+            change(
+                &["E", "Pair", "1"],
+                ChangeKind::FieldChanged {
+                    old: Format::U16,
+                    new: Format::U64
+                },
+                false
+            ),
+            change(
+                &["E", "Pair", "2"],
+                ChangeKind::ElementRemoved {
+                    index: 2,
+                    format: Format::U32,
+                    trailing: true
+                },
+                false
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_encoding_profiles() {
+    use serde_reflection::compat::{EncodingProfile, IntegerEncoding, PrefixEncoding, TagEncoding};
+
+    assert_eq!(
+        EncodingProfile::Bcs.integer_encoding(NumericType::U64),
+        IntegerEncoding::FixedLittleEndian
+    );
+    assert_eq!(
+        EncodingProfile::Postcard.integer_encoding(NumericType::U8),
+        IntegerEncoding::FixedLittleEndian
+    );
+    assert_eq!(
+        EncodingProfile::Postcard.integer_encoding(NumericType::I32),
+        IntegerEncoding::ZigZagVarint
+    );
+    assert_eq!(
+        EncodingProfile::Bincode1.length_encoding(),
+        PrefixEncoding::U64
+    );
+    assert_eq!(
+        EncodingProfile::Bcs.variant_tag_encoding(),
+        PrefixEncoding::Uleb128
+    );
+    assert_eq!(
+        EncodingProfile::Bincode1.tag_encoding(),
+        TagEncoding::Index(PrefixEncoding::U32)
+    );
+    let tags = EncodingProfile::Bcs.tag_encoding();
+    assert_eq!(tags.encoded_size(127, "Foo"), 1);
+    assert_eq!(tags.encoded_size(128, "Foo"), 2);
+    let tags = TagEncoding::Name(PrefixEncoding::Uleb128);
+    assert_eq!(tags.encoded_size(128, "Foo"), 4);
+
+    let rules = EncodingProfile::Postcard.compat_rules();
+    let change = |old, new| rules.numeric_change(old, new);
+    assert_eq!(
+        change(NumericType::U16, NumericType::U64),
+        Compatibility::Compatible
+    );
+    assert_eq!(
+        change(NumericType::I16, NumericType::I128),
+        Compatibility::Compatible
+    );
+    assert_eq!(
+        change(NumericType::U8, NumericType::U16),
+        Compatibility::Breaking
+    );
+    assert_eq!(
+        change(NumericType::U16, NumericType::I32),
+        Compatibility::Breaking
+    );
+    assert_eq!(
+        change(NumericType::U64, NumericType::U32),
+        Compatibility::Breaking
+    );
+    assert_eq!(EncodingProfile::Bcs.compat_rules(), CompatRules::binary());
+    assert_eq!(
+        EncodingProfile::Bincode1.compat_rules(),
+        CompatRules::binary()
+    );
+}
+
+#[test]
+fn test_registry_diff_of_reordered_fields() {
+    mod v1 {
+        use super::*;
+
+        #[derive(Serialize, Deserialize)]
+        pub struct Point {
+            pub y: u32,
+            pub x: u32,
+            pub label: String,
+        }
+    }
+
+    mod v2 {
+        use super::*;
+
+        #[derive(Serialize, Deserialize)]
+        pub struct Point {
+            pub label: String,
+            pub x: u32,
+            pub y: u32,
+        }
+    }
+
+    let old = trace::<v1::Point>();
+    let new = trace::<v2::Point>();
+    let diff = RegistryDiff::new(&old, &new);
+    assert_eq!(
+        diff.changes,
+        vec![change(
+            &["Point"],
+            ChangeKind::FieldsReordered {
+                old: vec!["y".into(), "x".into(), "label".into()],
+                new: vec!["label".into(), "x".into(), "y".into()],
+            },
+            false
+        )]
+    );
+    assert!(diff.is_breaking());
+    assert!(!diff.is_breaking_with(&CompatRules::json()));
+
+    // Canonicalize the field order.
+    let mut old = old;
+    let mut new = new;
+    for registry in [&mut old, &mut new] {
+        let format = registry.get_mut("Point").unwrap();
+        let mut names: Vec<String> = format
+            .named_fields()
+            .map(|field| field.name.clone())
+            .collect();
+        names.sort();
+        format.reorder_fields(&names.iter().map(String::as_str).collect::<Vec<_>>());
+    }
+    assert!(RegistryDiff::new(&old, &new).is_empty());
+    assert_eq!(
+        old.get("Point")
+            .unwrap()
+            .named_fields()
+            .map(|field| field.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["label", "x", "y"]
+    );
+}
+
+#[test]
+fn test_changelog_markdown() {
+    use serde_reflection::compat::EncodingProfile;
+
+    let mut old = Registry::new();
+    old.insert(
+        "Foo".into(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "a".into(),
+                value: Format::U16,
+            },
+            Named {
+                name: "b".into(),
+                value: Format::Str,
+            },
+        ]),
+    );
+    old.insert("Gone".into(), ContainerFormat::UnitStruct);
+    let mut new = Registry::new();
+    new.insert(
+        "Foo".into(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "a".into(),
+                value: Format::U64,
+            },
+            Named {
+                name: "b".into(),
+                value: Format::Str,
+            },
+            Named {
+                name: "c".into(),
+                value: Format::Bool,
+            },
+        ]),
+    );
+    new.insert("Bar".into(), ContainerFormat::UnitStruct);
+
+    let diff = RegistryDiff::new(&old, &new);
+    let profiles = [EncodingProfile::Bcs, EncodingProfile::Postcard];
+    assert_eq!(
+        diff.to_changelog_markdown("Schema changes in v1.4", &profiles),
+        r#"# Schema changes in v1.4
+
+## `Bar`
+
+### Added
+
+- `Bar`: container added
+
+## `Foo`
+
+### Added
+
+- `Foo::c`: field added at position 2 with format Bool **Breaking** (BCS, Postcard)
+
+### Modified
+
+- `Foo::a`: format changed from U16 to U64 **Breaking** (BCS)
+
+## `Gone`
+
+### Removed
+
+- `Gone`: container removed **Breaking** (BCS, Postcard)
+"#
+    );
+    assert!(diff
+        .to_changelog_markdown("Changes", &[])
+        .contains("- `Foo::a`: format changed from U16 to U64 **Breaking**\n"));
+    assert_eq!(
+        RegistryDiff::new(&old, &old).to_changelog_markdown("Changes", &profiles),
+        "# Changes\n\nNo changes.\n"
+    );
+}
+
+#[test]
+fn test_recommend_version_bump() {
+    use serde_reflection::compat::{
+        recommend_version_bump, EncodingProfile, SemverBump, SemverPolicy,
+    };
+
+    #[derive(Serialize, Deserialize)]
+    enum V1 {
+        A(u16),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "V1")]
+    enum V2 {
+        A(u16),
+        B,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "V1")]
+    enum V3 {
+        Renamed(u16),
+        B,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "V1")]
+    enum V4 {
+        Renamed(u32),
+        B,
+    }
+
+    let (v1, v2, v3, v4) = (trace::<V1>(), trace::<V2>(), trace::<V3>(), trace::<V4>());
+    let bump = |old, new| recommend_version_bump(old, new, EncodingProfile::Postcard);
+    assert_eq!(bump(&v1, &v1), SemverBump::None);
+    assert_eq!(bump(&v1, &v2), SemverBump::Minor);
+    assert_eq!(bump(&v2, &v3), SemverBump::Patch);
+    // Widening a varint is compatible with Postcard but not with BCS.
+    assert_eq!(bump(&v3, &v4), SemverBump::Patch);
+    assert_eq!(
+        recommend_version_bump(&v3, &v4, EncodingProfile::Bcs),
+        SemverBump::Major
+    );
+    assert_eq!(bump(&v2, &v1), SemverBump::Major);
+
+    let rules = EncodingProfile::Bcs.compat_rules();
+    let policy = SemverPolicy::initial_development();
+    assert_eq!(
+        policy.recommend(&RegistryDiff::new(&v2, &v1), &rules),
+        SemverBump::Minor
+    );
+    assert_eq!(
+        policy.recommend(&RegistryDiff::new(&v1, &v2), &rules),
+        SemverBump::Patch
+    );
+    let policy = SemverPolicy::default().compatible_changes(SemverBump::Minor);
+    assert_eq!(
+        policy.recommend(&RegistryDiff::new(&v2, &v3), &rules),
+        SemverBump::Minor
+    );
+
+    assert_eq!(
+        SemverBump::between("v1.4.2", "1.5.0-rc.1"),
+        Some(SemverBump::Minor)
+    );
+    assert_eq!(
+        SemverBump::between("1.4.2", "1.4.3+build"),
+        Some(SemverBump::Patch)
+    );
+    assert_eq!(SemverBump::between("1.4", "1.5.0"), None);
+    assert_eq!(SemverBump::between("1.4.2.1", "1.5.0"), None);
+}
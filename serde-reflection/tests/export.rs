@@ -0,0 +1,330 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![cfg(all(feature = "json", feature = "trace"))]
+
+use serde_reflection::{
+    export::{unsupported_constructs, write_error, Construct, Exporter, Exporters},
+    ContainerFormat, Error, Format, Registry, RegistryMetadata, Result,
+};
+use std::io::Write;
+
+struct Counter;
+
+impl Exporter for Counter {
+    fn name(&self) -> &str {
+        "counter"
+    }
+
+    fn supports(&self, construct: Construct) -> bool {
+        !matches!(
+            construct,
+            Construct::Map | Construct::Int128 | Construct::SelfDescribing
+        )
+    }
+
+    fn export_container(
+        &self,
+        _index: usize,
+        _name: &str,
+        _format: &ContainerFormat,
+        _out: &mut dyn Write,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn end(&self, registry: &Registry, out: &mut dyn Write) -> Result<()> {
+        write!(out, "{}", registry.len()).map_err(write_error)
+    }
+}
+
+/// Write the name of each container, failing on unit structs.
+struct Names;
+
+impl Exporter for Names {
+    fn name(&self) -> &str {
+        "names"
+    }
+
+    fn export_container(
+        &self,
+        _index: usize,
+        name: &str,
+        format: &ContainerFormat,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        write!(out, "{name}").map_err(write_error)?;
+        if *format == ContainerFormat::UnitStruct {
+            return Err(Error::Custom(format!("Cannot export {name}")));
+        }
+        writeln!(out).map_err(write_error)
+    }
+}
+
+/// Keep track of the data that was flushed.
+#[derive(Default)]
+struct FlushedWriter {
+    buffer: Vec<u8>,
+    flushed: Vec<u8>,
+}
+
+impl Write for FlushedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flushed.append(&mut self.buffer);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_exporters() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Foo".into(),
+        ContainerFormat::TupleStruct(vec![Format::U32, Format::Str]),
+    );
+
+    let mut exporters = Exporters::default();
+    assert!(exporters.get("json").is_some());
+    assert!(exporters.get("counter").is_none());
+    assert!(exporters.register(Counter).is_none());
+    assert!(exporters.register(Counter).is_some());
+    assert!(exporters.names().any(|name| name == "counter"));
+
+    let mut out = Vec::new();
+    exporters.export("counter", &registry, &mut out).unwrap();
+    assert_eq!(out, b"1");
+
+    let mut out = Vec::new();
+    exporters.export("json", &registry, &mut out).unwrap();
+    let parsed: Registry = serde_json::from_slice(&out).unwrap();
+    assert_eq!(parsed, registry);
+
+    assert_eq!(
+        exporters.export("unknown", &registry, &mut Vec::new()),
+        Err(Error::UnknownExporter("unknown".into()))
+    );
+
+    // Exporters can reject the containers that require a self-describing encoding.
+    let mut metadata = RegistryMetadata::new();
+    metadata.mark_self_describing("Foo");
+    assert_eq!(
+        exporters.export_with_metadata("counter", &registry, &metadata, &mut Vec::new()),
+        Err(Error::UnsupportedConstruct {
+            exporter: "counter".into(),
+            container: "Foo".into(),
+            construct: Construct::SelfDescribing,
+        })
+    );
+    let mut out = Vec::new();
+    exporters
+        .export_with_metadata("json", &registry, &metadata, &mut out)
+        .unwrap();
+    assert_eq!(serde_json::from_slice::<Registry>(&out).unwrap(), registry);
+
+    registry.insert(
+        "Bar".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Map {
+            key: Box::new(Format::Str),
+            value: Box::new(Format::U128),
+        })),
+    );
+    let unsupported = unsupported_constructs(exporters.get("counter").unwrap(), &registry);
+    assert_eq!(
+        unsupported.into_iter().collect::<Vec<_>>(),
+        vec![
+            (Construct::Int128, "Bar".to_string()),
+            (Construct::Map, "Bar".to_string()),
+        ]
+    );
+    assert_eq!(
+        exporters.export("counter", &registry, &mut Vec::new()),
+        Err(Error::UnsupportedConstruct {
+            exporter: "counter".into(),
+            container: "Bar".into(),
+            construct: Construct::Int128,
+        })
+    );
+}
+
+#[test]
+fn test_streaming_export() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "A".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::U32)),
+    );
+    registry.insert("B".into(), ContainerFormat::UnitStruct);
+    registry.insert(
+        "C".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Str)),
+    );
+
+    let mut exporters = Exporters::default();
+    exporters.register(Names);
+    let mut out = FlushedWriter::default();
+    assert_eq!(
+        exporters.export("names", &registry, &mut out),
+        Err(Error::Custom("Cannot export B".into()))
+    );
+    // Complete containers have been flushed.
+    assert_eq!(out.flushed, b"A\n");
+    assert_eq!(out.buffer, b"B");
+
+    // Built-in exporters produce the same output as serializing the whole registry.
+    registry.insert(
+        "B".into(),
+        ContainerFormat::TupleStruct(vec![Format::Seq(Box::new(Format::U8)), Format::Unit]),
+    );
+    for registry in [Registry::new(), registry] {
+        let mut out = Vec::new();
+        exporters.export("json", &registry, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            serde_json::to_string_pretty(&registry).unwrap() + "\n"
+        );
+        if exporters.get("yaml").is_some() {
+            let mut out = Vec::new();
+            exporters.export("yaml", &registry, &mut out).unwrap();
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                serde_yaml::to_string(&registry).unwrap()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_docs_exporter() {
+    use serde_reflection::{Named, VariantFormat};
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "Message".into(),
+        ContainerFormat::Enum(
+            [
+                (
+                    0,
+                    Named {
+                        name: "Ping".into(),
+                        value: VariantFormat::Unit,
+                    },
+                ),
+                (
+                    1,
+                    Named {
+                        name: "Data".into(),
+                        value: VariantFormat::Struct(vec![Named {
+                            name: "payload".into(),
+                            value: Format::Seq(Box::new(Format::TypeName("Chunk".into()))),
+                        }]),
+                    },
+                ),
+            ]
+            .into(),
+        ),
+    );
+    registry.insert(
+        "Chunk".into(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "index".into(),
+                value: Format::U32,
+            },
+            Named {
+                name: "data".into(),
+                value: Format::Map {
+                    key: Box::new(Format::Str),
+                    value: Box::new(Format::Bytes),
+                },
+            },
+        ]),
+    );
+
+    let exporters = Exporters::default();
+    let mut out = Vec::new();
+    exporters.export("docs", &registry, &mut out).unwrap();
+    let html = String::from_utf8(out).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.ends_with("</html>\n"));
+    assert!(html.contains("<h2 id=\"Chunk\">Chunk</h2>"));
+    assert!(html
+        .contains("<li><a href=\"#Message\">Message</a> uses <a href=\"#Chunk\">Chunk</a></li>"));
+    assert!(html.contains("  \"Message\" -> \"Chunk\";"));
+    assert!(html.contains("<tr><td>data</td><td><code>Map&lt;String, Bytes&gt;</code></td></tr>"));
+    assert!(html.contains(
+        "<tr><td>1</td><td>Data</td><td><code>{ payload: Vec&lt;<a href=\"#Chunk\">Chunk</a>&gt; }</code></td></tr>"
+    ));
+}
+
+#[test]
+fn test_empty_containers_round_trip() {
+    use serde::{Deserialize, Serialize};
+    use serde_reflection::{Samples, Tracer, TracerConfig};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize)]
+    struct Unit;
+
+    #[derive(Serialize, Deserialize)]
+    struct Empty {}
+
+    #[derive(Serialize, Deserialize)]
+    struct EmptyTuple();
+
+    #[derive(Serialize, Deserialize)]
+    enum Void {}
+
+    #[derive(Serialize, Deserialize)]
+    struct Holder {
+        unit: Unit,
+        empty: Empty,
+        tuple: EmptyTuple,
+        voids: Vec<Void>,
+    }
+
+    // Serialization and deserialization record the same formats.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let mut samples = Samples::new();
+    tracer
+        .trace_value(&mut samples, &(Unit, Empty {}, EmptyTuple()))
+        .unwrap();
+    let serialized = tracer.registry().unwrap();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Holder>().unwrap();
+    let registry = tracer.registry().unwrap();
+    for name in ["Unit", "Empty", "EmptyTuple"] {
+        assert_eq!(registry[name], serialized[name]);
+    }
+    assert_eq!(registry["Unit"], ContainerFormat::UnitStruct);
+    assert_eq!(registry["Empty"], ContainerFormat::Struct(Vec::new()));
+    assert_eq!(
+        registry["EmptyTuple"],
+        ContainerFormat::TupleStruct(Vec::new())
+    );
+    assert_eq!(registry["Void"], ContainerFormat::Enum(BTreeMap::new()));
+
+    // Exported registries are loaded back unchanged.
+    let exporters = Exporters::default();
+    let mut out = Vec::new();
+    exporters.export("json", &registry, &mut out).unwrap();
+    assert_eq!(serde_json::from_slice::<Registry>(&out).unwrap(), registry);
+    if exporters.get("yaml").is_some() {
+        let mut out = Vec::new();
+        exporters.export("yaml", &registry, &mut out).unwrap();
+        assert_eq!(serde_yaml::from_slice::<Registry>(&out).unwrap(), registry);
+    }
+    let mut out = Vec::new();
+    exporters.export("docs", &registry, &mut out).unwrap();
+    let html = String::from_utf8(out).unwrap();
+    assert!(html.contains("<h2 id=\"Unit\">Unit</h2>\n<p>Unit struct.</p>"));
+    assert!(html.contains("<h2 id=\"Empty\">Empty</h2>\n<p>Struct without fields.</p>"));
+    assert!(
+        html.contains("<h2 id=\"EmptyTuple\">EmptyTuple</h2>\n<p>Tuple struct without fields.</p>")
+    );
+    assert!(html
+        .contains("<h2 id=\"Void\">Void</h2>\n<p>Uninhabited enum: no value can be encoded.</p>"));
+}
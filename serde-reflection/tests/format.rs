@@ -1,7 +1,9 @@
 // Copyright (c) Facebook, Inc. and its affiliates
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use serde_reflection::{ContainerFormat, Error, Format, FormatHolder, Named, VariantFormat};
+use serde_reflection::{
+    ContainerFormat, ContainerKind, Error, Format, FormatHolder, FormatRef, Named, VariantFormat,
+};
 use std::{collections::HashSet, ops::Deref};
 
 #[test]
@@ -273,3 +275,140 @@ fn test_container_format_unification() {
             .is_err());
     }
 }
+
+#[test]
+fn test_c_like_enums() {
+    let unit = |name: &str| Named {
+        name: name.into(),
+        value: VariantFormat::Unit,
+    };
+    let c_like = ContainerFormat::Enum(vec![(0, unit("A")), (1, unit("B"))].into_iter().collect());
+    assert!(c_like.is_c_like());
+
+    let mixed = ContainerFormat::Enum(
+        vec![
+            (0, unit("A")),
+            (
+                1,
+                Named {
+                    name: "B".into(),
+                    value: VariantFormat::NewType(Box::new(Format::U8)),
+                },
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    assert!(!mixed.is_c_like());
+    assert!(!ContainerFormat::UnitStruct.is_c_like());
+}
+
+#[test]
+fn test_format_views() {
+    use Format::*;
+
+    let format = Map {
+        key: Box::new(Str),
+        value: Box::new(Tuple(vec![U8, TypeName("Foo".into())])),
+    };
+    let view = format.view();
+    assert_eq!(
+        view.children().collect::<Vec<_>>(),
+        vec![&Str, &Tuple(vec![U8, TypeName("Foo".into())])]
+    );
+    assert_eq!(view.to_owned(), format);
+    assert_eq!(U64.view(), FormatRef::Primitive(&U64));
+    assert_eq!(TypeName("Foo".into()).view(), FormatRef::TypeName("Foo"));
+    assert_eq!(U64.view().children().count(), 0);
+
+    let container = ContainerFormat::Struct(vec![
+        Named {
+            name: "a".into(),
+            value: U8,
+        },
+        Named {
+            name: "b".into(),
+            value: Seq(Box::new(U16)),
+        },
+    ]);
+    assert_eq!(
+        container.formats().collect::<Vec<_>>(),
+        vec![&U8, &Seq(Box::new(U16))]
+    );
+    assert_eq!(
+        container
+            .named_fields()
+            .map(|field| field.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["a", "b"]
+    );
+    assert_eq!(container.variants().count(), 0);
+
+    let container = ContainerFormat::Enum(
+        vec![
+            (
+                1,
+                Named {
+                    name: "B".into(),
+                    value: VariantFormat::Struct(vec![Named {
+                        name: "x".into(),
+                        value: Bool,
+                    }]),
+                },
+            ),
+            (
+                0,
+                Named {
+                    name: "A".into(),
+                    value: VariantFormat::Tuple(vec![I8, I16]),
+                },
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    assert_eq!(
+        container.formats().collect::<Vec<_>>(),
+        vec![&I8, &I16, &Bool]
+    );
+    assert_eq!(container.named_fields().count(), 0);
+    assert_eq!(
+        container
+            .variants()
+            .map(|(index, variant)| (index, variant.name.as_str()))
+            .collect::<Vec<_>>(),
+        vec![(0, "A"), (1, "B")]
+    );
+}
+
+#[test]
+fn test_container_kinds() {
+    let unit = |name: &str| Named {
+        name: name.into(),
+        value: VariantFormat::Unit,
+    };
+    let enum_ = ContainerFormat::Enum(vec![(1, unit("B")), (0, unit("A"))].into_iter().collect());
+    assert_eq!(enum_.kind(), ContainerKind::Enum);
+    assert!(enum_.is_enum());
+    assert!(!enum_.is_struct());
+    assert_eq!(enum_.field_count(), 0);
+    assert_eq!(enum_.variant_names().collect::<Vec<_>>(), vec!["A", "B"]);
+
+    let tuple = ContainerFormat::TupleStruct(vec![Format::U8, Format::Str]);
+    assert_eq!(tuple.kind(), ContainerKind::TupleStruct);
+    assert!(tuple.is_struct());
+    assert_eq!(tuple.field_count(), 2);
+    assert_eq!(tuple.variant_names().count(), 0);
+
+    let generic = ContainerFormat::Generic {
+        params: vec!["T".into()],
+        body: Box::new(ContainerFormat::NewTypeStruct(Box::new(Format::TypeParam(
+            0,
+        )))),
+    };
+    assert_eq!(generic.kind(), ContainerKind::NewTypeStruct);
+    assert!(generic.is_generic());
+    assert_eq!(generic.field_count(), 1);
+    assert!(!ContainerFormat::UnitStruct.is_generic());
+    assert_eq!(ContainerFormat::UnitStruct.field_count(), 0);
+}
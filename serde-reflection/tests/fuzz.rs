@@ -0,0 +1,126 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![cfg(feature = "trace")]
+
+use serde::{Deserialize, Serialize};
+use serde_reflection::{
+    compat::EncodingProfile,
+    fuzz::{mutate, MutationKind},
+    Error, Registry, Tracer, TracerConfig,
+};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Payload {
+    Ping,
+    Transfer { amount: u64, memo: String },
+    Batch(Vec<Option<bool>>),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Tx {
+    nonce: u32,
+    payload: Payload,
+}
+
+fn registry() -> Registry {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Tx>().unwrap();
+    tracer.trace_simple_type::<Payload>().unwrap();
+    tracer.registry().unwrap()
+}
+
+#[test]
+fn test_mutations_target_schema_positions() {
+    let registry = registry();
+    let tx = Tx {
+        nonce: 7,
+        payload: Payload::Transfer {
+            amount: 300,
+            memo: "hi".into(),
+        },
+    };
+    let bytes = bcs::to_bytes(&tx).unwrap();
+    let mutations = mutate(&registry, "Tx", &bytes, EncodingProfile::Bcs).unwrap();
+    let summary: Vec<_> = mutations.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        summary,
+        vec![
+            "other variant at byte 4 (Tx.payload.Payload)",
+            "unknown variant at byte 4 (Tx.payload.Payload)",
+            "length overflow at byte 13 (Tx.payload.Payload.Transfer.memo)",
+            "short length at byte 13 (Tx.payload.Payload.Transfer.memo)",
+            "invalid UTF-8 at byte 14 (Tx.payload.Payload.Transfer.memo)",
+            "truncated at byte 15 (Tx.payload.Payload.Transfer.memo)",
+        ]
+    );
+    // The other variant is `Batch`, whose remaining bytes do not decode.
+    for mutation in &mutations {
+        assert_eq!(mutation.bytes[..mutation.offset], bytes[..mutation.offset]);
+        assert!(
+            bcs::from_bytes::<Tx>(&mutation.bytes).is_err(),
+            "{mutation}"
+        );
+    }
+}
+
+#[test]
+fn test_mutations_of_options_and_sequences() {
+    let registry = registry();
+    let payload = Payload::Batch(vec![None, Some(true)]);
+    let bytes = bincode::serialize(&payload).unwrap();
+    let mutations = mutate(&registry, "Payload", &bytes, EncodingProfile::Bincode1).unwrap();
+    let kinds: Vec<_> = mutations
+        .iter()
+        .map(|mutation| (mutation.kind, mutation.offset))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (MutationKind::OtherVariant, 0),
+            (MutationKind::UnknownVariant, 0),
+            (MutationKind::LengthOverflow, 4),
+            (MutationKind::ShortLength, 4),
+            (MutationKind::InvalidTag, 12),
+            (MutationKind::InvalidTag, 13),
+            (MutationKind::Truncated, 14),
+            (MutationKind::InvalidTag, 14),
+        ]
+    );
+    for mutation in &mutations {
+        let decoded = bincode::deserialize::<Payload>(&mutation.bytes);
+        match mutation.kind {
+            // `Ping` ignores the rest of the payload.
+            MutationKind::OtherVariant => assert_eq!(decoded.unwrap(), Payload::Ping),
+            // The length of the shortened sequence leaves trailing bytes, which bincode 1
+            // accepts.
+            MutationKind::ShortLength => {
+                assert_eq!(decoded.unwrap(), Payload::Batch(vec![None]))
+            }
+            _ => assert!(decoded.is_err(), "{mutation}"),
+        }
+    }
+
+    // Postcard uses varints for tags and lengths.
+    let mutations = mutate(&registry, "Payload", &[2, 1, 0], EncodingProfile::Postcard).unwrap();
+    assert_eq!(mutations[0].bytes, vec![0, 1, 0]);
+    assert_eq!(mutations[1].bytes, vec![3, 1, 0]);
+    assert_eq!(mutations[2].bytes, vec![2, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0]);
+}
+
+#[test]
+fn test_mutate_rejects_invalid_payloads() {
+    let registry = registry();
+    assert!(matches!(
+        mutate(&registry, "Payload", &[5], EncodingProfile::Postcard),
+        Err(Error::RoundTripMismatch(_))
+    ));
+    assert!(matches!(
+        mutate(&registry, "Payload", &[0, 0], EncodingProfile::Postcard),
+        Err(Error::RoundTripMismatch(_))
+    ));
+    assert_eq!(
+        mutate(&registry, "Unknown", &[], EncodingProfile::Postcard),
+        Err(Error::MissingContainer("Unknown".into()))
+    );
+}
@@ -4,7 +4,6 @@
 //! Integration tests for the json_converter module
 #![cfg(feature = "json")]
 
-use bincode::Options;
 use serde::de::{DeserializeSeed, IntoDeserializer};
 use serde_json::{json, Value};
 use serde_reflection::{
@@ -1187,7 +1186,9 @@ fn test_roundtrip_containers() {
 // ============================================================================
 
 #[test]
+#[cfg(feature = "trace")]
 fn test_bincode_roundtrip_struct() {
+    use bincode::Options;
     use serde::{Deserialize, Serialize};
 
     #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -1233,7 +1234,9 @@ fn test_bincode_roundtrip_struct() {
 }
 
 #[test]
+#[cfg(feature = "trace")]
 fn test_bincode_roundtrip_enum() {
+    use bincode::Options;
     use serde::{Deserialize, Serialize};
 
     #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -1283,6 +1286,7 @@ fn test_bincode_roundtrip_enum() {
 // ============================================================================
 
 #[test]
+#[cfg(feature = "trace")]
 fn test_bcs_roundtrip_struct() {
     use serde::{Deserialize, Serialize};
 
@@ -1329,6 +1333,7 @@ fn test_bcs_roundtrip_struct() {
 }
 
 #[test]
+#[cfg(feature = "trace")]
 fn test_bcs_roundtrip_enum() {
     use serde::{Deserialize, Serialize};
 
@@ -1375,6 +1380,7 @@ fn test_bcs_roundtrip_enum() {
 }
 
 #[test]
+#[cfg(feature = "trace")]
 fn test_bcs_roundtrip_complex_types() {
     use serde::{Deserialize, Serialize};
 
@@ -1493,3 +1499,36 @@ fn test_serialize_error_unknown_variant() {
     let result = serialize_json(&json!({"UnknownVariant": null}), &format, &registry);
     assert!(result.is_err());
 }
+
+#[test]
+fn test_infer_format() {
+    use serde_reflection::json_converter::infer_format;
+
+    let value = json!({
+        "id": 7,
+        "tags": ["a", "b"],
+        "scores": [1.5, 2, -3],
+        "extra": [null, {"nested": true}],
+        "empty": [],
+    });
+    let seq = |format| Format::Seq(Box::new(format));
+    assert_eq!(
+        infer_format(&value),
+        Format::Map {
+            key: Box::new(Format::Str),
+            value: Box::new(Format::Union(vec![
+                seq(Format::Unit),
+                seq(Format::Union(vec![
+                    Format::Unit,
+                    Format::Map {
+                        key: Box::new(Format::Str),
+                        value: Box::new(Format::Bool),
+                    },
+                ])),
+                Format::U64,
+                seq(Format::Union(vec![Format::F64, Format::I128])),
+                seq(Format::Str),
+            ])),
+        }
+    );
+}
@@ -0,0 +1,940 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![cfg(feature = "trace")]
+
+use serde::{Deserialize, Serialize};
+use serde_reflection::{
+    Conditions, ContainerFormat, Error, Format, FrozenContainers, FrozenRegistry, Named, Registry,
+    Samples, TraceRoot, Tracer, TracerConfig,
+};
+use std::collections::BTreeMap;
+
+mod v1 {
+    use super::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Foo {
+        pub a: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum Bar {
+        A,
+        B(Foo),
+    }
+}
+
+mod v2 {
+    use super::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Foo {
+        pub a: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum Bar {
+        A,
+        B(Foo),
+    }
+}
+
+fn registry_v1() -> Registry {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<v1::Bar>().unwrap();
+    tracer.registry().unwrap()
+}
+
+fn registry_v2() -> Registry {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<v2::Bar>().unwrap();
+    tracer.registry().unwrap()
+}
+
+#[test]
+fn test_stable_hash() {
+    let foo = ContainerFormat::Struct(vec![Named {
+        name: "a".into(),
+        value: Format::U32,
+    }]);
+    // Hash values must never change across versions of the crate.
+    assert_eq!(foo.stable_hash(), 0x0127_f131_5f99_9666);
+    assert_eq!(
+        registry_v1().get("Foo").unwrap().stable_hash(),
+        foo.stable_hash()
+    );
+    assert_ne!(
+        registry_v2().get("Foo").unwrap().stable_hash(),
+        foo.stable_hash()
+    );
+    assert_ne!(
+        ContainerFormat::UnitStruct.stable_hash(),
+        ContainerFormat::TupleStruct(Vec::new()).stable_hash()
+    );
+}
+
+#[test]
+fn test_frozen_containers() {
+    let registry = registry_v1();
+    let mut frozen = FrozenContainers::new();
+    frozen.freeze(&registry, ["Foo", "Bar"], 3).unwrap();
+    assert_eq!(frozen.get("Foo").unwrap().version, 3);
+    frozen.check(&registry).unwrap();
+
+    // Unknown containers cannot be frozen.
+    assert_eq!(
+        frozen.freeze(&registry, ["Baz"], 3),
+        Err(Error::MissingContainer("Baz".into()))
+    );
+
+    // Changing a frozen container is detected.
+    let registry = registry_v2();
+    assert_eq!(
+        frozen.check(&registry),
+        Err(Error::FrozenContainerChanged("Foo".into(), 3))
+    );
+    // Re-freezing requires a version bump.
+    assert_eq!(
+        frozen.freeze(&registry, ["Foo"], 3),
+        Err(Error::FrozenContainerChanged("Foo".into(), 3))
+    );
+    frozen.freeze(&registry, ["Foo"], 4).unwrap();
+    frozen.check(&registry).unwrap();
+    // Versions cannot decrease.
+    assert!(frozen.freeze(&registry, ["Foo"], 2).is_err());
+
+    // The sidecar structure can be stored next to the registry.
+    let data = serde_yaml::to_string(&frozen).unwrap();
+    assert_eq!(frozen, serde_yaml::from_str(&data).unwrap());
+
+    // Removing a frozen container is detected.
+    let mut registry = registry;
+    registry.remove("Bar");
+    assert_eq!(
+        frozen.check(&registry),
+        Err(Error::MissingContainer("Bar".into()))
+    );
+}
+
+#[test]
+fn test_frozen_registry() {
+    use serde_reflection::registry::freeze_all;
+
+    let frozen = freeze_all(registry_v1());
+    assert_eq!(*frozen, registry_v1());
+    assert_eq!(frozen["Foo"], registry_v1()["Foo"]);
+    assert_eq!(
+        frozen.stable_hash(),
+        FrozenRegistry::from(registry_v1()).stable_hash()
+    );
+    assert_ne!(
+        frozen.stable_hash(),
+        freeze_all(registry_v2()).stable_hash()
+    );
+
+    // Renaming a container changes the hash.
+    let mut registry = frozen.clone().into_inner();
+    let foo = registry.remove("Foo").unwrap();
+    registry.insert("Baz".into(), foo);
+    assert_ne!(frozen.stable_hash(), freeze_all(registry).stable_hash());
+    assert_ne!(
+        frozen.stable_hash(),
+        freeze_all(Registry::new()).stable_hash()
+    );
+}
+
+#[derive(Serialize, Deserialize)]
+enum Shape {
+    Point,
+    Circle { radius: u32 },
+    Line(Point, Point),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Point {
+    z: i64,
+    y: i64,
+    x: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Zoo(Vec<Shape>, Option<Point>);
+
+#[test]
+fn test_deterministic_registries() {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Zoo>().unwrap();
+    tracer.trace_simple_type::<Shape>().unwrap();
+    let registry1 = tracer.registry().unwrap();
+
+    // Trace in a different order, discovering the variants of `Shape` in reverse order.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let mut samples = Samples::new();
+    let line = Shape::Line(Point { z: 0, y: 0, x: 0 }, Point { z: 1, y: 1, x: 1 });
+    tracer.trace_value(&mut samples, &line).unwrap();
+    tracer
+        .trace_value(&mut samples, &Shape::Circle { radius: 1 })
+        .unwrap();
+    tracer.trace_value(&mut samples, &Shape::Point).unwrap();
+    tracer.trace_type::<Zoo>(&samples).unwrap();
+    tracer.trace_type::<Shape>(&samples).unwrap();
+    let registry2 = tracer.registry().unwrap();
+
+    assert_eq!(registry1, registry2);
+    assert_eq!(
+        serde_yaml::to_string(&registry1).unwrap(),
+        serde_yaml::to_string(&registry2).unwrap()
+    );
+    assert_eq!(
+        serde_json::to_vec(&registry1).unwrap(),
+        serde_json::to_vec(&registry2).unwrap()
+    );
+    assert_eq!(
+        bincode::serialize(&registry1).unwrap(),
+        bincode::serialize(&registry2).unwrap()
+    );
+
+    // Containers are sorted by name, variants by index, fields by declaration order.
+    assert_eq!(
+        registry1.keys().collect::<Vec<_>>(),
+        vec!["Point", "Shape", "Zoo"]
+    );
+    let ContainerFormat::Enum(variants) = &registry1["Shape"] else {
+        panic!("Shape should be an enum");
+    };
+    let names: Vec<_> = variants.values().map(|v| v.name.as_str()).collect();
+    assert_eq!(names, vec!["Point", "Circle", "Line"]);
+    let ContainerFormat::Struct(fields) = &registry1["Point"] else {
+        panic!("Point should be a struct");
+    };
+    let names: Vec<_> = fields.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["z", "y", "x"]);
+}
+
+#[test]
+fn test_deterministic_registries_with_failing_roots() {
+    // Fails after exploring `Shape`.
+    let failing = || {
+        TraceRoot::new("failing", |tracer, samples| {
+            tracer.trace_type::<Zoo>(samples)?;
+            Err(Error::Custom("failure".into()))
+        })
+    };
+    let trace = |roots: Vec<TraceRoot>| {
+        let (registry, errors) =
+            Tracer::new(TracerConfig::default()).trace_all(roots, &Samples::new());
+        assert_eq!(errors.len(), 1);
+        registry
+    };
+    let registry1 = trace(vec![
+        failing(),
+        TraceRoot::of::<Shape>(),
+        TraceRoot::of::<Point>(),
+    ]);
+    let registry2 = trace(vec![
+        TraceRoot::of::<Point>(),
+        TraceRoot::of::<Shape>(),
+        failing(),
+    ]);
+    assert_eq!(registry1, registry2);
+    assert_eq!(registry1.keys().collect::<Vec<_>>(), vec!["Point", "Shape"]);
+}
+
+#[test]
+fn test_validate_registry() {
+    use serde_reflection::registry::{validate, ValidationIssue};
+    use serde_reflection::VariantFormat;
+    use std::collections::BTreeMap;
+
+    let registry = registry_v1();
+    assert_eq!(validate(&registry), Vec::new());
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "Foo".into(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "a".into(),
+                value: Format::Seq(Box::new(Format::TypeName("Missing".into()))),
+            },
+            Named {
+                name: "a".into(),
+                value: Format::unknown(),
+            },
+            Named {
+                name: "".into(),
+                value: Format::U8,
+            },
+        ]),
+    );
+    let mut variants = BTreeMap::new();
+    variants.insert(
+        0,
+        Named {
+            name: "1st".into(),
+            value: VariantFormat::Unit,
+        },
+    );
+    registry.insert("Bar-Baz".into(), ContainerFormat::Enum(variants));
+
+    let path = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let issues = validate(&registry);
+    assert_eq!(
+        issues,
+        vec![
+            ValidationIssue::InvalidName {
+                path: path(&["Bar-Baz"])
+            },
+            ValidationIssue::InvalidName {
+                path: path(&["Bar-Baz", "1st"])
+            },
+            ValidationIssue::DanglingReference {
+                path: path(&["Foo", "a"]),
+                name: "Missing".into(),
+            },
+            ValidationIssue::DuplicateName {
+                path: path(&["Foo", "a"])
+            },
+            ValidationIssue::UnknownFormat {
+                path: path(&["Foo", "a"])
+            },
+            ValidationIssue::EmptyName {
+                path: path(&["Foo", ""])
+            },
+        ]
+    );
+    assert_eq!(
+        issues[2].to_string(),
+        "Foo::a: reference to missing container Missing"
+    );
+}
+
+#[derive(Serialize, Deserialize)]
+enum Tree {
+    Leaf(u32),
+    Node(Vec<Tree>, Option<v1::Foo>),
+}
+
+#[test]
+fn test_resolve_format() {
+    use serde_reflection::registry::{resolve, ResolvedContainer, ResolvedFormat, ResolvedVariant};
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let (format, _) = tracer.trace_simple_type::<Tree>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    let foo = ResolvedFormat::Container {
+        name: "Foo",
+        format: ResolvedContainer::Struct(vec![("a", ResolvedFormat::Primitive(&Format::U32))]),
+    };
+    assert_eq!(
+        resolve(&registry, &format),
+        ResolvedFormat::Container {
+            name: "Tree",
+            format: ResolvedContainer::Enum(vec![
+                (
+                    0,
+                    "Leaf",
+                    ResolvedVariant::NewType(Box::new(ResolvedFormat::Primitive(&Format::U32)))
+                ),
+                (
+                    1,
+                    "Node",
+                    ResolvedVariant::Tuple(vec![
+                        ResolvedFormat::Seq(Box::new(ResolvedFormat::Recursive("Tree"))),
+                        ResolvedFormat::Option(Box::new(foo)),
+                    ])
+                ),
+            ]),
+        }
+    );
+
+    let missing = Format::TypeName("Missing".into());
+    assert_eq!(
+        resolve(&registry, &missing),
+        ResolvedFormat::Missing("Missing")
+    );
+    assert_eq!(resolve(&registry, &format).container_name(), Some("Tree"));
+}
+
+mod inline {
+    use super::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Name(pub String);
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Wrapper {
+        pub inner: Name,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct List(pub Option<Box<List>>);
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Outer {
+        pub wrapper: Wrapper,
+        pub names: Vec<Name>,
+        pub list: List,
+        pub id: Id,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Id(pub u64);
+}
+
+#[test]
+fn test_inline_containers() {
+    use serde_reflection::registry::inline_containers;
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<inline::Outer>().unwrap();
+    let mut registry = tracer.registry().unwrap();
+
+    let inlined = inline_containers(&mut registry, |name, _| name != "Id");
+    assert_eq!(inlined, vec!["Name".to_string(), "Wrapper".to_string()]);
+    assert_eq!(
+        registry.keys().collect::<Vec<_>>(),
+        vec!["Id", "List", "Outer"]
+    );
+    assert_eq!(
+        registry.get("Outer").unwrap(),
+        &ContainerFormat::Struct(vec![
+            Named {
+                name: "wrapper".into(),
+                value: Format::Str,
+            },
+            Named {
+                name: "names".into(),
+                value: Format::Seq(Box::new(Format::Str)),
+            },
+            Named {
+                name: "list".into(),
+                value: Format::TypeName("List".into()),
+            },
+            Named {
+                name: "id".into(),
+                value: Format::TypeName("Id".into()),
+            },
+        ])
+    );
+}
+
+#[test]
+fn test_merge_registries() {
+    use serde_reflection::registry::merge;
+
+    #[derive(Serialize, Deserialize)]
+    enum Bar {
+        A,
+        B(v1::Foo),
+    }
+
+    // Trace each variant of `Bar` separately.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_value(&mut Samples::new(), &Bar::A).unwrap();
+    let mut registry = tracer.registry().unwrap();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer
+        .trace_value(&mut Samples::new(), &Bar::B(v1::Foo { a: 0 }))
+        .unwrap();
+    assert!(merge(&mut registry, tracer.registry().unwrap()).is_empty());
+    assert_eq!(registry, registry_v1());
+
+    // `Foo` is left unchanged in case of conflict.
+    assert_eq!(merge(&mut registry, registry_v2()), vec!["Foo".to_string()]);
+    assert_eq!(registry, registry_v1());
+}
+
+#[test]
+fn test_merge_conditional_registries() {
+    use serde_reflection::registry::merge_conditional;
+
+    #[derive(Serialize, Deserialize)]
+    enum Bar {
+        A,
+        B(v1::Foo),
+    }
+
+    // Only `Bar::A` is used without features.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_value(&mut Samples::new(), &Bar::A).unwrap();
+    let mut registry = tracer.registry().unwrap();
+    let mut conditions = Conditions::new();
+
+    let extra = "feature = \"extra\"";
+    assert!(merge_conditional(&mut registry, &mut conditions, registry_v1(), extra).is_empty());
+    assert_eq!(registry, registry_v1());
+    assert_eq!(conditions.condition("Bar"), None);
+    assert_eq!(conditions.condition("Foo"), Some(extra));
+
+    let other = "feature = \"other\"";
+    assert!(merge_conditional(&mut registry, &mut conditions, registry_v1(), other).is_empty());
+    assert!(merge_conditional(&mut registry, &mut conditions, registry_v1(), "test").is_empty());
+    assert_eq!(
+        conditions.condition("Foo"),
+        Some("any(feature = \"extra\", feature = \"other\", test)")
+    );
+
+    // Conflicting containers keep their condition.
+    let conflicts = merge_conditional(&mut registry, &mut conditions, registry_v2(), "v2");
+    assert_eq!(conflicts, vec!["Foo".to_string()]);
+    assert!(!conditions.condition("Foo").unwrap().contains("v2"));
+
+    // Select the containers of a configuration.
+    let selected = conditions.select(&registry, |condition| condition.contains("test"));
+    assert_eq!(selected, registry);
+    let selected = conditions.select(&registry, |_| false);
+    assert_eq!(selected.keys().collect::<Vec<_>>(), vec!["Bar"]);
+
+    // Conditions can be stored next to the registry.
+    let data = serde_json::to_string(&conditions).unwrap();
+    assert_eq!(conditions, serde_json::from_str(&data).unwrap());
+}
+
+#[test]
+fn test_shared_registry() {
+    use serde_reflection::shared::{SharedContainer, SharedRegistry, SharedVariant};
+    use std::rc::Rc;
+
+    #[derive(Serialize, Deserialize)]
+    struct Left {
+        data: Vec<BTreeMap<String, Vec<u8>>>,
+        size: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Right(Vec<BTreeMap<String, Vec<u8>>>, u64);
+
+    #[derive(Serialize, Deserialize)]
+    enum Either {
+        Left(Left),
+        Right(Right),
+        Raw(Vec<BTreeMap<String, Vec<u8>>>),
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Either>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    let shared = SharedRegistry::from(registry.clone());
+    assert_eq!(shared.len(), 3);
+    assert_eq!(shared.to_registry(), registry);
+    // The nested format, `u64`, and the two container names.
+    assert_eq!(shared.distinct_formats(), 4);
+    let (Some(SharedContainer::Struct(fields)), Some(SharedContainer::TupleStruct(formats))) =
+        (shared.get("Left"), shared.get("Right"))
+    else {
+        panic!("unexpected containers");
+    };
+    assert!(Rc::ptr_eq(&fields[0].value, &formats[0]));
+
+    // The serialized form is the one of the registry.
+    let yaml = serde_yaml::to_string(&shared).unwrap();
+    assert_eq!(yaml, serde_yaml::to_string(&registry).unwrap());
+    let json = serde_json::to_string(&registry).unwrap();
+    let mut loaded: SharedRegistry = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, shared);
+    assert_eq!(loaded.distinct_formats(), 4);
+
+    // Copy on write.
+    if let Some(SharedContainer::Enum(variants)) = loaded.get_mut("Either") {
+        if let SharedVariant::NewType(format) = &mut variants.get_mut(&2).unwrap().value {
+            *Rc::make_mut(format) = Format::Bytes;
+        }
+    }
+    assert_ne!(loaded, shared);
+    assert_eq!(loaded.to_container("Left"), registry.get("Left").cloned());
+}
+
+#[test]
+fn test_reachable_containers() {
+    use serde_reflection::registry::reachable;
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<inline::Outer>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    let filtered = reachable(&registry, ["Wrapper", "List", "Missing"]);
+    assert_eq!(
+        filtered.keys().collect::<Vec<_>>(),
+        vec!["List", "Name", "Wrapper"]
+    );
+    assert_eq!(reachable(&registry, ["Outer"]), registry);
+}
+
+#[test]
+fn test_container_ids() {
+    use serde_reflection::{
+        diff::RegistryDiff,
+        registry::{lookup, rename},
+        ContainerId,
+    };
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let (format, _) = tracer.trace_simple_type::<inline::Outer>().unwrap();
+    let registry = tracer.registry().unwrap();
+    let outer = ContainerId::of(&format).unwrap();
+    assert_eq!(outer, ContainerId::from("Outer"));
+    assert_eq!(ContainerId::of(&Format::U64), None);
+    assert!(matches!(
+        lookup(&registry, &outer),
+        Ok(ContainerFormat::Struct(_))
+    ));
+    assert!(matches!(
+        lookup(&registry, &"Missing".into()),
+        Err(Error::MissingContainer(name)) if name == "Missing"
+    ));
+
+    let mut renamed = registry.clone();
+    let name = rename(&mut renamed, &"Name".into(), "Label".into()).unwrap();
+    assert_eq!(name.to_string(), "Label");
+    assert!(lookup(&renamed, &"Name".into()).is_err());
+    assert_eq!(
+        lookup(&renamed, &"Wrapper".into()).unwrap(),
+        &ContainerFormat::Struct(vec![Named {
+            name: "inner".into(),
+            value: Format::TypeName("Label".into()),
+        }])
+    );
+    assert!(matches!(
+        rename(&mut renamed, &"Id".into(), "Outer".into()),
+        Err(Error::ContainerExists(name)) if name == "Outer"
+    ));
+    assert!(matches!(
+        rename(&mut renamed, &"Name".into(), "Other".into()),
+        Err(Error::MissingContainer(_))
+    ));
+
+    let diff = RegistryDiff::new(&registry, &renamed);
+    assert_eq!(diff.changes_of(&name).count(), 1);
+    assert_eq!(diff.changes_of(&"Wrapper".into()).count(), 1);
+    assert_eq!(diff.changes_of(&outer).count(), 1);
+    assert_eq!(diff.changes_of(&"Id".into()).count(), 0);
+
+    let json = serde_json::to_string(&outer).unwrap();
+    assert_eq!(json, r#""Outer""#);
+    assert_eq!(serde_json::from_str::<ContainerId>(&json).unwrap(), outer);
+}
+
+#[test]
+fn test_registry_entries() {
+    use serde_reflection::{
+        registry::{entries, entries_matching},
+        ContainerKind,
+    };
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<inline::Outer>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    assert_eq!(
+        entries(&registry).map(|(name, _)| name).collect::<Vec<_>>(),
+        vec!["Id", "List", "Name", "Outer", "Wrapper"]
+    );
+    assert_eq!(
+        entries_matching(&registry, ContainerKind::NewTypeStruct)
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>(),
+        vec!["Id", "List", "Name"]
+    );
+    assert_eq!(entries_matching(&registry, ContainerKind::Enum).count(), 0);
+}
+
+#[test]
+fn test_validate_type_params() {
+    use serde_reflection::registry::{validate, ValidationIssue};
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "Foo".into(),
+        ContainerFormat::Generic {
+            params: vec!["T".into()],
+            body: Box::new(ContainerFormat::TupleStruct(vec![
+                Format::TypeParam(0),
+                Format::TypeParam(1),
+            ])),
+        },
+    );
+    registry.insert(
+        "Bar".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::TypeParam(0))),
+    );
+    assert_eq!(
+        validate(&registry),
+        vec![
+            ValidationIssue::InvalidTypeParam {
+                path: vec!["Bar".into()],
+                index: 0
+            },
+            ValidationIssue::InvalidTypeParam {
+                path: vec!["Foo".into()],
+                index: 1
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_naming_conventions() {
+    use serde_reflection::registry::{apply_naming_convention, NamingConvention, NamingScope};
+    use serde_reflection::VariantFormat;
+
+    assert_eq!(
+        NamingConvention::SnakeCase.apply("HTTPServer_v2"),
+        "http_server_v2"
+    );
+    assert_eq!(NamingConvention::PascalCase.apply("foo_bar"), "FooBar");
+    assert_eq!(NamingConvention::CamelCase.apply("FooBar"), "fooBar");
+    assert_eq!(
+        NamingConvention::ScreamingSnakeCase.apply("fooBar2Baz"),
+        "FOO_BAR2_BAZ"
+    );
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "my_struct".into(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "fieldOne".into(),
+                value: Format::TypeName("my_enum".into()),
+            },
+            Named {
+                name: "field_two".into(),
+                value: Format::U8,
+            },
+        ]),
+    );
+    registry.insert(
+        "my_enum".into(),
+        ContainerFormat::Enum(
+            vec![(
+                0,
+                Named {
+                    name: "some_variant".into(),
+                    value: VariantFormat::Struct(vec![Named {
+                        name: "innerField".into(),
+                        value: Format::Str,
+                    }]),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        ),
+    );
+
+    let scope = NamingScope {
+        fields: false,
+        ..NamingScope::ALL
+    };
+    let mut renamed = registry.clone();
+    let renames =
+        apply_naming_convention(&mut renamed, NamingConvention::PascalCase, scope).unwrap();
+    assert_eq!(
+        renames.into_iter().collect::<Vec<_>>(),
+        vec![
+            (vec!["my_enum".to_string()], "MyEnum".to_string()),
+            (
+                vec!["my_enum".to_string(), "some_variant".to_string()],
+                "SomeVariant".to_string()
+            ),
+            (vec!["my_struct".to_string()], "MyStruct".to_string()),
+        ]
+    );
+    assert_eq!(
+        renamed.get("MyStruct").unwrap(),
+        &ContainerFormat::Struct(vec![
+            Named {
+                name: "fieldOne".into(),
+                value: Format::TypeName("MyEnum".into()),
+            },
+            Named {
+                name: "field_two".into(),
+                value: Format::U8,
+            },
+        ])
+    );
+
+    let renames =
+        apply_naming_convention(&mut registry, NamingConvention::SnakeCase, NamingScope::ALL)
+            .unwrap();
+    assert_eq!(
+        renames.get(&vec![
+            "my_enum".to_string(),
+            "some_variant".to_string(),
+            "innerField".to_string()
+        ]),
+        Some(&"inner_field".to_string())
+    );
+
+    // Names in conflict are reported and the registry is left unchanged.
+    registry.insert("MyStruct".into(), ContainerFormat::UnitStruct);
+    let copy = registry.clone();
+    assert_eq!(
+        apply_naming_convention(&mut registry, NamingConvention::SnakeCase, NamingScope::ALL),
+        Err(vec![vec!["my_struct".to_string()]])
+    );
+    assert_eq!(registry, copy);
+}
+
+#[test]
+fn test_registry_leaves() {
+    use serde_reflection::registry::{container_leaves, leaves};
+
+    #[derive(Serialize, Deserialize)]
+    struct Tx {
+        payload: Payload,
+        memo: Option<String>,
+        fees: BTreeMap<String, f32>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Payload {
+        Noop,
+        Transfer { amount: u64, to: [u8; 2] },
+        Batch(Vec<Payload>),
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Payload>().unwrap();
+    tracer.trace_simple_type::<Tx>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    let rendered: Vec<String> = leaves(&registry).iter().map(ToString::to_string).collect();
+    assert_eq!(
+        rendered,
+        vec![
+            "Tx.payload.Transfer.amount: U64",
+            "Tx.payload.Transfer.to: U8",
+            "Tx.payload.Batch: TypeName(\"Payload\")",
+            "Tx.memo: Str",
+            "Tx.fees.key: Str",
+            "Tx.fees.value: F32",
+        ]
+    );
+
+    let floats: Vec<Vec<String>> = leaves(&registry)
+        .into_iter()
+        .filter(|leaf| matches!(leaf.format, Format::F32 | Format::F64))
+        .map(|leaf| leaf.path)
+        .collect();
+    assert_eq!(floats, vec![vec!["Tx", "fees", "value"]]);
+
+    let leaves = container_leaves(&registry, "Payload");
+    assert_eq!(leaves.len(), 3);
+    assert_eq!(leaves[0].path, vec!["Payload", "Transfer", "amount"]);
+    assert!(container_leaves(&registry, "Missing").is_empty());
+}
+
+#[test]
+fn test_find_container() {
+    use serde_reflection::registry::{find, FindOptions};
+
+    let mut registry = Registry::new();
+    for name in [
+        "Header",
+        "net::Packet",
+        "disk::Packet",
+        "PACKET",
+        "v2::Header",
+    ] {
+        registry.insert(name.into(), ContainerFormat::UnitStruct);
+    }
+
+    // Exact names always win.
+    let exact = FindOptions::new();
+    assert_eq!(find(&registry, "Header", &exact), Ok("Header"));
+    assert_eq!(
+        find(&registry, "header", &exact),
+        Err(Error::MissingContainer("header".into()))
+    );
+
+    let lenient = FindOptions::lenient();
+    assert_eq!(find(&registry, "header", &lenient), Ok("Header"));
+    assert_eq!(find(&registry, "packet", &lenient), Ok("PACKET"));
+    assert_eq!(find(&registry, "net::packet", &lenient), Ok("net::Packet"));
+    let error = find(&registry, "Packet", &FindOptions::new().suffix(true)).unwrap_err();
+    assert_eq!(
+        error,
+        Error::AmbiguousName(
+            "Packet".into(),
+            vec!["disk::Packet".into(), "net::Packet".into()]
+        )
+    );
+    assert_eq!(
+        error.to_string(),
+        "Name Packet matches several containers: disk::Packet, net::Packet"
+    );
+
+    // Aliases are tried before the other rules.
+    let options = FindOptions::lenient().alias("Datagram", "net::Packet");
+    assert_eq!(find(&registry, "datagram", &options), Ok("net::Packet"));
+    let options = FindOptions::new().alias("Frame", "Missing");
+    assert!(find(&registry, "Frame", &options).is_err());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_registry_format_versions() {
+    use serde_reflection::{
+        registry::{self, VersionedRegistry, FORMAT_VERSION},
+        RegistryMetadata,
+    };
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "version".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::U32)),
+    );
+    registry.insert(
+        "registry".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Str)),
+    );
+
+    // Bare maps are version 0, even with suspicious container names.
+    let bytes = serde_json::to_vec(&registry).unwrap();
+    assert_eq!(registry::detect_version(&bytes).unwrap(), 0);
+    assert_eq!(registry::migrate_from(&bytes, 0).unwrap(), registry);
+
+    let versioned = VersionedRegistry::new(registry.clone());
+    assert_eq!(versioned.version, FORMAT_VERSION);
+    let bytes = serde_json::to_vec(&versioned).unwrap();
+    assert_eq!(registry::detect_version(&bytes).unwrap(), FORMAT_VERSION);
+    assert_eq!(
+        registry::migrate_from(&bytes, FORMAT_VERSION).unwrap(),
+        registry
+    );
+
+    // Version 1 had no metadata.
+    let bytes =
+        serde_json::to_vec(&serde_json::json!({ "version": 1, "registry": registry })).unwrap();
+    assert_eq!(registry::detect_version(&bytes).unwrap(), 1);
+    assert_eq!(registry::migrate_from(&bytes, 1).unwrap(), registry);
+
+    // The metadata is persisted along with the registry.
+    let mut metadata = RegistryMetadata::new();
+    metadata.add_provenance("version", "Root");
+    let versioned = VersionedRegistry::with_metadata(registry.clone(), metadata.clone());
+    let bytes = serde_json::to_vec(&versioned).unwrap();
+    assert_eq!(registry::detect_version(&bytes).unwrap(), FORMAT_VERSION);
+    assert_eq!(
+        registry::migrate_versioned_from(&bytes, FORMAT_VERSION).unwrap(),
+        versioned
+    );
+    let bytes = serde_json::to_vec(&VersionedRegistry::new(registry.clone())).unwrap();
+    assert!(!String::from_utf8_lossy(&bytes).contains("metadata"));
+
+    // Mismatched and future versions are rejected.
+    assert!(matches!(
+        registry::migrate_from(&bytes, 0),
+        Err(Error::InvalidRegistryFile(_))
+    ));
+    let bytes = br#"{"version": 1000, "registry": {}}"#;
+    assert_eq!(registry::detect_version(bytes).unwrap(), 1000);
+    assert!(matches!(
+        registry::migrate_from(bytes, 1000),
+        Err(Error::InvalidRegistryFile(_))
+    ));
+    assert!(registry::detect_version(b"[]").is_err());
+}
@@ -1,10 +1,14 @@
 // Copyright (c) Facebook, Inc. and its affiliates
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+#![cfg(feature = "trace")]
+
 use serde::{de::IntoDeserializer, Deserialize, Serialize};
+#[cfg(feature = "json")]
+use serde_reflection::JsonFixtures;
 use serde_reflection::{
-    ContainerFormat, Error, Format, FormatHolder, Named, Samples, Tracer, TracerConfig, Value,
-    VariantFormat,
+    Constraint, Constraints, ContainerFormat, Error, FloatPolicy, Format, FormatHolder, Named,
+    Samples, TraceCheckpoint, TraceEvent, TraceRoot, Tracer, TracerConfig, Value, VariantFormat,
 };
 use std::collections::BTreeMap;
 
@@ -249,14 +253,357 @@ fn test_trace_deserialization_with_custom_invariants() {
     );
 }
 
+#[test]
+fn test_lazy_samples() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut samples = Samples::new();
+    let counter = calls.clone();
+    samples.register_lazy("Name", move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Name("Bob".into())
+    });
+    samples.register_lazy("Unused", || -> Name { panic!("never needed") });
+    assert_eq!(samples.value("Name"), None);
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let (format, values) = tracer.trace_type::<Person>(&samples).unwrap();
+    assert_eq!(format, Format::TypeName("Person".into()));
+    assert_eq!(
+        values,
+        vec![
+            Person::NickName(Name("Bob".into())),
+            Person::FullName {
+                first: Name("Bob".into()),
+                last: Name("Bob".into()),
+            }
+        ]
+    );
+    // The value was constructed once and traced like with `trace_value`.
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(samples.value("Name"), Some(&Value::Str("Bob".into())));
+    assert_eq!(samples.value("Unused"), None);
+    assert_eq!(
+        tracer.registry().unwrap()["Name"],
+        ContainerFormat::NewTypeStruct(Box::new(Format::Str))
+    );
+
+    // Clones share the evaluated value.
+    let copy = samples.clone();
+    assert_eq!(copy, samples);
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_type::<Person>(&copy).unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Recorded values take precedence.
+    let mut samples = Samples::new();
+    samples.register_lazy("Name", || -> Name { panic!("shadowed") });
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer
+        .trace_value(&mut samples, &Name("Alice".into()))
+        .unwrap();
+    tracer.trace_type::<Person>(&samples).unwrap();
+}
+
+#[cfg(feature = "async")]
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    struct Unparker(std::thread::Thread);
+
+    impl std::task::Wake for Unparker {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = std::sync::Arc::new(Unparker(std::thread::current())).into();
+    let mut context = std::task::Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            std::task::Poll::Ready(output) => return output,
+            std::task::Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+/// A future that is pending once before returning a value.
+#[cfg(feature = "async")]
+struct Delayed<T>(Option<T>, bool);
+
+#[cfg(feature = "async")]
+impl<T: Unpin> std::future::Future for Delayed<T> {
+    type Output = T;
+
+    fn poll(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<T> {
+        let this = self.get_mut();
+        if !this.1 {
+            this.1 = true;
+            cx.waker().wake_by_ref();
+            return std::task::Poll::Pending;
+        }
+        std::task::Poll::Ready(this.0.take().unwrap())
+    }
+}
+
+#[test]
+#[cfg(feature = "async")]
+fn test_trace_type_async() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut samples = Samples::new();
+    let counter = calls.clone();
+    samples.register_async(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Delayed(Some(Name("Bob".into())), false)
+    });
+    samples.register_async(|| async { vec![Delayed(Some(7u64), false).await] });
+    // Providers are not called eagerly.
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+    assert_eq!(samples.pending_async(), 2);
+    assert_eq!(samples.value("Name"), None);
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let (format, values) = block_on(tracer.trace_type_async::<Person>(&mut samples)).unwrap();
+    assert_eq!(format, Format::TypeName("Person".into()));
+    assert_eq!(values[0], Person::NickName(Name("Bob".into())));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(samples.pending_async(), 0);
+    assert_eq!(samples.value("Name"), Some(&Value::Str("Bob".into())));
+
+    // Fetched values are kept for later traces.
+    let (_, values) = block_on(tracer.trace_type_async::<Person>(&mut samples)).unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert!(tracer.registry().unwrap().contains_key("Name"));
+}
+
+#[test]
+fn test_trace_all_collects_errors() {
+    let tracer = Tracer::new(TracerConfig::default());
+    let roots = vec![
+        TraceRoot::of::<Person>(),
+        TraceRoot::of::<E>(),
+        TraceRoot::of::<Name>(),
+    ];
+    assert!(roots[0].name().ends_with("Person"));
+    let (registry, errors) = tracer.trace_all(roots, &Samples::new());
+
+    // Failed roots are rolled back.
+    assert_eq!(registry.keys().collect::<Vec<_>>(), vec!["E"]);
+    assert_eq!(errors.len(), 2);
+    let invalid_name = Error::Custom("Failed to deserialize value: \"Invalid name \"".into());
+    match &errors[0] {
+        Error::RootFailed(name, error) => {
+            assert!(name.ends_with("Person"));
+            assert_eq!(**error, invalid_name);
+        }
+        error => panic!("unexpected error {error:?}"),
+    }
+    assert!(matches!(&errors[1], Error::RootFailed(name, _) if name.ends_with("Name")));
+
+    // With samples, every root succeeds.
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer
+        .trace_value(&mut samples, &Name("Bob".into()))
+        .unwrap();
+    let roots = vec![TraceRoot::of::<Person>(), TraceRoot::of::<E>()];
+    let (registry, errors) = tracer.trace_all(roots, &samples);
+    assert!(errors.is_empty());
+    assert_eq!(registry.len(), 3);
+}
+
+#[test]
+fn test_trace_all_rolls_back_enum_exploration() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    enum Shared {
+        X(u8),
+        Y,
+    }
+
+    #[derive(Debug)]
+    struct Bad;
+
+    impl<'de> Deserialize<'de> for Bad {
+        fn deserialize<D>(_deserializer: D) -> std::result::Result<Self, D::Error>
+        where
+            D: ::serde::Deserializer<'de>,
+        {
+            Err(<D::Error as ::serde::de::Error>::custom("bad"))
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Failing {
+        shared: Shared,
+        bad: Bad,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Shared>().unwrap();
+    let expected = tracer.registry().unwrap();
+
+    // The failing root visits `Shared` before failing. The later root is traced as if the
+    // failing one had not been attempted.
+    let tracer = Tracer::new(TracerConfig::default());
+    let (registry, errors) = tracer.trace_all(
+        [TraceRoot::of::<Failing>(), TraceRoot::of::<Shared>()],
+        &Samples::new(),
+    );
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], Error::RootFailed(name, _) if name.ends_with("Failing")));
+    assert_eq!(registry, expected);
+}
+
+#[test]
+fn test_trace_plan() {
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Wrapper {
+        inner: Option<E>,
+        person: Person,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Holder(E);
+
+    let tracer = Tracer::new(TracerConfig::default());
+    let roots = vec![
+        TraceRoot::of::<Wrapper>(),
+        TraceRoot::of::<Holder>(),
+        TraceRoot::of::<E>(),
+    ];
+    let plan = tracer.plan(roots, &Samples::new());
+
+    // The shared enum comes first and the root failing without samples comes last.
+    let names: Vec<_> = plan
+        .names()
+        .map(|name| name.rsplit("::").next().unwrap())
+        .collect();
+    assert_eq!(names, vec!["E", "Holder", "Wrapper"]);
+    let name = plan.names().nth(1).unwrap().to_string();
+    assert_eq!(
+        plan.containers(&name).unwrap().collect::<Vec<_>>(),
+        vec!["E", "Holder"]
+    );
+    assert!(plan.containers("Missing").is_none());
+
+    let mut tracer = tracer;
+    let statuses = tracer.execute(plan, &Samples::new());
+    assert_eq!(statuses.len(), 3);
+    assert_eq!(statuses[0].new_containers, vec!["E"]);
+    assert_eq!(statuses[0].error, None);
+    assert_eq!(statuses[1].new_containers, vec!["Holder"]);
+    assert!(statuses[2].new_containers.is_empty());
+    assert!(statuses[2].error.is_some());
+    let registry = tracer.registry().unwrap();
+    assert_eq!(registry.keys().collect::<Vec<_>>(), vec!["E", "Holder"]);
+}
+
+#[test]
+fn test_trace_plan_saves_the_passes_of_failing_roots() {
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum Shared {
+        A,
+        B(u8),
+        C(String),
+        D { x: u16 },
+        E(Vec<u32>),
+    }
+
+    // Explores `Shared`, then fails.
+    let roots = || {
+        vec![
+            TraceRoot::new("failing", |tracer, samples| {
+                tracer.trace_type::<Shared>(samples)?;
+                Err(Error::Custom("failure".into()))
+            }),
+            TraceRoot::of::<Shared>(),
+        ]
+    };
+    let passes = |tracer: &Tracer| -> usize {
+        tracer
+            .profile()
+            .roots
+            .values()
+            .map(|profile| profile.passes)
+            .sum()
+    };
+    let config = TracerConfig::default().record_profile(true);
+
+    // Count the passes of `trace_all` with an extra root, since it consumes the tracer.
+    let counted = Arc::new(Mutex::new(0));
+    let counter = counted.clone();
+    let mut all_roots = roots();
+    all_roots.push(TraceRoot::new("count", move |tracer, _| {
+        *counter.lock().unwrap() = passes(tracer);
+        Ok(())
+    }));
+    let (registry, errors) = Tracer::new(config.clone()).trace_all(all_roots, &Samples::new());
+    assert_eq!(errors.len(), 1);
+    let trace_all_passes = *counted.lock().unwrap();
+
+    // The failing root comes last and finds `Shared` already explored. Planning costs one
+    // pass per root.
+    let mut tracer = Tracer::new(config);
+    let plan = tracer.plan(roots(), &Samples::new());
+    assert_eq!(
+        plan.names().collect::<Vec<_>>(),
+        vec![std::any::type_name::<Shared>(), "failing"]
+    );
+    let statuses = tracer.execute(plan, &Samples::new());
+    assert!(statuses[1].error.is_some());
+    let plan_passes = passes(&tracer) + 2;
+    assert!(
+        plan_passes < trace_all_passes,
+        "{plan_passes} passes with a plan, {trace_all_passes} without"
+    );
+    assert_eq!(tracer.registry().unwrap(), registry);
+}
+
 mod foo {
     #[derive(super::Serialize)]
     pub struct A;
+
+    #[derive(super::Serialize, super::Deserialize, Debug)]
+    pub enum B {
+        X,
+    }
+
+    #[derive(super::Serialize, super::Deserialize, Debug)]
+    pub enum C {
+        X,
+    }
 }
 
 mod bar {
     #[derive(super::Serialize)]
     pub struct A(pub u32);
+
+    #[derive(super::Serialize, super::Deserialize, Debug)]
+    pub enum B {
+        X,
+        Y,
+    }
+
+    #[derive(super::Serialize, super::Deserialize, Debug)]
+    pub enum C {
+        X,
+    }
 }
 
 #[test]
@@ -268,6 +615,43 @@ fn test_name_clash_not_supported() {
     assert!(tracer.trace_value(&mut samples, &foo::A).is_ok());
     // but format have to match.
     assert!(tracer.trace_value(&mut samples, &bar::A(0)).is_err());
+
+    // Enums with the same name but different variants are not merged.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<foo::B>().unwrap();
+    assert_eq!(
+        tracer.trace_simple_type::<bar::B>().unwrap_err(),
+        Error::NameClash("B".into())
+    );
+    // Enums with the same variants are merged, like structs.
+    tracer.trace_simple_type::<foo::C>().unwrap();
+    tracer.trace_simple_type::<bar::C>().unwrap();
+    assert_eq!(tracer.registry().unwrap().len(), 2);
+}
+
+#[test]
+fn test_borrowed_and_owned_enums_with_the_same_name() {
+    #[derive(Serialize, Deserialize)]
+    enum Msg {
+        Text(String),
+        Ping,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Msg")]
+    enum MsgRef<'a> {
+        Text(&'a str),
+        Ping,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Msg>().unwrap();
+    let expected = tracer.registry().unwrap();
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<MsgRef>().unwrap();
+    tracer.trace_simple_type::<Msg>().unwrap();
+    assert_eq!(tracer.registry().unwrap(), expected);
 }
 
 #[test]
@@ -281,17 +665,15 @@ fn test_borrowed_slice() {
 
     let (format, value) = tracer.trace_value(&mut samples, &Borrowed(&bytes)).unwrap();
     assert_eq!(format, Format::TypeName("Borrowed".into()));
-    // Slice was traced and serialized as a sequence.
-    assert_eq!(value, Value::Seq(vec![Value::U8(1); 4]));
+    // Slices serialize as sequences but can only deserialize as bytes, so they are traced
+    // as bytes in both directions.
+    assert_eq!(value, Value::Bytes(bytes.to_vec()));
 
-    // Unfortunately, borrowed slices can only de-serialize as bytes.
+    let (_, values) = tracer.trace_type::<Borrowed>(&samples).unwrap();
+    assert_eq!(values, vec![Borrowed(&bytes)]);
     assert_eq!(
-        tracer.trace_type::<Borrowed>(&samples),
-        Err(Error::UnexpectedDeserializationFormat(
-            "Borrowed",
-            ContainerFormat::NewTypeStruct(Box::new(Format::Seq(Box::new(Format::U8)))),
-            "bytes"
-        )),
+        tracer.registry().unwrap()["Borrowed"],
+        ContainerFormat::NewTypeStruct(Box::new(Format::Bytes))
     );
 }
 
@@ -320,6 +702,91 @@ fn test_borrowed_bytes() {
     );
 }
 
+#[test]
+fn test_byte_formats() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Payload<'a> {
+        plain: Vec<u8>,
+        #[serde(with = "serde_bytes")]
+        annotated: Vec<u8>,
+        buffer: serde_bytes::ByteBuf,
+        slice: Option<&'a [u8]>,
+        nested: Vec<Vec<u8>>,
+    }
+
+    let value = Payload {
+        plain: vec![1],
+        annotated: vec![2],
+        buffer: serde_bytes::ByteBuf::from(vec![3]),
+        slice: Some(&[4]),
+        nested: vec![vec![5]],
+    };
+    let u8_seq = || Format::Seq(Box::new(Format::U8));
+    let fields = |plain: Format, nested: Format| {
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "plain".into(),
+                value: plain,
+            },
+            Named {
+                name: "annotated".into(),
+                value: Format::Bytes,
+            },
+            Named {
+                name: "buffer".into(),
+                value: Format::Bytes,
+            },
+            Named {
+                name: "slice".into(),
+                value: Format::Option(Box::new(Format::Bytes)),
+            },
+            Named {
+                name: "nested".into(),
+                value: nested,
+            },
+        ])
+    };
+
+    let values = |plain: Value, nested: Value| {
+        Value::Seq(vec![
+            plain,
+            Value::Bytes(vec![2]),
+            Value::Bytes(vec![3]),
+            Value::Option(Some(Box::new(Value::Bytes(vec![4])))),
+            Value::Seq(vec![nested]),
+        ])
+    };
+
+    // Serialization and deserialization agree, and traced values agree with the formats.
+    for (config, expected, expected_value) in [
+        (
+            TracerConfig::default(),
+            fields(u8_seq(), Format::Seq(Box::new(u8_seq()))),
+            values(
+                Value::Seq(vec![Value::U8(1)]),
+                Value::Seq(vec![Value::U8(5)]),
+            ),
+        ),
+        (
+            TracerConfig::default().u8_seqs_as_bytes(true),
+            fields(Format::Bytes, Format::Seq(Box::new(Format::Bytes))),
+            values(Value::Bytes(vec![1]), Value::Bytes(vec![5])),
+        ),
+    ] {
+        let mut samples = Samples::new();
+        let mut tracer = Tracer::new(config);
+        let (_, traced) = tracer.trace_value(&mut samples, &value).unwrap();
+        assert_eq!(traced, expected_value);
+        // Traced values can be deserialized again.
+        assert_eq!(
+            Payload::deserialize(traced.into_deserializer()).unwrap(),
+            value
+        );
+        tracer.trace_type_once::<Payload>(&samples).unwrap();
+        assert_eq!(tracer.registry().unwrap()["Payload"], expected);
+    }
+}
+
 #[test]
 fn test_trace_deserialization_with_recursive_types() {
     #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
@@ -460,6 +927,121 @@ fn test_repeated_tracing() {
     );
 }
 
+mod chain {
+    use super::{BTreeMap, ContainerFormat, Deserialize, Format, Named, Serialize, VariantFormat};
+    use serde_reflection::Registry;
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+    pub enum Leaf {
+        A(u8),
+        B,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+    pub struct Inner(pub Option<Box<Leaf>>);
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+    pub struct Wrapper(pub Inner);
+
+    pub fn value() -> Wrapper {
+        Wrapper(Inner(Some(Box::new(Leaf::A(1)))))
+    }
+
+    /// Every level of the chain is a container of its own.
+    pub fn registry() -> Registry {
+        let variants = BTreeMap::from([
+            (
+                0,
+                Named {
+                    name: "A".into(),
+                    value: VariantFormat::NewType(Box::new(Format::U8)),
+                },
+            ),
+            (
+                1,
+                Named {
+                    name: "B".into(),
+                    value: VariantFormat::Unit,
+                },
+            ),
+        ]);
+        Registry::from([
+            ("Leaf".into(), ContainerFormat::Enum(variants)),
+            (
+                "Inner".into(),
+                ContainerFormat::NewTypeStruct(Box::new(Format::Option(Box::new(
+                    Format::TypeName("Leaf".into()),
+                )))),
+            ),
+            (
+                "Wrapper".into(),
+                ContainerFormat::NewTypeStruct(Box::new(Format::TypeName("Inner".into()))),
+            ),
+        ])
+    }
+}
+
+#[test]
+fn test_newtype_chains() {
+    use chain::{Inner, Leaf, Wrapper};
+    use serde_reflection::registry::inline_containers;
+
+    // The outer newtype first.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Wrapper>().unwrap();
+    tracer.trace_simple_type::<Leaf>().unwrap();
+    assert_eq!(tracer.registry().unwrap(), chain::registry());
+
+    // The inner levels first.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Leaf>().unwrap();
+    tracer.trace_simple_type::<Inner>().unwrap();
+    tracer.trace_simple_type::<Wrapper>().unwrap();
+    assert_eq!(tracer.registry().unwrap(), chain::registry());
+
+    // A value first, then its samples are used.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let mut samples = Samples::new();
+    tracer.trace_value(&mut samples, &chain::value()).unwrap();
+    tracer.trace_type::<Leaf>(&samples).unwrap();
+    let (_, values) = tracer.trace_type::<Wrapper>(&samples).unwrap();
+    assert_eq!(values, vec![chain::value()]);
+    let mut registry = tracer.registry().unwrap();
+    assert_eq!(registry, chain::registry());
+
+    // Collapsing the chain is an explicit transform.
+    assert_eq!(
+        inline_containers(&mut registry, |name, _| name == "Inner"),
+        vec!["Inner".to_string()]
+    );
+    assert_eq!(
+        registry["Wrapper"],
+        ContainerFormat::NewTypeStruct(Box::new(Format::Option(Box::new(Format::TypeName(
+            "Leaf".into()
+        )))))
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_newtype_chains_from_loaded_samples() {
+    use chain::{Leaf, Wrapper};
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let mut samples = Samples::new();
+    tracer.trace_value(&mut samples, &chain::value()).unwrap();
+    let mut data = Vec::new();
+    samples.save(&mut data).unwrap();
+    let loaded = Samples::load(data.as_slice()).unwrap();
+
+    // The levels of the sample are recorded by a tracer that has not seen them before.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let (_, values) = tracer.trace_type::<Wrapper>(&loaded).unwrap();
+    assert_eq!(values, vec![chain::value()]);
+    tracer.trace_type::<Leaf>(&loaded).unwrap();
+    assert_eq!(tracer.registry().unwrap(), chain::registry());
+}
+
 #[test]
 fn test_default_value_for_primitive_types() {
     let config = TracerConfig::default()
@@ -504,3 +1086,2746 @@ fn test_default_value_for_primitive_types() {
     assert_eq!(format, Format::Str);
     assert_eq!(value, "A borrowed str");
 }
+
+#[test]
+fn test_trace_deserialization_with_seed() {
+    use serde::de::{DeserializeSeed, Deserializer, EnumAccess, VariantAccess, Visitor};
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Scaled {
+        Zero,
+        Value(u64),
+    }
+
+    // A seed carrying a multiplier known only at runtime.
+    #[derive(Clone, Copy)]
+    struct Scale(u64);
+
+    impl<'de> DeserializeSeed<'de> for Scale {
+        type Value = Scaled;
+
+        fn deserialize<D>(self, deserializer: D) -> std::result::Result<Scaled, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct ScaledVisitor(u64);
+
+            impl<'de> Visitor<'de> for ScaledVisitor {
+                type Value = Scaled;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("enum Scaled")
+                }
+
+                fn visit_enum<A>(self, data: A) -> std::result::Result<Scaled, A::Error>
+                where
+                    A: EnumAccess<'de>,
+                {
+                    #[derive(Deserialize)]
+                    enum Tag {
+                        Zero,
+                        Value,
+                    }
+                    match data.variant()? {
+                        (Tag::Zero, variant) => {
+                            variant.unit_variant()?;
+                            Ok(Scaled::Zero)
+                        }
+                        (Tag::Value, variant) => {
+                            let x = variant.newtype_variant::<u64>()?;
+                            Ok(Scaled::Value(x * self.0))
+                        }
+                    }
+                }
+            }
+
+            deserializer.deserialize_enum("Scaled", &["Zero", "Value"], ScaledVisitor(self.0))
+        }
+    }
+
+    let samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default().default_u64_value(7));
+
+    let (format, value) = tracer
+        .trace_type_once_with_seed(&samples, Scale(3))
+        .unwrap();
+    assert_eq!(format, Format::TypeName("Scaled".into()));
+    assert_eq!(value, Scaled::Zero);
+
+    // The seed is threaded through every pass until all variants are known.
+    let (format, values) = tracer.trace_type_with_seed(&samples, Scale(3)).unwrap();
+    assert_eq!(format, Format::TypeName("Scaled".into()));
+    assert_eq!(values, vec![Scaled::Zero, Scaled::Value(21)]);
+
+    let registry = tracer.registry().unwrap();
+    let mut variants = BTreeMap::new();
+    variants.insert(
+        0,
+        Named {
+            name: "Zero".into(),
+            value: VariantFormat::Unit,
+        },
+    );
+    variants.insert(
+        1,
+        Named {
+            name: "Value".into(),
+            value: VariantFormat::NewType(Box::new(Format::U64)),
+        },
+    );
+    assert_eq!(
+        registry.get("Scaled").unwrap(),
+        &ContainerFormat::Enum(variants)
+    );
+}
+
+#[test]
+fn test_provenance_of_containers() {
+    #[derive(Serialize, Deserialize)]
+    struct Shared(u32);
+
+    #[derive(Serialize, Deserialize)]
+    struct Left {
+        shared: Shared,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Right {
+        A(Shared),
+        B(Vec<Shared>),
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default().record_provenance(true));
+    tracer.trace_simple_type::<Left>().unwrap();
+    tracer.trace_simple_type::<Right>().unwrap();
+    tracer.trace_simple_type::<Vec<Shared>>().unwrap();
+
+    assert_eq!(tracer.provenance("Left"), vec!["Left".to_string()]);
+    assert_eq!(tracer.provenance("Right"), vec!["Right".to_string()]);
+    // Non-container roots are identified by their Rust type name.
+    let roots = tracer.provenance("Shared");
+    assert_eq!(roots.len(), 3);
+    assert_eq!(roots[..2], ["Left".to_string(), "Right".to_string()]);
+    assert!(roots[2].contains("Vec"));
+    assert!(tracer.provenance("Unknown").is_empty());
+
+    // The provenance outlives the tracer in the metadata of the registry.
+    let (registry, metadata) = tracer.registry_with_metadata().unwrap();
+    for name in registry.keys() {
+        assert!(!metadata.provenance(name).is_empty());
+    }
+    assert_eq!(metadata.provenance("Shared"), roots);
+
+    // Provenance is not recorded by default.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Left>().unwrap();
+    assert!(tracer.provenance("Shared").is_empty());
+}
+
+#[test]
+fn test_profile_of_containers() {
+    let config = TracerConfig::default().record_profile(true);
+    let mut tracer = Tracer::new(config);
+    let mut samples = Samples::new();
+    tracer
+        .trace_value(&mut samples, &Name("Bob".into()))
+        .unwrap();
+    tracer.trace_type::<Person>(&samples).unwrap();
+
+    let profile = tracer.profile();
+    // `trace_type` needs several passes to discover the variants of `Person`.
+    assert_eq!(profile.roots["Person"].passes, 4);
+    assert_eq!(profile.roots["Name"].passes, 1);
+    assert_eq!(profile.containers["Person"].passes, 4);
+    assert_eq!(profile.containers["Name"].passes, 5);
+    assert_eq!(
+        profile.containers["Person"].time,
+        profile.roots["Person"].time
+    );
+    // The sample of `Name` is used for every occurrence of `Name` during deserialization.
+    assert_eq!(profile.containers["Name"].samples_used, 6);
+    assert_eq!(profile.containers["Person"].samples_used, 0);
+    assert_eq!(profile.slowest_containers().len(), 2);
+
+    // Profiling is disabled by default.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<E>().unwrap();
+    assert_eq!(tracer.profile(), Default::default());
+}
+
+#[test]
+fn test_trace_events() {
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper(u16);
+
+    #[derive(Serialize, Deserialize)]
+    enum Choice {
+        A(Wrapper),
+        B,
+    }
+
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_value(&mut samples, &Wrapper(3)).unwrap();
+    assert!(tracer.events().is_empty());
+
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default().record_events(true));
+    tracer.trace_value(&mut samples, &Wrapper(3)).unwrap();
+    assert_eq!(
+        tracer.events(),
+        &[
+            TraceEvent::SerializerCall {
+                method: "serialize_newtype_struct"
+            },
+            TraceEvent::SerializerCall {
+                method: "serialize_u16"
+            },
+        ]
+    );
+
+    tracer.trace_type::<Choice>(&samples).unwrap();
+    let events = &tracer.events()[2..];
+    assert_eq!(
+        events[..3],
+        [
+            TraceEvent::VisitorHit {
+                method: "deserialize_enum"
+            },
+            TraceEvent::VariantAdvanced {
+                enum_name: "Choice".into(),
+                name: Some("A".into()),
+                index: None,
+            },
+            TraceEvent::VisitorHit {
+                method: "deserialize_newtype_struct"
+            },
+        ]
+    );
+    // The recorded value of the newtype struct is used instead of a default value.
+    assert!(events.contains(&TraceEvent::SampleConsulted {
+        container: "Wrapper".into()
+    }));
+    let advances: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            TraceEvent::VariantAdvanced { name, index, .. } => Some((name.as_deref(), *index)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        advances,
+        vec![
+            (Some("A"), None),
+            (Some("B"), None),
+            (None, Some(0)),
+            (None, Some(1))
+        ]
+    );
+}
+
+#[test]
+fn test_nested_enum_passes_are_linear() {
+    #[derive(Serialize, Deserialize)]
+    enum Inner {
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Outer {
+        A(Inner),
+        B(Inner),
+        C(Inner),
+        D(Inner),
+        E(Inner),
+        F(Inner),
+        G(Inner),
+        H(Inner),
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default().record_profile(true));
+    tracer.trace_simple_type::<Outer>().unwrap();
+    tracer.trace_simple_type::<Inner>().unwrap();
+    // Variants are first visited by name, then by index. Already explored variants are
+    // skipped, so the number of passes is linear in the number of variants.
+    let profile = tracer.profile();
+    assert_eq!(profile.roots["Outer"].passes, 16);
+    assert!(profile.roots["Inner"].passes <= 16);
+    // Nested enums are visited once per pass of the enclosing root.
+    assert_eq!(profile.containers["Inner"].passes, 32);
+    tracer.registry().unwrap();
+}
+
+#[test]
+fn test_tracer_poisoned_by_panic() {
+    #[derive(Serialize)]
+    struct Panicky;
+
+    impl<'de> Deserialize<'de> for Panicky {
+        fn deserialize<D>(_deserializer: D) -> std::result::Result<Self, D::Error>
+        where
+            D: ::serde::Deserializer<'de>,
+        {
+            panic!("cannot deserialize")
+        }
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<E>().unwrap();
+    assert!(!tracer.is_poisoned());
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        tracer.trace_simple_type::<Panicky>()
+    }));
+    assert!(result.is_err());
+    assert!(tracer.is_poisoned());
+    assert_eq!(
+        tracer.trace_simple_type::<E>().unwrap_err(),
+        Error::TracerPoisoned
+    );
+    assert_eq!(tracer.registry().unwrap_err(), Error::TracerPoisoned);
+}
+
+#[test]
+fn test_chunked_enum_exploration() {
+    let mut tracer = Tracer::new(TracerConfig::default().max_passes_per_trace(3));
+    let samples = Samples::new();
+    let mut values = Vec::new();
+    let mut calls = 0;
+    loop {
+        let (format, chunk) = tracer.trace_type::<E>(&samples).unwrap();
+        assert_eq!(format, Format::TypeName("E".into()));
+        values.extend(chunk);
+        calls += 1;
+        if !tracer.is_incomplete_enum("E") {
+            break;
+        }
+    }
+    assert!(calls > 1);
+    assert_eq!(
+        values,
+        vec![
+            E::Unit,
+            E::Newtype(0),
+            E::Tuple(0, Some(false)),
+            E::Struct { a: 0 },
+            E::NewTupleArray((0, 0, 0))
+        ]
+    );
+    let registry = tracer.registry().unwrap();
+    let ContainerFormat::Enum(variants) = &registry["E"] else {
+        panic!("E should be an enum");
+    };
+    assert_eq!(variants.len(), 5);
+}
+
+#[test]
+fn test_enum_pass_bound() {
+    use serde::de::{Deserializer, EnumAccess, VariantAccess, Visitor};
+
+    // Each pass decreases the estimate, and at most two passes per variant name are needed.
+    let mut tracer = Tracer::new(TracerConfig::default().max_passes_per_trace(1));
+    let samples = Samples::new();
+    assert_eq!(tracer.estimated_remaining_passes("E"), None);
+    let mut calls = 0;
+    let mut previous = usize::MAX;
+    loop {
+        tracer.trace_type::<E>(&samples).unwrap();
+        calls += 1;
+        let remaining = tracer.estimated_remaining_passes("E").unwrap();
+        assert!(remaining < previous);
+        if remaining == 0 {
+            assert!(!tracer.is_incomplete_enum("E"));
+            break;
+        }
+        previous = remaining;
+    }
+    // Derived implementations list aliases among the variant names, hence 6 names for `E`.
+    assert!(calls <= 12);
+
+    // A Deserialize implementation mapping the indices 0 and 1 to the same variant never
+    // reveals the index of `B`.
+    #[derive(Serialize, Debug)]
+    enum Aliased {
+        A,
+        B,
+    }
+
+    impl<'de> Deserialize<'de> for Aliased {
+        fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct Tag(bool);
+
+            impl<'de> Deserialize<'de> for Tag {
+                fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    struct TagVisitor;
+
+                    impl<'de> Visitor<'de> for TagVisitor {
+                        type Value = Tag;
+
+                        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                            f.write_str("variant of Aliased")
+                        }
+
+                        fn visit_u64<E>(self, index: u64) -> std::result::Result<Tag, E> {
+                            Ok(Tag(index >= 2))
+                        }
+
+                        fn visit_str<E>(self, name: &str) -> std::result::Result<Tag, E> {
+                            Ok(Tag(name == "B"))
+                        }
+                    }
+
+                    deserializer.deserialize_identifier(TagVisitor)
+                }
+            }
+
+            struct AliasedVisitor;
+
+            impl<'de> Visitor<'de> for AliasedVisitor {
+                type Value = Aliased;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("enum Aliased")
+                }
+
+                fn visit_enum<A>(self, data: A) -> std::result::Result<Aliased, A::Error>
+                where
+                    A: EnumAccess<'de>,
+                {
+                    let (Tag(is_b), variant) = data.variant()?;
+                    variant.unit_variant()?;
+                    Ok(if is_b { Aliased::B } else { Aliased::A })
+                }
+            }
+
+            deserializer.deserialize_enum("Aliased", &["A", "B"], AliasedVisitor)
+        }
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    assert_eq!(
+        tracer.trace_simple_type::<Aliased>().unwrap_err(),
+        Error::NoConvergence("Aliased".into(), 4)
+    );
+}
+
+#[test]
+fn test_skipped_variants() {
+    #[derive(Serialize, PartialEq, Eq, Debug)]
+    struct Handle(u64);
+
+    impl<'de> Deserialize<'de> for Handle {
+        fn deserialize<D>(_deserializer: D) -> std::result::Result<Self, D::Error>
+        where
+            D: ::serde::Deserializer<'de>,
+        {
+            Err(<D::Error as ::serde::de::Error>::custom(
+                "handles cannot be synthesized",
+            ))
+        }
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    enum Resource {
+        Local(Handle),
+        Name(String),
+        Remote { handle: Handle },
+        Empty,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    assert!(tracer.trace_simple_type::<Resource>().is_err());
+
+    let config = TracerConfig::default()
+        .skip_variant("Resource", "Local")
+        .skip_variant("Resource", "Remote");
+    let mut tracer = Tracer::new(config);
+    let (_, values) = tracer.trace_simple_type::<Resource>().unwrap();
+    assert_eq!(values, vec![Resource::Name(String::new()), Resource::Empty]);
+    let registry = tracer.registry().unwrap();
+    let ContainerFormat::Enum(variants) = &registry["Resource"] else {
+        panic!("Resource should be an enum");
+    };
+    let names: Vec<_> = variants
+        .iter()
+        .map(|(index, variant)| (*index, variant.name.as_str()))
+        .collect();
+    assert_eq!(
+        names,
+        vec![(0, "Local"), (1, "Name"), (2, "Remote"), (3, "Empty")]
+    );
+    // Skipped variants are recorded as unit variants, unless declared.
+    assert_eq!(variants[&0].value, VariantFormat::Unit);
+    assert_eq!(variants[&2].value, VariantFormat::Unit);
+
+    let config = TracerConfig::default()
+        .skip_variant("Resource", "Local")
+        .skip_variant("Resource", "Remote")
+        .declare_variant(
+            "Resource",
+            0,
+            "Local",
+            VariantFormat::NewType(Box::new(Format::TypeName("Handle".into()))),
+        );
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Resource>().unwrap();
+    let registry = tracer.registry().unwrap();
+    let ContainerFormat::Enum(variants) = &registry["Resource"] else {
+        panic!("Resource should be an enum");
+    };
+    assert_eq!(
+        variants[&0].value,
+        VariantFormat::NewType(Box::new(Format::TypeName("Handle".into())))
+    );
+    assert_eq!(variants[&2].value, VariantFormat::Unit);
+}
+
+#[test]
+fn test_uninhabited_types() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    enum Void {}
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    enum Message {
+        Never(Void),
+        Text(String),
+        Pair(u8, Void),
+        Maybe(Option<Void>),
+        List(Vec<Void>),
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    enum Impossible {
+        A(Void),
+        B { void: Void },
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    assert_eq!(
+        tracer.trace_simple_type::<Void>().unwrap_err(),
+        Error::Uninhabited("Void".into())
+    );
+    let (_, values) = tracer.trace_simple_type::<Message>().unwrap();
+    assert_eq!(
+        values,
+        vec![
+            Message::Text(String::new()),
+            Message::Maybe(None),
+            Message::List(Vec::new())
+        ]
+    );
+    assert_eq!(
+        tracer.trace_simple_type::<Impossible>().unwrap_err(),
+        Error::Uninhabited("Impossible".into())
+    );
+    let registry = tracer.registry().unwrap();
+    assert_eq!(registry["Void"], ContainerFormat::Enum(BTreeMap::new()));
+    let ContainerFormat::Enum(variants) = &registry["Message"] else {
+        panic!("Message should be an enum");
+    };
+    let names: Vec<_> = variants
+        .iter()
+        .map(|(index, variant)| (*index, variant.name.as_str()))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            (0, "Never"),
+            (1, "Text"),
+            (2, "Pair"),
+            (3, "Maybe"),
+            (4, "List")
+        ]
+    );
+    assert_eq!(
+        variants[&2].value,
+        VariantFormat::Tuple(vec![Format::U8, Format::TypeName("Void".into())])
+    );
+    let ContainerFormat::Enum(variants) = &registry["Impossible"] else {
+        panic!("Impossible should be an enum");
+    };
+    assert_eq!(variants.len(), 2);
+
+    let mut out = Vec::new();
+    serde_reflection::export::Exporters::default()
+        .export("docs", &registry, &mut out)
+        .unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .contains("<p>Uninhabited enum: no value can be encoded.</p>"));
+
+    // Sequences of uninhabited elements are empty, even if a length is requested.
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Voids {
+        xs: Vec<Void>,
+    }
+
+    let config = TracerConfig::default().seq_sample_len_at(["Voids", "xs"], 1);
+    let mut tracer = Tracer::new(config);
+    let (_, values) = tracer.trace_simple_type::<Voids>().unwrap();
+    assert_eq!(values, vec![Voids { xs: Vec::new() }]);
+}
+
+#[test]
+fn test_enums_with_large_variants() {
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[rustfmt::skip]
+    enum Large {
+        Unit,
+        Tuple(u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8),
+        Struct {
+            f0: u8, f1: u8, f2: u8, f3: u8, f4: u8, f5: u8, f6: u8, f7: u8, f8: u8, f9: u8,
+            f10: u8, f11: u8, f12: u8, f13: u8, f14: u8, f15: u8, f16: u8, f17: u8, f18: u8,
+            f19: u16,
+        },
+        Nested(Box<Large>, Option<Large2>),
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    #[rustfmt::skip]
+    enum Large2 {
+        A(u32, u32, u32, u32, u32, u32, u32, u32, u32, u32, u32, u32, u32, u32, u32, u32, u32),
+        B {
+            g0: bool, g1: bool, g2: bool, g3: bool, g4: bool, g5: bool, g6: bool, g7: bool,
+            g8: bool, g9: bool, g10: bool, g11: bool, g12: bool, g13: bool, g14: bool,
+            g15: bool, g16: bool,
+        },
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Large>().unwrap();
+    tracer.trace_simple_type::<Large2>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    let ContainerFormat::Enum(variants) = &registry["Large"] else {
+        panic!("Large should be an enum");
+    };
+    assert_eq!(variants.len(), 4);
+    assert_eq!(
+        variants[&1].value,
+        VariantFormat::Tuple(vec![Format::U8; 20])
+    );
+    let VariantFormat::Struct(fields) = &variants[&2].value else {
+        panic!("Large::Struct should be a struct variant");
+    };
+    assert_eq!(fields.len(), 20);
+    assert_eq!(fields[19].name, "f19");
+    assert_eq!(fields[19].value, Format::U16);
+
+    let ContainerFormat::Enum(variants) = &registry["Large2"] else {
+        panic!("Large2 should be an enum");
+    };
+    assert_eq!(
+        variants[&0].value,
+        VariantFormat::Tuple(vec![Format::U32; 17])
+    );
+    let VariantFormat::Struct(fields) = &variants[&1].value else {
+        panic!("Large2::B should be a struct variant");
+    };
+    assert_eq!(fields.len(), 17);
+    assert!(fields.iter().all(|field| field.value == Format::Bool));
+
+    // Serialization tracing agrees.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let mut samples = Samples::new();
+    let value = Large2::A(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16);
+    let (_, value) = tracer.trace_value(&mut samples, &value).unwrap();
+    assert_eq!(
+        value,
+        Value::Variant(0, Box::new(Value::Seq((0..17).map(Value::U32).collect())))
+    );
+    let ContainerFormat::Enum(variants) = &tracer.registry_unchecked()["Large2"] else {
+        panic!("Large2 should be an enum");
+    };
+    assert_eq!(
+        variants[&0].value,
+        VariantFormat::Tuple(vec![Format::U32; 17])
+    );
+}
+
+#[test]
+fn test_value_serialization() {
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let (_, value) = tracer
+        .trace_value(&mut samples, &E::Tuple(3, Some(true)))
+        .unwrap();
+
+    let json = serde_json::to_string(&value).unwrap();
+    assert_eq!(
+        json,
+        r#"{"VARIANT":[2,{"SEQ":[{"U16":3},{"OPTION":{"BOOL":true}}]}]}"#
+    );
+    assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+
+    let yaml = serde_yaml::to_string(&value).unwrap();
+    assert_eq!(serde_yaml::from_str::<Value>(&yaml).unwrap(), value);
+
+    let bytes = bincode::serialize(&value).unwrap();
+    assert_eq!(bincode::deserialize::<Value>(&bytes).unwrap(), value);
+
+    let value = Value::Seq(vec![
+        Value::I128(-1),
+        Value::Bytes(vec![1, 2]),
+        Value::Str("a".into()),
+        Value::Option(None),
+    ]);
+    let bytes = bcs::to_bytes(&value).unwrap();
+    assert_eq!(bcs::from_bytes::<Value>(&bytes).unwrap(), value);
+}
+
+#[test]
+fn test_map_entries_recording() {
+    // A map serialized in insertion order, e.g. like JSON objects preserving their order.
+    struct Ordered(Vec<(&'static str, u32)>);
+
+    impl Serialize for Ordered {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+        }
+    }
+
+    let map = Ordered(vec![("b", 1), ("a", 2)]);
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let (_, value) = tracer.trace_value(&mut samples, &map).unwrap();
+    assert_eq!(
+        value,
+        Value::Seq(vec![
+            Value::Str("b".into()),
+            Value::U32(1),
+            Value::Str("a".into()),
+            Value::U32(2),
+        ])
+    );
+
+    let mut tracer = Tracer::new(TracerConfig::default().record_map_entries(true));
+    let (format, value) = tracer.trace_value(&mut samples, &map).unwrap();
+    assert_eq!(
+        format,
+        Format::Map {
+            key: Box::new(Format::Str),
+            value: Box::new(Format::U32),
+        }
+    );
+    assert_eq!(
+        value,
+        Value::Map(vec![
+            (Value::Str("b".into()), Value::U32(1)),
+            (Value::Str("a".into()), Value::U32(2)),
+        ])
+    );
+    let json = serde_json::to_string(&value).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+
+    // Recorded entries can be deserialized as maps.
+    let map = BTreeMap::<String, u32>::deserialize(value.into_deserializer()).unwrap();
+    assert_eq!(
+        map,
+        vec![("a".to_string(), 2), ("b".to_string(), 1)]
+            .into_iter()
+            .collect()
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_save_and_load_samples() {
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer
+        .trace_value(&mut samples, &Name("Bob".into()))
+        .unwrap();
+    assert_eq!(samples.names().collect::<Vec<_>>(), vec!["Name"]);
+
+    let mut data = Vec::new();
+    samples.save(&mut data).unwrap();
+    let loaded = Samples::load(data.as_slice()).unwrap();
+    assert_eq!(loaded, samples);
+
+    // Loaded samples can be used in a new tracing session.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_type::<Person>(&loaded).unwrap();
+    tracer.registry().unwrap();
+
+    // Incompatible files are rejected.
+    let data = String::from_utf8(data).unwrap().replace(
+        &format!("\"version\":{}", Samples::FILE_VERSION),
+        "\"version\":0",
+    );
+    assert!(matches!(
+        Samples::load(data.as_bytes()),
+        Err(Error::InvalidSamples(_))
+    ));
+    assert!(matches!(
+        Samples::load(&b"{}"[..]),
+        Err(Error::InvalidSamples(_))
+    ));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_save_and_load_extreme_samples() {
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default().record_samples_for_tuple_structs(true));
+    tracer
+        .trace_value(&mut samples, &Wide(u128::MAX, i128::MIN, i128::MAX))
+        .unwrap();
+
+    let mut data = Vec::new();
+    samples.save(&mut data).unwrap();
+    let loaded = Samples::load(data.as_slice()).unwrap();
+    assert_eq!(loaded, samples);
+    assert_eq!(
+        loaded.value("Wide"),
+        Some(&Value::Seq(vec![
+            Value::U128(u128::MAX),
+            Value::I128(i128::MIN),
+            Value::I128(i128::MAX),
+        ]))
+    );
+
+    // JSON cannot represent non-finite floats, which are rejected rather than saved as
+    // `null`. The compact encoding preserves them.
+    let mut samples = Samples::new();
+    tracer
+        .trace_value(&mut samples, &Floats(f32::NAN, f64::NEG_INFINITY))
+        .unwrap();
+    assert!(matches!(
+        samples.save(&mut Vec::new()),
+        Err(Error::InvalidSamples(_))
+    ));
+    let loaded = Samples::from_compact_bytes(&samples.to_compact_bytes()).unwrap();
+    match loaded.value("Floats") {
+        Some(Value::Seq(values)) => match values.as_slice() {
+            [Value::F32(x), Value::F64(y)] => {
+                assert!(x.is_nan());
+                assert_eq!(*y, f64::NEG_INFINITY);
+            }
+            _ => panic!("unexpected sample {values:?}"),
+        },
+        sample => panic!("unexpected sample {sample:?}"),
+    }
+
+    // The version is checked before the samples are parsed.
+    assert!(matches!(
+        Samples::load(&br#"{"version":0,"samples":[]}"#[..]),
+        Err(Error::InvalidSamples(message)) if message.contains("unsupported version 0")
+    ));
+}
+
+#[derive(Serialize, Deserialize)]
+struct Wide(u128, i128, i128);
+
+#[derive(Serialize, Deserialize)]
+struct Floats(f32, f64);
+
+#[cfg(feature = "json")]
+#[test]
+fn test_samples_from_json_fixtures() {
+    let dir =
+        std::env::temp_dir().join(format!("serde-reflection-fixtures-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("Name.json"), r#""Alice""#).unwrap();
+    std::fs::write(dir.join("README.md"), "ignored").unwrap();
+
+    let fixtures = JsonFixtures::new().register::<Name>("Name");
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let samples = Samples::from_json_dir(&mut tracer, &fixtures, &dir).unwrap();
+    assert_eq!(samples.value("Name"), Some(&Value::Str("Alice".into())));
+    tracer.trace_type::<Person>(&samples).unwrap();
+    tracer.registry().unwrap();
+
+    // Fixtures must be registered and valid.
+    std::fs::write(dir.join("Unknown.json"), "0").unwrap();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    assert!(matches!(
+        Samples::from_json_dir(&mut tracer, &fixtures, &dir),
+        Err(Error::InvalidSamples(msg)) if msg.contains("no type registered for fixture Unknown")
+    ));
+    std::fs::remove_file(dir.join("Unknown.json")).unwrap();
+    std::fs::write(dir.join("Name.json"), r#""A1""#).unwrap();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    assert!(matches!(
+        Samples::from_json_dir(&mut tracer, &fixtures, &dir),
+        Err(Error::InvalidSamples(msg)) if msg.contains("Invalid name A1")
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum Untagged {
+    Number(u32),
+    Label(Label),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Label(String);
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Shape {
+    size: Untagged,
+    origin: Untagged,
+    name: String,
+}
+
+#[test]
+fn test_self_describing_trace_mode() {
+    let shape = Shape {
+        size: Untagged::Number(3),
+        origin: Untagged::Label(Label("o".into())),
+        name: "a".into(),
+    };
+
+    // Untagged enums call `deserialize_any`.
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_value(&mut samples, &shape).unwrap();
+    assert_eq!(
+        tracer.trace_type::<Shape>(&samples).unwrap_err(),
+        Error::RequiresSelfDescribingFormat {
+            container: Some("Shape".into()),
+            field_path: vec!["size".into()],
+        }
+    );
+
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default().self_describing(true));
+    tracer.trace_value(&mut samples, &shape).unwrap();
+    tracer.trace_type::<Shape>(&samples).unwrap();
+    assert!(tracer.requires_self_describing("Shape"));
+    assert!(!tracer.requires_self_describing("Label"));
+    assert_eq!(
+        tracer.self_describing_containers(),
+        vec!["Shape".to_string()]
+    );
+
+    // The marker is kept in the metadata of the registry.
+    let (registry, metadata) = tracer.registry_with_metadata().unwrap();
+    assert_eq!(
+        metadata.self_describing_containers().collect::<Vec<_>>(),
+        vec!["Shape"]
+    );
+    assert_eq!(
+        registry.get("Shape").unwrap(),
+        &ContainerFormat::Struct(vec![
+            Named {
+                name: "size".into(),
+                value: Format::U32,
+            },
+            Named {
+                name: "origin".into(),
+                value: Format::TypeName("Label".into()),
+            },
+            Named {
+                name: "name".into(),
+                value: Format::Str,
+            },
+        ])
+    );
+
+    // The format of the location must be known from serialization tracing.
+    let mut tracer = Tracer::new(TracerConfig::default().self_describing(true));
+    assert!(matches!(
+        tracer.trace_type::<Shape>(&samples),
+        Err(Error::RequiresSelfDescribingFormat { .. })
+    ));
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Holder {
+    Empty,
+    Full { count: u8, content: Untagged },
+}
+
+#[test]
+fn test_deserialize_any_error_location() {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let error = tracer.trace_type::<Holder>(&Samples::new()).unwrap_err();
+    assert_eq!(
+        error,
+        Error::RequiresSelfDescribingFormat {
+            container: Some("Holder".into()),
+            field_path: vec!["Full".into(), "content".into()],
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "Type requires a self-describing format: deserialize_any was called at Holder::Full::content"
+    );
+    assert!(error
+        .explanation()
+        .contains("TracerConfig::self_describing"));
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    assert_eq!(
+        tracer
+            .trace_type_once::<Untagged>(&Samples::new())
+            .unwrap_err(),
+        Error::RequiresSelfDescribingFormat {
+            container: None,
+            field_path: Vec::new(),
+        }
+    );
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Counters {
+    hits: std::num::NonZeroU16,
+}
+
+#[derive(Serialize, Debug)]
+struct Email(String);
+
+impl<'de> Deserialize<'de> for Email {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename = "Email")]
+        struct Unchecked(String);
+
+        let Unchecked(value) = Unchecked::deserialize(deserializer)?;
+        if value.contains('@') {
+            Ok(Email(value))
+        } else {
+            Err(serde::de::Error::custom(format!(
+                "invalid email: {value:?}"
+            )))
+        }
+    }
+}
+
+#[test]
+fn test_error_hints() {
+    use serde_reflection::{compat::NumericType, Hint};
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let error = tracer.trace_simple_type::<Counters>().unwrap_err();
+    assert_eq!(error.hint(), Some(Hint::NonZeroInteger(NumericType::U16)));
+    assert_eq!(
+        error.hint().unwrap().suggestion(),
+        "TracerConfig::default().default_u16_value(1)"
+    );
+    let mut tracer = Tracer::new(TracerConfig::default().default_u16_value(1));
+    assert!(tracer.trace_simple_type::<Counters>().is_ok());
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let error = tracer.trace_simple_type::<Holder>().unwrap_err();
+    assert_eq!(
+        error.hint(),
+        Some(Hint::SelfDescribing {
+            container: Some("Holder".into())
+        })
+    );
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let error = tracer.trace_simple_type::<Email>().unwrap_err();
+    assert_eq!(error.hint(), Some(Hint::RecordSample));
+    let mut samples = Samples::new();
+    tracer
+        .trace_value(&mut samples, &Email("a@b.c".into()))
+        .unwrap();
+    assert!(tracer.trace_type::<Email>(&samples).is_ok());
+
+    assert_eq!(Error::UnknownFormat.hint(), None);
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Tolerant {
+    A(u8),
+    B,
+    #[serde(other)]
+    Unknown,
+}
+
+#[test]
+fn test_fallback_variant() {
+    // Fallback variants are only probed on demand.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Tolerant>().unwrap();
+    assert_eq!(tracer.fallback_variant("Tolerant"), None);
+
+    let mut tracer = Tracer::new(TracerConfig::default().probe_fallback_variants(true));
+    let (_, values) = tracer.trace_simple_type::<Tolerant>().unwrap();
+    assert_eq!(values.len(), 3);
+    tracer.trace_simple_type::<E>().unwrap();
+    assert_eq!(tracer.fallback_variant("Tolerant"), Some("Unknown"));
+    assert_eq!(tracer.fallback_variant("E"), None);
+
+    // The fallback variant is otherwise recorded as a normal variant, and kept in the
+    // metadata of the registry.
+    let (registry, metadata) = tracer.registry_with_metadata().unwrap();
+    assert_eq!(metadata.fallback_variant("Tolerant"), Some("Unknown"));
+    assert_eq!(metadata.fallback_variant("E"), None);
+    match registry.get("Tolerant").unwrap() {
+        ContainerFormat::Enum(variants) => {
+            assert_eq!(variants.len(), 3);
+            assert_eq!(variants[&2].name, "Unknown");
+            assert_eq!(variants[&2].value, VariantFormat::Unit);
+        }
+        _ => panic!(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Strict {
+    a: u8,
+    open: Open,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Open {
+    b: u8,
+}
+
+#[test]
+fn test_probe_unknown_fields() {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Strict>().unwrap();
+    assert_eq!(tracer.denies_unknown_fields("Strict"), None);
+
+    let mut tracer = Tracer::new(TracerConfig::default().probe_unknown_fields(true));
+    let (_, values) = tracer.trace_simple_type::<Strict>().unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(tracer.denies_unknown_fields("Strict"), Some(true));
+    assert_eq!(tracer.denies_unknown_fields("Open"), Some(false));
+    assert_eq!(tracer.denies_unknown_fields("Unknown"), None);
+    let (_, metadata) = tracer.registry_with_metadata().unwrap();
+    assert_eq!(metadata.denies_unknown_fields("Strict"), Some(true));
+    assert_eq!(metadata.denies_unknown_fields("Open"), Some(false));
+
+    // Declarations do not require probing.
+    let config = TracerConfig::default().deny_unknown_fields("Strict");
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Strict>().unwrap();
+    assert_eq!(tracer.denies_unknown_fields("Strict"), Some(true));
+    assert_eq!(tracer.denies_unknown_fields("Open"), None);
+}
+
+#[test]
+fn test_seq_sample_lengths() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Batch {
+        #[serde(deserialize_with = "non_empty")]
+        items: Vec<u8>,
+        tags: BTreeMap<String, u8>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Pair {
+        first: Batch,
+        second: Batch,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Tree(#[serde(deserialize_with = "non_empty")] Vec<Tree>);
+
+    fn non_empty<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        let values = Vec::<T>::deserialize(deserializer)?;
+        if values.is_empty() {
+            return Err(serde::de::Error::custom("empty batch"));
+        }
+        Ok(values)
+    }
+
+    // The second batch is given an empty vector once the format of the items is known.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    assert!(tracer.trace_simple_type::<Pair>().is_err());
+
+    let config = TracerConfig::default()
+        .seq_sample_len(2)
+        .seq_sample_len_at(["Batch", "items"], 3);
+    let mut tracer = Tracer::new(config);
+    let (_, values) = tracer.trace_simple_type::<Pair>().unwrap();
+    assert_eq!(values[0].first.items, vec![0; 3]);
+    assert_eq!(values[0].second.items, vec![0; 3]);
+    // Identical keys collapse into one entry; known formats still cut the exploration.
+    assert_eq!(values[0].first.tags.len(), 1);
+    assert!(values[0].second.tags.is_empty());
+
+    // Recursive containers are still cut once their format is known, so tracing
+    // terminates (and fails here).
+    let config = TracerConfig::default().seq_sample_len_at(["Tree", "0"], 1);
+    let mut tracer = Tracer::new(config);
+    assert!(tracer.trace_simple_type::<Tree>().is_err());
+}
+
+#[test]
+fn test_fallback_handler() {
+    use serde_reflection::FallbackContext;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Id {
+        Number(u64),
+        Name(String),
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Account {
+        id: Id,
+        aliases: Vec<Id>,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    assert!(matches!(
+        tracer.trace_simple_type::<Account>(),
+        Err(Error::RequiresSelfDescribingFormat { .. })
+    ));
+
+    let handler = |context: &FallbackContext| match (context.container, context.field_path) {
+        (Some("Account"), [field]) if field == "id" => Some(Format::Str),
+        (Some("Account"), [field]) if field == "aliases" => Some(Format::U64),
+        _ => None,
+    };
+    let config = TracerConfig::default()
+        .fallback_handler(handler)
+        .record_events(true);
+    let mut tracer = Tracer::new(config.clone());
+    let (_, values) = tracer.trace_simple_type::<Account>().unwrap();
+    assert_eq!(values[0].id, Id::Name(String::new()));
+    assert!(tracer.events().contains(&TraceEvent::FallbackUsed {
+        method: "deserialize_any",
+        format: Format::Str,
+    }));
+    let registry = tracer.registry().unwrap();
+    assert_eq!(
+        registry["Account"],
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "id".into(),
+                value: Format::Str,
+            },
+            Named {
+                name: "aliases".into(),
+                value: Format::Seq(Box::new(Format::U64)),
+            },
+        ])
+    );
+
+    // Handlers are shared by clones of the configuration, but not serialized.
+    assert_eq!(config.clone(), config);
+    let json = serde_json::to_string(&config).unwrap();
+    let loaded: TracerConfig = serde_json::from_str(&json).unwrap();
+    assert_ne!(loaded, config);
+    let mut tracer = Tracer::new(loaded);
+    assert!(tracer.trace_simple_type::<Account>().is_err());
+}
+
+#[test]
+fn test_constrained_default_values() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Level {
+        #[serde(deserialize_with = "nonzero")]
+        value: u8,
+        name: String,
+    }
+
+    fn nonzero<'de, D>(deserializer: D) -> std::result::Result<u8, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = u8::deserialize(deserializer)?;
+        if value == 0 {
+            return Err(serde::de::Error::custom("zero level"));
+        }
+        Ok(value)
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    assert!(tracer.trace_simple_type::<Level>().is_err());
+
+    // Default values are clamped into the declared bounds.
+    let constraint = Constraint::new().min(1).max(10);
+    let config = TracerConfig::default()
+        .constrain(["Level", "value"], constraint.clone())
+        .constrain(["Level", "name"], Constraint::new().max_length(16));
+    let mut tracer = Tracer::new(config);
+    let (_, values) = tracer.trace_simple_type::<Level>().unwrap();
+    assert_eq!(values[0].value, 1);
+    assert_eq!(
+        tracer.constraints().get(&["Level", "value"]),
+        Some(&constraint)
+    );
+    assert!(tracer.constraints().get(&["Level"]).is_none());
+
+    // Constraints can be stored next to the registry.
+    let constraints = tracer.constraints().clone();
+    let json = serde_json::to_string(&constraints).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Constraints>(&json).unwrap(),
+        constraints
+    );
+}
+
+#[test]
+fn test_float_policy() {
+    #[derive(Serialize, Deserialize)]
+    struct Reading {
+        id: u32,
+        celsius: f64,
+        samples: Vec<f32>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Sensor {
+        Off,
+        Reading(Reading),
+        Raw(Option<f32>),
+    }
+
+    let config = TracerConfig::default().float_policy(FloatPolicy::Reject);
+    let mut tracer = Tracer::new(config.clone());
+    tracer.trace_simple_type::<Sensor>().unwrap();
+    let error = tracer.registry().unwrap_err();
+    assert_eq!(
+        error,
+        Error::UnspecifiedFloat {
+            container: "Reading".into(),
+            field_path: vec!["celsius".into()],
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "Floating-point member at Reading::celsius may be NaN or infinite"
+    );
+
+    let config = config
+        .constrain(["Reading", "celsius"], Constraint::new().finite(true))
+        .constrain(["Reading", "samples"], Constraint::new().finite(false))
+        .constrain(["Sensor", "Raw"], Constraint::new().finite(true));
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Sensor>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    // Flagged members are recorded next to the declared ones.
+    let config = TracerConfig::default()
+        .float_policy(FloatPolicy::Flag)
+        .constrain(["Reading", "celsius"], Constraint::new().finite(true));
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Sensor>().unwrap();
+    let constraints = tracer.constraints();
+    let flagged = Constraint::new().finite(false);
+    assert_eq!(
+        constraints.get(&["Reading", "celsius"]),
+        Some(&Constraint::new().finite(true))
+    );
+    assert_eq!(constraints.get(&["Reading", "samples"]), Some(&flagged));
+    assert_eq!(constraints.get(&["Sensor", "Raw"]), Some(&flagged));
+    assert!(constraints.get(&["Reading", "id"]).is_none());
+    assert!(constraints.unspecified_floats(&registry).is_empty());
+
+    // The same metadata can be computed for a saved registry.
+    let mut constraints = Constraints::new();
+    assert_eq!(
+        constraints.flag_floats(&registry),
+        vec![
+            vec!["Reading".to_string(), "celsius".to_string()],
+            vec!["Reading".to_string(), "samples".to_string()],
+            vec!["Sensor".to_string(), "Raw".to_string()],
+        ]
+    );
+    assert!(constraints
+        .get(&["Reading", "celsius"])
+        .unwrap()
+        .is_satisfied_by(&Value::F64(f64::NAN)));
+    assert!(!Constraint::new()
+        .finite(true)
+        .is_satisfied_by(&Value::Option(Some(Box::new(Value::F32(f32::INFINITY))))));
+}
+
+#[test]
+fn test_variant_order() {
+    use serde_reflection::VariantOrder;
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+    enum Command {
+        Stop,
+        Move(#[serde(deserialize_with = "nonzero")] u8),
+        Wait(u32),
+    }
+
+    fn nonzero<'de, D>(deserializer: D) -> std::result::Result<u8, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = u8::deserialize(deserializer)?;
+        if value == 0 {
+            return Err(serde::de::Error::custom("zero distance"));
+        }
+        Ok(value)
+    }
+
+    // By default, every variant is explored in the order of declaration, including the
+    // ones recorded from samples.
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_value(&mut samples, &Command::Move(3)).unwrap();
+    assert!(tracer.trace_type::<Command>(&samples).is_err());
+
+    // Variants identified by a sample are not explored again.
+    let config = TracerConfig::default().variant_order(VariantOrder::SampleFirst);
+    let mut tracer = Tracer::new(config);
+    tracer.trace_value(&mut samples, &Command::Move(3)).unwrap();
+    let (_, values) = tracer.trace_type::<Command>(&samples).unwrap();
+    assert!(!values.contains(&Command::Move(0)));
+    let registry = tracer.registry().unwrap();
+    let variants = match &registry["Command"] {
+        ContainerFormat::Enum(variants) => variants,
+        _ => panic!("should be an enum"),
+    };
+    assert_eq!(
+        variants
+            .iter()
+            .map(|(index, variant)| (*index, variant.name.as_str()))
+            .collect::<Vec<_>>(),
+        vec![(0, "Stop"), (1, "Move"), (2, "Wait")]
+    );
+    assert_eq!(
+        variants[&1].value,
+        VariantFormat::NewType(Box::new(Format::U8))
+    );
+}
+
+#[test]
+fn test_random_seed() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Reading {
+        id: u64,
+        level: i16,
+        valid: bool,
+        label: String,
+        ratio: f64,
+    }
+
+    let trace = |config: TracerConfig| {
+        let mut tracer = Tracer::new(config);
+        let (_, values) = tracer.trace_simple_type::<Reading>().unwrap();
+        values
+    };
+    let values = trace(TracerConfig::default().random_seed(7));
+    assert_eq!(values, trace(TracerConfig::default().random_seed(7)));
+    assert_ne!(values, trace(TracerConfig::default().random_seed(8)));
+    assert_ne!(values, trace(TracerConfig::default()));
+    assert_eq!(values[0].label.len(), 8);
+    assert!(values[0].label.chars().all(|c| c.is_ascii_alphanumeric()));
+    assert!((0.0..1.0).contains(&values[0].ratio));
+
+    // Synthesized integers are clamped by constraints.
+    let config = TracerConfig::default()
+        .random_seed(7)
+        .constrain(["Reading", "level"], Constraint::new().min(-5).max(5));
+    assert!((-5..=5).contains(&trace(config)[0].level));
+}
+
+#[test]
+fn test_tracer_config_serialization() {
+    let config = TracerConfig::default()
+        .is_human_readable(true)
+        .max_passes_per_trace(3)
+        .skip_variant("E", "Unit")
+        .default_u8_value(1)
+        .default_borrowed_str_value("foo")
+        .constrain(["E", "Newtype"], Constraint::new().min(1).pattern("[0-9]+"));
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(serde_json::from_str::<TracerConfig>(&json).unwrap(), config);
+
+    // Missing options take their default values.
+    let config: TracerConfig =
+        serde_json::from_str(r#"{ "record_samples_for_structs": true }"#).unwrap();
+    assert_eq!(
+        config,
+        TracerConfig::default().record_samples_for_structs(true)
+    );
+
+    // Misspelled options are rejected.
+    assert!(serde_json::from_str::<TracerConfig>(r#"{ "record_sample": true }"#).is_err());
+}
+
+#[test]
+fn test_generic_containers() {
+    use serde_reflection::TypeParam;
+
+    #[derive(Serialize, Deserialize)]
+    struct Inner<T>(Vec<T>);
+
+    #[derive(Serialize, Deserialize)]
+    struct Plain(u8);
+
+    #[derive(Serialize, Deserialize)]
+    enum Outer<K, V> {
+        Entry { key: K, values: Inner<V> },
+        Plain(Plain),
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer
+        .trace_simple_type::<Outer<TypeParam<0>, TypeParam<1>>>()
+        .unwrap();
+    let registry = tracer.registry().unwrap();
+
+    assert_eq!(
+        registry.get("Plain"),
+        Some(&ContainerFormat::NewTypeStruct(Box::new(Format::U8)))
+    );
+    // Parameters are numbered per container.
+    assert_eq!(
+        registry.get("Inner"),
+        Some(&ContainerFormat::Generic {
+            params: vec!["T0".into()],
+            body: Box::new(ContainerFormat::NewTypeStruct(Box::new(Format::Seq(
+                Box::new(Format::TypeParam(0))
+            )))),
+        })
+    );
+    let outer = registry.get("Outer").unwrap();
+    assert!(matches!(outer, ContainerFormat::Generic { params, .. } if params.len() == 2));
+    assert_eq!(outer.variants().count(), 2);
+    assert_eq!(
+        outer.formats().collect::<Vec<_>>(),
+        vec![
+            &Format::TypeParam(0),
+            &Format::Apply {
+                name: "Inner".into(),
+                args: vec![Format::TypeParam(1)].into(),
+            },
+            &Format::TypeName("Plain".into())
+        ]
+    );
+    assert!(serde_reflection::registry::validate(&registry).is_empty());
+
+    // A container passing a single parameter to another one.
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper<A, B> {
+        inner: Inner<B>,
+        #[serde(skip)]
+        _marker: std::marker::PhantomData<A>,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer
+        .trace_simple_type::<Wrapper<TypeParam<0>, TypeParam<1>>>()
+        .unwrap();
+    let registry = tracer.registry().unwrap();
+    assert_eq!(
+        registry.get("Inner"),
+        Some(&ContainerFormat::Generic {
+            params: vec!["T0".into()],
+            body: Box::new(ContainerFormat::NewTypeStruct(Box::new(Format::Seq(
+                Box::new(Format::TypeParam(0))
+            )))),
+        })
+    );
+    assert_eq!(
+        registry.get("Wrapper"),
+        Some(&ContainerFormat::Generic {
+            params: vec!["T0".into()],
+            body: Box::new(ContainerFormat::Struct(vec![Named {
+                name: "inner".into(),
+                value: Format::Apply {
+                    name: "Inner".into(),
+                    args: vec![Format::TypeParam(0)].into(),
+                },
+            }])),
+        })
+    );
+}
+
+#[test]
+fn test_generic_container_instances() {
+    use serde_reflection::TypeParam;
+
+    #[derive(Serialize, Deserialize)]
+    struct Pair<T> {
+        first: T,
+        second: T,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Cell<T>(T);
+
+    #[derive(Serialize, Deserialize)]
+    struct Root {
+        pair: Pair<u32>,
+        cells: Vec<Cell<Pair<u32>>>,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Pair<TypeParam<0>>>().unwrap();
+    tracer.trace_simple_type::<Root>().unwrap();
+    tracer.trace_simple_type::<Cell<TypeParam<0>>>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    // Instances are referred to with their type arguments, so that `Root` is not generic.
+    let pair_of_u32 = Format::Apply {
+        name: "Pair".into(),
+        args: vec![Format::U32].into(),
+    };
+    assert_eq!(
+        registry.get("Root"),
+        Some(&ContainerFormat::Struct(vec![
+            Named {
+                name: "pair".into(),
+                value: pair_of_u32.clone(),
+            },
+            Named {
+                name: "cells".into(),
+                value: Format::Seq(Box::new(Format::Apply {
+                    name: "Cell".into(),
+                    args: vec![pair_of_u32].into(),
+                })),
+            },
+        ]))
+    );
+    assert!(registry.get("Pair").unwrap().is_generic());
+    assert!(registry.get("Cell").unwrap().is_generic());
+    assert!(serde_reflection::registry::validate(&registry).is_empty());
+
+    // Distinct instances of the same container still conflict.
+    #[derive(Serialize, Deserialize)]
+    struct Other {
+        pair: Pair<String>,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Root>().unwrap();
+    assert!(matches!(
+        tracer.trace_simple_type::<Other>(),
+        Err(Error::ConflictingFormats { .. })
+    ));
+}
+
+#[test]
+fn test_declared_variants() {
+    #[derive(Serialize, Deserialize)]
+    enum Platform {
+        Linux,
+        // Only available elsewhere: `Windows(u32)`.
+        #[cfg(any())]
+        Windows(u32),
+    }
+
+    let config = TracerConfig::default().declare_variant(
+        "Platform",
+        1,
+        "Windows",
+        VariantFormat::NewType(Box::new(Format::U32)),
+    );
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Platform>().unwrap();
+    let registry = tracer.registry().unwrap();
+    let variants: Vec<_> = registry
+        .get("Platform")
+        .unwrap()
+        .variants()
+        .map(|(index, variant)| (index, variant.name.as_str(), variant.value.clone()))
+        .collect();
+    assert_eq!(
+        variants,
+        vec![
+            (0, "Linux", VariantFormat::Unit),
+            (1, "Windows", VariantFormat::NewType(Box::new(Format::U32))),
+        ]
+    );
+
+    // Declarations must agree with the traced variants.
+    let config = TracerConfig::default().declare_variant(
+        "Platform",
+        0,
+        "Linux",
+        VariantFormat::Tuple(vec![]),
+    );
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Platform>().unwrap();
+    assert!(matches!(tracer.registry(), Err(Error::Incompatible(..))));
+}
+
+#[derive(Serialize, Deserialize)]
+struct Signed {
+    #[serde(with = "serde_bytes")]
+    payload: Vec<u8>,
+    signature: Option<serde_bytes::ByteBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Digest(#[serde(with = "serde_bytes")] Vec<u8>);
+
+#[test]
+fn test_declared_fixed_bytes() {
+    use serde_reflection::compat::{CompatRules, Compatibility};
+
+    let config = TracerConfig::default()
+        .declare_fixed_bytes("Signed", "signature", 64)
+        .declare_fixed_bytes("Digest", "0", 32);
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Signed>().unwrap();
+    tracer.trace_simple_type::<Digest>().unwrap();
+    let registry = tracer.registry().unwrap();
+    assert_eq!(
+        registry["Signed"],
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "payload".into(),
+                value: Format::Bytes,
+            },
+            Named {
+                name: "signature".into(),
+                value: Format::Option(Box::new(Format::FixedBytes(64))),
+            },
+        ])
+    );
+    assert_eq!(
+        registry["Digest"],
+        ContainerFormat::NewTypeStruct(Box::new(Format::FixedBytes(32)))
+    );
+    assert_eq!(
+        serde_yaml::to_string(&registry["Digest"]).unwrap(),
+        "---\nNEWTYPESTRUCT:\n  FIXEDBYTES: 32\n"
+    );
+
+    // Fixed-size bytes can be read as bytes of any size.
+    let rules = CompatRules::binary();
+    assert_eq!(
+        rules.format_change(&Format::FixedBytes(32), &Format::Bytes),
+        Compatibility::Compatible
+    );
+    assert_eq!(
+        rules.format_change(&Format::Bytes, &Format::FixedBytes(32)),
+        Compatibility::Breaking
+    );
+
+    // Declarations must match a field containing bytes.
+    let mut tracer =
+        Tracer::new(TracerConfig::default().declare_fixed_bytes("Signed", "nonce", 12));
+    tracer.trace_simple_type::<Signed>().unwrap();
+    assert_eq!(
+        tracer.registry().unwrap_err(),
+        Error::InvalidDeclaration("Signed::nonce is not a field containing bytes".into())
+    );
+}
+
+/// A type without Deserialize, as if it came from another crate.
+#[derive(Serialize)]
+enum Foreign {
+    Id(u64),
+    Pair { left: Option<u8>, right: Option<u8> },
+}
+
+#[test]
+fn test_serialize_only_types() {
+    use serde_reflection::SerializeSampleProvider;
+
+    struct Provider;
+
+    impl SerializeSampleProvider for Provider {
+        type Value = Foreign;
+
+        fn samples(&self) -> Vec<Foreign> {
+            vec![
+                Foreign::Id(1),
+                Foreign::Pair {
+                    left: None,
+                    right: Some(1),
+                },
+                Foreign::Pair {
+                    left: Some(2),
+                    right: None,
+                },
+            ]
+        }
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let format = tracer
+        .trace_serialize_only(&mut Samples::new(), &Provider)
+        .unwrap();
+    assert_eq!(format, Format::TypeName("Foreign".into()));
+    let registry = tracer.registry().unwrap();
+    let variants: Vec<_> = registry["Foreign"].variants().collect();
+    assert_eq!(variants.len(), 2);
+    assert_eq!(
+        variants[1].1.value,
+        VariantFormat::Struct(vec![
+            Named {
+                name: "left".into(),
+                value: Format::Option(Box::new(Format::U8)),
+            },
+            Named {
+                name: "right".into(),
+                value: Format::Option(Box::new(Format::U8)),
+            },
+        ])
+    );
+
+    // Closures are providers too.
+    let tracer = Tracer::new(TracerConfig::default());
+    let (registry, errors) = tracer.trace_all(
+        [
+            TraceRoot::serialize_only(|| vec![Foreign::Id(0)]),
+            TraceRoot::serialize_only(Vec::<Foreign>::new),
+        ],
+        &Samples::new(),
+    );
+    assert_eq!(registry["Foreign"].variants().count(), 1);
+    assert_eq!(errors.len(), 1);
+}
+
+/// A model of a framework wrapping Serde, with columns only known at runtime.
+struct Table {
+    name: &'static str,
+    columns: Vec<(&'static str, Format)>,
+}
+
+impl serde_reflection::TraceableDyn for Table {
+    fn type_name(&self) -> String {
+        format!("table {}", self.name)
+    }
+
+    fn trace(
+        &self,
+        tracer: &mut Tracer,
+        _samples: &mut Samples,
+    ) -> serde_reflection::Result<Format> {
+        // Columns holding Serde types refer to them by name.
+        let (status, _) = tracer.trace_simple_type::<Status>()?;
+        let mut fields: Vec<_> = self
+            .columns
+            .iter()
+            .map(|(name, format)| Named {
+                name: name.to_string(),
+                value: format.clone(),
+            })
+            .collect();
+        fields.push(Named {
+            name: "status".into(),
+            value: status,
+        });
+        tracer.record_dyn_container(self.name, ContainerFormat::Struct(fields))
+    }
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+enum Status {
+    Active,
+    Banned { reason: String },
+}
+
+#[test]
+fn test_trace_dyn() {
+    let users = Table {
+        name: "User",
+        columns: vec![("id", Format::U64), ("email", Format::Str)],
+    };
+    let mut tracer = Tracer::new(TracerConfig::default().record_provenance(true));
+    let format = tracer.trace_dyn(&mut Samples::new(), &users).unwrap();
+    assert_eq!(format, Format::TypeName("User".into()));
+    assert_eq!(tracer.provenance("User"), vec!["table User".to_string()]);
+    assert_eq!(tracer.provenance("Status"), vec!["Status".to_string()]);
+    let registry = tracer.registry().unwrap();
+    assert_eq!(
+        registry["User"],
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "id".into(),
+                value: Format::U64,
+            },
+            Named {
+                name: "email".into(),
+                value: Format::Str,
+            },
+            Named {
+                name: "status".into(),
+                value: Format::TypeName("Status".into()),
+            },
+        ])
+    );
+    assert_eq!(registry["Status"].variants().count(), 2);
+
+    // Descriptions of the same container are unified.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_dyn(&mut Samples::new(), &users).unwrap();
+    let conflicting = Table {
+        name: "User",
+        columns: vec![("id", Format::Str)],
+    };
+    assert!(matches!(
+        tracer.trace_dyn(&mut Samples::new(), &conflicting),
+        Err(Error::Incompatible(..))
+    ));
+
+    // Forbidden types apply to container names.
+    let mut tracer = Tracer::new(TracerConfig::default().forbid_type("User"));
+    assert_eq!(
+        tracer.trace_dyn(&mut Samples::new(), &users).unwrap_err(),
+        Error::ForbiddenType {
+            type_name: "User".into(),
+            container: None,
+            field_path: Vec::new(),
+        }
+    );
+
+    // Roots of `trace_all`.
+    let tracer = Tracer::new(TracerConfig::default());
+    let (registry, errors) = tracer.trace_all(
+        [TraceRoot::of_dyn(users), TraceRoot::of_dyn(conflicting)],
+        &Samples::new(),
+    );
+    assert!(registry.contains_key("User"));
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], Error::RootFailed(name, _) if name == "table User"));
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Dynamic {
+    Small(u8),
+    Large(i64),
+    Text(String),
+    List(Vec<Dynamic>),
+}
+
+#[test]
+fn test_heterogeneous_map_values() {
+    let mut map = BTreeMap::new();
+    map.insert("a", Dynamic::Small(1));
+    map.insert("b", Dynamic::Large(-1));
+    map.insert("c", Dynamic::List(vec![Dynamic::Small(2)]));
+    map.insert("d", Dynamic::List(vec![Dynamic::Large(3)]));
+    map.insert("e", Dynamic::Text("x".into()));
+
+    // Integer types are widened.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let mut samples = Samples::new();
+    let mut small = BTreeMap::new();
+    small.insert("a", Dynamic::Small(1));
+    small.insert("b", Dynamic::Large(-1));
+    let (format, _) = tracer.trace_value(&mut samples, &small).unwrap();
+    assert_eq!(
+        format,
+        Format::Map {
+            key: Box::new(Format::Str),
+            value: Box::new(Format::I64),
+        }
+    );
+
+    // Other formats require unions.
+    let error = tracer.trace_value(&mut samples, &map).unwrap_err();
+    assert_eq!(
+        error,
+        Error::IncompatibleMapValues(Format::I64, Format::Seq(Box::new(Format::U8)))
+    );
+
+    let mut tracer = Tracer::new(TracerConfig::default().union_formats(true));
+    let (format, _) = tracer.trace_value(&mut samples, &map).unwrap();
+    let union = Format::Union(vec![
+        Format::I64,
+        Format::Seq(Box::new(Format::I64)),
+        Format::Str,
+    ]);
+    assert_eq!(
+        format,
+        Format::Map {
+            key: Box::new(Format::Str),
+            value: Box::new(union.clone()),
+        }
+    );
+    assert_eq!(
+        serde_yaml::to_string(&union).unwrap(),
+        "---\nUNION:\n  - I64\n  - SEQ: I64\n  - STR\n"
+    );
+
+    // Samples of a container may give different formats to the same field.
+    #[derive(Serialize)]
+    struct Record {
+        id: u8,
+        value: Dynamic,
+    }
+
+    let record = |id, value| Record { id, value };
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer
+        .trace_value(&mut samples, &record(1, Dynamic::Small(1)))
+        .unwrap();
+    assert!(matches!(
+        tracer.trace_value(&mut samples, &record(2, Dynamic::Text("x".into()))),
+        Err(Error::ConflictingFormats { .. })
+    ));
+
+    let mut tracer = Tracer::new(TracerConfig::default().union_formats(true));
+    for value in [
+        Dynamic::Small(1),
+        Dynamic::Text("x".into()),
+        Dynamic::Large(2),
+    ] {
+        tracer.trace_value(&mut samples, &record(1, value)).unwrap();
+    }
+    let registry = tracer.registry().unwrap();
+    assert_eq!(
+        registry["Record"],
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "id".into(),
+                value: Format::U8,
+            },
+            Named {
+                name: "value".into(),
+                value: Format::Union(vec![Format::I64, Format::Str]),
+            },
+        ])
+    );
+}
+
+/// Read a `u32` that `Serialize` writes as such, but as a `u64`.
+fn wide_u32<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = u64::deserialize(deserializer)?;
+    u32::try_from(value).map_err(serde::de::Error::custom)
+}
+
+#[test]
+fn test_conflicting_formats() {
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Counter {
+        #[serde(deserialize_with = "wide_u32")]
+        count: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    enum Event {
+        Tick(#[serde(deserialize_with = "wide_u32")] u32),
+    }
+
+    let counter = Counter { count: 1 };
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_value(&mut samples, &counter).unwrap();
+    let error = tracer.trace_type::<Counter>(&samples).unwrap_err();
+    assert_eq!(
+        error,
+        Error::ConflictingFormats {
+            container: Some("Counter".into()),
+            field_path: vec!["count".into()],
+            first: "U32".into(),
+            second: "U64".into(),
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "Conflicting formats U32 and U64 were traced at Counter::count"
+    );
+
+    // The order of the calls does not matter.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Counter>().unwrap();
+    assert_eq!(
+        tracer.trace_value(&mut samples, &counter).unwrap_err(),
+        Error::ConflictingFormats {
+            container: Some("Counter".into()),
+            field_path: Vec::new(),
+            first: "U64".into(),
+            second: "U32".into(),
+        }
+    );
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_value(&mut samples, &Event::Tick(1)).unwrap();
+    assert_eq!(
+        tracer.trace_type::<Event>(&samples).unwrap_err(),
+        Error::ConflictingFormats {
+            container: Some("Event".into()),
+            field_path: vec!["Tick".into()],
+            first: "U32".into(),
+            second: "U64".into(),
+        }
+    );
+
+    // The lenient mode records the widest format.
+    let widened = ContainerFormat::Struct(vec![Named {
+        name: "count".into(),
+        value: Format::U64,
+    }]);
+    let config = TracerConfig::default().widen_formats(true);
+    let mut tracer = Tracer::new(config.clone());
+    tracer.trace_value(&mut samples, &counter).unwrap();
+    tracer.trace_type::<Counter>(&samples).unwrap();
+    tracer.trace_value(&mut samples, &Event::Tick(1)).unwrap();
+    tracer.trace_type::<Event>(&samples).unwrap();
+    let registry = tracer.registry().unwrap();
+    assert_eq!(registry["Counter"], widened);
+    assert_eq!(
+        registry["Event"].variants().next().unwrap().1.value,
+        VariantFormat::NewType(Box::new(Format::U64))
+    );
+
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Counter>().unwrap();
+    tracer.trace_value(&mut samples, &counter).unwrap();
+    assert_eq!(tracer.registry().unwrap()["Counter"], widened);
+}
+
+/// An address written as a string by human-readable encodings and as a number otherwise.
+#[derive(Debug, PartialEq)]
+struct Address(u32);
+
+impl Serialize for Address {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_newtype_struct("Address", &self.0.to_string())
+        } else {
+            serializer.serialize_newtype_struct("Address", &self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Address")]
+        struct Text(String);
+
+        #[derive(Deserialize)]
+        #[serde(rename = "Address")]
+        struct Number(u32);
+
+        if deserializer.is_human_readable() {
+            let Text(text) = Text::deserialize(deserializer)?;
+            text.parse().map(Address).map_err(serde::de::Error::custom)
+        } else {
+            let Number(number) = Number::deserialize(deserializer)?;
+            Ok(Address(number))
+        }
+    }
+}
+
+#[test]
+fn test_both_encodings() {
+    use serde_reflection::EncodingKind;
+
+    #[derive(Serialize, Deserialize)]
+    struct Host {
+        name: String,
+        address: Address,
+    }
+
+    let config = TracerConfig::default()
+        .both_encodings(true)
+        .default_string_value(String::from("1"));
+    let mut tracer = Tracer::new(config);
+    let mut samples = Samples::new();
+    let host = Host {
+        name: "localhost".into(),
+        address: Address(1),
+    };
+    tracer.trace_value(&mut samples, &host).unwrap();
+    tracer.trace_type::<Host>(&samples).unwrap();
+    let registry = tracer.multi_registry().unwrap();
+    assert!(!registry.is_uniform());
+    assert_eq!(registry.core().keys().collect::<Vec<_>>(), vec!["Host"]);
+    assert_eq!(
+        registry.specific(EncodingKind::HumanReadable)["Address"],
+        ContainerFormat::NewTypeStruct(Box::new(Format::Str))
+    );
+    assert_eq!(
+        registry.specific(EncodingKind::Binary)["Address"],
+        ContainerFormat::NewTypeStruct(Box::new(Format::U32))
+    );
+
+    // Each view is the registry of a single encoding.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Host>().unwrap();
+    assert_eq!(
+        registry.view(EncodingKind::Binary),
+        tracer.registry().unwrap()
+    );
+
+    // The option is required.
+    let tracer = Tracer::new(TracerConfig::default());
+    assert!(matches!(
+        tracer.multi_registry(),
+        Err(Error::NotSupported(_))
+    ));
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Pointers {
+    boxed_str: Box<str>,
+    boxed_slice: Box<[u16]>,
+    rc_str: std::rc::Rc<str>,
+    arc_slice: std::sync::Arc<[u32]>,
+    nested: std::rc::Rc<Option<Box<u8>>>,
+    cow: std::borrow::Cow<'static, str>,
+    boxed_strs: Box<Box<[Box<str>]>>,
+    optional: std::sync::Arc<Option<std::sync::Arc<[u8]>>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Shared<T>(std::sync::Arc<T>);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Document {
+    title: Shared<String>,
+    pages: Shared<Vec<Shared<u64>>>,
+}
+
+#[test]
+fn test_transparent_pointers() {
+    let expected = ContainerFormat::Struct(vec![
+        Named {
+            name: "boxed_str".into(),
+            value: Format::Str,
+        },
+        Named {
+            name: "boxed_slice".into(),
+            value: Format::Seq(Box::new(Format::U16)),
+        },
+        Named {
+            name: "rc_str".into(),
+            value: Format::Str,
+        },
+        Named {
+            name: "arc_slice".into(),
+            value: Format::Seq(Box::new(Format::U32)),
+        },
+        Named {
+            name: "nested".into(),
+            value: Format::Option(Box::new(Format::U8)),
+        },
+        Named {
+            name: "cow".into(),
+            value: Format::Str,
+        },
+        Named {
+            name: "boxed_strs".into(),
+            value: Format::Seq(Box::new(Format::Str)),
+        },
+        Named {
+            name: "optional".into(),
+            value: Format::Option(Box::new(Format::Seq(Box::new(Format::U8)))),
+        },
+    ]);
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Pointers>().unwrap();
+    assert_eq!(tracer.registry().unwrap()["Pointers"], expected);
+
+    let value = Pointers {
+        boxed_str: "a".into(),
+        boxed_slice: vec![1].into(),
+        rc_str: "b".into(),
+        arc_slice: vec![2].into(),
+        nested: std::rc::Rc::new(Some(Box::new(3))),
+        cow: "c".into(),
+        boxed_strs: Box::new(vec!["d".into()].into()),
+        optional: std::sync::Arc::new(Some(vec![4].into())),
+    };
+    let mut samples = Samples::new();
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_value(&mut samples, &value).unwrap();
+    tracer.trace_type::<Pointers>(&samples).unwrap();
+    assert_eq!(tracer.registry().unwrap()["Pointers"], expected);
+}
+
+#[test]
+fn test_interior_mutability() {
+    use serde_reflection::Hint;
+    use std::{
+        cell::{Cell, RefCell},
+        sync::{Arc, Mutex, RwLock},
+    };
+
+    #[derive(Serialize, Deserialize)]
+    struct Shared {
+        counter: Mutex<u64>,
+        names: RwLock<Vec<Mutex<String>>>,
+        cache: RefCell<Option<Arc<RwLock<Mutex<u16>>>>>,
+        hits: Cell<u32>,
+    }
+
+    let expected = ContainerFormat::Struct(vec![
+        Named {
+            name: "counter".into(),
+            value: Format::U64,
+        },
+        Named {
+            name: "names".into(),
+            value: Format::Seq(Box::new(Format::Str)),
+        },
+        Named {
+            name: "cache".into(),
+            value: Format::Option(Box::new(Format::U16)),
+        },
+        Named {
+            name: "hits".into(),
+            value: Format::U32,
+        },
+    ]);
+    let value = Shared {
+        counter: Mutex::new(1),
+        names: RwLock::new(vec![Mutex::new("a".into())]),
+        cache: RefCell::new(Some(Arc::new(RwLock::new(Mutex::new(2))))),
+        hits: Cell::new(3),
+    };
+
+    // Locks are traced as their content in both directions.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Shared>().unwrap();
+    let mut samples = Samples::new();
+    tracer.trace_value(&mut samples, &value).unwrap();
+    assert_eq!(tracer.registry().unwrap()["Shared"], expected);
+
+    // Unavailable locks are reported with a hint.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let _borrow = value.cache.borrow_mut();
+    let error = tracer.trace_value(&mut samples, &value).unwrap_err();
+    assert_eq!(error.hint(), Some(Hint::LockUnavailable));
+    drop(_borrow);
+
+    let counter = Arc::new(Mutex::new(1u64));
+    let poisoned = counter.clone();
+    std::thread::spawn(move || {
+        let _guard = poisoned.lock().unwrap();
+        panic!("poisoning the lock");
+    })
+    .join()
+    .unwrap_err();
+    let error = tracer.trace_value(&mut samples, &counter).unwrap_err();
+    assert_eq!(error.hint(), Some(Hint::LockUnavailable));
+}
+
+#[test]
+fn test_transparent_containers() {
+    // A generic wrapper is recorded once, with incompatible contents.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    assert!(tracer.trace_simple_type::<Document>().is_err());
+
+    let config = TracerConfig::default().transparent_container("Shared");
+    let mut tracer = Tracer::new(config.clone());
+    tracer.trace_simple_type::<Document>().unwrap();
+    let value = Document {
+        title: Shared(std::sync::Arc::new("title".into())),
+        pages: Shared(vec![Shared(1.into())].into()),
+    };
+    let mut samples = Samples::new();
+    tracer.trace_value(&mut samples, &value).unwrap();
+    let registry = tracer.registry().unwrap();
+    assert_eq!(registry.len(), 1);
+    assert_eq!(
+        registry["Document"],
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "title".into(),
+                value: Format::Str,
+            },
+            Named {
+                name: "pages".into(),
+                value: Format::Seq(Box::new(Format::U64)),
+            },
+        ])
+    );
+
+    // The binary encoding is the one of the content.
+    assert_eq!(
+        bcs::to_bytes(&value).unwrap(),
+        bcs::to_bytes(&("title", vec![1u64])).unwrap()
+    );
+}
+
+#[test]
+fn test_ignored_containers() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Report {
+        id: u32,
+        debug: DebugBlob,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct DebugBlob {
+        frames: Vec<Frame>,
+        cause: Option<Box<DebugBlob>>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Frame {
+        Native(u64),
+        Script { line: u32 },
+    }
+
+    let config = TracerConfig::default()
+        .ignore_container("telemetry::DebugBlob")
+        .forbid_type("u64");
+    let placeholder = ContainerFormat::NewTypeStruct(Box::new(Format::Bytes));
+
+    // Without a sample, the content is only traced to obtain a value.
+    let mut tracer = Tracer::new(config.clone());
+    tracer.trace_simple_type::<Report>().unwrap();
+    let registry = tracer.registry().unwrap();
+    assert_eq!(registry.len(), 2);
+    assert_eq!(registry["DebugBlob"], placeholder);
+    assert_eq!(
+        registry["Report"],
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "id".into(),
+                value: Format::U32,
+            },
+            Named {
+                name: "debug".into(),
+                value: Format::TypeName("DebugBlob".into()),
+            },
+        ])
+    );
+
+    // Serialization records the value as a sample, which is then used for deserialization.
+    let value = Report {
+        id: 1,
+        debug: DebugBlob {
+            frames: vec![Frame::Native(7), Frame::Script { line: 3 }],
+            cause: None,
+        },
+    };
+    let mut tracer = Tracer::new(config);
+    let mut samples = Samples::new();
+    tracer.trace_value(&mut samples, &value).unwrap();
+    assert!(samples.value("DebugBlob").is_some());
+    let (_, report) = tracer.trace_type_once::<Report>(&samples).unwrap();
+    assert_eq!(report.debug, value.debug);
+    let registry = tracer.registry().unwrap();
+    assert_eq!(registry.len(), 2);
+    assert_eq!(registry["DebugBlob"], placeholder);
+}
+
+#[test]
+fn test_remote_names() {
+    mod remote {
+        pub struct Duration {
+            pub secs: u64,
+            pub nanos: u32,
+        }
+    }
+
+    mod local {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        pub struct Duration {
+            pub secs: u64,
+            pub nanos: u32,
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "remote::Duration")]
+    struct DurationDef {
+        secs: u64,
+        nanos: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Timeout {
+        #[serde(with = "DurationDef")]
+        after: remote::Duration,
+        retry: Option<local::Duration>,
+    }
+
+    let duration = ContainerFormat::Struct(vec![
+        Named {
+            name: "secs".into(),
+            value: Format::U64,
+        },
+        Named {
+            name: "nanos".into(),
+            value: Format::U32,
+        },
+    ]);
+
+    // Without renaming, the proxy is recorded next to the real type.
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Timeout>().unwrap();
+    let registry = tracer.registry().unwrap();
+    assert_eq!(registry["DurationDef"], duration);
+    assert_eq!(registry["Duration"], duration);
+
+    for config in [
+        TracerConfig::default().remote_name("DurationDef", "Duration"),
+        TracerConfig::default().remote_suffix("Def"),
+    ] {
+        let mut tracer = Tracer::new(config);
+        tracer.trace_simple_type::<Timeout>().unwrap();
+        let registry = tracer.registry().unwrap();
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry["Duration"], duration);
+        assert_eq!(
+            registry["Timeout"],
+            ContainerFormat::Struct(vec![
+                Named {
+                    name: "after".into(),
+                    value: Format::TypeName("Duration".into()),
+                },
+                Named {
+                    name: "retry".into(),
+                    value: Format::Option(Box::new(Format::TypeName("Duration".into()))),
+                },
+            ])
+        );
+    }
+
+    // Proxies must agree with the canonical containers.
+    let mut tracer = Tracer::new(TracerConfig::default().remote_name("DurationDef", "Timeout"));
+    tracer.trace_simple_type::<Timeout>().unwrap();
+    assert!(matches!(tracer.registry(), Err(Error::Incompatible(_, _))));
+}
+
+#[test]
+fn test_forbidden_types() {
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Settings {
+        name: String,
+        cache: Option<std::path::PathBuf>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    enum Request {
+        Load(Settings),
+    }
+
+    let expected = Error::ForbiddenType {
+        type_name: "std::path::PathBuf".into(),
+        container: Some("Settings".into()),
+        field_path: vec!["cache".into()],
+    };
+    let config = TracerConfig::default().forbid_type("std::path::PathBuf");
+    let mut tracer = Tracer::new(config.clone());
+    assert_eq!(tracer.trace_simple_type::<Request>().unwrap_err(), expected);
+    assert_eq!(
+        expected.to_string(),
+        "Forbidden type std::path::PathBuf traced at Settings::cache"
+    );
+
+    let mut tracer = Tracer::new(config);
+    let value = Request::Load(Settings {
+        name: "default".into(),
+        cache: None,
+    });
+    let mut samples = Samples::new();
+    assert_eq!(
+        tracer.trace_value(&mut samples, &value).unwrap_err(),
+        expected
+    );
+    assert!(!tracer.is_poisoned());
+
+    // A name without a path matches any path, including at the root.
+    let mut tracer = Tracer::new(TracerConfig::default().forbid_type("PathBuf"));
+    assert!(matches!(
+        tracer.trace_simple_type::<Vec<std::path::PathBuf>>(),
+        Err(Error::ForbiddenType {
+            container: None,
+            ..
+        })
+    ));
+    // Paths must match whole segments.
+    let mut tracer = Tracer::new(TracerConfig::default().forbid_type("Buf"));
+    tracer.trace_simple_type::<Request>().unwrap();
+}
+
+#[test]
+fn test_forbidden_formats() {
+    #[derive(Serialize, Deserialize)]
+    struct Quote {
+        symbol: String,
+        price: Option<f64>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Message {
+        Ping,
+        Quote(Quote),
+    }
+
+    let config = TracerConfig::default().forbid_format(Format::F64);
+    let mut tracer = Tracer::new(config);
+    tracer.trace_simple_type::<Message>().unwrap();
+    let error = tracer.registry().unwrap_err();
+    assert_eq!(
+        error,
+        Error::ForbiddenFormat {
+            format: Format::F64,
+            container: "Quote".into(),
+            field_path: vec!["price".into()],
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "Forbidden format F64 used at Quote::price"
+    );
+
+    let config = TracerConfig::default().forbid_format(Format::TypeName("Quote".into()));
+    let (registry, errors) =
+        Tracer::new(config).trace_all([TraceRoot::of::<Message>()], &Samples::new());
+    assert_eq!(registry.len(), 2);
+    assert_eq!(
+        errors,
+        vec![Error::ForbiddenFormat {
+            format: Format::TypeName("Quote".into()),
+            container: "Message".into(),
+            field_path: vec!["Quote".into()],
+        }]
+    );
+}
+
+#[test]
+fn test_trace_checkpoint() {
+    #[derive(Serialize, Deserialize, Debug)]
+    enum Inner {
+        A(u8),
+        B(String),
+        C,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    enum Outer {
+        X(Inner),
+        Y(Vec<Inner>),
+        Z { flag: bool },
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let (_, values) = tracer.trace_simple_type::<Inner>().unwrap();
+    assert_eq!(values.len(), 3);
+    tracer.trace_simple_type::<Outer>().unwrap();
+    let expected = tracer.registry().unwrap();
+
+    // A first run is interrupted while exploring `Outer`.
+    let config = TracerConfig::default().max_passes_per_trace(2);
+    let mut tracer = Tracer::new(config.clone());
+    tracer.trace_simple_type::<Inner>().unwrap();
+    while tracer.is_incomplete_enum("Inner") {
+        tracer.trace_simple_type::<Inner>().unwrap();
+    }
+    tracer.trace_simple_type::<Outer>().unwrap();
+    assert!(tracer.is_incomplete_enum("Outer"));
+    let checkpoint = tracer.checkpoint();
+    assert_eq!(checkpoint.names().collect::<Vec<_>>(), vec!["Inner"]);
+    assert_eq!(
+        checkpoint.complete_enums().collect::<Vec<_>>(),
+        vec!["Inner"]
+    );
+    let saved = serde_json::to_string(&checkpoint).unwrap();
+
+    // The next run does not explore `Inner` again: only the value of its first variant is
+    // returned.
+    let mut tracer = Tracer::new(config);
+    tracer
+        .restore(serde_json::from_str::<TraceCheckpoint>(&saved).unwrap())
+        .unwrap();
+    let (_, values) = tracer.trace_simple_type::<Inner>().unwrap();
+    assert!(matches!(values[..], [Inner::A(_)]));
+    assert_eq!(tracer.estimated_remaining_passes("Inner"), Some(0));
+    // `Outer` is explored again from its first variant.
+    assert!(!tracer.is_incomplete_enum("Outer"));
+    let mut outer_values = Vec::new();
+    loop {
+        let (_, values) = tracer.trace_simple_type::<Outer>().unwrap();
+        outer_values.extend(values);
+        if !tracer.is_incomplete_enum("Outer") {
+            break;
+        }
+    }
+    assert!(matches!(
+        outer_values[..],
+        [Outer::X(_), Outer::Y(_), Outer::Z { .. }]
+    ));
+    assert_eq!(tracer.registry().unwrap(), expected);
+
+    // Checkpoints of other versions are rejected.
+    let mut value: serde_json::Value = serde_json::from_str(&saved).unwrap();
+    value["version"] = 0.into();
+    let checkpoint = serde_json::from_value(value).unwrap();
+    assert!(matches!(
+        Tracer::new(TracerConfig::default()).restore(checkpoint),
+        Err(Error::InvalidCheckpoint(_))
+    ));
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_spans() {
+    use std::sync::{Arc, Mutex};
+    use tracing::{
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+
+    /// Records the names of the spans entered and the number of events.
+    #[derive(Default, Clone)]
+    struct Recorder {
+        spans: Arc<Mutex<Vec<&'static str>>>,
+        entered: Arc<Mutex<Vec<&'static str>>>,
+        events: Arc<Mutex<usize>>,
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span.metadata().name());
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {
+            *self.events.lock().unwrap() += 1;
+        }
+
+        fn enter(&self, span: &Id) {
+            let name = self.spans.lock().unwrap()[span.into_u64() as usize - 1];
+            self.entered.lock().unwrap().push(name);
+        }
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Choice {
+        A(u16),
+        B { flag: bool },
+    }
+
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut tracer = Tracer::new(TracerConfig::default());
+        tracer.trace_simple_type::<Choice>().unwrap();
+    });
+    let entered = recorder.entered.lock().unwrap();
+    assert_eq!(entered.first(), Some(&"trace_type"));
+    assert!(entered.contains(&"trace_pass"));
+    assert!(entered.contains(&"container"));
+    // At least one event per variant.
+    assert!(*recorder.events.lock().unwrap() >= 2);
+}
@@ -0,0 +1,62 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Integration tests for the snapshot module
+#![cfg(feature = "snapshot")]
+
+use serde::{Deserialize, Serialize};
+use serde_reflection::{assert_registry_snapshot, Samples, TracerConfig};
+
+#[derive(Serialize, Deserialize)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Shape {
+    Circle { center: Point, radius: u32 },
+    Polygon(Vec<Point>),
+}
+
+#[test]
+fn test_registry_snapshot() {
+    assert_registry_snapshot!(
+        |tracer| {
+            tracer.trace_simple_type::<Shape>()?;
+            Ok(())
+        },
+        "tests/snapshots/shape.yaml"
+    );
+}
+
+#[test]
+fn test_registry_snapshot_with_config() {
+    let samples = Samples::new();
+    assert_registry_snapshot!(
+        |tracer| {
+            tracer.trace_type::<Shape>(&samples)?;
+            Ok(())
+        },
+        "tests/snapshots/shape.yaml",
+        TracerConfig::default().is_human_readable(false),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Shape::Polygon: variant removed from index 1")]
+fn test_registry_snapshot_mismatch() {
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Shape")]
+    enum Shape2 {
+        Circle { center: Point, radius: u32 },
+    }
+
+    assert_registry_snapshot!(
+        |tracer| {
+            tracer.trace_simple_type::<Shape2>()?;
+            Ok(())
+        },
+        "tests/snapshots/shape.yaml"
+    );
+}
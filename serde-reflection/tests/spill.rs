@@ -0,0 +1,151 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Integration tests for the spilling of registries
+#![cfg(feature = "spill")]
+
+use serde::{Deserialize, Serialize};
+use serde_reflection::{
+    ContainerFormat, Error, Format, Samples, SpilledRegistry, TraceRoot, Tracer, TracerConfig,
+};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize)]
+struct Length(u32);
+
+#[derive(Serialize, Deserialize)]
+struct Point {
+    x: Length,
+    y: Length,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Shape {
+    Circle(Point, Length),
+    Square { corner: Point, side: Length },
+    Empty,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Drawing {
+    shapes: Vec<Shape>,
+    title: Option<String>,
+}
+
+fn spill_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "serde-reflection-spill-{}-{name}",
+        std::process::id()
+    ))
+}
+
+fn trace(config: TracerConfig) -> Tracer {
+    let mut tracer = Tracer::new(config);
+    let samples = Samples::new();
+    tracer.trace_type::<Point>(&samples).unwrap();
+    tracer.trace_type::<Shape>(&samples).unwrap();
+    tracer.trace_type::<Drawing>(&samples).unwrap();
+    tracer
+}
+
+#[test]
+fn test_spilled_containers_are_read_back() {
+    let path = spill_path("registry");
+    let expected = trace(TracerConfig::default()).registry().unwrap();
+    let registry = trace(TracerConfig::default().spill_registry(&path, 1))
+        .registry()
+        .unwrap();
+    assert_eq!(registry, expected);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_spilled_registry() {
+    let path = spill_path("spilled");
+    let expected = trace(TracerConfig::default()).registry().unwrap();
+    let spilled = trace(TracerConfig::default().spill_registry(&path, 1))
+        .spilled_registry()
+        .unwrap();
+    assert_eq!(spilled.path(), path);
+    assert_eq!(spilled.len(), expected.len());
+    assert!(spilled.contains_key("Shape"));
+    assert!(!spilled.contains_key("Circle"));
+    assert!(spilled.keys().eq(expected.keys().map(String::as_str)));
+    assert_eq!(
+        spilled.get("Point").unwrap().as_ref(),
+        expected.get("Point")
+    );
+    assert_eq!(spilled.get("Circle").unwrap(), None);
+    assert_eq!(spilled.to_registry().unwrap(), expected);
+
+    // The file can be opened again, e.g. by a later step of a code-generation pipeline.
+    let mut reopened = SpilledRegistry::open(&path).unwrap();
+    assert_eq!(reopened.to_registry().unwrap(), expected);
+    // Records of the same container are unified.
+    reopened
+        .insert(
+            "Length".to_string(),
+            ContainerFormat::NewTypeStruct(Box::new(Format::U32)),
+        )
+        .unwrap();
+    assert_eq!(reopened.len(), expected.len());
+    assert_eq!(
+        reopened.get("Length").unwrap().as_ref(),
+        expected.get("Length")
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_spilling_keeps_incomplete_containers() {
+    let path = spill_path("incomplete");
+    let mut tracer = Tracer::new(
+        TracerConfig::default()
+            .spill_registry(&path, 0)
+            .max_passes_per_trace(1),
+    );
+    let samples = Samples::new();
+    tracer.trace_type::<Shape>(&samples).unwrap();
+    // The enum is still being explored, so it is kept in memory.
+    assert!(tracer.is_incomplete_enum("Shape"));
+    assert!(!SpilledRegistry::open(&path).unwrap().contains_key("Shape"));
+    assert!(matches!(
+        tracer.spilled_registry(),
+        Err(Error::MissingVariants(names)) if names == ["Shape"]
+    ));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_spilled_registry_rejects_malformed_files() {
+    let path = spill_path("malformed");
+    std::fs::write(
+        &path,
+        b"[\"Length\", {\"NEWTYPESTRUCT\": \"U32\"}]\nnot json\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        SpilledRegistry::open(&path),
+        Err(Error::InvalidRegistryFile(_))
+    ));
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        SpilledRegistry::open(&path),
+        Err(Error::Custom(_))
+    ));
+}
+
+#[test]
+fn test_spilled_containers_of_failed_roots_are_rolled_back() {
+    let path = spill_path("rollback");
+    let failing = TraceRoot::new("failing", |tracer, samples| {
+        tracer.trace_type::<Shape>(samples)?;
+        Err(Error::Custom("failure".into()))
+    });
+    let tracer = Tracer::new(TracerConfig::default().spill_registry(&path, 1));
+    let (registry, errors) =
+        tracer.trace_all([failing, TraceRoot::of::<Length>()], &Samples::new());
+    assert_eq!(errors.len(), 1);
+    assert_eq!(registry.keys().collect::<Vec<_>>(), vec!["Length"]);
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,91 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Integration tests for the test_fixtures module
+#![cfg(feature = "test_fixtures")]
+
+use serde_reflection::{
+    test_fixtures::{encoding_fixtures, fixtures, CompactEncoding},
+    Error,
+};
+
+#[test]
+fn test_fixtures_match_expected_registries() {
+    let fixtures = fixtures();
+    let names: Vec<_> = fixtures.iter().map(|fixture| fixture.name).collect();
+    let mut sorted = names.clone();
+    sorted.sort_unstable();
+    assert_eq!(names, sorted);
+
+    for fixture in fixtures {
+        assert_eq!(
+            fixture.registry().unwrap(),
+            fixture.expected,
+            "{}",
+            fixture.name
+        );
+    }
+}
+
+#[test]
+fn test_encoding_fixtures() {
+    let fixtures = encoding_fixtures();
+    let names: Vec<_> = fixtures.iter().map(|fixture| fixture.name).collect();
+    let mut sorted = names.clone();
+    sorted.sort_unstable();
+    assert_eq!(names, sorted);
+
+    for fixture in &fixtures {
+        fixture.check().unwrap();
+    }
+
+    // Truncated or altered bytes are detected.
+    let mut fixture = encoding_fixtures().swap_remove(0);
+    fixture.postcard.pop();
+    assert!(matches!(fixture.check(), Err(Error::RoundTripMismatch(_))));
+    let mut fixture = encoding_fixtures().swap_remove(0);
+    fixture.bincode_v2.push(0);
+    assert!(matches!(fixture.check(), Err(Error::RoundTripMismatch(_))));
+}
+
+#[test]
+fn test_compact_encodings_of_integers() {
+    use serde_reflection::{Format, Registry, Value};
+
+    let registry = Registry::new();
+    let cases = [
+        (Value::U64(250), vec![250], vec![0xFA, 0x01]),
+        (Value::U64(251), vec![251, 251, 0], vec![0xFB, 0x01]),
+        (
+            Value::U64(1 << 16),
+            vec![252, 0, 0, 1, 0],
+            vec![0x80, 0x80, 0x04],
+        ),
+        (
+            Value::U64(1 << 32),
+            vec![253, 0, 0, 0, 0, 1, 0, 0, 0],
+            vec![0x80, 0x80, 0x80, 0x80, 0x10],
+        ),
+    ];
+    for (value, bincode, postcard) in cases {
+        for (encoding, bytes) in [
+            (CompactEncoding::BincodeV2, bincode),
+            (CompactEncoding::Postcard, postcard),
+        ] {
+            assert_eq!(
+                encoding.encode(&registry, &Format::U64, &value).unwrap(),
+                bytes
+            );
+            assert_eq!(
+                encoding.decode(&registry, &Format::U64, &bytes).unwrap(),
+                value
+            );
+        }
+    }
+    assert_eq!(
+        CompactEncoding::Postcard
+            .encode(&registry, &Format::I32, &Value::I32(i32::MIN))
+            .unwrap(),
+        vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]
+    );
+}
@@ -0,0 +1,144 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![cfg(all(feature = "json", feature = "trace"))]
+
+use serde::{Deserialize, Serialize};
+use serde_reflection::{
+    compat::EncodingProfile,
+    json_converter::{EmptyEnvironment, SerializationContext},
+    testvectors::{generate, TestVector},
+    Error, Format, Registry, Tracer, TracerConfig,
+};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Tree {
+    Leaf(i16),
+    Node(Box<Tree>, Box<Tree>),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Record {
+    id: u128,
+    delta: i64,
+    name: String,
+    tags: BTreeMap<String, Option<bool>>,
+    hash: [u8; 4],
+    tree: Tree,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Measure(f64);
+
+fn registry() -> Registry {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Record>().unwrap();
+    tracer.trace_simple_type::<Tree>().unwrap();
+    tracer.trace_simple_type::<Measure>().unwrap();
+    tracer.registry().unwrap()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[test]
+fn test_vectors_match_encoders() {
+    let registry = registry();
+    let format = Format::TypeName("Record".into());
+    for profile in [EncodingProfile::Bcs, EncodingProfile::Bincode1] {
+        let vectors = generate(&registry, "Record", profile, 7, 20).unwrap();
+        assert_eq!(vectors.len(), 20);
+        assert_eq!(
+            vectors[3].description,
+            format!("Record #3 ({profile}, seed 7)")
+        );
+        for vector in &vectors {
+            // 128-bit integers are strings in JSON, so values are converted dynamically.
+            let context = SerializationContext {
+                value: &vector.value,
+                format: &format,
+                registry: &registry,
+                environment: &EmptyEnvironment,
+            };
+            let bytes = match profile {
+                EncodingProfile::Bcs => {
+                    let bytes = bcs::to_bytes(&context).unwrap();
+                    bcs::from_bytes::<Record>(&bytes).unwrap();
+                    bytes
+                }
+                _ => {
+                    let bytes = bincode::serialize(&context).unwrap();
+                    bincode::deserialize::<Record>(&bytes).unwrap();
+                    bytes
+                }
+            };
+            assert_eq!(vector.bytes, hex(&bytes), "{}", vector.description);
+        }
+    }
+}
+
+#[test]
+fn test_vectors_are_reproducible() {
+    let registry = registry();
+    let vectors = generate(&registry, "Record", EncodingProfile::Postcard, 1, 5).unwrap();
+    assert_eq!(
+        vectors,
+        generate(&registry, "Record", EncodingProfile::Postcard, 1, 5).unwrap()
+    );
+    assert_ne!(
+        vectors,
+        generate(&registry, "Record", EncodingProfile::Postcard, 2, 5).unwrap()
+    );
+
+    let json = serde_json::to_string(&vectors).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Vec<TestVector>>(&json).unwrap(),
+        vectors
+    );
+}
+
+#[test]
+fn test_vectors_unsupported_formats() {
+    let registry = registry();
+    assert!(matches!(
+        generate(&registry, "Measure", EncodingProfile::Bcs, 0, 1),
+        Err(Error::NotSupported(_))
+    ));
+    assert!(generate(&registry, "Measure", EncodingProfile::Bincode1, 0, 1).is_ok());
+    assert!(matches!(
+        generate(&registry, "Unknown", EncodingProfile::Bcs, 0, 1),
+        Err(Error::MissingContainer(name)) if name == "Unknown"
+    ));
+}
+
+#[test]
+fn test_vectors_avoid_uninhabited_containers() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Void {}
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Empty {}
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Slot {
+        Never(Void),
+        Unit,
+        Empty(Empty),
+        Maybe(Option<Void>),
+        List(Vec<Void>),
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Slot>().unwrap();
+    let registry = tracer.registry().unwrap();
+    for vector in generate(&registry, "Slot", EncodingProfile::Bcs, 3, 20).unwrap() {
+        let slot: Slot = serde_json::from_value(vector.value.clone()).unwrap();
+        assert_eq!(vector.bytes, hex(&bcs::to_bytes(&slot).unwrap()));
+    }
+    assert_eq!(
+        generate(&registry, "Void", EncodingProfile::Bcs, 3, 1),
+        Err(Error::Uninhabited("Void".into()))
+    );
+}
@@ -0,0 +1,154 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![cfg(feature = "trace")]
+
+use serde::{Deserialize, Serialize};
+use serde_reflection::{Error, Samples, Tracer, TracerConfig, Value};
+
+fn sample_values() -> Vec<Value> {
+    vec![
+        Value::Unit,
+        Value::Bool(true),
+        Value::I8(-128),
+        Value::I16(-300),
+        Value::I32(i32::MIN),
+        Value::I64(i64::MAX),
+        Value::I128(i128::MIN),
+        Value::U8(255),
+        Value::U16(300),
+        Value::U32(u32::MAX),
+        Value::U64(0),
+        Value::U128(u128::MAX),
+        Value::F32(-1.5),
+        Value::F64(f64::INFINITY),
+        Value::Char('é'),
+        Value::Str("hello".into()),
+        Value::Bytes(vec![0, 1, 255]),
+        Value::Option(None),
+        Value::Option(Some(Box::new(Value::Unit))),
+        Value::Variant(70000, Box::new(Value::Seq(vec![Value::U8(1)]))),
+        Value::Seq(vec![]),
+        Value::Map(vec![
+            (Value::Str("a".into()), Value::Bool(false)),
+            (Value::Str("b".into()), Value::Option(None)),
+        ]),
+    ]
+}
+
+#[test]
+fn test_compact_bytes_round_trip() {
+    let value = Value::Seq(sample_values());
+    let bytes = value.to_compact_bytes();
+    assert_eq!(&bytes[..4], b"SRV\x01");
+    assert_eq!(Value::from_compact_bytes(&bytes).unwrap(), value);
+
+    // The layout is stable.
+    assert_eq!(Value::I16(-300).to_compact_bytes(), b"SRV\x01\x04\xd7\x04");
+    assert_eq!(Value::U128(u128::MAX).to_compact_bytes().len(), 4 + 1 + 19);
+    assert_eq!(
+        Value::Option(Some(Box::new(Value::Bool(true)))).to_compact_bytes(),
+        b"SRV\x01\x13\x02"
+    );
+}
+
+#[test]
+fn test_compact_bytes_rejects_invalid_inputs() {
+    let invalid = |bytes: &[u8]| {
+        assert!(
+            matches!(
+                Value::from_compact_bytes(bytes),
+                Err(Error::InvalidSamples(_))
+            ),
+            "{bytes:?}"
+        )
+    };
+    // Header and version.
+    invalid(b"");
+    invalid(b"SRS\x01\x00");
+    invalid(b"SRV\x02\x00");
+    // Unknown tag, trailing bytes.
+    invalid(b"SRV\x01\x17");
+    invalid(b"SRV\x01\x00\x00");
+    // Non-canonical and overflowing varints.
+    invalid(b"SRV\x01\x09\x81\x00");
+    invalid(b"SRV\x01\x09\xff\xff\x04");
+    // Lengths beyond the input, invalid strings and chars.
+    invalid(b"SRV\x01\x15\xff\xff\xff\xff\x0f");
+    invalid(b"SRV\x01\x10\x01\xff");
+    invalid(b"SRV\x01\x0f\x80\xb0\x03");
+    // Deeply nested values.
+    let mut bytes = b"SRV\x01".to_vec();
+    bytes.extend(std::iter::repeat(0x13).take(100_000));
+    bytes.push(0);
+    invalid(&bytes);
+}
+
+#[test]
+fn test_compact_bytes_fuzzing() {
+    // A deterministic generator, so that failures are reproducible.
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let encodings: Vec<_> = sample_values()
+        .iter()
+        .map(Value::to_compact_bytes)
+        .chain(std::iter::once(
+            Value::Seq(sample_values()).to_compact_bytes(),
+        ))
+        .collect();
+    for bytes in &encodings {
+        // Truncated inputs are rejected.
+        for len in 0..bytes.len() {
+            assert!(Value::from_compact_bytes(&bytes[..len]).is_err());
+        }
+        // Corrupted inputs never panic, and decode to values with the same encoding.
+        for _ in 0..200 {
+            let mut bytes = bytes.clone();
+            let position = next() as usize % bytes.len();
+            bytes[position] = next() as u8;
+            if let Ok(value) = Value::from_compact_bytes(&bytes) {
+                assert_eq!(value.to_compact_bytes(), bytes);
+            }
+        }
+    }
+    // So do random inputs after a valid header.
+    for _ in 0..2000 {
+        let mut bytes = b"SRV\x01".to_vec();
+        bytes.extend((0..next() % 32).map(|_| next() as u8));
+        if let Ok(value) = Value::from_compact_bytes(&bytes) {
+            assert_eq!(value.to_compact_bytes(), bytes);
+        }
+    }
+}
+
+#[test]
+fn test_compact_samples() {
+    #[derive(Serialize, Deserialize)]
+    struct Name(String);
+
+    #[derive(Serialize, Deserialize)]
+    struct Id(u64);
+
+    let mut tracer = Tracer::new(TracerConfig::default().both_encodings(true));
+    let mut samples = Samples::new();
+    tracer
+        .trace_value(&mut samples, &Name("alice".into()))
+        .unwrap();
+    tracer.trace_value(&mut samples, &Id(7)).unwrap();
+
+    let bytes = samples.to_compact_bytes();
+    assert_eq!(&bytes[..4], b"SRS\x01");
+    let loaded = Samples::from_compact_bytes(&bytes).unwrap();
+    assert_eq!(loaded, samples);
+    assert_eq!(loaded.value("Id"), Some(&Value::U64(7)));
+
+    assert!(matches!(
+        Samples::from_compact_bytes(&Value::U64(7).to_compact_bytes()),
+        Err(Error::InvalidSamples(_))
+    ));
+}
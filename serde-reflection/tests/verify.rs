@@ -0,0 +1,107 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![cfg(all(feature = "json", feature = "trace"))]
+
+use bincode::Options;
+use serde::{de::DeserializeSeed, Deserialize, Serialize, Serializer};
+use serde_reflection::{
+    verify::{self, Encoding},
+    Error, Samples, Tracer, TracerConfig,
+};
+
+struct Bincode;
+
+impl Encoding for Bincode {
+    fn encode<T: ?Sized + Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        bincode::DefaultOptions::new()
+            .serialize(value)
+            .map_err(|e| e.to_string())
+    }
+
+    fn decode<'de, S: DeserializeSeed<'de>>(
+        &self,
+        bytes: &'de [u8],
+        seed: S,
+    ) -> Result<S::Value, String> {
+        bincode::DefaultOptions::new()
+            .deserialize_seed(seed, bytes)
+            .map_err(|e| e.to_string())
+    }
+}
+
+struct Bcs;
+
+impl Encoding for Bcs {
+    fn encode<T: ?Sized + Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        bcs::to_bytes(value).map_err(|e| e.to_string())
+    }
+
+    fn decode<'de, S: DeserializeSeed<'de>>(
+        &self,
+        bytes: &'de [u8],
+        seed: S,
+    ) -> Result<S::Value, String> {
+        bcs::from_bytes_seed(seed, bytes).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Message {
+    Ping,
+    Data(Vec<u8>, Option<String>),
+    Move {
+        x: i16,
+        y: i16,
+        tags: Vec<(u32, bool)>,
+    },
+}
+
+// Serialization writes a `u32` where deserialization expects a `u16`.
+#[derive(Deserialize)]
+struct Divergent {
+    id: u16,
+}
+
+impl Serialize for Divergent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Divergent", 1)?;
+        state.serialize_field("id", &u32::from(self.id))?;
+        state.end()
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Message>().unwrap();
+    let registry = tracer.registry().unwrap();
+    let samples = Samples::new();
+    assert_eq!(
+        verify::round_trip::<Message, _>(&Bincode, &registry, &samples).unwrap(),
+        3
+    );
+    assert_eq!(
+        verify::round_trip::<Message, _>(&Bcs, &registry, &samples).unwrap(),
+        3
+    );
+}
+
+#[test]
+fn test_round_trip_mismatch() {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Divergent>().unwrap();
+    let registry = tracer.registry().unwrap();
+    let samples = Samples::new();
+    assert!(matches!(
+        verify::round_trip::<Divergent, _>(&Bcs, &registry, &samples),
+        Err(Error::RoundTripMismatch(msg)) if msg.contains("Divergent")
+    ));
+    // Bincode uses variable-length integers by default, so small values do not reveal
+    // the divergence.
+    assert_eq!(
+        verify::round_trip::<Divergent, _>(&Bincode, &registry, &samples).unwrap(),
+        1
+    );
+}
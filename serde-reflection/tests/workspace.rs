@@ -0,0 +1,169 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+#![cfg(all(feature = "json", feature = "trace"))]
+
+use serde::{Deserialize, Serialize};
+use serde_reflection::{
+    export::Exporters,
+    workspace::{Workspace, WorkspaceIssue},
+    ContainerFormat, Format, Registry, Tracer, TracerConfig,
+};
+
+mod common {
+    use super::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Id(pub u64);
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Envelope {
+        pub id: Id,
+        pub payload: Vec<u8>,
+    }
+}
+
+mod billing {
+    use super::{common::Envelope, Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Invoice {
+        pub envelope: Envelope,
+        pub amount: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Status(pub bool);
+}
+
+mod shipping {
+    use super::{common::Envelope, Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Parcel {
+        pub envelope: Envelope,
+        pub weight: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Status(pub u8);
+}
+
+fn trace<T>() -> Registry
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<T>().unwrap();
+    tracer.registry().unwrap()
+}
+
+#[test]
+fn test_workspace() {
+    let mut billing = trace::<billing::Invoice>();
+    billing.extend(trace::<billing::Status>());
+    let mut shipping = trace::<shipping::Parcel>();
+    shipping.extend(trace::<shipping::Status>());
+
+    let mut workspace = Workspace::new();
+    workspace.insert("billing", billing);
+    workspace.insert("shipping", shipping);
+    assert_eq!(workspace.iter().count(), 2);
+
+    // `Status` is defined differently by the two members.
+    assert_eq!(
+        workspace.check(),
+        vec![WorkspaceIssue::ConflictingDefinitions {
+            name: "Status".into(),
+            registries: vec!["billing".into(), "shipping".into()],
+            in_common: false,
+        }]
+    );
+    assert_eq!(workspace.hoist_common(), vec!["Envelope", "Id"]);
+    assert_eq!(
+        workspace.common().keys().collect::<Vec<_>>(),
+        vec!["Envelope", "Id"]
+    );
+    assert!(!workspace.get("billing").unwrap().contains_key("Id"));
+    assert_eq!(workspace.hoist_common(), Vec::<String>::new());
+
+    // Once the conflict is solved, the combined registry has every container.
+    let status = workspace.get("billing").unwrap()["Status"].clone();
+    let mut shipping = workspace.remove("shipping").unwrap();
+    shipping.insert("Status".into(), status);
+    workspace.insert("shipping", shipping);
+    assert_eq!(workspace.check(), vec![]);
+    let combined = workspace.combined();
+    assert_eq!(
+        combined.keys().collect::<Vec<_>>(),
+        vec!["Envelope", "Id", "Invoice", "Parcel", "Status"]
+    );
+    let mut out = Vec::new();
+    workspace
+        .export(&Exporters::default(), "json", &mut out)
+        .unwrap();
+    assert_eq!(serde_json::from_slice::<Registry>(&out).unwrap(), combined);
+
+    // Workspaces are saved as a single manifest.
+    let manifest = serde_yaml::to_string(&workspace).unwrap();
+    assert_eq!(
+        serde_yaml::from_str::<Workspace>(&manifest).unwrap(),
+        workspace
+    );
+}
+
+#[test]
+fn test_workspace_references() {
+    let mut workspace = Workspace::new();
+    let mut registry = Registry::new();
+    registry.insert(
+        "A".into(),
+        ContainerFormat::TupleStruct(vec![
+            Format::TypeName("B".into()),
+            Format::TypeName("Missing".into()),
+            Format::TypeName("Shared".into()),
+        ]),
+    );
+    workspace.insert("first", registry);
+    let mut registry = Registry::new();
+    registry.insert("B".into(), ContainerFormat::UnitStruct);
+    registry.insert("Shared".into(), ContainerFormat::UnitStruct);
+    workspace.insert("second", registry);
+    workspace
+        .common_mut()
+        .insert("Shared".into(), ContainerFormat::UnitStruct);
+
+    let issues = workspace.check();
+    assert_eq!(
+        issues,
+        vec![
+            WorkspaceIssue::ForeignReference {
+                registry: "first".into(),
+                container: "A".into(),
+                name: "B".into(),
+                defined_in: vec!["second".into()],
+            },
+            WorkspaceIssue::DanglingReference {
+                registry: "first".into(),
+                container: "A".into(),
+                name: "Missing".into(),
+            },
+        ]
+    );
+    assert_eq!(
+        issues[0].to_string(),
+        "first: A refers to B, which is only defined in second"
+    );
+
+    // A member may repeat the definition of a common container.
+    assert_eq!(workspace.hoist_common(), vec!["Shared"]);
+    assert!(!workspace.get("second").unwrap().contains_key("Shared"));
+    workspace.common_mut().insert(
+        "B".into(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::U8)),
+    );
+    assert_eq!(
+        workspace.check()[0].to_string(),
+        "B: conflicting definitions in second and in the common registry"
+    );
+}